@@ -6,11 +6,14 @@ use std::fs;
 use std::process::Command;
 
 fn main() -> Result<(), Error> {
+    generate_iods();
+
     let url = "https://raw.githubusercontent.com/innolitics/dicom-standard/master/standard/attributes.json";
     let response = get(url)?;
 
     if response.status().is_success() {
         let dicom_tags: Value = response.json()?;
+        generate_dictionary(dicom_tags.as_array().map(Vec::as_slice).unwrap_or_default());
         let mut output = String::new();
 
         output.push_str("// AUTO-GENERATED FILE - DO NOT EDIT\n\n");
@@ -139,8 +142,7 @@ fn main() -> Result<(), Error> {
         println!("Code generation complete.");
         Ok(())
     } else {
-        eprintln!("Failed to fetch DICOM attributes JSON");
-        Ok(())
+        panic!("Failed to fetch DICOM attributes JSON: {}", response.status());
     }
 }
 
@@ -153,4 +155,191 @@ fn parse_tag(tag: &str) -> Option<(u16, u16)> {
     } else {
         None
     }
-}
\ No newline at end of file
+}
+
+// Command elements of PS3.7 and the item delimiters of PS3.5, which the
+// attributes of PS3.6 leave out
+const FIXED_ENTRIES: &[((u16, u16), &str, &str, &str)] = &[
+    ((0x0000, 0x0000), "UL", "1", "CommandGroupLength"),
+    ((0x0000, 0x0002), "UI", "1", "AffectedSOPClassUID"),
+    ((0x0000, 0x0003), "UI", "1", "RequestedSOPClassUID"),
+    ((0x0000, 0x0100), "US", "1", "CommandField"),
+    ((0x0000, 0x0110), "US", "1", "MessageID"),
+    ((0x0000, 0x0120), "US", "1", "MessageIDBeingRespondedTo"),
+    ((0x0000, 0x0600), "AE", "1", "MoveDestination"),
+    ((0x0000, 0x0700), "US", "1", "Priority"),
+    ((0x0000, 0x0800), "US", "1", "CommandDataSetType"),
+    ((0x0000, 0x0900), "US", "1", "Status"),
+    ((0x0000, 0x0901), "AT", "1-n", "OffendingElement"),
+    ((0x0000, 0x0902), "LO", "1", "ErrorComment"),
+    ((0x0000, 0x0903), "US", "1", "ErrorID"),
+    ((0x0000, 0x1000), "UI", "1", "AffectedSOPInstanceUID"),
+    ((0x0000, 0x1001), "UI", "1", "RequestedSOPInstanceUID"),
+    ((0x0000, 0x1002), "US", "1", "EventTypeID"),
+    ((0x0000, 0x1005), "AT", "1-n", "AttributeIdentifierList"),
+    ((0x0000, 0x1008), "US", "1", "ActionTypeID"),
+    ((0x0000, 0x1020), "US", "1", "NumberOfRemainingSuboperations"),
+    ((0x0000, 0x1021), "US", "1", "NumberOfCompletedSuboperations"),
+    ((0x0000, 0x1022), "US", "1", "NumberOfFailedSuboperations"),
+    ((0x0000, 0x1023), "US", "1", "NumberOfWarningSuboperations"),
+    ((0x0000, 0x1030), "AE", "1", "MoveOriginatorApplicationEntityTitle"),
+    ((0x0000, 0x1031), "US", "1", "MoveOriginatorMessageID"),
+    ((0xFFFE, 0xE000), "NONE", "1", "Item"),
+    ((0xFFFE, 0xE00D), "NONE", "1", "ItemDelimitationItem"),
+    ((0xFFFE, 0xE0DD), "NONE", "1", "SequenceDelimitationItem"),
+];
+
+// Tag, VR and keyword table for core::dictionary, written to OUT_DIR and
+// sorted by tag. Repeating groups such as (60xx,3000) are left out. The
+// readers cannot do without it, so a missing table fails the build
+fn generate_dictionary(attributes: &[Value]) {
+    if attributes.is_empty() {
+        panic!("DICOM attributes JSON holds no attributes");
+    }
+    let text = |value: &Value, key: &str| value[key].as_str().unwrap_or("").to_string();
+
+    let mut entries: Vec<((u16, u16), String, String, String)> = FIXED_ENTRIES
+        .iter()
+        .map(|(tag, vr, vm, keyword)| (*tag, vr.to_string(), vm.to_string(), keyword.to_string()))
+        .collect();
+    for attribute in attributes {
+        let keyword = text(attribute, "keyword");
+        let vr = text(attribute, "valueRepresentation");
+        if keyword.is_empty() {
+            continue;
+        }
+        if let (Some(tag), Some(vr)) = (parse_tag(&text(attribute, "tag")), implicit_vr(&vr)) {
+            entries.push((tag, vr.to_string(), text(attribute, "valueMultiplicity"), keyword));
+        }
+    }
+    entries.sort_by_key(|entry| entry.0);
+    entries.dedup_by_key(|entry| entry.0);
+
+    let mut output = String::new();
+    output.push_str("// AUTO-GENERATED FILE - DO NOT EDIT\n\n");
+    output.push_str("pub static ENTRIES: &[DictionaryEntry] = &[\n");
+    for ((group, element), vr, vm, keyword) in &entries {
+        output.push_str(&format!(
+            "    DictionaryEntry {{ tag: (0x{:04X}, 0x{:04X}), vr: {:?}, vm: {:?}, keyword: {:?} }},\n",
+            group, element, vr, vm, keyword
+        ));
+    }
+    output.push_str("];\n");
+
+    let path = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("dictionary.rs");
+    fs::write(path, output).expect("Unable to write the dictionary");
+}
+
+// Attributes with a choice of VRs, as "US or SS", are read as the one
+// implicit VR files use. Items have no VR and are not attributes
+fn implicit_vr(vr: &str) -> Option<&str> {
+    match vr {
+        "OB or OW" => Some("OW"),
+        _ => vr
+            .split(" or ")
+            .next()
+            .filter(|vr| vr.len() == 2 && vr.bytes().all(|byte| byte.is_ascii_uppercase())),
+    }
+}
+
+// Top level attributes of each module with their type, keyed by module id
+type ModuleAttributes = HashMap<String, Vec<((u16, u16), String)>>;
+
+// IOD and module tables for core::iod, written to OUT_DIR. Like the attributes
+// in main, a table that cannot be fetched fails the build
+fn generate_iods() {
+    let base = "https://raw.githubusercontent.com/innolitics/dicom-standard/master/standard";
+    let fetch = |name: &str| -> Vec<Value> {
+        let response = get(format!("{}/{}", base, name))
+            .unwrap_or_else(|error| panic!("Failed to fetch {}: {}", name, error));
+        if !response.status().is_success() {
+            panic!("Failed to fetch {}: {}", name, response.status());
+        }
+        response
+            .json::<Value>()
+            .ok()
+            .and_then(|value| value.as_array().cloned())
+            .unwrap_or_else(|| panic!("{} is not a JSON array", name))
+    };
+    let text = |value: &Value, key: &str| value[key].as_str().unwrap_or("").to_string();
+
+    let ciods = fetch("ciods.json");
+    let modules = fetch("modules.json");
+    let ciod_to_modules = fetch("ciod_to_modules.json");
+    let module_to_attributes = fetch("module_to_attributes.json");
+    let sops = fetch("sops.json");
+
+    let mut output = String::new();
+    output.push_str("// AUTO-GENERATED FILE - DO NOT EDIT\n\n");
+
+    output.push_str("pub static CIODS: &[Ciod] = &[\n");
+    for ciod in &ciods {
+        let id = text(ciod, "id");
+        output.push_str(&format!("    Ciod {{ id: {:?}, name: {:?}, modules: &[\n", id, text(ciod, "name")));
+        for usage in ciod_to_modules.iter().filter(|usage| text(usage, "ciodId") == id) {
+            let kind = match text(usage, "usage").chars().next() {
+                Some('M') => "Mandatory",
+                Some('C') => "Conditional",
+                _ => "UserOptional",
+            };
+            output.push_str(&format!(
+                "        ModuleUsage {{ module: {:?}, usage: Usage::{}, information_entity: {:?} }},\n",
+                text(usage, "moduleId"),
+                kind,
+                text(usage, "informationEntity")
+            ));
+        }
+        output.push_str("    ] },\n");
+    }
+    output.push_str("];\n\n");
+
+    // Only the attributes at the top level of each module, sequence contents
+    // have paths with more than one step
+    let mut attributes: ModuleAttributes = HashMap::new();
+    for attribute in &module_to_attributes {
+        let path = text(attribute, "path");
+        if path.split(':').count() != 2 {
+            continue;
+        }
+        if let Some(tag) = parse_tag(&text(attribute, "tag")) {
+            attributes
+                .entry(text(attribute, "moduleId"))
+                .or_default()
+                .push((tag, text(attribute, "type")));
+        }
+    }
+
+    output.push_str("pub static MODULES: &[Module] = &[\n");
+    for module in &modules {
+        let id = text(module, "id");
+        output.push_str(&format!("    Module {{ id: {:?}, name: {:?}, attributes: &[\n", id, text(module, "name")));
+        for ((group, element), kind) in attributes.get(&id).into_iter().flatten() {
+            output.push_str(&format!(
+                "        ModuleAttribute {{ tag: (0x{:04X}, 0x{:04X}), kind: {:?} }},\n",
+                group, element, kind
+            ));
+        }
+        output.push_str("    ] },\n");
+    }
+    output.push_str("];\n\n");
+
+    output.push_str("pub static SOP_CLASSES: &[SopClass] = &[\n");
+    for sop in &sops {
+        let ciod = text(sop, "ciod");
+        let ciod_id = ciods
+            .iter()
+            .find(|candidate| text(candidate, "name") == ciod)
+            .map(|candidate| text(candidate, "id"))
+            .unwrap_or_default();
+        output.push_str(&format!(
+            "    SopClass {{ uid: {:?}, name: {:?}, ciod: {:?} }},\n",
+            text(sop, "id"),
+            text(sop, "name"),
+            ciod_id
+        ));
+    }
+    output.push_str("];\n");
+
+    let path = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("iods.rs");
+    fs::write(path, output).expect("Unable to write IOD tables");
+}
@@ -0,0 +1,123 @@
+// File IO on tokio::fs, so async servers and SCPs do not block their runtime
+// threads on large files. Parsing and encoding stay synchronous; datasets
+// are not Send, so writes encode before returning a future that only holds
+// the bytes and can be spawned on any runtime thread
+
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+};
+
+use tokio::fs::{self, OpenOptions};
+
+use super::{
+    dataset::Dataset,
+    error::{DicomError, DicomResult},
+    meta::FileMeta,
+    padding::ValuePolicy,
+    reader::{self, DicomFile, ParserLimits},
+    writer,
+};
+
+pub async fn read_file<P: AsRef<Path>>(path: P) -> DicomResult<DicomFile> {
+    reader::read_file(&fs::read(path).await?)
+}
+
+// As read_file, the size checked against the limits before anything is read
+pub async fn read_file_with_limits<P: AsRef<Path>>(
+    path: P,
+    policy: &ValuePolicy,
+    limits: &ParserLimits,
+) -> DicomResult<DicomFile> {
+    let path = path.as_ref();
+    if let Some(max) = limits.max_file_size {
+        let size = fs::metadata(path).await?.len();
+        if size > max as u64 {
+            return Err(DicomError::InvalidLength(format!(
+                "{} bytes exceed the limit of {}",
+                size, max
+            )));
+        }
+    }
+
+    let data = fs::read(path).await?;
+    reader::read_file_with_limits(&data, policy, limits).map(|(file, _)| file)
+}
+
+// Bare dataset without a preamble or meta information
+pub async fn read_dataset<P: AsRef<Path>>(path: P, transfer_syntax: &str) -> DicomResult<Dataset> {
+    reader::read_dataset(&fs::read(path).await?, transfer_syntax)
+}
+
+// Part 10 file replacing the one at path, as DicomDocument writes it
+pub fn write_file<P: AsRef<Path>>(
+    path: P,
+    dataset: &Dataset,
+    transfer_syntax: &str,
+) -> impl Future<Output = DicomResult<()>> + Send + 'static {
+    let encoded = writer::write_file(dataset, transfer_syntax);
+    write_encoded(path.as_ref().to_path_buf(), encoded)
+}
+
+pub fn write_file_with_meta<P: AsRef<Path>>(
+    path: P,
+    meta: &FileMeta,
+    dataset: &Dataset,
+) -> impl Future<Output = DicomResult<()>> + Send + 'static {
+    let encoded = writer::write_file_with_meta(meta, dataset);
+    write_encoded(path.as_ref().to_path_buf(), encoded)
+}
+
+pub fn write_dataset<P: AsRef<Path>>(
+    path: P,
+    dataset: &Dataset,
+    transfer_syntax: &str,
+) -> impl Future<Output = DicomResult<()>> + Send + 'static {
+    let encoded = writer::write_dataset(dataset, transfer_syntax);
+    write_encoded(path.as_ref().to_path_buf(), encoded)
+}
+
+async fn write_encoded(path: PathBuf, encoded: DicomResult<Vec<u8>>) -> DicomResult<()> {
+    write_atomic(&path, &encoded?).await
+}
+
+// Async counterpart of document::write_atomic, without the backup
+pub async fn write_atomic(path: &Path, data: &[u8]) -> DicomResult<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| {
+            DicomError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is not a file", path.display()),
+            ))
+        })?
+        .to_string_lossy()
+        .into_owned();
+    let temporary = path.with_file_name(format!(".{}.tmp", name));
+
+    let result = async {
+        fs::write(&temporary, data).await?;
+        OpenOptions::new()
+            .write(true)
+            .open(&temporary)
+            .await?
+            .sync_all()
+            .await?;
+        fs::rename(&temporary, path).await?;
+        // The rename itself is durable once the directory is synced
+        #[cfg(unix)]
+        if let Some(directory) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::File::open(directory).await?.sync_all().await?;
+        }
+        Ok::<_, DicomError>(())
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary).await;
+    }
+    result
+}
@@ -0,0 +1,215 @@
+use super::error::{DicomError, DicomResult};
+
+// Pixel data whose samples are packed tighter than a byte or word each, as
+// 1 bit segmentations and overlays and the retired 12 bit allocated images
+// store them. Bits run from the least significant one of each byte on,
+// PS3.5 8.1.1 and D
+
+// Bytes holding `count` samples of `bits_allocated` bits
+pub fn packed_size(count: usize, bits_allocated: u16) -> usize {
+    (count * bits_allocated as usize).div_ceil(8)
+}
+
+// One byte of 0 or 1 per sample
+pub fn unpack_bits(data: &[u8], count: usize) -> Vec<u8> {
+    (0..count.min(data.len() * 8))
+        .map(|index| (data[index / 8] >> (index % 8)) & 1)
+        .collect()
+}
+
+// Non zero values set their bit, the last byte padded with zeros
+pub fn pack_bits(values: &[u8]) -> Vec<u8> {
+    let mut data = vec![0u8; values.len().div_ceil(8)];
+    for (index, value) in values.iter().enumerate() {
+        if *value != 0 {
+            data[index / 8] |= 1 << (index % 8);
+        }
+    }
+    data
+}
+
+// `bits` bits from bit `start` on, moved to the start of their own bytes.
+// Frames of 1 bit pixel data follow each other without padding, so all but
+// the first may begin within a byte
+pub fn bit_range(data: &[u8], start: usize, bits: usize) -> Vec<u8> {
+    if start.is_multiple_of(8) {
+        let mut range = data
+            .get(start / 8..)
+            .unwrap_or_default()
+            .iter()
+            .take(bits.div_ceil(8))
+            .copied()
+            .collect::<Vec<u8>>();
+        if !bits.is_multiple_of(8) {
+            if let Some(last) = range.get_mut(bits / 8) {
+                *last &= (1 << (bits % 8)) - 1;
+            }
+        }
+        return range;
+    }
+
+    let mut range = vec![0u8; bits.div_ceil(8)];
+    for index in 0..bits {
+        let bit = start + index;
+        let Some(byte) = data.get(bit / 8) else {
+            break;
+        };
+        if (byte >> (bit % 8)) & 1 == 1 {
+            range[index / 8] |= 1 << (index % 8);
+        }
+    }
+    range
+}
+
+// Two samples in every three bytes, the first in the low byte and low
+// nibble of the second
+pub fn unpack_12(data: &[u8], count: usize) -> Vec<u16> {
+    let mut samples = Vec::with_capacity(count);
+    for chunk in data.chunks(3) {
+        let byte = |index: usize| *chunk.get(index).unwrap_or(&0) as u16;
+        samples.push(byte(0) | (byte(1) & 0x0F) << 8);
+        samples.push(byte(1) >> 4 | byte(2) << 4);
+    }
+    samples.truncate(count);
+    samples
+}
+
+// Bits above the twelfth are dropped, an odd sample count leaves the high
+// nibble of the last byte zero
+pub fn pack_12(samples: &[u16]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(packed_size(samples.len(), 12));
+    for pair in samples.chunks(2) {
+        let first = pair[0] & 0x0FFF;
+        let second = pair.get(1).map_or(0, |sample| sample & 0x0FFF);
+        data.push(first as u8);
+        data.push((first >> 8) as u8 | (second << 4) as u8);
+        if pair.len() == 2 {
+            data.push((second >> 4) as u8);
+        }
+    }
+    data
+}
+
+// Native little endian samples widened to one u16 each, whatever they were
+// allocated in
+pub fn unpack(data: &[u8], count: usize, bits_allocated: u16) -> DicomResult<Vec<u16>> {
+    let needed = packed_size(count, bits_allocated);
+    if data.len() < needed {
+        return Err(DicomError::InvalidLength(format!(
+            "{} bytes cannot hold {} samples of {} bits",
+            data.len(),
+            count,
+            bits_allocated
+        )));
+    }
+    Ok(match bits_allocated {
+        1 => unpack_bits(data, count)
+            .into_iter()
+            .map(u16::from)
+            .collect(),
+        8 => data[..count].iter().map(|byte| *byte as u16).collect(),
+        12 => unpack_12(data, count),
+        16 => data[..needed]
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect(),
+        bits => return Err(unsupported(bits)),
+    })
+}
+
+pub fn pack(samples: &[u16], bits_allocated: u16) -> DicomResult<Vec<u8>> {
+    Ok(match bits_allocated {
+        1 => pack_bits(
+            &samples
+                .iter()
+                .map(|sample| (*sample != 0) as u8)
+                .collect::<Vec<u8>>(),
+        ),
+        8 => samples.iter().map(|sample| *sample as u8).collect(),
+        12 => pack_12(samples),
+        16 => samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect(),
+        bits => return Err(unsupported(bits)),
+    })
+}
+
+fn unsupported(bits_allocated: u16) -> DicomError {
+    DicomError::InvalidValue(format!(
+        "Samples of {} bits allocated cannot be unpacked",
+        bits_allocated
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Rows of a 3x5 binary segment, 15 bits over two bytes
+    const SEGMENT: [u8; 2] = [0b1010_0101, 0b0101_1010];
+    const SEGMENT_PIXELS: [u8; 15] = [1, 0, 1, 0, 0, 1, 0, 1, 0, 1, 0, 1, 1, 0, 1];
+
+    // 0x321, 0x654, 0x987 and 0xCBA packed as 12 bit allocated samples
+    const PACKED_12: [u8; 6] = [0x21, 0x43, 0x65, 0x87, 0xA9, 0xCB];
+    const SAMPLES_12: [u16; 4] = [0x321, 0x654, 0x987, 0xCBA];
+
+    #[test]
+    fn unpacks_bits_from_the_least_significant() {
+        assert_eq!(unpack_bits(&SEGMENT, 15), SEGMENT_PIXELS);
+        assert_eq!(unpack_bits(&[0b0000_0001], 8), [1, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn packs_bits_with_zero_padding() {
+        assert_eq!(pack_bits(&SEGMENT_PIXELS), [0b1010_0101, 0b0101_1010]);
+        assert_eq!(pack_bits(&[0, 0, 0, 0, 0, 0, 0, 0, 1]), [0, 1]);
+        assert_eq!(pack_bits(&[255, 0, 7]), [0b0000_0101]);
+    }
+
+    #[test]
+    fn takes_unaligned_bit_ranges() {
+        // Second frame of two 3x3 frames starts at bit 9
+        let data = pack_bits(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 0, 0, 0, 0, 1]);
+        assert_eq!(bit_range(&data, 9, 9), [0b0000_1011, 0b0000_0001]);
+        assert_eq!(bit_range(&data, 0, 9), [0, 0]);
+        assert_eq!(bit_range(&[0xFF, 0xFF], 8, 3), [0b0000_0111]);
+    }
+
+    #[test]
+    fn unpacks_12_bit_samples() {
+        assert_eq!(unpack_12(&PACKED_12, 4), SAMPLES_12);
+        assert_eq!(unpack_12(&PACKED_12[..3], 1), [0x321]);
+    }
+
+    #[test]
+    fn packs_12_bit_samples() {
+        assert_eq!(pack_12(&SAMPLES_12), PACKED_12);
+        assert_eq!(pack_12(&[0x321]), [0x21, 0x03]);
+        assert_eq!(pack_12(&[0xF321]), [0x21, 0x03]);
+    }
+
+    #[test]
+    fn round_trips_every_supported_allocation() {
+        let samples: Vec<u16> = (0..33).map(|index| index * 97).collect();
+        for (bits, mask) in [(1, 1), (8, 0xFF), (12, 0xFFF), (16, 0xFFFF)] {
+            let expected: Vec<u16> = samples
+                .iter()
+                .map(|sample| match bits {
+                    1 => (*sample != 0) as u16,
+                    _ => sample & mask,
+                })
+                .collect();
+            let packed = pack(&samples, bits).unwrap();
+            assert_eq!(packed.len(), packed_size(samples.len(), bits));
+            assert_eq!(unpack(&packed, samples.len(), bits).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn rejects_short_or_unsupported_data() {
+        assert!(unpack(&[0; 2], 2, 12).is_err());
+        assert!(unpack(&[0; 4], 2, 32).is_err());
+        assert!(pack(&[0], 10).is_err());
+    }
+}
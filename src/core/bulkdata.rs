@@ -0,0 +1,204 @@
+use std::{cell::OnceCell, fmt::Display, fs, path::PathBuf, rc::Rc};
+
+use super::{
+    error::{DicomError, DicomResult},
+    json,
+    tag::{DicomTag, VisualRepresentation},
+};
+
+// Fetches the bytes a BulkDataURI of DICOM JSON points at, PS3.18 F.2.6,
+// little endian as InlineBinary would carry them
+pub trait BulkDataResolver {
+    fn fetch(&self, uri: &str) -> DicomResult<Vec<u8>>;
+}
+
+impl<F> BulkDataResolver for F
+where
+    F: Fn(&str) -> DicomResult<Vec<u8>>,
+{
+    fn fetch(&self, uri: &str) -> DicomResult<Vec<u8>> {
+        self(uri)
+    }
+}
+
+// Scheme of a URI, lowercase, None for relative references
+pub fn scheme(uri: &str) -> Option<String> {
+    let (scheme, _) = uri.split_once(':')?;
+    let valid = scheme.len() > 1
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then(|| scheme.to_ascii_lowercase())
+}
+
+// file: URIs and plain paths, relative ones taken from the base folder
+#[derive(Debug, Clone, Default)]
+pub struct FileResolver {
+    pub base: Option<PathBuf>,
+}
+
+impl FileResolver {
+    pub fn new() -> Self {
+        FileResolver::default()
+    }
+
+    pub fn with_base(mut self, base: &std::path::Path) -> Self {
+        self.base = Some(base.to_path_buf());
+        self
+    }
+}
+
+impl BulkDataResolver for FileResolver {
+    fn fetch(&self, uri: &str) -> DicomResult<Vec<u8>> {
+        let path = uri
+            .strip_prefix("file://")
+            .or_else(|| uri.strip_prefix("file:"))
+            .unwrap_or(uri);
+        let path = match &self.base {
+            Some(base) if !path.starts_with('/') => base.join(path),
+            _ => PathBuf::from(path),
+        };
+        Ok(fs::read(path)?)
+    }
+}
+
+// Resolvers by URI scheme, e.g. http and https to a DICOMweb client, s3 to
+// an object store. Relative references go to the resolver of the empty
+// scheme
+#[derive(Clone, Default)]
+pub struct SchemeResolver {
+    resolvers: Vec<(String, Rc<dyn BulkDataResolver>)>,
+}
+
+impl SchemeResolver {
+    pub fn new() -> Self {
+        SchemeResolver::default()
+    }
+
+    pub fn with<R: BulkDataResolver + 'static>(mut self, scheme: &str, resolver: R) -> Self {
+        self.register(scheme, Rc::new(resolver));
+        self
+    }
+
+    pub fn register(&mut self, scheme: &str, resolver: Rc<dyn BulkDataResolver>) {
+        let scheme = scheme.to_ascii_lowercase();
+        self.resolvers
+            .retain(|(registered, _)| *registered != scheme);
+        self.resolvers.push((scheme, resolver));
+    }
+}
+
+impl BulkDataResolver for SchemeResolver {
+    fn fetch(&self, uri: &str) -> DicomResult<Vec<u8>> {
+        let scheme = scheme(uri).unwrap_or_default();
+        let (_, resolver) = self
+            .resolvers
+            .iter()
+            .find(|(registered, _)| *registered == scheme)
+            .ok_or_else(|| {
+                DicomError::InvalidValue(format!("No bulk data resolver for {}", uri))
+            })?;
+        resolver.fetch(uri)
+    }
+}
+
+// Element whose value stays behind its BulkDataURI until first read. The
+// bytes are fetched once and kept; a failed fetch reads as an empty value,
+// with `load` giving the error
+pub struct BulkDataElement {
+    tag: (u16, u16),
+    vr: String,
+    uri: String,
+    resolver: Rc<dyn BulkDataResolver>,
+    value: OnceCell<VisualRepresentation>,
+}
+
+impl BulkDataElement {
+    pub fn new(tag: (u16, u16), vr: &str, uri: &str, resolver: Rc<dyn BulkDataResolver>) -> Self {
+        BulkDataElement {
+            tag,
+            vr: vr.to_string(),
+            uri: uri.to_string(),
+            resolver,
+            value: OnceCell::new(),
+        }
+    }
+
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.value.get().is_some()
+    }
+
+    pub fn load(&self) -> DicomResult<&VisualRepresentation> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let bytes = self.resolver.fetch(&self.uri)?;
+        Ok(self
+            .value
+            .get_or_init(|| json::from_binary(&self.vr, &bytes)))
+    }
+}
+
+impl std::fmt::Debug for BulkDataElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BulkDataElement")
+            .field("tag", &self.tag)
+            .field("vr", &self.vr)
+            .field("uri", &self.uri)
+            .field("loaded", &self.is_loaded())
+            .finish()
+    }
+}
+
+impl DicomTag for BulkDataElement {
+    fn name(&self) -> String {
+        format!("({:04X},{:04X})", self.tag.0, self.tag.1)
+    }
+
+    fn tag(&self) -> (u16, u16) {
+        self.tag
+    }
+
+    fn vr(&self) -> VisualRepresentation {
+        self.load()
+            .cloned()
+            .unwrap_or_else(|_| VisualRepresentation::empty(&self.vr))
+    }
+
+    fn group(&self) -> u16 {
+        self.tag.0
+    }
+
+    fn element(&self) -> Option<u16> {
+        Some(self.tag.1)
+    }
+
+    fn is_deprecated(&self) -> bool {
+        false
+    }
+
+    fn multiplicity(&self) -> &str {
+        "1"
+    }
+
+    // Values not fetched yet take no memory
+    fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.uri.len()
+            + self.value.get().map_or(0, VisualRepresentation::heap_size)
+    }
+}
+
+impl Display for BulkDataElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value.get() {
+            Some(value) => writeln!(f, "{} {} {}", self.name(), self.vr, value),
+            None => writeln!(f, "{} {} <{}>", self.name(), self.vr, self.uri),
+        }
+    }
+}
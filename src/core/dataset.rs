@@ -1,9 +1,34 @@
-use std::{collections::VecDeque, fmt::Display, rc::Rc};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Display,
+    rc::Rc,
+};
 
-use super::tag::DicomTag;
+use super::{
+    dictionary,
+    element::{DicomElement, Item, LengthEncoding},
+    error::{DicomError, DicomResult},
+    tag::{DicomTag, VisualRepresentation},
+};
 
 pub type CursorPosition = usize;
 
+// Elements kept in DatasetStatistics::largest
+pub const LARGEST_ELEMENTS: usize = 10;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DatasetStatistics {
+    // Elements at any depth, sequence items not counted
+    pub elements: usize,
+    pub by_group: BTreeMap<u16, usize>,
+    pub by_vr: BTreeMap<&'static str, usize>,
+    // Top level elements by memory usage, largest first
+    pub largest: Vec<((u16, u16), usize)>,
+    // Levels of nested sequences, 0 for a flat dataset
+    pub sequence_depth: usize,
+    pub memory_usage: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct Dataset {
     objects: VecDeque<Rc<dyn DicomTag>>,
@@ -72,6 +97,188 @@ impl Dataset {
     pub fn pop_front(&mut self) -> Option<Rc<dyn DicomTag>> {
         self.objects.pop_front()
     }
+
+    pub fn position(&self, tag: (u16, u16)) -> Option<CursorPosition> {
+        self.objects.iter().position(|object| object.tag() == tag)
+    }
+
+    pub fn find(&self, tag: (u16, u16)) -> Option<&Rc<dyn DicomTag>> {
+        self.objects.iter().find(|object| object.tag() == tag)
+    }
+
+    pub fn contains(&self, tag: (u16, u16)) -> bool {
+        self.position(tag).is_some()
+    }
+
+    pub fn value(&self, tag: (u16, u16)) -> Option<VisualRepresentation> {
+        self.find(tag).map(|object| object.vr())
+    }
+
+    // String values come back without the DICOM padding
+    pub fn string(&self, tag: (u16, u16)) -> Option<String> {
+        self.value(tag)
+            .map(|value| value.to_string().trim_end_matches([' ', '\0']).to_string())
+    }
+
+    pub fn sequence(&self, tag: (u16, u16)) -> Vec<Dataset> {
+        self.value(tag)
+            .map(|value| {
+                value
+                    .items()
+                    .iter()
+                    .filter_map(|item| item.dataset().cloned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // Replaces the element with the same tag, or appends it
+    pub fn put(&mut self, dicom_object: Rc<dyn DicomTag>) {
+        match self.position(dicom_object.tag()) {
+            Some(position) => self.objects[position] = dicom_object,
+            None => self.objects.push_back(dicom_object),
+        }
+    }
+
+    pub fn put_string(&mut self, tag: (u16, u16), vr: &str, value: &str) {
+        self.put(Rc::new(DicomElement::from_string(tag, vr, value)));
+    }
+
+    // Items of a sequence with their encoding, empty when the tag is absent
+    pub fn items(&self, tag: (u16, u16)) -> Vec<Item> {
+        self.value(tag)
+            .map(|value| {
+                value
+                    .items()
+                    .iter()
+                    .filter_map(|item| Item::from_tag(item.as_ref()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn item(&self, tag: (u16, u16), index: usize) -> Option<Item> {
+        self.items(tag).into_iter().nth(index)
+    }
+
+    // Replaces the items of a sequence, keeping its own length encoding
+    pub fn set_items(&mut self, tag: (u16, u16), items: Vec<Item>) {
+        let encoding = self
+            .find(tag)
+            .map(|element| element.length_encoding())
+            .unwrap_or_default();
+        self.put(Rc::new(
+            DicomElement::sequence_of(tag, items).with_length_encoding(encoding),
+        ));
+    }
+
+    pub fn set_item(&mut self, tag: (u16, u16), index: usize, item: Item) -> DicomResult<()> {
+        let mut items = self.items(tag);
+        let count = items.len();
+        let slot = items.get_mut(index).ok_or_else(|| {
+            DicomError::InvalidValue(format!(
+                "Item {} of ({:04X},{:04X}), which has {}",
+                index, tag.0, tag.1, count
+            ))
+        })?;
+        *slot = item;
+        self.set_items(tag, items);
+        Ok(())
+    }
+
+    // Appends an item, creating the sequence when absent
+    pub fn push_item(&mut self, tag: (u16, u16), item: Item) {
+        let mut items = self.items(tag);
+        items.push(item);
+        self.set_items(tag, items);
+    }
+
+    pub fn remove_item(&mut self, tag: (u16, u16), index: usize) -> Option<Item> {
+        let mut items = self.items(tag);
+        if index >= items.len() {
+            return None;
+        }
+        let item = items.remove(index);
+        self.set_items(tag, items);
+        Some(item)
+    }
+
+    pub fn set_sequence_encoding(&mut self, tag: (u16, u16), length_encoding: LengthEncoding) {
+        let items = self.items(tag);
+        if self.contains(tag) {
+            self.put(Rc::new(
+                DicomElement::sequence_of(tag, items).with_length_encoding(length_encoding),
+            ));
+        }
+    }
+
+    // Element present with a zero length value, as Type 2 attributes are when
+    // their value is unknown. The VR comes from the dictionary
+    pub fn set_empty(&mut self, tag: (u16, u16)) {
+        self.set_empty_as(tag, dictionary::vr_of(tag));
+    }
+
+    pub fn set_empty_as(&mut self, tag: (u16, u16), vr: &str) {
+        self.put(Rc::new(DicomElement::new(
+            tag,
+            VisualRepresentation::empty(vr),
+        )));
+    }
+
+    // Present but empty, as opposed to absent
+    pub fn is_empty_value(&self, tag: (u16, u16)) -> bool {
+        self.find(tag).is_some_and(|object| object.vr().is_empty())
+    }
+
+    pub fn remove(&mut self, tag: (u16, u16)) -> Option<Rc<dyn DicomTag>> {
+        self.position(tag)
+            .and_then(|position| self.objects.remove(position))
+    }
+
+    // Bytes held in memory, each element behind an Rc with its two counters
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.objects.capacity() * std::mem::size_of::<Rc<dyn DicomTag>>()
+            + self
+                .objects
+                .iter()
+                .map(|object| 2 * std::mem::size_of::<usize>() + object.memory_usage())
+                .sum::<usize>()
+    }
+
+    pub fn statistics(&self) -> DatasetStatistics {
+        let mut statistics = DatasetStatistics {
+            memory_usage: self.memory_usage(),
+            ..DatasetStatistics::default()
+        };
+        self.count(&mut statistics, 0);
+
+        let mut largest: Vec<_> = self
+            .objects
+            .iter()
+            .map(|object| (object.tag(), object.memory_usage()))
+            .collect();
+        largest.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        largest.truncate(LARGEST_ELEMENTS);
+        statistics.largest = largest;
+        statistics
+    }
+
+    fn count(&self, statistics: &mut DatasetStatistics, depth: usize) {
+        for object in &self.objects {
+            let value = object.vr();
+            statistics.elements += 1;
+            *statistics.by_group.entry(object.group()).or_default() += 1;
+            *statistics.by_vr.entry(value.code()).or_default() += 1;
+
+            if let VisualRepresentation::SQ(items) = &value {
+                statistics.sequence_depth = statistics.sequence_depth.max(depth + 1);
+                for item in items.iter().filter_map(|item| item.dataset()) {
+                    item.count(statistics, depth + 1);
+                }
+            }
+        }
+    }
 }
 
 // Implementing Iterator for Dataset
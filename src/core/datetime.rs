@@ -0,0 +1,375 @@
+use std::{fmt, ops::Range};
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+
+use super::{
+    dataset::Dataset,
+    error::{DicomError, DicomResult},
+    tag::VisualRepresentation,
+};
+
+pub const TIMEZONE_OFFSET_FROM_UTC: (u16, u16) = (0x0008, 0x0201);
+
+// DA value at the precision it was written with. PS3.5 gives DA full dates
+// only, but DT values and older files carry YYYY and YYYYMM, which are
+// written back as read rather than as the first of the month
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DateValue {
+    Year(i32),
+    YearMonth(i32, u32),
+    Date(NaiveDate),
+}
+
+impl Default for DateValue {
+    fn default() -> Self {
+        DateValue::Date(NaiveDate::default())
+    }
+}
+
+impl DateValue {
+    pub fn parse(value: &str) -> DicomResult<Self> {
+        let invalid = || DicomError::InvalidValue(format!("{} is not a valid DA value", value));
+        let value = value.trim_end_matches(['\0', ' ']);
+        if !value.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let number = |range: Range<usize>| -> DicomResult<u32> {
+            value[range].parse().map_err(|_| invalid())
+        };
+        let date = match value.len() {
+            4 => DateValue::Year(number(0..4)? as i32),
+            6 => DateValue::YearMonth(number(0..4)? as i32, number(4..6)?),
+            8 => DateValue::Date(
+                NaiveDate::from_ymd_opt(number(0..4)? as i32, number(4..6)?, number(6..8)?)
+                    .ok_or_else(invalid)?,
+            ),
+            _ => return Err(invalid()),
+        };
+        match date.first_day() {
+            Some(_) => Ok(date),
+            None => Err(invalid()),
+        }
+    }
+
+    // Start of the year or month a reduced precision value stands for
+    pub fn first_day(&self) -> Option<NaiveDate> {
+        match *self {
+            DateValue::Year(year) => NaiveDate::from_ymd_opt(year, 1, 1),
+            DateValue::YearMonth(year, month) => NaiveDate::from_ymd_opt(year, month, 1),
+            DateValue::Date(date) => Some(date),
+        }
+    }
+
+    // Another date at the precision of this one, e.g. after a date shift
+    pub fn with_date(&self, date: NaiveDate) -> Self {
+        match self {
+            DateValue::Year(_) => DateValue::Year(date.year()),
+            DateValue::YearMonth(..) => DateValue::YearMonth(date.year(), date.month()),
+            DateValue::Date(_) => DateValue::Date(date),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        matches!(self, DateValue::Date(_))
+    }
+}
+
+impl From<NaiveDate> for DateValue {
+    fn from(date: NaiveDate) -> Self {
+        DateValue::Date(date)
+    }
+}
+
+impl fmt::Display for DateValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateValue::Year(year) => write!(f, "{:04}", year),
+            DateValue::YearMonth(year, month) => write!(f, "{:04}{:02}", year, month),
+            DateValue::Date(date) => write!(f, "{}", date.format("%Y%m%d")),
+        }
+    }
+}
+
+// TM value at the precision it was written with, HH, HHMM or HHMMSS with
+// up to 6 fraction digits, the number of which Time keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeValue {
+    Hour(u32),
+    HourMinute(u32, u32),
+    Time(NaiveTime, u8),
+}
+
+impl Default for TimeValue {
+    fn default() -> Self {
+        TimeValue::Time(NaiveTime::default(), 0)
+    }
+}
+
+impl TimeValue {
+    pub fn parse(value: &str) -> DicomResult<Self> {
+        let invalid = || DicomError::InvalidValue(format!("{} is not a valid TM value", value));
+        let value = value.trim_end_matches(['\0', ' ']);
+        let (main, fraction) = match value.split_once('.') {
+            Some((main, fraction)) if main.len() == 6 && (1..=6).contains(&fraction.len()) => {
+                (main, Some(fraction))
+            }
+            Some(_) => return Err(invalid()),
+            None => (value, None),
+        };
+        let digits = |text: &str| text.bytes().all(|byte| byte.is_ascii_digit());
+        if !digits(main) || !fraction.is_none_or(digits) {
+            return Err(invalid());
+        }
+        let number = |range: Range<usize>| -> DicomResult<u32> {
+            main[range].parse().map_err(|_| invalid())
+        };
+
+        let time = match main.len() {
+            2 => TimeValue::Hour(number(0..2)?),
+            4 => TimeValue::HourMinute(number(0..2)?, number(2..4)?),
+            6 => {
+                let (micro, digits) = match fraction {
+                    Some(fraction) => (
+                        format!("{:0<6}", fraction).parse().map_err(|_| invalid())?,
+                        fraction.len() as u8,
+                    ),
+                    None => (0, 0),
+                };
+                let time = NaiveTime::from_hms_micro_opt(
+                    number(0..2)?,
+                    number(2..4)?,
+                    number(4..6)?,
+                    micro,
+                )
+                .ok_or_else(invalid)?;
+                TimeValue::Time(time, digits)
+            }
+            _ => return Err(invalid()),
+        };
+        match time.start() {
+            Some(_) => Ok(time),
+            None => Err(invalid()),
+        }
+    }
+
+    // Start of the hour or minute a reduced precision value stands for
+    pub fn start(&self) -> Option<NaiveTime> {
+        match *self {
+            TimeValue::Hour(hour) => NaiveTime::from_hms_opt(hour, 0, 0),
+            TimeValue::HourMinute(hour, minute) => NaiveTime::from_hms_opt(hour, minute, 0),
+            TimeValue::Time(time, _) => Some(time),
+        }
+    }
+
+    // Another time at the precision of this one
+    pub fn with_time(&self, time: NaiveTime) -> Self {
+        match self {
+            TimeValue::Hour(_) => TimeValue::Hour(time.hour()),
+            TimeValue::HourMinute(..) => TimeValue::HourMinute(time.hour(), time.minute()),
+            TimeValue::Time(_, digits) => TimeValue::Time(time, *digits),
+        }
+    }
+}
+
+// Fraction digits as far as the time has any
+impl From<NaiveTime> for TimeValue {
+    fn from(time: NaiveTime) -> Self {
+        let digits = if time.nanosecond() / 1000 == 0 { 0 } else { 6 };
+        TimeValue::Time(time, digits)
+    }
+}
+
+impl fmt::Display for TimeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeValue::Hour(hour) => write!(f, "{:02}", hour),
+            TimeValue::HourMinute(hour, minute) => write!(f, "{:02}{:02}", hour, minute),
+            TimeValue::Time(time, digits) => {
+                write!(f, "{}", time.format("%H%M%S"))?;
+                if *digits > 0 {
+                    let micro = format!("{:06}", time.nanosecond() / 1000 % 1_000_000);
+                    write!(f, ".{}", &micro[..usize::from(*digits).min(6)])?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// DT value with the optional &ZZXX suffix of PS3.5 6.2, and the date and
+// time at the precision they were written with. Without an offset the
+// value is in the one of Timezone Offset From UTC, or local time of the
+// writer when the dataset has none
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DicomDateTime {
+    pub date: DateValue,
+    // Only with a complete date
+    pub time: Option<TimeValue>,
+    pub offset: Option<FixedOffset>,
+}
+
+impl DicomDateTime {
+    pub fn new(datetime: NaiveDateTime) -> Self {
+        DicomDateTime {
+            date: DateValue::Date(datetime.date()),
+            time: Some(TimeValue::from(datetime.time())),
+            offset: None,
+        }
+    }
+
+    pub fn parse(value: &str) -> DicomResult<Self> {
+        let invalid = || DicomError::InvalidValue(format!("{} is not a valid DT value", value));
+        let value = value.trim_end_matches(['\0', ' ']);
+        let (datetime, offset) = match value.rfind(['+', '-']) {
+            Some(index) => (&value[..index], Some(parse_offset(&value[index..])?)),
+            None => (value, None),
+        };
+        if !datetime.is_char_boundary(datetime.len().min(8)) {
+            return Err(invalid());
+        }
+        let (date, time) = datetime.split_at(datetime.len().min(8));
+        let date = DateValue::parse(date).map_err(|_| invalid())?;
+        let time = match time {
+            "" => None,
+            _ if date.is_complete() => Some(TimeValue::parse(time).map_err(|_| invalid())?),
+            _ => return Err(invalid()),
+        };
+        Ok(DicomDateTime { date, time, offset })
+    }
+
+    pub fn with_offset(mut self, offset: FixedOffset) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    // Start of the period the value stands for, midnight without a time
+    pub fn naive(&self) -> Option<NaiveDateTime> {
+        let time = match self.time {
+            Some(time) => time.start()?,
+            None => NaiveTime::default(),
+        };
+        Some(self.date.first_day()?.and_time(time))
+    }
+
+    // Another date and time at the precision of this one, its offset kept
+    pub fn with_datetime(&self, datetime: NaiveDateTime) -> Self {
+        DicomDateTime {
+            date: self.date.with_date(datetime.date()),
+            time: self.time.map(|time| time.with_time(datetime.time())),
+            offset: self.offset,
+        }
+    }
+
+    // The instant, in the value's own offset or else the one given, e.g.
+    // Dataset::timezone_offset
+    pub fn to_fixed_offset(&self, default: FixedOffset) -> Option<DateTime<FixedOffset>> {
+        self.naive()?
+            .and_local_timezone(self.offset.unwrap_or(default))
+            .single()
+    }
+    pub fn to_utc(&self, default: FixedOffset) -> Option<DateTime<Utc>> {
+        self.to_fixed_offset(default)
+            .map(|datetime| datetime.with_timezone(&Utc))
+    }
+}
+
+impl From<NaiveDateTime> for DicomDateTime {
+    fn from(datetime: NaiveDateTime) -> Self {
+        DicomDateTime::new(datetime)
+    }
+}
+
+impl From<DateTime<FixedOffset>> for DicomDateTime {
+    fn from(datetime: DateTime<FixedOffset>) -> Self {
+        DicomDateTime::new(datetime.naive_local()).with_offset(*datetime.offset())
+    }
+}
+
+impl fmt::Display for DicomDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.date)?;
+        if let Some(time) = self.time {
+            write!(f, "{}", time)?;
+        }
+        match self.offset {
+            Some(offset) => write!(f, "{}", format_offset(offset)),
+            None => Ok(()),
+        }
+    }
+}
+
+// &ZZXX as in DT values and Timezone Offset From UTC, -1200 to +1400
+pub fn parse_offset(text: &str) -> DicomResult<FixedOffset> {
+    let invalid = || DicomError::InvalidValue(format!("{} is not a UTC offset", text));
+    let text = text.trim();
+    let (sign, digits) = if let Some(digits) = text.strip_prefix('+') {
+        (1, digits)
+    } else if let Some(digits) = text.strip_prefix('-') {
+        (-1, digits)
+    } else {
+        return Err(invalid());
+    };
+    if digits.len() != 4 || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let hours: i32 = digits[..2].parse().map_err(|_| invalid())?;
+    let minutes: i32 = digits[2..].parse().map_err(|_| invalid())?;
+    let seconds = sign * (hours * 3600 + minutes * 60);
+    if minutes >= 60 || !(-12 * 3600..=14 * 3600).contains(&seconds) {
+        return Err(invalid());
+    }
+    FixedOffset::east_opt(seconds).ok_or_else(invalid)
+}
+
+pub fn format_offset(offset: FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    format!(
+        "{}{:02}{:02}",
+        if seconds < 0 { '-' } else { '+' },
+        seconds.abs() / 3600,
+        seconds.abs() % 3600 / 60
+    )
+}
+
+impl Dataset {
+    // Timezone Offset From UTC, which DA, TM and DT values without their
+    // own offset are in
+    pub fn timezone_offset(&self) -> Option<FixedOffset> {
+        parse_offset(&self.string(TIMEZONE_OFFSET_FROM_UTC)?).ok()
+    }
+
+    pub fn set_timezone_offset(&mut self, offset: FixedOffset) {
+        self.put_string(TIMEZONE_OFFSET_FROM_UTC, "SH", &format_offset(offset));
+    }
+
+    // DT value as an instant, None when it has no offset of its own and
+    // the dataset none either
+    pub fn datetime(&self, tag: (u16, u16)) -> Option<DateTime<FixedOffset>> {
+        let VisualRepresentation::DT(value) = self.value(tag)? else {
+            return None;
+        };
+        value.to_fixed_offset(value.offset.or_else(|| self.timezone_offset())?)
+    }
+
+    // A DA and TM pair such as Study Date and Study Time as an instant, the
+    // day's start when the time is missing
+    pub fn date_and_time(
+        &self,
+        date: (u16, u16),
+        time: (u16, u16),
+    ) -> Option<DateTime<FixedOffset>> {
+        let VisualRepresentation::DA(date) = self.value(date)? else {
+            return None;
+        };
+        let time = match self.value(time) {
+            Some(VisualRepresentation::TM(time)) => Some(time),
+            _ => None,
+        };
+        DicomDateTime {
+            date,
+            time,
+            offset: None,
+        }
+        .to_fixed_offset(self.timezone_offset()?)
+    }
+}
@@ -0,0 +1,62 @@
+// Attributes the readers need to know about when the VR is not on the wire.
+// build.rs generates the table from the PS3.6 attributes, sorted by tag so
+// lookups can binary search.
+
+use super::error::{DicomError, DicomResult};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DictionaryEntry {
+    pub tag: (u16, u16),
+    pub vr: &'static str,
+    pub vm: &'static str,
+    pub keyword: &'static str,
+}
+
+include!(concat!(env!("OUT_DIR"), "/dictionary.rs"));
+
+pub fn lookup(tag: (u16, u16)) -> Option<&'static DictionaryEntry> {
+    ENTRIES
+        .binary_search_by(|entry| entry.tag.cmp(&tag))
+        .ok()
+        .map(|index| &ENTRIES[index])
+}
+
+pub fn by_keyword(keyword: &str) -> Option<&'static DictionaryEntry> {
+    ENTRIES.iter().find(|entry| entry.keyword == keyword)
+}
+
+pub fn keyword(tag: (u16, u16)) -> Option<&'static str> {
+    lookup(tag).map(|entry| entry.keyword)
+}
+
+// VR to use when the transfer syntax leaves it implicit
+pub fn vr_of(tag: (u16, u16)) -> &'static str {
+    if let Some(entry) = lookup(tag) {
+        return entry.vr;
+    }
+
+    match tag {
+        (_, 0x0000) => "UL",
+        (group, element) if group % 2 == 1 && (0x0010..=0x00FF).contains(&element) => "LO",
+        _ => "UN",
+    }
+}
+
+// Tag as (gggg,eeee), gggg,eeee, ggggeeee or a dictionary keyword
+pub fn parse_tag(text: &str) -> DicomResult<(u16, u16)> {
+    if let Some(entry) = by_keyword(text) {
+        return Ok(entry.tag);
+    }
+
+    let hex: String = text
+        .chars()
+        .filter(|c| !matches!(c, '(' | ')' | ','))
+        .collect();
+    let invalid = || DicomError::InvalidTag(format!("{} is neither a tag nor a keyword", text));
+    if hex.len() != 8 {
+        return Err(invalid());
+    }
+    let group = u16::from_str_radix(&hex[..4], 16).map_err(|_| invalid())?;
+    let element = u16::from_str_radix(&hex[4..], 16).map_err(|_| invalid())?;
+    Ok((group, element))
+}
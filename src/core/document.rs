@@ -1,33 +1,59 @@
 use std::{
     fs::{self, File},
     io::{Read, Seek, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     time::SystemTime,
 };
 
 use super::{
     dataset::Dataset,
     error::{DicomError, DicomResult},
+    reader,
     tag::{DicomTag, DicomValue},
+    transfer_syntax, writer, zip,
 };
 
+// Storage backend holding one DICOM file, usable as Box<dyn Document>. File
+// details are None for sources that are not files on disk
 pub trait Document {
-    fn open(path: &str) -> DicomResult<Self>
-    where
-        Self: Sized;
     fn refresh(&mut self) -> ();
     fn read(&mut self) -> DicomResult<&Dataset>;
     fn write(&mut self, dataset: &Dataset) -> DicomResult<()>;
     fn close(&mut self) -> DicomResult<()>;
     fn is_open(&self) -> bool;
     fn is_modified(&self) -> bool;
-    fn get_path(&self) -> Option<&str>;
-    fn get_name(&self) -> Option<&str>;
-    fn get_extension(&self) -> Option<&str>;
-    fn get_size(&self) -> Option<usize>;
-    fn get_creation_date(&self) -> Option<SystemTime>;
-    fn get_modification_date(&self) -> Option<SystemTime>;
-    fn get_access_date(&self) -> Option<SystemTime>;
+    fn get_path(&self) -> Option<&str> {
+        None
+    }
+    fn get_name(&self) -> Option<&str> {
+        None
+    }
+    fn get_extension(&self) -> Option<&str> {
+        None
+    }
+    fn get_size(&self) -> Option<usize> {
+        None
+    }
+    fn get_creation_date(&self) -> Option<SystemTime> {
+        None
+    }
+    fn get_modification_date(&self) -> Option<SystemTime> {
+        None
+    }
+    fn get_access_date(&self) -> Option<SystemTime> {
+        None
+    }
+}
+
+// Opens a file, or an entry of a zip archive written as archive.zip!entry
+pub fn open(path: &str) -> DicomResult<Box<dyn Document>> {
+    if let Some((archive, entry)) = path.split_once(".zip!") {
+        return Ok(Box::new(ZipDocument::open(
+            format!("{}.zip", archive),
+            entry,
+        )?));
+    }
+    Ok(Box::new(DicomDocument::open(path)?))
 }
 
 #[derive(PartialEq)]
@@ -43,11 +69,13 @@ pub enum DocumentMode {
     ReadWrite,
 }
 
+// Writes go to a temporary file beside the document, synced and renamed
+// over it, so a failed write never leaves a half written file behind
 #[derive(PartialEq)]
 pub enum WritingMode {
-    Append,
-    Overwrite,
-    Truncate,
+    Replace,
+    // Keeps the previous contents as <name>.bak
+    Backup,
 }
 
 pub struct DicomDocument {
@@ -57,55 +85,97 @@ pub struct DicomDocument {
     dataset: Option<Dataset>,
     mode: DocumentMode,
     writer: WritingMode,
+    transfer_syntax: String,
     should_sync: bool,
 }
 
-impl Document for DicomDocument {
-    fn open(path: &str) -> DicomResult<Self> {
-        let mut _this = None;
-        if PathBuf::from(path).exists() {
-            let file = File::options().append(true).open(path)?;
-            let writer = WritingMode::Append;
-            let state = DocumentState::Open;
-            _this = Some(DicomDocument {
-                file,
-                state,
-                dataset: None,
-                mode: DocumentMode::ReadWrite,
-                writer,
-                path: Some(PathBuf::from(path)),
-                should_sync: true,
-            });
-        } else {
-            let file = File::create(path)?;
-            let writer = WritingMode::Overwrite;
-            let state = DocumentState::Open;
-            _this = Some(DicomDocument {
-                file,
-                state,
-                dataset: None,
-                mode: DocumentMode::ReadWrite,
-                writer,
-                path: Some(PathBuf::from(path)),
-                should_sync: true,
-            });
+impl DicomDocument {
+    // Read-write access to an existing file, see create for new ones
+    pub fn open(path: &str) -> DicomResult<Self> {
+        DicomDocument::open_rw(path)
+    }
+
+    // New empty document, failing rather than replacing an existing file
+    pub fn create<P: AsRef<Path>>(path: P) -> DicomResult<Self> {
+        let path = path.as_ref();
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        let mut document = DicomDocument::from_file(file, path, DocumentMode::ReadWrite);
+        document.dataset = Some(Dataset::new());
+        document.should_sync = false;
+        Ok(document)
+    }
+
+    pub fn open_read<P: AsRef<Path>>(path: P) -> DicomResult<Self> {
+        let path = existing(path.as_ref())?;
+        let file = File::open(path)?;
+        Ok(DicomDocument::from_file(file, path, DocumentMode::ReadOnly))
+    }
+
+    pub fn open_rw<P: AsRef<Path>>(path: P) -> DicomResult<Self> {
+        let path = existing(path.as_ref())?;
+        let file = File::options().read(true).write(true).open(path)?;
+        Ok(DicomDocument::from_file(
+            file,
+            path,
+            DocumentMode::ReadWrite,
+        ))
+    }
+
+    fn from_file(file: File, path: &Path, mode: DocumentMode) -> Self {
+        DicomDocument {
+            file,
+            path: Some(path.to_path_buf()),
+            state: DocumentState::Open,
+            dataset: None,
+            mode,
+            writer: WritingMode::Replace,
+            transfer_syntax: transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN.to_string(),
+            should_sync: true,
         }
+    }
+
+    pub fn with_writing_mode(mut self, writer: WritingMode) -> Self {
+        self.writer = writer;
+        self
+    }
+
+    // Syntax of the next write, the one read from the file by default
+    pub fn with_transfer_syntax(mut self, transfer_syntax: &str) -> Self {
+        self.transfer_syntax = transfer_syntax.to_string();
+        self
+    }
+}
 
-        Ok(_this.unwrap())
+fn existing(path: &Path) -> DicomResult<&Path> {
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(DicomError::InvalidFile(format!(
+            "{} is not an existing file",
+            path.display()
+        )))
     }
+}
 
+impl Document for DicomDocument {
     fn refresh(&mut self) {
         self.should_sync = true;
     }
 
     fn read(&mut self) -> DicomResult<&Dataset> {
         if self.should_sync {
-            let mut buffer = String::new();
+            let mut buffer = Vec::new();
 
-            self.file.read_to_string(&mut buffer)?;
-            let dataset = parse_dicom(&buffer)?;
+            self.file.rewind()?;
+            self.file.read_to_end(&mut buffer)?;
+            let file = reader::read_file(&buffer)?;
 
-            self.dataset = Some(dataset);
+            self.transfer_syntax = file.transfer_syntax;
+            self.dataset = Some(file.dataset);
             self.state = DocumentState::Closed;
 
             self.should_sync = false;
@@ -116,18 +186,25 @@ impl Document for DicomDocument {
 
     fn write(&mut self, dataset: &Dataset) -> DicomResult<()> {
         if self.mode == DocumentMode::ReadOnly {
-            return Err(DicomError::IOError("Document is read-only".to_string()));
+            return Err(DicomError::Io(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "Document is read-only",
+            )));
         }
 
-        if self.writer == WritingMode::Truncate {
-            self.file.set_len(0)?;
-        }
+        let path = self
+            .path
+            .clone()
+            .ok_or_else(|| DicomError::Io(std::io::Error::other("Document is closed")))?;
 
-        else if self.writer == WritingMode::Append {
-            self.file.seek(std::io::SeekFrom::End(0))?;
-        }
+        // Encoding fails on a dataset that cannot make a valid file, before
+        // anything on disk is touched
+        let data = writer::write_file(dataset, &self.transfer_syntax)?;
+        write_atomic(&path, &data, self.writer == WritingMode::Backup)?;
 
-        self.file.write_all(dataset.to_string().as_bytes())?;
+        self.file = File::options().read(true).write(true).open(&path)?;
+        self.dataset = Some(dataset.clone());
+        self.should_sync = false;
         self.state = DocumentState::Modified;
 
         Ok(())
@@ -198,6 +275,215 @@ impl Document for DicomDocument {
     }
 }
 
-fn parse_dicom(input: &str) -> DicomResult<Dataset> {
-    unimplemented!()
+// Document over a buffer, e.g. an upload or a file fetched by other means
+pub struct MemoryDocument {
+    data: Vec<u8>,
+    dataset: Option<Dataset>,
+    transfer_syntax: String,
+    state: DocumentState,
+}
+
+impl MemoryDocument {
+    pub fn new(data: Vec<u8>) -> Self {
+        MemoryDocument {
+            data,
+            dataset: None,
+            transfer_syntax: transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN.to_string(),
+            state: DocumentState::Open,
+        }
+    }
+
+    // Part 10 encoding of the last write, or the buffer it was made with
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl Document for MemoryDocument {
+    fn refresh(&mut self) {
+        self.dataset = None;
+    }
+
+    fn read(&mut self) -> DicomResult<&Dataset> {
+        if self.dataset.is_none() {
+            let file = reader::read_file(&self.data)?;
+            self.transfer_syntax = file.transfer_syntax;
+            self.dataset = Some(file.dataset);
+        }
+        Ok(self.dataset.as_ref().unwrap())
+    }
+
+    fn write(&mut self, dataset: &Dataset) -> DicomResult<()> {
+        if self.state == DocumentState::Closed {
+            return Err(DicomError::Io(std::io::Error::other("Document is closed")));
+        }
+        self.data = writer::write_file(dataset, &self.transfer_syntax)?;
+        self.dataset = Some(dataset.clone());
+        self.state = DocumentState::Modified;
+        Ok(())
+    }
+
+    fn close(&mut self) -> DicomResult<()> {
+        self.state = DocumentState::Closed;
+        Ok(())
+    }
+
+    fn is_open(&self) -> bool {
+        self.state != DocumentState::Closed
+    }
+
+    fn is_modified(&self) -> bool {
+        self.state == DocumentState::Modified
+    }
+
+    fn get_size(&self) -> Option<usize> {
+        Some(self.data.len())
+    }
+}
+
+// Entry of a zip archive. Entries are read when stored uncompressed and
+// written that way, the archive's other entries being copied as they are
+pub struct ZipDocument {
+    archive: PathBuf,
+    entry: String,
+    dataset: Option<Dataset>,
+    transfer_syntax: String,
+    state: DocumentState,
+    size: Option<usize>,
+}
+
+impl ZipDocument {
+    pub fn open<P: AsRef<Path>>(archive: P, entry: &str) -> DicomResult<Self> {
+        let archive = existing(archive.as_ref())?.to_path_buf();
+        let data = fs::read(&archive)?;
+        if !zip::entries(&data)?.iter().any(|found| found.name == entry) {
+            return Err(DicomError::InvalidFile(format!(
+                "{} has no entry {}",
+                archive.display(),
+                entry
+            )));
+        }
+
+        Ok(ZipDocument {
+            archive,
+            entry: entry.to_string(),
+            dataset: None,
+            transfer_syntax: transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN.to_string(),
+            state: DocumentState::Open,
+            size: None,
+        })
+    }
+}
+
+impl Document for ZipDocument {
+    fn refresh(&mut self) {
+        self.dataset = None;
+    }
+
+    fn read(&mut self) -> DicomResult<&Dataset> {
+        if self.dataset.is_none() {
+            let data = fs::read(&self.archive)?;
+            let entry = zip::read_entry(&data, &self.entry)?;
+            let file = reader::read_file(&entry)?;
+            self.size = Some(entry.len());
+            self.transfer_syntax = file.transfer_syntax;
+            self.dataset = Some(file.dataset);
+        }
+        Ok(self.dataset.as_ref().unwrap())
+    }
+
+    fn write(&mut self, dataset: &Dataset) -> DicomResult<()> {
+        if self.state == DocumentState::Closed {
+            return Err(DicomError::Io(std::io::Error::other("Document is closed")));
+        }
+        let entry = writer::write_file(dataset, &self.transfer_syntax)?;
+        let archive = zip::replace_entry(&fs::read(&self.archive)?, &self.entry, &entry)?;
+        write_atomic(&self.archive, &archive, false)?;
+
+        self.size = Some(entry.len());
+        self.dataset = Some(dataset.clone());
+        self.state = DocumentState::Modified;
+        Ok(())
+    }
+
+    fn close(&mut self) -> DicomResult<()> {
+        self.state = DocumentState::Closed;
+        Ok(())
+    }
+
+    fn is_open(&self) -> bool {
+        self.state != DocumentState::Closed
+    }
+
+    fn is_modified(&self) -> bool {
+        self.state == DocumentState::Modified
+    }
+
+    fn get_name(&self) -> Option<&str> {
+        self.entry.rsplit('/').next()
+    }
+
+    fn get_extension(&self) -> Option<&str> {
+        self.entry
+            .rsplit('/')
+            .next()
+            .and_then(|name| name.rsplit_once('.'))
+            .map(|(_, extension)| extension)
+    }
+
+    fn get_size(&self) -> Option<usize> {
+        self.size
+    }
+}
+
+// Replaces the file at `path` with `data` through a synced temporary file
+// and a rename, optionally keeping the previous file as <name>.bak
+pub fn write_atomic(path: &Path, data: &[u8], backup: bool) -> DicomResult<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| {
+            DicomError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is not a file", path.display()),
+            ))
+        })?
+        .to_string_lossy()
+        .into_owned();
+    let temporary = path.with_file_name(format!(".{}.tmp", name));
+
+    let result = (|| -> DicomResult<()> {
+        let mut file = File::create(&temporary)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+
+        if backup && path.exists() {
+            let backup = path.with_file_name(format!("{}.bak", name));
+            if backup.exists() {
+                fs::remove_file(&backup)?;
+            }
+            if fs::hard_link(path, &backup).is_err() {
+                fs::copy(path, &backup)?;
+            }
+        }
+
+        fs::rename(&temporary, path)?;
+        // The rename itself is durable once the directory is synced
+        #[cfg(unix)]
+        if let Some(directory) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            File::open(directory)?.sync_all()?;
+        }
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
 }
@@ -0,0 +1,202 @@
+use std::{fmt::Display, rc::Rc};
+
+use super::{
+    dataset::Dataset,
+    tag::{DicomTag, VisualRepresentation},
+};
+
+pub const ITEM_TAG: (u16, u16) = (0xFFFE, 0xE000);
+
+// How a sequence or item is delimited when written. Undefined length ends it
+// with a delimitation item instead of giving its length up front
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LengthEncoding {
+    #[default]
+    Defined,
+    Undefined,
+}
+
+#[derive(Debug, Clone)]
+pub struct DicomElement {
+    tag: (u16, u16),
+    value: VisualRepresentation,
+    length_encoding: LengthEncoding,
+}
+
+impl DicomElement {
+    pub fn new(tag: (u16, u16), value: VisualRepresentation) -> Self {
+        DicomElement {
+            tag,
+            value,
+            length_encoding: LengthEncoding::Defined,
+        }
+    }
+
+    // An empty string gives an empty value rather than e.g. a default date
+    pub fn from_string(tag: (u16, u16), vr: &str, value: &str) -> Self {
+        if value.is_empty() && vr != "SQ" {
+            return DicomElement::new(tag, VisualRepresentation::empty(vr));
+        }
+        DicomElement::new(tag, VisualRepresentation::from_string(vr, value))
+    }
+
+    pub fn sequence(tag: (u16, u16), items: Vec<Dataset>) -> Self {
+        let items = items
+            .into_iter()
+            .map(|item| Rc::new(DicomItem::new(item)) as Rc<dyn DicomTag>)
+            .collect();
+
+        DicomElement::new(tag, VisualRepresentation::SQ(items))
+    }
+
+    // Sequence keeping each item's encoding
+    pub fn sequence_of(tag: (u16, u16), items: Vec<Item>) -> Self {
+        let items = items
+            .into_iter()
+            .map(|item| Rc::new(item) as Rc<dyn DicomTag>)
+            .collect();
+
+        DicomElement::new(tag, VisualRepresentation::SQ(items))
+    }
+
+    // Only sequences are written with an undefined length
+    pub fn with_length_encoding(mut self, length_encoding: LengthEncoding) -> Self {
+        self.length_encoding = length_encoding;
+        self
+    }
+
+    pub fn value(&self) -> &VisualRepresentation {
+        &self.value
+    }
+}
+
+impl DicomTag for DicomElement {
+    fn name(&self) -> String {
+        format!("({:04X},{:04X})", self.tag.0, self.tag.1)
+    }
+
+    fn tag(&self) -> (u16, u16) {
+        self.tag
+    }
+
+    fn vr(&self) -> VisualRepresentation {
+        self.value.clone()
+    }
+
+    fn group(&self) -> u16 {
+        self.tag.0
+    }
+
+    fn element(&self) -> Option<u16> {
+        Some(self.tag.1)
+    }
+
+    fn is_deprecated(&self) -> bool {
+        false
+    }
+
+    fn multiplicity(&self) -> &str {
+        "1"
+    }
+
+    fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>() + self.value.heap_size()
+    }
+
+    fn length_encoding(&self) -> LengthEncoding {
+        self.length_encoding
+    }
+}
+
+impl Display for DicomElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} {} {}", self.name(), self.value.code(), self.value)
+    }
+}
+
+// Sequence item, its dataset and how it is delimited
+#[derive(Debug, Clone)]
+pub struct DicomItem {
+    dataset: Dataset,
+    length_encoding: LengthEncoding,
+}
+
+pub type Item = DicomItem;
+
+impl DicomItem {
+    pub fn new(dataset: Dataset) -> Self {
+        DicomItem {
+            dataset,
+            length_encoding: LengthEncoding::Defined,
+        }
+    }
+
+    // Copy of an item held by a sequence
+    pub fn from_tag(item: &dyn DicomTag) -> Option<Self> {
+        item.dataset().map(|dataset| {
+            DicomItem::new(dataset.clone()).with_length_encoding(item.length_encoding())
+        })
+    }
+
+    pub fn with_length_encoding(mut self, length_encoding: LengthEncoding) -> Self {
+        self.length_encoding = length_encoding;
+        self
+    }
+
+    pub fn dataset_mut(&mut self) -> &mut Dataset {
+        &mut self.dataset
+    }
+
+    pub fn into_dataset(self) -> Dataset {
+        self.dataset
+    }
+}
+
+impl DicomTag for DicomItem {
+    fn name(&self) -> String {
+        "Item".to_string()
+    }
+
+    fn tag(&self) -> (u16, u16) {
+        ITEM_TAG
+    }
+
+    fn vr(&self) -> VisualRepresentation {
+        VisualRepresentation::new("")
+    }
+
+    fn group(&self) -> u16 {
+        ITEM_TAG.0
+    }
+
+    fn element(&self) -> Option<u16> {
+        Some(ITEM_TAG.1)
+    }
+
+    fn is_deprecated(&self) -> bool {
+        false
+    }
+
+    fn multiplicity(&self) -> &str {
+        "1"
+    }
+
+    fn dataset(&self) -> Option<&Dataset> {
+        Some(&self.dataset)
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.dataset.memory_usage()
+    }
+
+    fn length_encoding(&self) -> LengthEncoding {
+        self.length_encoding
+    }
+}
+
+impl Display for DicomItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.name())?;
+        write!(f, "{}", self.dataset)
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use thiserror::Error;
 
 pub type DicomResult<T> = Result<T, DicomError>;
@@ -21,11 +23,290 @@ pub enum DicomError {
     #[error("Syntax error: {0}")]
     SyntaxError(SyntaxErrorKind),
     #[error("IO error: {0}")]
-    IOError(String),
+    Io(#[source] std::io::Error),
+    #[error("HTTP status {0}")]
+    HttpStatus(u16),
+    #[error("Unsupported transfer syntax: {0}")]
+    UnsupportedTransferSyntax(String),
+    #[error("Association aborted by {by}: {reason}")]
+    AssociationAborted { by: AbortSource, reason: String },
+    #[error("Association rejected: {0}")]
+    AssociationRejected(String),
+    #[error("Timed out: {0}")]
+    Timeout(String),
+    // Connection level failure known only by its description, e.g. from a
+    // report that kept it as text
+    #[error("Network failure: {0}")]
+    Network(String),
+    // Checks that ran and did not pass, as opposed to input that could not
+    // be read
+    #[error("Validation failed: {0}")]
+    ValidationFailed(String),
+    // Failure of a library the crate builds on, e.g. the HTTP client
+    #[error("{context}: {source}")]
+    External {
+        context: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("At offset {offset}: {source}")]
+    AtOffset {
+        offset: usize,
+        #[source]
+        source: Box<DicomError>,
+    },
+    #[error("In {path}: {source}")]
+    InElement {
+        path: TagPath,
+        #[source]
+        source: Box<DicomError>,
+    },
     #[error("Unknown error: {0}")]
     Error(String),
 }
 
+impl DicomError {
+    // Byte offset of the failure, when it happened while reading
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            DicomError::AtOffset { offset, .. } => Some(*offset),
+            DicomError::InElement { source, .. } => source.offset(),
+            _ => None,
+        }
+    }
+
+    // Element the failure happened in, from the top level dataset down
+    pub fn path(&self) -> Option<&TagPath> {
+        match self {
+            DicomError::InElement { path, .. } => Some(path),
+            DicomError::AtOffset { source, .. } => source.path(),
+            _ => None,
+        }
+    }
+
+    // The error without the offset and element it was wrapped with
+    pub fn root(&self) -> &DicomError {
+        match self {
+            DicomError::AtOffset { source, .. } | DicomError::InElement { source, .. } => {
+                source.root()
+            }
+            error => error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagPathStep {
+    Tag((u16, u16)),
+    Item(usize),
+}
+
+// Location of an element inside nested sequences, e.g.
+// (0040,A730)[2]/(0040,A043)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagPath {
+    pub steps: Vec<TagPathStep>,
+}
+
+impl TagPath {
+    pub fn new(tag: (u16, u16)) -> Self {
+        TagPath {
+            steps: vec![TagPathStep::Tag(tag)],
+        }
+    }
+
+    // Innermost tag of the path
+    pub fn tag(&self) -> Option<(u16, u16)> {
+        self.steps.iter().rev().find_map(|step| match step {
+            TagPathStep::Tag(tag) => Some(*tag),
+            TagPathStep::Item(_) => None,
+        })
+    }
+}
+
+impl fmt::Display for TagPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, step) in self.steps.iter().enumerate() {
+            match step {
+                TagPathStep::Tag(tag) if index == 0 => write!(f, "({:04X},{:04X})", tag.0, tag.1)?,
+                TagPathStep::Tag(tag) => write!(f, "/({:04X},{:04X})", tag.0, tag.1)?,
+                TagPathStep::Item(item) => write!(f, "[{}]", item)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Adds the location of a failure while it propagates out of nested readers
+pub trait ErrorContext<T> {
+    // Kept when an inner reader already gave a more precise offset
+    fn at_offset(self, offset: usize) -> DicomResult<T>;
+    fn in_element(self, tag: (u16, u16)) -> DicomResult<T>;
+    fn in_item(self, item: usize) -> DicomResult<T>;
+}
+
+impl<T> ErrorContext<T> for DicomResult<T> {
+    fn at_offset(self, offset: usize) -> DicomResult<T> {
+        self.map_err(|error| match error.offset() {
+            Some(_) => error,
+            None => DicomError::AtOffset {
+                offset,
+                source: Box::new(error),
+            },
+        })
+    }
+
+    fn in_element(self, tag: (u16, u16)) -> DicomResult<T> {
+        self.map_err(|error| error.within(TagPathStep::Tag(tag)))
+    }
+
+    fn in_item(self, item: usize) -> DicomResult<T> {
+        self.map_err(|error| error.within(TagPathStep::Item(item)))
+    }
+}
+
+impl DicomError {
+    fn within(self, step: TagPathStep) -> DicomError {
+        match self {
+            DicomError::InElement { mut path, source } => {
+                path.steps.insert(0, step);
+                DicomError::InElement { path, source }
+            }
+            DicomError::AtOffset { offset, source } => DicomError::AtOffset {
+                offset,
+                source: Box::new(source.within(step)),
+            },
+            error => DicomError::InElement {
+                path: TagPath { steps: vec![step] },
+                source: Box::new(error),
+            },
+        }
+    }
+}
+
+// Problem that does not stop a file from being read, e.g. a value of odd
+// length
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub message: String,
+    pub path: Option<TagPath>,
+    pub offset: Option<usize>,
+}
+
+impl Warning {
+    pub fn new(message: &str) -> Self {
+        Warning {
+            message: message.to_string(),
+            path: None,
+            offset: None,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "In {}: ", path)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, "At offset {}: ", offset)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Warnings {
+    pub warnings: Vec<Warning>,
+}
+
+impl Warnings {
+    pub fn new() -> Self {
+        Warnings::default()
+    }
+
+    pub fn push(&mut self, message: &str) {
+        self.warnings.push(Warning::new(message));
+    }
+
+    pub fn push_at(&mut self, message: &str, path: Option<TagPath>, offset: Option<usize>) {
+        self.warnings.push(Warning {
+            message: message.to_string(),
+            path,
+            offset,
+        });
+    }
+
+    pub fn extend(&mut self, other: Warnings) {
+        self.warnings.extend(other.warnings);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.warnings.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Warning> {
+        self.warnings.iter()
+    }
+}
+
+impl IntoIterator for Warnings {
+    type Item = Warning;
+    type IntoIter = std::vec::IntoIter<Warning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.warnings.into_iter()
+    }
+}
+
+impl fmt::Display for Warnings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for warning in &self.warnings {
+            writeln!(f, "{}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbortSource {
+    ServiceUser,
+    ServiceProvider,
+    Reserved(u8),
+}
+
+impl AbortSource {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => AbortSource::ServiceUser,
+            2 => AbortSource::ServiceProvider,
+            other => AbortSource::Reserved(other),
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            AbortSource::ServiceUser => 0,
+            AbortSource::ServiceProvider => 2,
+            AbortSource::Reserved(value) => *value,
+        }
+    }
+}
+
+impl fmt::Display for AbortSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbortSource::ServiceUser => write!(f, "service user"),
+            AbortSource::ServiceProvider => write!(f, "service provider"),
+            AbortSource::Reserved(value) => write!(f, "reserved source {}", value),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum SyntaxErrorKind {
     #[error("Invalid character: {0}")]
@@ -48,7 +329,20 @@ pub enum SyntaxErrorKind {
 
 impl From<std::io::Error> for DicomError {
     fn from(error: std::io::Error) -> Self {
-        DicomError::IOError(error.to_string())
+        DicomError::Io(error)
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl From<reqwest::Error> for DicomError {
+    fn from(error: reqwest::Error) -> Self {
+        match error.status() {
+            Some(status) => DicomError::HttpStatus(status.as_u16()),
+            None => DicomError::External {
+                context: "HTTP request failed".to_string(),
+                source: Box::new(error),
+            },
+        }
     }
 }
 
@@ -66,4 +360,4 @@ impl<T> OptionExt<T> for Option<T> {
     fn ok_or_err(self) -> Result<T, DicomError> {
         self.ok_or(DicomError::Error("Unwrapped empty value.".to_string()))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,120 @@
+use std::rc::Rc;
+
+use sha2::{Digest, Sha256};
+
+use super::{
+    dataset::Dataset, dictionary, element::DicomElement, error::DicomResult,
+    tag::VisualRepresentation, transfer_syntax, writer,
+};
+
+// What a content hash leaves out, so that re-exports of the same image by
+// other archives still hash the same. The defaults skip what archives
+// usually rewrite: instance UIDs, dates and times and private groups
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashPolicy {
+    // UI elements other than SOP class UIDs, which name what the object is
+    pub exclude_uids: bool,
+    // DA, DT and TM elements
+    pub exclude_dates: bool,
+    pub exclude_private: bool,
+    // Further tags, left out at any depth
+    pub excluded_tags: Vec<(u16, u16)>,
+}
+
+impl Default for HashPolicy {
+    fn default() -> Self {
+        HashPolicy {
+            exclude_uids: true,
+            exclude_dates: true,
+            exclude_private: true,
+            excluded_tags: Vec::new(),
+        }
+    }
+}
+
+impl HashPolicy {
+    // Every element hashed
+    pub fn all() -> Self {
+        HashPolicy {
+            exclude_uids: false,
+            exclude_dates: false,
+            exclude_private: false,
+            excluded_tags: Vec::new(),
+        }
+    }
+
+    pub fn with_uids(mut self, exclude: bool) -> Self {
+        self.exclude_uids = exclude;
+        self
+    }
+
+    pub fn with_dates(mut self, exclude: bool) -> Self {
+        self.exclude_dates = exclude;
+        self
+    }
+
+    pub fn with_private(mut self, exclude: bool) -> Self {
+        self.exclude_private = exclude;
+        self
+    }
+
+    pub fn with_excluded_tag(mut self, tag: (u16, u16)) -> Self {
+        self.excluded_tags.push(tag);
+        self
+    }
+
+    pub fn excludes(&self, tag: (u16, u16), vr: &str) -> bool {
+        if self.excluded_tags.contains(&tag) {
+            return true;
+        }
+        match vr {
+            "UI" if self.exclude_uids => {
+                !dictionary::keyword(tag).is_some_and(|keyword| keyword.ends_with("ClassUID"))
+            }
+            "DA" | "DT" | "TM" => self.exclude_dates,
+            _ => self.exclude_private && tag.0 % 2 == 1,
+        }
+    }
+
+    // The dataset without the excluded elements, sequence items included
+    pub fn filter(&self, dataset: &Dataset) -> Dataset {
+        let mut kept = Dataset::new();
+        for object in dataset {
+            let tag = object.tag();
+            let value = object.vr();
+            if self.excludes(tag, value.code()) {
+                continue;
+            }
+            if let VisualRepresentation::SQ(_) = value {
+                let items = dataset
+                    .sequence(tag)
+                    .iter()
+                    .map(|item| self.filter(item))
+                    .collect();
+                kept.push_back(Rc::new(DicomElement::sequence(tag, items)));
+            } else {
+                kept.push_back(object.clone());
+            }
+        }
+        kept
+    }
+}
+
+impl Dataset {
+    // SHA-256 in hex of what the policy keeps, written deterministically in
+    // Explicit VR Little Endian. Pixel data is hashed as stored, so copies
+    // in other compressed transfer syntaxes differ; storage::dedup decodes
+    // frames for that
+    pub fn content_hash(&self, policy: &HashPolicy) -> DicomResult<String> {
+        let mut encoded = Vec::new();
+        writer::write_dataset_deterministic(
+            &policy.filter(self),
+            transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN,
+            &mut encoded,
+        )?;
+        Ok(Sha256::digest(&encoded)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    }
+}
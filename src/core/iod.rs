@@ -0,0 +1,105 @@
+// Composite IODs and their modules as tabulated by the innolitics
+// dicom-standard project, generated by build.rs. Validation, builders and the
+// conformance statement all read the same tables
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Usage {
+    Mandatory,
+    Conditional,
+    UserOptional,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleUsage {
+    pub module: &'static str,
+    pub usage: Usage,
+    pub information_entity: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ciod {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub modules: &'static [ModuleUsage],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleAttribute {
+    pub tag: (u16, u16),
+    // Attribute type, 1, 1C, 2, 2C or 3
+    pub kind: &'static str,
+}
+
+impl ModuleAttribute {
+    // Has to be present, conditional ones only when their condition holds
+    pub fn is_required(&self) -> bool {
+        self.kind.starts_with('1') || self.kind.starts_with('2')
+    }
+
+    // Has to hold a value when present
+    pub fn needs_value(&self) -> bool {
+        self.kind.starts_with('1')
+    }
+
+    pub fn is_conditional(&self) -> bool {
+        self.kind.ends_with('C')
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Module {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub attributes: &'static [ModuleAttribute],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SopClass {
+    pub uid: &'static str,
+    pub name: &'static str,
+    // Id of the CIOD the SOP class stores
+    pub ciod: &'static str,
+}
+
+include!(concat!(env!("OUT_DIR"), "/iods.rs"));
+
+pub fn ciod(id: &str) -> Option<&'static Ciod> {
+    CIODS.iter().find(|ciod| ciod.id == id)
+}
+
+pub fn module(id: &str) -> Option<&'static Module> {
+    MODULES.iter().find(|module| module.id == id)
+}
+
+pub fn sop_class(uid: &str) -> Option<&'static SopClass> {
+    let uid = uid.trim_end_matches(['\0', ' ']);
+    SOP_CLASSES.iter().find(|sop_class| sop_class.uid == uid)
+}
+
+pub fn ciod_for_sop_class(uid: &str) -> Option<&'static Ciod> {
+    sop_class(uid).and_then(|sop_class| ciod(sop_class.ciod))
+}
+
+impl Ciod {
+    pub fn modules(&self, usage: Usage) -> Vec<&'static Module> {
+        self.modules
+            .iter()
+            .filter(|entry| entry.usage == usage)
+            .filter_map(|entry| module(entry.module))
+            .collect()
+    }
+
+    // Unconditional type 1 and 2 attributes of the mandatory modules
+    pub fn required_attributes(&self) -> Vec<ModuleAttribute> {
+        let mut attributes: Vec<ModuleAttribute> = self
+            .modules(Usage::Mandatory)
+            .iter()
+            .flat_map(|module| module.attributes.iter())
+            .filter(|attribute| attribute.is_required() && !attribute.is_conditional())
+            .copied()
+            .collect();
+        attributes.sort_by_key(|attribute| attribute.tag);
+        attributes.dedup_by_key(|attribute| attribute.tag);
+        attributes
+    }
+}
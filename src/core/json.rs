@@ -0,0 +1,297 @@
+use std::rc::Rc;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{Map, Value};
+
+use super::{
+    bulkdata::{BulkDataElement, BulkDataResolver},
+    dataset::Dataset,
+    element::{DicomElement, ITEM_TAG},
+    error::{DicomError, DicomResult},
+    tag::{DicomTag, VisualRepresentation},
+};
+
+// DICOM JSON model, PS3.18 Annex F
+pub fn to_json(dataset: &Dataset) -> Value {
+    let mut object = Map::new();
+
+    for element in dataset {
+        let tag = element.tag();
+        if tag == ITEM_TAG {
+            continue;
+        }
+
+        object.insert(
+            format!("{:04X}{:04X}", tag.0, tag.1),
+            attribute(&element.vr()),
+        );
+    }
+
+    Value::Object(object)
+}
+
+// Bulk data references read as empty values
+pub fn from_json(value: &Value) -> DicomResult<Dataset> {
+    read(value, None)
+}
+
+// Bulk data references become elements fetching their value through the
+// resolver when first read
+pub fn from_json_with_resolver(
+    value: &Value,
+    resolver: Rc<dyn BulkDataResolver>,
+) -> DicomResult<Dataset> {
+    read(value, Some(&resolver))
+}
+
+fn read(value: &Value, resolver: Option<&Rc<dyn BulkDataResolver>>) -> DicomResult<Dataset> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| DicomError::InvalidDataset("Expected a JSON object".to_string()))?;
+    let mut dataset = Dataset::new();
+
+    for (key, attribute) in object {
+        let tag = parse_tag(key)?;
+        let vr = attribute
+            .get("vr")
+            .and_then(Value::as_str)
+            .ok_or_else(|| DicomError::InvalidVR(format!("Missing vr for {}", key)))?;
+
+        dataset.push_back(element(tag, vr, attribute, resolver)?);
+    }
+
+    Ok(dataset)
+}
+
+pub fn to_vec(dataset: &Dataset) -> DicomResult<Vec<u8>> {
+    serde_json::to_vec(&to_json(dataset)).map_err(|error| DicomError::Error(error.to_string()))
+}
+
+pub fn from_slice(data: &[u8]) -> DicomResult<Dataset> {
+    let value: Value =
+        serde_json::from_slice(data).map_err(|error| DicomError::InvalidFile(error.to_string()))?;
+    from_json(&value)
+}
+
+pub fn from_slice_with_resolver(
+    data: &[u8],
+    resolver: Rc<dyn BulkDataResolver>,
+) -> DicomResult<Dataset> {
+    let value: Value =
+        serde_json::from_slice(data).map_err(|error| DicomError::InvalidFile(error.to_string()))?;
+    from_json_with_resolver(&value, resolver)
+}
+
+pub fn parse_tag(key: &str) -> DicomResult<(u16, u16)> {
+    let invalid = || DicomError::InvalidTag(key.to_string());
+    if key.len() != 8 {
+        return Err(invalid());
+    }
+
+    let group = u16::from_str_radix(&key[..4], 16).map_err(|_| invalid())?;
+    let element = u16::from_str_radix(&key[4..], 16).map_err(|_| invalid())?;
+    Ok((group, element))
+}
+
+fn attribute(value: &VisualRepresentation) -> Value {
+    let mut object = Map::new();
+    object.insert("vr".to_string(), Value::from(value.code()));
+
+    let values: Vec<Value> = match value {
+        VisualRepresentation::Empty(_) => vec![],
+        VisualRepresentation::SQ(items) => items
+            .iter()
+            .filter_map(|item| item.dataset())
+            .map(to_json)
+            .collect(),
+        VisualRepresentation::OB(bytes) | VisualRepresentation::UN(bytes) => {
+            object.insert(
+                "InlineBinary".to_string(),
+                Value::from(STANDARD.encode(bytes)),
+            );
+            return Value::Object(object);
+        }
+        VisualRepresentation::OW(_)
+        | VisualRepresentation::OD(_)
+        | VisualRepresentation::OF(_)
+        | VisualRepresentation::OL(_)
+        | VisualRepresentation::OV(_) => {
+            object.insert(
+                "InlineBinary".to_string(),
+                Value::from(STANDARD.encode(binary(value))),
+            );
+            return Value::Object(object);
+        }
+        VisualRepresentation::FL(number) => vec![Value::from(*number as f64)],
+        VisualRepresentation::FD(number) => vec![Value::from(*number)],
+        VisualRepresentation::SL(number) => vec![Value::from(*number)],
+        VisualRepresentation::SS(number) => vec![Value::from(*number)],
+        VisualRepresentation::SV(number) => vec![Value::from(*number)],
+        VisualRepresentation::UL(number) => vec![Value::from(*number)],
+        VisualRepresentation::US(number) => vec![Value::from(*number)],
+        VisualRepresentation::PN(_) => strings(value)
+            .into_iter()
+            .map(|name| {
+                let mut name_object = Map::new();
+                name_object.insert("Alphabetic".to_string(), Value::from(name));
+                Value::Object(name_object)
+            })
+            .collect(),
+        VisualRepresentation::DS(_) => strings(value)
+            .into_iter()
+            .map(|number| match number.trim().parse::<f64>() {
+                Ok(parsed) => Value::from(parsed),
+                Err(_) => Value::from(number),
+            })
+            .collect(),
+        VisualRepresentation::IS(_) => strings(value)
+            .into_iter()
+            .map(|number| match number.trim().parse::<i64>() {
+                Ok(parsed) => Value::from(parsed),
+                Err(_) => Value::from(number),
+            })
+            .collect(),
+        _ => strings(value).into_iter().map(Value::from).collect(),
+    };
+
+    if !values.is_empty() {
+        object.insert("Value".to_string(), Value::Array(values));
+    }
+
+    Value::Object(object)
+}
+
+fn strings(value: &VisualRepresentation) -> Vec<String> {
+    let text = value.to_string();
+    let text = text.trim_end_matches([' ', '\0']);
+    if text.is_empty() {
+        return vec![];
+    }
+
+    text.split('\\')
+        .map(|part| part.trim().to_string())
+        .collect()
+}
+
+// Binary VRs travel as little endian, the same as on the wire
+fn binary(value: &VisualRepresentation) -> Vec<u8> {
+    match value {
+        VisualRepresentation::OB(bytes) | VisualRepresentation::UN(bytes) => bytes.clone(),
+        VisualRepresentation::OW(words) => words.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        VisualRepresentation::OD(words) => words.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        VisualRepresentation::OF(words) => words.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        VisualRepresentation::OL(words) => words.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        VisualRepresentation::OV(words) => words.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        _ => vec![],
+    }
+}
+
+fn element(
+    tag: (u16, u16),
+    vr: &str,
+    attribute: &Value,
+    resolver: Option<&Rc<dyn BulkDataResolver>>,
+) -> DicomResult<Rc<dyn DicomTag>> {
+    let values = attribute
+        .get("Value")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    match vr {
+        "SQ" => {
+            let items = values
+                .iter()
+                .map(|item| read(item, resolver))
+                .collect::<DicomResult<_>>()?;
+            Ok(Rc::new(DicomElement::sequence(tag, items)))
+        }
+        "OB" | "OD" | "OF" | "OL" | "OV" | "OW" | "UN" => {
+            let uri = attribute.get("BulkDataURI").and_then(Value::as_str);
+            if let (Some(uri), Some(resolver)) = (uri, resolver) {
+                return Ok(Rc::new(BulkDataElement::new(
+                    tag,
+                    vr,
+                    uri,
+                    resolver.clone(),
+                )));
+            }
+            let bytes = match attribute.get("InlineBinary").and_then(Value::as_str) {
+                Some(encoded) => STANDARD
+                    .decode(encoded)
+                    .map_err(|error| DicomError::InvalidValue(error.to_string()))?,
+                // Bulk data references without a resolver are left empty
+                None => vec![],
+            };
+            Ok(Rc::new(DicomElement::new(tag, from_binary(vr, &bytes))))
+        }
+        _ => {
+            let parts: Vec<String> = values
+                .iter()
+                .map(|value| match value {
+                    Value::String(text) => text.clone(),
+                    Value::Object(name) => name
+                        .get("Alphabetic")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string(),
+                    Value::Null => String::new(),
+                    other => other.to_string(),
+                })
+                .collect();
+
+            if parts.is_empty() {
+                return Ok(Rc::new(DicomElement::new(
+                    tag,
+                    VisualRepresentation::empty(vr),
+                )));
+            }
+
+            // The model keeps a single number for binary numeric VRs
+            let text = match vr {
+                "FL" | "FD" | "SL" | "SS" | "SV" | "UL" | "US" => parts[0].clone(),
+                _ => parts.join("\\"),
+            };
+            let value = VisualRepresentation::try_from_string(vr, &text)?;
+            Ok(Rc::new(DicomElement::new(tag, value)))
+        }
+    }
+}
+
+// Value of a binary VR from its little endian bytes
+pub fn from_binary(vr: &str, bytes: &[u8]) -> VisualRepresentation {
+    match vr {
+        "OW" => VisualRepresentation::OW(
+            bytes
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect(),
+        ),
+        "OF" => VisualRepresentation::OF(
+            bytes
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect(),
+        ),
+        "OL" => VisualRepresentation::OL(
+            bytes
+                .chunks_exact(4)
+                .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect(),
+        ),
+        "OD" => VisualRepresentation::OD(
+            bytes
+                .chunks_exact(8)
+                .map(|c| f64::from_le_bytes(c.try_into().unwrap()))
+                .collect(),
+        ),
+        "OV" => VisualRepresentation::OV(
+            bytes
+                .chunks_exact(8)
+                .map(|c| i64::from_le_bytes(c.try_into().unwrap()))
+                .collect(),
+        ),
+        "UN" => VisualRepresentation::UN(bytes.to_vec()),
+        _ => VisualRepresentation::OB(bytes.to_vec()),
+    }
+}
@@ -0,0 +1,192 @@
+use std::rc::Rc;
+
+use super::{
+    dataset::Dataset,
+    element::DicomElement,
+    error::{DicomError, DicomResult},
+    reader::{DicomFile, TRANSFER_SYNTAX_UID},
+    tag::VisualRepresentation,
+    transfer_syntax, writer,
+};
+
+pub const FILE_META_INFORMATION_GROUP_LENGTH: (u16, u16) = (0x0002, 0x0000);
+pub const FILE_META_INFORMATION_VERSION: (u16, u16) = (0x0002, 0x0001);
+pub const MEDIA_STORAGE_SOP_CLASS_UID: (u16, u16) = (0x0002, 0x0002);
+pub const MEDIA_STORAGE_SOP_INSTANCE_UID: (u16, u16) = (0x0002, 0x0003);
+pub const IMPLEMENTATION_CLASS_UID: (u16, u16) = (0x0002, 0x0012);
+pub const IMPLEMENTATION_VERSION_NAME: (u16, u16) = (0x0002, 0x0013);
+pub const SOURCE_APPLICATION_ENTITY_TITLE: (u16, u16) = (0x0002, 0x0016);
+pub const SOP_CLASS_UID: (u16, u16) = (0x0008, 0x0016);
+pub const SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x0018);
+
+// File meta information of PS3.10 7.1
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileMeta {
+    pub information_version: [u8; 2],
+    pub media_storage_sop_class_uid: String,
+    pub media_storage_sop_instance_uid: String,
+    pub transfer_syntax: String,
+    pub implementation_class_uid: String,
+    pub implementation_version_name: Option<String>,
+    // AE that wrote the file, or that sent it over the network
+    pub source_ae_title: Option<String>,
+}
+
+impl FileMeta {
+    pub fn new(sop_class_uid: &str, sop_instance_uid: &str, transfer_syntax: &str) -> Self {
+        FileMeta {
+            information_version: [0, 1],
+            media_storage_sop_class_uid: sop_class_uid.to_string(),
+            media_storage_sop_instance_uid: sop_instance_uid.to_string(),
+            transfer_syntax: transfer_syntax.to_string(),
+            implementation_class_uid: writer::IMPLEMENTATION_CLASS_UID.to_string(),
+            implementation_version_name: Some(writer::IMPLEMENTATION_VERSION_NAME.to_string()),
+            source_ae_title: None,
+        }
+    }
+
+    // Meta information for writing the dataset, SOP class and instance taken
+    // from it
+    pub fn from_dataset(dataset: &Dataset, transfer_syntax: &str) -> DicomResult<Self> {
+        let required = |tag: (u16, u16), name: &str| {
+            dataset
+                .string(tag)
+                .ok_or_else(|| DicomError::InvalidDataset(format!("Dataset lacks its {}", name)))
+        };
+        if transfer_syntax::lookup(transfer_syntax).is_none() {
+            return Err(DicomError::UnsupportedTransferSyntax(
+                transfer_syntax.to_string(),
+            ));
+        }
+
+        Ok(FileMeta::new(
+            &required(SOP_CLASS_UID, "SOP Class UID")?,
+            &required(SOP_INSTANCE_UID, "SOP Instance UID")?,
+            transfer_syntax,
+        ))
+    }
+
+    // Reads the group 0002 elements of a file
+    pub fn from_meta(meta: &Dataset) -> DicomResult<Self> {
+        let required = |tag: (u16, u16), name: &str| {
+            meta.string(tag).ok_or_else(|| {
+                DicomError::InvalidFile(format!("File meta information lacks its {}", name))
+            })
+        };
+
+        let information_version = match meta.value(FILE_META_INFORMATION_VERSION) {
+            Some(VisualRepresentation::OB(bytes)) | Some(VisualRepresentation::UN(bytes))
+                if bytes.len() == 2 =>
+            {
+                [bytes[0], bytes[1]]
+            }
+            _ => [0, 1],
+        };
+
+        Ok(FileMeta {
+            information_version,
+            media_storage_sop_class_uid: required(
+                MEDIA_STORAGE_SOP_CLASS_UID,
+                "Media Storage SOP Class UID",
+            )?,
+            media_storage_sop_instance_uid: required(
+                MEDIA_STORAGE_SOP_INSTANCE_UID,
+                "Media Storage SOP Instance UID",
+            )?,
+            transfer_syntax: required(TRANSFER_SYNTAX_UID, "Transfer Syntax UID")?,
+            implementation_class_uid: required(
+                IMPLEMENTATION_CLASS_UID,
+                "Implementation Class UID",
+            )?,
+            implementation_version_name: meta.string(IMPLEMENTATION_VERSION_NAME),
+            source_ae_title: meta.string(SOURCE_APPLICATION_ENTITY_TITLE),
+        })
+    }
+
+    pub fn with_transfer_syntax(mut self, transfer_syntax: &str) -> Self {
+        self.transfer_syntax = transfer_syntax.to_string();
+        self
+    }
+
+    pub fn with_source_ae_title(mut self, ae_title: &str) -> Self {
+        self.source_ae_title = Some(ae_title.to_string());
+        self
+    }
+
+    pub fn with_implementation(mut self, class_uid: &str, version_name: Option<&str>) -> Self {
+        self.implementation_class_uid = class_uid.to_string();
+        self.implementation_version_name = version_name.map(str::to_string);
+        self
+    }
+
+    // Group 0002 elements, the group length included
+    pub fn to_dataset(&self) -> DicomResult<Dataset> {
+        let mut meta = Dataset::new();
+        meta.put(Rc::new(DicomElement::new(
+            FILE_META_INFORMATION_VERSION,
+            VisualRepresentation::OB(self.information_version.to_vec()),
+        )));
+        meta.put_string(
+            MEDIA_STORAGE_SOP_CLASS_UID,
+            "UI",
+            &self.media_storage_sop_class_uid,
+        );
+        meta.put_string(
+            MEDIA_STORAGE_SOP_INSTANCE_UID,
+            "UI",
+            &self.media_storage_sop_instance_uid,
+        );
+        meta.put_string(TRANSFER_SYNTAX_UID, "UI", &self.transfer_syntax);
+        meta.put_string(
+            IMPLEMENTATION_CLASS_UID,
+            "UI",
+            &self.implementation_class_uid,
+        );
+        if let Some(version_name) = &self.implementation_version_name {
+            meta.put_string(IMPLEMENTATION_VERSION_NAME, "SH", version_name);
+        }
+        if let Some(ae_title) = &self.source_ae_title {
+            meta.put_string(SOURCE_APPLICATION_ENTITY_TITLE, "AE", ae_title);
+        }
+
+        let length =
+            writer::write_dataset(&meta, transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN)?.len();
+        meta.put(Rc::new(DicomElement::new(
+            FILE_META_INFORMATION_GROUP_LENGTH,
+            VisualRepresentation::UL(length as u32),
+        )));
+        Ok(meta)
+    }
+
+    // The meta information has to describe the dataset it is written with
+    pub fn check(&self, dataset: &Dataset) -> DicomResult<()> {
+        for (tag, expected, name) in [
+            (
+                SOP_CLASS_UID,
+                &self.media_storage_sop_class_uid,
+                "SOP Class UID",
+            ),
+            (
+                SOP_INSTANCE_UID,
+                &self.media_storage_sop_instance_uid,
+                "SOP Instance UID",
+            ),
+        ] {
+            if let Some(value) = dataset.string(tag) {
+                if value.trim_end_matches('\0') != expected.trim_end_matches('\0') {
+                    return Err(DicomError::InvalidDataset(format!(
+                        "{} {} differs from the file meta information's {}",
+                        name, value, expected
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DicomFile {
+    pub fn file_meta(&self) -> DicomResult<FileMeta> {
+        FileMeta::from_meta(&self.meta)
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock, PoisonError,
+    },
+    time::Duration,
+};
+
+// Upper bounds of the histogram buckets, in seconds
+pub const DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Durations counted into DURATION_BUCKETS, the last bucket for anything
+// longer
+#[derive(Debug)]
+pub struct Histogram {
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum_nanos: AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            buckets: (0..=DURATION_BUCKETS.len())
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            count: AtomicU64::new(0),
+            sum_nanos: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Histogram::default()
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(DURATION_BUCKETS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos.fetch_add(
+            duration.as_nanos().min(u64::MAX as u128) as u64,
+            Ordering::Relaxed,
+        );
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn sum(&self) -> Duration {
+        Duration::from_nanos(self.sum_nanos.load(Ordering::Relaxed))
+    }
+
+    // Observations up to each bound, as Prometheus buckets count them
+    pub fn cumulative(&self) -> Vec<(f64, u64)> {
+        let mut total = 0;
+        DURATION_BUCKETS
+            .iter()
+            .zip(&self.buckets)
+            .map(|(bound, bucket)| {
+                total += bucket.load(Ordering::Relaxed);
+                (*bound, total)
+            })
+            .collect()
+    }
+}
+
+// Counters and timings of the network services, storage and codecs. The
+// crate records into `global()`, which the web module exports for Prometheus
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub associations_accepted: Counter,
+    pub associations_rejected: Counter,
+    pub instances_stored: Counter,
+    // P-DATA values, command and data set fragments
+    pub bytes_sent: Counter,
+    pub bytes_received: Counter,
+    // By DIMSE operation, from request to final response
+    dimse: Mutex<BTreeMap<String, Arc<Histogram>>>,
+    // By transfer syntax, per frame
+    decode: Mutex<BTreeMap<String, Arc<Histogram>>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    pub fn dimse_latency(&self, operation: &str) -> Arc<Histogram> {
+        histogram(&self.dimse, operation)
+    }
+
+    pub fn decode_time(&self, transfer_syntax: &str) -> Arc<Histogram> {
+        histogram(&self.decode, transfer_syntax.trim_end_matches(['\0', ' ']))
+    }
+
+    // Text exposition format of Prometheus
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        let counters = [
+            (
+                "dicom_associations_accepted_total",
+                "Associations accepted",
+                &self.associations_accepted,
+            ),
+            (
+                "dicom_associations_rejected_total",
+                "Associations rejected",
+                &self.associations_rejected,
+            ),
+            (
+                "dicom_instances_stored_total",
+                "Instances written to storage",
+                &self.instances_stored,
+            ),
+            (
+                "dicom_bytes_sent_total",
+                "Bytes of P-DATA values sent",
+                &self.bytes_sent,
+            ),
+            (
+                "dicom_bytes_received_total",
+                "Bytes of P-DATA values received",
+                &self.bytes_received,
+            ),
+        ];
+        for (name, help, counter) in counters {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} counter", name);
+            let _ = writeln!(text, "{} {}", name, counter.get());
+        }
+
+        render_histograms(
+            &mut text,
+            "dicom_dimse_duration_seconds",
+            "DIMSE operations from request to final response",
+            "operation",
+            &self.dimse,
+        );
+        render_histograms(
+            &mut text,
+            "dicom_decode_duration_seconds",
+            "Frames decoded by pixel codecs",
+            "transfer_syntax",
+            &self.decode,
+        );
+        text
+    }
+}
+
+fn histogram(histograms: &Mutex<BTreeMap<String, Arc<Histogram>>>, label: &str) -> Arc<Histogram> {
+    histograms
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(label.to_string())
+        .or_default()
+        .clone()
+}
+
+fn render_histograms(
+    text: &mut String,
+    name: &str,
+    help: &str,
+    label: &str,
+    histograms: &Mutex<BTreeMap<String, Arc<Histogram>>>,
+) {
+    let _ = writeln!(text, "# HELP {} {}", name, help);
+    let _ = writeln!(text, "# TYPE {} histogram", name);
+    let histograms = histograms.lock().unwrap_or_else(PoisonError::into_inner);
+    for (value, histogram) in histograms.iter() {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        for (bound, count) in histogram.cumulative() {
+            let _ = writeln!(
+                text,
+                "{}_bucket{{{}=\"{}\",le=\"{}\"}} {}",
+                name, label, value, bound, count
+            );
+        }
+        let _ = writeln!(
+            text,
+            "{}_bucket{{{}=\"{}\",le=\"+Inf\"}} {}",
+            name,
+            label,
+            value,
+            histogram.count()
+        );
+        let _ = writeln!(
+            text,
+            "{}_sum{{{}=\"{}\"}} {}",
+            name,
+            label,
+            value,
+            histogram.sum().as_secs_f64()
+        );
+        let _ = writeln!(
+            text,
+            "{}_count{{{}=\"{}\"}} {}",
+            name,
+            label,
+            value,
+            histogram.count()
+        );
+    }
+}
+
+pub fn global() -> &'static Metrics {
+    static GLOBAL: OnceLock<Metrics> = OnceLock::new();
+    GLOBAL.get_or_init(Metrics::new)
+}
@@ -1,6 +1,33 @@
+#[cfg(feature = "async")]
+pub mod async_io;
+pub mod bits;
+#[cfg(feature = "serde")]
+pub mod bulkdata;
 pub mod dataset;
+pub mod datetime;
+pub mod dictionary;
 pub mod document;
+pub mod element;
 pub mod error;
+#[cfg(feature = "sha2")]
+pub mod fingerprint;
+pub mod iod;
+pub mod obsolete;
+pub mod padding;
+pub mod person_name;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod meta;
+pub mod metrics;
+pub mod reader;
+pub mod references;
+pub mod syntax_policy;
 pub mod tag;
+pub mod template;
+pub mod transfer_syntax;
+pub mod uid;
+pub mod update;
+pub mod writer;
+pub mod zip;
 
 pub use tag::dicom_groups;
\ No newline at end of file
@@ -0,0 +1,228 @@
+use std::fmt::Display;
+
+use super::{
+    dataset::Dataset,
+    error::{DicomError, DicomResult},
+    reader::{self, DicomFile},
+};
+
+pub const MEDIA_STORAGE_SOP_CLASS_UID: (u16, u16) = (0x0002, 0x0002);
+pub const SOP_CLASS_UID: (u16, u16) = (0x0008, 0x0016);
+
+// Attributes retired from PS3.6 that still turn up in older archives. Kept
+// sorted by tag so lookups can binary search
+pub const RETIRED_ELEMENTS: &[((u16, u16), &str)] = &[
+    ((0x0008, 0x0001), "LengthToEnd"),
+    ((0x0008, 0x0010), "RecognitionCode"),
+    ((0x0008, 0x0040), "DataSetType"),
+    ((0x0008, 0x0041), "DataSetSubtype"),
+    ((0x0008, 0x1000), "NetworkID"),
+    ((0x0008, 0x4000), "IdentifyingComments"),
+    ((0x0010, 0x1050), "InsurancePlanIdentification"),
+    ((0x0018, 0x4000), "AcquisitionComments"),
+    ((0x0020, 0x0030), "ImagePosition"),
+    ((0x0020, 0x0035), "ImageOrientation"),
+    ((0x0020, 0x0050), "Location"),
+    ((0x0020, 0x0070), "ImageGeometryType"),
+    ((0x0020, 0x1000), "SeriesInStudy"),
+    ((0x0020, 0x1001), "AcquisitionsInSeries"),
+    ((0x0020, 0x1003), "ImagesInSeries"),
+    ((0x0020, 0x3401), "ModifyingDeviceID"),
+    ((0x0020, 0x3402), "ModifiedImageID"),
+    ((0x0020, 0x3403), "ModifiedImageDate"),
+    ((0x0020, 0x3404), "ModifyingDeviceManufacturer"),
+    ((0x0020, 0x3405), "ModifiedImageTime"),
+    ((0x0020, 0x3406), "ModifiedImageDescription"),
+    ((0x0020, 0x5000), "OriginalImageIdentification"),
+    ((0x0020, 0x5002), "OriginalImageIdentificationNomenclature"),
+    ((0x0028, 0x0005), "ImageDimensions"),
+    ((0x0028, 0x0040), "ImageFormat"),
+    ((0x0028, 0x0050), "ManipulatedImage"),
+    ((0x0028, 0x0060), "CompressionCode"),
+    ((0x0028, 0x0200), "ImageLocation"),
+    ((0x0028, 0x0400), "TransformLabel"),
+    ((0x0028, 0x1080), "GrayScale"),
+    ((0x0028, 0x1100), "GrayLookupTableDescriptor"),
+    ((0x0028, 0x1200), "GrayLookupTableData"),
+    ((0x0028, 0x4000), "ImagePresentationComments"),
+    ((0x0032, 0x000A), "StudyStatusID"),
+    ((0x0032, 0x000C), "StudyPriorityID"),
+    ((0x0032, 0x0012), "StudyIDIssuer"),
+    ((0x0032, 0x0032), "StudyVerifiedDate"),
+    ((0x0032, 0x0033), "StudyVerifiedTime"),
+    ((0x0032, 0x0034), "StudyReadDate"),
+    ((0x0032, 0x0035), "StudyReadTime"),
+    ((0x0032, 0x4000), "StudyComments"),
+    ((0x4000, 0x0010), "Arbitrary"),
+    ((0x4000, 0x4000), "TextComments"),
+];
+
+pub const RETIRED_SOP_CLASSES: &[(&str, &str)] = &[
+    (
+        "1.2.840.10008.5.1.4.1.1.3",
+        "Ultrasound Multi-frame Image Storage (Retired)",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.1.5",
+        "Nuclear Medicine Image Storage (Retired)",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.1.6",
+        "Ultrasound Image Storage (Retired)",
+    ),
+    ("1.2.840.10008.5.1.4.1.1.8", "Standalone Overlay Storage"),
+    ("1.2.840.10008.5.1.4.1.1.9", "Standalone Curve Storage"),
+    (
+        "1.2.840.10008.5.1.4.1.1.10",
+        "Standalone Modality LUT Storage",
+    ),
+    ("1.2.840.10008.5.1.4.1.1.11", "Standalone VOI LUT Storage"),
+    (
+        "1.2.840.10008.5.1.4.1.1.12.3",
+        "X-Ray Angiographic Bi-Plane Image Storage",
+    ),
+    ("1.2.840.10008.5.1.4.1.1.77.1", "VL Image Storage - Trial"),
+    (
+        "1.2.840.10008.5.1.4.1.1.77.2",
+        "VL Multi-frame Image Storage - Trial",
+    ),
+    ("1.2.840.10008.5.1.4.1.1.88.1", "Text SR Storage - Trial"),
+    ("1.2.840.10008.5.1.4.1.1.88.2", "Audio SR Storage - Trial"),
+    ("1.2.840.10008.5.1.4.1.1.88.3", "Detail SR Storage - Trial"),
+    (
+        "1.2.840.10008.5.1.4.1.1.88.4",
+        "Comprehensive SR Storage - Trial",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.1.129",
+        "Standalone PET Curve Storage",
+    ),
+];
+
+pub fn retired_keyword(tag: (u16, u16)) -> Option<&'static str> {
+    if let Ok(index) = RETIRED_ELEMENTS.binary_search_by(|(retired, _)| retired.cmp(&tag)) {
+        return Some(RETIRED_ELEMENTS[index].1);
+    }
+
+    match tag {
+        // Curve data repeating groups
+        (group, _) if (0x5000..=0x50FF).contains(&group) && group % 2 == 0 => Some("CurveData"),
+        // Group lengths outside the command and file meta groups
+        (group, 0x0000) if group > 0x0002 && group % 2 == 0 => Some("GroupLength"),
+        _ => None,
+    }
+}
+
+pub fn is_retired(tag: (u16, u16)) -> bool {
+    retired_keyword(tag).is_some()
+}
+
+pub fn retired_sop_class(uid: &str) -> Option<&'static str> {
+    RETIRED_SOP_CLASSES
+        .iter()
+        .find(|(retired, _)| *retired == uid.trim_end_matches('\0').trim())
+        .map(|(_, name)| *name)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ObsoletePolicy {
+    // Fail on the first obsolete element, unless forced
+    Deny,
+    // Accept them and report what was found
+    #[default]
+    Warn,
+    Allow,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Obsolete {
+    Element((u16, u16), &'static str),
+    SopClass(String, &'static str),
+}
+
+impl Display for Obsolete {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Obsolete::Element(tag, keyword) => {
+                write!(f, "{} ({:04X},{:04X}) is retired", keyword, tag.0, tag.1)
+            }
+            Obsolete::SopClass(uid, name) => write!(f, "{} ({}) is retired", name, uid),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ObsoleteCheck {
+    pub policy: ObsoletePolicy,
+    // Lets a denying policy through, reporting instead of failing
+    pub force: bool,
+}
+
+impl ObsoleteCheck {
+    pub fn new(policy: ObsoletePolicy) -> Self {
+        ObsoleteCheck {
+            policy,
+            force: false,
+        }
+    }
+
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    // Obsolete elements and SOP classes of the dataset and its sequences,
+    // whatever the policy
+    pub fn find(&self, dataset: &Dataset) -> Vec<Obsolete> {
+        let mut found = Vec::new();
+        collect(dataset, &mut found);
+        found
+    }
+
+    // Applies the policy, returning what was let through
+    pub fn check(&self, dataset: &Dataset) -> DicomResult<Vec<Obsolete>> {
+        if self.policy == ObsoletePolicy::Allow {
+            return Ok(Vec::new());
+        }
+
+        let found = self.find(dataset);
+        if self.policy == ObsoletePolicy::Deny && !self.force {
+            if let Some(first) = found.first() {
+                return Err(DicomError::ObsoleteElement(first.to_string()));
+            }
+        }
+        Ok(found)
+    }
+
+    pub fn check_file(&self, file: &DicomFile) -> DicomResult<Vec<Obsolete>> {
+        let mut found = self.check(&file.meta)?;
+        found.extend(self.check(&file.dataset)?);
+        Ok(found)
+    }
+}
+
+// Reads a Part 10 file like reader::read_file, then applies the policy
+pub fn read_file(data: &[u8], check: &ObsoleteCheck) -> DicomResult<(DicomFile, Vec<Obsolete>)> {
+    let file = reader::read_file(data)?;
+    let found = check.check_file(&file)?;
+    Ok((file, found))
+}
+
+fn collect(dataset: &Dataset, found: &mut Vec<Obsolete>) {
+    for element in dataset {
+        let tag = element.tag();
+        if let Some(keyword) = retired_keyword(tag) {
+            found.push(Obsolete::Element(tag, keyword));
+        }
+        if tag == SOP_CLASS_UID || tag == MEDIA_STORAGE_SOP_CLASS_UID {
+            if let Some(uid) = dataset.string(tag) {
+                if let Some(name) = retired_sop_class(&uid) {
+                    found.push(Obsolete::SopClass(uid, name));
+                }
+            }
+        }
+        for item in dataset.sequence(tag) {
+            collect(&item, found);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+
+// What is removed from string values when they are read. Dates, times and
+// numbers are always read without their padding, so they still parse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trim {
+    // Values as stored, padding included
+    Keep,
+    // Trailing spaces and NULs
+    Trailing,
+    // Spaces PS3.5 6.2 calls insignificant for the VR, leading ones included
+    // for e.g. CS and DS but not for texts
+    Insignificant,
+}
+
+// Byte odd length values are padded with when they are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pad {
+    Space,
+    Null,
+}
+
+impl Pad {
+    pub fn byte(&self) -> u8 {
+        match self {
+            Pad::Space => b' ',
+            Pad::Null => 0,
+        }
+    }
+}
+
+// Trimming and padding of values, set for all VRs and overridden per VR.
+// The default trims trailing padding on read, which keeps string comparisons
+// working, and pads as PS3.5 asks on write
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValuePolicy {
+    pub trim: Trim,
+    // Trailing padding read with Trim::Keep is removed before padding again,
+    // so values do not grow on every write
+    pub normalize: bool,
+    pub trim_overrides: BTreeMap<String, Trim>,
+    pub pad_overrides: BTreeMap<String, Pad>,
+}
+
+impl Default for ValuePolicy {
+    fn default() -> Self {
+        ValuePolicy {
+            trim: Trim::Trailing,
+            normalize: true,
+            trim_overrides: BTreeMap::new(),
+            pad_overrides: BTreeMap::new(),
+        }
+    }
+}
+
+impl ValuePolicy {
+    // Values exactly as stored, e.g. for editors that show what is in the file
+    pub fn preserve() -> Self {
+        ValuePolicy {
+            trim: Trim::Keep,
+            normalize: false,
+            ..ValuePolicy::default()
+        }
+    }
+
+    // Values as PS3.5 compares them
+    pub fn insignificant() -> Self {
+        ValuePolicy {
+            trim: Trim::Insignificant,
+            ..ValuePolicy::default()
+        }
+    }
+
+    pub fn with_trim(mut self, vr: &str, trim: Trim) -> Self {
+        self.trim_overrides.insert(vr.to_string(), trim);
+        self
+    }
+
+    pub fn with_pad(mut self, vr: &str, pad: Pad) -> Self {
+        self.pad_overrides.insert(vr.to_string(), pad);
+        self
+    }
+
+    pub fn with_normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    pub fn trim_for(&self, vr: &str) -> Trim {
+        self.trim_overrides.get(vr).copied().unwrap_or(self.trim)
+    }
+
+    pub fn pad_for(&self, vr: &str) -> Pad {
+        self.pad_overrides
+            .get(vr)
+            .copied()
+            .unwrap_or_else(|| standard_pad(vr))
+    }
+
+    pub fn trim<'a>(&self, vr: &str, value: &'a str) -> &'a str {
+        match self.trim_for(vr) {
+            Trim::Keep => value,
+            Trim::Trailing => value.trim_end_matches(['\0', ' ']),
+            Trim::Insignificant if leading_insignificant(vr) => {
+                value.trim_end_matches(['\0', ' ']).trim_start_matches(' ')
+            }
+            Trim::Insignificant => value.trim_end_matches(['\0', ' ']),
+        }
+    }
+
+    // Pads an encoded value to even length
+    pub fn pad(&self, vr: &str, bytes: &mut Vec<u8>) {
+        if self.normalize && is_text(vr) {
+            while bytes.last().is_some_and(|byte| *byte == b' ' || *byte == 0) {
+                bytes.pop();
+            }
+        }
+        if bytes.len() % 2 == 1 {
+            bytes.push(self.pad_for(vr).byte());
+        }
+    }
+}
+
+// UIDs and binary data pad with NUL, text with spaces
+pub fn standard_pad(vr: &str) -> Pad {
+    match vr {
+        "UI" | "OB" | "UN" => Pad::Null,
+        _ => Pad::Space,
+    }
+}
+
+// VRs whose leading spaces carry no meaning, PS3.5 6.2
+pub fn leading_insignificant(vr: &str) -> bool {
+    matches!(
+        vr,
+        "AE" | "AS" | "CS" | "DA" | "DS" | "DT" | "IS" | "LO" | "SH" | "TM"
+    )
+}
+
+// VRs held as strings by the model, which the policy applies to
+pub fn is_text(vr: &str) -> bool {
+    matches!(
+        vr,
+        "AE" | "AS"
+            | "CS"
+            | "DS"
+            | "IS"
+            | "LO"
+            | "LT"
+            | "PN"
+            | "SH"
+            | "ST"
+            | "UC"
+            | "UI"
+            | "UR"
+            | "UT"
+    )
+}
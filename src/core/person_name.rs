@@ -0,0 +1,230 @@
+use std::fmt;
+
+// Person names of PS3.5 6.2.1: up to three component groups separated by '=',
+// alphabetic, ideographic and phonetic, each of five components separated by
+// '^', family name, given name, middle name, prefix and suffix
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComponentGroup {
+    pub family: String,
+    pub given: String,
+    pub middle: String,
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl ComponentGroup {
+    pub fn parse(text: &str) -> Self {
+        let mut components = text
+            .split('^')
+            .map(|component| component.trim().to_string());
+        ComponentGroup {
+            family: components.next().unwrap_or_default(),
+            given: components.next().unwrap_or_default(),
+            middle: components.next().unwrap_or_default(),
+            prefix: components.next().unwrap_or_default(),
+            suffix: components.next().unwrap_or_default(),
+        }
+    }
+
+    pub fn components(&self) -> [&str; 5] {
+        [
+            &self.family,
+            &self.given,
+            &self.middle,
+            &self.prefix,
+            &self.suffix,
+        ]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components()
+            .iter()
+            .all(|component| component.is_empty())
+    }
+}
+
+impl fmt::Display for ComponentGroup {
+    // Trailing empty components are left out, as PS3.5 asks
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let components = self.components();
+        let used = components
+            .iter()
+            .rposition(|component| !component.is_empty())
+            .map_or(0, |last| last + 1);
+        write!(f, "{}", components[..used].join("^"))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PersonName {
+    pub alphabetic: ComponentGroup,
+    pub ideographic: ComponentGroup,
+    pub phonetic: ComponentGroup,
+}
+
+impl PersonName {
+    pub fn parse(text: &str) -> Self {
+        let mut groups = text.trim_end_matches(['\0', ' ']).split('=');
+        PersonName {
+            alphabetic: ComponentGroup::parse(groups.next().unwrap_or_default()),
+            ideographic: ComponentGroup::parse(groups.next().unwrap_or_default()),
+            phonetic: ComponentGroup::parse(groups.next().unwrap_or_default()),
+        }
+    }
+
+    pub fn groups(&self) -> [&ComponentGroup; 3] {
+        [&self.alphabetic, &self.ideographic, &self.phonetic]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups().iter().all(|group| group.is_empty())
+    }
+
+    // The name as compared by `matches`
+    pub fn normalized(&self) -> Self {
+        PersonName::parse(&normalize(&self.to_string()))
+    }
+}
+
+impl fmt::Display for PersonName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let groups: Vec<String> = self
+            .groups()
+            .iter()
+            .map(|group| group.to_string())
+            .collect();
+        let used = groups
+            .iter()
+            .rposition(|group| !group.is_empty())
+            .map_or(0, |last| last + 1);
+        write!(f, "{}", groups[..used].join("="))
+    }
+}
+
+// Base letter of Latin letters with diacritics, after lower casing
+fn fold(c: char, out: &mut String) {
+    let base = match c {
+        'à'..='å' | 'ā' | 'ă' | 'ą' => 'a',
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => 'c',
+        'ď' | 'đ' | 'ð' => 'd',
+        'è'..='ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => 'e',
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => 'g',
+        'ĥ' | 'ħ' => 'h',
+        'ì'..='ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => 'i',
+        'ĵ' => 'j',
+        'ķ' => 'k',
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => 'l',
+        'ñ' | 'ń' | 'ņ' | 'ň' => 'n',
+        'ò'..='ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => 'o',
+        'ŕ' | 'ŗ' | 'ř' => 'r',
+        'ś' | 'ŝ' | 'ş' | 'š' => 's',
+        'ţ' | 'ť' | 'ŧ' => 't',
+        'ù'..='ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => 'u',
+        'ŵ' => 'w',
+        'ý' | 'ÿ' | 'ŷ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        'ß' => return out.push_str("ss"),
+        'æ' => return out.push_str("ae"),
+        'œ' => return out.push_str("oe"),
+        'þ' => return out.push_str("th"),
+        other => other,
+    };
+    out.push(base);
+}
+
+// Case and diacritics folded, full width forms of ideographic character sets
+// mapped to ASCII and spaces collapsed, components trimmed. Ideographs and
+// kana are kept as they are
+pub fn normalize(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.trim_end_matches(['\0', ' ']).chars() {
+        let c = match c {
+            '\u{3000}' => ' ',
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            other => other,
+        };
+        for lower in c.to_lowercase() {
+            fold(lower, &mut folded);
+        }
+    }
+
+    folded
+        .split('=')
+        .map(|group| {
+            group
+                .split('^')
+                .map(|component| component.split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect::<Vec<_>>()
+                .join("^")
+        })
+        .collect::<Vec<_>>()
+        .join("=")
+}
+
+// '*' for any run of characters, '?' for exactly one
+pub fn wildcard(pattern: &str, value: &str) -> bool {
+    fn matches(pattern: &[char], value: &[char]) -> bool {
+        match (pattern.first(), value.first()) {
+            (None, None) => true,
+            (Some('*'), _) => {
+                matches(&pattern[1..], value)
+                    || (!value.is_empty() && matches(pattern, &value[1..]))
+            }
+            (Some('?'), Some(_)) => matches(&pattern[1..], &value[1..]),
+            (Some(expected), Some(actual)) => {
+                expected == actual && matches(&pattern[1..], &value[1..])
+            }
+            _ => false,
+        }
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    matches(&pattern, &value)
+}
+
+fn component_matches(pattern: &str, value: &str) -> bool {
+    pattern.is_empty() || pattern == "*" || wildcard(pattern, value)
+}
+
+fn group_matches(pattern: &ComponentGroup, value: &ComponentGroup) -> bool {
+    pattern
+        .components()
+        .iter()
+        .zip(value.components())
+        .all(|(pattern, value)| component_matches(pattern, value))
+}
+
+// C-FIND matching of a PN key, PS3.4 C.2.2.2, after normalizing both sides.
+// Besides the whole value matching the pattern as a string, components match
+// one by one, components left out of the pattern matching anything, so that
+// "smith" finds "Smith^John". A pattern of one group is also tried against
+// the ideographic and phonetic groups of the value
+pub fn matches(pattern: &str, value: &str) -> bool {
+    let pattern = normalize(pattern);
+    if pattern.is_empty() || pattern == "*" {
+        return true;
+    }
+    let value = normalize(value);
+    if wildcard(&pattern, &value) {
+        return true;
+    }
+
+    let pattern = PersonName::parse(&pattern);
+    let value = PersonName::parse(&value);
+    let single_group = pattern.ideographic.is_empty() && pattern.phonetic.is_empty();
+    if single_group {
+        return value
+            .groups()
+            .into_iter()
+            .filter(|group| !group.is_empty())
+            .any(|group| group_matches(&pattern.alphabetic, group));
+    }
+
+    for (pattern, value) in pattern.groups().into_iter().zip(value.groups()) {
+        if !pattern.is_empty() && !group_matches(pattern, value) {
+            return false;
+        }
+    }
+    true
+}
@@ -0,0 +1,611 @@
+use std::rc::Rc;
+
+use super::{
+    dataset::Dataset,
+    dictionary,
+    element::{DicomElement, Item, LengthEncoding, ITEM_TAG},
+    error::{DicomError, DicomResult, ErrorContext, TagPath, TagPathStep, Warning, Warnings},
+    padding::{self, ValuePolicy},
+    tag::{DicomTag, VisualRepresentation},
+    transfer_syntax,
+};
+
+pub const ITEM_DELIMITATION_TAG: (u16, u16) = (0xFFFE, 0xE00D);
+pub const SEQUENCE_DELIMITATION_TAG: (u16, u16) = (0xFFFE, 0xE0DD);
+pub const TRANSFER_SYNTAX_UID: (u16, u16) = (0x0002, 0x0010);
+pub const PIXEL_DATA: (u16, u16) = (0x7FE0, 0x0010);
+
+const UNDEFINED_LENGTH: u32 = 0xFFFF_FFFF;
+
+#[derive(Debug, Clone)]
+pub struct DicomFile {
+    pub meta: Dataset,
+    pub dataset: Dataset,
+    pub transfer_syntax: String,
+}
+
+// Reads a Part 10 file, or a bare Implicit VR Little Endian dataset when
+// there is no preamble
+pub fn read_file(data: &[u8]) -> DicomResult<DicomFile> {
+    read_file_with_warnings(data).map(|(file, _)| file)
+}
+
+// As read_file, also returning what was wrong with the file but could be
+// read anyway. Error and warning offsets count from the start of the file
+pub fn read_file_with_warnings(data: &[u8]) -> DicomResult<(DicomFile, Warnings)> {
+    read_file_with_policy(data, &ValuePolicy::default())
+}
+
+// Nesting the reader accepts unless told otherwise, each level of which
+// takes stack, far beyond what real structured reports need
+pub const DEFAULT_MAX_SEQUENCE_DEPTH: usize = 128;
+
+// Bounds on what a file can make the reader allocate, for files from
+// untrusted sources. None leaves a bound off. Only the sequence depth is
+// bounded by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParserLimits {
+    pub max_file_size: Option<usize>,
+    pub max_value_length: Option<usize>,
+    pub max_sequence_depth: Option<usize>,
+}
+
+impl Default for ParserLimits {
+    fn default() -> Self {
+        ParserLimits {
+            max_file_size: None,
+            max_value_length: None,
+            max_sequence_depth: Some(DEFAULT_MAX_SEQUENCE_DEPTH),
+        }
+    }
+}
+
+impl ParserLimits {
+    pub fn new() -> Self {
+        ParserLimits::default()
+    }
+
+    pub fn with_max_file_size(mut self, size: usize) -> Self {
+        self.max_file_size = Some(size);
+        self
+    }
+
+    pub fn with_max_value_length(mut self, length: usize) -> Self {
+        self.max_value_length = Some(length);
+        self
+    }
+
+    pub fn with_max_sequence_depth(mut self, depth: usize) -> Self {
+        self.max_sequence_depth = Some(depth);
+        self
+    }
+
+    fn check_size(&self, size: usize) -> DicomResult<()> {
+        match self.max_file_size {
+            Some(max) if size > max => Err(DicomError::InvalidLength(format!(
+                "{} bytes exceed the limit of {}",
+                size, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+// As read_file_with_warnings, string values trimmed as the policy says
+pub fn read_file_with_policy(
+    data: &[u8],
+    policy: &ValuePolicy,
+) -> DicomResult<(DicomFile, Warnings)> {
+    read_file_with_limits(data, policy, &ParserLimits::default())
+}
+
+// As read_file_with_policy, failing on files beyond the limits
+pub fn read_file_with_limits(
+    data: &[u8],
+    policy: &ValuePolicy,
+    limits: &ParserLimits,
+) -> DicomResult<(DicomFile, Warnings)> {
+    limits.check_size(data.len())?;
+    if data.len() < 132 || &data[128..132] != b"DICM" {
+        let (dataset, mut warnings) = read_dataset_with_limits(
+            data,
+            transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN,
+            policy,
+            limits,
+        )?;
+        warnings.warnings.insert(
+            0,
+            Warning::new("No preamble, read as Implicit VR Little Endian"),
+        );
+        let file = DicomFile {
+            meta: Dataset::new(),
+            dataset,
+            transfer_syntax: transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN.to_string(),
+        };
+        return Ok((file, warnings));
+    }
+
+    // File meta information is always Explicit VR Little Endian
+    let mut reader = Reader::new(data, true, false);
+    reader.position = 132;
+    reader.policy = policy.clone();
+    reader.limits = *limits;
+
+    let mut meta = Dataset::new();
+    while reader.remaining() >= 4 && reader.peek_tag()?.0 == 0x0002 {
+        let start = reader.position;
+        let tag = reader.peek_tag()?;
+        if let Some(element) = reader.read_element().at_offset(start).in_element(tag)? {
+            meta.push_back(element);
+        }
+    }
+
+    let transfer_syntax = meta
+        .string(TRANSFER_SYNTAX_UID)
+        .ok_or_else(|| DicomError::InvalidFile("Missing transfer syntax UID".to_string()))?;
+    let syntax = syntax_of(&transfer_syntax, &mut reader.warnings)?;
+    reader.explicit_vr = syntax.explicit_vr;
+    reader.big_endian = syntax.big_endian;
+    let dataset = reader.read_dataset(None)?;
+
+    let file = DicomFile {
+        meta,
+        dataset,
+        transfer_syntax,
+    };
+    Ok((file, reader.warnings))
+}
+
+pub fn read_dataset(data: &[u8], transfer_syntax: &str) -> DicomResult<Dataset> {
+    read_dataset_with_warnings(data, transfer_syntax).map(|(dataset, _)| dataset)
+}
+
+pub fn read_dataset_with_warnings(
+    data: &[u8],
+    transfer_syntax: &str,
+) -> DicomResult<(Dataset, Warnings)> {
+    read_dataset_with_policy(data, transfer_syntax, &ValuePolicy::default())
+}
+
+pub fn read_dataset_with_policy(
+    data: &[u8],
+    transfer_syntax: &str,
+    policy: &ValuePolicy,
+) -> DicomResult<(Dataset, Warnings)> {
+    read_dataset_with_limits(data, transfer_syntax, policy, &ParserLimits::default())
+}
+
+pub fn read_dataset_with_limits(
+    data: &[u8],
+    transfer_syntax: &str,
+    policy: &ValuePolicy,
+    limits: &ParserLimits,
+) -> DicomResult<(Dataset, Warnings)> {
+    limits.check_size(data.len())?;
+    let mut warnings = Warnings::new();
+    let syntax = syntax_of(transfer_syntax, &mut warnings)?;
+
+    let mut reader = Reader::new(data, syntax.explicit_vr, syntax.big_endian);
+    reader.warnings = warnings;
+    reader.policy = policy.clone();
+    reader.limits = *limits;
+    let dataset = reader.read_dataset(None)?;
+    Ok((dataset, reader.warnings))
+}
+
+fn syntax_of(
+    transfer_syntax: &str,
+    warnings: &mut Warnings,
+) -> DicomResult<transfer_syntax::TransferSyntax> {
+    let syntax = match transfer_syntax::lookup(transfer_syntax) {
+        Some(syntax) => *syntax,
+        None => {
+            warnings.push(&format!(
+                "Unknown transfer syntax {}, read as Explicit VR Little Endian",
+                transfer_syntax
+            ));
+            // Unknown syntaxes are compressed ones we do not know yet, and
+            // those are all Explicit VR Little Endian outside the pixel data
+            transfer_syntax::TransferSyntax {
+                uid: "",
+                name: "",
+                explicit_vr: true,
+                big_endian: false,
+                encapsulated: true,
+                lossy: false,
+            }
+        }
+    };
+
+    if syntax.uid == transfer_syntax::DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN {
+        return Err(DicomError::UnsupportedTransferSyntax(
+            syntax.uid.to_string(),
+        ));
+    }
+    Ok(syntax)
+}
+
+// Splits encapsulated pixel data as kept by the reader into its fragments,
+// the first one being the basic offset table
+pub fn fragments(data: &[u8]) -> DicomResult<Vec<&[u8]>> {
+    let mut reader = Reader::new(data, false, false);
+    let mut fragments = Vec::new();
+
+    while reader.remaining() >= 8 {
+        let tag = reader.read_tag()?;
+        let length = reader.read_u32()? as usize;
+        if tag == SEQUENCE_DELIMITATION_TAG {
+            break;
+        }
+        if tag != ITEM_TAG {
+            return Err(DicomError::InvalidTag(format!(
+                "({:04X},{:04X}) in encapsulated pixel data",
+                tag.0, tag.1
+            )));
+        }
+        fragments.push(reader.take(length)?);
+    }
+
+    Ok(fragments)
+}
+
+pub fn decode_value(vr: &str, bytes: &[u8], big_endian: bool) -> VisualRepresentation {
+    decode_value_with_policy(vr, bytes, big_endian, &ValuePolicy::default())
+}
+
+pub fn decode_value_with_policy(
+    vr: &str,
+    bytes: &[u8],
+    big_endian: bool,
+    policy: &ValuePolicy,
+) -> VisualRepresentation {
+    if bytes.is_empty() {
+        return VisualRepresentation::empty(vr);
+    }
+
+    macro_rules! numbers {
+        ($type:ty) => {
+            bytes
+                .chunks_exact(std::mem::size_of::<$type>())
+                .map(|chunk| {
+                    let chunk = chunk.try_into().unwrap();
+                    if big_endian {
+                        <$type>::from_be_bytes(chunk)
+                    } else {
+                        <$type>::from_le_bytes(chunk)
+                    }
+                })
+                .collect::<Vec<$type>>()
+        };
+    }
+
+    match vr {
+        "OB" => VisualRepresentation::OB(bytes.to_vec()),
+        "UN" => VisualRepresentation::UN(bytes.to_vec()),
+        "OW" => VisualRepresentation::OW(numbers!(u16)),
+        "OF" => VisualRepresentation::OF(numbers!(f32)),
+        "OD" => VisualRepresentation::OD(numbers!(f64)),
+        "OL" => VisualRepresentation::OL(numbers!(u32)),
+        "OV" => VisualRepresentation::OV(numbers!(i64)),
+        // Several unsigned or signed shorts are kept as words, which LUT
+        // descriptors and data need. The writer gives them their VR back
+        "US" | "SS" if bytes.len() > 2 => VisualRepresentation::OW(numbers!(u16)),
+        // The model holds a single value for binary numbers
+        "FL" => first(vr, numbers!(f32), VisualRepresentation::FL),
+        "FD" => first(vr, numbers!(f64), VisualRepresentation::FD),
+        "SL" => first(vr, numbers!(i32), VisualRepresentation::SL),
+        "SS" => first(vr, numbers!(i16), VisualRepresentation::SS),
+        "SV" => first(vr, numbers!(i64), VisualRepresentation::SV),
+        "UL" => first(vr, numbers!(u32), VisualRepresentation::UL),
+        "US" => first(vr, numbers!(u16), VisualRepresentation::US),
+        "AT" => {
+            let tags = numbers!(u16)
+                .chunks_exact(2)
+                .map(|pair| format!("{:04X}{:04X}", pair[0], pair[1]))
+                .collect::<Vec<_>>();
+            VisualRepresentation::AT(tags.join("\\").into())
+        }
+        _ => {
+            let text = String::from_utf8_lossy(bytes);
+            let text = if padding::is_text(vr) {
+                policy.trim(vr, &text)
+            } else {
+                text.trim_end_matches(['\0', ' ']).trim_start_matches(' ')
+            };
+            if text.is_empty() {
+                return VisualRepresentation::empty(vr);
+            }
+
+            // Dates and times the model cannot hold are kept as raw bytes
+            VisualRepresentation::try_from_string(vr, text)
+                .unwrap_or_else(|_| VisualRepresentation::UN(bytes.to_vec()))
+        }
+    }
+}
+
+fn first<T: Copy>(
+    vr: &str,
+    values: Vec<T>,
+    variant: fn(T) -> VisualRepresentation,
+) -> VisualRepresentation {
+    values
+        .first()
+        .map(|value| variant(*value))
+        .unwrap_or_else(|| VisualRepresentation::new(vr))
+}
+
+pub fn has_long_length(vr: &str) -> bool {
+    matches!(
+        vr,
+        "OB" | "OD" | "OF" | "OL" | "OV" | "OW" | "SQ" | "SV" | "UC" | "UN" | "UR" | "UT" | "UV"
+    )
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+    explicit_vr: bool,
+    big_endian: bool,
+    // Sequences and items being read, for warnings
+    path: TagPath,
+    warnings: Warnings,
+    policy: ValuePolicy,
+    limits: ParserLimits,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], explicit_vr: bool, big_endian: bool) -> Self {
+        Reader {
+            data,
+            position: 0,
+            explicit_vr,
+            big_endian,
+            path: TagPath::default(),
+            warnings: Warnings::new(),
+            policy: ValuePolicy::default(),
+            limits: ParserLimits::default(),
+        }
+    }
+
+    fn warn(&mut self, tag: (u16, u16), offset: usize, message: &str) {
+        let mut path = self.path.clone();
+        path.steps.push(TagPathStep::Tag(tag));
+        self.warnings.push_at(message, Some(path), Some(offset));
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.position)
+    }
+
+    fn take(&mut self, length: usize) -> DicomResult<&'a [u8]> {
+        if length > self.remaining() {
+            return Err(DicomError::InvalidLength(format!(
+                "{} bytes requested at offset {}, {} left",
+                length,
+                self.position,
+                self.remaining()
+            )));
+        }
+
+        let bytes = &self.data[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn read_u16(&mut self) -> DicomResult<u16> {
+        let bytes = self.take(2)?.try_into().unwrap();
+        Ok(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn read_u32(&mut self) -> DicomResult<u32> {
+        let bytes = self.take(4)?.try_into().unwrap();
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn read_tag(&mut self) -> DicomResult<(u16, u16)> {
+        Ok((self.read_u16()?, self.read_u16()?))
+    }
+
+    fn peek_tag(&mut self) -> DicomResult<(u16, u16)> {
+        let position = self.position;
+        let tag = self.read_tag();
+        self.position = position;
+        tag
+    }
+
+    // Reads until the end of the data, or the item delimiter when `end` is None
+    // inside an undefined length item
+    fn read_dataset(&mut self, end: Option<usize>) -> DicomResult<Dataset> {
+        let end = end.unwrap_or(self.data.len());
+        let mut dataset = Dataset::new();
+
+        while self.position < end && self.remaining() >= 4 {
+            let start = self.position;
+            let tag = self.peek_tag()?;
+            match self.read_element().at_offset(start).in_element(tag)? {
+                Some(element) => dataset.push_back(element),
+                None => break,
+            }
+        }
+
+        Ok(dataset)
+    }
+
+    // None marks an item or sequence delimiter
+    fn read_element(&mut self) -> DicomResult<Option<Rc<dyn DicomTag>>> {
+        let start = self.position;
+        let tag = self.read_tag()?;
+
+        if tag.0 == 0xFFFE {
+            self.read_u32()?;
+            return match tag {
+                ITEM_DELIMITATION_TAG | SEQUENCE_DELIMITATION_TAG => Ok(None),
+                _ => Err(DicomError::InvalidTag(format!(
+                    "Unexpected ({:04X},{:04X})",
+                    tag.0, tag.1
+                ))),
+            };
+        }
+
+        let vr = if self.explicit_vr {
+            let code = self.take(2)?;
+            String::from_utf8_lossy(code).into_owned()
+        } else {
+            dictionary::vr_of(tag).to_string()
+        };
+
+        let length = if self.explicit_vr && has_long_length(&vr) {
+            self.take(2)?;
+            self.read_u32()?
+        } else if self.explicit_vr {
+            self.read_u16()? as u32
+        } else {
+            self.read_u32()?
+        };
+
+        if let Some(max) = self.limits.max_value_length {
+            if length != UNDEFINED_LENGTH && length as usize > max {
+                return Err(DicomError::InvalidLength(format!(
+                    "Value of {} bytes exceeds the limit of {}",
+                    length, max
+                )));
+            }
+        }
+        if length != UNDEFINED_LENGTH && length % 2 == 1 {
+            self.warn(tag, start, &format!("Odd value length {}", length));
+        }
+
+        let element = if vr == "SQ" || (vr == "UN" && length == UNDEFINED_LENGTH) {
+            let depth = self
+                .path
+                .steps
+                .iter()
+                .filter(|step| matches!(step, TagPathStep::Tag(_)))
+                .count();
+            if self
+                .limits
+                .max_sequence_depth
+                .is_some_and(|max| depth >= max)
+            {
+                return Err(DicomError::InvalidDataset(format!(
+                    "Sequences nested deeper than {}",
+                    depth
+                )));
+            }
+            self.path.steps.push(TagPathStep::Tag(tag));
+            let items = self.read_sequence(length, vr == "UN");
+            self.path.steps.pop();
+            let encoding = if length == UNDEFINED_LENGTH {
+                LengthEncoding::Undefined
+            } else {
+                LengthEncoding::Defined
+            };
+            DicomElement::sequence_of(tag, items?).with_length_encoding(encoding)
+        } else if length == UNDEFINED_LENGTH {
+            // Encapsulated pixel data, kept whole so codecs can split the fragments
+            let start = self.position;
+            let mut end = start;
+            loop {
+                let item = self.read_tag()?;
+                let item_length = self.read_u32()? as usize;
+                if item == SEQUENCE_DELIMITATION_TAG {
+                    break;
+                }
+                self.take(item_length)?;
+                end = self.position;
+            }
+            DicomElement::new(
+                tag,
+                VisualRepresentation::OB(self.data[start..end].to_vec()),
+            )
+        } else {
+            let bytes = self.take(length as usize)?;
+            let value = decode_value_with_policy(&vr, bytes, self.big_endian, &self.policy);
+            if matches!(value, VisualRepresentation::UN(_)) && vr != "UN" {
+                self.warn(tag, start, &format!("{} value kept as raw bytes", vr));
+            }
+            DicomElement::new(tag, value)
+        };
+
+        Ok(Some(Rc::new(element)))
+    }
+
+    fn read_sequence(&mut self, length: u32, implicit: bool) -> DicomResult<Vec<Item>> {
+        let explicit_vr = self.explicit_vr;
+        let big_endian = self.big_endian;
+
+        // UN of undefined length is always Implicit VR Little Endian inside
+        if implicit {
+            self.explicit_vr = false;
+            self.big_endian = false;
+        }
+
+        let end = if length == UNDEFINED_LENGTH {
+            None
+        } else {
+            Some(self.end_of(length)?)
+        };
+
+        let mut items = Vec::new();
+        while end.is_none_or(|end| self.position < end) && self.remaining() >= 8 {
+            let tag = self.read_tag()?;
+            let item_length = self.read_u32()?;
+
+            match tag {
+                SEQUENCE_DELIMITATION_TAG => break,
+                ITEM_TAG if item_length == UNDEFINED_LENGTH => {
+                    let item = self.read_item(items.len(), None)?;
+                    items.push(Item::new(item).with_length_encoding(LengthEncoding::Undefined));
+                }
+                ITEM_TAG => {
+                    let item_end = self.end_of(item_length)?;
+                    if item_end > self.data.len() {
+                        return Err(DicomError::InvalidLength(format!(
+                            "Item of {} bytes at offset {} runs past the end",
+                            item_length, self.position
+                        )));
+                    }
+                    let item = self.read_item(items.len(), Some(item_end))?;
+                    items.push(Item::new(item));
+                    self.position = item_end;
+                }
+                _ => {
+                    return Err(DicomError::InvalidTag(format!(
+                        "({:04X},{:04X}) inside a sequence",
+                        tag.0, tag.1
+                    )))
+                }
+            }
+        }
+
+        self.explicit_vr = explicit_vr;
+        self.big_endian = big_endian;
+        Ok(items)
+    }
+
+    // Offset a value of the length starting here ends at. Lengths near 4 GiB
+    // do not fit the address space of 32 bit targets such as wasm32
+    fn end_of(&self, length: u32) -> DicomResult<usize> {
+        self.position.checked_add(length as usize).ok_or_else(|| {
+            DicomError::InvalidLength(format!(
+                "{} bytes at offset {} run past the end",
+                length, self.position
+            ))
+        })
+    }
+
+    fn read_item(&mut self, index: usize, end: Option<usize>) -> DicomResult<Dataset> {
+        self.path.steps.push(TagPathStep::Item(index));
+        let item = self.read_dataset(end).in_item(index);
+        self.path.steps.pop();
+        item
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use super::{
+    dataset::Dataset,
+    error::{TagPath, TagPathStep},
+};
+
+pub const SOP_CLASS_UID: (u16, u16) = (0x0008, 0x0016);
+pub const SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x0018);
+pub const MODALITY: (u16, u16) = (0x0008, 0x0060);
+pub const REFERENCED_STUDY_SEQUENCE: (u16, u16) = (0x0008, 0x1110);
+pub const REFERENCED_PERFORMED_PROCEDURE_STEP_SEQUENCE: (u16, u16) = (0x0008, 0x1111);
+pub const REFERENCED_PATIENT_SEQUENCE: (u16, u16) = (0x0008, 0x1120);
+pub const REFERENCED_SOP_CLASS_UID: (u16, u16) = (0x0008, 0x1150);
+pub const REFERENCED_SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x1155);
+pub const REFERENCED_FRAME_NUMBER: (u16, u16) = (0x0008, 0x1160);
+pub const STUDY_INSTANCE_UID: (u16, u16) = (0x0020, 0x000D);
+pub const SERIES_INSTANCE_UID: (u16, u16) = (0x0020, 0x000E);
+
+// Sequences whose Referenced SOP Instance UID names a study, procedure step
+// or patient rather than a composite instance
+pub const NON_INSTANCE_SEQUENCES: [(u16, u16); 3] = [
+    REFERENCED_STUDY_SEQUENCE,
+    REFERENCED_PERFORMED_PROCEDURE_STEP_SEQUENCE,
+    REFERENCED_PATIENT_SEQUENCE,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstanceNode {
+    pub sop_instance_uid: String,
+    pub sop_class_uid: String,
+    pub modality: String,
+    pub study_instance_uid: String,
+    pub series_instance_uid: String,
+}
+
+impl InstanceNode {
+    pub fn from_dataset(dataset: &Dataset) -> Option<Self> {
+        let text = |tag: (u16, u16)| dataset.string(tag).unwrap_or_default().trim().to_string();
+        let sop_instance_uid = text(SOP_INSTANCE_UID);
+        if sop_instance_uid.is_empty() {
+            return None;
+        }
+        Some(InstanceNode {
+            sop_instance_uid,
+            sop_class_uid: text(SOP_CLASS_UID),
+            modality: text(MODALITY),
+            study_instance_uid: text(STUDY_INSTANCE_UID),
+            series_instance_uid: text(SERIES_INSTANCE_UID),
+        })
+    }
+}
+
+// One Referenced SOP Instance UID found in an instance, wherever it was
+// nested: SR content items, KOS evidence, RT Structure Set contour images,
+// presentation state image references...
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub source: String,
+    pub target: String,
+    // Referenced SOP Class UID given next to the instance, if any
+    pub target_class: Option<String>,
+    pub frames: Vec<u32>,
+    // Item holding the reference, e.g. (3006,0010)[0]/.../(3006,0016)[4]
+    pub path: TagPath,
+}
+
+impl Reference {
+    // Innermost sequence holding the reference
+    pub fn sequence(&self) -> Option<(u16, u16)> {
+        self.path.tag()
+    }
+}
+
+// The instances of a set of datasets linked by their SOP references, for
+// checking a study is complete before export and for navigating from a
+// report or structure set to what it was made from
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceGraph {
+    pub nodes: BTreeMap<String, InstanceNode>,
+    pub references: Vec<Reference>,
+}
+
+impl ReferenceGraph {
+    pub fn new() -> Self {
+        ReferenceGraph::default()
+    }
+
+    pub fn from_datasets<'a>(datasets: impl IntoIterator<Item = &'a Dataset>) -> Self {
+        let mut graph = ReferenceGraph::new();
+        for dataset in datasets {
+            graph.add(dataset);
+        }
+        graph
+    }
+
+    // Adds an instance and the references it makes. Datasets without a SOP
+    // Instance UID cannot be referenced and are ignored, as is an instance
+    // added twice
+    pub fn add(&mut self, dataset: &Dataset) -> bool {
+        let Some(node) = InstanceNode::from_dataset(dataset) else {
+            return false;
+        };
+        if self.nodes.contains_key(&node.sop_instance_uid) {
+            return false;
+        }
+        let mut found = Vec::new();
+        collect(
+            dataset,
+            &node.sop_instance_uid,
+            &mut TagPath::default(),
+            &mut found,
+        );
+        self.references.extend(found);
+        self.nodes.insert(node.sop_instance_uid.clone(), node);
+        true
+    }
+
+    pub fn node(&self, sop_instance_uid: &str) -> Option<&InstanceNode> {
+        self.nodes.get(sop_instance_uid)
+    }
+
+    pub fn references_from<'a>(
+        &'a self,
+        sop_instance_uid: &'a str,
+    ) -> impl Iterator<Item = &'a Reference> {
+        self.references
+            .iter()
+            .filter(move |reference| reference.source == sop_instance_uid)
+    }
+
+    pub fn references_to<'a>(
+        &'a self,
+        sop_instance_uid: &'a str,
+    ) -> impl Iterator<Item = &'a Reference> {
+        self.references
+            .iter()
+            .filter(move |reference| reference.target == sop_instance_uid)
+    }
+
+    // Instances referenced by this one that are part of the graph
+    pub fn targets(&self, sop_instance_uid: &str) -> Vec<&InstanceNode> {
+        let uids: BTreeSet<&str> = self
+            .references_from(sop_instance_uid)
+            .map(|reference| reference.target.as_str())
+            .collect();
+        uids.into_iter().filter_map(|uid| self.node(uid)).collect()
+    }
+
+    // Instances of the graph referencing this one
+    pub fn sources(&self, sop_instance_uid: &str) -> Vec<&InstanceNode> {
+        let uids: BTreeSet<&str> = self
+            .references_to(sop_instance_uid)
+            .map(|reference| reference.source.as_str())
+            .collect();
+        uids.into_iter().filter_map(|uid| self.node(uid)).collect()
+    }
+
+    // Every instance reachable from this one through any number of
+    // references, breadth first, the instance itself excluded
+    pub fn reachable(&self, sop_instance_uid: &str) -> Vec<&InstanceNode> {
+        let mut seen = BTreeSet::from([sop_instance_uid]);
+        let mut queue = VecDeque::from([sop_instance_uid]);
+        let mut reached = Vec::new();
+        while let Some(uid) = queue.pop_front() {
+            for node in self.targets(uid) {
+                if seen.insert(&node.sop_instance_uid) {
+                    queue.push_back(&node.sop_instance_uid);
+                    reached.push(node);
+                }
+            }
+        }
+        reached
+    }
+
+    // References to instances the graph does not hold
+    pub fn dangling(&self) -> Vec<&Reference> {
+        self.references
+            .iter()
+            .filter(|reference| !self.nodes.contains_key(&reference.target))
+            .collect()
+    }
+
+    // References whose Referenced SOP Class UID is not the class of the
+    // instance they resolve to
+    pub fn mismatched(&self) -> Vec<&Reference> {
+        self.references
+            .iter()
+            .filter(
+                |reference| match (&reference.target_class, self.node(&reference.target)) {
+                    (Some(class), Some(node)) => *class != node.sop_class_uid,
+                    _ => false,
+                },
+            )
+            .collect()
+    }
+
+    // Instances no other instance of the graph references, the reports,
+    // structure sets and presentation states to start navigating from
+    pub fn roots(&self) -> Vec<&InstanceNode> {
+        let referenced: BTreeSet<&str> = self
+            .references
+            .iter()
+            .filter(|reference| reference.source != reference.target)
+            .map(|reference| reference.target.as_str())
+            .collect();
+        self.nodes
+            .values()
+            .filter(|node| !referenced.contains(node.sop_instance_uid.as_str()))
+            .collect()
+    }
+}
+
+fn collect(dataset: &Dataset, source: &str, path: &mut TagPath, found: &mut Vec<Reference>) {
+    if !path.steps.is_empty() {
+        if let Some(target) = dataset.string(REFERENCED_SOP_INSTANCE_UID) {
+            let target = target.trim();
+            if !target.is_empty()
+                && !path
+                    .tag()
+                    .is_some_and(|tag| NON_INSTANCE_SEQUENCES.contains(&tag))
+            {
+                found.push(Reference {
+                    source: source.to_string(),
+                    target: target.to_string(),
+                    target_class: dataset
+                        .string(REFERENCED_SOP_CLASS_UID)
+                        .map(|class| class.trim().to_string())
+                        .filter(|class| !class.is_empty()),
+                    frames: dataset
+                        .string(REFERENCED_FRAME_NUMBER)
+                        .unwrap_or_default()
+                        .split('\\')
+                        .filter_map(|frame| frame.trim().parse().ok())
+                        .collect(),
+                    path: path.clone(),
+                });
+            }
+        }
+    }
+
+    let sequences: Vec<(u16, u16)> = dataset
+        .into_iter()
+        .filter(|element| element.vr().code() == "SQ")
+        .map(|element| element.tag())
+        .collect();
+    for tag in sequences {
+        for (index, item) in dataset.sequence(tag).iter().enumerate() {
+            path.steps.push(TagPathStep::Tag(tag));
+            path.steps.push(TagPathStep::Item(index));
+            collect(item, source, path, found);
+            path.steps.truncate(path.steps.len() - 2);
+        }
+    }
+}
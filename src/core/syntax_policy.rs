@@ -0,0 +1,172 @@
+use super::transfer_syntax;
+
+// Preferred transfer syntaxes of a SOP class and the ones it must never use
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxPreference {
+    // In order of preference, before any other syntax both sides support
+    pub preferred: Vec<String>,
+    pub excluded: Vec<String>,
+    pub allow_lossy: bool,
+}
+
+impl Default for SyntaxPreference {
+    fn default() -> Self {
+        SyntaxPreference {
+            preferred: Vec::new(),
+            excluded: Vec::new(),
+            allow_lossy: true,
+        }
+    }
+}
+
+impl SyntaxPreference {
+    pub fn new() -> Self {
+        SyntaxPreference::default()
+    }
+
+    // Lossless compression first, then the uncompressed syntaxes, and never
+    // a lossy one
+    pub fn lossless_compressed() -> Self {
+        SyntaxPreference::new()
+            .with_preferred(&[
+                transfer_syntax::JPEG_LS_LOSSLESS,
+                transfer_syntax::JPEG_2000_LOSSLESS,
+                transfer_syntax::JPEG_LOSSLESS_SV1,
+                transfer_syntax::RLE_LOSSLESS,
+                transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN,
+                transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN,
+            ])
+            .with_allow_lossy(false)
+    }
+
+    pub fn with_preferred(mut self, transfer_syntaxes: &[&str]) -> Self {
+        self.preferred = transfer_syntaxes.iter().map(|ts| ts.to_string()).collect();
+        self
+    }
+
+    pub fn with_excluded(mut self, transfer_syntax: &str) -> Self {
+        self.excluded.push(transfer_syntax.to_string());
+        self
+    }
+
+    pub fn with_allow_lossy(mut self, allow_lossy: bool) -> Self {
+        self.allow_lossy = allow_lossy;
+        self
+    }
+
+    // Unknown syntaxes count as lossy, nothing vouches for them
+    pub fn allows(&self, transfer_syntax: &str) -> bool {
+        let uid = transfer_syntax.trim_end_matches(['\0', ' ']);
+        if self.excluded.iter().any(|excluded| excluded == uid) {
+            return false;
+        }
+        self.allow_lossy || transfer_syntax::lookup(uid).is_some_and(|ts| !ts.lossy)
+    }
+
+    // The candidates this preference allows, preferred ones first in their
+    // order and the others in the order given
+    pub fn rank<'a>(&self, candidates: &[&'a str]) -> Vec<&'a str> {
+        let mut ranked: Vec<&str> = self
+            .preferred
+            .iter()
+            .filter_map(|preferred| candidates.iter().copied().find(|ts| ts == preferred))
+            .collect();
+        for candidate in candidates {
+            if !ranked.contains(candidate) {
+                ranked.push(candidate);
+            }
+        }
+        ranked.retain(|ts| self.allows(ts));
+        ranked
+    }
+}
+
+// Transfer syntaxes by SOP class for negotiation and transcoding. Rules
+// cover the SOP class UID they are given for and the ones below it, so
+// 1.2.840.10008.5.1.4.1.1.1.2 holds for both kinds of digital mammography;
+// the longest matching rule wins
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransferSyntaxPolicy {
+    pub default: SyntaxPreference,
+    pub sop_classes: Vec<(String, SyntaxPreference)>,
+}
+
+impl TransferSyntaxPolicy {
+    pub fn new() -> Self {
+        TransferSyntaxPolicy::default()
+    }
+
+    pub fn with_default(mut self, preference: SyntaxPreference) -> Self {
+        self.default = preference;
+        self
+    }
+
+    pub fn with_sop_class(mut self, sop_class_uid: &str, preference: SyntaxPreference) -> Self {
+        self.sop_classes
+            .retain(|(uid, _)| uid != sop_class_uid.trim());
+        self.sop_classes
+            .push((sop_class_uid.trim().to_string(), preference));
+        self
+    }
+
+    pub fn preference(&self, sop_class_uid: &str) -> &SyntaxPreference {
+        let sop_class_uid = sop_class_uid.trim_end_matches(['\0', ' ']);
+        self.sop_classes
+            .iter()
+            .filter(|(uid, _)| {
+                sop_class_uid == uid
+                    || sop_class_uid
+                        .strip_prefix(uid.as_str())
+                        .is_some_and(|rest| rest.starts_with('.'))
+            })
+            .max_by_key(|(uid, _)| uid.len())
+            .map_or(&self.default, |(_, preference)| preference)
+    }
+
+    pub fn allows(&self, sop_class_uid: &str, transfer_syntax: &str) -> bool {
+        self.preference(sop_class_uid).allows(transfer_syntax)
+    }
+
+    // What an SCU proposes for the SOP class out of the syntaxes it can send
+    pub fn proposal<'a>(&self, sop_class_uid: &str, supported: &[&'a str]) -> Vec<&'a str> {
+        self.preference(sop_class_uid).rank(supported)
+    }
+
+    // What an SCP accepts out of a proposal, our preference deciding between
+    // the syntaxes we support and the order of the proposal after that
+    pub fn choose(
+        &self,
+        sop_class_uid: &str,
+        proposed: &[String],
+        supported: &[&str],
+    ) -> Option<String> {
+        let candidates: Vec<&str> = proposed
+            .iter()
+            .map(String::as_str)
+            .filter(|ts| supported.contains(ts))
+            .collect();
+        self.preference(sop_class_uid)
+            .rank(&candidates)
+            .first()
+            .map(|ts| ts.to_string())
+    }
+
+    // Syntax to send an instance in over a context that accepted
+    // `available`: its own when allowed, the best allowed one otherwise,
+    // which the instance is then transcoded to
+    pub fn target(&self, sop_class_uid: &str, current: &str, available: &[&str]) -> Option<String> {
+        let current = current.trim_end_matches(['\0', ' ']);
+        let preference = self.preference(sop_class_uid);
+        if available.contains(&current) && preference.allows(current) {
+            return Some(current.to_string());
+        }
+        preference.rank(available).first().map(|ts| ts.to_string())
+    }
+}
+
+// Whether moving pixel data from one syntax to the other loses information
+pub fn is_lossy_conversion(from: &str, to: &str) -> bool {
+    let from = transfer_syntax::lookup(from);
+    let to = transfer_syntax::lookup(to);
+    from.map(|ts| ts.uid) != to.map(|ts| ts.uid) && to.is_none_or(|ts| ts.lossy)
+}
@@ -1,4 +1,3 @@
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use std::{
     borrow::Cow,
     cell::UnsafeCell,
@@ -6,6 +5,13 @@ use std::{
     rc::Rc,
 };
 
+use super::{
+    dataset::Dataset,
+    datetime::{DateValue, DicomDateTime, TimeValue},
+    element::LengthEncoding,
+    error::{DicomError, DicomResult},
+};
+
 pub trait DicomTag: Debug + Display {
     fn name(&self) -> String;
     fn tag(&self) -> (u16, u16);
@@ -14,6 +20,21 @@ pub trait DicomTag: Debug + Display {
     fn element(&self) -> Option<u16>;
     fn is_deprecated(&self) -> bool;
     fn multiplicity(&self) -> &str;
+
+    // Only sequence items carry a nested dataset
+    fn dataset(&self) -> Option<&Dataset> {
+        None
+    }
+
+    // Bytes held in memory, the value and everything nested in it
+    fn memory_usage(&self) -> usize {
+        std::mem::size_of::<VisualRepresentation>() + self.vr().heap_size()
+    }
+
+    // Encoding of sequences and items, kept from the file they were read from
+    fn length_encoding(&self) -> LengthEncoding {
+        LengthEncoding::Defined
+    }
 }
 
 pub enum DicomValue<'a> {
@@ -28,9 +49,9 @@ pub enum VisualRepresentation {
     AS(Cow<'static, str>),     // Age String
     AT(Cow<'static, str>),     // Attribute Tag
     CS(Cow<'static, str>),     // Code String
-    DA(NaiveDate),             // Date
+    DA(DateValue),             // Date
     DS(Cow<'static, str>),     // Decimal String
-    DT(NaiveDateTime),         // DateTime
+    DT(DicomDateTime),         // DateTime
     FL(f32),                   // Floating Point Single
     FD(f64),                   // Floating Point Double
     IS(Cow<'static, str>),     // Integer String
@@ -49,7 +70,7 @@ pub enum VisualRepresentation {
     SS(i16),                   // Signed Short
     ST(Cow<'static, str>),     // Short Text
     SV(i64),                   // Signed Very Long
-    TM(NaiveTime),             // Time
+    TM(TimeValue),             // Time
     UC(Cow<'static, str>),     // Unlimited Characters
     UI(Cow<'static, str>),     // Unique Identifier (UID)
     UL(u32),                   // Unsigned Long
@@ -57,6 +78,7 @@ pub enum VisualRepresentation {
     UR(Cow<'static, str>),     // Universal Resource Identifier
     US(u16),                   // Unsigned Short
     UT(Cow<'static, str>),     // Unlimited Text
+    Empty(&'static str),       // Zero length value of the VR, e.g. Type 2
 }
 
 impl !Send for VisualRepresentation {}
@@ -69,11 +91,9 @@ impl VisualRepresentation {
             "AS" => VisualRepresentation::AS(value.to_string().into()),
             "AT" => VisualRepresentation::AT(value.to_string().into()),
             "CS" => VisualRepresentation::CS(value.to_string().into()),
-            "DA" => VisualRepresentation::DA(NaiveDate::parse_from_str(value, "%Y%m%d").unwrap()),
+            "DA" => VisualRepresentation::DA(DateValue::parse(value).unwrap()),
             "DS" => VisualRepresentation::DS(value.to_string().into()),
-            "DT" => VisualRepresentation::DT(
-                NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M%S%.6f").unwrap(),
-            ),
+            "DT" => VisualRepresentation::DT(DicomDateTime::parse(value).unwrap()),
             "FL" => VisualRepresentation::FL(value.parse().unwrap()),
             "FD" => VisualRepresentation::FD(value.parse().unwrap()),
             "IS" => VisualRepresentation::IS(value.to_string().into()),
@@ -117,9 +137,7 @@ impl VisualRepresentation {
             "SS" => VisualRepresentation::SS(value.parse().unwrap()),
             "ST" => VisualRepresentation::ST(value.to_string().into()),
             "SV" => VisualRepresentation::SV(value.parse().unwrap()),
-            "TM" => {
-                VisualRepresentation::TM(NaiveTime::parse_from_str(value, "%H%M%S%.6f").unwrap())
-            }
+            "TM" => VisualRepresentation::TM(TimeValue::parse(value).unwrap()),
             "UC" => VisualRepresentation::UC(value.to_string().into()),
             "UI" => VisualRepresentation::UI(value.to_string().into()),
             "UL" => VisualRepresentation::UL(value.parse().unwrap()),
@@ -131,15 +149,50 @@ impl VisualRepresentation {
         }
     }
 
+    // Same as from_string, but refuses values that would not parse instead of panicking
+    pub fn try_from_string(vr: &str, value: &str) -> DicomResult<Self> {
+        fn all<T: std::str::FromStr>(value: &str) -> bool {
+            value.split_whitespace().all(|v| v.parse::<T>().is_ok())
+        }
+
+        let valid = match vr {
+            "DA" => DateValue::parse(value).is_ok(),
+            "DT" => DicomDateTime::parse(value).is_ok(),
+            "TM" => TimeValue::parse(value).is_ok(),
+            "FL" => value.parse::<f32>().is_ok(),
+            "FD" => value.parse::<f64>().is_ok(),
+            "SL" => value.parse::<i32>().is_ok(),
+            "SS" => value.parse::<i16>().is_ok(),
+            "SV" => value.parse::<i64>().is_ok(),
+            "UL" => value.parse::<u32>().is_ok(),
+            "US" => value.parse::<u16>().is_ok(),
+            "OD" => all::<f64>(value),
+            "OF" => all::<f32>(value),
+            "OL" => all::<u32>(value),
+            "OV" => all::<i64>(value),
+            "OW" => all::<u16>(value),
+            _ => true,
+        };
+
+        if valid {
+            Ok(VisualRepresentation::from_string(vr, value))
+        } else {
+            Err(DicomError::InvalidValue(format!(
+                "{} is not a valid {} value",
+                value, vr
+            )))
+        }
+    }
+
     pub fn new(vr: &str) -> Self {
         match vr {
             "AE" => VisualRepresentation::AE(Cow::default()),
             "AS" => VisualRepresentation::AS(Cow::default()),
             "AT" => VisualRepresentation::AT(Cow::default()),
             "CS" => VisualRepresentation::CS(Cow::default()),
-            "DA" => VisualRepresentation::DA(NaiveDate::default()),
+            "DA" => VisualRepresentation::DA(DateValue::default()),
             "DS" => VisualRepresentation::DS(Cow::default()),
-            "DT" => VisualRepresentation::DT(NaiveDateTime::default()),
+            "DT" => VisualRepresentation::DT(DicomDateTime::default()),
             "FL" => VisualRepresentation::FL(0.0),
             "FD" => VisualRepresentation::FD(0.0),
             "IS" => VisualRepresentation::IS(Cow::default()),
@@ -158,7 +211,7 @@ impl VisualRepresentation {
             "SS" => VisualRepresentation::SS(0),
             "ST" => VisualRepresentation::ST(Cow::default()),
             "SV" => VisualRepresentation::SV(0),
-            "TM" => VisualRepresentation::TM(NaiveTime::from_hms_opt(0, 0, 0).unwrap()),
+            "TM" => VisualRepresentation::TM(TimeValue::default()),
             "UC" => VisualRepresentation::UC(Cow::default()),
             "UI" => VisualRepresentation::UI(Cow::default()),
             "UL" => VisualRepresentation::UL(0),
@@ -170,6 +223,101 @@ impl VisualRepresentation {
         }
     }
 
+    // Zero length value, which Type 2 elements have when the value is unknown
+    pub fn empty(vr: &str) -> Self {
+        VisualRepresentation::Empty(VisualRepresentation::new(vr).code())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, VisualRepresentation::Empty(_))
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            VisualRepresentation::Empty(vr) => vr,
+            VisualRepresentation::AE(_) => "AE",
+            VisualRepresentation::AS(_) => "AS",
+            VisualRepresentation::AT(_) => "AT",
+            VisualRepresentation::CS(_) => "CS",
+            VisualRepresentation::DA(_) => "DA",
+            VisualRepresentation::DS(_) => "DS",
+            VisualRepresentation::DT(_) => "DT",
+            VisualRepresentation::FL(_) => "FL",
+            VisualRepresentation::FD(_) => "FD",
+            VisualRepresentation::IS(_) => "IS",
+            VisualRepresentation::LO(_) => "LO",
+            VisualRepresentation::LT(_) => "LT",
+            VisualRepresentation::OB(_) => "OB",
+            VisualRepresentation::OD(_) => "OD",
+            VisualRepresentation::OF(_) => "OF",
+            VisualRepresentation::OL(_) => "OL",
+            VisualRepresentation::OV(_) => "OV",
+            VisualRepresentation::OW(_) => "OW",
+            VisualRepresentation::PN(_) => "PN",
+            VisualRepresentation::SH(_) => "SH",
+            VisualRepresentation::SL(_) => "SL",
+            VisualRepresentation::SQ(_) => "SQ",
+            VisualRepresentation::SS(_) => "SS",
+            VisualRepresentation::ST(_) => "ST",
+            VisualRepresentation::SV(_) => "SV",
+            VisualRepresentation::TM(_) => "TM",
+            VisualRepresentation::UC(_) => "UC",
+            VisualRepresentation::UI(_) => "UI",
+            VisualRepresentation::UL(_) => "UL",
+            VisualRepresentation::UN(_) => "UN",
+            VisualRepresentation::UR(_) => "UR",
+            VisualRepresentation::US(_) => "US",
+            VisualRepresentation::UT(_) => "UT",
+        }
+    }
+
+    // Bytes the value holds outside the enum itself
+    pub fn heap_size(&self) -> usize {
+        match self {
+            VisualRepresentation::AE(v)
+            | VisualRepresentation::AS(v)
+            | VisualRepresentation::AT(v)
+            | VisualRepresentation::CS(v)
+            | VisualRepresentation::DS(v)
+            | VisualRepresentation::IS(v)
+            | VisualRepresentation::LO(v)
+            | VisualRepresentation::LT(v)
+            | VisualRepresentation::PN(v)
+            | VisualRepresentation::SH(v)
+            | VisualRepresentation::ST(v)
+            | VisualRepresentation::UC(v)
+            | VisualRepresentation::UI(v)
+            | VisualRepresentation::UR(v)
+            | VisualRepresentation::UT(v) => match v {
+                Cow::Owned(v) => v.capacity(),
+                Cow::Borrowed(_) => 0,
+            },
+            VisualRepresentation::OB(v) | VisualRepresentation::UN(v) => v.capacity(),
+            VisualRepresentation::OD(v) => v.capacity() * 8,
+            VisualRepresentation::OF(v) => v.capacity() * 4,
+            VisualRepresentation::OL(v) => v.capacity() * 4,
+            VisualRepresentation::OV(v) => v.capacity() * 8,
+            VisualRepresentation::OW(v) => v.capacity() * 2,
+            // Each item sits behind an Rc with its two counters
+            VisualRepresentation::SQ(items) => items
+                .iter()
+                .map(|item| {
+                    std::mem::size_of::<Rc<dyn DicomTag>>()
+                        + 2 * std::mem::size_of::<usize>()
+                        + item.memory_usage()
+                })
+                .sum(),
+            _ => 0,
+        }
+    }
+
+    pub fn items(&self) -> Vec<Rc<dyn DicomTag>> {
+        match self {
+            VisualRepresentation::SQ(items) => items.iter().map(Rc::clone).collect(),
+            _ => vec![],
+        }
+    }
+
     pub fn set(&self, value: DicomValue) -> &Self {
         // SAFETY: This is safe because the inner value is set based on the type of the VisualRepresentation
         unsafe { self.set_inner(value) }
@@ -192,14 +340,13 @@ impl VisualRepresentation {
                     *v = value.to_string().into();
                 }
                 VisualRepresentation::DA(v) => {
-                    *v = NaiveDate::parse_from_str(&value.to_string(), "%Y%m%d").unwrap();
+                    *v = DateValue::parse(&value.to_string()).unwrap();
                 }
                 VisualRepresentation::DS(v) => {
                     *v = value.to_string().into();
                 }
                 VisualRepresentation::DT(v) => {
-                    *v = NaiveDateTime::parse_from_str(&value.to_string(), "%Y%m%d%H%M%S%.6f")
-                        .unwrap();
+                    *v = DicomDateTime::parse(&value.to_string()).unwrap();
                 }
                 VisualRepresentation::FL(v) => {
                     *v = value.to_string().parse().unwrap();
@@ -276,7 +423,7 @@ impl VisualRepresentation {
                     *v = value.to_string().parse().unwrap();
                 }
                 VisualRepresentation::TM(v) => {
-                    *v = NaiveTime::parse_from_str(&value.to_string(), "%H%M%S%.6f").unwrap();
+                    *v = TimeValue::parse(&value.to_string()).unwrap();
                 }
                 VisualRepresentation::UC(v) => {
                     *v = value.to_string().into();
@@ -299,6 +446,10 @@ impl VisualRepresentation {
                 VisualRepresentation::UT(v) => {
                     *v = value.to_string().into();
                 }
+                VisualRepresentation::Empty(vr) => {
+                    let vr = *vr;
+                    *mutable_self.get() = VisualRepresentation::from_string(vr, &value.to_string());
+                }
             };
         }
 
@@ -306,6 +457,57 @@ impl VisualRepresentation {
     }
 }
 
+// Renders the value the same way `from_string` expects it back
+impl Display for VisualRepresentation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn join<T: ToString>(values: &[T]) -> String {
+            values
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+
+        match self {
+            VisualRepresentation::AE(v)
+            | VisualRepresentation::AS(v)
+            | VisualRepresentation::AT(v)
+            | VisualRepresentation::CS(v)
+            | VisualRepresentation::DS(v)
+            | VisualRepresentation::IS(v)
+            | VisualRepresentation::LO(v)
+            | VisualRepresentation::LT(v)
+            | VisualRepresentation::PN(v)
+            | VisualRepresentation::SH(v)
+            | VisualRepresentation::ST(v)
+            | VisualRepresentation::UC(v)
+            | VisualRepresentation::UI(v)
+            | VisualRepresentation::UR(v)
+            | VisualRepresentation::UT(v) => write!(f, "{}", v),
+            VisualRepresentation::DA(v) => write!(f, "{}", v),
+            VisualRepresentation::DT(v) => write!(f, "{}", v),
+            VisualRepresentation::TM(v) => write!(f, "{}", v),
+            VisualRepresentation::FL(v) => write!(f, "{}", v),
+            VisualRepresentation::FD(v) => write!(f, "{}", v),
+            VisualRepresentation::SL(v) => write!(f, "{}", v),
+            VisualRepresentation::SS(v) => write!(f, "{}", v),
+            VisualRepresentation::SV(v) => write!(f, "{}", v),
+            VisualRepresentation::UL(v) => write!(f, "{}", v),
+            VisualRepresentation::US(v) => write!(f, "{}", v),
+            VisualRepresentation::OB(v) | VisualRepresentation::UN(v) => {
+                write!(f, "{}", String::from_utf8_lossy(v))
+            }
+            VisualRepresentation::OD(v) => write!(f, "{}", join(v)),
+            VisualRepresentation::OF(v) => write!(f, "{}", join(v)),
+            VisualRepresentation::OL(v) => write!(f, "{}", join(v)),
+            VisualRepresentation::OV(v) => write!(f, "{}", join(v)),
+            VisualRepresentation::OW(v) => write!(f, "{}", join(v)),
+            VisualRepresentation::SQ(v) => write!(f, "[{} items]", v.len()),
+            VisualRepresentation::Empty(_) => Ok(()),
+        }
+    }
+}
+
 impl Display for DicomValue<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
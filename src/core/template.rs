@@ -0,0 +1,334 @@
+use std::rc::Rc;
+
+use chrono::Local;
+
+use super::{
+    dataset::Dataset,
+    dictionary,
+    element::DicomElement,
+    iod,
+    reader::PIXEL_DATA,
+    tag::{DicomTag, VisualRepresentation},
+    uid, writer,
+};
+
+pub const CT_IMAGE_STORAGE: &str = "1.2.840.10008.5.1.4.1.1.2";
+pub const MR_IMAGE_STORAGE: &str = "1.2.840.10008.5.1.4.1.1.4";
+pub const US_IMAGE_STORAGE: &str = "1.2.840.10008.5.1.4.1.1.6.1";
+pub const SECONDARY_CAPTURE_IMAGE_STORAGE: &str = "1.2.840.10008.5.1.4.1.1.7";
+pub const COMPREHENSIVE_SR_STORAGE: &str = "1.2.840.10008.5.1.4.1.1.88.33";
+pub const SEGMENTATION_STORAGE: &str = "1.2.840.10008.5.1.4.1.1.66.4";
+
+const SAMPLES_PER_PIXEL: (u16, u16) = (0x0028, 0x0002);
+const NUMBER_OF_FRAMES: (u16, u16) = (0x0028, 0x0008);
+const ROWS: (u16, u16) = (0x0028, 0x0010);
+const COLUMNS: (u16, u16) = (0x0028, 0x0011);
+const BITS_ALLOCATED: (u16, u16) = (0x0028, 0x0100);
+
+// Skeleton of an instance of an IOD, e.g. for test data and modality
+// simulators. Type 1 attributes hold placeholder values, Type 2 ones are
+// empty and the UIDs are fresh, so the instance is valid before anything
+// is filled in
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub sop_class_uid: &'static str,
+    pub dataset: Dataset,
+}
+
+impl Template {
+    // Patient, study, series, equipment and SOP common attributes, the
+    // modules every composite IOD has
+    pub fn new(sop_class_uid: &'static str, modality: &str) -> Self {
+        Template {
+            sop_class_uid,
+            dataset: Dataset::new(),
+        }
+        .with_string((0x0008, 0x0016), "UI", sop_class_uid)
+        .with_string((0x0008, 0x0018), "UI", &uid::generate())
+        .with_empty((0x0010, 0x0010), "PN")
+        .with_empty((0x0010, 0x0020), "LO")
+        .with_empty((0x0010, 0x0030), "DA")
+        .with_empty((0x0010, 0x0040), "CS")
+        .with_string((0x0020, 0x000D), "UI", &uid::generate())
+        .with_empty((0x0008, 0x0020), "DA")
+        .with_empty((0x0008, 0x0030), "TM")
+        .with_empty((0x0008, 0x0050), "SH")
+        .with_empty((0x0008, 0x0090), "PN")
+        .with_empty((0x0020, 0x0010), "SH")
+        .with_string((0x0008, 0x0060), "CS", modality)
+        .with_string((0x0020, 0x000E), "UI", &uid::generate())
+        .with_string((0x0020, 0x0011), "IS", "1")
+        .with_empty((0x0008, 0x0070), "LO")
+        .with_string((0x0020, 0x0013), "IS", "1")
+    }
+
+    pub fn with_string(mut self, tag: (u16, u16), vr: &str, value: &str) -> Self {
+        self.dataset.put_string(tag, vr, value);
+        self
+    }
+
+    // VR as the dictionary has it
+    pub fn with_value(self, tag: (u16, u16), value: &str) -> Self {
+        self.with_string(tag, dictionary::vr_of(tag), value)
+    }
+
+    pub fn with_empty(mut self, tag: (u16, u16), vr: &str) -> Self {
+        self.dataset.set_empty_as(tag, vr);
+        self
+    }
+
+    pub fn with_element(mut self, element: Rc<dyn DicomTag>) -> Self {
+        self.dataset.put(element);
+        self
+    }
+
+    pub fn with_patient(self, patient_id: &str, patient_name: &str) -> Self {
+        self.with_string((0x0010, 0x0020), "LO", patient_id)
+            .with_string((0x0010, 0x0010), "PN", patient_name)
+    }
+
+    pub fn with_study(self, study_instance_uid: &str) -> Self {
+        self.with_string((0x0020, 0x000D), "UI", study_instance_uid)
+    }
+
+    pub fn with_series(self, series_instance_uid: &str) -> Self {
+        self.with_string((0x0020, 0x000E), "UI", series_instance_uid)
+    }
+
+    // Image size, with zeroed pixel data of the template's sample layout
+    pub fn with_image(self, rows: u16, columns: u16) -> Self {
+        let mut template = self.with_string(ROWS, "US", &rows.to_string()).with_string(
+            COLUMNS,
+            "US",
+            &columns.to_string(),
+        );
+        template.reset_pixel_data();
+        template
+    }
+
+    pub fn with_frames(self, frames: u32) -> Self {
+        let mut template = self.with_string(NUMBER_OF_FRAMES, "IS", &frames.max(1).to_string());
+        template.reset_pixel_data();
+        template
+    }
+
+    fn reset_pixel_data(&mut self) {
+        let number = |tag: (u16, u16), default: usize| {
+            self.dataset
+                .string(tag)
+                .and_then(|value| value.trim().parse().ok())
+                .unwrap_or(default)
+        };
+        let (rows, columns) = (number(ROWS, 0), number(COLUMNS, 0));
+        let samples = number(SAMPLES_PER_PIXEL, 1) * number(NUMBER_OF_FRAMES, 1);
+        let value = match number(BITS_ALLOCATED, 8) {
+            16 => VisualRepresentation::OW(vec![0; rows * columns * samples]),
+            // Single bit segmentations pack the frames without padding
+            1 => VisualRepresentation::OB(vec![0; (rows * columns * samples).div_ceil(8)]),
+            bits => VisualRepresentation::OB(vec![0; rows * columns * samples * bits / 8]),
+        };
+        self.dataset
+            .put(Rc::new(DicomElement::new(PIXEL_DATA, value)));
+    }
+
+    // Type 1 attributes of the IOD's mandatory modules still without a
+    // value, from the CIOD tables. Conditional ones are left to the caller
+    pub fn missing(&self) -> Vec<(u16, u16)> {
+        let Some(ciod) = iod::ciod_for_sop_class(self.sop_class_uid) else {
+            return Vec::new();
+        };
+        ciod.required_attributes()
+            .iter()
+            .filter(|attribute| attribute.needs_value())
+            .filter(|attribute| {
+                self.dataset
+                    .find(attribute.tag)
+                    .is_none_or(|element| element.vr().is_empty())
+            })
+            .map(|attribute| attribute.tag)
+            .collect()
+    }
+
+    // The instance, with the Type 2 attributes of the CIOD tables this
+    // template does not set added empty
+    pub fn build(self) -> Dataset {
+        let mut dataset = self.dataset;
+        if let Some(ciod) = iod::ciod_for_sop_class(self.sop_class_uid) {
+            for attribute in ciod.required_attributes() {
+                if !attribute.needs_value() && !dataset.contains(attribute.tag) {
+                    dataset.set_empty(attribute.tag);
+                }
+            }
+        }
+        dataset
+    }
+
+    fn with_pixels(
+        self,
+        samples: u16,
+        photometric: &str,
+        bits_allocated: u16,
+        bits_stored: u16,
+    ) -> Self {
+        let template = self
+            .with_string(SAMPLES_PER_PIXEL, "US", &samples.to_string())
+            .with_string((0x0028, 0x0004), "CS", photometric)
+            .with_string(BITS_ALLOCATED, "US", &bits_allocated.to_string())
+            .with_string((0x0028, 0x0101), "US", &bits_stored.to_string())
+            .with_string((0x0028, 0x0102), "US", &(bits_stored - 1).to_string())
+            .with_string((0x0028, 0x0103), "US", "0");
+        if samples > 1 {
+            template.with_string((0x0028, 0x0006), "US", "0")
+        } else {
+            template
+        }
+    }
+
+    fn with_content_time(self) -> Self {
+        let now = Local::now();
+        self.with_string((0x0008, 0x0023), "DA", &now.format("%Y%m%d").to_string())
+            .with_string((0x0008, 0x0033), "TM", &now.format("%H%M%S").to_string())
+    }
+
+    // Frame of Reference and Image Plane modules, an axial slice at the
+    // origin with 1 mm pixels
+    fn with_plane(self) -> Self {
+        self.with_string((0x0020, 0x0052), "UI", &uid::generate())
+            .with_empty((0x0020, 0x1040), "LO")
+            .with_string((0x0028, 0x0030), "DS", "1\\1")
+            .with_string((0x0020, 0x0037), "DS", "1\\0\\0\\0\\1\\0")
+            .with_string((0x0020, 0x0032), "DS", "0\\0\\0")
+            .with_string((0x0018, 0x0050), "DS", "1")
+    }
+}
+
+fn code(tag: (u16, u16), value: &str, scheme: &str, meaning: &str) -> Rc<DicomElement> {
+    let mut item = Dataset::new();
+    item.put_string((0x0008, 0x0100), "SH", value);
+    item.put_string((0x0008, 0x0102), "SH", scheme);
+    item.put_string((0x0008, 0x0104), "LO", meaning);
+    Rc::new(DicomElement::sequence(tag, vec![item]))
+}
+
+// 512 by 512 slice of 12 bit values stored unsigned, in Hounsfield units
+// after the rescale
+pub fn ct_image() -> Template {
+    Template::new(CT_IMAGE_STORAGE, "CT")
+        .with_string((0x0008, 0x0008), "CS", "ORIGINAL\\PRIMARY\\AXIAL")
+        .with_empty((0x0008, 0x0023), "DA")
+        .with_empty((0x0008, 0x0033), "TM")
+        .with_plane()
+        .with_pixels(1, "MONOCHROME2", 16, 12)
+        .with_string((0x0028, 0x1052), "DS", "-1024")
+        .with_string((0x0028, 0x1053), "DS", "1")
+        .with_empty((0x0018, 0x0060), "DS")
+        .with_empty((0x0020, 0x0012), "IS")
+        .with_image(512, 512)
+}
+
+// 256 by 256 spin echo slice of 12 bit values
+pub fn mr_image() -> Template {
+    Template::new(MR_IMAGE_STORAGE, "MR")
+        .with_string((0x0008, 0x0008), "CS", "ORIGINAL\\PRIMARY\\OTHER")
+        .with_empty((0x0008, 0x0023), "DA")
+        .with_empty((0x0008, 0x0033), "TM")
+        .with_plane()
+        .with_pixels(1, "MONOCHROME2", 16, 12)
+        .with_string((0x0018, 0x0020), "CS", "SE")
+        .with_string((0x0018, 0x0021), "CS", "NONE")
+        .with_empty((0x0018, 0x0022), "CS")
+        .with_string((0x0018, 0x0023), "CS", "2D")
+        .with_empty((0x0018, 0x0081), "DS")
+        .with_empty((0x0018, 0x0091), "IS")
+        .with_image(256, 256)
+}
+
+// 640 by 480 RGB frame
+pub fn us_image() -> Template {
+    Template::new(US_IMAGE_STORAGE, "US")
+        .with_string((0x0008, 0x0008), "CS", "ORIGINAL\\PRIMARY")
+        .with_empty((0x0008, 0x0023), "DA")
+        .with_empty((0x0008, 0x0033), "TM")
+        .with_empty((0x0020, 0x0020), "CS")
+        .with_pixels(3, "RGB", 8, 8)
+        .with_image(480, 640)
+}
+
+// Workstation screen capture, 256 by 256 of 8 bit grey
+pub fn secondary_capture() -> Template {
+    Template::new(SECONDARY_CAPTURE_IMAGE_STORAGE, "OT")
+        .with_string((0x0008, 0x0064), "CS", "WSD")
+        .with_empty((0x0008, 0x0023), "DA")
+        .with_empty((0x0008, 0x0033), "TM")
+        .with_empty((0x0020, 0x0020), "CS")
+        .with_pixels(1, "MONOCHROME2", 8, 8)
+        .with_image(256, 256)
+}
+
+// Unverified report with an empty root container titled Radiology Report,
+// see sr::ContentItem for writing its content
+pub fn comprehensive_sr() -> Template {
+    Template::new(COMPREHENSIVE_SR_STORAGE, "SR")
+        .with_empty((0x0008, 0x1111), "SQ")
+        .with_content_time()
+        .with_string((0x0040, 0xA491), "CS", "PARTIAL")
+        .with_string((0x0040, 0xA493), "CS", "UNVERIFIED")
+        .with_empty((0x0040, 0xA370), "SQ")
+        .with_empty((0x0040, 0xA372), "SQ")
+        .with_string((0x0040, 0xA040), "CS", "CONTAINER")
+        .with_element(code((0x0040, 0xA043), "11528-7", "LN", "Radiology Report"))
+        .with_string((0x0040, 0xA050), "CS", "SEPARATE")
+}
+
+// Binary segmentation of one 256 by 256 frame with a single tissue segment.
+// The Frame of Reference and references to the segmented images come from
+// the source series, which the template cannot know
+pub fn segmentation() -> Template {
+    let mut segment = Dataset::new();
+    segment.put_string((0x0062, 0x0004), "US", "1");
+    segment.put_string((0x0062, 0x0005), "LO", "Segment 1");
+    segment.put_string((0x0062, 0x0008), "CS", "MANUAL");
+    segment.put(code(
+        (0x0062, 0x0003),
+        "91723000",
+        "SCT",
+        "Anatomical Structure",
+    ));
+    segment.put(code((0x0062, 0x000F), "85756007", "SCT", "Tissue"));
+
+    let mut identification = Dataset::new();
+    identification.put_string((0x0062, 0x000B), "US", "1");
+    let mut frame = Dataset::new();
+    frame.put(Rc::new(DicomElement::sequence(
+        (0x0062, 0x000A),
+        vec![identification],
+    )));
+
+    Template::new(SEGMENTATION_STORAGE, "SEG")
+        .with_string((0x0008, 0x0008), "CS", "DERIVED\\PRIMARY")
+        .with_content_time()
+        .with_string((0x0008, 0x0070), "LO", writer::IMPLEMENTATION_VERSION_NAME)
+        .with_string((0x0008, 0x1090), "LO", writer::IMPLEMENTATION_VERSION_NAME)
+        .with_string((0x0018, 0x1000), "LO", "0")
+        .with_string((0x0018, 0x1020), "LO", env!("CARGO_PKG_VERSION"))
+        .with_pixels(1, "MONOCHROME2", 1, 1)
+        .with_string((0x0028, 0x2110), "CS", "00")
+        .with_string((0x0062, 0x0001), "CS", "BINARY")
+        .with_string((0x0070, 0x0080), "CS", "SEGMENTATION")
+        .with_empty((0x0070, 0x0081), "LO")
+        .with_empty((0x0070, 0x0084), "PN")
+        .with_element(Rc::new(DicomElement::sequence(
+            (0x0062, 0x0002),
+            vec![segment],
+        )))
+        .with_element(Rc::new(DicomElement::sequence(
+            (0x5200, 0x9229),
+            vec![Dataset::new()],
+        )))
+        .with_element(Rc::new(DicomElement::sequence(
+            (0x5200, 0x9230),
+            vec![frame],
+        )))
+        .with_frames(1)
+        .with_image(256, 256)
+}
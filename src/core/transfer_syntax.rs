@@ -0,0 +1,141 @@
+pub const IMPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2";
+pub const EXPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1";
+pub const DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1.99";
+pub const EXPLICIT_VR_BIG_ENDIAN: &str = "1.2.840.10008.1.2.2";
+pub const JPEG_BASELINE: &str = "1.2.840.10008.1.2.4.50";
+pub const JPEG_EXTENDED: &str = "1.2.840.10008.1.2.4.51";
+pub const JPEG_LOSSLESS: &str = "1.2.840.10008.1.2.4.57";
+pub const JPEG_LOSSLESS_SV1: &str = "1.2.840.10008.1.2.4.70";
+pub const JPEG_LS_LOSSLESS: &str = "1.2.840.10008.1.2.4.80";
+pub const JPEG_LS_NEAR_LOSSLESS: &str = "1.2.840.10008.1.2.4.81";
+pub const JPEG_2000_LOSSLESS: &str = "1.2.840.10008.1.2.4.90";
+pub const JPEG_2000: &str = "1.2.840.10008.1.2.4.91";
+pub const RLE_LOSSLESS: &str = "1.2.840.10008.1.2.5";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransferSyntax {
+    pub uid: &'static str,
+    pub name: &'static str,
+    pub explicit_vr: bool,
+    pub big_endian: bool,
+    pub encapsulated: bool,
+    pub lossy: bool,
+}
+
+pub const KNOWN: &[TransferSyntax] = &[
+    TransferSyntax {
+        uid: IMPLICIT_VR_LITTLE_ENDIAN,
+        name: "Implicit VR Little Endian",
+        explicit_vr: false,
+        big_endian: false,
+        encapsulated: false,
+        lossy: false,
+    },
+    TransferSyntax {
+        uid: EXPLICIT_VR_LITTLE_ENDIAN,
+        name: "Explicit VR Little Endian",
+        explicit_vr: true,
+        big_endian: false,
+        encapsulated: false,
+        lossy: false,
+    },
+    TransferSyntax {
+        uid: DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN,
+        name: "Deflated Explicit VR Little Endian",
+        explicit_vr: true,
+        big_endian: false,
+        encapsulated: false,
+        lossy: false,
+    },
+    TransferSyntax {
+        uid: EXPLICIT_VR_BIG_ENDIAN,
+        name: "Explicit VR Big Endian",
+        explicit_vr: true,
+        big_endian: true,
+        encapsulated: false,
+        lossy: false,
+    },
+    TransferSyntax {
+        uid: JPEG_BASELINE,
+        name: "JPEG Baseline (Process 1)",
+        explicit_vr: true,
+        big_endian: false,
+        encapsulated: true,
+        lossy: true,
+    },
+    TransferSyntax {
+        uid: JPEG_EXTENDED,
+        name: "JPEG Extended (Process 2 & 4)",
+        explicit_vr: true,
+        big_endian: false,
+        encapsulated: true,
+        lossy: true,
+    },
+    TransferSyntax {
+        uid: JPEG_LOSSLESS,
+        name: "JPEG Lossless, Non-Hierarchical (Process 14)",
+        explicit_vr: true,
+        big_endian: false,
+        encapsulated: true,
+        lossy: false,
+    },
+    TransferSyntax {
+        uid: JPEG_LOSSLESS_SV1,
+        name: "JPEG Lossless, First-Order Prediction",
+        explicit_vr: true,
+        big_endian: false,
+        encapsulated: true,
+        lossy: false,
+    },
+    TransferSyntax {
+        uid: JPEG_LS_LOSSLESS,
+        name: "JPEG-LS Lossless",
+        explicit_vr: true,
+        big_endian: false,
+        encapsulated: true,
+        lossy: false,
+    },
+    TransferSyntax {
+        uid: JPEG_LS_NEAR_LOSSLESS,
+        name: "JPEG-LS Near-Lossless",
+        explicit_vr: true,
+        big_endian: false,
+        encapsulated: true,
+        lossy: true,
+    },
+    TransferSyntax {
+        uid: JPEG_2000_LOSSLESS,
+        name: "JPEG 2000 (Lossless Only)",
+        explicit_vr: true,
+        big_endian: false,
+        encapsulated: true,
+        lossy: false,
+    },
+    TransferSyntax {
+        uid: JPEG_2000,
+        name: "JPEG 2000",
+        explicit_vr: true,
+        big_endian: false,
+        encapsulated: true,
+        lossy: true,
+    },
+    TransferSyntax {
+        uid: RLE_LOSSLESS,
+        name: "RLE Lossless",
+        explicit_vr: true,
+        big_endian: false,
+        encapsulated: true,
+        lossy: false,
+    },
+];
+
+pub fn lookup(uid: &str) -> Option<&'static TransferSyntax> {
+    let uid = uid.trim_end_matches(['\0', ' ']);
+    KNOWN.iter().find(|syntax| syntax.uid == uid)
+}
+
+pub fn is_encapsulated(uid: &str) -> bool {
+    lookup(uid)
+        .map(|syntax| syntax.encapsulated)
+        .unwrap_or(true)
+}
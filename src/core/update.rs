@@ -0,0 +1,409 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+    rc::Rc,
+};
+
+use super::{
+    dataset::Dataset,
+    dictionary,
+    element::{DicomElement, ITEM_TAG},
+    error::{DicomError, DicomResult},
+    reader::{self, has_long_length, ITEM_DELIMITATION_TAG, SEQUENCE_DELIMITATION_TAG},
+    tag::VisualRepresentation,
+    transfer_syntax, writer,
+};
+
+const UNDEFINED_LENGTH: u32 = 0xFFFF_FFFF;
+
+// Bytes moved at a time when shifting the rest of the file
+const CHUNK: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateOutcome {
+    // The new element took the place of the old one, padded where needed
+    InPlace,
+    // The rest of the file moved to make room, or to close the gap
+    Shifted { bytes: u64 },
+}
+
+// Position of a top level element in the file, header included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElementSpan {
+    pub tag: (u16, u16),
+    pub offset: u64,
+    pub length: u64,
+}
+
+// Replaces or inserts one top level element of a Part 10 file, rewriting
+// only that element and, when its size changes, the bytes after it
+pub fn update_in_place<P: AsRef<Path>>(
+    path: P,
+    tag: (u16, u16),
+    value: &VisualRepresentation,
+) -> DicomResult<UpdateOutcome> {
+    if tag.0 == 0x0002 || tag.0 == 0xFFFE {
+        return Err(DicomError::InvalidTag(format!(
+            "({:04X},{:04X}) cannot be updated in place",
+            tag.0, tag.1
+        )));
+    }
+    if let VisualRepresentation::SQ(_) = value {
+        return Err(DicomError::InvalidVR(
+            "Sequences cannot be updated in place".to_string(),
+        ));
+    }
+
+    let mut file = File::options().read(true).write(true).open(path)?;
+    let mut scanner = Scanner::open(&mut file)?;
+    let found = scanner.find(tag)?;
+    let syntax = scanner.syntax;
+
+    let mut element = Dataset::new();
+    element.put(Rc::new(DicomElement::new(tag, value.clone())));
+    let mut encoded = writer::write_dataset(&element, syntax.uid)?;
+
+    let (offset, old_length) = match found {
+        Ok(span) => (span.offset, span.length),
+        Err(insert_at) => (insert_at, 0),
+    };
+
+    // Text values may take trailing spaces, which keeps the rest in place
+    let padding = old_length.saturating_sub(encoded.len() as u64) as usize;
+    let vr = value.code();
+    if found.is_ok() && padding > 0 && padding.is_multiple_of(2) && is_padded_text(vr) {
+        let value_length = encoded.len() + padding - header_length(syntax.explicit_vr, vr);
+        if syntax.explicit_vr && !has_long_length(vr) {
+            if let Ok(value_length) = u16::try_from(value_length) {
+                encoded[6..8].copy_from_slice(&endian_u16(value_length, syntax.big_endian));
+                encoded.resize(encoded.len() + padding, b' ');
+            }
+        } else {
+            let field = header_length(syntax.explicit_vr, vr) - 4;
+            encoded[field..field + 4]
+                .copy_from_slice(&endian_u32(value_length as u32, syntax.big_endian));
+            encoded.resize(encoded.len() + padding, b' ');
+        }
+    }
+
+    let outcome = if encoded.len() as u64 == old_length {
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(&encoded)?;
+        UpdateOutcome::InPlace
+    } else {
+        let moved = shift(
+            &mut file,
+            offset + old_length,
+            encoded.len() as i64 - old_length as i64,
+        )?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(&encoded)?;
+        UpdateOutcome::Shifted { bytes: moved }
+    };
+
+    file.sync_all()?;
+    Ok(outcome)
+}
+
+// Top level elements of a file with their positions, values left unread
+pub fn element_spans<P: AsRef<Path>>(path: P) -> DicomResult<Vec<ElementSpan>> {
+    let mut file = File::open(path)?;
+    let mut scanner = Scanner::open(&mut file)?;
+    let mut spans = Vec::new();
+    while let Some(span) = scanner.next_span()? {
+        spans.push(span);
+    }
+    Ok(spans)
+}
+
+fn is_padded_text(vr: &str) -> bool {
+    matches!(
+        vr,
+        "AE" | "AS"
+            | "CS"
+            | "DA"
+            | "DS"
+            | "DT"
+            | "IS"
+            | "LO"
+            | "LT"
+            | "PN"
+            | "SH"
+            | "ST"
+            | "TM"
+            | "UC"
+            | "UR"
+            | "UT"
+    )
+}
+
+fn header_length(explicit_vr: bool, vr: &str) -> usize {
+    if explicit_vr && has_long_length(vr) {
+        12
+    } else {
+        8
+    }
+}
+
+fn endian_u16(value: u16, big_endian: bool) -> [u8; 2] {
+    if big_endian {
+        value.to_be_bytes()
+    } else {
+        value.to_le_bytes()
+    }
+}
+
+fn endian_u32(value: u32, big_endian: bool) -> [u8; 4] {
+    if big_endian {
+        value.to_be_bytes()
+    } else {
+        value.to_le_bytes()
+    }
+}
+
+// Moves everything from `from` to the end of the file by `delta` bytes, in
+// chunks so a large tail never has to fit in memory
+fn shift(file: &mut File, from: u64, delta: i64) -> DicomResult<u64> {
+    let end = file.metadata()?.len();
+    let tail = end.saturating_sub(from);
+    let mut buffer = vec![0; CHUNK];
+
+    if delta > 0 {
+        file.set_len(end + delta as u64)?;
+        let mut position = end;
+        while position > from {
+            let length = (position - from).min(CHUNK as u64) as usize;
+            position -= length as u64;
+            file.seek(SeekFrom::Start(position))?;
+            file.read_exact(&mut buffer[..length])?;
+            file.seek(SeekFrom::Start(position + delta as u64))?;
+            file.write_all(&buffer[..length])?;
+        }
+    } else if delta < 0 {
+        let delta = delta.unsigned_abs();
+        let mut position = from;
+        while position < end {
+            let length = (end - position).min(CHUNK as u64) as usize;
+            file.seek(SeekFrom::Start(position))?;
+            file.read_exact(&mut buffer[..length])?;
+            file.seek(SeekFrom::Start(position - delta))?;
+            file.write_all(&buffer[..length])?;
+            position += length as u64;
+        }
+        file.set_len(end - delta)?;
+    }
+    Ok(tail)
+}
+
+// Walks element headers in the file, seeking past values
+struct Scanner<'a> {
+    file: &'a mut File,
+    position: u64,
+    end: u64,
+    syntax: transfer_syntax::TransferSyntax,
+    explicit_vr: bool,
+    big_endian: bool,
+}
+
+impl<'a> Scanner<'a> {
+    // Positioned at the first dataset element, after the file meta
+    // information when there is a preamble
+    fn open(file: &'a mut File) -> DicomResult<Self> {
+        let end = file.metadata()?.len();
+        let mut prefix = [0; 132];
+        let has_preamble = end >= 132 && {
+            file.read_exact(&mut prefix)?;
+            &prefix[128..132] == b"DICM"
+        };
+
+        let (syntax, position) = if has_preamble {
+            // Group length, an Explicit VR Little Endian UL
+            let mut group_length = [0; 12];
+            file.read_exact(&mut group_length)?;
+            if group_length[..4] != [0x02, 0x00, 0x00, 0x00] {
+                return Err(DicomError::InvalidFile(
+                    "File meta information lacks its group length".to_string(),
+                ));
+            }
+            let length = u32::from_le_bytes(group_length[8..12].try_into().unwrap());
+            let mut meta = vec![0; length as usize];
+            file.read_exact(&mut meta)?;
+            let meta = reader::read_dataset(&meta, transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN)?;
+            let uid = meta.string(reader::TRANSFER_SYNTAX_UID).ok_or_else(|| {
+                DicomError::InvalidFile("Missing transfer syntax UID".to_string())
+            })?;
+            let syntax = *transfer_syntax::lookup(&uid)
+                .ok_or_else(|| DicomError::UnsupportedTransferSyntax(uid.clone()))?;
+            (syntax, 144 + length as u64)
+        } else {
+            let syntax = *transfer_syntax::lookup(transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN)
+                .ok_or_else(|| {
+                    DicomError::UnsupportedTransferSyntax(
+                        transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN.to_string(),
+                    )
+                })?;
+            (syntax, 0)
+        };
+
+        if syntax.uid == transfer_syntax::DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN {
+            return Err(DicomError::UnsupportedTransferSyntax(
+                syntax.uid.to_string(),
+            ));
+        }
+
+        Ok(Scanner {
+            file,
+            position,
+            end,
+            syntax,
+            explicit_vr: syntax.explicit_vr,
+            big_endian: syntax.big_endian,
+        })
+    }
+
+    // The element with the tag, or the offset it belongs at, top level
+    // elements being in ascending tag order
+    fn find(&mut self, tag: (u16, u16)) -> DicomResult<Result<ElementSpan, u64>> {
+        loop {
+            let position = self.position;
+            match self.next_span()? {
+                Some(span) if span.tag == tag => return Ok(Ok(span)),
+                Some(span) if span.tag > tag => return Ok(Err(position)),
+                Some(_) => continue,
+                None => return Ok(Err(position)),
+            }
+        }
+    }
+
+    fn next_span(&mut self) -> DicomResult<Option<ElementSpan>> {
+        if self.end.saturating_sub(self.position) < 8 {
+            return Ok(None);
+        }
+        let offset = self.position;
+        let tag = self.read_tag()?;
+        let (vr, length) = self.read_header_rest(tag)?;
+        self.skip_value(&vr, length)?;
+        Ok(Some(ElementSpan {
+            tag,
+            offset,
+            length: self.position - offset,
+        }))
+    }
+
+    fn read(&mut self, length: usize) -> DicomResult<Vec<u8>> {
+        if self.position + length as u64 > self.end {
+            return Err(DicomError::InvalidLength(format!(
+                "{} bytes requested at offset {}, {} left",
+                length,
+                self.position,
+                self.end - self.position
+            )));
+        }
+        let mut bytes = vec![0; length];
+        self.file.seek(SeekFrom::Start(self.position))?;
+        self.file.read_exact(&mut bytes)?;
+        self.position += length as u64;
+        Ok(bytes)
+    }
+
+    fn read_u16(&mut self) -> DicomResult<u16> {
+        let bytes = self.read(2)?.try_into().unwrap();
+        Ok(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn read_u32(&mut self) -> DicomResult<u32> {
+        let bytes = self.read(4)?.try_into().unwrap();
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn read_tag(&mut self) -> DicomResult<(u16, u16)> {
+        Ok((self.read_u16()?, self.read_u16()?))
+    }
+
+    // VR and value length of an element whose tag was just read
+    fn read_header_rest(&mut self, tag: (u16, u16)) -> DicomResult<(String, u32)> {
+        if tag.0 == 0xFFFE {
+            return Ok((String::new(), self.read_u32()?));
+        }
+        let vr = if self.explicit_vr {
+            String::from_utf8_lossy(&self.read(2)?).into_owned()
+        } else {
+            dictionary::vr_of(tag).to_string()
+        };
+        let length = if self.explicit_vr && has_long_length(&vr) {
+            self.read(2)?;
+            self.read_u32()?
+        } else if self.explicit_vr {
+            self.read_u16()? as u32
+        } else {
+            self.read_u32()?
+        };
+        Ok((vr, length))
+    }
+
+    fn skip(&mut self, length: u64) -> DicomResult<()> {
+        if self.position + length > self.end {
+            return Err(DicomError::InvalidLength(format!(
+                "Value of {} bytes at offset {} runs past the end",
+                length, self.position
+            )));
+        }
+        self.position += length;
+        Ok(())
+    }
+
+    fn skip_value(&mut self, vr: &str, length: u32) -> DicomResult<()> {
+        if length != UNDEFINED_LENGTH {
+            return self.skip(length as u64);
+        }
+
+        // UN of undefined length is always Implicit VR Little Endian inside
+        let (explicit_vr, big_endian) = (self.explicit_vr, self.big_endian);
+        if vr == "UN" {
+            self.explicit_vr = false;
+            self.big_endian = false;
+        }
+
+        // Items of a sequence, or fragments of encapsulated pixel data
+        loop {
+            let tag = self.read_tag()?;
+            let item_length = self.read_u32()?;
+            match tag {
+                SEQUENCE_DELIMITATION_TAG => break,
+                ITEM_TAG if item_length == UNDEFINED_LENGTH => self.skip_item()?,
+                ITEM_TAG => self.skip(item_length as u64)?,
+                _ => {
+                    return Err(DicomError::InvalidTag(format!(
+                        "({:04X},{:04X}) inside a sequence",
+                        tag.0, tag.1
+                    )))
+                }
+            }
+        }
+
+        self.explicit_vr = explicit_vr;
+        self.big_endian = big_endian;
+        Ok(())
+    }
+
+    // Elements of an undefined length item, up to its delimiter
+    fn skip_item(&mut self) -> DicomResult<()> {
+        loop {
+            let tag = self.read_tag()?;
+            let (vr, length) = self.read_header_rest(tag)?;
+            if tag == ITEM_DELIMITATION_TAG {
+                return Ok(());
+            }
+            self.skip_value(&vr, length)?;
+        }
+    }
+}
@@ -0,0 +1,460 @@
+use std::io::Write;
+
+use super::{
+    dataset::Dataset,
+    dictionary,
+    element::{LengthEncoding, ITEM_TAG},
+    error::{DicomError, DicomResult},
+    meta::FileMeta,
+    padding::ValuePolicy,
+    reader::{ITEM_DELIMITATION_TAG, PIXEL_DATA, SEQUENCE_DELIMITATION_TAG},
+    tag::VisualRepresentation,
+    transfer_syntax,
+};
+
+const UNDEFINED_LENGTH: u32 = 0xFFFF_FFFF;
+
+pub const IMPLEMENTATION_CLASS_UID: &str = "1.2.826.0.1.3680043.10.1437.1";
+pub const IMPLEMENTATION_VERSION_NAME: &str = "DICOM_RS";
+
+// Elements are written in ascending tag order, sequences and items with
+// the length encoding they carry, explicit unless read otherwise
+pub fn write_dataset(dataset: &Dataset, transfer_syntax: &str) -> DicomResult<Vec<u8>> {
+    let mut output = Vec::new();
+    write_dataset_to(dataset, transfer_syntax, &mut output)?;
+    Ok(output)
+}
+
+// Streams the encoded dataset element by element, only sequences are
+// assembled in memory to compute their length
+pub fn write_dataset_to<W: Write>(
+    dataset: &Dataset,
+    transfer_syntax: &str,
+    output: &mut W,
+) -> DicomResult<()> {
+    write_dataset_with_policy(dataset, transfer_syntax, &ValuePolicy::default(), output)
+}
+
+// As write_dataset_to, values padded as the policy says
+pub fn write_dataset_with_policy<W: Write>(
+    dataset: &Dataset,
+    transfer_syntax: &str,
+    policy: &ValuePolicy,
+    output: &mut W,
+) -> DicomResult<()> {
+    write_with(dataset, transfer_syntax, policy, false, output)
+}
+
+// Output that depends on the logical content only: sequences and items of
+// defined length whatever they were read with, padding normalized and
+// group lengths left out. The writer stamps no times of its own, so equal
+// datasets give equal bytes, e.g. for content hashes and test fixtures
+pub fn write_dataset_deterministic<W: Write>(
+    dataset: &Dataset,
+    transfer_syntax: &str,
+    output: &mut W,
+) -> DicomResult<()> {
+    write_with(
+        dataset,
+        transfer_syntax,
+        &ValuePolicy::default(),
+        true,
+        output,
+    )
+}
+
+fn write_with<W: Write>(
+    dataset: &Dataset,
+    transfer_syntax: &str,
+    policy: &ValuePolicy,
+    deterministic: bool,
+    output: &mut W,
+) -> DicomResult<()> {
+    let syntax = transfer_syntax::lookup(transfer_syntax)
+        .ok_or_else(|| DicomError::UnsupportedTransferSyntax(transfer_syntax.to_string()))?;
+    if syntax.uid == transfer_syntax::DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN {
+        return Err(DicomError::UnsupportedTransferSyntax(
+            syntax.uid.to_string(),
+        ));
+    }
+
+    let writer = Writer {
+        explicit_vr: syntax.explicit_vr,
+        big_endian: syntax.big_endian,
+        encapsulated: syntax.encapsulated,
+        policy,
+        deterministic,
+    };
+    writer.write_dataset(output, dataset)
+}
+
+// Encoder writing datasets straight to a socket, a compressor or a buffer.
+// With a chunk size the output is flushed each time that many bytes went
+// out since the last flush, so a stream wrapper can send as it goes
+pub struct Encoder<W: Write> {
+    output: W,
+    transfer_syntax: String,
+    policy: ValuePolicy,
+    chunk_size: Option<usize>,
+    pending: usize,
+    written: u64,
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(output: W, transfer_syntax: &str) -> DicomResult<Self> {
+        let syntax = transfer_syntax::lookup(transfer_syntax)
+            .ok_or_else(|| DicomError::UnsupportedTransferSyntax(transfer_syntax.to_string()))?;
+        if syntax.uid == transfer_syntax::DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN {
+            return Err(DicomError::UnsupportedTransferSyntax(
+                syntax.uid.to_string(),
+            ));
+        }
+
+        Ok(Encoder {
+            output,
+            transfer_syntax: syntax.uid.to_string(),
+            policy: ValuePolicy::default(),
+            chunk_size: None,
+            pending: 0,
+            written: 0,
+        })
+    }
+
+    pub fn with_policy(mut self, policy: ValuePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    // Zero leaves flushing to the caller
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = (chunk_size > 0).then_some(chunk_size);
+        self
+    }
+
+    pub fn transfer_syntax(&self) -> &str {
+        &self.transfer_syntax
+    }
+
+    // Bytes encoded so far, whether flushed or not
+    pub fn bytes_written(&self) -> u64 {
+        self.written
+    }
+
+    pub fn encode(&mut self, dataset: &Dataset) -> DicomResult<()> {
+        let mut chunks = Chunks {
+            output: &mut self.output,
+            chunk_size: self.chunk_size,
+            pending: &mut self.pending,
+            written: &mut self.written,
+        };
+        write_dataset_with_policy(dataset, &self.transfer_syntax, &self.policy, &mut chunks)
+    }
+
+    // Raw bytes between datasets, e.g. a preamble or a delimiter
+    pub fn encode_bytes(&mut self, bytes: &[u8]) -> DicomResult<()> {
+        let mut chunks = Chunks {
+            output: &mut self.output,
+            chunk_size: self.chunk_size,
+            pending: &mut self.pending,
+            written: &mut self.written,
+        };
+        chunks.write_all(bytes)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> DicomResult<()> {
+        self.output.flush()?;
+        self.pending = 0;
+        Ok(())
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.output
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    // Flushes what is left before giving the output back
+    pub fn into_inner(mut self) -> DicomResult<W> {
+        self.flush()?;
+        Ok(self.output)
+    }
+}
+
+struct Chunks<'a, W: Write> {
+    output: &'a mut W,
+    chunk_size: Option<usize>,
+    pending: &'a mut usize,
+    written: &'a mut u64,
+}
+
+impl<W: Write> Write for Chunks<'_, W> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        let written = self.output.write(data)?;
+        *self.written += written as u64;
+        *self.pending += written;
+        if let Some(chunk_size) = self.chunk_size {
+            if *self.pending >= chunk_size {
+                self.output.flush()?;
+                *self.pending = 0;
+            }
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.output.flush()?;
+        *self.pending = 0;
+        Ok(())
+    }
+}
+
+// Part 10 file, with the meta information derived from the dataset's SOP
+// class and instance
+pub fn write_file(dataset: &Dataset, transfer_syntax: &str) -> DicomResult<Vec<u8>> {
+    write_file_with_meta(&FileMeta::from_dataset(dataset, transfer_syntax)?, dataset)
+}
+
+// Part 10 file with the given meta information, whose transfer syntax the
+// dataset is encoded in
+pub fn write_file_with_meta(meta: &FileMeta, dataset: &Dataset) -> DicomResult<Vec<u8>> {
+    write_file_with_policy(meta, dataset, &ValuePolicy::default())
+}
+
+pub fn write_file_with_policy(
+    meta: &FileMeta,
+    dataset: &Dataset,
+    policy: &ValuePolicy,
+) -> DicomResult<Vec<u8>> {
+    meta.check(dataset)?;
+
+    let mut output = vec![0; 128];
+    output.extend_from_slice(b"DICM");
+    write_dataset_to(
+        &meta.to_dataset()?,
+        transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN,
+        &mut output,
+    )?;
+    write_dataset_with_policy(dataset, &meta.transfer_syntax, policy, &mut output)?;
+    Ok(output)
+}
+
+// Part 10 file written as write_dataset_deterministic does, with the
+// implementation UID and version name of this library whatever the meta
+// carried and without the source AE title, which differs by sender
+pub fn write_file_deterministic(meta: &FileMeta, dataset: &Dataset) -> DicomResult<Vec<u8>> {
+    meta.check(dataset)?;
+    let meta = FileMeta {
+        source_ae_title: None,
+        ..meta.clone()
+    }
+    .with_implementation(IMPLEMENTATION_CLASS_UID, Some(IMPLEMENTATION_VERSION_NAME));
+
+    let mut output = vec![0; 128];
+    output.extend_from_slice(b"DICM");
+    write_dataset_to(
+        &meta.to_dataset()?,
+        transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN,
+        &mut output,
+    )?;
+    write_dataset_deterministic(dataset, &meta.transfer_syntax, &mut output)?;
+    Ok(output)
+}
+
+pub fn encode_value(value: &VisualRepresentation, big_endian: bool) -> Vec<u8> {
+    encode_value_with_policy(value, big_endian, &ValuePolicy::default())
+}
+
+pub fn encode_value_with_policy(
+    value: &VisualRepresentation,
+    big_endian: bool,
+    policy: &ValuePolicy,
+) -> Vec<u8> {
+    macro_rules! numbers {
+        ($values:expr) => {
+            $values
+                .iter()
+                .flat_map(|v| {
+                    if big_endian {
+                        v.to_be_bytes().to_vec()
+                    } else {
+                        v.to_le_bytes().to_vec()
+                    }
+                })
+                .collect::<Vec<u8>>()
+        };
+    }
+
+    let mut bytes = match value {
+        VisualRepresentation::OB(bytes) | VisualRepresentation::UN(bytes) => bytes.clone(),
+        VisualRepresentation::OW(words) => numbers!(words),
+        VisualRepresentation::OF(words) => numbers!(words),
+        VisualRepresentation::OD(words) => numbers!(words),
+        VisualRepresentation::OL(words) => numbers!(words),
+        VisualRepresentation::OV(words) => numbers!(words),
+        VisualRepresentation::FL(number) => numbers!([*number]),
+        VisualRepresentation::FD(number) => numbers!([*number]),
+        VisualRepresentation::SL(number) => numbers!([*number]),
+        VisualRepresentation::SS(number) => numbers!([*number]),
+        VisualRepresentation::SV(number) => numbers!([*number]),
+        VisualRepresentation::UL(number) => numbers!([*number]),
+        VisualRepresentation::US(number) => numbers!([*number]),
+        VisualRepresentation::AT(tags) => {
+            let words: Vec<u16> = tags
+                .split('\\')
+                .filter(|tag| tag.len() == 8)
+                .flat_map(|tag| {
+                    [
+                        u16::from_str_radix(&tag[..4], 16).unwrap_or_default(),
+                        u16::from_str_radix(&tag[4..], 16).unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            numbers!(words)
+        }
+        VisualRepresentation::SQ(_) | VisualRepresentation::Empty(_) => vec![],
+        _ => value.to_string().into_bytes(),
+    };
+
+    // Values are always of even length
+    policy.pad(value.code(), &mut bytes);
+    bytes
+}
+
+fn has_long_length(vr: &str) -> bool {
+    matches!(
+        vr,
+        "OB" | "OD" | "OF" | "OL" | "OV" | "OW" | "SQ" | "SV" | "UC" | "UN" | "UR" | "UT" | "UV"
+    )
+}
+
+struct Writer<'a> {
+    explicit_vr: bool,
+    big_endian: bool,
+    encapsulated: bool,
+    policy: &'a ValuePolicy,
+    deterministic: bool,
+}
+
+impl Writer<'_> {
+    fn u16<W: Write>(&self, output: &mut W, value: u16) -> DicomResult<()> {
+        if self.big_endian {
+            output.write_all(&value.to_be_bytes())?;
+        } else {
+            output.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn u32<W: Write>(&self, output: &mut W, value: u32) -> DicomResult<()> {
+        if self.big_endian {
+            output.write_all(&value.to_be_bytes())?;
+        } else {
+            output.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn write_dataset<W: Write>(&self, output: &mut W, dataset: &Dataset) -> DicomResult<()> {
+        let mut elements: Vec<_> = dataset
+            .into_iter()
+            .filter(|element| element.tag() != ITEM_TAG)
+            .filter(|element| !(self.deterministic && element.tag().1 == 0x0000))
+            .collect();
+        elements.sort_by_key(|element| element.tag());
+
+        for element in elements {
+            let tag = element.tag();
+            let value = element.vr();
+
+            if let VisualRepresentation::SQ(items) = &value {
+                let mut content = Vec::new();
+                for item in items.iter() {
+                    let Some(dataset) = item.dataset() else {
+                        continue;
+                    };
+                    let mut encoded = Vec::new();
+                    self.write_dataset(&mut encoded, dataset)?;
+                    self.u16(&mut content, ITEM_TAG.0)?;
+                    self.u16(&mut content, ITEM_TAG.1)?;
+                    if item.length_encoding() == LengthEncoding::Undefined && !self.deterministic {
+                        self.u32(&mut content, UNDEFINED_LENGTH)?;
+                        content.extend(encoded);
+                        self.delimiter(&mut content, ITEM_DELIMITATION_TAG)?;
+                    } else {
+                        self.u32(&mut content, encoded.len() as u32)?;
+                        content.extend(encoded);
+                    }
+                }
+
+                if element.length_encoding() == LengthEncoding::Undefined && !self.deterministic {
+                    self.header(output, tag, "SQ", UNDEFINED_LENGTH)?;
+                    output.write_all(&content)?;
+                    self.delimiter(output, SEQUENCE_DELIMITATION_TAG)?;
+                } else {
+                    self.header(output, tag, "SQ", content.len() as u32)?;
+                    output.write_all(&content)?;
+                }
+                continue;
+            }
+
+            // The reader keeps encapsulated pixel data as its raw item stream
+            if tag == PIXEL_DATA && self.encapsulated {
+                if let VisualRepresentation::OB(stream) = &value {
+                    self.header(output, tag, "OB", UNDEFINED_LENGTH)?;
+                    output.write_all(stream)?;
+                    self.delimiter(output, SEQUENCE_DELIMITATION_TAG)?;
+                    continue;
+                }
+            }
+
+            let bytes = encode_value_with_policy(&value, self.big_endian, self.policy);
+            // Words the reader kept for several shorts go out with their own VR
+            let vr = match (&value, dictionary::vr_of(tag)) {
+                (VisualRepresentation::OW(_), vr @ ("US" | "SS")) if bytes.len() <= 0xFFFF => vr,
+                _ => value.code(),
+            };
+            self.header(output, tag, vr, bytes.len() as u32)?;
+            output.write_all(&bytes)?;
+        }
+
+        Ok(())
+    }
+
+    fn delimiter<W: Write>(&self, output: &mut W, tag: (u16, u16)) -> DicomResult<()> {
+        self.u16(output, tag.0)?;
+        self.u16(output, tag.1)?;
+        self.u32(output, 0)
+    }
+
+    fn header<W: Write>(
+        &self,
+        output: &mut W,
+        tag: (u16, u16),
+        vr: &str,
+        length: u32,
+    ) -> DicomResult<()> {
+        self.u16(output, tag.0)?;
+        self.u16(output, tag.1)?;
+
+        if !self.explicit_vr {
+            self.u32(output, length)?;
+        } else if has_long_length(vr) {
+            output.write_all(vr.as_bytes())?;
+            output.write_all(&[0, 0])?;
+            self.u32(output, length)?;
+        } else if length <= u16::MAX as u32 {
+            output.write_all(vr.as_bytes())?;
+            self.u16(output, length as u16)?;
+        } else {
+            return Err(DicomError::InvalidLength(format!(
+                "({:04X},{:04X}) {} cannot hold {} bytes",
+                tag.0, tag.1, vr, length
+            )));
+        }
+
+        Ok(())
+    }
+}
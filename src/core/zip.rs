@@ -0,0 +1,201 @@
+use super::error::{DicomError, DicomResult};
+
+// Minimal zip support for DICOM files kept in archives. Entries are read
+// when stored, deflated ones being reported as unsupported
+const LOCAL_HEADER: u32 = 0x04034b50;
+const CENTRAL_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+const STORED: u16 = 0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZipEntry {
+    pub name: String,
+    pub method: u16,
+    pub crc32: u32,
+    pub compressed_size: u32,
+    pub size: u32,
+    pub local_header_offset: u32,
+}
+
+fn u16_at(data: &[u8], offset: usize) -> DicomResult<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| DicomError::InvalidFile("Truncated zip archive".to_string()))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> DicomResult<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| DicomError::InvalidFile("Truncated zip archive".to_string()))
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xEDB88320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+// Offset of the end of central directory record, searched from the end as
+// an archive comment may follow it
+fn end_of_central_directory(data: &[u8]) -> DicomResult<usize> {
+    (0..data.len().saturating_sub(21))
+        .rev()
+        .find(|offset| u32_at(data, *offset).ok() == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(|| DicomError::InvalidFile("Not a zip archive".to_string()))
+}
+
+pub fn entries(data: &[u8]) -> DicomResult<Vec<ZipEntry>> {
+    let end = end_of_central_directory(data)?;
+    let count = u16_at(data, end + 10)?;
+    let mut offset = u32_at(data, end + 16)? as usize;
+
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        if u32_at(data, offset)? != CENTRAL_HEADER {
+            return Err(DicomError::InvalidFile(
+                "Corrupt zip central directory".to_string(),
+            ));
+        }
+        let name_length = u16_at(data, offset + 28)? as usize;
+        let extra_length = u16_at(data, offset + 30)? as usize;
+        let comment_length = u16_at(data, offset + 32)? as usize;
+        let name = data
+            .get(offset + 46..offset + 46 + name_length)
+            .ok_or_else(|| DicomError::InvalidFile("Truncated zip archive".to_string()))?;
+
+        entries.push(ZipEntry {
+            name: String::from_utf8_lossy(name).to_string(),
+            method: u16_at(data, offset + 10)?,
+            crc32: u32_at(data, offset + 16)?,
+            compressed_size: u32_at(data, offset + 20)?,
+            size: u32_at(data, offset + 24)?,
+            local_header_offset: u32_at(data, offset + 42)?,
+        });
+        offset += 46 + name_length + extra_length + comment_length;
+    }
+    Ok(entries)
+}
+
+// Bytes of an entry as stored in the archive, compressed or not
+fn entry_data<'a>(data: &'a [u8], entry: &ZipEntry) -> DicomResult<&'a [u8]> {
+    let offset = entry.local_header_offset as usize;
+    if u32_at(data, offset)? != LOCAL_HEADER {
+        return Err(DicomError::InvalidFile(format!(
+            "Corrupt zip entry {}",
+            entry.name
+        )));
+    }
+    let start =
+        offset + 30 + u16_at(data, offset + 26)? as usize + u16_at(data, offset + 28)? as usize;
+    data.get(start..start + entry.compressed_size as usize)
+        .ok_or_else(|| DicomError::InvalidFile("Truncated zip archive".to_string()))
+}
+
+pub fn read_entry(data: &[u8], name: &str) -> DicomResult<Vec<u8>> {
+    let entry = entries(data)?
+        .into_iter()
+        .find(|entry| entry.name == name)
+        .ok_or_else(|| DicomError::InvalidFile(format!("No zip entry {}", name)))?;
+    if entry.method != STORED {
+        return Err(DicomError::InvalidFile(format!(
+            "Zip entry {} is compressed with method {}",
+            name, entry.method
+        )));
+    }
+
+    let content = entry_data(data, &entry)?;
+    if crc32(content) != entry.crc32 {
+        return Err(DicomError::InvalidFile(format!(
+            "Zip entry {} fails its CRC check",
+            name
+        )));
+    }
+    Ok(content.to_vec())
+}
+
+fn local_header(out: &mut Vec<u8>, entry: &ZipEntry) {
+    out.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+    out.extend_from_slice(&20u16.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&entry.method.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&entry.crc32.to_le_bytes());
+    out.extend_from_slice(&entry.compressed_size.to_le_bytes());
+    out.extend_from_slice(&entry.size.to_le_bytes());
+    out.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(entry.name.as_bytes());
+}
+
+fn central_header(out: &mut Vec<u8>, entry: &ZipEntry) {
+    out.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+    out.extend_from_slice(&20u16.to_le_bytes());
+    out.extend_from_slice(&20u16.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&entry.method.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&entry.crc32.to_le_bytes());
+    out.extend_from_slice(&entry.compressed_size.to_le_bytes());
+    out.extend_from_slice(&entry.size.to_le_bytes());
+    out.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+    out.extend_from_slice(&[0; 12]);
+    out.extend_from_slice(&entry.local_header_offset.to_le_bytes());
+    out.extend_from_slice(entry.name.as_bytes());
+}
+
+// New archive with `name` holding `content`, stored uncompressed. Other
+// entries are copied without recompression, and the entry is appended when
+// the archive lacks it
+pub fn replace_entry(data: &[u8], name: &str, content: &[u8]) -> DicomResult<Vec<u8>> {
+    let size = u32::try_from(content.len())
+        .map_err(|_| DicomError::InvalidLength("Zip entries over 4 GiB".to_string()))?;
+
+    let mut out = Vec::with_capacity(data.len() + content.len());
+    let mut written = Vec::new();
+    let mut replaced = false;
+    for mut entry in entries(data)? {
+        let raw = if entry.name == name {
+            replaced = true;
+            entry.method = STORED;
+            entry.crc32 = crc32(content);
+            entry.compressed_size = size;
+            entry.size = size;
+            content
+        } else {
+            entry_data(data, &entry)?
+        };
+        entry.local_header_offset = out.len() as u32;
+        local_header(&mut out, &entry);
+        out.extend_from_slice(raw);
+        written.push(entry);
+    }
+    if !replaced {
+        let entry = ZipEntry {
+            name: name.to_string(),
+            method: STORED,
+            crc32: crc32(content),
+            compressed_size: size,
+            size,
+            local_header_offset: out.len() as u32,
+        };
+        local_header(&mut out, &entry);
+        out.extend_from_slice(content);
+        written.push(entry);
+    }
+
+    let directory = out.len();
+    for entry in &written {
+        central_header(&mut out, entry);
+    }
+    let directory_size = out.len() - directory;
+    out.extend_from_slice(&END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&(written.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(written.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(directory_size as u32).to_le_bytes());
+    out.extend_from_slice(&(directory as u32).to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    Ok(out)
+}
@@ -0,0 +1,129 @@
+use crate::core::error::{DicomError, DicomResult};
+
+// Range of the Grayscale Standard Display Function of PS3.14, in cd/m2
+pub const MIN_LUMINANCE: f64 = 0.05;
+pub const MAX_LUMINANCE: f64 = 4000.0;
+pub const MIN_JND_INDEX: f64 = 1.0;
+pub const MAX_JND_INDEX: f64 = 1023.0;
+
+// Coefficients of PS3.14 7.1, JND index to luminance
+const A: f64 = -1.3011877;
+const B: f64 = -2.5840191e-2;
+const C: f64 = 8.0242636e-2;
+const D: f64 = -1.0320229e-1;
+const E: f64 = 1.3646699e-1;
+const F: f64 = 2.8745620e-2;
+const G: f64 = -2.5468404e-2;
+const H: f64 = -3.1978977e-3;
+const K: f64 = 1.2992634e-4;
+const M: f64 = 1.3635334e-3;
+
+// Coefficients of PS3.14 7.1, luminance to JND index, by power of log10 L
+const INVERSE: [f64; 9] = [
+    71.498068,
+    94.593053,
+    41.912053,
+    9.8247004,
+    0.28175407,
+    -1.1878455,
+    -0.18014349,
+    0.14710899,
+    -0.017046845,
+];
+
+// Luminance in cd/m2 of a JND index, clamped to 1..=1023
+pub fn luminance(jnd_index: f64) -> f64 {
+    let x = jnd_index.clamp(MIN_JND_INDEX, MAX_JND_INDEX).ln();
+    let numerator = A + C * x + E * x.powi(2) + G * x.powi(3) + M * x.powi(4);
+    let denominator = 1.0 + B * x + D * x.powi(2) + F * x.powi(3) + H * x.powi(4) + K * x.powi(5);
+    10f64.powf(numerator / denominator)
+}
+
+// JND index of a luminance in cd/m2, clamped to the range of the function
+pub fn jnd_index(luminance: f64) -> f64 {
+    let x = luminance.clamp(MIN_LUMINANCE, MAX_LUMINANCE).log10();
+    INVERSE
+        .iter()
+        .rev()
+        .fold(0.0, |sum, coefficient| sum * x + coefficient)
+}
+
+// Luminance range of a display, ambient light included as PS3.14 asks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LuminanceRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl LuminanceRange {
+    pub fn new(min: f64, max: f64) -> DicomResult<Self> {
+        if !(min > 0.0 && min < max) || !max.is_finite() {
+            return Err(DicomError::InvalidValue(format!(
+                "Luminance range {} to {} cd/m2",
+                min, max
+            )));
+        }
+        Ok(LuminanceRange { min, max })
+    }
+
+    // Room light reflected by the screen adds to both ends
+    pub fn with_ambient(self, ambient: f64) -> Self {
+        LuminanceRange {
+            min: self.min + ambient,
+            max: self.max + ambient,
+        }
+    }
+
+    // Just noticeable differences the display can show
+    pub fn jnd_count(&self) -> f64 {
+        jnd_index(self.max) - jnd_index(self.min)
+    }
+
+    // Luminance a P-value from 0 to 1 is to be shown at, P-values being
+    // spaced evenly in JND index
+    pub fn luminance(&self, p_value: f64) -> f64 {
+        let low = jnd_index(self.min);
+        let high = jnd_index(self.max);
+        luminance(low + p_value.clamp(0.0, 1.0) * (high - low))
+    }
+}
+
+// Target luminance of each windowed value, the VOI output scaled to 0..=1
+pub fn apply_gsdf(window_output: &[f64], display_luminance_range: &LuminanceRange) -> Vec<f64> {
+    window_output
+        .iter()
+        .map(|p_value| display_luminance_range.luminance(*p_value))
+        .collect()
+}
+
+// P-value to digital driving level table for a display whose measured
+// luminance at each driving level is given, increasing with the level
+pub fn calibration_lut(p_values: usize, measured: &[f64]) -> DicomResult<Vec<u16>> {
+    if p_values < 2 || measured.len() < 2 || measured.len() > u16::MAX as usize + 1 {
+        return Err(DicomError::InvalidValue(format!(
+            "Calibration of {} P-values over {} driving levels",
+            p_values,
+            measured.len()
+        )));
+    }
+    if measured.windows(2).any(|pair| pair[1] < pair[0]) {
+        return Err(DicomError::InvalidValue(
+            "Measured luminance decreases with the driving level".to_string(),
+        ));
+    }
+    let range = LuminanceRange::new(measured[0], measured[measured.len() - 1])?;
+
+    let mut level = 0;
+    Ok((0..p_values)
+        .map(|p_value| {
+            let target = range.luminance(p_value as f64 / (p_values - 1) as f64);
+            // Targets increase, so the search carries on from the last level
+            while level + 1 < measured.len()
+                && (measured[level + 1] - target).abs() <= (measured[level] - target).abs()
+            {
+                level += 1;
+            }
+            level as u16
+        })
+        .collect())
+}
@@ -0,0 +1,257 @@
+use crate::{
+    core::{
+        dataset::Dataset,
+        error::{DicomError, DicomResult},
+        tag::VisualRepresentation,
+    },
+    plugins::codec::FrameInfo,
+};
+
+pub const RED_PALETTE_DESCRIPTOR: (u16, u16) = (0x0028, 0x1101);
+pub const GREEN_PALETTE_DESCRIPTOR: (u16, u16) = (0x0028, 0x1102);
+pub const BLUE_PALETTE_DESCRIPTOR: (u16, u16) = (0x0028, 0x1103);
+pub const RED_PALETTE_DATA: (u16, u16) = (0x0028, 0x1201);
+pub const GREEN_PALETTE_DATA: (u16, u16) = (0x0028, 0x1202);
+pub const BLUE_PALETTE_DATA: (u16, u16) = (0x0028, 0x1203);
+pub const MODALITY_LUT_SEQUENCE: (u16, u16) = (0x0028, 0x3000);
+pub const LUT_DESCRIPTOR: (u16, u16) = (0x0028, 0x3002);
+pub const LUT_DATA: (u16, u16) = (0x0028, 0x3006);
+pub const VOI_LUT_SEQUENCE: (u16, u16) = (0x0028, 0x3010);
+
+// The three values describing a LUT, PS3.3 C.11.1.1.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LutDescriptor {
+    pub entries: usize,
+    // Input value mapped to the first entry
+    pub first_mapped: i32,
+    // Bits of each entry, 8 or 16
+    pub bits: u16,
+}
+
+impl LutDescriptor {
+    // A number of entries of 0 stands for 65536. The first mapped value is
+    // signed when the values looked up are
+    pub fn from_values(values: &[u16], signed: bool) -> DicomResult<Self> {
+        let [entries, first_mapped, bits] = values else {
+            return Err(DicomError::InvalidValue(format!(
+                "LUT descriptor of {} values instead of 3",
+                values.len()
+            )));
+        };
+        let bits = match *bits {
+            8 => 8,
+            // Some writers give the bits stored of 10 to 15 bit tables
+            bits @ 9..=16 => bits,
+            bits => {
+                return Err(DicomError::InvalidValue(format!(
+                    "LUT entries of {} bits",
+                    bits
+                )))
+            }
+        };
+        Ok(LutDescriptor {
+            entries: if *entries == 0 {
+                65536
+            } else {
+                *entries as usize
+            },
+            first_mapped: if signed {
+                *first_mapped as i16 as i32
+            } else {
+                *first_mapped as i32
+            },
+            bits,
+        })
+    }
+}
+
+// Modality, VOI or palette color lookup table, its entries widened to 16
+// bits whatever the data held
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut {
+    pub descriptor: LutDescriptor,
+    pub entries: Vec<u16>,
+}
+
+impl Lut {
+    pub fn new(descriptor: LutDescriptor, entries: Vec<u16>) -> DicomResult<Self> {
+        if entries.len() != descriptor.entries {
+            return Err(DicomError::InvalidLength(format!(
+                "LUT of {} entries with {} given",
+                descriptor.entries,
+                entries.len()
+            )));
+        }
+        Ok(Lut {
+            descriptor,
+            entries,
+        })
+    }
+
+    pub fn from_dataset(
+        dataset: &Dataset,
+        descriptor: (u16, u16),
+        data: (u16, u16),
+        signed: bool,
+    ) -> DicomResult<Self> {
+        let missing = |tag: (u16, u16)| {
+            DicomError::InvalidDataset(format!("Missing ({:04X},{:04X})", tag.0, tag.1))
+        };
+        let values = dataset
+            .value(descriptor)
+            .map(|value| words(&value))
+            .ok_or_else(|| missing(descriptor))?;
+        let descriptor = LutDescriptor::from_values(&values, signed)?;
+        let value = dataset.value(data).ok_or_else(|| missing(data))?;
+
+        let entries = match value {
+            VisualRepresentation::OB(bytes) if descriptor.bits == 8 => {
+                bytes.iter().map(|byte| *byte as u16).collect()
+            }
+            value => {
+                let words = words(&value);
+                // 8 bit entries packed two to a word, the first in the low byte
+                if descriptor.bits == 8 && words.len() == descriptor.entries.div_ceil(2) {
+                    words
+                        .iter()
+                        .flat_map(|word| word.to_le_bytes())
+                        .take(descriptor.entries)
+                        .map(u16::from)
+                        .collect()
+                } else {
+                    words
+                }
+            }
+        };
+        Lut::new(descriptor, entries)
+    }
+
+    // The first item of the Modality LUT Sequence, which replaces the
+    // rescale slope and intercept
+    pub fn modality(dataset: &Dataset, signed: bool) -> DicomResult<Option<Self>> {
+        Lut::from_sequence(dataset, MODALITY_LUT_SEQUENCE, signed)
+    }
+
+    // The first VOI LUT of the dataset, used when no window is given
+    pub fn voi(dataset: &Dataset, signed: bool) -> DicomResult<Option<Self>> {
+        Lut::from_sequence(dataset, VOI_LUT_SEQUENCE, signed)
+    }
+
+    fn from_sequence(
+        dataset: &Dataset,
+        sequence: (u16, u16),
+        signed: bool,
+    ) -> DicomResult<Option<Self>> {
+        dataset
+            .sequence(sequence)
+            .first()
+            .map(|item| Lut::from_dataset(item, LUT_DESCRIPTOR, LUT_DATA, signed))
+            .transpose()
+    }
+
+    // Red, green and blue tables of a PALETTE COLOR image
+    pub fn palette(dataset: &Dataset, signed: bool) -> DicomResult<Option<[Self; 3]>> {
+        if !dataset.contains(RED_PALETTE_DESCRIPTOR) {
+            return Ok(None);
+        }
+        Ok(Some([
+            Lut::from_dataset(dataset, RED_PALETTE_DESCRIPTOR, RED_PALETTE_DATA, signed)?,
+            Lut::from_dataset(
+                dataset,
+                GREEN_PALETTE_DESCRIPTOR,
+                GREEN_PALETTE_DATA,
+                signed,
+            )?,
+            Lut::from_dataset(dataset, BLUE_PALETTE_DESCRIPTOR, BLUE_PALETTE_DATA, signed)?,
+        ]))
+    }
+
+    pub fn max_output(&self) -> u16 {
+        if self.descriptor.bits >= 16 {
+            u16::MAX
+        } else {
+            (1 << self.descriptor.bits) - 1
+        }
+    }
+
+    // Values below the first mapped one take the first entry, values past
+    // the table the last
+    pub fn lookup(&self, value: i32) -> u16 {
+        let index = (value as i64 - self.descriptor.first_mapped as i64)
+            .clamp(0, self.entries.len() as i64 - 1);
+        self.entries[index as usize]
+    }
+
+    // Output scaled down to 8 bits, for display and palette colors
+    pub fn lookup_u8(&self, value: i32) -> u8 {
+        let bits = self.descriptor.bits.min(16);
+        (self.lookup(value) >> (bits - 8)) as u8
+    }
+
+    // The table expanded to every stored value of `bits_stored` bits,
+    // indexed by the raw value so frames map with one load per sample and no
+    // branches
+    pub fn table(&self, bits_stored: u16, signed: bool) -> Vec<u16> {
+        let bits = bits_stored.clamp(1, 16) as u32;
+        (0..1u32 << bits)
+            .map(|raw| {
+                let value = if signed && raw & (1 << (bits - 1)) != 0 {
+                    raw as i32 - (1 << bits)
+                } else {
+                    raw as i32
+                };
+                self.lookup(value)
+            })
+            .collect()
+    }
+
+    // Maps a frame of 8 bit stored values
+    pub fn apply_u8(&self, samples: &[u8], bits_stored: u16, signed: bool) -> Vec<u16> {
+        let table = self.table(bits_stored.min(8), signed);
+        let mask = (table.len() - 1) as u8;
+        samples
+            .iter()
+            .map(|sample| table[(sample & mask) as usize])
+            .collect()
+    }
+
+    // Maps a frame of 16 bit stored values, bits above bits stored ignored
+    pub fn apply_u16(&self, samples: &[u16], bits_stored: u16, signed: bool) -> Vec<u16> {
+        let table = self.table(bits_stored.min(16), signed);
+        let mask = (table.len() - 1) as u16;
+        let mut output = vec![0; samples.len()];
+        for (chunk, samples) in output.chunks_mut(8).zip(samples.chunks(8)) {
+            for (out, sample) in chunk.iter_mut().zip(samples) {
+                *out = table[(sample & mask) as usize];
+            }
+        }
+        output
+    }
+
+    // Maps a native little endian frame of one or two bytes a sample
+    pub fn apply_frame(&self, data: &[u8], info: &FrameInfo) -> Vec<u16> {
+        let signed = info.pixel_representation == 1;
+        if info.bytes_per_sample() == 1 {
+            return self.apply_u8(data, info.bits_stored, signed);
+        }
+        let samples: Vec<u16> = data
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        self.apply_u16(&samples, info.bits_stored, signed)
+    }
+}
+
+// Unsigned shorts of a descriptor or table, however the reader kept them
+fn words(value: &VisualRepresentation) -> Vec<u16> {
+    match value {
+        VisualRepresentation::US(value) => vec![*value],
+        VisualRepresentation::SS(value) => vec![*value as u16],
+        VisualRepresentation::OW(words) => words.clone(),
+        VisualRepresentation::OB(bytes) | VisualRepresentation::UN(bytes) => bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,5 @@
+pub mod gsdf;
+pub mod lut;
+pub mod render;
+pub mod suv;
+pub mod thumbnail;
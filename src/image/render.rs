@@ -0,0 +1,380 @@
+use super::lut::Lut;
+use crate::{
+    core::{
+        bits,
+        dataset::Dataset,
+        error::{DicomError, DicomResult},
+        reader::{self, DicomFile, PIXEL_DATA},
+        tag::VisualRepresentation,
+        transfer_syntax,
+    },
+    plugins::codec::{self, FrameInfo},
+};
+
+pub const NUMBER_OF_FRAMES: (u16, u16) = (0x0028, 0x0008);
+pub const PLANAR_CONFIGURATION: (u16, u16) = (0x0028, 0x0006);
+pub const WINDOW_CENTER: (u16, u16) = (0x0028, 0x1050);
+pub const WINDOW_WIDTH: (u16, u16) = (0x0028, 0x1051);
+pub const RESCALE_INTERCEPT: (u16, u16) = (0x0028, 0x1052);
+pub const RESCALE_SLOPE: (u16, u16) = (0x0028, 0x1053);
+pub const FLOAT_PIXEL_DATA: (u16, u16) = (0x7FE0, 0x0008);
+pub const DOUBLE_FLOAT_PIXEL_DATA: (u16, u16) = (0x7FE0, 0x0009);
+
+#[derive(Debug, Clone)]
+pub struct RenderedFrame {
+    pub width: u32,
+    pub height: u32,
+    // RGBA, 4 bytes per pixel, row by row
+    pub pixels: Vec<u8>,
+}
+
+pub fn number_of_frames(dataset: &Dataset) -> usize {
+    dataset
+        .string(NUMBER_OF_FRAMES)
+        .and_then(|frames| frames.trim().parse().ok())
+        .unwrap_or(1)
+}
+
+// The element holding the pixels, Parametric Maps and some RT Dose objects
+// keeping theirs as floats
+pub fn pixel_data_tag(dataset: &Dataset) -> Option<(u16, u16)> {
+    [PIXEL_DATA, FLOAT_PIXEL_DATA, DOUBLE_FLOAT_PIXEL_DATA]
+        .into_iter()
+        .find(|tag| dataset.contains(*tag))
+}
+
+pub fn is_float(dataset: &Dataset) -> bool {
+    pixel_data_tag(dataset).is_some_and(|tag| tag != PIXEL_DATA)
+}
+
+// Native little endian samples of one frame, decoded through the codec registry
+pub fn frame_data(file: &DicomFile, frame: usize) -> DicomResult<Vec<u8>> {
+    let dataset = &file.dataset;
+    let info = FrameInfo::from_dataset(dataset)?;
+    let frames = number_of_frames(dataset);
+    if frame >= frames {
+        return Err(DicomError::InvalidValue(format!(
+            "Frame {} out of {}",
+            frame, frames
+        )));
+    }
+
+    let tag = pixel_data_tag(dataset)
+        .ok_or_else(|| DicomError::InvalidDataset("No pixel data".to_string()))?;
+    let value = dataset
+        .value(tag)
+        .ok_or_else(|| DicomError::InvalidDataset("No pixel data".to_string()))?;
+
+    // Float pixel data is never encapsulated
+    if tag == PIXEL_DATA && transfer_syntax::is_encapsulated(&file.transfer_syntax) {
+        let VisualRepresentation::OB(data) = value else {
+            return Err(DicomError::InvalidVR(
+                "Encapsulated pixel data has to be OB".to_string(),
+            ));
+        };
+
+        // Skip the basic offset table, then either one fragment per frame or
+        // a single frame split over several fragments
+        let fragments = reader::fragments(&data)?;
+        let fragments = fragments.get(1..).unwrap_or_default();
+        let fragment = if fragments.len() == frames {
+            fragments[frame].to_vec()
+        } else if frames == 1 {
+            fragments.concat()
+        } else {
+            return Err(DicomError::InvalidValue(format!(
+                "{} fragments for {} frames",
+                fragments.len(),
+                frames
+            )));
+        };
+
+        return codec::global_registry().decode(&file.transfer_syntax, &fragment, &info);
+    }
+
+    // The reader already turned words into native order
+    let data: Vec<u8> = match value {
+        VisualRepresentation::OB(bytes) | VisualRepresentation::UN(bytes) => bytes,
+        VisualRepresentation::OW(words) => words.iter().flat_map(|w| w.to_le_bytes()).collect(),
+        VisualRepresentation::OF(floats) => floats.iter().flat_map(|f| f.to_le_bytes()).collect(),
+        VisualRepresentation::OD(doubles) => doubles.iter().flat_map(|d| d.to_le_bytes()).collect(),
+        _ => {
+            return Err(DicomError::InvalidVR(format!(
+                "Pixel data as {}",
+                value.code()
+            )))
+        }
+    };
+
+    let bits = info.frame_bits();
+    if bits % 8 != 0 && data.len() >= (bits * frames).div_ceil(8) {
+        return Ok(bits::bit_range(&data, frame * bits, bits));
+    }
+    let size = info.frame_size();
+    data.get(frame * size..(frame + 1) * size)
+        .map(|frame| frame.to_vec())
+        .ok_or_else(|| DicomError::InvalidLength(format!("Pixel data is missing frame {}", frame)))
+}
+
+// One frame of Float Pixel Data
+pub fn float_frame(file: &DicomFile, frame: usize) -> DicomResult<Vec<f32>> {
+    if !file.dataset.contains(FLOAT_PIXEL_DATA) {
+        return Err(DicomError::InvalidDataset(
+            "No float pixel data".to_string(),
+        ));
+    }
+    Ok(frame_data(file, frame)?
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect())
+}
+
+// One frame of Double Float Pixel Data
+pub fn double_frame(file: &DicomFile, frame: usize) -> DicomResult<Vec<f64>> {
+    if !file.dataset.contains(DOUBLE_FLOAT_PIXEL_DATA) {
+        return Err(DicomError::InvalidDataset(
+            "No double float pixel data".to_string(),
+        ));
+    }
+    Ok(frame_data(file, frame)?
+        .chunks_exact(8)
+        .map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap_or_default()))
+        .collect())
+}
+
+// Values of one frame whatever holds them, stored integer samples or floats
+pub fn frame_values(file: &DicomFile, frame: usize) -> DicomResult<Vec<f64>> {
+    let dataset = &file.dataset;
+    match pixel_data_tag(dataset) {
+        Some(FLOAT_PIXEL_DATA) => Ok(float_frame(file, frame)?
+            .into_iter()
+            .map(f64::from)
+            .collect()),
+        Some(DOUBLE_FLOAT_PIXEL_DATA) => double_frame(file, frame),
+        _ => Ok(samples(
+            &frame_data(file, frame)?,
+            &FrameInfo::from_dataset(dataset)?,
+        )),
+    }
+}
+
+// VOI window, overriding the one of the dataset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+    pub center: f64,
+    pub width: f64,
+}
+
+impl Window {
+    pub fn new(center: f64, width: f64) -> Self {
+        Window { center, width }
+    }
+}
+
+pub fn render_frame(file: &DicomFile, frame: usize) -> DicomResult<RenderedFrame> {
+    render_frame_with_window(file, frame, None)
+}
+
+pub fn render_frame_with_window(
+    file: &DicomFile,
+    frame: usize,
+    window: Option<Window>,
+) -> DicomResult<RenderedFrame> {
+    let dataset = &file.dataset;
+    let info = FrameInfo::from_dataset(dataset)?;
+    let data = frame_data(file, frame)?;
+    let pixels = info.pixel_count();
+
+    let rgba = match info.photometric_interpretation.as_str() {
+        "MONOCHROME1" | "MONOCHROME2" => {
+            let signed = info.pixel_representation == 1;
+            // Float values are real values already, without modality LUT
+            let values = match Lut::modality(dataset, signed)? {
+                _ if is_float(dataset) => frame_values(file, frame)?,
+                Some(lut) => lut
+                    .apply_frame(&data, &info)
+                    .iter()
+                    .map(|value| *value as f64)
+                    .collect(),
+                None => {
+                    let slope = number(dataset, RESCALE_SLOPE).unwrap_or(1.0);
+                    let intercept = number(dataset, RESCALE_INTERCEPT).unwrap_or(0.0);
+                    samples(&data, &info)
+                        .iter()
+                        .map(|v| v * slope + intercept)
+                        .collect::<Vec<f64>>()
+                }
+            };
+            let invert = info.photometric_interpretation == "MONOCHROME1";
+
+            let window = match window {
+                Some(window) => (Some(window.center), Some(window.width)),
+                None => (
+                    number(dataset, WINDOW_CENTER),
+                    number(dataset, WINDOW_WIDTH),
+                ),
+            };
+            // A VOI LUT applies when the dataset gives no window. Its first
+            // mapped value is signed when the modality output can be negative
+            let voi = match window {
+                (Some(_), Some(_)) => None,
+                _ => Lut::voi(dataset, signed || values.iter().any(|v| *v < 0.0))?,
+            };
+            if let Some(lut) = voi {
+                let max = lut.max_output() as f64;
+                return Ok(RenderedFrame {
+                    width: info.columns as u32,
+                    height: info.rows as u32,
+                    pixels: values
+                        .iter()
+                        .flat_map(|value| {
+                            let gray = lut.lookup(value.round() as i32) as f64 / max;
+                            let gray = (gray * 255.0).round() as u8;
+                            let gray = if invert { 255 - gray } else { gray };
+                            [gray, gray, gray, 255]
+                        })
+                        .collect(),
+                });
+            }
+            let (center, width) = match window {
+                (Some(center), Some(width)) if width >= 1.0 => (center, width),
+                _ => {
+                    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+                    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                    ((min + max) / 2.0, (max - min).max(1.0))
+                }
+            };
+
+            values
+                .iter()
+                .flat_map(|value| {
+                    // Linear VOI function from PS3.3 C.11.2.1.2
+                    let scaled = ((value - (center - 0.5)) / (width - 1.0) + 0.5).clamp(0.0, 1.0);
+                    let gray = (scaled * 255.0).round() as u8;
+                    let gray = if invert { 255 - gray } else { gray };
+                    [gray, gray, gray, 255]
+                })
+                .collect()
+        }
+        "RGB" | "YBR_FULL" if info.samples_per_pixel == 3 && info.bits_allocated == 8 => {
+            let planar = dataset
+                .string(PLANAR_CONFIGURATION)
+                .is_some_and(|value| value.trim() == "1");
+            let ybr = info.photometric_interpretation == "YBR_FULL";
+            // Decoded frames come from codecs as they are, not cut to size
+            if pixels
+                .checked_mul(3)
+                .is_none_or(|needed| data.len() < needed)
+            {
+                return Err(DicomError::InvalidLength(format!(
+                    "Frame of {} bytes, {} pixels of 3 samples expected",
+                    data.len(),
+                    pixels
+                )));
+            }
+
+            (0..pixels)
+                .flat_map(|pixel| {
+                    let sample = |channel: usize| {
+                        if planar {
+                            data[channel * pixels + pixel]
+                        } else {
+                            data[pixel * 3 + channel]
+                        }
+                    };
+                    let (a, b, c) = (sample(0), sample(1), sample(2));
+                    let [r, g, b] = if ybr { ybr_to_rgb(a, b, c) } else { [a, b, c] };
+                    [r, g, b, 255]
+                })
+                .collect()
+        }
+        "PALETTE COLOR" if info.samples_per_pixel == 1 => {
+            let signed = info.pixel_representation == 1;
+            let [red, green, blue] = Lut::palette(dataset, signed)?.ok_or_else(|| {
+                DicomError::InvalidDataset("PALETTE COLOR without palette tables".to_string())
+            })?;
+            samples(&data, &info)
+                .iter()
+                .flat_map(|value| {
+                    let value = *value as i32;
+                    [
+                        red.lookup_u8(value),
+                        green.lookup_u8(value),
+                        blue.lookup_u8(value),
+                        255,
+                    ]
+                })
+                .collect()
+        }
+        other => {
+            return Err(DicomError::InvalidValue(format!(
+                "Cannot render {} with {} samples of {} bits",
+                other, info.samples_per_pixel, info.bits_allocated
+            )))
+        }
+    };
+
+    Ok(RenderedFrame {
+        width: info.columns as u32,
+        height: info.rows as u32,
+        pixels: rgba,
+    })
+}
+
+fn number(dataset: &Dataset, tag: (u16, u16)) -> Option<f64> {
+    dataset
+        .string(tag)
+        .and_then(|value| value.split('\\').next()?.trim().parse().ok())
+}
+
+// Stored values honouring bits stored and pixel representation
+pub fn samples(data: &[u8], info: &FrameInfo) -> Vec<f64> {
+    let bits = info.bits_stored.clamp(1, 32) as u32;
+    let mask = if bits == 32 {
+        u32::MAX
+    } else {
+        (1u32 << bits) - 1
+    };
+    let signed = info.pixel_representation == 1;
+
+    let value = |raw: u32| {
+        let raw = raw & mask;
+        if signed && raw & (1 << (bits - 1)) != 0 {
+            raw as f64 - (1u64 << bits) as f64
+        } else {
+            raw as f64
+        }
+    };
+
+    if matches!(info.bits_allocated, 1 | 12) {
+        let count = info.pixel_count() * info.samples_per_pixel as usize;
+        return bits::unpack(data, count, info.bits_allocated)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|sample| value(sample as u32))
+            .collect();
+    }
+
+    match info.bytes_per_sample() {
+        1 => data.iter().map(|byte| value(*byte as u32)).collect(),
+        2 => data
+            .chunks_exact(2)
+            .map(|c| value(u16::from_le_bytes([c[0], c[1]]) as u32))
+            .collect(),
+        _ => data
+            .chunks_exact(4)
+            .map(|c| value(u32::from_le_bytes([c[0], c[1], c[2], c[3]])))
+            .collect(),
+    }
+}
+
+fn ybr_to_rgb(y: u8, cb: u8, cr: u8) -> [u8; 3] {
+    let (y, cb, cr) = (y as f64, cb as f64 - 128.0, cr as f64 - 128.0);
+    let clamp = |v: f64| v.round().clamp(0.0, 255.0) as u8;
+
+    [
+        clamp(y + 1.402 * cr),
+        clamp(y - 0.344136 * cb - 0.714136 * cr),
+        clamp(y + 1.772 * cb),
+    ]
+}
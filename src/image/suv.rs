@@ -0,0 +1,224 @@
+use super::render::{frame_data, samples, RESCALE_INTERCEPT, RESCALE_SLOPE};
+use crate::{
+    core::{
+        dataset::Dataset,
+        error::{DicomError, DicomResult},
+        reader::DicomFile,
+    },
+    plugins::codec::FrameInfo,
+};
+
+pub const SERIES_DATE: (u16, u16) = (0x0008, 0x0021);
+pub const ACQUISITION_DATE: (u16, u16) = (0x0008, 0x0022);
+pub const SERIES_TIME: (u16, u16) = (0x0008, 0x0031);
+pub const ACQUISITION_TIME: (u16, u16) = (0x0008, 0x0032);
+pub const PATIENT_SEX: (u16, u16) = (0x0010, 0x0040);
+pub const PATIENT_SIZE: (u16, u16) = (0x0010, 0x1020);
+pub const PATIENT_WEIGHT: (u16, u16) = (0x0010, 0x1030);
+pub const RADIOPHARMACEUTICAL_START_TIME: (u16, u16) = (0x0018, 0x1072);
+pub const RADIONUCLIDE_TOTAL_DOSE: (u16, u16) = (0x0018, 0x1074);
+pub const RADIONUCLIDE_HALF_LIFE: (u16, u16) = (0x0018, 0x1075);
+pub const RADIOPHARMACEUTICAL_START_DATETIME: (u16, u16) = (0x0018, 0x1078);
+pub const RADIOPHARMACEUTICAL_INFORMATION_SEQUENCE: (u16, u16) = (0x0054, 0x0016);
+pub const UNITS: (u16, u16) = (0x0054, 0x1001);
+pub const DECAY_CORRECTION: (u16, u16) = (0x0054, 0x1102);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuvType {
+    // SUVbw, g/mL
+    BodyWeight,
+    // SUVlbm by the James formula, g/mL
+    LeanBodyMass,
+    // SUVbsa by the Du Bois formula, cm2/mL
+    BodySurfaceArea,
+}
+
+// What SUV needs from a PET image, times in seconds from 1970 and the dose
+// in Bq as administered
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuvParameters {
+    pub injected_dose: f64,
+    pub half_life: f64,
+    pub injection_time: f64,
+    // Time the activity in the image is decay corrected to
+    pub reference_time: f64,
+    // Kilograms
+    pub weight: f64,
+    // Metres
+    pub height: Option<f64>,
+    pub sex: Option<String>,
+}
+
+impl SuvParameters {
+    pub fn from_dataset(dataset: &Dataset) -> DicomResult<Self> {
+        let units = dataset.string(UNITS).unwrap_or_default();
+        if units.trim() != "BQML" {
+            return Err(DicomError::InvalidValue(format!(
+                "SUV needs activity in BQML, not {}",
+                units.trim()
+            )));
+        }
+
+        let radiopharmaceutical = dataset
+            .sequence(RADIOPHARMACEUTICAL_INFORMATION_SEQUENCE)
+            .into_iter()
+            .next()
+            .ok_or_else(|| missing(RADIOPHARMACEUTICAL_INFORMATION_SEQUENCE))?;
+        let injected_dose = required(&radiopharmaceutical, RADIONUCLIDE_TOTAL_DOSE)?;
+        let half_life = required(&radiopharmaceutical, RADIONUCLIDE_HALF_LIFE)?;
+
+        let series_date = dataset.string(SERIES_DATE).unwrap_or_default();
+        let injection_time = match radiopharmaceutical.string(RADIOPHARMACEUTICAL_START_DATETIME) {
+            Some(datetime) if datetime.trim().len() >= 8 => {
+                let datetime = datetime.trim();
+                date_time(&datetime[..8], &datetime[8..])
+            }
+            _ => radiopharmaceutical
+                .string(RADIOPHARMACEUTICAL_START_TIME)
+                .and_then(|time| date_time(&series_date, &time)),
+        }
+        .ok_or_else(|| missing(RADIOPHARMACEUTICAL_START_DATETIME))?;
+
+        // START corrects to the series start, ADMIN to the injection itself,
+        // NONE leaves the activity at acquisition time
+        let series_time = dataset
+            .string(SERIES_TIME)
+            .and_then(|time| date_time(&series_date, &time));
+        let acquisition_time = dataset.string(ACQUISITION_TIME).and_then(|time| {
+            date_time(
+                &dataset
+                    .string(ACQUISITION_DATE)
+                    .unwrap_or(series_date.clone()),
+                &time,
+            )
+        });
+        let correction = dataset.string(DECAY_CORRECTION).unwrap_or_default();
+        let reference_time = match correction.trim() {
+            "ADMIN" => Some(injection_time),
+            "NONE" => acquisition_time.or(series_time),
+            _ => series_time.or(acquisition_time),
+        }
+        .ok_or_else(|| missing(SERIES_TIME))?;
+
+        Ok(SuvParameters {
+            injected_dose,
+            half_life,
+            injection_time,
+            reference_time,
+            weight: required(dataset, PATIENT_WEIGHT)?,
+            height: number(dataset, PATIENT_SIZE).filter(|height| *height > 0.0),
+            sex: dataset
+                .string(PATIENT_SEX)
+                .map(|sex| sex.trim().to_string()),
+        })
+    }
+
+    // Injected activity decayed to the reference time
+    pub fn decayed_dose(&self) -> f64 {
+        let elapsed = self.reference_time - self.injection_time;
+        self.injected_dose * (-elapsed * std::f64::consts::LN_2 / self.half_life).exp()
+    }
+
+    // Kilograms, from weight in kg and height in cm
+    pub fn lean_body_mass(&self) -> DicomResult<f64> {
+        let height = self.height.ok_or_else(|| missing(PATIENT_SIZE))? * 100.0;
+        let ratio = self.weight / height;
+        match self.sex.as_deref() {
+            Some("M") => Ok(1.10 * self.weight - 128.0 * ratio * ratio),
+            Some("F") => Ok(1.07 * self.weight - 148.0 * ratio * ratio),
+            _ => Err(DicomError::InvalidValue(
+                "Lean body mass needs a patient sex of M or F".to_string(),
+            )),
+        }
+    }
+
+    // Square metres
+    pub fn body_surface_area(&self) -> DicomResult<f64> {
+        let height = self.height.ok_or_else(|| missing(PATIENT_SIZE))? * 100.0;
+        Ok(0.007184 * self.weight.powf(0.425) * height.powf(0.725))
+    }
+
+    // Multiplier taking Bq/mL to the SUV
+    pub fn factor(&self, kind: SuvType) -> DicomResult<f64> {
+        let dose = self.decayed_dose();
+        if dose <= 0.0 || !dose.is_finite() {
+            return Err(DicomError::InvalidValue(format!(
+                "Decayed dose of {} Bq",
+                dose
+            )));
+        }
+        let normalizer = match kind {
+            SuvType::BodyWeight => self.weight * 1000.0,
+            SuvType::LeanBodyMass => self.lean_body_mass()? * 1000.0,
+            SuvType::BodySurfaceArea => self.body_surface_area()? * 10000.0,
+        };
+        Ok(normalizer / dose)
+    }
+}
+
+// SUV of every pixel of a frame, numbered from 0. Images already in GML are
+// SUVbw as stored
+pub fn suv_frame(file: &DicomFile, frame: usize, kind: SuvType) -> DicomResult<Vec<f64>> {
+    let dataset = &file.dataset;
+    let factor = match dataset.string(UNITS).as_deref().map(str::trim) {
+        Some("GML") if kind == SuvType::BodyWeight => 1.0,
+        _ => SuvParameters::from_dataset(dataset)?.factor(kind)?,
+    };
+    let slope = number(dataset, RESCALE_SLOPE).unwrap_or(1.0);
+    let intercept = number(dataset, RESCALE_INTERCEPT).unwrap_or(0.0);
+
+    let info = FrameInfo::from_dataset(dataset)?;
+    let data = frame_data(file, frame)?;
+    Ok(samples(&data, &info)
+        .into_iter()
+        .map(|value| (value * slope + intercept) * factor)
+        .collect())
+}
+
+fn number(dataset: &Dataset, tag: (u16, u16)) -> Option<f64> {
+    dataset
+        .string(tag)
+        .and_then(|value| value.split('\\').next()?.trim().parse().ok())
+}
+
+fn required(dataset: &Dataset, tag: (u16, u16)) -> DicomResult<f64> {
+    number(dataset, tag).ok_or_else(|| missing(tag))
+}
+
+fn missing(tag: (u16, u16)) -> DicomError {
+    DicomError::InvalidDataset(format!("SUV needs ({:04X},{:04X})", tag.0, tag.1))
+}
+
+// Seconds from 1970 of a DA and TM pair, ignoring time zones as both come
+// from the same clock
+fn date_time(date: &str, time: &str) -> Option<f64> {
+    let date = date.trim();
+    if date.len() < 8 {
+        return None;
+    }
+    let year: i64 = date[..4].parse().ok()?;
+    let month: i64 = date[4..6].parse().ok()?;
+    let day: i64 = date[6..8].parse().ok()?;
+
+    // Days from civil, counting years from March
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let time = time.trim().split(['+', '-']).next().unwrap_or("");
+    let field = |range: std::ops::Range<usize>| -> f64 {
+        time.get(range)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0.0)
+    };
+    let seconds = field(0..2) * 3600.0
+        + field(2..4) * 60.0
+        + time
+            .get(4..)
+            .and_then(|value| value.parse::<f64>().ok())
+            .unwrap_or(0.0);
+    Some(days as f64 * 86400.0 + seconds)
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ::image::{codecs::jpeg::JpegEncoder, ExtendedColorType};
+
+use super::render::{self, RenderedFrame, Window};
+use crate::core::{
+    document,
+    error::{DicomError, DicomResult},
+    meta::SOP_INSTANCE_UID,
+    reader::DicomFile,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThumbnailOptions {
+    // Longest edge in pixels, frames are never scaled up
+    pub size: u32,
+    pub quality: u8,
+    pub frame: usize,
+    // Window of the dataset when None
+    pub window: Option<Window>,
+}
+
+impl Default for ThumbnailOptions {
+    fn default() -> Self {
+        ThumbnailOptions {
+            size: 128,
+            quality: 75,
+            frame: 0,
+            window: None,
+        }
+    }
+}
+
+impl ThumbnailOptions {
+    pub fn with_size(mut self, size: u32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_quality(mut self, quality: u8) -> Self {
+        self.quality = quality.clamp(1, 100);
+        self
+    }
+
+    pub fn with_frame(mut self, frame: usize) -> Self {
+        self.frame = frame;
+        self
+    }
+
+    pub fn with_window(mut self, window: Window) -> Self {
+        self.window = Some(window);
+        self
+    }
+}
+
+// FNV-1a, stable across builds unlike the hasher of the standard library, so
+// keys stay valid for caches on disk
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// Same instance and options, same key
+pub fn cache_key(sop_instance_uid: &str, options: &ThumbnailOptions) -> String {
+    let window = match options.window {
+        Some(window) => format!("{}/{}", window.center, window.width),
+        None => "dataset".to_string(),
+    };
+    let text = format!(
+        "{}|{}|{}|{}|{}",
+        sop_instance_uid.trim_end_matches('\0'),
+        options.frame,
+        options.size,
+        options.quality,
+        window
+    );
+    format!("{:016x}", fnv1a(text.as_bytes()))
+}
+
+// Averages the pixels each output pixel covers, which keeps fine detail from
+// aliasing the way nearest neighbour sampling would
+pub fn downsample(frame: &RenderedFrame, size: u32) -> RenderedFrame {
+    let longest = frame.width.max(frame.height);
+    if longest <= size || size == 0 {
+        return frame.clone();
+    }
+    let width = (frame.width as u64 * size as u64 / longest as u64).max(1) as u32;
+    let height = (frame.height as u64 * size as u64 / longest as u64).max(1) as u32;
+
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        let top = y * frame.height / height;
+        let bottom = ((y + 1) * frame.height / height).max(top + 1);
+        for x in 0..width {
+            let left = x * frame.width / width;
+            let right = ((x + 1) * frame.width / width).max(left + 1);
+
+            let mut sums = [0u64; 4];
+            for source_y in top..bottom {
+                for source_x in left..right {
+                    let offset = ((source_y * frame.width + source_x) * 4) as usize;
+                    for (sum, value) in sums.iter_mut().zip(&frame.pixels[offset..offset + 4]) {
+                        *sum += *value as u64;
+                    }
+                }
+            }
+            let count = ((bottom - top) * (right - left)) as u64;
+            pixels.extend(sums.iter().map(|sum| (sum / count) as u8));
+        }
+    }
+
+    RenderedFrame {
+        width,
+        height,
+        pixels,
+    }
+}
+
+pub fn encode_jpeg(frame: &RenderedFrame, quality: u8) -> DicomResult<Vec<u8>> {
+    let rgb: Vec<u8> = frame
+        .pixels
+        .chunks_exact(4)
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+        .collect();
+
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, quality)
+        .encode(&rgb, frame.width, frame.height, ExtendedColorType::Rgb8)
+        .map_err(|error| DicomError::External {
+            context: "JPEG encoding failed".to_string(),
+            source: Box::new(error),
+        })?;
+    Ok(jpeg)
+}
+
+pub fn thumbnail(file: &DicomFile, options: &ThumbnailOptions) -> DicomResult<Vec<u8>> {
+    let frame = render::render_frame_with_window(file, options.frame, options.window)?;
+    encode_jpeg(&downsample(&frame, options.size), options.quality)
+}
+
+// Thumbnails kept in memory, least recently used dropped first, and in a
+// folder when one is given so they outlive the process
+#[derive(Debug, Clone)]
+pub struct ThumbnailCache {
+    dir: Option<PathBuf>,
+    capacity: usize,
+    entries: HashMap<String, Vec<u8>>,
+    recent: VecDeque<String>,
+}
+
+impl ThumbnailCache {
+    pub fn new(capacity: usize) -> Self {
+        ThumbnailCache {
+            dir: None,
+            capacity,
+            entries: HashMap::new(),
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn with_dir(mut self, dir: &Path) -> Self {
+        self.dir = Some(dir.to_path_buf());
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.jpg", key)))
+    }
+
+    fn touch(&mut self, key: &str) {
+        self.recent.retain(|recent| recent != key);
+        self.recent.push_back(key.to_string());
+    }
+
+    fn insert(&mut self, key: &str, jpeg: Vec<u8>) {
+        self.entries.insert(key.to_string(), jpeg);
+        self.touch(key);
+        while self.entries.len() > self.capacity {
+            match self.recent.pop_front() {
+                Some(oldest) => self.entries.remove(&oldest),
+                None => break,
+            };
+        }
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<Vec<u8>> {
+        if let Some(jpeg) = self.entries.get(key).cloned() {
+            self.touch(key);
+            return Some(jpeg);
+        }
+        let jpeg = fs::read(self.path(key)?).ok()?;
+        self.insert(key, jpeg.clone());
+        Some(jpeg)
+    }
+
+    // Cached thumbnail of the instance, generated on a miss
+    pub fn thumbnail(
+        &mut self,
+        file: &DicomFile,
+        options: &ThumbnailOptions,
+    ) -> DicomResult<Vec<u8>> {
+        let sop_instance_uid = file.dataset.string(SOP_INSTANCE_UID).ok_or_else(|| {
+            DicomError::InvalidDataset("Dataset lacks its SOP Instance UID".to_string())
+        })?;
+        let key = cache_key(&sop_instance_uid, options);
+        if let Some(jpeg) = self.get(&key) {
+            return Ok(jpeg);
+        }
+
+        let jpeg = thumbnail(file, options)?;
+        if let Some(path) = self.path(&key) {
+            fs::create_dir_all(path.parent().unwrap())?;
+            document::write_atomic(&path, &jpeg, false)?;
+        }
+        self.insert(&key, jpeg.clone());
+        Ok(jpeg)
+    }
+
+    pub fn clear(&mut self) -> DicomResult<()> {
+        self.entries.clear();
+        self.recent.clear();
+        if let Some(dir) = &self.dir {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|extension| extension == "jpg") {
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
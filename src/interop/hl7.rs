@@ -0,0 +1,452 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::PoisonError,
+    thread,
+};
+
+use chrono::Local;
+
+use crate::core::{
+    error::{DicomError, DicomResult},
+    uid,
+};
+use crate::net::worklist::{SharedWorklist, WorklistItem};
+
+// MLLP framing of HL7 v2 Appendix C
+pub const START_BLOCK: u8 = 0x0B;
+pub const END_BLOCK: u8 = 0x1C;
+pub const CARRIAGE_RETURN: u8 = 0x0D;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub id: String,
+    // Raw fields, the segment ID at index 0
+    fields: Vec<String>,
+}
+
+impl Segment {
+    // HL7 numbering, MSH-1 being the field separator itself
+    pub fn field(&self, index: usize) -> &str {
+        let position = match self.id.as_str() {
+            "MSH" if index < 2 => return "",
+            "MSH" => index - 1,
+            _ => index,
+        };
+        self.fields
+            .get(position)
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hl7Message {
+    pub segments: Vec<Segment>,
+    field_separator: char,
+    component_separator: char,
+    repetition_separator: char,
+    escape_character: char,
+    subcomponent_separator: char,
+}
+
+impl Hl7Message {
+    pub fn parse(text: &str) -> DicomResult<Self> {
+        let text = text.trim_start_matches(['\r', '\n']);
+        if !text.starts_with("MSH") || text.len() < 8 {
+            return Err(DicomError::InvalidValue(
+                "HL7 message does not start with an MSH segment".to_string(),
+            ));
+        }
+
+        let mut delimiters = text[3..].chars();
+        let field_separator = delimiters.next().unwrap_or('|');
+        let encoding: Vec<char> = delimiters.take(4).collect();
+        let encoding_at =
+            |index: usize, default: char| encoding.get(index).copied().unwrap_or(default);
+
+        let segments = text
+            .split(['\r', '\n'])
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let fields: Vec<String> = line.split(field_separator).map(str::to_string).collect();
+                Segment {
+                    id: fields[0].clone(),
+                    fields,
+                }
+            })
+            .collect();
+
+        Ok(Hl7Message {
+            segments,
+            field_separator,
+            component_separator: encoding_at(0, '^'),
+            repetition_separator: encoding_at(1, '~'),
+            escape_character: encoding_at(2, '\\'),
+            subcomponent_separator: encoding_at(3, '&'),
+        })
+    }
+
+    pub fn segment(&self, id: &str) -> Option<&Segment> {
+        self.segments.iter().find(|segment| segment.id == id)
+    }
+
+    // Unescaped component of the first repetition, both 1-based. Missing
+    // segments, fields and components all read as empty
+    pub fn component(&self, segment: &str, field: usize, component: usize) -> String {
+        let Some(segment) = self.segment(segment) else {
+            return String::new();
+        };
+        let value = segment
+            .field(field)
+            .split(self.repetition_separator)
+            .next()
+            .unwrap_or_default();
+        let value = value
+            .split(self.component_separator)
+            .nth(component.saturating_sub(1))
+            .unwrap_or_default();
+        let value = value
+            .split(self.subcomponent_separator)
+            .next()
+            .unwrap_or_default();
+
+        self.unescape(value)
+    }
+
+    pub fn value(&self, segment: &str, field: usize) -> String {
+        self.component(segment, field, 1)
+    }
+
+    // e.g. ORM^O01
+    pub fn message_type(&self) -> String {
+        format!(
+            "{}^{}",
+            self.component("MSH", 9, 1),
+            self.component("MSH", 9, 2)
+        )
+    }
+
+    pub fn control_id(&self) -> String {
+        self.value("MSH", 10)
+    }
+
+    fn unescape(&self, value: &str) -> String {
+        let escape = self.escape_character;
+        let mut result = String::with_capacity(value.len());
+        let mut parts = value.split(escape);
+        result.push_str(parts.next().unwrap_or_default());
+
+        // Sequences alternate with text between escape characters
+        while let Some(sequence) = parts.next() {
+            match sequence {
+                "F" => result.push(self.field_separator),
+                "S" => result.push(self.component_separator),
+                "R" => result.push(self.repetition_separator),
+                "T" => result.push(self.subcomponent_separator),
+                "E" => result.push(escape),
+                ".br" => result.push('\n'),
+                _ => {}
+            }
+            result.push_str(parts.next().unwrap_or_default());
+        }
+
+        result
+    }
+
+    // Acknowledgement with code AA, AE or AR and an optional error text
+    pub fn ack(&self, code: &str, text: Option<&str>) -> String {
+        let separator = self.field_separator;
+        let encoding: String = [
+            self.component_separator,
+            self.repetition_separator,
+            self.escape_character,
+            self.subcomponent_separator,
+        ]
+        .iter()
+        .collect();
+        let field = |segment: &str, index: usize| {
+            self.segment(segment)
+                .map(|segment| segment.field(index).to_string())
+                .unwrap_or_default()
+        };
+
+        let msh = [
+            "MSH".to_string(),
+            encoding,
+            field("MSH", 5),
+            field("MSH", 6),
+            field("MSH", 3),
+            field("MSH", 4),
+            Local::now().format("%Y%m%d%H%M%S").to_string(),
+            String::new(),
+            format!(
+                "ACK{}{}",
+                self.component_separator,
+                self.component("MSH", 9, 2)
+            ),
+            Local::now().format("%Y%m%d%H%M%S%3f").to_string(),
+            field("MSH", 11),
+            field("MSH", 12),
+        ];
+        let mut msa = vec!["MSA".to_string(), code.to_string(), self.control_id()];
+        if let Some(text) = text {
+            msa.push(text.replace(separator, " "));
+        }
+
+        format!(
+            "{}\r{}\r",
+            msh.join(&separator.to_string()),
+            msa.join(&separator.to_string())
+        )
+    }
+}
+
+// Reads one MLLP framed message, None once the peer closed the connection
+pub fn read_mllp<R: BufRead>(reader: &mut R) -> DicomResult<Option<String>> {
+    let mut skipped = Vec::new();
+    if reader.read_until(START_BLOCK, &mut skipped)? == 0 {
+        return Ok(None);
+    }
+    if skipped.last() != Some(&START_BLOCK) {
+        return Ok(None);
+    }
+
+    let mut block = Vec::new();
+    reader.read_until(END_BLOCK, &mut block)?;
+    if block.pop() != Some(END_BLOCK) {
+        return Err(DicomError::InvalidLength(
+            "MLLP block ended before its end marker".to_string(),
+        ));
+    }
+
+    let mut trailer = [0u8; 1];
+    reader.read_exact(&mut trailer)?;
+    if trailer[0] != CARRIAGE_RETURN {
+        return Err(DicomError::InvalidValue(
+            "MLLP end marker is not followed by a carriage return".to_string(),
+        ));
+    }
+
+    Ok(Some(String::from_utf8_lossy(&block).into_owned()))
+}
+
+pub fn write_mllp<W: Write>(writer: &mut W, message: &str) -> DicomResult<()> {
+    writer.write_all(&[START_BLOCK])?;
+    writer.write_all(message.as_bytes())?;
+    writer.write_all(&[END_BLOCK, CARRIAGE_RETURN])?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorklistUpdate {
+    Schedule(Box<WorklistItem>),
+    // Cancelled or discontinued orders, and orders with results
+    Remove { accession_number: String },
+    // Messages and order controls the worklist has no use for
+    Ignore,
+}
+
+// Maps ORM^O01 and ORU^R01 messages onto worklist changes
+#[derive(Debug, Clone)]
+pub struct Hl7Bridge {
+    worklist: SharedWorklist,
+    // Station the steps are scheduled on, when OBR carries none
+    pub station_ae_title: Option<String>,
+}
+
+impl Hl7Bridge {
+    pub fn new(worklist: SharedWorklist) -> Self {
+        Hl7Bridge {
+            worklist,
+            station_ae_title: None,
+        }
+    }
+
+    pub fn with_station_ae_title(mut self, ae_title: &str) -> Self {
+        self.station_ae_title = Some(ae_title.to_string());
+        self
+    }
+
+    pub fn update(&self, message: &Hl7Message) -> DicomResult<WorklistUpdate> {
+        let message_type = message.component("MSH", 9, 1);
+        match message_type.as_str() {
+            "ORM" | "OMI" | "OMG" => self.order(message),
+            "ORU" => {
+                let accession_number = accession_number(message);
+                if accession_number.is_empty() {
+                    return Ok(WorklistUpdate::Ignore);
+                }
+                Ok(WorklistUpdate::Remove { accession_number })
+            }
+            _ => Ok(WorklistUpdate::Ignore),
+        }
+    }
+
+    fn order(&self, message: &Hl7Message) -> DicomResult<WorklistUpdate> {
+        if message.segment("PID").is_none() || message.segment("OBR").is_none() {
+            return Err(DicomError::InvalidValue(
+                "Order message lacks a PID or OBR segment".to_string(),
+            ));
+        }
+
+        let accession_number = accession_number(message);
+        if accession_number.is_empty() {
+            return Err(DicomError::InvalidValue(
+                "Order carries no accession or filler order number".to_string(),
+            ));
+        }
+
+        match message.value("ORC", 1).as_str() {
+            "CA" | "DC" | "OC" | "OD" => return Ok(WorklistUpdate::Remove { accession_number }),
+            "" | "NW" | "XO" | "SC" => {}
+            _ => return Ok(WorklistUpdate::Ignore),
+        }
+
+        // Scheduled start from OBR-36, the ORC-7 timing or OBR-7 otherwise
+        let start = [
+            message.value("OBR", 36),
+            message.component("ORC", 7, 4),
+            message.value("OBR", 7),
+        ]
+        .into_iter()
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+        let (date, time) = split_timestamp(&start);
+
+        let procedure_code = message.component("OBR", 4, 1);
+        let procedure_text = message.component("OBR", 4, 2);
+        let description = if procedure_text.is_empty() {
+            procedure_code.clone()
+        } else {
+            procedure_text
+        };
+
+        let study_instance_uid = [message.value("ZDS", 1), message.value("IPC", 3)]
+            .into_iter()
+            .find(|value| uid::is_valid(value))
+            .unwrap_or_else(uid::generate);
+
+        let station = message.component("OBR", 21, 1);
+        let station = if station.is_empty() {
+            self.station_ae_title.clone().unwrap_or_default()
+        } else {
+            station
+        };
+
+        Ok(WorklistUpdate::Schedule(Box::new(WorklistItem {
+            patient_id: message.component("PID", 3, 1),
+            patient_name: person_name(message, "PID", 5, 1),
+            patient_birth_date: message.value("PID", 7).chars().take(8).collect(),
+            patient_sex: match message.value("PID", 8).as_str() {
+                "M" => "M",
+                "F" => "F",
+                "" => "",
+                _ => "O",
+            }
+            .to_string(),
+            accession_number: accession_number.clone(),
+            referring_physician_name: person_name(message, "OBR", 16, 2),
+            placer_order_number: first_of(&[message.value("ORC", 2), message.value("OBR", 2)]),
+            filler_order_number: first_of(&[message.value("ORC", 3), message.value("OBR", 3)]),
+            study_instance_uid,
+            requested_procedure_id: first_of(&[message.value("OBR", 19), accession_number.clone()]),
+            requested_procedure_description: description.clone(),
+            modality: message.value("OBR", 24),
+            scheduled_station_ae_title: station,
+            scheduled_start_date: date,
+            scheduled_start_time: time,
+            scheduled_performing_physician_name: person_name(message, "OBR", 34, 1),
+            scheduled_procedure_step_id: first_of(&[message.value("OBR", 20), accession_number]),
+            scheduled_procedure_step_description: description,
+        })))
+    }
+
+    pub fn apply(&self, update: &WorklistUpdate) {
+        let mut worklist = self.worklist.lock().unwrap_or_else(PoisonError::into_inner);
+        match update {
+            WorklistUpdate::Schedule(item) => worklist.upsert(item.as_ref().clone()),
+            WorklistUpdate::Remove { accession_number } => {
+                worklist.remove(accession_number);
+            }
+            WorklistUpdate::Ignore => {}
+        }
+    }
+
+    // Parses and applies one message, answering with the acknowledgement
+    pub fn handle(&self, text: &str) -> DicomResult<String> {
+        let message = Hl7Message::parse(text)?;
+        match self.update(&message) {
+            Ok(update) => {
+                self.apply(&update);
+                Ok(message.ack("AA", None))
+            }
+            Err(error) => Ok(message.ack("AE", Some(&error.to_string()))),
+        }
+    }
+
+    // Serves MLLP connections from the RIS, each on its own thread
+    pub fn listen<A: ToSocketAddrs>(&self, address: A) -> DicomResult<()> {
+        let listener = TcpListener::bind(address)?;
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let bridge = self.clone();
+            thread::spawn(move || bridge.serve(stream));
+        }
+
+        Ok(())
+    }
+
+    pub fn serve(&self, stream: TcpStream) -> DicomResult<()> {
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+
+        while let Some(text) = read_mllp(&mut reader)? {
+            // Without a readable MSH there is nothing to acknowledge to
+            let ack = self.handle(&text)?;
+            write_mllp(&mut writer, &ack)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn accession_number(message: &Hl7Message) -> String {
+    first_of(&[
+        message.value("OBR", 18),
+        message.value("ORC", 3),
+        message.value("OBR", 3),
+    ])
+}
+
+fn first_of(values: &[String]) -> String {
+    values
+        .iter()
+        .find(|value| !value.is_empty())
+        .cloned()
+        .unwrap_or_default()
+}
+
+// XPN and XCN names to DICOM PN, family^given^middle^prefix^suffix. XCN
+// fields start with the ID, hence `first` as the family name component
+fn person_name(message: &Hl7Message, segment: &str, field: usize, first: usize) -> String {
+    let family = message.component(segment, field, first);
+    let given = message.component(segment, field, first + 1);
+    let middle = message.component(segment, field, first + 2);
+    let suffix = message.component(segment, field, first + 3);
+    let prefix = message.component(segment, field, first + 4);
+
+    [family, given, middle, prefix, suffix]
+        .join("^")
+        .trim_end_matches('^')
+        .to_string()
+}
+
+// HL7 TS, YYYYMMDD[HHMM[SS[.S]]][+ZZZZ], into DICOM DA and TM
+fn split_timestamp(value: &str) -> (String, String) {
+    let value = value.split(['+', '-']).next().unwrap_or_default();
+    let date = value.chars().take(8).collect();
+    let time = value.chars().skip(8).collect();
+    (date, time)
+}
@@ -0,0 +1,8 @@
+#[cfg(any(feature = "net", feature = "default"))]
+pub mod hl7;
+
+#[cfg(any(feature = "serde", feature = "default"))]
+pub mod roundtrip;
+
+#[cfg(any(feature = "sha1", feature = "default"))]
+pub mod xds;
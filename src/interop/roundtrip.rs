@@ -0,0 +1,430 @@
+// Round trip guarantees between the binary encoding and the DICOM JSON model,
+// for property tests here and in downstream crates. A dataset read from the
+// wire must come back from binary -> JSON -> binary with the same elements,
+// apart from these documented exceptions the comparison tolerates
+//   - DS and IS values travel as JSON numbers, so they compare numerically
+//     ("1.50" and "1.5" are the same value)
+//   - Leading and trailing spaces and NUL padding of each value are dropped
+//   - Element order, and defined or undefined lengths of sequences and items,
+//     are encoding details and not compared
+//   - A zero length binary value and an empty value of the same VR are equal
+// There is no DICOM XML (PS3.19) reader or writer in this crate, so only the
+// JSON model is covered
+
+use std::{ops::Range, rc::Rc};
+
+use crate::core::{
+    dataset::Dataset,
+    element::DicomElement,
+    error::{DicomError, DicomResult},
+    json, reader,
+    tag::VisualRepresentation,
+    writer,
+};
+
+// A standard attribute per VR, in tag order, which generated datasets pick
+// from. Items of the sequences below draw from the same table
+const ATTRIBUTES: &[((u16, u16), &str)] = &[
+    ((0x0008, 0x0018), "UI"),
+    ((0x0008, 0x0020), "DA"),
+    ((0x0008, 0x002A), "DT"),
+    ((0x0008, 0x0030), "TM"),
+    ((0x0008, 0x0050), "SH"),
+    ((0x0008, 0x0054), "AE"),
+    ((0x0008, 0x0060), "CS"),
+    ((0x0008, 0x0070), "LO"),
+    ((0x0008, 0x0081), "ST"),
+    ((0x0008, 0x0119), "UC"),
+    ((0x0008, 0x0190), "UR"),
+    ((0x0008, 0x9459), "FL"),
+    ((0x0009, 0x1010), "UN"),
+    ((0x0010, 0x0010), "PN"),
+    ((0x0010, 0x1010), "AS"),
+    ((0x0018, 0x0050), "DS"),
+    ((0x0018, 0x6020), "SL"),
+    ((0x0018, 0x9087), "FD"),
+    ((0x0018, 0x9219), "SS"),
+    ((0x0020, 0x0013), "IS"),
+    ((0x0020, 0x4000), "LT"),
+    ((0x0028, 0x0009), "AT"),
+    ((0x0028, 0x0010), "US"),
+    ((0x0028, 0x1201), "OW"),
+    ((0x0040, 0xA132), "UL"),
+    ((0x0040, 0xA160), "UT"),
+    ((0x0042, 0x0011), "OB"),
+    ((0x0066, 0x0016), "OF"),
+    ((0x0066, 0x0022), "OD"),
+    ((0x0066, 0x0040), "OL"),
+    ((0x0072, 0x0082), "SV"),
+    ((0x7FE0, 0x0001), "OV"),
+];
+
+const SEQUENCES: &[(u16, u16)] = &[(0x0008, 0x1115), (0x0040, 0x0275)];
+
+const LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const TEXT: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789 .,-";
+
+// Deterministic datasets from a seed, so a failing case can be replayed from
+// the seed alone
+pub struct DatasetGenerator {
+    state: u64,
+    depth: usize,
+    items: usize,
+}
+
+impl DatasetGenerator {
+    pub fn new(seed: u64) -> Self {
+        DatasetGenerator {
+            // xorshift never leaves zero
+            state: seed ^ 0x9E37_79B9_7F4A_7C15,
+            depth: 2,
+            items: 3,
+        }
+    }
+
+    // Nesting levels of sequences below the top level dataset
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    // Largest number of items per sequence
+    pub fn with_items(mut self, items: usize) -> Self {
+        self.items = items;
+        self
+    }
+
+    pub fn generate(&mut self) -> Dataset {
+        self.dataset(self.depth)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound.max(1)
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+
+    fn dataset(&mut self, depth: usize) -> Dataset {
+        let mut dataset = Dataset::new();
+
+        for &(tag, vr) in ATTRIBUTES {
+            if !self.chance(60) {
+                continue;
+            }
+            let value = if self.chance(10) {
+                VisualRepresentation::empty(vr)
+            } else {
+                self.value(vr)
+            };
+            dataset.push_back(Rc::new(DicomElement::new(tag, value)));
+        }
+
+        if depth > 0 {
+            for &tag in SEQUENCES {
+                if !self.chance(50) {
+                    continue;
+                }
+                let count = self.below(self.items as u64 + 1) as usize;
+                let items = (0..count).map(|_| self.dataset(depth - 1)).collect();
+                dataset.push_back(Rc::new(DicomElement::sequence(tag, items)));
+            }
+        }
+
+        dataset
+    }
+
+    fn value(&mut self, vr: &str) -> VisualRepresentation {
+        let text = match vr {
+            "AE" => self.string(LETTERS, 1, 16),
+            "AS" => format!(
+                "{:03}{}",
+                self.below(1000),
+                ["D", "W", "M", "Y"][self.below(4) as usize]
+            ),
+            "AT" => self.multiple(|g| format!("{:04X}{:04X}", g.below(0x10000), g.below(0x10000))),
+            "CS" => self.multiple(|g| g.string(LETTERS, 1, 16)),
+            "DA" => format!(
+                "{:04}{:02}{:02}",
+                1900 + self.below(200),
+                1 + self.below(12),
+                1 + self.below(28)
+            ),
+            // At most 9 characters, well within the 16 of DS
+            "DS" => self.multiple(|g| {
+                let number = g.below(2_000_000) as f64 / 1000.0 - 1000.0;
+                format!("{:.3}", number)
+            }),
+            "DT" => format!(
+                "{:04}{:02}{:02}{:02}{:02}{:02}",
+                1900 + self.below(200),
+                1 + self.below(12),
+                1 + self.below(28),
+                self.below(24),
+                self.below(60),
+                self.below(60)
+            ),
+            "IS" => self.multiple(|g| format!("{}", g.below(2_000_000) as i64 - 1_000_000)),
+            "LO" | "SH" => self.multiple(|g| g.string(TEXT, 1, 16)),
+            "LT" | "ST" | "UT" => self.string(TEXT, 1, 200),
+            "PN" => format!(
+                "{}^{}",
+                self.string(LETTERS, 1, 20),
+                self.string(LETTERS, 1, 20)
+            ),
+            "TM" => format!(
+                "{:02}{:02}{:02}",
+                self.below(24),
+                self.below(60),
+                self.below(60)
+            ),
+            "UC" => self.multiple(|g| g.string(TEXT, 1, 64)),
+            "UI" => {
+                self.multiple(|g| format!("1.2.826.0.1.{}.{}", g.below(100_000), g.below(100_000)))
+            }
+            "UR" => format!("http://example.com/{}", self.string(LETTERS, 1, 32)),
+            "FL" => return VisualRepresentation::FL(self.next() as u32 as f32 / 1024.0),
+            "FD" => return VisualRepresentation::FD(self.next() as i64 as f64 / 1e6),
+            "SL" => return VisualRepresentation::SL(self.next() as i32),
+            "SS" => return VisualRepresentation::SS(self.next() as i16),
+            "SV" => return VisualRepresentation::SV(self.next() as i64),
+            "UL" => return VisualRepresentation::UL(self.next() as u32),
+            "US" => return VisualRepresentation::US(self.next() as u16),
+            // Even lengths, which is what the wire gives back
+            "OB" | "UN" => {
+                let bytes = (0..self.below(32) * 2).map(|_| self.next() as u8).collect();
+                return if vr == "OB" {
+                    VisualRepresentation::OB(bytes)
+                } else {
+                    VisualRepresentation::UN(bytes)
+                };
+            }
+            "OW" => {
+                return VisualRepresentation::OW(
+                    (0..self.below(32)).map(|_| self.next() as u16).collect(),
+                )
+            }
+            "OF" => {
+                return VisualRepresentation::OF(
+                    (0..self.below(16))
+                        .map(|_| self.next() as u32 as f32 / 1024.0)
+                        .collect(),
+                )
+            }
+            "OD" => {
+                return VisualRepresentation::OD(
+                    (0..self.below(16))
+                        .map(|_| self.next() as i64 as f64 / 1e6)
+                        .collect(),
+                )
+            }
+            "OL" => {
+                return VisualRepresentation::OL(
+                    (0..self.below(16)).map(|_| self.next() as u32).collect(),
+                )
+            }
+            "OV" => {
+                return VisualRepresentation::OV(
+                    (0..self.below(16)).map(|_| self.next() as i64).collect(),
+                )
+            }
+            _ => return VisualRepresentation::empty(vr),
+        };
+
+        VisualRepresentation::from_string(vr, &text)
+    }
+
+    // Characters from the set, with no leading or trailing space
+    fn string(&mut self, characters: &[u8], min: usize, max: usize) -> String {
+        let length = min + self.below((max - min + 1) as u64) as usize;
+        let mut text: String = (0..length)
+            .map(|_| characters[self.below(characters.len() as u64) as usize] as char)
+            .collect();
+        text = text.trim().to_string();
+        if text.is_empty() {
+            text.push(characters[0] as char);
+        }
+        text
+    }
+
+    fn multiple(&mut self, mut value: impl FnMut(&mut Self) -> String) -> String {
+        let count = 1 + self.below(3);
+        (0..count)
+            .map(|_| value(self))
+            .collect::<Vec<_>>()
+            .join("\\")
+    }
+}
+
+// Elements of the two datasets which differ, ignoring the documented
+// exceptions. Each difference is described with its tag path
+pub fn differences(expected: &Dataset, actual: &Dataset) -> Vec<String> {
+    let mut found = vec![];
+    compare(expected, actual, "", &mut found);
+    found
+}
+
+fn compare(expected: &Dataset, actual: &Dataset, path: &str, found: &mut Vec<String>) {
+    for element in expected {
+        let tag = element.tag();
+        let name = format!("{}({:04X},{:04X})", path, tag.0, tag.1);
+        let Some(other) = actual.find(tag) else {
+            found.push(format!("{} is missing", name));
+            continue;
+        };
+
+        let (value, other_value) = (element.vr(), other.vr());
+        if value.code() != other_value.code() {
+            found.push(format!(
+                "{} has VR {} instead of {}",
+                name,
+                other_value.code(),
+                value.code()
+            ));
+            continue;
+        }
+
+        if let (VisualRepresentation::SQ(_), VisualRepresentation::SQ(_)) = (&value, &other_value) {
+            let (items, other_items) = (expected.sequence(tag), actual.sequence(tag));
+            if items.len() != other_items.len() {
+                found.push(format!(
+                    "{} has {} items instead of {}",
+                    name,
+                    other_items.len(),
+                    items.len()
+                ));
+                continue;
+            }
+            for (index, (item, other_item)) in items.iter().zip(&other_items).enumerate() {
+                compare(item, other_item, &format!("{}[{}]", name, index), found);
+            }
+            continue;
+        }
+
+        let (values, other_values) = (normalized(&value), normalized(&other_value));
+        if values != other_values {
+            found.push(format!(
+                "{} is {:?} instead of {:?}",
+                name, other_values, values
+            ));
+        }
+    }
+
+    for element in actual {
+        let tag = element.tag();
+        if !expected.contains(tag) {
+            found.push(format!("{}({:04X},{:04X}) is extra", path, tag.0, tag.1));
+        }
+    }
+}
+
+// Values in the form the comparison treats as equal
+fn normalized(value: &VisualRepresentation) -> Vec<String> {
+    match value {
+        VisualRepresentation::Empty(_) => vec![],
+        VisualRepresentation::OB(bytes) | VisualRepresentation::UN(bytes) => {
+            bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+        }
+        VisualRepresentation::OD(_)
+        | VisualRepresentation::OF(_)
+        | VisualRepresentation::OL(_)
+        | VisualRepresentation::OV(_)
+        | VisualRepresentation::OW(_) => value
+            .to_string()
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+        _ => {
+            let text = value.to_string();
+            let text = text.trim_end_matches([' ', '\0']);
+            if text.is_empty() {
+                return vec![];
+            }
+            text.split('\\')
+                .map(|part| {
+                    let part = part.trim();
+                    match value {
+                        VisualRepresentation::DS(_) => part
+                            .parse::<f64>()
+                            .map(|number| number.to_string())
+                            .unwrap_or_else(|_| part.to_string()),
+                        VisualRepresentation::IS(_) => part
+                            .parse::<i64>()
+                            .map(|number| number.to_string())
+                            .unwrap_or_else(|_| part.to_string()),
+                        _ => part.to_string(),
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
+// Encodes the dataset, then takes what was read back through JSON and the
+// binary encoding again. Gives the dataset first read from the wire and the
+// one at the end of the round trip
+pub fn json_round_trip(
+    dataset: &Dataset,
+    transfer_syntax: &str,
+) -> DicomResult<(Dataset, Dataset)> {
+    let decoded = reader::read_dataset(
+        &writer::write_dataset(dataset, transfer_syntax)?,
+        transfer_syntax,
+    )?;
+    let from_json = json::from_slice(&json::to_vec(&decoded)?)?;
+    let back = reader::read_dataset(
+        &writer::write_dataset(&from_json, transfer_syntax)?,
+        transfer_syntax,
+    )?;
+    Ok((decoded, back))
+}
+
+pub fn assert_json_round_trip(dataset: &Dataset, transfer_syntax: &str) -> DicomResult<()> {
+    let (decoded, back) = json_round_trip(dataset, transfer_syntax)?;
+    let found = differences(&decoded, &back);
+    if found.is_empty() {
+        return Ok(());
+    }
+
+    Err(DicomError::ValidationFailed(format!(
+        "Binary -> JSON -> binary changed the dataset: {}",
+        found.join("; ")
+    )))
+}
+
+// Property check over generated datasets, naming the first failing seed
+pub fn check_json_round_trips(seeds: Range<u64>, transfer_syntax: &str) -> DicomResult<()> {
+    for seed in seeds {
+        let dataset = DatasetGenerator::new(seed).generate();
+        assert_json_round_trip(&dataset, transfer_syntax)
+            .map_err(|error| DicomError::ValidationFailed(format!("Seed {}: {}", seed, error)))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::transfer_syntax;
+
+    #[test]
+    fn generated_datasets_survive_json() {
+        for syntax in [
+            transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN,
+            transfer_syntax::EXPLICIT_VR_BIG_ENDIAN,
+        ] {
+            if let Err(error) = check_json_round_trips(0..300, syntax) {
+                panic!("{}: {}", syntax, error);
+            }
+        }
+    }
+}
@@ -0,0 +1,535 @@
+use std::collections::BTreeSet;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use sha1::{Digest, Sha1};
+
+use crate::{
+    core::{
+        dataset::Dataset,
+        error::{DicomError, DicomResult},
+        transfer_syntax, uid, writer,
+    },
+    sr::{
+        kos::{self, KeyObjectSelection, SelectedInstance},
+        Code,
+    },
+};
+
+pub const DICOM_MIME_TYPE: &str = "application/dicom";
+pub const DCM_CODING_SCHEME_OID: &str = "1.2.840.10008.2.16.4";
+pub const SOP_CLASS_CODING_SCHEME_OID: &str = "1.2.840.10008.2.6.1";
+pub const MODALITY: (u16, u16) = (0x0008, 0x0060);
+
+// ebRIM identifiers of the XDS metadata, ITI TF-3 4.2.5
+pub const DOCUMENT_ENTRY: &str = "urn:uuid:7edca82f-054d-47f2-a032-9b2a5b5186c1";
+pub const DOCUMENT_ENTRY_AUTHOR: &str = "urn:uuid:93606bcf-9494-43ec-9b4e-a7748d1a838d";
+pub const DOCUMENT_ENTRY_CLASS_CODE: &str = "urn:uuid:41a5887f-8865-4c09-adf7-e362475b143a";
+pub const DOCUMENT_ENTRY_CONFIDENTIALITY_CODE: &str =
+    "urn:uuid:f4f85eac-e6cb-4883-b524-f2705394840f";
+pub const DOCUMENT_ENTRY_EVENT_CODE_LIST: &str = "urn:uuid:2c6b8cb7-8b2a-4051-b291-b1ae6a575ef4";
+pub const DOCUMENT_ENTRY_FORMAT_CODE: &str = "urn:uuid:a09d5840-386c-46f2-b5ad-9c3699a4309d";
+pub const DOCUMENT_ENTRY_HEALTHCARE_FACILITY_TYPE_CODE: &str =
+    "urn:uuid:f33fb8ac-18af-42cc-ae0e-ed0b0bdb91e1";
+pub const DOCUMENT_ENTRY_PRACTICE_SETTING_CODE: &str =
+    "urn:uuid:cccf5598-8b07-4b77-a05e-ae952c785ead";
+pub const DOCUMENT_ENTRY_TYPE_CODE: &str = "urn:uuid:f0306f51-975f-434e-a61c-c59651d33983";
+pub const DOCUMENT_ENTRY_PATIENT_ID: &str = "urn:uuid:58a6f841-87b3-4a3e-92fd-a8ffeff98427";
+pub const DOCUMENT_ENTRY_UNIQUE_ID: &str = "urn:uuid:2e82c1f6-a085-4c72-9da3-8640a32e42ab";
+pub const SUBMISSION_SET: &str = "urn:uuid:a54d6aa5-d40d-43f9-88c5-b4633d873bdd";
+pub const SUBMISSION_SET_AUTHOR: &str = "urn:uuid:a7058bb9-b4e4-4307-ba5b-e3f0ab85e12d";
+pub const SUBMISSION_SET_CONTENT_TYPE_CODE: &str = "urn:uuid:aa543740-bdda-424e-8c96-df4873be8500";
+pub const SUBMISSION_SET_PATIENT_ID: &str = "urn:uuid:6b5aea1a-874d-4603-a4bc-96a0a7b38446";
+pub const SUBMISSION_SET_SOURCE_ID: &str = "urn:uuid:554ac39e-e3fe-47fe-b233-965d2a147832";
+pub const SUBMISSION_SET_UNIQUE_ID: &str = "urn:uuid:96fdda7c-d067-4183-912e-bf5ee74998a8";
+pub const HAS_MEMBER: &str = "urn:oasis:names:tc:ebxml-regrep:AssociationType:HasMember";
+
+const DOCUMENT_ID: &str = "Document01";
+const SUBMISSION_SET_ID: &str = "SubmissionSet01";
+
+// Affinity domain values of the submission. The codes default to the ones
+// most XDS-I.b domains use for imaging manifests
+#[derive(Debug, Clone)]
+pub struct XdsMetadata {
+    // Affinity domain patient ID in CX form, e.g. 1234^^^&1.2.3&ISO
+    pub patient_id: String,
+    pub source_patient_id: Option<String>,
+    // OID of the imaging document source
+    pub source_id: String,
+    pub class_code: Code,
+    pub type_code: Code,
+    pub confidentiality_code: Code,
+    pub healthcare_facility_type_code: Code,
+    pub practice_setting_code: Code,
+    pub content_type_code: Code,
+    pub language_code: String,
+    pub author_institution: Option<String>,
+    pub author_person: Option<String>,
+}
+
+impl XdsMetadata {
+    pub fn new(patient_id: &str, source_id: &str) -> Self {
+        let imaging_study = Code::new(
+            "18748-4",
+            "2.16.840.1.113883.6.1",
+            "Diagnostic imaging study",
+        );
+        XdsMetadata {
+            patient_id: patient_id.to_string(),
+            source_patient_id: None,
+            source_id: source_id.to_string(),
+            class_code: imaging_study.clone(),
+            type_code: imaging_study.clone(),
+            confidentiality_code: Code::new("N", "2.16.840.1.113883.5.25", "Normal"),
+            healthcare_facility_type_code: Code::new(
+                "RADDX",
+                "2.16.840.1.113883.5.111",
+                "Radiology diagnostics or therapeutics unit",
+            ),
+            practice_setting_code: Code::new("394914008", "2.16.840.1.113883.6.96", "Radiology"),
+            content_type_code: imaging_study,
+            language_code: "en-US".to_string(),
+            author_institution: None,
+            author_person: None,
+        }
+    }
+
+    pub fn with_source_patient_id(mut self, source_patient_id: &str) -> Self {
+        self.source_patient_id = Some(source_patient_id.to_string());
+        self
+    }
+
+    pub fn with_class_code(mut self, code: Code) -> Self {
+        self.class_code = code;
+        self
+    }
+
+    pub fn with_type_code(mut self, code: Code) -> Self {
+        self.type_code = code;
+        self
+    }
+
+    pub fn with_confidentiality_code(mut self, code: Code) -> Self {
+        self.confidentiality_code = code;
+        self
+    }
+
+    pub fn with_healthcare_facility_type_code(mut self, code: Code) -> Self {
+        self.healthcare_facility_type_code = code;
+        self
+    }
+
+    pub fn with_practice_setting_code(mut self, code: Code) -> Self {
+        self.practice_setting_code = code;
+        self
+    }
+
+    pub fn with_content_type_code(mut self, code: Code) -> Self {
+        self.content_type_code = code;
+        self
+    }
+
+    pub fn with_language_code(mut self, language_code: &str) -> Self {
+        self.language_code = language_code.to_string();
+        self
+    }
+
+    pub fn with_author(mut self, institution: &str, person: &str) -> Self {
+        self.author_institution = Some(institution.to_string());
+        self.author_person = Some(person.to_string());
+        self
+    }
+}
+
+// Imaging manifest of RAD TF-3 4.68 referencing every instance, all of which
+// must belong to one patient
+pub fn manifest(
+    instances: &[Dataset],
+    retrieve_location_uid: &str,
+) -> DicomResult<KeyObjectSelection> {
+    let first = instances.first().ok_or_else(|| {
+        DicomError::InvalidDataset("An imaging manifest needs at least one instance".to_string())
+    })?;
+
+    let mut manifest = KeyObjectSelection::for_study(kos::manifest(), first)?;
+    for instance in instances {
+        let patient_id = instance.string(kos::PATIENT_ID).unwrap_or_default();
+        if patient_id != manifest.patient_id {
+            return Err(DicomError::InvalidDataset(format!(
+                "Instances of patients {} and {} cannot share a manifest",
+                manifest.patient_id, patient_id
+            )));
+        }
+        manifest.select(
+            SelectedInstance::from_dataset(instance)?
+                .with_retrieve_location_uid(retrieve_location_uid),
+        );
+    }
+
+    Ok(manifest)
+}
+
+// Distinct acquisition modalities as event codes of the DCM scheme
+pub fn modality_codes(instances: &[Dataset]) -> Vec<Code> {
+    let modalities: BTreeSet<String> = instances
+        .iter()
+        .filter_map(|instance| instance.string(MODALITY))
+        .filter(|modality| !matches!(modality.as_str(), "" | "KO" | "PR" | "SR"))
+        .collect();
+
+    modalities
+        .into_iter()
+        .map(|modality| {
+            let meaning = match modality.as_str() {
+                "CR" => "Computed Radiography",
+                "CT" => "Computed Tomography",
+                "DX" => "Digital Radiography",
+                "MG" => "Mammography",
+                "MR" => "Magnetic Resonance",
+                "NM" => "Nuclear Medicine",
+                "PT" => "Positron emission tomography",
+                "RF" => "Radio Fluoroscopy",
+                "US" => "Ultrasound",
+                "XA" => "X-Ray Angiography",
+                "IO" => "Intra-oral Radiography",
+                "OT" => "Other",
+                other => other,
+            };
+            Code::new(&modality, DCM_CODING_SCHEME_OID, meaning)
+        })
+        .collect()
+}
+
+// Provide and Register Document Set-b request carrying an imaging manifest
+#[derive(Debug, Clone)]
+pub struct XdsSubmission {
+    pub metadata: XdsMetadata,
+    pub manifest: KeyObjectSelection,
+    // Part 10 encoding of the manifest
+    pub document: Vec<u8>,
+    pub hash: String,
+    pub event_codes: Vec<Code>,
+    pub submission_set_unique_id: String,
+    pub submission_time: String,
+}
+
+impl XdsSubmission {
+    pub fn new(manifest: KeyObjectSelection, metadata: XdsMetadata) -> DicomResult<Self> {
+        let document = writer::write_file(
+            &manifest.to_dataset()?,
+            transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN,
+        )?;
+
+        let mut hasher = Sha1::new();
+        hasher.update(&document);
+        let hash = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        Ok(XdsSubmission {
+            metadata,
+            manifest,
+            document,
+            hash,
+            event_codes: Vec::new(),
+            submission_set_unique_id: uid::generate(),
+            submission_time: Utc::now().format("%Y%m%d%H%M%S").to_string(),
+        })
+    }
+
+    // Manifest of the instances with their modalities as event codes
+    pub fn from_instances(
+        instances: &[Dataset],
+        retrieve_location_uid: &str,
+        metadata: XdsMetadata,
+    ) -> DicomResult<Self> {
+        let mut submission =
+            XdsSubmission::new(manifest(instances, retrieve_location_uid)?, metadata)?;
+        submission.event_codes = modality_codes(instances);
+        Ok(submission)
+    }
+
+    pub fn with_event_code(mut self, code: Code) -> Self {
+        if !self.event_codes.contains(&code) {
+            self.event_codes.push(code);
+        }
+        self
+    }
+
+    // XDSDocumentEntry.uniqueId is the manifest's SOP Instance UID
+    pub fn document_unique_id(&self) -> &str {
+        &self.manifest.sop_instance_uid
+    }
+
+    // Request with the manifest inline, for registries not requiring MTOM
+    pub fn to_xml(&self) -> String {
+        let mut rim = Rim::default();
+        rim.push(
+            "<xdsb:ProvideAndRegisterDocumentSetRequest xmlns:xdsb=\"urn:ihe:iti:xds-b:2007\">",
+        );
+        rim.push(
+            "<lcm:SubmitObjectsRequest xmlns:lcm=\"urn:oasis:names:tc:ebxml-regrep:xsd:lcm:3.0\">",
+        );
+        rim.push(
+            "<rim:RegistryObjectList xmlns:rim=\"urn:oasis:names:tc:ebxml-regrep:xsd:rim:3.0\">",
+        );
+        self.document_entry(&mut rim);
+        self.submission_set(&mut rim);
+        let id = rim.next_id("Association");
+        rim.push(&format!(
+            "<rim:Association id=\"{}\" associationType=\"{}\" sourceObject=\"{}\" targetObject=\"{}\">",
+            id,
+            HAS_MEMBER,
+            SUBMISSION_SET_ID,
+            DOCUMENT_ID
+        ));
+        rim.slot("SubmissionSetStatus", &["Original"]);
+        rim.push("</rim:Association>");
+        rim.push("</rim:RegistryObjectList>");
+        rim.push("</lcm:SubmitObjectsRequest>");
+        rim.push(&format!(
+            "<xdsb:Document id=\"{}\">{}</xdsb:Document>",
+            DOCUMENT_ID,
+            STANDARD.encode(&self.document)
+        ));
+        rim.push("</xdsb:ProvideAndRegisterDocumentSetRequest>");
+        rim.xml
+    }
+
+    fn document_entry(&self, rim: &mut Rim) {
+        let manifest = &self.manifest;
+        let metadata = &self.metadata;
+
+        rim.push(&format!(
+            "<rim:ExtrinsicObject id=\"{}\" mimeType=\"{}\" objectType=\"{}\">",
+            DOCUMENT_ID, DICOM_MIME_TYPE, DOCUMENT_ENTRY
+        ));
+        rim.slot("creationTime", &[&self.submission_time]);
+        rim.slot("hash", &[&self.hash]);
+        rim.slot("languageCode", &[&metadata.language_code]);
+        rim.slot("size", &[&self.document.len().to_string()]);
+        if !manifest.study_date.is_empty() {
+            let time: String = manifest.study_time.chars().take(6).collect();
+            rim.slot(
+                "serviceStartTime",
+                &[&format!("{}{}", manifest.study_date, time)],
+            );
+        }
+        rim.slot(
+            "sourcePatientId",
+            &[metadata
+                .source_patient_id
+                .as_deref()
+                .unwrap_or(&metadata.patient_id)],
+        );
+
+        let mut patient_info = vec![format!("PID-5|{}", manifest.patient_name)];
+        if !manifest.patient_birth_date.is_empty() {
+            patient_info.push(format!("PID-7|{}", manifest.patient_birth_date));
+        }
+        if !manifest.patient_sex.is_empty() {
+            patient_info.push(format!("PID-8|{}", manifest.patient_sex));
+        }
+        let patient_info: Vec<&str> = patient_info.iter().map(String::as_str).collect();
+        rim.slot("sourcePatientInfo", &patient_info);
+
+        // Studies and accession numbers the manifest covers, for XDS-I.b queries
+        let studies: BTreeSet<&str> = manifest
+            .instances
+            .iter()
+            .map(|instance| instance.study_instance_uid.as_str())
+            .collect();
+        let mut references: Vec<String> = studies
+            .into_iter()
+            .map(|study| format!("{}^^^^urn:ihe:iti:xds:2016:studyInstanceUID", study))
+            .collect();
+        if !manifest.accession_number.is_empty() {
+            references.push(format!(
+                "{}^^^^urn:ihe:iti:xds:2013:accession",
+                manifest.accession_number
+            ));
+        }
+        let references: Vec<&str> = references.iter().map(String::as_str).collect();
+        rim.slot("urn:ihe:iti:xds:2013:referenceIdList", &references);
+
+        rim.name(&manifest.title.meaning);
+        self.author(rim, DOCUMENT_ENTRY_AUTHOR, DOCUMENT_ID);
+        rim.code(DOCUMENT_ENTRY_CLASS_CODE, DOCUMENT_ID, &metadata.class_code);
+        rim.code(
+            DOCUMENT_ENTRY_CONFIDENTIALITY_CODE,
+            DOCUMENT_ID,
+            &metadata.confidentiality_code,
+        );
+        for code in &self.event_codes {
+            rim.code(DOCUMENT_ENTRY_EVENT_CODE_LIST, DOCUMENT_ID, code);
+        }
+        rim.code(
+            DOCUMENT_ENTRY_FORMAT_CODE,
+            DOCUMENT_ID,
+            &Code::new(
+                kos::KEY_OBJECT_SELECTION_DOCUMENT_SOP_CLASS,
+                SOP_CLASS_CODING_SCHEME_OID,
+                "Key Object Selection Document",
+            ),
+        );
+        rim.code(
+            DOCUMENT_ENTRY_HEALTHCARE_FACILITY_TYPE_CODE,
+            DOCUMENT_ID,
+            &metadata.healthcare_facility_type_code,
+        );
+        rim.code(
+            DOCUMENT_ENTRY_PRACTICE_SETTING_CODE,
+            DOCUMENT_ID,
+            &metadata.practice_setting_code,
+        );
+        rim.code(DOCUMENT_ENTRY_TYPE_CODE, DOCUMENT_ID, &metadata.type_code);
+        rim.identifier(
+            DOCUMENT_ENTRY_PATIENT_ID,
+            DOCUMENT_ID,
+            &metadata.patient_id,
+            "XDSDocumentEntry.patientId",
+        );
+        rim.identifier(
+            DOCUMENT_ENTRY_UNIQUE_ID,
+            DOCUMENT_ID,
+            self.document_unique_id(),
+            "XDSDocumentEntry.uniqueId",
+        );
+        rim.push("</rim:ExtrinsicObject>");
+    }
+
+    fn submission_set(&self, rim: &mut Rim) {
+        let metadata = &self.metadata;
+
+        rim.push(&format!(
+            "<rim:RegistryPackage id=\"{}\">",
+            SUBMISSION_SET_ID
+        ));
+        rim.slot("submissionTime", &[&self.submission_time]);
+        rim.name(&self.manifest.title.meaning);
+        self.author(rim, SUBMISSION_SET_AUTHOR, SUBMISSION_SET_ID);
+        rim.code(
+            SUBMISSION_SET_CONTENT_TYPE_CODE,
+            SUBMISSION_SET_ID,
+            &metadata.content_type_code,
+        );
+        rim.identifier(
+            SUBMISSION_SET_UNIQUE_ID,
+            SUBMISSION_SET_ID,
+            &self.submission_set_unique_id,
+            "XDSSubmissionSet.uniqueId",
+        );
+        rim.identifier(
+            SUBMISSION_SET_SOURCE_ID,
+            SUBMISSION_SET_ID,
+            &metadata.source_id,
+            "XDSSubmissionSet.sourceId",
+        );
+        rim.identifier(
+            SUBMISSION_SET_PATIENT_ID,
+            SUBMISSION_SET_ID,
+            &metadata.patient_id,
+            "XDSSubmissionSet.patientId",
+        );
+        rim.push("</rim:RegistryPackage>");
+        let id = rim.next_id("Classification");
+        rim.push(&format!(
+            "<rim:Classification id=\"{}\" classifiedObject=\"{}\" classificationNode=\"{}\"/>",
+            id, SUBMISSION_SET_ID, SUBMISSION_SET
+        ));
+    }
+
+    fn author(&self, rim: &mut Rim, scheme: &str, object: &str) {
+        let metadata = &self.metadata;
+        if metadata.author_institution.is_none() && metadata.author_person.is_none() {
+            return;
+        }
+
+        let id = rim.next_id("Classification");
+        rim.push(&format!(
+            "<rim:Classification id=\"{}\" classificationScheme=\"{}\" classifiedObject=\"{}\" nodeRepresentation=\"\">",
+            id,
+            scheme,
+            object
+        ));
+        if let Some(institution) = &metadata.author_institution {
+            rim.slot("authorInstitution", &[institution]);
+        }
+        if let Some(person) = &metadata.author_person {
+            rim.slot("authorPerson", &[person]);
+        }
+        rim.push("</rim:Classification>");
+    }
+}
+
+// ebRIM writer numbering its classification and identifier ids
+#[derive(Default)]
+struct Rim {
+    xml: String,
+    ids: usize,
+}
+
+impl Rim {
+    fn push(&mut self, xml: &str) {
+        self.xml.push_str(xml);
+    }
+
+    fn next_id(&mut self, kind: &str) -> String {
+        self.ids += 1;
+        format!("{}{:02}", kind, self.ids)
+    }
+
+    fn slot(&mut self, name: &str, values: &[&str]) {
+        self.push(&format!(
+            "<rim:Slot name=\"{}\"><rim:ValueList>",
+            escape(name)
+        ));
+        for value in values {
+            self.push(&format!("<rim:Value>{}</rim:Value>", escape(value)));
+        }
+        self.push("</rim:ValueList></rim:Slot>");
+    }
+
+    fn name(&mut self, name: &str) {
+        self.push(&format!(
+            "<rim:Name><rim:LocalizedString value=\"{}\"/></rim:Name>",
+            escape(name)
+        ));
+    }
+
+    fn code(&mut self, scheme: &str, object: &str, code: &Code) {
+        let id = self.next_id("Classification");
+        self.push(&format!(
+            "<rim:Classification id=\"{}\" classificationScheme=\"{}\" classifiedObject=\"{}\" nodeRepresentation=\"{}\">",
+            id,
+            scheme,
+            object,
+            escape(&code.value)
+        ));
+        self.slot("codingScheme", &[&code.scheme]);
+        self.name(&code.meaning);
+        self.push("</rim:Classification>");
+    }
+
+    fn identifier(&mut self, scheme: &str, object: &str, value: &str, name: &str) {
+        let id = self.next_id("ExternalIdentifier");
+        self.push(&format!(
+            "<rim:ExternalIdentifier id=\"{}\" identificationScheme=\"{}\" registryObject=\"{}\" value=\"{}\">",
+            id,
+            scheme,
+            object,
+            escape(value)
+        ));
+        self.name(name);
+        self.push("</rim:ExternalIdentifier>");
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -6,6 +6,9 @@
 // Core module always included
 pub mod core;
 
+// Structured reporting, needs nothing beyond core
+pub mod sr;
+
 #[cfg(any(
     feature = "image", 
     feature = "default"
@@ -14,6 +17,7 @@ pub mod image;
 
 #[cfg(any(
     all(feature = "serde", feature = "compress", feature = "images"),
+    feature = "wasm",
     feature = "default"
 ))]
 pub mod interop;
@@ -24,6 +28,13 @@ pub mod interop;
 ))]
 pub mod mods;
 
+// Radiotherapy objects, reading dose grids through the pixel data of image
+#[cfg(any(
+    feature = "image", 
+    feature = "default"
+))]
+pub mod rt;
+
 #[cfg(any(
     feature = "net", 
     feature = "default"
@@ -32,18 +43,26 @@ pub mod net;
 
 #[cfg(any(
     all(feature = "net", feature = "serde", feature = "compress"),
+    feature = "image",
     feature = "default"
 ))]
 pub mod plugins;
 
 #[cfg(any(
-    feature = "cli",
+    feature = "storage",
+    feature = "default"
+))]
+pub mod storage;
+
+#[cfg(any(
+    all(feature = "cli", feature = "net", feature = "config"),
     feature = "default"
 ))]
 pub mod tools;
 
 #[cfg(any(
     all(feature = "cli", feature = "compress", feature = "log", feature = "fs"),
+    feature = "config",
     feature = "default"
 ))]
 pub mod utils;
@@ -55,9 +74,13 @@ pub mod utils;
 pub mod validation;
 
 #[cfg(any(
-    all(feature = "net", feature = "secure"),
+    all(feature = "net", feature = "secure", feature = "serde"),
     feature = "default"
 ))]
 pub mod web;
 
+// Browser bindings, build with --no-default-features --features wasm
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
 pub use core::*;
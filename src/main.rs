@@ -0,0 +1,48 @@
+use clap::{error::ErrorKind, CommandFactory, Parser};
+
+use dicom::tools::{
+    self,
+    output::{self, FailureClass, OutputFormat},
+    Cli,
+};
+use dicom::utils::config::Config;
+
+#[cfg(any(feature = "log", feature = "default"))]
+fn init_logging(config: &Config) {
+    let level = config
+        .log_level
+        .as_deref()
+        .and_then(|level| level.parse::<tracing::Level>().ok());
+    if let Some(level) = level {
+        tracing_subscriber::fmt().with_max_level(level).init();
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let format = if cli.json {
+        OutputFormat::Json
+    } else {
+        OutputFormat::Text
+    };
+    if cli.json && !cli.command.has_json_output() {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--json is not available for interactive commands",
+            )
+            .exit();
+    }
+
+    let config = Config::load().unwrap_or_else(|error| {
+        output::print_error(&error, format);
+        std::process::exit(FailureClass::of(&error).exit_code());
+    });
+    #[cfg(any(feature = "log", feature = "default"))]
+    init_logging(&config);
+
+    if let Err(error) = tools::run(cli.command, format, &config) {
+        output::print_error(&error, format);
+        std::process::exit(FailureClass::of(&error).exit_code());
+    }
+}
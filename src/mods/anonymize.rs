@@ -0,0 +1,727 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    fmt::Display,
+    hash::{BuildHasher, Hasher},
+    rc::Rc,
+};
+
+use chrono::{NaiveDate, NaiveTime, TimeDelta, Timelike};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::core::{
+    dataset::Dataset,
+    datetime::DicomDateTime,
+    dictionary,
+    element::DicomElement,
+    error::{DicomResult, TagPath, TagPathStep},
+    tag::VisualRepresentation,
+    uid,
+};
+
+pub const ACCESSION_NUMBER: (u16, u16) = (0x0008, 0x0050);
+pub const REFERRING_PHYSICIAN_NAME: (u16, u16) = (0x0008, 0x0090);
+pub const PATIENT_NAME: (u16, u16) = (0x0010, 0x0010);
+pub const PATIENT_ID: (u16, u16) = (0x0010, 0x0020);
+pub const PATIENT_BIRTH_DATE: (u16, u16) = (0x0010, 0x0030);
+pub const PATIENT_AGE: (u16, u16) = (0x0010, 0x1010);
+pub const STUDY_DATE: (u16, u16) = (0x0008, 0x0020);
+pub const LONGITUDINAL_TEMPORAL_INFORMATION_MODIFIED: (u16, u16) = (0x0028, 0x0303);
+pub const STUDY_ID: (u16, u16) = (0x0020, 0x0010);
+pub const PATIENT_IDENTITY_REMOVED: (u16, u16) = (0x0012, 0x0062);
+pub const DEIDENTIFICATION_METHOD: (u16, u16) = (0x0012, 0x0063);
+
+// HIPAA safe harbor aggregates all ages above into 90
+pub const MAXIMUM_AGE: u32 = 89;
+
+// Attributes of PS3.15 E.1 the basic profile removes
+pub const REMOVED: [(u16, u16); 22] = [
+    (0x0008, 0x0080), // Institution Name
+    (0x0008, 0x0081), // Institution Address
+    (0x0008, 0x0092), // Referring Physician's Address
+    (0x0008, 0x0094), // Referring Physician's Telephone Numbers
+    (0x0008, 0x1010), // Station Name
+    (0x0008, 0x1040), // Institutional Department Name
+    (0x0008, 0x1048), // Physician(s) of Record
+    (0x0008, 0x1050), // Performing Physician's Name
+    (0x0008, 0x1060), // Name of Physician(s) Reading Study
+    (0x0008, 0x1070), // Operators' Name
+    (0x0010, 0x1000), // Other Patient IDs
+    (0x0010, 0x1001), // Other Patient Names
+    (0x0010, 0x1002), // Other Patient IDs Sequence
+    (0x0010, 0x1040), // Patient's Address
+    (0x0010, 0x1060), // Patient's Mother's Birth Name
+    (0x0010, 0x1090), // Medical Record Locator
+    (0x0010, 0x2154), // Patient's Telephone Numbers
+    (0x0010, 0x21B0), // Additional Patient History
+    (0x0010, 0x4000), // Patient Comments
+    (0x0018, 0x1000), // Device Serial Number
+    (0x0032, 0x1032), // Requesting Physician
+    (0x0040, 0xA730), // Content Sequence
+];
+
+// Attributes kept but emptied
+pub const EMPTIED: [(u16, u16); 3] = [ACCESSION_NUMBER, REFERRING_PHYSICIAN_NAME, STUDY_ID];
+
+// UIDs replaced consistently, wherever they appear
+pub const REPLACED_UIDS: [(u16, u16); 7] = [
+    (0x0008, 0x0018), // SOP Instance UID
+    (0x0008, 0x1155), // Referenced SOP Instance UID
+    (0x0020, 0x000D), // Study Instance UID
+    (0x0020, 0x000E), // Series Instance UID
+    (0x0020, 0x0052), // Frame of Reference UID
+    (0x0020, 0x0200), // Synchronization Frame of Reference UID
+    (0x0008, 0x3010), // Irradiation Event UID
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PatientIdentity {
+    pub patient_id: String,
+    pub patient_name: String,
+    pub birth_date: String,
+}
+
+impl PatientIdentity {
+    pub fn from_dataset(dataset: &Dataset) -> Self {
+        PatientIdentity {
+            patient_id: dataset.string(PATIENT_ID).unwrap_or_default(),
+            patient_name: dataset.string(PATIENT_NAME).unwrap_or_default(),
+            birth_date: dataset.string(PATIENT_BIRTH_DATE).unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pseudonym {
+    pub patient_id: String,
+    pub patient_name: String,
+    // None leaves the birth date empty
+    pub birth_date: Option<String>,
+}
+
+// Source of pseudonyms, e.g. a site's trusted third party service. It is
+// asked once per patient and anonymizer
+pub trait PseudonymProvider {
+    fn pseudonym(&self, identity: &PatientIdentity) -> DicomResult<Pseudonym>;
+}
+
+// Built-in replacements: an HMAC of the patient ID as ID and name, and no
+// birth date. Without the key, pseudonyms cannot be reversed by trying
+// patient IDs
+#[derive(Debug, Clone)]
+pub struct HashedPseudonyms {
+    pub key: Vec<u8>,
+}
+
+impl HashedPseudonyms {
+    pub fn new(key: &[u8]) -> Self {
+        HashedPseudonyms { key: key.to_vec() }
+    }
+}
+
+impl PseudonymProvider for HashedPseudonyms {
+    fn pseudonym(&self, identity: &PatientIdentity) -> DicomResult<Pseudonym> {
+        let digest = keyed_digest(&self.key, identity.patient_id.as_bytes());
+        let hex: String = digest[..16]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        let pseudonym = format!("ANON{}", hex);
+
+        Ok(Pseudonym {
+            patient_id: pseudonym.clone(),
+            patient_name: pseudonym,
+            birth_date: None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyKind {
+    Uid,
+    Pseudonym,
+    // Offset in days of the dates of a patient
+    DateShift,
+}
+
+impl KeyKind {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "UID" => Some(KeyKind::Uid),
+            "PSEUDONYM" => Some(KeyKind::Pseudonym),
+            "DATE_SHIFT" => Some(KeyKind::DateShift),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyKind::Uid => "UID",
+            KeyKind::Pseudonym => "PSEUDONYM",
+            KeyKind::DateShift => "DATE_SHIFT",
+        }
+    }
+}
+
+impl Display for KeyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// Keeps the mappings an anonymizer makes, so data can be re-identified
+// later, e.g. key_log::KeyLog. The original of a pseudonym or date shift is
+// the patient ID
+pub trait KeyRecorder {
+    fn record(&mut self, kind: KeyKind, original: &str, replacement: &str) -> DicomResult<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeOfDay {
+    Keep,
+    // Hours only, minutes and seconds zeroed
+    Truncate,
+    Remove,
+}
+
+// Retain Longitudinal Temporal Information with Modified Dates option of
+// PS3.15 E.3.6: every date of a patient moves back by the same number of
+// days, so intervals between studies survive
+#[derive(Debug, Clone)]
+pub struct DateShift {
+    pub max_days: u32,
+    pub time_of_day: TimeOfDay,
+    // Offsets derive from the patient ID and this secret, a random one unless
+    // runs have to agree
+    pub secret: Vec<u8>,
+}
+
+impl DateShift {
+    pub fn new(max_days: u32) -> Self {
+        DateShift {
+            max_days: max_days.max(1),
+            time_of_day: TimeOfDay::Keep,
+            secret: random_key(),
+        }
+    }
+
+    pub fn with_time_of_day(mut self, time_of_day: TimeOfDay) -> Self {
+        self.time_of_day = time_of_day;
+        self
+    }
+
+    pub fn with_secret(mut self, secret: &[u8]) -> Self {
+        self.secret = secret.to_vec();
+        self
+    }
+
+    // Between 1 and max_days days into the past
+    pub fn offset(&self, patient_id: &str) -> i64 {
+        let digest = keyed_digest(&self.secret, patient_id.as_bytes());
+        let value = u64::from_be_bytes(digest[..8].try_into().unwrap());
+        -((value % u64::from(self.max_days)) as i64 + 1)
+    }
+
+    fn time(&self, time: NaiveTime) -> Option<NaiveTime> {
+        match self.time_of_day {
+            TimeOfDay::Keep => Some(time),
+            TimeOfDay::Truncate => NaiveTime::from_hms_opt(time.hour(), 0, 0),
+            TimeOfDay::Remove => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Remove,
+    Empty,
+    Replace,
+    ReplaceUid,
+    Shift,
+    Add,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Remove => "remove",
+            Action::Empty => "empty",
+            Action::Replace => "replace",
+            Action::ReplaceUid => "replace UID",
+            Action::Shift => "shift",
+            Action::Add => "add",
+        }
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// One change a dry run found, with the rule of the profile behind it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedChange {
+    pub path: TagPath,
+    pub action: Action,
+    // Summary of the original value, None for added attributes
+    pub before: Option<String>,
+    pub reason: String,
+}
+
+impl PlannedChange {
+    pub fn keyword(&self) -> Option<&'static str> {
+        dictionary::keyword(self.path.tag()?)
+    }
+}
+
+impl Display for PlannedChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.path, self.keyword().unwrap_or("Unknown"))?;
+        write!(f, " {}", self.action)?;
+        if let Some(before) = &self.before {
+            write!(f, " \"{}\"", before)?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+// Actions of the Basic Application Level Confidentiality Profile of PS3.15
+// E.1 for the attributes listed above, not the whole profile, and without its
+// pixel options. Dates and times are emptied unless shifted. UIDs and
+// pseudonyms stay consistent across all datasets one anonymizer handles
+pub struct Anonymizer {
+    provider: Box<dyn PseudonymProvider>,
+    pub remove_private: bool,
+    pub date_shift: Option<DateShift>,
+    uids: HashMap<String, String>,
+    pseudonyms: HashMap<PatientIdentity, Pseudonym>,
+    recorder: Option<Box<dyn KeyRecorder>>,
+    // Mappings not recorded yet
+    pending: Vec<(KeyKind, String, String)>,
+}
+
+impl Default for Anonymizer {
+    fn default() -> Self {
+        Anonymizer {
+            provider: Box::new(HashedPseudonyms::new(&random_key())),
+            remove_private: true,
+            date_shift: None,
+            uids: HashMap::new(),
+            pseudonyms: HashMap::new(),
+            recorder: None,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Anonymizer {
+    pub fn new() -> Self {
+        Anonymizer::default()
+    }
+
+    pub fn with_pseudonym_provider<P: PseudonymProvider + 'static>(mut self, provider: P) -> Self {
+        self.provider = Box::new(provider);
+        self.pseudonyms.clear();
+        self
+    }
+
+    pub fn with_private_tags(mut self) -> Self {
+        self.remove_private = false;
+        self
+    }
+
+    pub fn with_date_shift(mut self, date_shift: DateShift) -> Self {
+        self.date_shift = Some(date_shift);
+        self
+    }
+
+    pub fn with_key_recorder<R: KeyRecorder + 'static>(mut self, recorder: R) -> Self {
+        self.recorder = Some(Box::new(recorder));
+        self
+    }
+
+    // Replacement of an original UID, the same for every occurrence
+    pub fn uid(&mut self, original: &str) -> String {
+        if let Some(replacement) = self.uids.get(original) {
+            return replacement.clone();
+        }
+        let replacement = uid::generate();
+        self.uids.insert(original.to_string(), replacement.clone());
+        if self.recorder.is_some() {
+            self.pending
+                .push((KeyKind::Uid, original.to_string(), replacement.clone()));
+        }
+        replacement
+    }
+
+    pub fn pseudonym(&mut self, identity: &PatientIdentity) -> DicomResult<Pseudonym> {
+        if let Some(pseudonym) = self.pseudonyms.get(identity) {
+            return Ok(pseudonym.clone());
+        }
+        let pseudonym = self.provider.pseudonym(identity)?;
+        self.pseudonyms.insert(identity.clone(), pseudonym.clone());
+        if self.recorder.is_some() {
+            let patient_id = &identity.patient_id;
+            self.pending.push((
+                KeyKind::Pseudonym,
+                patient_id.clone(),
+                pseudonym.patient_id.clone(),
+            ));
+            if let Some(shift) = &self.date_shift {
+                let offset = shift.offset(patient_id).to_string();
+                self.pending
+                    .push((KeyKind::DateShift, patient_id.clone(), offset));
+            }
+        }
+        Ok(pseudonym)
+    }
+
+    // Hands the mappings made so far to the key recorder. Anonymize does so
+    // before returning, callers of uid and pseudonym call it themselves
+    pub fn record_keys(&mut self) -> DicomResult<()> {
+        let Some(recorder) = self.recorder.as_mut() else {
+            return Ok(());
+        };
+        let mut recorded = 0;
+        let result = self
+            .pending
+            .iter()
+            .try_for_each(|(kind, original, replacement)| {
+                recorder.record(*kind, original, replacement)?;
+                recorded += 1;
+                Ok(())
+            });
+        self.pending.drain(..recorded);
+        result
+    }
+
+    pub fn anonymize(&mut self, dataset: &Dataset) -> DicomResult<Dataset> {
+        let identity = PatientIdentity::from_dataset(dataset);
+        let pseudonym = self.pseudonym(&identity)?;
+        let offset = self
+            .date_shift
+            .as_ref()
+            .map(|shift| shift.offset(&identity.patient_id));
+
+        let mut anonymized = self.clean(dataset, offset);
+        anonymized.put_string(PATIENT_ID, "LO", &pseudonym.patient_id);
+        anonymized.put_string(PATIENT_NAME, "PN", &pseudonym.patient_name);
+
+        // Ages come from the original dates, which shifting leaves unchanged
+        let age = match (date(dataset, PATIENT_BIRTH_DATE), date(dataset, STUDY_DATE)) {
+            (Some(birth), Some(study)) => study.years_since(birth),
+            _ => dataset
+                .string(PATIENT_AGE)
+                .filter(|age| age.ends_with('Y'))
+                .and_then(|age| age.trim_end_matches('Y').parse().ok()),
+        };
+        if let Some(age) = age {
+            anonymized.put_string(
+                PATIENT_AGE,
+                "AS",
+                &format!("{:03}Y", age.min(MAXIMUM_AGE + 1)),
+            );
+        }
+
+        // A pseudonym's birth date wins, then the shifted one unless it
+        // would reveal an age above the cap
+        let birth_date = match (&pseudonym.birth_date, offset) {
+            (Some(birth_date), _) => birth_date.clone(),
+            (None, Some(offset)) if age.is_some_and(|age| age <= MAXIMUM_AGE) => {
+                date(dataset, PATIENT_BIRTH_DATE)
+                    .and_then(|birth| birth.checked_add_signed(TimeDelta::days(offset)))
+                    .map(|birth| birth.format("%Y%m%d").to_string())
+                    .unwrap_or_default()
+            }
+            _ => String::new(),
+        };
+        put_value(&mut anonymized, PATIENT_BIRTH_DATE, "DA", &birth_date);
+
+        if offset.is_some() {
+            anonymized.put_string(LONGITUDINAL_TEMPORAL_INFORMATION_MODIFIED, "CS", "MODIFIED");
+        }
+        anonymized.put_string(PATIENT_IDENTITY_REMOVED, "CS", "YES");
+        let method = if offset.is_some() {
+            "Pseudonymized, part of PS3.15 E.1 applied, dates shifted"
+        } else {
+            "Pseudonymized, part of PS3.15 E.1 applied, dates emptied"
+        };
+        anonymized.put_string(DEIDENTIFICATION_METHOD, "LO", method);
+        self.record_keys()?;
+        Ok(anonymized)
+    }
+
+    // What anonymize would change, for review of a profile before a batch
+    // run. The dataset is left as it is, and UIDs and pseudonyms made along
+    // the way are forgotten again without reaching the key recorder
+    pub fn preview(&mut self, dataset: &Dataset) -> DicomResult<Vec<PlannedChange>> {
+        let recorder = self.recorder.take();
+        let uids = self.uids.clone();
+        let pseudonyms = self.pseudonyms.clone();
+        let anonymized = self.anonymize(dataset).and_then(|anonymized| {
+            let pseudonym = self.pseudonym(&PatientIdentity::from_dataset(dataset))?;
+            Ok((anonymized, pseudonym))
+        });
+        self.recorder = recorder;
+        self.uids = uids;
+        self.pseudonyms = pseudonyms;
+        let (anonymized, pseudonym) = anonymized?;
+
+        let mut changes = Vec::new();
+        self.compare(
+            dataset,
+            &anonymized,
+            &pseudonym,
+            &mut TagPath::default(),
+            &mut changes,
+        );
+        for element in &anonymized {
+            let tag = element.tag();
+            if !dataset.contains(tag) {
+                let (_, reason) = self.rule(tag, &element.vr(), &pseudonym);
+                changes.push(PlannedChange {
+                    path: TagPath::new(tag),
+                    action: Action::Add,
+                    before: None,
+                    reason: reason.to_string(),
+                });
+            }
+        }
+        Ok(changes)
+    }
+
+    fn compare(
+        &self,
+        original: &Dataset,
+        anonymized: &Dataset,
+        pseudonym: &Pseudonym,
+        path: &mut TagPath,
+        changes: &mut Vec<PlannedChange>,
+    ) {
+        for element in original {
+            let tag = element.tag();
+            let before = element.vr();
+            path.steps.push(TagPathStep::Tag(tag));
+            match (&before, anonymized.value(tag)) {
+                (_, None) => {
+                    let reason = if REMOVED.contains(&tag) {
+                        "Basic profile, X"
+                    } else {
+                        "Private attribute, Retain Private Option not used"
+                    };
+                    changes.push(PlannedChange {
+                        path: path.clone(),
+                        action: Action::Remove,
+                        before: Some(summary(&before)),
+                        reason: reason.to_string(),
+                    });
+                }
+                (VisualRepresentation::SQ(items), Some(VisualRepresentation::SQ(cleaned))) => {
+                    let items = items.iter().filter_map(|item| item.dataset());
+                    let cleaned = cleaned.iter().filter_map(|item| item.dataset());
+                    for (index, (item, cleaned)) in items.zip(cleaned).enumerate() {
+                        path.steps.push(TagPathStep::Item(index));
+                        self.compare(item, cleaned, pseudonym, path, changes);
+                        path.steps.pop();
+                    }
+                }
+                (_, Some(after)) if after.to_string() != before.to_string() => {
+                    let (action, reason) = self.rule(tag, &after, pseudonym);
+                    changes.push(PlannedChange {
+                        path: path.clone(),
+                        action,
+                        before: Some(summary(&before)),
+                        reason: reason.to_string(),
+                    });
+                }
+                _ => {}
+            }
+            path.steps.pop();
+        }
+    }
+
+    // Action and rule behind a value anonymize changed or added
+    fn rule(
+        &self,
+        tag: (u16, u16),
+        after: &VisualRepresentation,
+        pseudonym: &Pseudonym,
+    ) -> (Action, &'static str) {
+        let empty = after.to_string().trim_end_matches(['\0', ' ']).is_empty();
+        match tag {
+            _ if EMPTIED.contains(&tag) => (Action::Empty, "Basic profile, Z"),
+            _ if REPLACED_UIDS.contains(&tag) => (Action::ReplaceUid, "Basic profile, U"),
+            PATIENT_ID | PATIENT_NAME => (Action::Replace, "Pseudonym of the patient"),
+            PATIENT_BIRTH_DATE if pseudonym.birth_date.is_some() => {
+                (Action::Replace, "Pseudonym of the patient")
+            }
+            PATIENT_BIRTH_DATE if empty => (Action::Empty, "Basic profile, Z"),
+            PATIENT_AGE => (
+                Action::Replace,
+                "Age of the original dates, aggregated above 89",
+            ),
+            PATIENT_IDENTITY_REMOVED
+            | DEIDENTIFICATION_METHOD
+            | LONGITUDINAL_TEMPORAL_INFORMATION_MODIFIED => {
+                (Action::Replace, "Records the de-identification")
+            }
+            _ if empty && self.date_shift.is_none() => (Action::Empty, "Basic profile, Z"),
+            _ if empty => (Action::Empty, "Date that could not be shifted"),
+            _ => (
+                Action::Shift,
+                "Retain Longitudinal Temporal Information with Modified Dates Option",
+            ),
+        }
+    }
+
+    // Attribute level actions, applied within sequence items as well
+    fn clean(&mut self, dataset: &Dataset, offset: Option<i64>) -> Dataset {
+        let mut cleaned = Dataset::new();
+        for element in dataset {
+            let tag = element.tag();
+            if REMOVED.contains(&tag) || (self.remove_private && tag.0 % 2 == 1) {
+                continue;
+            }
+
+            let value = element.vr();
+            if EMPTIED.contains(&tag) {
+                cleaned.put(Rc::new(DicomElement::new(
+                    tag,
+                    VisualRepresentation::empty(value.code()),
+                )));
+            } else if REPLACED_UIDS.contains(&tag) {
+                let uids: Vec<String> = value
+                    .to_string()
+                    .trim_end_matches(['\0', ' '])
+                    .split('\\')
+                    .map(|original| self.uid(original))
+                    .collect();
+                cleaned.put_string(tag, "UI", &uids.join("\\"));
+            } else if let VisualRepresentation::SQ(items) = &value {
+                let items = items
+                    .iter()
+                    .filter_map(|item| item.dataset())
+                    .map(|item| self.clean(item, offset))
+                    .collect();
+                cleaned.put(Rc::new(DicomElement::sequence(tag, items)));
+            } else if let (Some(offset), Some(shift)) = (offset, &self.date_shift) {
+                cleaned.put(Rc::new(DicomElement::new(
+                    tag,
+                    shifted(tag, value, offset, shift),
+                )));
+            } else if is_date_or_time(tag, &value) {
+                cleaned.put(Rc::new(DicomElement::new(
+                    tag,
+                    VisualRepresentation::empty(value.code()),
+                )));
+            } else {
+                cleaned.put(element.clone());
+            }
+        }
+        cleaned
+    }
+}
+
+// Dates move by the offset and times follow the time of day option. Dates
+// the model could not parse are emptied rather than leaked
+fn shifted(
+    tag: (u16, u16),
+    value: VisualRepresentation,
+    offset: i64,
+    shift: &DateShift,
+) -> VisualRepresentation {
+    let empty = VisualRepresentation::UN(Vec::new());
+    match value {
+        VisualRepresentation::DA(date) => date
+            .first_day()
+            .and_then(|day| day.checked_add_signed(TimeDelta::days(offset)))
+            .map(|day| VisualRepresentation::DA(date.with_date(day)))
+            .unwrap_or(empty),
+        VisualRepresentation::DT(value) => {
+            let Some(datetime) = value
+                .naive()
+                .and_then(|datetime| datetime.checked_add_signed(TimeDelta::days(offset)))
+            else {
+                return empty;
+            };
+            let shifted = value.with_datetime(datetime);
+            VisualRepresentation::DT(match value.time.map(|_| shift.time(datetime.time())) {
+                Some(Some(time)) => value.with_datetime(datetime.date().and_time(time)),
+                // Times removed leave a date only value
+                Some(None) => DicomDateTime {
+                    time: None,
+                    ..shifted
+                },
+                None => shifted,
+            })
+        }
+        VisualRepresentation::TM(time) => time
+            .start()
+            .and_then(|start| shift.time(start))
+            .map(|start| VisualRepresentation::TM(time.with_time(start)))
+            .unwrap_or(empty),
+        VisualRepresentation::UN(_) if matches!(dictionary::vr_of(tag), "DA" | "DT" | "TM") => {
+            empty
+        }
+        value => value,
+    }
+}
+
+// Dates and times, also those the model could not parse
+fn is_date_or_time(tag: (u16, u16), value: &VisualRepresentation) -> bool {
+    match value {
+        VisualRepresentation::DA(_) | VisualRepresentation::DT(_) | VisualRepresentation::TM(_) => {
+            true
+        }
+        VisualRepresentation::UN(_) => matches!(dictionary::vr_of(tag), "DA" | "DT" | "TM"),
+        _ => false,
+    }
+}
+
+// Values short enough to read in a report, binary ones by their size
+fn summary(value: &VisualRepresentation) -> String {
+    match value {
+        VisualRepresentation::SQ(items) => format!("{} items", items.len()),
+        _ if value.code().starts_with('O') => format!("{} bytes", value.heap_size()),
+        _ => {
+            let text = value.to_string();
+            let text = text.trim_end_matches(['\0', ' ']);
+            match text.char_indices().nth(64) {
+                Some((end, _)) => format!("{}...", &text[..end]),
+                None => text.to_string(),
+            }
+        }
+    }
+}
+
+fn date(dataset: &Dataset, tag: (u16, u16)) -> Option<NaiveDate> {
+    match dataset.value(tag)? {
+        VisualRepresentation::DA(date) => date.first_day(),
+        _ => None,
+    }
+}
+
+// HMAC-SHA256, which unlike the std hashers gives the same result on every
+// toolchain
+fn keyed_digest(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC key of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+// Key of an anonymizer given no pseudonym provider, or of a date shift given
+// no secret, so results only agree within the one run
+fn random_key() -> Vec<u8> {
+    (0..4)
+        .flat_map(|_| RandomState::new().build_hasher().finish().to_be_bytes())
+        .collect()
+}
+
+// Values the model cannot hold, such as empty dates, are kept as raw bytes
+fn put_value(dataset: &mut Dataset, tag: (u16, u16), vr: &str, value: &str) {
+    let value = VisualRepresentation::try_from_string(vr, value)
+        .unwrap_or_else(|_| VisualRepresentation::UN(value.as_bytes().to_vec()));
+    dataset.put(Rc::new(DicomElement::new(tag, value)));
+}
@@ -0,0 +1,344 @@
+use std::rc::Rc;
+
+use crate::{
+    core::{
+        bits,
+        dataset::Dataset,
+        element::{DicomElement, ITEM_TAG},
+        error::{DicomError, DicomResult},
+        reader::PIXEL_DATA,
+        tag::VisualRepresentation,
+        transfer_syntax, uid,
+    },
+    image::render::{
+        is_float, number_of_frames, pixel_data_tag, DOUBLE_FLOAT_PIXEL_DATA, NUMBER_OF_FRAMES,
+    },
+    plugins::codec::FrameInfo,
+};
+
+pub const IMAGE_TYPE: (u16, u16) = (0x0008, 0x0008);
+pub const SOP_CLASS_UID: (u16, u16) = (0x0008, 0x0016);
+pub const SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x0018);
+pub const REFERENCED_SOP_CLASS_UID: (u16, u16) = (0x0008, 0x1150);
+pub const REFERENCED_SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x1155);
+pub const REFERENCED_FRAME_NUMBER: (u16, u16) = (0x0008, 0x1160);
+pub const SOURCE_IMAGE_SEQUENCE: (u16, u16) = (0x0008, 0x2112);
+pub const FRAME_TIME_VECTOR: (u16, u16) = (0x0018, 0x1065);
+pub const BITS_ALLOCATED: (u16, u16) = (0x0028, 0x0100);
+pub const PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE: (u16, u16) = (0x5200, 0x9230);
+
+// Frames of the pixel data as stored, native samples or the concatenated
+// fragments of each compressed frame
+pub fn split_frames(dataset: &Dataset, transfer_syntax: &str) -> DicomResult<Vec<Vec<u8>>> {
+    split(dataset, transfer_syntax::is_encapsulated(transfer_syntax))
+}
+
+// Pixel data value holding the frames, encapsulated with one fragment per
+// frame for compressed syntaxes
+pub fn join_frames(
+    frames: &[Vec<u8>],
+    transfer_syntax: &str,
+    bits_allocated: u16,
+) -> VisualRepresentation {
+    join(
+        frames,
+        transfer_syntax::is_encapsulated(transfer_syntax),
+        bits_allocated,
+    )
+}
+
+// Encapsulated pixel data is kept by the reader as its item stream, which
+// native pixel data of the declared size cannot be mistaken for
+pub fn is_encapsulated(dataset: &Dataset) -> bool {
+    let Some(VisualRepresentation::OB(data)) = dataset.value(PIXEL_DATA) else {
+        return false;
+    };
+    let native_size = FrameInfo::from_dataset(dataset)
+        .map(|info| (info.frame_bits() * number_of_frames(dataset)).div_ceil(8))
+        .unwrap_or(0);
+    data.starts_with(&[0xFE, 0xFF, 0x00, 0xE0]) && data.len() != native_size
+}
+
+// New instance holding only the selected frames, numbered from 0 as in
+// render::frame_data. It references the original as its source image
+pub fn extract_frames(dataset: &Dataset, frames: &[usize]) -> DicomResult<Dataset> {
+    if frames.is_empty() {
+        return Err(DicomError::InvalidValue("No frames selected".to_string()));
+    }
+
+    let encapsulated = is_encapsulated(dataset);
+    let pixels = split(dataset, encapsulated)?;
+    let selected = frames
+        .iter()
+        .map(|frame| {
+            pixels.get(*frame).cloned().ok_or_else(|| {
+                DicomError::InvalidValue(format!("Frame {} out of {}", frame, pixels.len()))
+            })
+        })
+        .collect::<DicomResult<Vec<_>>>()?;
+
+    let mut extracted = dataset.clone();
+    let bits_allocated = extracted
+        .string(BITS_ALLOCATED)
+        .and_then(|bits| bits.trim().parse().ok())
+        .unwrap_or(16);
+    // Packed frames go back to back again, without the padding of each
+    let frame_bits = FrameInfo::from_dataset(dataset).map_or(0, |info| info.frame_bits());
+    let selected = if !encapsulated && !frame_bits.is_multiple_of(8) {
+        let pixels: Vec<u8> = selected
+            .iter()
+            .flat_map(|frame| bits::unpack_bits(frame, frame_bits))
+            .collect();
+        vec![bits::pack_bits(&pixels)]
+    } else {
+        selected
+    };
+    let (tag, value) = match pixel_data_tag(dataset) {
+        Some(tag) if tag != PIXEL_DATA => (tag, join_float(&selected, tag)),
+        _ => (PIXEL_DATA, join(&selected, encapsulated, bits_allocated)),
+    };
+    extracted.put(Rc::new(DicomElement::new(tag, value)));
+    if dataset.contains(NUMBER_OF_FRAMES) || frames.len() > 1 {
+        extracted.put_string(NUMBER_OF_FRAMES, "IS", &frames.len().to_string());
+    }
+
+    // Frame level attributes follow the selection
+    let per_frame = dataset.sequence(PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE);
+    if per_frame.len() == pixels.len() {
+        let groups = frames
+            .iter()
+            .map(|frame| per_frame[*frame].clone())
+            .collect();
+        extracted.put(Rc::new(DicomElement::sequence(
+            PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE,
+            groups,
+        )));
+    }
+    if let Some(vector) = dataset.string(FRAME_TIME_VECTOR) {
+        let times: Vec<&str> = vector.split('\\').collect();
+        if times.len() == pixels.len() {
+            let times: Vec<&str> = frames.iter().map(|frame| times[*frame]).collect();
+            extracted.put_string(FRAME_TIME_VECTOR, "DS", &times.join("\\"));
+        }
+    }
+
+    if let Some(image_type) = dataset.string(IMAGE_TYPE) {
+        let mut values: Vec<&str> = image_type.split('\\').collect();
+        values[0] = "DERIVED";
+        extracted.put_string(IMAGE_TYPE, "CS", &values.join("\\"));
+    }
+
+    let mut source = Dataset::new();
+    source.put_string(
+        REFERENCED_SOP_CLASS_UID,
+        "UI",
+        &dataset.string(SOP_CLASS_UID).unwrap_or_default(),
+    );
+    source.put_string(
+        REFERENCED_SOP_INSTANCE_UID,
+        "UI",
+        &dataset.string(SOP_INSTANCE_UID).unwrap_or_default(),
+    );
+    if dataset.contains(NUMBER_OF_FRAMES) {
+        let numbers: Vec<String> = frames.iter().map(|frame| (frame + 1).to_string()).collect();
+        source.put_string(REFERENCED_FRAME_NUMBER, "IS", &numbers.join("\\"));
+    }
+    extracted.put(Rc::new(DicomElement::sequence(
+        SOURCE_IMAGE_SEQUENCE,
+        vec![source],
+    )));
+    extracted.put_string(SOP_INSTANCE_UID, "UI", &uid::generate());
+
+    Ok(extracted)
+}
+
+// Item stream of PS3.5 A.4 with one fragment per frame, the basic offset
+// table pointing at each of them
+pub fn encapsulate(fragments: &[Vec<u8>]) -> Vec<u8> {
+    fn item(stream: &mut Vec<u8>, data: &[u8]) {
+        let padding = data.len() % 2;
+        stream.extend_from_slice(&ITEM_TAG.0.to_le_bytes());
+        stream.extend_from_slice(&ITEM_TAG.1.to_le_bytes());
+        stream.extend_from_slice(&((data.len() + padding) as u32).to_le_bytes());
+        stream.extend_from_slice(data);
+        if padding == 1 {
+            stream.push(0);
+        }
+    }
+
+    let mut table = Vec::new();
+    let mut offset = 0u32;
+    for fragment in fragments {
+        table.extend_from_slice(&offset.to_le_bytes());
+        offset += 8 + (fragment.len() + fragment.len() % 2) as u32;
+    }
+
+    let mut stream = Vec::new();
+    item(&mut stream, &table);
+    for fragment in fragments {
+        item(&mut stream, fragment);
+    }
+    stream
+}
+
+fn split(dataset: &Dataset, encapsulated: bool) -> DicomResult<Vec<Vec<u8>>> {
+    let frames = number_of_frames(dataset);
+    let value = pixel_data_tag(dataset)
+        .and_then(|tag| dataset.value(tag))
+        .ok_or_else(|| DicomError::InvalidDataset("No pixel data".to_string()))?;
+
+    if encapsulated && !is_float(dataset) {
+        let VisualRepresentation::OB(stream) = value else {
+            return Err(DicomError::InvalidVR(
+                "Encapsulated pixel data has to be OB".to_string(),
+            ));
+        };
+        return split_fragments(&stream, frames);
+    }
+
+    // The reader already turned words into native order
+    let data: Vec<u8> = match value {
+        VisualRepresentation::OB(bytes) | VisualRepresentation::UN(bytes) => bytes,
+        VisualRepresentation::OW(words) => words.iter().flat_map(|w| w.to_le_bytes()).collect(),
+        VisualRepresentation::OF(floats) => floats.iter().flat_map(|f| f.to_le_bytes()).collect(),
+        VisualRepresentation::OD(doubles) => doubles.iter().flat_map(|d| d.to_le_bytes()).collect(),
+        _ => {
+            return Err(DicomError::InvalidVR(format!(
+                "Pixel data as {}",
+                value.code()
+            )))
+        }
+    };
+
+    let info = FrameInfo::from_dataset(dataset)?;
+    let size = info.frame_size();
+    if size == 0 || data.len() < (info.frame_bits() * frames).div_ceil(8) {
+        return Err(DicomError::InvalidLength(format!(
+            "{} bytes of pixel data cannot hold {} frames of {} bytes",
+            data.len(),
+            frames,
+            size
+        )));
+    }
+
+    // Packed frames that do not end on a byte boundary run into each other
+    let bits = info.frame_bits();
+    if bits % 8 != 0 {
+        return Ok((0..frames)
+            .map(|frame| bits::bit_range(&data, frame * bits, bits))
+            .collect());
+    }
+
+    Ok(data
+        .chunks(size)
+        .take(frames)
+        .map(|frame| frame.to_vec())
+        .collect())
+}
+
+fn join(frames: &[Vec<u8>], encapsulated: bool, bits_allocated: u16) -> VisualRepresentation {
+    if encapsulated {
+        return VisualRepresentation::OB(encapsulate(frames));
+    }
+
+    let data = frames.concat();
+    if bits_allocated > 8 {
+        VisualRepresentation::OW(
+            data.chunks(2)
+                .map(|word| u16::from_le_bytes([word[0], *word.get(1).unwrap_or(&0)]))
+                .collect(),
+        )
+    } else {
+        VisualRepresentation::OB(data)
+    }
+}
+
+// Float Pixel Data or Double Float Pixel Data holding the frames
+fn join_float(frames: &[Vec<u8>], tag: (u16, u16)) -> VisualRepresentation {
+    let data = frames.concat();
+    if tag == DOUBLE_FLOAT_PIXEL_DATA {
+        VisualRepresentation::OD(
+            data.chunks_exact(8)
+                .map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap_or_default()))
+                .collect(),
+        )
+    } else {
+        VisualRepresentation::OF(
+            data.chunks_exact(4)
+                .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap_or_default()))
+                .collect(),
+        )
+    }
+}
+
+// Groups the fragments into frames, by the basic offset table when frames
+// span several fragments
+fn split_fragments(stream: &[u8], frames: usize) -> DicomResult<Vec<Vec<u8>>> {
+    let mut items = Vec::new();
+    let mut position = 0;
+    while position + 8 <= stream.len() {
+        let group = u16::from_le_bytes([stream[position], stream[position + 1]]);
+        let element = u16::from_le_bytes([stream[position + 2], stream[position + 3]]);
+        let length = u32::from_le_bytes([
+            stream[position + 4],
+            stream[position + 5],
+            stream[position + 6],
+            stream[position + 7],
+        ]) as usize;
+        if (group, element) != ITEM_TAG {
+            break;
+        }
+
+        let data = stream
+            .get(position + 8..position + 8 + length)
+            .ok_or_else(|| {
+                DicomError::InvalidLength("Truncated pixel data fragment".to_string())
+            })?;
+        items.push((position, data));
+        position += 8 + length;
+    }
+
+    let Some(((_, table), fragments)) = items.split_first() else {
+        return Err(DicomError::InvalidDataset(
+            "Encapsulated pixel data without items".to_string(),
+        ));
+    };
+
+    if fragments.len() == frames {
+        return Ok(fragments.iter().map(|(_, data)| data.to_vec()).collect());
+    }
+    if frames == 1 {
+        return Ok(vec![fragments
+            .iter()
+            .flat_map(|(_, data)| data.to_vec())
+            .collect()]);
+    }
+
+    // Offsets count from the first byte of the first fragment item
+    let offsets: Vec<usize> = table
+        .chunks(4)
+        .filter(|offset| offset.len() == 4)
+        .map(|offset| u32::from_le_bytes([offset[0], offset[1], offset[2], offset[3]]) as usize)
+        .collect();
+    if offsets.len() != frames {
+        return Err(DicomError::InvalidValue(format!(
+            "{} fragments for {} frames and no offset table",
+            fragments.len(),
+            frames
+        )));
+    }
+
+    let origin = fragments
+        .first()
+        .map(|(position, _)| *position)
+        .unwrap_or(0);
+    let mut result = vec![Vec::new(); frames];
+    for (position, data) in fragments {
+        let frame = offsets
+            .iter()
+            .rposition(|offset| origin + offset <= *position)
+            .unwrap_or(0);
+        result[frame].extend_from_slice(data);
+    }
+    Ok(result)
+}
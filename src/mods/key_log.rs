@@ -0,0 +1,276 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::anonymize::{KeyKind, KeyRecorder};
+use crate::core::error::{DicomError, DicomResult};
+
+// One mapping of an original value to its replacement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyEntry {
+    pub sequence: u64,
+    // Seconds since the Unix epoch
+    pub time: u64,
+    pub kind: KeyKind,
+    pub original: String,
+    pub replacement: String,
+    // HMAC-SHA256 over the previous entry's MAC and this entry, hex
+    pub mac: String,
+}
+
+impl KeyEntry {
+    fn record(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            self.sequence,
+            self.time,
+            self.kind,
+            escape(&self.original),
+            escape(&self.replacement)
+        )
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [sequence, time, kind, original, replacement, mac] = fields[..] else {
+            return None;
+        };
+        Some(KeyEntry {
+            sequence: sequence.parse().ok()?,
+            time: time.parse().ok()?,
+            kind: KeyKind::parse(kind)?,
+            original: unescape(original),
+            replacement: unescape(replacement),
+            mac: mac.to_string(),
+        })
+    }
+}
+
+// Key file of an anonymizer for re-identification, one line per mapping.
+// Lines are only ever appended and each carries an HMAC chained to the one
+// before, so editing, reordering or removing lines breaks verification
+// without the secret. Cutting lines off the end leaves a valid chain, which
+// callers notice by keeping the head somewhere else and passing it to
+// verify
+pub struct KeyLog {
+    path: PathBuf,
+    secret: Vec<u8>,
+    head: Vec<u8>,
+    sequence: u64,
+}
+
+impl std::fmt::Debug for KeyLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyLog")
+            .field("path", &self.path)
+            .field("sequence", &self.sequence)
+            .finish()
+    }
+}
+
+impl KeyLog {
+    // Continues an existing log after verifying it, or starts an empty one
+    pub fn open(path: &Path, secret: &[u8]) -> DicomResult<Self> {
+        let mut log = KeyLog {
+            path: path.to_path_buf(),
+            secret: secret.to_vec(),
+            head: Vec::new(),
+            sequence: 0,
+        };
+        if path.exists() {
+            if let Some(last) = log.verify(None)?.last() {
+                log.head = unhex(&last.mac).unwrap_or_default();
+                log.sequence = last.sequence + 1;
+            }
+        }
+        Ok(log)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // MAC of the last entry, hex, empty for an empty log
+    pub fn head(&self) -> String {
+        hex(&self.head)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sequence == 0
+    }
+
+    // Written through to disk before returning, so a mapping that went into
+    // a dataset is never lost
+    pub fn append(
+        &mut self,
+        kind: KeyKind,
+        original: &str,
+        replacement: &str,
+    ) -> DicomResult<KeyEntry> {
+        let mut entry = KeyEntry {
+            sequence: self.sequence,
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_secs())
+                .unwrap_or_default(),
+            kind,
+            original: original.to_string(),
+            replacement: replacement.to_string(),
+            mac: String::new(),
+        };
+        let mut mac = self.mac(&self.head)?;
+        mac.update(entry.record().as_bytes());
+        let mac = mac.finalize().into_bytes().to_vec();
+        entry.mac = hex(&mac);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}\t{}", entry.record(), entry.mac)?;
+        file.sync_data()?;
+        self.head = mac;
+        self.sequence += 1;
+        Ok(entry)
+    }
+
+    // Entries of the log once every MAC checks out, or the first line that
+    // does not. A head kept from earlier has to be among the entries
+    pub fn verify(&self, head: Option<&str>) -> DicomResult<Vec<KeyEntry>> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(error.into()),
+        };
+        let tampered = |line: usize, reason: &str| {
+            DicomError::InvalidFile(format!(
+                "Key log {} line {}: {}",
+                self.path.display(),
+                line + 1,
+                reason
+            ))
+        };
+
+        let mut entries: Vec<KeyEntry> = Vec::new();
+        let mut previous = Vec::new();
+        for (line, text) in text.lines().enumerate() {
+            let entry = KeyEntry::parse(text).ok_or_else(|| tampered(line, "malformed"))?;
+            if entry.sequence != line as u64 {
+                return Err(tampered(line, "out of sequence"));
+            }
+            let expected = unhex(&entry.mac).ok_or_else(|| tampered(line, "malformed MAC"))?;
+            let mut mac = self.mac(&previous)?;
+            mac.update(entry.record().as_bytes());
+            if mac.verify_slice(&expected).is_err() {
+                return Err(tampered(line, "MAC does not match"));
+            }
+            previous = expected;
+            entries.push(entry);
+        }
+
+        if let Some(head) = head.filter(|head| !head.is_empty()) {
+            if !entries
+                .iter()
+                .any(|entry| entry.mac.eq_ignore_ascii_case(head))
+            {
+                return Err(DicomError::InvalidFile(format!(
+                    "Key log {} lost the entry of head {}",
+                    self.path.display(),
+                    head
+                )));
+            }
+        }
+        Ok(entries)
+    }
+
+    // Verified entries of one kind
+    pub fn entries(&self, kind: KeyKind) -> DicomResult<Vec<KeyEntry>> {
+        Ok(self
+            .verify(None)?
+            .into_iter()
+            .filter(|entry| entry.kind == kind)
+            .collect())
+    }
+
+    // Replacement an original value was given
+    pub fn replacement(&self, kind: KeyKind, original: &str) -> DicomResult<Option<String>> {
+        Ok(self
+            .entries(kind)?
+            .into_iter()
+            .find(|entry| entry.original == original)
+            .map(|entry| entry.replacement))
+    }
+
+    // Original behind a replacement, e.g. the patient ID of a pseudonym
+    pub fn reidentify(&self, kind: KeyKind, replacement: &str) -> DicomResult<Option<String>> {
+        Ok(self
+            .entries(kind)?
+            .into_iter()
+            .find(|entry| entry.replacement == replacement)
+            .map(|entry| entry.original))
+    }
+
+    fn mac(&self, previous: &[u8]) -> DicomResult<Hmac<Sha256>> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret)
+            .map_err(|error| DicomError::Error(error.to_string()))?;
+        mac.update(previous);
+        Ok(mac)
+    }
+}
+
+impl KeyRecorder for KeyLog {
+    fn record(&mut self, kind: KeyKind, original: &str, replacement: &str) -> DicomResult<()> {
+        self.append(kind, original, replacement).map(|_| ())
+    }
+}
+
+// Tabs and line breaks would split records
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut characters = text.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            unescaped.push(character);
+            continue;
+        }
+        match characters.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&text[index..index + 2], 16).ok())
+        .collect()
+}
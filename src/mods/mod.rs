@@ -0,0 +1,14 @@
+#[cfg(any(all(feature = "sha2", feature = "hmac"), feature = "default"))]
+pub mod anonymize;
+pub mod frames;
+#[cfg(any(all(feature = "sha2", feature = "hmac"), feature = "default"))]
+pub mod key_log;
+#[cfg(any(feature = "regex", feature = "default"))]
+pub mod modify;
+pub mod morph;
+pub mod redact;
+#[cfg(any(feature = "text-detection", feature = "default"))]
+pub mod text_detection;
+pub mod transcode;
+
+pub use frames::extract_frames;
@@ -0,0 +1,297 @@
+use std::rc::Rc;
+
+use regex::Regex;
+
+use crate::core::{
+    dataset::Dataset,
+    dictionary::{self, parse_tag},
+    element::DicomElement,
+    error::{DicomError, DicomResult, TagPath, TagPathStep},
+    tag::VisualRepresentation,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemSelector {
+    All,
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathStep {
+    Sequence((u16, u16), ItemSelector),
+    // Any number of sequence levels, none included
+    AnyDepth,
+}
+
+// Elements to modify, written as dcmodify writes them: sequences with the
+// item to descend into, then the element, e.g. (0040,0275)[*].(0040,0009).
+// Keywords work in place of tags, and ** stands for any depth so
+// **.(0008,1155) reaches every Referenced SOP Instance UID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagPattern {
+    pub steps: Vec<PathStep>,
+    pub tag: (u16, u16),
+}
+
+impl TagPattern {
+    pub fn new(tag: (u16, u16)) -> Self {
+        TagPattern {
+            steps: Vec::new(),
+            tag,
+        }
+    }
+
+    pub fn parse(text: &str) -> DicomResult<Self> {
+        let invalid = |reason: &str| DicomError::InvalidTag(format!("{} in {}", reason, text));
+        let mut parts: Vec<&str> = text.trim().split('.').map(str::trim).collect();
+        let last = parts.pop().filter(|part| !part.is_empty());
+        let last = last.ok_or_else(|| invalid("No element"))?;
+        if last.ends_with(']') || last == "**" {
+            return Err(invalid("No element after the last sequence"));
+        }
+
+        let mut steps = Vec::new();
+        for part in parts {
+            if part == "**" {
+                steps.push(PathStep::AnyDepth);
+                continue;
+            }
+            let (name, selector) = part
+                .strip_suffix(']')
+                .and_then(|part| part.split_once('['))
+                .ok_or_else(|| invalid(&format!("{} names no item", part)))?;
+            let selector = match selector.trim() {
+                "*" => ItemSelector::All,
+                index => ItemSelector::Index(
+                    index
+                        .parse()
+                        .map_err(|_| invalid(&format!("Item {} is not a number", index)))?,
+                ),
+            };
+            steps.push(PathStep::Sequence(parse_tag(name)?, selector));
+        }
+        Ok(TagPattern {
+            steps,
+            tag: parse_tag(last)?,
+        })
+    }
+
+    pub fn is_wildcard(&self) -> bool {
+        self.steps.iter().any(|step| {
+            matches!(
+                step,
+                PathStep::AnyDepth | PathStep::Sequence(_, ItemSelector::All)
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    // An empty value leaves the element present but empty
+    Set(String),
+    Clear,
+    Remove,
+}
+
+// One operation on the elements a pattern reaches, optionally only on those
+// whose current value matches a regular expression. Absent elements match
+// as an empty value
+#[derive(Debug, Clone)]
+pub struct Modification {
+    pub pattern: TagPattern,
+    pub operation: Operation,
+    pub condition: Option<Regex>,
+    // Set creates the element in the datasets the pattern reaches, never
+    // sequences or items along the way
+    pub insert: bool,
+}
+
+impl Modification {
+    pub fn new(pattern: TagPattern, operation: Operation) -> Self {
+        Modification {
+            pattern,
+            operation,
+            condition: None,
+            insert: false,
+        }
+    }
+
+    pub fn set(pattern: &str, value: &str) -> DicomResult<Self> {
+        Ok(Modification::new(
+            TagPattern::parse(pattern)?,
+            Operation::Set(value.to_string()),
+        ))
+    }
+
+    pub fn clear(pattern: &str) -> DicomResult<Self> {
+        Ok(Modification::new(
+            TagPattern::parse(pattern)?,
+            Operation::Clear,
+        ))
+    }
+
+    pub fn remove(pattern: &str) -> DicomResult<Self> {
+        Ok(Modification::new(
+            TagPattern::parse(pattern)?,
+            Operation::Remove,
+        ))
+    }
+
+    pub fn with_condition(mut self, condition: &str) -> DicomResult<Self> {
+        let regex = Regex::new(condition).map_err(|error| {
+            DicomError::InvalidValue(format!("Condition {}: {}", condition, error))
+        })?;
+        self.condition = Some(regex);
+        Ok(self)
+    }
+
+    pub fn with_insert(mut self, insert: bool) -> Self {
+        self.insert = insert;
+        self
+    }
+
+    // Locations of the elements the modification changed
+    pub fn apply(&self, dataset: &mut Dataset) -> DicomResult<Vec<TagPath>> {
+        let mut changed = Vec::new();
+        self.apply_at(
+            dataset,
+            &self.pattern.steps,
+            &mut TagPath::default(),
+            &mut changed,
+        )?;
+        Ok(changed)
+    }
+
+    fn apply_at(
+        &self,
+        dataset: &mut Dataset,
+        steps: &[PathStep],
+        path: &mut TagPath,
+        changed: &mut Vec<TagPath>,
+    ) -> DicomResult<()> {
+        match steps.split_first() {
+            None => self.apply_element(dataset, path, changed),
+            Some((PathStep::Sequence(tag, selector), rest)) => {
+                self.apply_items(dataset, *tag, *selector, rest, path, changed)
+            }
+            Some((PathStep::AnyDepth, rest)) => {
+                self.apply_at(dataset, rest, path, changed)?;
+                let sequences: Vec<(u16, u16)> = (&*dataset)
+                    .into_iter()
+                    .filter(|element| element.vr().code() == "SQ")
+                    .map(|element| element.tag())
+                    .collect();
+                for tag in sequences {
+                    self.apply_items(dataset, tag, ItemSelector::All, steps, path, changed)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn apply_items(
+        &self,
+        dataset: &mut Dataset,
+        tag: (u16, u16),
+        selector: ItemSelector,
+        steps: &[PathStep],
+        path: &mut TagPath,
+        changed: &mut Vec<TagPath>,
+    ) -> DicomResult<()> {
+        let mut items = dataset.items(tag);
+        let before = changed.len();
+        for (index, item) in items.iter_mut().enumerate() {
+            if selector != ItemSelector::All && selector != ItemSelector::Index(index) {
+                continue;
+            }
+            path.steps.push(TagPathStep::Tag(tag));
+            path.steps.push(TagPathStep::Item(index));
+            let result = self.apply_at(item.dataset_mut(), steps, path, changed);
+            path.steps.truncate(path.steps.len() - 2);
+            result?;
+        }
+        // Items are copies, the sequence is only rebuilt when one changed
+        if changed.len() > before {
+            dataset.set_items(tag, items);
+        }
+        Ok(())
+    }
+
+    fn apply_element(
+        &self,
+        dataset: &mut Dataset,
+        path: &TagPath,
+        changed: &mut Vec<TagPath>,
+    ) -> DicomResult<()> {
+        let tag = self.pattern.tag;
+        let current = dataset.string(tag);
+        if let Some(condition) = &self.condition {
+            if !condition.is_match(current.as_deref().unwrap_or_default()) {
+                return Ok(());
+            }
+        }
+        let vr = match dataset.find(tag) {
+            Some(element) => element.vr().code(),
+            None => dictionary::vr_of(tag),
+        };
+
+        let modified = match &self.operation {
+            Operation::Set(_) if current.is_none() && !self.insert => false,
+            Operation::Set(value) if current.as_deref() == Some(value.as_str()) => false,
+            Operation::Set(_) if vr == "SQ" => {
+                return Err(DicomError::InvalidVR(format!(
+                    "({:04X},{:04X}) is a sequence and takes no value",
+                    tag.0, tag.1
+                )))
+            }
+            Operation::Set(value) if value.is_empty() => {
+                dataset.set_empty_as(tag, vr);
+                true
+            }
+            Operation::Set(value) => {
+                let value = VisualRepresentation::try_from_string(vr, value)?;
+                dataset.put(Rc::new(DicomElement::new(tag, value)));
+                true
+            }
+            Operation::Clear if current.as_deref().is_none_or(str::is_empty) => false,
+            Operation::Clear => {
+                dataset.set_empty_as(tag, vr);
+                true
+            }
+            Operation::Remove => dataset.remove(tag).is_some(),
+        };
+        if modified {
+            let mut location = path.clone();
+            location.steps.push(TagPathStep::Tag(tag));
+            changed.push(location);
+        }
+        Ok(())
+    }
+}
+
+// Modifications applied in order, each one seeing the result of the last,
+// for fixups run over a whole archive
+#[derive(Debug, Clone, Default)]
+pub struct Modifications {
+    pub modifications: Vec<Modification>,
+}
+
+impl Modifications {
+    pub fn new() -> Self {
+        Modifications::default()
+    }
+
+    pub fn with(mut self, modification: Modification) -> Self {
+        self.modifications.push(modification);
+        self
+    }
+
+    pub fn apply(&self, dataset: &mut Dataset) -> DicomResult<Vec<TagPath>> {
+        let mut changed = Vec::new();
+        for modification in &self.modifications {
+            changed.extend(modification.apply(dataset)?);
+        }
+        Ok(changed)
+    }
+}
@@ -0,0 +1,442 @@
+use std::rc::Rc;
+
+use super::frames::{self, BITS_ALLOCATED};
+use crate::{
+    core::{
+        dataset::Dataset,
+        element::DicomElement,
+        error::{DicomError, DicomResult},
+        reader::{DicomFile, PIXEL_DATA},
+        tag::VisualRepresentation,
+        transfer_syntax, uid, writer,
+    },
+    image::render::NUMBER_OF_FRAMES,
+};
+
+pub const CT_IMAGE_STORAGE: &str = "1.2.840.10008.5.1.4.1.1.2";
+pub const ENHANCED_CT_IMAGE_STORAGE: &str = "1.2.840.10008.5.1.4.1.1.2.1";
+pub const LEGACY_CONVERTED_ENHANCED_CT_IMAGE_STORAGE: &str = "1.2.840.10008.5.1.4.1.1.2.2";
+pub const MR_IMAGE_STORAGE: &str = "1.2.840.10008.5.1.4.1.1.4";
+pub const ENHANCED_MR_IMAGE_STORAGE: &str = "1.2.840.10008.5.1.4.1.1.4.1";
+pub const LEGACY_CONVERTED_ENHANCED_MR_IMAGE_STORAGE: &str = "1.2.840.10008.5.1.4.1.1.4.4";
+
+pub const IMAGE_TYPE: (u16, u16) = (0x0008, 0x0008);
+pub const SOP_CLASS_UID: (u16, u16) = (0x0008, 0x0016);
+pub const SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x0018);
+pub const ACQUISITION_DATE: (u16, u16) = (0x0008, 0x0022);
+pub const ACQUISITION_DATETIME: (u16, u16) = (0x0008, 0x002A);
+pub const ACQUISITION_TIME: (u16, u16) = (0x0008, 0x0032);
+pub const REFERENCED_SOP_CLASS_UID: (u16, u16) = (0x0008, 0x1150);
+pub const REFERENCED_SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x1155);
+pub const FRAME_TYPE: (u16, u16) = (0x0008, 0x9007);
+pub const ECHO_TIME: (u16, u16) = (0x0018, 0x0081);
+pub const SLICE_THICKNESS: (u16, u16) = (0x0018, 0x0050);
+pub const SPACING_BETWEEN_SLICES: (u16, u16) = (0x0018, 0x0088);
+pub const FRAME_ACQUISITION_DATETIME: (u16, u16) = (0x0018, 0x9074);
+pub const EFFECTIVE_ECHO_TIME: (u16, u16) = (0x0018, 0x9082);
+pub const SERIES_INSTANCE_UID: (u16, u16) = (0x0020, 0x000E);
+pub const ACQUISITION_NUMBER: (u16, u16) = (0x0020, 0x0012);
+pub const INSTANCE_NUMBER: (u16, u16) = (0x0020, 0x0013);
+pub const IMAGE_POSITION_PATIENT: (u16, u16) = (0x0020, 0x0032);
+pub const IMAGE_ORIENTATION_PATIENT: (u16, u16) = (0x0020, 0x0037);
+pub const STACK_ID: (u16, u16) = (0x0020, 0x9056);
+pub const IN_STACK_POSITION_NUMBER: (u16, u16) = (0x0020, 0x9057);
+pub const FRAME_CONTENT_SEQUENCE: (u16, u16) = (0x0020, 0x9111);
+pub const PLANE_POSITION_SEQUENCE: (u16, u16) = (0x0020, 0x9113);
+pub const PLANE_ORIENTATION_SEQUENCE: (u16, u16) = (0x0020, 0x9116);
+pub const TEMPORAL_POSITION_INDEX: (u16, u16) = (0x0020, 0x9128);
+pub const FRAME_ACQUISITION_NUMBER: (u16, u16) = (0x0020, 0x9156);
+pub const DIMENSION_INDEX_VALUES: (u16, u16) = (0x0020, 0x9157);
+pub const UNASSIGNED_SHARED_CONVERTED_ATTRIBUTES_SEQUENCE: (u16, u16) = (0x0020, 0x9170);
+pub const UNASSIGNED_PER_FRAME_CONVERTED_ATTRIBUTES_SEQUENCE: (u16, u16) = (0x0020, 0x9171);
+pub const CONVERSION_SOURCE_ATTRIBUTES_SEQUENCE: (u16, u16) = (0x0020, 0x9172);
+pub const DIMENSION_ORGANIZATION_SEQUENCE: (u16, u16) = (0x0020, 0x9221);
+pub const DIMENSION_INDEX_SEQUENCE: (u16, u16) = (0x0020, 0x9222);
+pub const PIXEL_SPACING: (u16, u16) = (0x0028, 0x0030);
+pub const WINDOW_CENTER: (u16, u16) = (0x0028, 0x1050);
+pub const WINDOW_WIDTH: (u16, u16) = (0x0028, 0x1051);
+pub const RESCALE_INTERCEPT: (u16, u16) = (0x0028, 0x1052);
+pub const RESCALE_SLOPE: (u16, u16) = (0x0028, 0x1053);
+pub const RESCALE_TYPE: (u16, u16) = (0x0028, 0x1054);
+pub const WINDOW_CENTER_WIDTH_EXPLANATION: (u16, u16) = (0x0028, 0x1055);
+pub const PIXEL_MEASURES_SEQUENCE: (u16, u16) = (0x0028, 0x9110);
+pub const FRAME_VOI_LUT_SEQUENCE: (u16, u16) = (0x0028, 0x9132);
+pub const PIXEL_VALUE_TRANSFORMATION_SEQUENCE: (u16, u16) = (0x0028, 0x9145);
+pub const SHARED_FUNCTIONAL_GROUPS_SEQUENCE: (u16, u16) = (0x5200, 0x9229);
+pub const PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE: (u16, u16) = (0x5200, 0x9230);
+
+// A functional group sequence and the classic attributes its item holds
+type FunctionalGroupMacro = ((u16, u16), &'static [(u16, u16)]);
+
+// Functional group macros rebuilt from classic attributes
+const MACROS: [FunctionalGroupMacro; 5] = [
+    (
+        PIXEL_MEASURES_SEQUENCE,
+        &[PIXEL_SPACING, SLICE_THICKNESS, SPACING_BETWEEN_SLICES],
+    ),
+    (PLANE_POSITION_SEQUENCE, &[IMAGE_POSITION_PATIENT]),
+    (PLANE_ORIENTATION_SEQUENCE, &[IMAGE_ORIENTATION_PATIENT]),
+    (
+        PIXEL_VALUE_TRANSFORMATION_SEQUENCE,
+        &[RESCALE_INTERCEPT, RESCALE_SLOPE, RESCALE_TYPE],
+    ),
+    (
+        FRAME_VOI_LUT_SEQUENCE,
+        &[WINDOW_CENTER, WINDOW_WIDTH, WINDOW_CENTER_WIDTH_EXPLANATION],
+    ),
+];
+
+// Frame Content attributes that only index the frame in its dimensions
+const DIMENSION_ATTRIBUTES: [(u16, u16); 5] = [
+    STACK_ID,
+    IN_STACK_POSITION_NUMBER,
+    TEMPORAL_POSITION_INDEX,
+    DIMENSION_INDEX_VALUES,
+    FRAME_ACQUISITION_NUMBER,
+];
+
+pub fn is_enhanced(sop_class_uid: &str) -> bool {
+    classic_sop_class(sop_class_uid).is_some()
+}
+
+// Single frame counterpart of an enhanced SOP class
+pub fn classic_sop_class(enhanced: &str) -> Option<&'static str> {
+    match enhanced {
+        ENHANCED_CT_IMAGE_STORAGE | LEGACY_CONVERTED_ENHANCED_CT_IMAGE_STORAGE => {
+            Some(CT_IMAGE_STORAGE)
+        }
+        ENHANCED_MR_IMAGE_STORAGE | LEGACY_CONVERTED_ENHANCED_MR_IMAGE_STORAGE => {
+            Some(MR_IMAGE_STORAGE)
+        }
+        _ => None,
+    }
+}
+
+// Classic objects convert to the Legacy Converted Enhanced classes of
+// PS3.3 A.70, the true Enhanced ones needing acquisition details classic
+// objects lack
+pub fn enhanced_sop_class(classic: &str) -> Option<&'static str> {
+    match classic {
+        CT_IMAGE_STORAGE => Some(LEGACY_CONVERTED_ENHANCED_CT_IMAGE_STORAGE),
+        MR_IMAGE_STORAGE => Some(LEGACY_CONVERTED_ENHANCED_MR_IMAGE_STORAGE),
+        _ => None,
+    }
+}
+
+// Splits an enhanced multi-frame object into one classic instance per frame,
+// shared and then per-frame functional groups flattened into top-level
+// attributes. The instances form a new series
+pub fn enhanced_to_classic(file: &DicomFile) -> DicomResult<Vec<DicomFile>> {
+    let dataset = &file.dataset;
+    let sop_class = dataset.string(SOP_CLASS_UID).unwrap_or_default();
+    let classic = classic_sop_class(&sop_class).ok_or_else(|| {
+        DicomError::InvalidDataset(format!("{} is not an enhanced CT or MR object", sop_class))
+    })?;
+
+    let pixels = frames::split_frames(dataset, &file.transfer_syntax)?;
+    let shared = dataset.sequence(SHARED_FUNCTIONAL_GROUPS_SEQUENCE);
+    let per_frame = dataset.sequence(PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE);
+    if per_frame.len() != pixels.len() {
+        return Err(DicomError::InvalidDataset(format!(
+            "{} per-frame functional groups for {} frames",
+            per_frame.len(),
+            pixels.len()
+        )));
+    }
+
+    let mut common = Dataset::new();
+    for element in dataset {
+        if !matches!(
+            element.tag(),
+            SHARED_FUNCTIONAL_GROUPS_SEQUENCE
+                | PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE
+                | DIMENSION_ORGANIZATION_SEQUENCE
+                | DIMENSION_INDEX_SEQUENCE
+                | NUMBER_OF_FRAMES
+                | PIXEL_DATA
+        ) {
+            common.put(element.clone());
+        }
+    }
+    common.put_string(SOP_CLASS_UID, "UI", classic);
+    common.put_string(SERIES_INSTANCE_UID, "UI", &uid::generate());
+
+    let mut instances = Vec::new();
+    for (index, (groups, frame)) in per_frame.iter().zip(pixels).enumerate() {
+        let mut instance = common.clone();
+        for group in shared.iter().chain(std::iter::once(groups)) {
+            flatten(group, &mut instance);
+        }
+
+        instance.put_string(SOP_INSTANCE_UID, "UI", &uid::generate());
+        instance.put_string(INSTANCE_NUMBER, "IS", &(index + 1).to_string());
+        let bits_allocated = bits_allocated(&instance);
+        instance.put(Rc::new(DicomElement::new(
+            PIXEL_DATA,
+            frames::join_frames(&[frame], &file.transfer_syntax, bits_allocated),
+        )));
+
+        instances.push(DicomFile {
+            meta: Dataset::new(),
+            dataset: instance,
+            transfer_syntax: file.transfer_syntax.clone(),
+        });
+    }
+
+    Ok(instances)
+}
+
+// Merges classic single frame instances of one series into a legacy converted
+// enhanced object, frames ordered by instance number. Attributes equal across
+// the instances are shared, the others go per frame
+pub fn classic_to_enhanced(files: &[DicomFile]) -> DicomResult<DicomFile> {
+    let first = files
+        .first()
+        .ok_or_else(|| DicomError::InvalidDataset("No instances to convert".to_string()))?;
+    let sop_class = first.dataset.string(SOP_CLASS_UID).unwrap_or_default();
+    let enhanced = enhanced_sop_class(&sop_class).ok_or_else(|| {
+        DicomError::InvalidDataset(format!("{} is not a classic CT or MR object", sop_class))
+    })?;
+
+    for file in files {
+        if file.dataset.string(SOP_CLASS_UID).unwrap_or_default() != sop_class {
+            return Err(DicomError::InvalidDataset(
+                "Instances of different SOP classes cannot be merged".to_string(),
+            ));
+        }
+        if file.transfer_syntax != first.transfer_syntax {
+            return Err(DicomError::UnsupportedTransferSyntax(format!(
+                "{} mixed with {}",
+                file.transfer_syntax, first.transfer_syntax
+            )));
+        }
+    }
+
+    let mut sorted: Vec<&DicomFile> = files.iter().collect();
+    sorted.sort_by_key(|file| {
+        file.dataset
+            .string(INSTANCE_NUMBER)
+            .and_then(|number| number.trim().parse::<i64>().ok())
+            .unwrap_or(i64::MAX)
+    });
+    let datasets: Vec<&Dataset> = sorted.iter().map(|file| &file.dataset).collect();
+
+    // Attributes handled by the macros, the frame content or the merge itself
+    let mut handled: Vec<(u16, u16)> = MACROS
+        .iter()
+        .flat_map(|(_, tags)| tags.iter().copied())
+        .collect();
+    handled.extend([
+        SOP_INSTANCE_UID,
+        INSTANCE_NUMBER,
+        ACQUISITION_DATE,
+        ACQUISITION_TIME,
+        ACQUISITION_DATETIME,
+        ACQUISITION_NUMBER,
+        NUMBER_OF_FRAMES,
+        PIXEL_DATA,
+    ]);
+
+    // Everything else is top-level when equal in all instances
+    let mut dataset = Dataset::new();
+    let mut varying = Vec::new();
+    for element in datasets[0] {
+        let tag = element.tag();
+        if handled.contains(&tag) {
+            continue;
+        }
+        if datasets
+            .iter()
+            .all(|other| same_values(other, datasets[0], &[tag]))
+        {
+            dataset.put(element.clone());
+        } else {
+            varying.push(tag);
+        }
+    }
+
+    let mut shared_group = Dataset::new();
+    let mut per_frame_groups = vec![Dataset::new(); datasets.len()];
+    for (sequence, tags) in MACROS {
+        let items: Vec<Dataset> = datasets.iter().map(|source| pick(source, tags)).collect();
+        if items.iter().all(Dataset::is_empty) {
+            continue;
+        }
+
+        if datasets
+            .iter()
+            .all(|other| same_values(other, datasets[0], tags))
+        {
+            shared_group.put(Rc::new(DicomElement::sequence(
+                sequence,
+                vec![items[0].clone()],
+            )));
+        } else {
+            for (group, item) in per_frame_groups.iter_mut().zip(items) {
+                group.put(Rc::new(DicomElement::sequence(sequence, vec![item])));
+            }
+        }
+    }
+
+    for (index, (group, source)) in per_frame_groups.iter_mut().zip(&datasets).enumerate() {
+        group.put(Rc::new(DicomElement::sequence(
+            FRAME_CONTENT_SEQUENCE,
+            vec![frame_content(source, index)],
+        )));
+
+        let mut conversion_source = Dataset::new();
+        conversion_source.put_string(REFERENCED_SOP_CLASS_UID, "UI", &sop_class);
+        conversion_source.put_string(
+            REFERENCED_SOP_INSTANCE_UID,
+            "UI",
+            &source.string(SOP_INSTANCE_UID).unwrap_or_default(),
+        );
+        group.put(Rc::new(DicomElement::sequence(
+            CONVERSION_SOURCE_ATTRIBUTES_SEQUENCE,
+            vec![conversion_source],
+        )));
+
+        if !varying.is_empty() {
+            group.put(Rc::new(DicomElement::sequence(
+                UNASSIGNED_PER_FRAME_CONVERTED_ATTRIBUTES_SEQUENCE,
+                vec![pick(source, &varying)],
+            )));
+        }
+    }
+
+    let mut pixels = Vec::new();
+    for file in &sorted {
+        let mut frames = frames::split_frames(&file.dataset, &file.transfer_syntax)?;
+        pixels.append(&mut frames);
+    }
+
+    dataset.put_string(SOP_CLASS_UID, "UI", enhanced);
+    dataset.put_string(SOP_INSTANCE_UID, "UI", &uid::generate());
+    dataset.put_string(SERIES_INSTANCE_UID, "UI", &uid::generate());
+    dataset.put_string(INSTANCE_NUMBER, "IS", "1");
+    dataset.put_string(NUMBER_OF_FRAMES, "IS", &pixels.len().to_string());
+    if let Some(acquisition) = frame_acquisition_datetime(datasets[0]) {
+        put_value(&mut dataset, ACQUISITION_DATETIME, "DT", &acquisition);
+    }
+    dataset.put(Rc::new(DicomElement::sequence(
+        SHARED_FUNCTIONAL_GROUPS_SEQUENCE,
+        vec![shared_group],
+    )));
+    dataset.put(Rc::new(DicomElement::sequence(
+        PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE,
+        per_frame_groups,
+    )));
+    let bits_allocated = bits_allocated(&dataset);
+    dataset.put(Rc::new(DicomElement::new(
+        PIXEL_DATA,
+        frames::join_frames(&pixels, &first.transfer_syntax, bits_allocated),
+    )));
+
+    Ok(DicomFile {
+        meta: Dataset::new(),
+        dataset,
+        transfer_syntax: first.transfer_syntax.clone(),
+    })
+}
+
+// Copies the content of each functional group macro to the top level,
+// renaming the attributes that classic objects know under another name
+fn flatten(group: &Dataset, instance: &mut Dataset) {
+    for macro_element in group {
+        let tag = macro_element.tag();
+        if tag == CONVERSION_SOURCE_ATTRIBUTES_SEQUENCE {
+            continue;
+        }
+
+        for item in macro_element.vr().items() {
+            let Some(item) = item.dataset() else {
+                continue;
+            };
+            for element in item {
+                match element.tag() {
+                    FRAME_ACQUISITION_DATETIME => {
+                        let datetime = element.vr().to_string();
+                        put_value(instance, ACQUISITION_DATETIME, "DT", &datetime);
+                        if datetime.len() >= 8 {
+                            put_value(instance, ACQUISITION_DATE, "DA", &datetime[..8]);
+                            put_value(instance, ACQUISITION_TIME, "TM", &datetime[8..]);
+                        }
+                    }
+                    FRAME_ACQUISITION_NUMBER => {
+                        instance.put_string(ACQUISITION_NUMBER, "IS", &element.vr().to_string())
+                    }
+                    EFFECTIVE_ECHO_TIME => {
+                        instance.put_string(ECHO_TIME, "DS", &element.vr().to_string())
+                    }
+                    FRAME_TYPE => instance.put_string(IMAGE_TYPE, "CS", &element.vr().to_string()),
+                    tag if DIMENSION_ATTRIBUTES.contains(&tag) => {}
+                    _ => instance.put(element.clone()),
+                }
+            }
+        }
+    }
+}
+
+fn frame_content(source: &Dataset, index: usize) -> Dataset {
+    let mut content = Dataset::new();
+    content.put_string(
+        FRAME_ACQUISITION_NUMBER,
+        "US",
+        &source
+            .string(ACQUISITION_NUMBER)
+            .and_then(|number| number.trim().parse::<u16>().ok())
+            .unwrap_or(index as u16 + 1)
+            .to_string(),
+    );
+    if let Some(datetime) = frame_acquisition_datetime(source) {
+        put_value(&mut content, FRAME_ACQUISITION_DATETIME, "DT", &datetime);
+    }
+    content
+}
+
+fn frame_acquisition_datetime(source: &Dataset) -> Option<String> {
+    if let Some(datetime) = source.string(ACQUISITION_DATETIME) {
+        return Some(datetime);
+    }
+    Some(format!(
+        "{}{}",
+        source.string(ACQUISITION_DATE)?,
+        source.string(ACQUISITION_TIME)?
+    ))
+}
+
+fn pick(source: &Dataset, tags: &[(u16, u16)]) -> Dataset {
+    let mut item = Dataset::new();
+    for tag in tags {
+        if let Some(element) = source.find(*tag) {
+            item.put(element.clone());
+        }
+    }
+    item
+}
+
+// Compares the encoded attributes, which also covers sequences
+fn same_values(a: &Dataset, b: &Dataset, tags: &[(u16, u16)]) -> bool {
+    let encode = |dataset: &Dataset| {
+        writer::write_dataset(
+            &pick(dataset, tags),
+            transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN,
+        )
+        .ok()
+    };
+    encode(a) == encode(b)
+}
+
+fn bits_allocated(dataset: &Dataset) -> u16 {
+    dataset
+        .string(BITS_ALLOCATED)
+        .and_then(|bits| bits.trim().parse().ok())
+        .unwrap_or(16)
+}
+
+// Values the model cannot parse are kept as raw bytes, as the reader does
+fn put_value(dataset: &mut Dataset, tag: (u16, u16), vr: &str, value: &str) {
+    let value = VisualRepresentation::try_from_string(vr, value)
+        .unwrap_or_else(|_| VisualRepresentation::UN(value.as_bytes().to_vec()));
+    dataset.put(Rc::new(DicomElement::new(tag, value)));
+}
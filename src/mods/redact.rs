@@ -0,0 +1,141 @@
+use std::rc::Rc;
+
+use super::frames;
+use crate::{
+    core::{
+        element::DicomElement,
+        error::{DicomError, DicomResult},
+        reader::{DicomFile, PIXEL_DATA},
+        transfer_syntax,
+    },
+    image::render::PLANAR_CONFIGURATION,
+    plugins::codec::FrameInfo,
+};
+
+pub const BURNED_IN_ANNOTATION: (u16, u16) = (0x0028, 0x0301);
+
+// Rectangle in pixel coordinates, from the top left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn right(&self) -> u32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> u32 {
+        self.y + self.height
+    }
+
+    pub fn intersects(&self, other: &Region) -> bool {
+        self.x < other.right()
+            && other.x < self.right()
+            && self.y < other.bottom()
+            && other.y < self.bottom()
+    }
+
+    // Smallest region covering both
+    pub fn union(&self, other: &Region) -> Region {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Region::new(
+            x,
+            y,
+            self.right().max(other.right()) - x,
+            self.bottom().max(other.bottom()) - y,
+        )
+    }
+
+    // Grown by the margin on every side, kept within the image
+    pub fn padded(&self, margin: u32, columns: u32, rows: u32) -> Region {
+        let x = self.x.saturating_sub(margin);
+        let y = self.y.saturating_sub(margin);
+        Region::new(
+            x,
+            y,
+            (self.right() + margin).min(columns) - x,
+            (self.bottom() + margin).min(rows) - y,
+        )
+    }
+}
+
+// Blacks out the regions in every frame of native pixel data. Compressed
+// pixel data has to be decompressed first, as rewriting it in place would
+// leave the original samples recoverable
+pub fn redact(file: &mut DicomFile, regions: &[Region]) -> DicomResult<()> {
+    if transfer_syntax::is_encapsulated(&file.transfer_syntax) {
+        return Err(DicomError::UnsupportedTransferSyntax(format!(
+            "{} pixel data cannot be redacted in place",
+            file.transfer_syntax
+        )));
+    }
+
+    let dataset = &mut file.dataset;
+    let info = FrameInfo::from_dataset(dataset)?;
+    let planar = dataset
+        .string(PLANAR_CONFIGURATION)
+        .is_some_and(|value| value.trim() == "1");
+    let fill = fill_values(&info);
+    let bytes = info.bytes_per_sample();
+    let samples = info.samples_per_pixel as usize;
+    let (columns, rows) = (info.columns as u32, info.rows as u32);
+
+    let mut pixels = frames::split_frames(dataset, &file.transfer_syntax)?;
+    for frame in &mut pixels {
+        for region in regions {
+            for y in region.y.min(rows)..region.bottom().min(rows) {
+                for x in region.x.min(columns)..region.right().min(columns) {
+                    let pixel = (y * columns + x) as usize;
+                    for (sample, value) in fill.iter().enumerate().take(samples) {
+                        let index = if planar {
+                            sample * info.pixel_count() + pixel
+                        } else {
+                            pixel * samples + sample
+                        };
+                        let start = index * bytes;
+                        let encoded = value.to_le_bytes();
+                        frame[start..start + bytes].copy_from_slice(&encoded[..bytes]);
+                    }
+                }
+            }
+        }
+    }
+
+    dataset.put(Rc::new(DicomElement::new(
+        PIXEL_DATA,
+        frames::join_frames(&pixels, &file.transfer_syntax, info.bits_allocated),
+    )));
+    Ok(())
+}
+
+// Stored values displaying as black for each sample, signed ones in two's
+// complement over the whole sample
+fn fill_values(info: &FrameInfo) -> Vec<u32> {
+    let bits = info.bits_stored.clamp(1, 32) as u32;
+    let (minimum, maximum) = if info.pixel_representation == 1 {
+        (u32::MAX << (bits - 1), (1u32 << (bits - 1)) - 1)
+    } else {
+        (0, ((1u64 << bits) - 1) as u32)
+    };
+
+    match info.photometric_interpretation.as_str() {
+        "MONOCHROME1" => vec![maximum],
+        "MONOCHROME2" => vec![minimum],
+        photometric if photometric.starts_with("YBR") => vec![0, 128, 128],
+        _ => vec![0; info.samples_per_pixel.max(1) as usize],
+    }
+}
@@ -0,0 +1,205 @@
+use std::rc::Rc;
+
+use super::redact::{self, Region, BURNED_IN_ANNOTATION};
+use crate::{
+    core::{element::DicomElement, error::DicomResult, reader::DicomFile},
+    image::render::{number_of_frames, render_frame},
+};
+
+// Finds burned in text by its glyphs, bright and small connected blobs lined
+// up next to each other. It is a screening pass for annotations overlaid on
+// the image, not a character recognizer
+#[derive(Debug, Clone)]
+pub struct TextDetector {
+    // Fraction of full brightness a pixel needs to count as text
+    pub threshold: f32,
+    pub min_glyph_height: u32,
+    pub max_glyph_height: u32,
+    // Largest horizontal gap between glyphs of one line
+    pub merge_distance: u32,
+    // Glyphs a line needs before it counts as text
+    pub min_glyphs: usize,
+    pub margin: u32,
+}
+
+impl Default for TextDetector {
+    fn default() -> Self {
+        TextDetector {
+            threshold: 0.85,
+            min_glyph_height: 5,
+            max_glyph_height: 48,
+            merge_distance: 8,
+            min_glyphs: 2,
+            margin: 2,
+        }
+    }
+}
+
+impl TextDetector {
+    pub fn new() -> Self {
+        TextDetector::default()
+    }
+
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_glyph_height(mut self, min: u32, max: u32) -> Self {
+        self.min_glyph_height = min;
+        self.max_glyph_height = max.max(min);
+        self
+    }
+
+    pub fn with_merge_distance(mut self, distance: u32) -> Self {
+        self.merge_distance = distance;
+        self
+    }
+
+    pub fn with_min_glyphs(mut self, glyphs: usize) -> Self {
+        self.min_glyphs = glyphs.max(1);
+        self
+    }
+
+    pub fn with_margin(mut self, margin: u32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    // Regions likely holding text in one frame, numbered from 0
+    pub fn detect(&self, file: &DicomFile, frame: usize) -> DicomResult<Vec<Region>> {
+        let rendered = render_frame(file, frame)?;
+        let (width, height) = (rendered.width, rendered.height);
+        let cutoff = (self.threshold * 255.0) as u32;
+        let mut mask: Vec<bool> = rendered
+            .pixels
+            .chunks(4)
+            .map(|rgba| (rgba[0] as u32 * 299 + rgba[1] as u32 * 587 + rgba[2] as u32 * 114) / 1000)
+            .map(|luminance| luminance >= cutoff)
+            .collect();
+
+        let glyphs: Vec<Region> = components(&mut mask, width, height)
+            .into_iter()
+            .filter(|glyph| {
+                glyph.height >= self.min_glyph_height
+                    && glyph.height <= self.max_glyph_height
+                    && glyph.width <= self.max_glyph_height * 2
+            })
+            .collect();
+
+        Ok(self
+            .lines(glyphs)
+            .into_iter()
+            .filter(|(_, count)| *count >= self.min_glyphs)
+            .map(|(line, _)| line.padded(self.margin, width, height))
+            .collect())
+    }
+
+    // Regions of every frame, overlapping ones merged
+    pub fn detect_all(&self, file: &DicomFile) -> DicomResult<Vec<Region>> {
+        let mut regions = Vec::new();
+        for frame in 0..number_of_frames(&file.dataset) {
+            for region in self.detect(file, frame)? {
+                merge(&mut regions, region);
+            }
+        }
+        Ok(regions)
+    }
+
+    // Chains glyphs of similar height sitting on the same row into lines,
+    // counting the glyphs of each
+    fn lines(&self, mut glyphs: Vec<Region>) -> Vec<(Region, usize)> {
+        glyphs.sort_by_key(|glyph| (glyph.y, glyph.x));
+
+        let mut lines: Vec<(Region, usize)> = Vec::new();
+        for glyph in glyphs {
+            let line = lines.iter_mut().find(|(line, _)| {
+                let overlap = glyph.bottom().min(line.bottom()) as i64 - glyph.y.max(line.y) as i64;
+                let gap = if glyph.x >= line.right() {
+                    glyph.x - line.right()
+                } else {
+                    line.x.saturating_sub(glyph.right())
+                };
+                overlap * 2 >= glyph.height.min(line.height) as i64 && gap <= self.merge_distance
+            });
+            match line {
+                Some((line, count)) => {
+                    *line = line.union(&glyph);
+                    *count += 1;
+                }
+                None => lines.push((glyph, 1)),
+            }
+        }
+        lines
+    }
+}
+
+// Suggests the redaction of the detected text, or applies it when asked,
+// then marks the instance as free of burned in annotation
+pub fn clean_pixel_data(
+    file: &mut DicomFile,
+    detector: &TextDetector,
+    apply: bool,
+) -> DicomResult<Vec<Region>> {
+    let regions = detector.detect_all(file)?;
+    if apply {
+        redact::redact(file, &regions)?;
+        file.dataset.put(Rc::new(DicomElement::from_string(
+            BURNED_IN_ANNOTATION,
+            "CS",
+            "NO",
+        )));
+    }
+    Ok(regions)
+}
+
+// Bounding boxes of the 4-connected components of the mask, clearing it
+fn components(mask: &mut [bool], width: u32, height: u32) -> Vec<Region> {
+    let mut found = Vec::new();
+    let mut stack = Vec::new();
+    for start in 0..mask.len() {
+        if !mask[start] {
+            continue;
+        }
+
+        mask[start] = false;
+        stack.push(start);
+        let (mut left, mut top) = (u32::MAX, u32::MAX);
+        let (mut right, mut bottom) = (0, 0);
+        while let Some(index) = stack.pop() {
+            let (x, y) = (index as u32 % width, index as u32 / width);
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+
+            let mut visit = |next: usize| {
+                if mask[next] {
+                    mask[next] = false;
+                    stack.push(next);
+                }
+            };
+            if x > 0 {
+                visit(index - 1);
+            }
+            if x + 1 < width {
+                visit(index + 1);
+            }
+            if y > 0 {
+                visit(index - width as usize);
+            }
+            if y + 1 < height {
+                visit(index + width as usize);
+            }
+        }
+        found.push(Region::new(left, top, right - left + 1, bottom - top + 1));
+    }
+    found
+}
+
+fn merge(regions: &mut Vec<Region>, mut region: Region) {
+    while let Some(position) = regions.iter().position(|other| other.intersects(&region)) {
+        region = region.union(&regions.remove(position));
+    }
+    regions.push(region);
+}
@@ -0,0 +1,85 @@
+use std::rc::Rc;
+
+use super::frames;
+use crate::{
+    core::{
+        element::DicomElement,
+        error::{DicomError, DicomResult},
+        meta::SOP_CLASS_UID,
+        reader::{DicomFile, PIXEL_DATA, TRANSFER_SYNTAX_UID},
+        syntax_policy::{self, TransferSyntaxPolicy},
+        transfer_syntax,
+    },
+    plugins::codec::{CodecRegistry, FrameInfo},
+};
+
+pub const LOSSY_IMAGE_COMPRESSION: (u16, u16) = (0x0028, 0x2110);
+
+// Copy of the file in another transfer syntax, its frames decoded and encoded
+// again through the registry. Files without pixel data only change syntax
+pub fn transcode(
+    file: &DicomFile,
+    target: &str,
+    registry: &CodecRegistry,
+) -> DicomResult<DicomFile> {
+    let target = target.trim_end_matches(['\0', ' ']);
+    let source = file.transfer_syntax.trim_end_matches(['\0', ' ']);
+    if transfer_syntax::lookup(target).is_none() {
+        return Err(DicomError::UnsupportedTransferSyntax(target.to_string()));
+    }
+
+    let mut transcoded = file.clone();
+    transcoded.transfer_syntax = target.to_string();
+    transcoded
+        .meta
+        .put_string(TRANSFER_SYNTAX_UID, "UI", target);
+    if source == target || !file.dataset.contains(PIXEL_DATA) {
+        return Ok(transcoded);
+    }
+
+    let info = FrameInfo::from_dataset(&file.dataset)?;
+    let decode = transfer_syntax::is_encapsulated(source);
+    let encode = transfer_syntax::is_encapsulated(target);
+    let mut pixels = frames::split_frames(&file.dataset, source)?;
+    for frame in &mut pixels {
+        // Native frames come out of the reader in little endian already
+        if decode {
+            *frame = registry.decode(source, frame, &info)?;
+        }
+        if encode {
+            *frame = registry.encode(target, frame, &info)?;
+        }
+    }
+
+    transcoded.dataset.put(Rc::new(DicomElement::new(
+        PIXEL_DATA,
+        frames::join_frames(&pixels, target, info.bits_allocated),
+    )));
+    if syntax_policy::is_lossy_conversion(source, target) {
+        transcoded
+            .dataset
+            .put_string(LOSSY_IMAGE_COMPRESSION, "CS", "01");
+    }
+    Ok(transcoded)
+}
+
+// The file in the syntax the policy picks for its SOP class out of
+// `available`, untouched when its own syntax is acceptable
+pub fn transcode_for(
+    file: &DicomFile,
+    policy: &TransferSyntaxPolicy,
+    available: &[&str],
+    registry: &CodecRegistry,
+) -> DicomResult<DicomFile> {
+    let sop_class_uid = file.dataset.string(SOP_CLASS_UID).unwrap_or_default();
+    let target = policy
+        .target(&sop_class_uid, &file.transfer_syntax, available)
+        .ok_or_else(|| {
+            DicomError::UnsupportedTransferSyntax(format!(
+                "No transfer syntax the policy allows for {} among {}",
+                sop_class_uid,
+                available.join(", ")
+            ))
+        })?;
+    transcode(file, &target, registry)
+}
@@ -0,0 +1,1486 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{ErrorKind, Write},
+    net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use super::{
+    dimse::{self, CommandSet, DimseMessage},
+    pdu::{
+        self, AbortReason, AssociateAc, AssociateRj, AssociateRq, ExtendedNegotiation, Pdu,
+        PresentationContextAc, PresentationContextResult, PresentationContextRq, RoleSelection,
+        UserInformation,
+    },
+    status::DimseStatus,
+};
+use crate::core::{
+    dataset::Dataset,
+    error::{AbortSource, DicomError, DicomResult},
+    metrics, reader,
+    syntax_policy::TransferSyntaxPolicy,
+    writer,
+};
+
+pub use crate::core::writer::{IMPLEMENTATION_CLASS_UID, IMPLEMENTATION_VERSION_NAME};
+
+// States of the PS3.8 9.2 upper layer state machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    // Sta1
+    Idle,
+    // Sta2, transport connection open, awaiting A-ASSOCIATE-RQ
+    AwaitingAssociateRq,
+    // Sta3, awaiting the local A-ASSOCIATE response
+    AwaitingLocalAssociateResponse,
+    // Sta4, awaiting transport connection to open
+    AwaitingTransportOpen,
+    // Sta5, awaiting A-ASSOCIATE-AC or A-ASSOCIATE-RJ
+    AwaitingAssociateAc,
+    // Sta6
+    Established,
+    // Sta7, awaiting A-RELEASE-RP
+    AwaitingReleaseRp,
+    // Sta8, awaiting the local A-RELEASE response
+    AwaitingLocalReleaseResponse,
+    // Sta9, release collision requestor side, awaiting local A-RELEASE response
+    CollisionRequestorAwaitingResponse,
+    // Sta10, release collision acceptor side, awaiting A-RELEASE-RP
+    CollisionAcceptorAwaitingRp,
+    // Sta11, release collision requestor side, awaiting A-RELEASE-RP
+    CollisionRequestorAwaitingRp,
+    // Sta12, release collision acceptor side, awaiting local A-RELEASE response
+    CollisionAcceptorAwaitingResponse,
+    // Sta13, awaiting transport connection close
+    AwaitingTransportClose,
+}
+
+impl Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let number = match self {
+            State::Idle => 1,
+            State::AwaitingAssociateRq => 2,
+            State::AwaitingLocalAssociateResponse => 3,
+            State::AwaitingTransportOpen => 4,
+            State::AwaitingAssociateAc => 5,
+            State::Established => 6,
+            State::AwaitingReleaseRp => 7,
+            State::AwaitingLocalReleaseResponse => 8,
+            State::CollisionRequestorAwaitingResponse => 9,
+            State::CollisionAcceptorAwaitingRp => 10,
+            State::CollisionRequestorAwaitingRp => 11,
+            State::CollisionAcceptorAwaitingResponse => 12,
+            State::AwaitingTransportClose => 13,
+        };
+        write!(f, "Sta{}", number)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    // Evt1
+    AssociateRequest,
+    // Evt2
+    TransportConnected,
+    // Evt3
+    AssociateAcReceived,
+    // Evt4
+    AssociateRjReceived,
+    // Evt5
+    TransportIndication,
+    // Evt6
+    AssociateRqReceived,
+    // Evt7
+    AssociateAccept,
+    // Evt8
+    AssociateReject,
+    // Evt9
+    PDataRequest,
+    // Evt10
+    PDataReceived,
+    // Evt11
+    ReleaseRequest,
+    // Evt12
+    ReleaseRqReceived,
+    // Evt13
+    ReleaseRpReceived,
+    // Evt14
+    ReleaseResponse,
+    // Evt15
+    AbortRequest,
+    // Evt16
+    AbortReceived,
+    // Evt17
+    TransportClosed,
+    // Evt18
+    ArtimExpired,
+    // Evt19
+    InvalidPdu,
+}
+
+impl Event {
+    pub fn received(pdu: &Pdu) -> Self {
+        match pdu {
+            Pdu::AssociateRq(_) => Event::AssociateRqReceived,
+            Pdu::AssociateAc(_) => Event::AssociateAcReceived,
+            Pdu::AssociateRj(_) => Event::AssociateRjReceived,
+            Pdu::PData(_) => Event::PDataReceived,
+            Pdu::ReleaseRq => Event::ReleaseRqReceived,
+            Pdu::ReleaseRp => Event::ReleaseRpReceived,
+            Pdu::Abort { .. } => Event::AbortReceived,
+        }
+    }
+}
+
+// Actions of PS3.8 Table 9-6 to 9-9, named after the standard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    // Issue transport connect
+    Ae1,
+    // Send A-ASSOCIATE-RQ
+    Ae2,
+    // Issue A-ASSOCIATE confirmation (accept)
+    Ae3,
+    // Issue A-ASSOCIATE confirmation (reject) and close transport
+    Ae4,
+    // Accept transport connection and start ARTIM
+    Ae5,
+    // Stop ARTIM and check the A-ASSOCIATE-RQ
+    Ae6,
+    // Send A-ASSOCIATE-AC
+    Ae7,
+    // Send A-ASSOCIATE-RJ and start ARTIM
+    Ae8,
+    // Send P-DATA-TF
+    Dt1,
+    // Issue P-DATA indication
+    Dt2,
+    // Send A-RELEASE-RQ
+    Ar1,
+    // Issue A-RELEASE indication
+    Ar2,
+    // Issue A-RELEASE confirmation and close transport
+    Ar3,
+    // Send A-RELEASE-RP and start ARTIM
+    Ar4,
+    // Stop ARTIM
+    Ar5,
+    // Issue P-DATA indication while awaiting release
+    Ar6,
+    // Send P-DATA-TF while awaiting release
+    Ar7,
+    // Release collision
+    Ar8,
+    // Send A-RELEASE-RP
+    Ar9,
+    // Issue A-RELEASE confirmation
+    Ar10,
+    // Send A-ABORT and start ARTIM
+    Aa1,
+    // Stop ARTIM and close transport
+    Aa2,
+    // Issue A-ABORT indication and close transport
+    Aa3,
+    // Issue A-P-ABORT indication
+    Aa4,
+    // Stop ARTIM
+    Aa5,
+    // Ignore the PDU
+    Aa6,
+    // Send A-ABORT
+    Aa7,
+    // Send A-ABORT, issue A-P-ABORT indication and start ARTIM
+    Aa8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Requestor,
+    Acceptor,
+}
+
+#[derive(Debug, Clone)]
+pub struct StateMachine {
+    state: State,
+    role: Role,
+}
+
+impl StateMachine {
+    pub fn new(role: Role) -> Self {
+        StateMachine {
+            state: State::Idle,
+            role,
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    // Follows the PS3.8 Table 9-10 transitions; events the local user may not
+    // raise in the current state are errors, unexpected PDUs lead to an abort
+    pub fn transition(&mut self, event: Event) -> DicomResult<Action> {
+        use Event::*;
+        use State::*;
+
+        let (action, next) = match (self.state, event) {
+            (Idle, AssociateRequest) => (Action::Ae1, AwaitingTransportOpen),
+            (Idle, TransportIndication) => (Action::Ae5, AwaitingAssociateRq),
+
+            (AwaitingAssociateRq, AssociateRqReceived) => {
+                (Action::Ae6, AwaitingLocalAssociateResponse)
+            }
+            (AwaitingAssociateRq, AbortReceived | ArtimExpired) => (Action::Aa2, Idle),
+            (AwaitingAssociateRq, TransportClosed) => (Action::Aa5, Idle),
+            (
+                AwaitingAssociateRq,
+                AssociateAcReceived | AssociateRjReceived | PDataReceived | ReleaseRqReceived
+                | ReleaseRpReceived | InvalidPdu,
+            ) => (Action::Aa1, AwaitingTransportClose),
+
+            (AwaitingLocalAssociateResponse, AssociateAccept) => (Action::Ae7, Established),
+            (AwaitingLocalAssociateResponse, AssociateReject) => {
+                (Action::Ae8, AwaitingTransportClose)
+            }
+
+            (AwaitingTransportOpen, TransportConnected) => (Action::Ae2, AwaitingAssociateAc),
+            (AwaitingTransportOpen, AbortRequest) => (Action::Aa2, Idle),
+            (AwaitingTransportOpen, TransportClosed) => (Action::Aa4, Idle),
+
+            (AwaitingAssociateAc, AssociateAcReceived) => (Action::Ae3, Established),
+            (AwaitingAssociateAc, AssociateRjReceived) => (Action::Ae4, Idle),
+
+            (Established, PDataRequest) => (Action::Dt1, Established),
+            (Established, PDataReceived) => (Action::Dt2, Established),
+            (Established, ReleaseRequest) => (Action::Ar1, AwaitingReleaseRp),
+            (Established, ReleaseRqReceived) => (Action::Ar2, AwaitingLocalReleaseResponse),
+
+            (AwaitingReleaseRp, PDataReceived) => (Action::Ar6, AwaitingReleaseRp),
+            (AwaitingReleaseRp, ReleaseRqReceived) => {
+                let next = match self.role {
+                    Role::Requestor => CollisionRequestorAwaitingResponse,
+                    Role::Acceptor => CollisionAcceptorAwaitingRp,
+                };
+                (Action::Ar8, next)
+            }
+            (AwaitingReleaseRp, ReleaseRpReceived) => (Action::Ar3, Idle),
+
+            (AwaitingLocalReleaseResponse, PDataRequest) => {
+                (Action::Ar7, AwaitingLocalReleaseResponse)
+            }
+            (AwaitingLocalReleaseResponse, ReleaseResponse) => {
+                (Action::Ar4, AwaitingTransportClose)
+            }
+
+            (CollisionRequestorAwaitingResponse, ReleaseResponse) => {
+                (Action::Ar9, CollisionRequestorAwaitingRp)
+            }
+            (CollisionAcceptorAwaitingRp, ReleaseRpReceived) => {
+                (Action::Ar10, CollisionAcceptorAwaitingResponse)
+            }
+            (CollisionRequestorAwaitingRp, ReleaseRpReceived) => (Action::Ar3, Idle),
+            (CollisionAcceptorAwaitingResponse, ReleaseResponse) => {
+                (Action::Ar4, AwaitingTransportClose)
+            }
+
+            (AwaitingTransportClose, AssociateRqReceived) => (Action::Aa7, AwaitingTransportClose),
+            (AwaitingTransportClose, AbortReceived | ArtimExpired) => (Action::Aa2, Idle),
+            (AwaitingTransportClose, TransportClosed) => (Action::Ar5, Idle),
+            (
+                AwaitingTransportClose,
+                AssociateAcReceived | AssociateRjReceived | PDataReceived | ReleaseRqReceived
+                | ReleaseRpReceived | InvalidPdu,
+            ) => (Action::Aa6, AwaitingTransportClose),
+
+            // Common to the states from Sta3 and Sta5 to Sta12
+            (state, AbortRequest) if state != Idle && state != AwaitingAssociateRq => {
+                (Action::Aa1, AwaitingTransportClose)
+            }
+            (state, AbortReceived) if state != Idle => (Action::Aa3, Idle),
+            (state, TransportClosed) if state != Idle => (Action::Aa4, Idle),
+            (
+                state,
+                AssociateAcReceived | AssociateRjReceived | AssociateRqReceived | PDataReceived
+                | ReleaseRqReceived | ReleaseRpReceived | InvalidPdu,
+            ) if state != Idle => (Action::Aa8, AwaitingTransportClose),
+
+            (state, event) => {
+                return Err(DicomError::InvalidValue(format!(
+                    "{:?} is not valid in state {}",
+                    event, state
+                )))
+            }
+        };
+
+        self.state = next;
+        Ok(action)
+    }
+}
+
+// DIMSE timeouts, optionally per command field
+#[derive(Debug, Clone, Default)]
+pub struct DimseTimeouts {
+    pub default: Option<Duration>,
+    pub per_command: HashMap<u16, Duration>,
+}
+
+impl DimseTimeouts {
+    pub fn new(default: Option<Duration>) -> Self {
+        DimseTimeouts {
+            default,
+            per_command: HashMap::new(),
+        }
+    }
+
+    pub fn with_command(mut self, command_field: u16, timeout: Duration) -> Self {
+        self.per_command.insert(command_field & 0x7FFF, timeout);
+        self
+    }
+
+    // Responses share the timeout of their request
+    pub fn for_command(&self, command_field: u16) -> Option<Duration> {
+        self.per_command
+            .get(&(command_field & 0x7FFF))
+            .copied()
+            .or(self.default)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AssociationOptions {
+    pub calling_ae: String,
+    pub called_ae: String,
+    pub presentation_contexts: Vec<PresentationContextRq>,
+    // What we accept, advertised to the peer
+    pub max_pdu_length: u32,
+    // Caps the PDUs we send below what the peer accepts
+    pub max_send_pdu_length: Option<u32>,
+    pub connect_timeout: Option<Duration>,
+    // ARTIM, bounds association negotiation, release and connection close
+    pub artim_timeout: Duration,
+    pub dimse_timeouts: DimseTimeouts,
+    // Proposed by a requestor, the roles an acceptor agrees to otherwise
+    pub role_selections: Vec<RoleSelection>,
+    // Proposed by a requestor, the most an acceptor agrees to otherwise
+    pub extended_negotiations: Vec<ExtendedNegotiation>,
+}
+
+impl Default for AssociationOptions {
+    fn default() -> Self {
+        AssociationOptions {
+            calling_ae: "ANY-SCU".to_string(),
+            called_ae: "ANY-SCP".to_string(),
+            presentation_contexts: Vec::new(),
+            max_pdu_length: pdu::DEFAULT_MAX_PDU_LENGTH,
+            max_send_pdu_length: None,
+            connect_timeout: Some(Duration::from_secs(30)),
+            artim_timeout: Duration::from_secs(30),
+            dimse_timeouts: DimseTimeouts::default(),
+            role_selections: Vec::new(),
+            extended_negotiations: Vec::new(),
+        }
+    }
+}
+
+impl AssociationOptions {
+    pub fn new(calling_ae: &str, called_ae: &str) -> Self {
+        AssociationOptions {
+            calling_ae: calling_ae.to_string(),
+            called_ae: called_ae.to_string(),
+            ..AssociationOptions::default()
+        }
+    }
+
+    // Context IDs are odd and assigned in order
+    pub fn with_presentation_context(
+        mut self,
+        abstract_syntax: &str,
+        transfer_syntaxes: &[&str],
+    ) -> Self {
+        let id = (self.presentation_contexts.len() * 2 + 1) as u8;
+        self.presentation_contexts.push(PresentationContextRq {
+            id,
+            abstract_syntax: abstract_syntax.to_string(),
+            transfer_syntaxes: transfer_syntaxes.iter().map(|ts| ts.to_string()).collect(),
+        });
+        self
+    }
+
+    // Proposes what the policy allows of `transfer_syntaxes` for the class,
+    // in its order of preference. Nothing is proposed when it allows none
+    pub fn with_policy_context(
+        self,
+        abstract_syntax: &str,
+        transfer_syntaxes: &[&str],
+        policy: &TransferSyntaxPolicy,
+    ) -> Self {
+        let proposal = policy.proposal(abstract_syntax, transfer_syntaxes);
+        if proposal.is_empty() {
+            return self;
+        }
+        self.with_presentation_context(abstract_syntax, &proposal)
+    }
+
+    // Roles of the requestor, C-GET needs `scp_role` for the storage classes
+    // it retrieves so the peer may send C-STORE requests back
+    pub fn with_role_selection(
+        mut self,
+        sop_class_uid: &str,
+        scu_role: bool,
+        scp_role: bool,
+    ) -> Self {
+        self.role_selections
+            .retain(|selection| selection.sop_class_uid != sop_class_uid);
+        self.role_selections.push(RoleSelection {
+            sop_class_uid: sop_class_uid.to_string(),
+            scu_role,
+            scp_role,
+        });
+        self
+    }
+
+    pub fn with_extended_negotiation(mut self, sop_class_uid: &str, information: &[u8]) -> Self {
+        self.extended_negotiations
+            .retain(|negotiation| negotiation.sop_class_uid != sop_class_uid);
+        self.extended_negotiations.push(ExtendedNegotiation {
+            sop_class_uid: sop_class_uid.to_string(),
+            service_class_application_information: information.to_vec(),
+        });
+        self
+    }
+
+    pub fn with_max_pdu_length(mut self, max_pdu_length: u32) -> Self {
+        self.max_pdu_length = max_pdu_length;
+        self
+    }
+
+    pub fn with_max_send_pdu_length(mut self, max_pdu_length: Option<u32>) -> Self {
+        self.max_send_pdu_length = max_pdu_length;
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn with_artim_timeout(mut self, timeout: Duration) -> Self {
+        self.artim_timeout = timeout;
+        self
+    }
+
+    pub fn with_dimse_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.dimse_timeouts.default = timeout;
+        self
+    }
+
+    pub fn with_command_timeout(mut self, command_field: u16, timeout: Duration) -> Self {
+        self.dimse_timeouts = self.dimse_timeouts.with_command(command_field, timeout);
+        self
+    }
+}
+
+// What an acceptor answers to an A-ASSOCIATE-RQ
+#[derive(Debug, Clone)]
+pub enum AssociateResponse {
+    Accept(Vec<PresentationContextAc>),
+    Reject(AssociateRj),
+}
+
+#[derive(Debug)]
+pub struct Association {
+    stream: TcpStream,
+    machine: StateMachine,
+    options: AssociationOptions,
+    peer_ae: String,
+    peer_max_pdu_length: u32,
+    presentation_contexts: Vec<PresentationContextAc>,
+    abstract_syntaxes: HashMap<u8, String>,
+    // As agreed in the A-ASSOCIATE-AC
+    role_selections: Vec<RoleSelection>,
+    extended_negotiations: Vec<ExtendedNegotiation>,
+    // Start of the operations awaiting their final response, by direction
+    // of the request and message ID
+    operations: HashMap<(bool, u16), Instant>,
+    // P-DATA values, as counted for the metrics
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+impl Association {
+    pub fn request<A: ToSocketAddrs>(address: A, options: AssociationOptions) -> DicomResult<Self> {
+        let mut machine = StateMachine::new(Role::Requestor);
+        machine.transition(Event::AssociateRequest)?;
+
+        let stream = connect(address, options.connect_timeout).inspect_err(|_| {
+            let _ = machine.transition(Event::TransportClosed);
+        })?;
+        stream.set_nodelay(true)?;
+        machine.transition(Event::TransportConnected)?;
+
+        let mut association = Association {
+            stream,
+            machine,
+            peer_ae: options.called_ae.clone(),
+            peer_max_pdu_length: 0,
+            presentation_contexts: Vec::new(),
+            role_selections: Vec::new(),
+            extended_negotiations: Vec::new(),
+            operations: HashMap::new(),
+            bytes_sent: 0,
+            bytes_received: 0,
+            abstract_syntaxes: options
+                .presentation_contexts
+                .iter()
+                .map(|context| (context.id, context.abstract_syntax.clone()))
+                .collect(),
+            options,
+        };
+
+        let rq = AssociateRq {
+            protocol_version: 1,
+            called_ae: association.options.called_ae.clone(),
+            calling_ae: association.options.calling_ae.clone(),
+            application_context: pdu::APPLICATION_CONTEXT_NAME.to_string(),
+            presentation_contexts: association.options.presentation_contexts.clone(),
+            user_information: association.user_information(
+                &association.options.role_selections,
+                &association.options.extended_negotiations,
+            ),
+        };
+        association.send(&Pdu::AssociateRq(rq))?;
+
+        let artim = association.options.artim_timeout;
+        match association.receive(Some(artim), "A-ASSOCIATE-AC")? {
+            Pdu::AssociateAc(ac) => {
+                association.peer_max_pdu_length = ac.user_information.max_pdu_length;
+                association.presentation_contexts = ac.presentation_contexts;
+                // A peer may only narrow down what we proposed
+                association.role_selections = ac
+                    .user_information
+                    .role_selections
+                    .into_iter()
+                    .filter_map(|reply| {
+                        let proposed = association
+                            .options
+                            .role_selections
+                            .iter()
+                            .find(|proposed| proposed.sop_class_uid == reply.sop_class_uid)?;
+                        Some(RoleSelection {
+                            scu_role: reply.scu_role && proposed.scu_role,
+                            scp_role: reply.scp_role && proposed.scp_role,
+                            sop_class_uid: reply.sop_class_uid,
+                        })
+                    })
+                    .collect();
+                association.extended_negotiations = agree(
+                    &association.options.extended_negotiations,
+                    &ac.user_information.extended_negotiations,
+                );
+                Ok(association)
+            }
+            Pdu::AssociateRj(rj) => {
+                association.close();
+                Err(DicomError::AssociationRejected(rj.to_string()))
+            }
+            other => Err(association.unexpected(&other)),
+        }
+    }
+
+    // Negotiates an incoming association, `respond` decides on the request
+    pub fn accept<F>(
+        stream: TcpStream,
+        options: AssociationOptions,
+        respond: F,
+    ) -> DicomResult<Self>
+    where
+        F: FnOnce(&AssociateRq) -> AssociateResponse,
+    {
+        let mut machine = StateMachine::new(Role::Acceptor);
+        machine.transition(Event::TransportIndication)?;
+        stream.set_nodelay(true)?;
+
+        let mut association = Association {
+            stream,
+            machine,
+            peer_ae: String::new(),
+            peer_max_pdu_length: 0,
+            presentation_contexts: Vec::new(),
+            role_selections: Vec::new(),
+            extended_negotiations: Vec::new(),
+            operations: HashMap::new(),
+            bytes_sent: 0,
+            bytes_received: 0,
+            abstract_syntaxes: HashMap::new(),
+            options,
+        };
+
+        let artim = association.options.artim_timeout;
+        let rq = match association.receive(Some(artim), "A-ASSOCIATE-RQ")? {
+            Pdu::AssociateRq(rq) => rq,
+            other => return Err(association.unexpected(&other)),
+        };
+
+        association.peer_ae = rq.calling_ae.clone();
+        association.peer_max_pdu_length = rq.user_information.max_pdu_length;
+        association.options.calling_ae = rq.called_ae.clone();
+        association.options.called_ae = rq.calling_ae.clone();
+
+        if rq.protocol_version & 1 == 0 {
+            return Err(association.reject(AssociateRj {
+                result: 1,
+                source: 2,
+                reason: 2,
+            }));
+        }
+
+        match respond(&rq) {
+            AssociateResponse::Accept(contexts) => {
+                association.machine.transition(Event::AssociateAccept)?;
+                association.abstract_syntaxes = rq
+                    .presentation_contexts
+                    .iter()
+                    .map(|context| (context.id, context.abstract_syntax.clone()))
+                    .collect();
+                association.presentation_contexts = contexts.clone();
+
+                // Proposals for classes we have no opinion on go unanswered,
+                // which leaves the default roles in place
+                association.role_selections =
+                    rq.user_information
+                        .role_selections
+                        .iter()
+                        .filter_map(|proposed| {
+                            let allowed =
+                                association.options.role_selections.iter().find(|allowed| {
+                                    allowed.sop_class_uid == proposed.sop_class_uid
+                                })?;
+                            Some(RoleSelection {
+                                sop_class_uid: proposed.sop_class_uid.clone(),
+                                scu_role: proposed.scu_role && allowed.scu_role,
+                                scp_role: proposed.scp_role && allowed.scp_role,
+                            })
+                        })
+                        .collect();
+
+                // Only what both sides support is agreed, an acceptor never
+                // answers for classes it knows nothing of
+                association.extended_negotiations = agree(
+                    &rq.user_information.extended_negotiations,
+                    &association.options.extended_negotiations,
+                );
+
+                let ac = AssociateAc {
+                    protocol_version: 1,
+                    called_ae: rq.called_ae.clone(),
+                    calling_ae: rq.calling_ae.clone(),
+                    application_context: pdu::APPLICATION_CONTEXT_NAME.to_string(),
+                    presentation_contexts: contexts,
+                    user_information: association.user_information(
+                        &association.role_selections,
+                        &association.extended_negotiations,
+                    ),
+                };
+                association.send(&Pdu::AssociateAc(ac))?;
+                metrics::global().associations_accepted.increment();
+                Ok(association)
+            }
+            AssociateResponse::Reject(rj) => Err(association.reject(rj)),
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.machine.state()
+    }
+
+    pub fn peer_ae(&self) -> &str {
+        &self.peer_ae
+    }
+
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    pub fn peer_address(&self) -> DicomResult<SocketAddr> {
+        Ok(self.stream.peer_addr()?)
+    }
+
+    pub fn peer_max_pdu_length(&self) -> u32 {
+        self.peer_max_pdu_length
+    }
+
+    pub fn max_pdu_length(&self) -> u32 {
+        self.options.max_pdu_length
+    }
+
+    pub fn presentation_contexts(&self) -> &[PresentationContextAc] {
+        &self.presentation_contexts
+    }
+
+    pub fn abstract_syntax(&self, context_id: u8) -> Option<&str> {
+        self.abstract_syntaxes.get(&context_id).map(String::as_str)
+    }
+
+    // The accepted context for an abstract syntax and its transfer syntax
+    pub fn context_for(&self, abstract_syntax: &str) -> Option<&PresentationContextAc> {
+        self.presentation_contexts.iter().find(|context| {
+            context.result == PresentationContextResult::Acceptance
+                && self.abstract_syntax(context.id) == Some(abstract_syntax)
+        })
+    }
+
+    pub fn role_selections(&self) -> &[RoleSelection] {
+        &self.role_selections
+    }
+
+    pub fn extended_negotiations(&self) -> &[ExtendedNegotiation] {
+        &self.extended_negotiations
+    }
+
+    // Service class application information agreed for the SOP class
+    pub fn extended_negotiation(&self, sop_class_uid: &str) -> Option<&[u8]> {
+        self.extended_negotiations
+            .iter()
+            .find(|negotiation| negotiation.sop_class_uid == sop_class_uid)
+            .map(|negotiation| negotiation.service_class_application_information.as_slice())
+    }
+
+    // Whether the local side may issue requests for the SOP class
+    pub fn can_act_as_scu(&self, sop_class_uid: &str) -> bool {
+        let (requestor_scu, requestor_scp) = self.requestor_roles(sop_class_uid);
+        match self.machine.role() {
+            Role::Requestor => requestor_scu,
+            Role::Acceptor => requestor_scp,
+        }
+    }
+
+    // Whether the local side may serve requests for the SOP class
+    pub fn can_act_as_scp(&self, sop_class_uid: &str) -> bool {
+        let (requestor_scu, requestor_scp) = self.requestor_roles(sop_class_uid);
+        match self.machine.role() {
+            Role::Requestor => requestor_scp,
+            Role::Acceptor => requestor_scu,
+        }
+    }
+
+    fn requestor_roles(&self, sop_class_uid: &str) -> (bool, bool) {
+        self.role_selections
+            .iter()
+            .find(|selection| selection.sop_class_uid == sop_class_uid)
+            .map(|selection| (selection.scu_role, selection.scp_role))
+            .unwrap_or((true, false))
+    }
+
+    pub fn transfer_syntax(&self, context_id: u8) -> Option<&str> {
+        self.presentation_contexts
+            .iter()
+            .find(|context| {
+                context.id == context_id && context.result == PresentationContextResult::Acceptance
+            })
+            .map(|context| context.transfer_syntax.as_str())
+    }
+
+    pub fn send_message(&mut self, context_id: u8, message: &DimseMessage) -> DicomResult<()> {
+        let transfer_syntax = self
+            .transfer_syntax(context_id)
+            .ok_or_else(|| {
+                DicomError::InvalidValue(format!(
+                    "Presentation context {} was not accepted",
+                    context_id
+                ))
+            })?
+            .to_string();
+
+        // Sub-operations of a C-GET are C-STORE requests from the acceptor,
+        // which conformant peers only take when role selection allowed it
+        if message.command.command_field() == dimse::C_STORE_RQ {
+            let sop_class = self.abstract_syntax(context_id).unwrap_or_default();
+            if !self.can_act_as_scu(sop_class) {
+                return Err(DicomError::InvalidValue(format!(
+                    "Role selection does not allow sending C-STORE requests for {}",
+                    sop_class
+                )));
+            }
+        }
+
+        let command = message.encode_command()?;
+        self.send_fragments(context_id, true, &command)?;
+        if let Some(data_set) = &message.data_set {
+            self.stream_data_set(context_id, data_set, &transfer_syntax)?;
+        }
+        self.track(true, &message.command);
+
+        Ok(())
+    }
+
+    // Receives the next message, None when the peer asked to release the
+    // association, which has then already been answered
+    pub fn receive_message(&mut self) -> DicomResult<Option<(u8, DimseMessage)>> {
+        let received = self.receive_message_within(self.options.dimse_timeouts.default)?;
+        if let Some((_, message)) = &received {
+            self.track(false, &message.command);
+        }
+        Ok(received)
+    }
+
+    // Waits for the response to a request, under the timeout of its command
+    pub fn receive_response(&mut self, request: &CommandSet) -> DicomResult<(u8, DimseMessage)> {
+        let timeout = self
+            .options
+            .dimse_timeouts
+            .for_command(request.command_field());
+        match self.receive_message_within(timeout)? {
+            Some(message) => {
+                self.track(false, &message.1.command);
+                Ok(message)
+            }
+            None => Err(DicomError::AssociationAborted {
+                by: AbortSource::ServiceUser,
+                reason: "peer released the association while a response was pending".to_string(),
+            }),
+        }
+    }
+
+    // Times operations from request to final response for the metrics. The
+    // direction is part of the key, C-GET sub-operations run both ways on
+    // one association
+    fn track(&mut self, outgoing: bool, command: &CommandSet) {
+        if !command.is_response() {
+            if let Some(message_id) = command.u16(dimse::MESSAGE_ID) {
+                self.operations
+                    .insert((outgoing, message_id), Instant::now());
+            }
+            return;
+        }
+
+        let status = DimseStatus::from_u16(command.u16(dimse::STATUS).unwrap_or_default());
+        let Some(message_id) = command.u16(dimse::MESSAGE_ID_BEING_RESPONDED_TO) else {
+            return;
+        };
+        if status.is_pending() {
+            return;
+        }
+        if let Some(started) = self.operations.remove(&(!outgoing, message_id)) {
+            let operation = dimse::command_name(command.command_field()).trim_end_matches("-RSP");
+            metrics::global()
+                .dimse_latency(operation)
+                .observe(started.elapsed());
+        }
+    }
+
+    // Whether the peer sent anything not read yet, without blocking, so long
+    // running services can look for a C-CANCEL between their responses
+    pub fn has_pending_input(&mut self) -> DicomResult<bool> {
+        self.stream.set_nonblocking(true)?;
+        let result = self.stream.peek(&mut [0u8; 1]);
+        self.stream.set_nonblocking(false)?;
+        match result {
+            // A closed connection counts, the next read reports it
+            Ok(_) => Ok(true),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    // PDVs are read from the socket straight into the command and data set
+    // buffers, P-DATA-TF PDUs are never held as a whole
+    fn receive_message_within(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> DicomResult<Option<(u8, DimseMessage)>> {
+        let started = Instant::now();
+        let mut context_id = None;
+        let mut command: Vec<u8> = Vec::new();
+        let mut command_set: Option<CommandSet> = None;
+        let mut data: Vec<u8> = Vec::new();
+
+        loop {
+            let remaining = match timeout {
+                Some(timeout) => Some(timeout.checked_sub(started.elapsed()).ok_or_else(|| {
+                    self.abort_with(AbortReason::NotSpecified);
+                    DicomError::Timeout("DIMSE message".to_string())
+                })?),
+                None => None,
+            };
+
+            self.stream.set_read_timeout(remaining)?;
+            let (pdu_type, length) = self.read_header("DIMSE message")?;
+            if pdu_type != pdu::P_DATA_TF {
+                match self.receive_body(pdu_type, length, "DIMSE message")? {
+                    Pdu::ReleaseRq => {
+                        self.machine.transition(Event::ReleaseResponse)?;
+                        self.send(&Pdu::ReleaseRp)?;
+                        self.await_close();
+                        return Ok(None);
+                    }
+                    other => return Err(self.unexpected(&other)),
+                }
+            }
+            self.machine.transition(Event::PDataReceived)?;
+
+            let mut left = length as usize;
+            while left > 0 {
+                let (item_length, pdv_context, control) = self.read_pdv_header("DIMSE message")?;
+                let size = item_length as usize - 2;
+                if item_length as usize + 4 > left {
+                    return Err(self.abort_invalid(format!(
+                        "PDV of {} bytes in {} bytes of P-DATA-TF",
+                        item_length, left
+                    )));
+                }
+                left -= item_length as usize + 4;
+
+                if *context_id.get_or_insert(pdv_context) != pdv_context {
+                    return Err(self.abort_invalid(format!(
+                        "PDV for context {} inside a message on context {}",
+                        pdv_context,
+                        context_id.unwrap_or_default()
+                    )));
+                }
+
+                let is_command = control & 0x01 != 0;
+                let is_last = control & 0x02 != 0;
+
+                if is_command {
+                    if command_set.is_some() {
+                        return Err(self.abort_invalid("command fragment after the last one"));
+                    }
+                    self.read_into(&mut command, size)?;
+                    if is_last {
+                        let decoded = CommandSet::decode(&command)?;
+                        if !decoded.has_data_set() {
+                            return Ok(Some((
+                                pdv_context,
+                                DimseMessage {
+                                    command: decoded,
+                                    data_set: None,
+                                },
+                            )));
+                        }
+                        command_set = Some(decoded);
+                    }
+                } else {
+                    if command_set.is_none() {
+                        return Err(self.abort_invalid("data set fragment before the command"));
+                    }
+                    self.read_into(&mut data, size)?;
+                    if is_last {
+                        let transfer_syntax = self.accepted_syntax(pdv_context)?;
+                        let data_set = reader::read_dataset(&data, &transfer_syntax)?;
+                        return Ok(Some((
+                            pdv_context,
+                            DimseMessage {
+                                command: command_set.take().unwrap(),
+                                data_set: Some(data_set),
+                            },
+                        )));
+                    }
+                }
+            }
+        }
+    }
+
+    // Sends a data set on its own, for services that stream identifiers
+    pub fn send_data_set(&mut self, context_id: u8, data_set: &Dataset) -> DicomResult<()> {
+        let transfer_syntax = self.accepted_syntax(context_id)?;
+        self.stream_data_set(context_id, data_set, &transfer_syntax)
+    }
+
+    fn stream_data_set(
+        &mut self,
+        context_id: u8,
+        data_set: &Dataset,
+        transfer_syntax: &str,
+    ) -> DicomResult<()> {
+        let mut pdvs = PdvWriter::new(self, context_id, false);
+        let encoded = writer::Encoder::new(&mut pdvs, transfer_syntax)
+            .and_then(|mut encoder| encoder.encode(data_set));
+        if let Err(error) = encoded {
+            return Err(pdvs.error.take().unwrap_or(error));
+        }
+        pdvs.finish()
+    }
+
+    fn accepted_syntax(&self, context_id: u8) -> DicomResult<String> {
+        self.transfer_syntax(context_id)
+            .map(str::to_string)
+            .ok_or_else(|| {
+                DicomError::InvalidValue(format!(
+                    "Presentation context {} was not accepted",
+                    context_id
+                ))
+            })
+    }
+
+    pub fn release(mut self) -> DicomResult<()> {
+        self.machine.transition(Event::ReleaseRequest)?;
+        self.send(&Pdu::ReleaseRq)?;
+
+        let artim = self.options.artim_timeout;
+        loop {
+            match self.receive(Some(artim), "A-RELEASE-RP")? {
+                Pdu::ReleaseRp => {
+                    self.close();
+                    return Ok(());
+                }
+                // Late data for a cancelled operation is dropped
+                Pdu::PData(_) => {}
+                Pdu::ReleaseRq => {
+                    // Release collision, the requestor answers first
+                    if self.machine.role() == Role::Requestor {
+                        self.machine.transition(Event::ReleaseResponse)?;
+                        self.send(&Pdu::ReleaseRp)?;
+                    } else {
+                        match self.receive(Some(artim), "A-RELEASE-RP")? {
+                            Pdu::ReleaseRp => {}
+                            other => return Err(self.unexpected(&other)),
+                        }
+                        self.machine.transition(Event::ReleaseResponse)?;
+                        self.send(&Pdu::ReleaseRp)?;
+                        self.await_close();
+                        return Ok(());
+                    }
+                }
+                other => return Err(self.unexpected(&other)),
+            }
+        }
+    }
+
+    pub fn abort(mut self) -> DicomResult<()> {
+        self.abort_with(AbortReason::NotSpecified);
+        Ok(())
+    }
+
+    fn user_information(
+        &self,
+        role_selections: &[RoleSelection],
+        extended_negotiations: &[ExtendedNegotiation],
+    ) -> UserInformation {
+        UserInformation {
+            max_pdu_length: self.options.max_pdu_length,
+            implementation_class_uid: IMPLEMENTATION_CLASS_UID.to_string(),
+            implementation_version_name: Some(IMPLEMENTATION_VERSION_NAME.to_string()),
+            role_selections: role_selections.to_vec(),
+            extended_negotiations: extended_negotiations.to_vec(),
+        }
+    }
+
+    fn send_fragments(&mut self, context_id: u8, is_command: bool, data: &[u8]) -> DicomResult<()> {
+        let mut pdvs = PdvWriter::new(self, context_id, is_command);
+        if let Err(error) = pdvs.write_all(data) {
+            return Err(pdvs.error.take().unwrap_or(error.into()));
+        }
+        pdvs.finish()
+    }
+
+    // Largest PDV value we send, the PDU length limit minus the 6 bytes of
+    // PDV item header
+    pub fn fragment_size(&self) -> usize {
+        let peer = match self.peer_max_pdu_length {
+            0 => pdu::PDU_LENGTH_LIMIT,
+            length => length,
+        };
+        let limit = match self.options.max_send_pdu_length {
+            Some(local) => peer.min(local),
+            None => peer,
+        };
+        (limit as usize).saturating_sub(6).max(1)
+    }
+
+    fn send_pdv(
+        &mut self,
+        context_id: u8,
+        is_command: bool,
+        is_last: bool,
+        data: &[u8],
+    ) -> DicomResult<()> {
+        self.machine.transition(Event::PDataRequest)?;
+        let result = pdu::write_pdv(&mut self.stream, context_id, is_command, is_last, data);
+        match result {
+            Ok(()) => {
+                self.bytes_sent += data.len() as u64;
+                metrics::global().bytes_sent.add(data.len() as u64);
+            }
+            Err(_) => {
+                let _ = self.machine.transition(Event::TransportClosed);
+            }
+        }
+        result
+    }
+
+    fn send(&mut self, pdu: &Pdu) -> DicomResult<()> {
+        self.write(pdu)
+    }
+
+    fn write(&mut self, pdu: &Pdu) -> DicomResult<()> {
+        let result = pdu::write_pdu(&mut self.stream, pdu);
+        if result.is_err() {
+            let _ = self.machine.transition(Event::TransportClosed);
+        }
+        result
+    }
+
+    // Reads the next PDU and runs it through the state machine; aborts and
+    // protocol violations come back as errors
+    fn receive(&mut self, timeout: Option<Duration>, awaiting: &str) -> DicomResult<Pdu> {
+        self.stream.set_read_timeout(timeout)?;
+        let (pdu_type, length) = self.read_header(awaiting)?;
+        self.receive_body(pdu_type, length, awaiting)
+    }
+
+    fn read_header(&mut self, awaiting: &str) -> DicomResult<(u8, u32)> {
+        let result = pdu::read_header(&mut self.stream, self.options.max_pdu_length);
+        self.checked(result, awaiting)
+    }
+
+    fn read_pdv_header(&mut self, awaiting: &str) -> DicomResult<(u32, u8, u8)> {
+        let result = pdu::read_pdv_header(&mut self.stream);
+        self.checked(result, awaiting)
+    }
+
+    fn read_into(&mut self, buffer: &mut Vec<u8>, length: usize) -> DicomResult<()> {
+        let start = buffer.len();
+        buffer.resize(start + length, 0);
+        let result = pdu::read_exact(&mut self.stream, &mut buffer[start..]);
+        self.checked(result, "DIMSE message")?;
+        self.bytes_received += length as u64;
+        metrics::global().bytes_received.add(length as u64);
+        Ok(())
+    }
+
+    fn receive_body(&mut self, pdu_type: u8, length: u32, awaiting: &str) -> DicomResult<Pdu> {
+        let mut body = vec![0u8; length as usize];
+        let result = pdu::read_exact(&mut self.stream, &mut body);
+        self.checked(result, awaiting)?;
+        let result = Pdu::decode(pdu_type, &body);
+        let pdu = self.checked(result, awaiting)?;
+
+        match self.machine.transition(Event::received(&pdu))? {
+            Action::Aa3 | Action::Aa2 => {
+                self.close();
+                let Pdu::Abort { source, reason } = pdu else {
+                    unreachable!()
+                };
+                Err(DicomError::AssociationAborted {
+                    by: source,
+                    reason: reason.to_string(),
+                })
+            }
+            Action::Aa1 | Action::Aa8 => {
+                self.send_abort(AbortReason::UnexpectedPdu);
+                self.close();
+                Err(DicomError::AssociationAborted {
+                    by: AbortSource::ServiceProvider,
+                    reason: format!("unexpected {} while awaiting {}", pdu_name(&pdu), awaiting),
+                })
+            }
+            _ => Ok(pdu),
+        }
+    }
+
+    // Turns read failures into timeouts or aborts, driving the state machine
+    fn checked<T>(&mut self, result: DicomResult<T>, awaiting: &str) -> DicomResult<T> {
+        match result {
+            Ok(value) => Ok(value),
+            Err(DicomError::Timeout(_)) => {
+                let event = match self.machine.state() {
+                    State::AwaitingAssociateRq | State::AwaitingTransportClose => {
+                        Event::ArtimExpired
+                    }
+                    _ => Event::AbortRequest,
+                };
+                if let Ok(Action::Aa1) = self.machine.transition(event) {
+                    self.send_abort(AbortReason::NotSpecified);
+                }
+                self.close();
+                Err(DicomError::Timeout(format!(
+                    "awaiting {} from {}",
+                    awaiting, self.peer_ae
+                )))
+            }
+            Err(error @ DicomError::Io(_)) => {
+                let _ = self.machine.transition(Event::TransportClosed);
+                Err(DicomError::AssociationAborted {
+                    by: AbortSource::ServiceProvider,
+                    reason: format!("connection lost while awaiting {}: {}", awaiting, error),
+                })
+            }
+            Err(error) => {
+                let _ = self.machine.transition(Event::InvalidPdu);
+                self.send_abort(AbortReason::InvalidPduParameterValue);
+                self.close();
+                Err(DicomError::AssociationAborted {
+                    by: AbortSource::ServiceProvider,
+                    reason: format!("invalid PDU while awaiting {}: {}", awaiting, error),
+                })
+            }
+        }
+    }
+
+    fn unexpected(&mut self, pdu: &Pdu) -> DicomError {
+        if self.machine.state() != State::Idle {
+            let _ = self.machine.transition(Event::AbortRequest);
+            self.send_abort(AbortReason::UnexpectedPdu);
+        }
+        self.close();
+        DicomError::AssociationAborted {
+            by: AbortSource::ServiceProvider,
+            reason: format!("unexpected {}", pdu_name(pdu)),
+        }
+    }
+
+    fn abort_invalid<S: Display>(&mut self, reason: S) -> DicomError {
+        self.abort_with(AbortReason::InvalidPduParameterValue);
+        DicomError::AssociationAborted {
+            by: AbortSource::ServiceProvider,
+            reason: reason.to_string(),
+        }
+    }
+
+    fn abort_with(&mut self, reason: AbortReason) {
+        if let Ok(Action::Aa1) = self.machine.transition(Event::AbortRequest) {
+            self.send_abort(reason);
+        }
+        self.close();
+    }
+
+    fn reject(&mut self, rj: AssociateRj) -> DicomError {
+        metrics::global().associations_rejected.increment();
+        let _ = self.machine.transition(Event::AssociateReject);
+        let _ = self.send(&Pdu::AssociateRj(rj));
+        self.await_close();
+        DicomError::AssociationRejected(rj.to_string())
+    }
+
+    fn send_abort(&mut self, reason: AbortReason) {
+        let _ = pdu::write_pdu(
+            &mut self.stream,
+            &Pdu::Abort {
+                source: AbortSource::ServiceProvider,
+                reason,
+            },
+        );
+    }
+
+    // The requestor closes the connection, we wait for it up to the ARTIM timeout
+    fn await_close(&mut self) {
+        let _ = self
+            .stream
+            .set_read_timeout(Some(self.options.artim_timeout));
+        let mut buffer = [0u8; 64];
+        let deadline = Instant::now() + self.options.artim_timeout;
+        while Instant::now() < deadline {
+            match std::io::Read::read(&mut self.stream, &mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+        let event = if Instant::now() < deadline {
+            Event::TransportClosed
+        } else {
+            Event::ArtimExpired
+        };
+        let _ = self.machine.transition(event);
+        self.close();
+    }
+
+    fn close(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+        if self.machine.state() != State::Idle {
+            let _ = self.machine.transition(Event::TransportClosed);
+        }
+    }
+}
+
+impl Drop for Association {
+    // Dropping an established association aborts it rather than leaving the
+    // peer waiting
+    fn drop(&mut self) {
+        if self.machine.state() == State::Established {
+            self.abort_with(AbortReason::NotSpecified);
+        }
+    }
+}
+
+// Cuts what is written into PDVs of the negotiated size, holding at most
+// one fragment in memory
+struct PdvWriter<'a> {
+    association: &'a mut Association,
+    context_id: u8,
+    is_command: bool,
+    fragment: usize,
+    buffer: Vec<u8>,
+    // The association error behind a failed write, io::Error cannot carry it
+    error: Option<DicomError>,
+}
+
+impl<'a> PdvWriter<'a> {
+    fn new(association: &'a mut Association, context_id: u8, is_command: bool) -> Self {
+        let fragment = association.fragment_size();
+        PdvWriter {
+            association,
+            context_id,
+            is_command,
+            fragment,
+            buffer: Vec::with_capacity(fragment.min(1 << 20)),
+            error: None,
+        }
+    }
+
+    fn send(&mut self, is_last: bool) -> std::io::Result<()> {
+        let result =
+            self.association
+                .send_pdv(self.context_id, self.is_command, is_last, &self.buffer);
+        self.buffer.clear();
+        result.map_err(|error| {
+            let io_error = std::io::Error::other(error.to_string());
+            self.error = Some(error);
+            io_error
+        })
+    }
+
+    // The last fragment is only sent here, so it always carries the last flag
+    fn finish(mut self) -> DicomResult<()> {
+        match self.send(true) {
+            Ok(()) => Ok(()),
+            Err(error) => Err(self.error.take().unwrap_or(error.into())),
+        }
+    }
+}
+
+impl Write for PdvWriter<'_> {
+    fn write(&mut self, mut data: &[u8]) -> std::io::Result<usize> {
+        let written = data.len();
+        while !data.is_empty() {
+            if self.buffer.len() == self.fragment {
+                self.send(false)?;
+            }
+            let take = (self.fragment - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn pdu_name(pdu: &Pdu) -> &'static str {
+    match pdu {
+        Pdu::AssociateRq(_) => "A-ASSOCIATE-RQ",
+        Pdu::AssociateAc(_) => "A-ASSOCIATE-AC",
+        Pdu::AssociateRj(_) => "A-ASSOCIATE-RJ",
+        Pdu::PData(_) => "P-DATA-TF",
+        Pdu::ReleaseRq => "A-RELEASE-RQ",
+        Pdu::ReleaseRp => "A-RELEASE-RP",
+        Pdu::Abort { .. } => "A-ABORT",
+    }
+}
+
+fn connect<A: ToSocketAddrs>(address: A, timeout: Option<Duration>) -> DicomResult<TcpStream> {
+    let mut last_error = None;
+    for address in address.to_socket_addrs()? {
+        let result = match timeout {
+            Some(timeout) => TcpStream::connect_timeout(&address, timeout),
+            None => TcpStream::connect(address),
+        };
+        match result {
+            Ok(stream) => return Ok(stream),
+            Err(error) if matches!(error.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => {
+                last_error = Some(DicomError::Timeout(format!("connecting to {}", address)))
+            }
+            Err(error) => last_error = Some(error.into()),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        DicomError::Io(std::io::Error::new(
+            ErrorKind::AddrNotAvailable,
+            "No address to connect to",
+        ))
+    }))
+}
+
+// Extended negotiation of the classes both lists hold. The bytes of the
+// application information are flags or levels of support, of which the
+// lower one holds; bytes only one side sent are dropped
+fn agree(
+    proposed: &[ExtendedNegotiation],
+    supported: &[ExtendedNegotiation],
+) -> Vec<ExtendedNegotiation> {
+    proposed
+        .iter()
+        .filter_map(|proposal| {
+            let support = supported
+                .iter()
+                .find(|support| support.sop_class_uid == proposal.sop_class_uid)?;
+            Some(ExtendedNegotiation {
+                sop_class_uid: proposal.sop_class_uid.clone(),
+                service_class_application_information: proposal
+                    .service_class_application_information
+                    .iter()
+                    .zip(&support.service_class_application_information)
+                    .map(|(proposed, supported)| *proposed.min(supported))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+// Accepts every context whose abstract syntax is listed, picking the first
+// proposed transfer syntax we support
+pub fn negotiate(rq: &AssociateRq, supported: &[(&str, &[&str])]) -> Vec<PresentationContextAc> {
+    negotiate_with_policy(rq, supported, &TransferSyntaxPolicy::default())
+}
+
+// As negotiate, with the policy choosing among the supported syntaxes the
+// peer proposed and refusing the ones it does not allow for the class
+pub fn negotiate_with_policy(
+    rq: &AssociateRq,
+    supported: &[(&str, &[&str])],
+    policy: &TransferSyntaxPolicy,
+) -> Vec<PresentationContextAc> {
+    rq.presentation_contexts
+        .iter()
+        .map(|context| {
+            let Some((_, syntaxes)) = supported
+                .iter()
+                .find(|(abstract_syntax, _)| *abstract_syntax == context.abstract_syntax)
+            else {
+                return PresentationContextAc {
+                    id: context.id,
+                    result: PresentationContextResult::AbstractSyntaxNotSupported,
+                    transfer_syntax: String::new(),
+                };
+            };
+
+            match policy.choose(
+                &context.abstract_syntax,
+                &context.transfer_syntaxes,
+                syntaxes,
+            ) {
+                Some(ts) => PresentationContextAc {
+                    id: context.id,
+                    result: PresentationContextResult::Acceptance,
+                    transfer_syntax: ts,
+                },
+                None => PresentationContextAc {
+                    id: context.id,
+                    result: PresentationContextResult::TransferSyntaxesNotSupported,
+                    transfer_syntax: String::new(),
+                },
+            }
+        })
+        .collect()
+}
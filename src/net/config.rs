@@ -0,0 +1,305 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::Deserialize;
+
+use super::{
+    association::{Association, AssociationOptions},
+    dimse::{CStoreRq, CStoreRsp, DimseCommand, DimseMessage, MessageIdGenerator, Priority},
+    status::DimseStatus,
+};
+use crate::core::{
+    dataset::Dataset,
+    error::{DicomError, DicomResult},
+    meta::{SOP_CLASS_UID, SOP_INSTANCE_UID},
+    transfer_syntax,
+};
+
+pub const DEFAULT_PORT: u16 = 104;
+
+// Proposed when a peer does not list its own
+pub const DEFAULT_TRANSFER_SYNTAXES: &[&str] = &[
+    transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN,
+    transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN,
+];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TlsSettings {
+    // PEM bundle of the CAs trusted for the peer certificate
+    pub ca_file: Option<String>,
+    pub cert_file: Option<String>,
+    pub key_file: Option<String>,
+    // Name checked against the peer certificate, the host otherwise
+    pub server_name: Option<String>,
+    pub accept_invalid_certs: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PeerConfig {
+    // Key of the peer in the registry, taken from its table name
+    #[serde(skip)]
+    pub name: String,
+    pub ae_title: String,
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub tls: Option<TlsSettings>,
+    // Empty allows any SOP class
+    #[serde(default)]
+    pub sop_classes: Vec<String>,
+    // In order of preference
+    #[serde(default)]
+    pub transfer_syntaxes: Vec<String>,
+}
+
+fn default_port() -> u16 {
+    DEFAULT_PORT
+}
+
+impl PeerConfig {
+    pub fn new(name: &str, ae_title: &str, host: &str, port: u16) -> Self {
+        PeerConfig {
+            name: name.to_string(),
+            ae_title: ae_title.to_string(),
+            host: host.to_string(),
+            port,
+            tls: None,
+            sop_classes: Vec::new(),
+            transfer_syntaxes: Vec::new(),
+        }
+    }
+
+    pub fn with_tls(mut self, tls: TlsSettings) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    pub fn with_sop_class(mut self, sop_class_uid: &str) -> Self {
+        self.sop_classes.push(sop_class_uid.to_string());
+        self
+    }
+
+    pub fn with_transfer_syntax(mut self, transfer_syntax: &str) -> Self {
+        self.transfer_syntaxes.push(transfer_syntax.to_string());
+        self
+    }
+
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    pub fn allows(&self, sop_class_uid: &str) -> bool {
+        self.sop_classes.is_empty() || self.sop_classes.iter().any(|uid| uid == sop_class_uid)
+    }
+
+    pub fn preferred_transfer_syntaxes(&self) -> Vec<&str> {
+        if self.transfer_syntaxes.is_empty() {
+            DEFAULT_TRANSFER_SYNTAXES.to_vec()
+        } else {
+            self.transfer_syntaxes.iter().map(String::as_str).collect()
+        }
+    }
+
+    // One presentation context per allowed SOP class, `sop_classes` narrows
+    // them when the peer allows any
+    pub fn association_options(
+        &self,
+        calling_ae: &str,
+        sop_classes: &[&str],
+    ) -> AssociationOptions {
+        let transfer_syntaxes = self.preferred_transfer_syntaxes();
+        let mut options = AssociationOptions::new(calling_ae, &self.ae_title);
+
+        let proposed: Vec<&str> = if sop_classes.is_empty() {
+            self.sop_classes.iter().map(String::as_str).collect()
+        } else {
+            sop_classes
+                .iter()
+                .copied()
+                .filter(|uid| self.allows(uid))
+                .collect()
+        };
+        for sop_class_uid in proposed {
+            options = options.with_presentation_context(sop_class_uid, &transfer_syntaxes);
+        }
+
+        options
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct RegistryFile {
+    ae_title: Option<String>,
+    peers: BTreeMap<String, PeerConfig>,
+}
+
+// Peers by name, loaded from TOML such as
+//
+// ae_title = "MY-SCU"
+//
+// [peers.pacs]
+// ae_title = "PACS"
+// host = "pacs.example.org"
+// port = 11112
+// sop_classes = ["1.2.840.10008.5.1.4.1.1.2"]
+//
+// [peers.pacs.tls]
+// ca_file = "ca.pem"
+#[derive(Debug, Clone, Default)]
+pub struct AeRegistry {
+    // Our own AE title, used as the calling AE
+    pub local_ae: Option<String>,
+    peers: BTreeMap<String, PeerConfig>,
+}
+
+impl AeRegistry {
+    pub fn new() -> Self {
+        AeRegistry::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> DicomResult<Self> {
+        AeRegistry::from_toml_str(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml_str(text: &str) -> DicomResult<Self> {
+        let file: RegistryFile =
+            toml::from_str(text).map_err(|error| DicomError::InvalidFile(error.to_string()))?;
+
+        let mut registry = AeRegistry {
+            local_ae: file.ae_title,
+            peers: BTreeMap::new(),
+        };
+        for (name, mut peer) in file.peers {
+            peer.name = name;
+            registry.add(peer)?;
+        }
+
+        Ok(registry)
+    }
+
+    pub fn with_local_ae(mut self, ae_title: &str) -> Self {
+        self.local_ae = Some(ae_title.to_string());
+        self
+    }
+
+    pub fn with_peer(mut self, peer: PeerConfig) -> DicomResult<Self> {
+        self.add(peer)?;
+        Ok(self)
+    }
+
+    pub fn add(&mut self, peer: PeerConfig) -> DicomResult<()> {
+        if peer.ae_title.is_empty() || peer.ae_title.len() > 16 {
+            return Err(DicomError::InvalidValue(format!(
+                "AE title of peer {} must be 1 to 16 characters",
+                peer.name
+            )));
+        }
+        self.peers.insert(peer.name.clone(), peer);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<PeerConfig> {
+        self.peers.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PeerConfig> {
+        self.peers.get(name)
+    }
+
+    pub fn peer(&self, name: &str) -> DicomResult<&PeerConfig> {
+        self.get(name)
+            .ok_or_else(|| DicomError::InvalidValue(format!("Unknown peer: {name}")))
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = &PeerConfig> {
+        self.peers.values()
+    }
+
+    // For acceptors, the peer an incoming calling AE belongs to
+    pub fn find_by_ae(&self, ae_title: &str) -> Option<&PeerConfig> {
+        self.peers
+            .values()
+            .find(|peer| peer.ae_title == ae_title.trim())
+    }
+
+    // Resolves a `--peer` argument, either a registered name or AE@host[:port]
+    pub fn resolve(&self, spec: &str) -> DicomResult<PeerConfig> {
+        if let Some(peer) = self.get(spec) {
+            return Ok(peer.clone());
+        }
+
+        let Some((ae_title, address)) = spec.split_once('@') else {
+            return Err(DicomError::InvalidValue(format!("Unknown peer: {spec}")));
+        };
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| DicomError::InvalidValue(format!("Invalid port: {port}")))?,
+            ),
+            None => (address, DEFAULT_PORT),
+        };
+
+        Ok(PeerConfig::new(spec, ae_title, host, port))
+    }
+
+    pub fn calling_ae(&self) -> &str {
+        self.local_ae.as_deref().unwrap_or("ANY-SCU")
+    }
+
+    // Opens an association to a peer, as `resolve` finds it, proposing the
+    // given SOP classes or every class the peer allows when empty
+    pub fn associate(&self, name: &str, sop_classes: &[&str]) -> DicomResult<Association> {
+        let peer = self.resolve(name)?;
+        if peer.tls.is_some() {
+            return Err(DicomError::Error(format!(
+                "Peer {name} requires TLS, which DIMSE associations do not support yet"
+            )));
+        }
+
+        let options = peer.association_options(self.calling_ae(), sop_classes);
+        if options.presentation_contexts.is_empty() {
+            return Err(DicomError::InvalidValue(format!(
+                "No SOP class to propose to peer {name}"
+            )));
+        }
+
+        Association::request((peer.host.as_str(), peer.port), options)
+    }
+
+    // C-STORE of one dataset over an association of its own
+    pub fn store(&self, name: &str, dataset: &Dataset) -> DicomResult<DimseStatus> {
+        let required = |tag, keyword: &str| {
+            dataset
+                .string(tag)
+                .ok_or_else(|| DicomError::InvalidDataset(format!("Dataset lacks its {}", keyword)))
+        };
+        let sop_class_uid = required(SOP_CLASS_UID, "SOP Class UID")?;
+        let sop_instance_uid = required(SOP_INSTANCE_UID, "SOP Instance UID")?;
+
+        let mut association = self.associate(name, &[&sop_class_uid])?;
+        let context_id = association
+            .context_for(&sop_class_uid)
+            .map(|context| context.id)
+            .ok_or_else(|| {
+                DicomError::InvalidValue(format!("Peer {} rejected {}", name, sop_class_uid))
+            })?;
+
+        let request = CStoreRq {
+            message_id: MessageIdGenerator::new().next_id(),
+            affected_sop_class_uid: sop_class_uid,
+            affected_sop_instance_uid: sop_instance_uid,
+            priority: Priority::default(),
+            move_originator_ae_title: None,
+            move_originator_message_id: None,
+        };
+        let message = DimseMessage::new(&request, Some(dataset.clone()));
+        association.send_message(context_id, &message)?;
+        let (_, response) = association.receive_response(&message.command)?;
+        let response = CStoreRsp::from_command(&response.command)?;
+        association.release()?;
+        Ok(response.status)
+    }
+}
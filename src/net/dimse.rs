@@ -0,0 +1,1229 @@
+use std::{
+    rc::Rc,
+    sync::atomic::{AtomicU16, Ordering},
+};
+
+use super::status::DimseStatus;
+use crate::core::{
+    dataset::Dataset,
+    element::DicomElement,
+    error::{DicomError, DicomResult},
+    meta::FileMeta,
+    reader,
+    tag::VisualRepresentation,
+    transfer_syntax, writer,
+};
+
+pub const COMMAND_GROUP_LENGTH: (u16, u16) = (0x0000, 0x0000);
+pub const AFFECTED_SOP_CLASS_UID: (u16, u16) = (0x0000, 0x0002);
+pub const REQUESTED_SOP_CLASS_UID: (u16, u16) = (0x0000, 0x0003);
+pub const COMMAND_FIELD: (u16, u16) = (0x0000, 0x0100);
+pub const MESSAGE_ID: (u16, u16) = (0x0000, 0x0110);
+pub const MESSAGE_ID_BEING_RESPONDED_TO: (u16, u16) = (0x0000, 0x0120);
+pub const MOVE_DESTINATION: (u16, u16) = (0x0000, 0x0600);
+pub const PRIORITY: (u16, u16) = (0x0000, 0x0700);
+pub const COMMAND_DATA_SET_TYPE: (u16, u16) = (0x0000, 0x0800);
+pub const STATUS: (u16, u16) = (0x0000, 0x0900);
+pub const OFFENDING_ELEMENT: (u16, u16) = (0x0000, 0x0901);
+pub const ERROR_COMMENT: (u16, u16) = (0x0000, 0x0902);
+pub const ERROR_ID: (u16, u16) = (0x0000, 0x0903);
+pub const AFFECTED_SOP_INSTANCE_UID: (u16, u16) = (0x0000, 0x1000);
+pub const REQUESTED_SOP_INSTANCE_UID: (u16, u16) = (0x0000, 0x1001);
+pub const EVENT_TYPE_ID: (u16, u16) = (0x0000, 0x1002);
+pub const ATTRIBUTE_IDENTIFIER_LIST: (u16, u16) = (0x0000, 0x1005);
+pub const ACTION_TYPE_ID: (u16, u16) = (0x0000, 0x1008);
+pub const NUMBER_OF_REMAINING_SUBOPERATIONS: (u16, u16) = (0x0000, 0x1020);
+pub const NUMBER_OF_COMPLETED_SUBOPERATIONS: (u16, u16) = (0x0000, 0x1021);
+pub const NUMBER_OF_FAILED_SUBOPERATIONS: (u16, u16) = (0x0000, 0x1022);
+pub const NUMBER_OF_WARNING_SUBOPERATIONS: (u16, u16) = (0x0000, 0x1023);
+pub const MOVE_ORIGINATOR_AE_TITLE: (u16, u16) = (0x0000, 0x1030);
+pub const MOVE_ORIGINATOR_MESSAGE_ID: (u16, u16) = (0x0000, 0x1031);
+
+pub const C_STORE_RQ: u16 = 0x0001;
+pub const C_STORE_RSP: u16 = 0x8001;
+pub const C_GET_RQ: u16 = 0x0010;
+pub const C_GET_RSP: u16 = 0x8010;
+pub const C_FIND_RQ: u16 = 0x0020;
+pub const C_FIND_RSP: u16 = 0x8020;
+pub const C_MOVE_RQ: u16 = 0x0021;
+pub const C_MOVE_RSP: u16 = 0x8021;
+pub const C_ECHO_RQ: u16 = 0x0030;
+pub const C_ECHO_RSP: u16 = 0x8030;
+pub const N_EVENT_REPORT_RQ: u16 = 0x0100;
+pub const N_EVENT_REPORT_RSP: u16 = 0x8100;
+pub const N_GET_RQ: u16 = 0x0110;
+pub const N_GET_RSP: u16 = 0x8110;
+pub const N_SET_RQ: u16 = 0x0120;
+pub const N_SET_RSP: u16 = 0x8120;
+pub const N_ACTION_RQ: u16 = 0x0130;
+pub const N_ACTION_RSP: u16 = 0x8130;
+pub const N_CREATE_RQ: u16 = 0x0140;
+pub const N_CREATE_RSP: u16 = 0x8140;
+pub const N_DELETE_RQ: u16 = 0x0150;
+pub const N_DELETE_RSP: u16 = 0x8150;
+pub const C_CANCEL_RQ: u16 = 0x0FFF;
+
+pub const VERIFICATION_SOP_CLASS: &str = "1.2.840.10008.1.1";
+
+// Command names as used in PS3.7, for logs and errors
+pub fn command_name(command_field: u16) -> &'static str {
+    match command_field {
+        C_STORE_RQ => "C-STORE-RQ",
+        C_STORE_RSP => "C-STORE-RSP",
+        C_GET_RQ => "C-GET-RQ",
+        C_GET_RSP => "C-GET-RSP",
+        C_FIND_RQ => "C-FIND-RQ",
+        C_FIND_RSP => "C-FIND-RSP",
+        C_MOVE_RQ => "C-MOVE-RQ",
+        C_MOVE_RSP => "C-MOVE-RSP",
+        C_ECHO_RQ => "C-ECHO-RQ",
+        C_ECHO_RSP => "C-ECHO-RSP",
+        C_CANCEL_RQ => "C-CANCEL-RQ",
+        N_EVENT_REPORT_RQ => "N-EVENT-REPORT-RQ",
+        N_EVENT_REPORT_RSP => "N-EVENT-REPORT-RSP",
+        N_GET_RQ => "N-GET-RQ",
+        N_GET_RSP => "N-GET-RSP",
+        N_SET_RQ => "N-SET-RQ",
+        N_SET_RSP => "N-SET-RSP",
+        N_ACTION_RQ => "N-ACTION-RQ",
+        N_ACTION_RSP => "N-ACTION-RSP",
+        N_CREATE_RQ => "N-CREATE-RQ",
+        N_CREATE_RSP => "N-CREATE-RSP",
+        N_DELETE_RQ => "N-DELETE-RQ",
+        N_DELETE_RSP => "N-DELETE-RSP",
+        _ => "unknown command",
+    }
+}
+
+// Any other value of Command Data Set Type means a data set follows
+pub const NO_DATA_SET: u16 = 0x0101;
+pub const DATA_SET_PRESENT: u16 = 0x0000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl Priority {
+    pub fn from_u16(value: u16) -> Self {
+        match value {
+            0x0001 => Priority::High,
+            0x0002 => Priority::Low,
+            _ => Priority::Medium,
+        }
+    }
+
+    pub fn as_u16(&self) -> u16 {
+        match self {
+            Priority::Low => 0x0002,
+            Priority::Medium => 0x0000,
+            Priority::High => 0x0001,
+        }
+    }
+}
+
+// Message IDs only have to be unique among the outstanding requests of an
+// association, so wrapping around is fine; zero is skipped to keep logs readable
+#[derive(Debug)]
+pub struct MessageIdGenerator {
+    next: AtomicU16,
+}
+
+impl Default for MessageIdGenerator {
+    fn default() -> Self {
+        MessageIdGenerator {
+            next: AtomicU16::new(1),
+        }
+    }
+}
+
+impl MessageIdGenerator {
+    pub fn new() -> Self {
+        MessageIdGenerator::default()
+    }
+
+    pub fn starting_at(first: u16) -> Self {
+        MessageIdGenerator {
+            next: AtomicU16::new(first.max(1)),
+        }
+    }
+
+    pub fn next_id(&self) -> u16 {
+        loop {
+            let id = self.next.fetch_add(1, Ordering::Relaxed);
+            if id != 0 {
+                return id;
+            }
+        }
+    }
+}
+
+// Group 0000 elements, always encoded as Implicit VR Little Endian
+#[derive(Debug, Clone)]
+pub struct CommandSet {
+    dataset: Dataset,
+}
+
+impl CommandSet {
+    pub fn new(command_field: u16) -> Self {
+        let mut command = CommandSet {
+            dataset: Dataset::new(),
+        };
+        command.set_u16(COMMAND_FIELD, command_field);
+        command.set_u16(COMMAND_DATA_SET_TYPE, NO_DATA_SET);
+        command
+    }
+
+    pub fn from_dataset(dataset: Dataset) -> Self {
+        CommandSet { dataset }
+    }
+
+    pub fn dataset(&self) -> &Dataset {
+        &self.dataset
+    }
+
+    pub fn decode(data: &[u8]) -> DicomResult<Self> {
+        let dataset = reader::read_dataset(data, transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN)?;
+        let command = CommandSet { dataset };
+        if command.u16(COMMAND_FIELD).is_none() {
+            return Err(DicomError::InvalidDataset(
+                "Command set without a command field".to_string(),
+            ));
+        }
+
+        Ok(command)
+    }
+
+    pub fn encode(&self) -> DicomResult<Vec<u8>> {
+        let mut dataset = self.dataset.clone();
+        dataset.remove(COMMAND_GROUP_LENGTH);
+        let body = writer::write_dataset(&dataset, transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN)?;
+
+        let mut length = Dataset::new();
+        length.put(Rc::new(DicomElement::new(
+            COMMAND_GROUP_LENGTH,
+            VisualRepresentation::UL(body.len() as u32),
+        )));
+        let mut output =
+            writer::write_dataset(&length, transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN)?;
+        output.extend(body);
+
+        Ok(output)
+    }
+
+    pub fn command_field(&self) -> u16 {
+        self.u16(COMMAND_FIELD).unwrap_or_default()
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.command_field() & 0x8000 != 0
+    }
+
+    pub fn has_data_set(&self) -> bool {
+        self.u16(COMMAND_DATA_SET_TYPE)
+            .is_some_and(|value| value != NO_DATA_SET)
+    }
+
+    pub fn set_has_data_set(&mut self, present: bool) {
+        let value = if present {
+            DATA_SET_PRESENT
+        } else {
+            NO_DATA_SET
+        };
+        self.set_u16(COMMAND_DATA_SET_TYPE, value);
+    }
+
+    pub fn u16(&self, tag: (u16, u16)) -> Option<u16> {
+        match self.dataset.value(tag)? {
+            VisualRepresentation::US(value) => Some(value),
+            VisualRepresentation::UL(value) => Some(value as u16),
+            other => other.to_string().trim().parse().ok(),
+        }
+    }
+
+    pub fn string(&self, tag: (u16, u16)) -> Option<String> {
+        self.dataset.string(tag).filter(|value| !value.is_empty())
+    }
+
+    pub fn tags(&self, tag: (u16, u16)) -> Vec<(u16, u16)> {
+        self.string(tag)
+            .map(|tags| {
+                tags.split('\\')
+                    .map(str::trim)
+                    .filter(|tag| tag.len() == 8)
+                    .filter_map(|tag| {
+                        Some((
+                            u16::from_str_radix(&tag[..4], 16).ok()?,
+                            u16::from_str_radix(&tag[4..], 16).ok()?,
+                        ))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn set_u16(&mut self, tag: (u16, u16), value: u16) {
+        self.dataset.put(Rc::new(DicomElement::new(
+            tag,
+            VisualRepresentation::US(value),
+        )));
+    }
+
+    pub fn set_string(&mut self, tag: (u16, u16), vr: &str, value: &str) {
+        self.dataset.put_string(tag, vr, value);
+    }
+
+    pub fn set_tags(&mut self, tag: (u16, u16), tags: &[(u16, u16)]) {
+        let value = tags
+            .iter()
+            .map(|(group, element)| format!("{:04X}{:04X}", group, element))
+            .collect::<Vec<_>>()
+            .join("\\");
+        self.dataset.put(Rc::new(DicomElement::new(
+            tag,
+            VisualRepresentation::AT(value.into()),
+        )));
+    }
+
+    fn optional_u16(&mut self, tag: (u16, u16), value: Option<u16>) {
+        if let Some(value) = value {
+            self.set_u16(tag, value);
+        }
+    }
+
+    fn optional_string(&mut self, tag: (u16, u16), vr: &str, value: &Option<String>) {
+        if let Some(value) = value {
+            self.set_string(tag, vr, value);
+        }
+    }
+
+    fn required_u16(&self, tag: (u16, u16)) -> DicomResult<u16> {
+        self.u16(tag).ok_or_else(|| missing(tag))
+    }
+
+    fn required_string(&self, tag: (u16, u16)) -> DicomResult<String> {
+        self.string(tag).ok_or_else(|| missing(tag))
+    }
+}
+
+fn missing(tag: (u16, u16)) -> DicomError {
+    DicomError::InvalidDataset(format!("Command set lacks ({:04X},{:04X})", tag.0, tag.1))
+}
+
+pub trait DimseCommand: Sized {
+    const COMMAND_FIELD: u16;
+
+    fn to_command(&self) -> CommandSet;
+    fn from_command(command: &CommandSet) -> DicomResult<Self>;
+
+    fn check(command: &CommandSet) -> DicomResult<()> {
+        if command.command_field() == Self::COMMAND_FIELD {
+            Ok(())
+        } else {
+            Err(DicomError::InvalidValue(format!(
+                "Expected command {:#06X}, got {:#06X}",
+                Self::COMMAND_FIELD,
+                command.command_field()
+            )))
+        }
+    }
+}
+
+// Sub-operation counters of C-GET and C-MOVE responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SubOperations {
+    pub remaining: Option<u16>,
+    pub completed: u16,
+    pub failed: u16,
+    pub warning: u16,
+}
+
+impl SubOperations {
+    fn write(&self, command: &mut CommandSet) {
+        command.optional_u16(NUMBER_OF_REMAINING_SUBOPERATIONS, self.remaining);
+        command.set_u16(NUMBER_OF_COMPLETED_SUBOPERATIONS, self.completed);
+        command.set_u16(NUMBER_OF_FAILED_SUBOPERATIONS, self.failed);
+        command.set_u16(NUMBER_OF_WARNING_SUBOPERATIONS, self.warning);
+    }
+
+    fn read(command: &CommandSet) -> Self {
+        SubOperations {
+            remaining: command.u16(NUMBER_OF_REMAINING_SUBOPERATIONS),
+            completed: command
+                .u16(NUMBER_OF_COMPLETED_SUBOPERATIONS)
+                .unwrap_or_default(),
+            failed: command
+                .u16(NUMBER_OF_FAILED_SUBOPERATIONS)
+                .unwrap_or_default(),
+            warning: command
+                .u16(NUMBER_OF_WARNING_SUBOPERATIONS)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CEchoRq {
+    pub message_id: u16,
+    pub affected_sop_class_uid: String,
+}
+
+impl DimseCommand for CEchoRq {
+    const COMMAND_FIELD: u16 = C_ECHO_RQ;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(MESSAGE_ID, self.message_id);
+        command.set_string(AFFECTED_SOP_CLASS_UID, "UI", &self.affected_sop_class_uid);
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(CEchoRq {
+            message_id: command.required_u16(MESSAGE_ID)?,
+            affected_sop_class_uid: command.required_string(AFFECTED_SOP_CLASS_UID)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CEchoRsp {
+    pub message_id_being_responded_to: u16,
+    pub affected_sop_class_uid: String,
+    pub status: DimseStatus,
+}
+
+impl DimseCommand for CEchoRsp {
+    const COMMAND_FIELD: u16 = C_ECHO_RSP;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(
+            MESSAGE_ID_BEING_RESPONDED_TO,
+            self.message_id_being_responded_to,
+        );
+        command.set_string(AFFECTED_SOP_CLASS_UID, "UI", &self.affected_sop_class_uid);
+        command.set_u16(STATUS, self.status.code());
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(CEchoRsp {
+            message_id_being_responded_to: command.required_u16(MESSAGE_ID_BEING_RESPONDED_TO)?,
+            affected_sop_class_uid: command.string(AFFECTED_SOP_CLASS_UID).unwrap_or_default(),
+            status: DimseStatus::from_u16(command.required_u16(STATUS)?),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CStoreRq {
+    pub message_id: u16,
+    pub affected_sop_class_uid: String,
+    pub affected_sop_instance_uid: String,
+    pub priority: Priority,
+    pub move_originator_ae_title: Option<String>,
+    pub move_originator_message_id: Option<u16>,
+}
+
+impl CStoreRq {
+    // Request storing the instance a file's meta information describes
+    pub fn for_file(message_id: u16, meta: &FileMeta) -> Self {
+        CStoreRq {
+            message_id,
+            affected_sop_class_uid: meta.media_storage_sop_class_uid.clone(),
+            affected_sop_instance_uid: meta.media_storage_sop_instance_uid.clone(),
+            priority: Priority::default(),
+            move_originator_ae_title: None,
+            move_originator_message_id: None,
+        }
+    }
+
+    // Meta information for writing the received data set to a file, the
+    // sending AE being its source
+    pub fn file_meta(&self, transfer_syntax: &str, calling_ae_title: &str) -> FileMeta {
+        FileMeta::new(
+            &self.affected_sop_class_uid,
+            &self.affected_sop_instance_uid,
+            transfer_syntax,
+        )
+        .with_source_ae_title(calling_ae_title)
+    }
+}
+
+impl DimseCommand for CStoreRq {
+    const COMMAND_FIELD: u16 = C_STORE_RQ;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(MESSAGE_ID, self.message_id);
+        command.set_string(AFFECTED_SOP_CLASS_UID, "UI", &self.affected_sop_class_uid);
+        command.set_string(
+            AFFECTED_SOP_INSTANCE_UID,
+            "UI",
+            &self.affected_sop_instance_uid,
+        );
+        command.set_u16(PRIORITY, self.priority.as_u16());
+        command.optional_string(
+            MOVE_ORIGINATOR_AE_TITLE,
+            "AE",
+            &self.move_originator_ae_title,
+        );
+        command.optional_u16(MOVE_ORIGINATOR_MESSAGE_ID, self.move_originator_message_id);
+        command.set_has_data_set(true);
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(CStoreRq {
+            message_id: command.required_u16(MESSAGE_ID)?,
+            affected_sop_class_uid: command.required_string(AFFECTED_SOP_CLASS_UID)?,
+            affected_sop_instance_uid: command.required_string(AFFECTED_SOP_INSTANCE_UID)?,
+            priority: Priority::from_u16(command.u16(PRIORITY).unwrap_or_default()),
+            move_originator_ae_title: command.string(MOVE_ORIGINATOR_AE_TITLE),
+            move_originator_message_id: command.u16(MOVE_ORIGINATOR_MESSAGE_ID),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CStoreRsp {
+    pub message_id_being_responded_to: u16,
+    pub affected_sop_class_uid: String,
+    pub affected_sop_instance_uid: String,
+    pub status: DimseStatus,
+    pub error_comment: Option<String>,
+}
+
+impl DimseCommand for CStoreRsp {
+    const COMMAND_FIELD: u16 = C_STORE_RSP;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(
+            MESSAGE_ID_BEING_RESPONDED_TO,
+            self.message_id_being_responded_to,
+        );
+        command.set_string(AFFECTED_SOP_CLASS_UID, "UI", &self.affected_sop_class_uid);
+        command.set_string(
+            AFFECTED_SOP_INSTANCE_UID,
+            "UI",
+            &self.affected_sop_instance_uid,
+        );
+        command.set_u16(STATUS, self.status.code());
+        command.optional_string(ERROR_COMMENT, "LO", &self.error_comment);
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(CStoreRsp {
+            message_id_being_responded_to: command.required_u16(MESSAGE_ID_BEING_RESPONDED_TO)?,
+            affected_sop_class_uid: command.string(AFFECTED_SOP_CLASS_UID).unwrap_or_default(),
+            affected_sop_instance_uid: command
+                .string(AFFECTED_SOP_INSTANCE_UID)
+                .unwrap_or_default(),
+            status: DimseStatus::from_u16(command.required_u16(STATUS)?),
+            error_comment: command.string(ERROR_COMMENT),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CFindRq {
+    pub message_id: u16,
+    pub affected_sop_class_uid: String,
+    pub priority: Priority,
+}
+
+impl DimseCommand for CFindRq {
+    const COMMAND_FIELD: u16 = C_FIND_RQ;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(MESSAGE_ID, self.message_id);
+        command.set_string(AFFECTED_SOP_CLASS_UID, "UI", &self.affected_sop_class_uid);
+        command.set_u16(PRIORITY, self.priority.as_u16());
+        command.set_has_data_set(true);
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(CFindRq {
+            message_id: command.required_u16(MESSAGE_ID)?,
+            affected_sop_class_uid: command.required_string(AFFECTED_SOP_CLASS_UID)?,
+            priority: Priority::from_u16(command.u16(PRIORITY).unwrap_or_default()),
+        })
+    }
+}
+
+// Pending responses carry an identifier, the final one does not
+#[derive(Debug, Clone, PartialEq)]
+pub struct CFindRsp {
+    pub message_id_being_responded_to: u16,
+    pub affected_sop_class_uid: String,
+    pub status: DimseStatus,
+    pub error_comment: Option<String>,
+}
+
+impl DimseCommand for CFindRsp {
+    const COMMAND_FIELD: u16 = C_FIND_RSP;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(
+            MESSAGE_ID_BEING_RESPONDED_TO,
+            self.message_id_being_responded_to,
+        );
+        command.set_string(AFFECTED_SOP_CLASS_UID, "UI", &self.affected_sop_class_uid);
+        command.set_u16(STATUS, self.status.code());
+        command.optional_string(ERROR_COMMENT, "LO", &self.error_comment);
+        command.set_has_data_set(self.status.is_pending());
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(CFindRsp {
+            message_id_being_responded_to: command.required_u16(MESSAGE_ID_BEING_RESPONDED_TO)?,
+            affected_sop_class_uid: command.string(AFFECTED_SOP_CLASS_UID).unwrap_or_default(),
+            status: DimseStatus::from_u16(command.required_u16(STATUS)?),
+            error_comment: command.string(ERROR_COMMENT),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CGetRq {
+    pub message_id: u16,
+    pub affected_sop_class_uid: String,
+    pub priority: Priority,
+}
+
+impl DimseCommand for CGetRq {
+    const COMMAND_FIELD: u16 = C_GET_RQ;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(MESSAGE_ID, self.message_id);
+        command.set_string(AFFECTED_SOP_CLASS_UID, "UI", &self.affected_sop_class_uid);
+        command.set_u16(PRIORITY, self.priority.as_u16());
+        command.set_has_data_set(true);
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(CGetRq {
+            message_id: command.required_u16(MESSAGE_ID)?,
+            affected_sop_class_uid: command.required_string(AFFECTED_SOP_CLASS_UID)?,
+            priority: Priority::from_u16(command.u16(PRIORITY).unwrap_or_default()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CGetRsp {
+    pub message_id_being_responded_to: u16,
+    pub affected_sop_class_uid: String,
+    pub status: DimseStatus,
+    pub sub_operations: SubOperations,
+    // Set when the response carries the Failed SOP Instance UID List
+    pub has_identifier: bool,
+}
+
+impl DimseCommand for CGetRsp {
+    const COMMAND_FIELD: u16 = C_GET_RSP;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(
+            MESSAGE_ID_BEING_RESPONDED_TO,
+            self.message_id_being_responded_to,
+        );
+        command.set_string(AFFECTED_SOP_CLASS_UID, "UI", &self.affected_sop_class_uid);
+        command.set_u16(STATUS, self.status.code());
+        self.sub_operations.write(&mut command);
+        command.set_has_data_set(self.has_identifier);
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(CGetRsp {
+            message_id_being_responded_to: command.required_u16(MESSAGE_ID_BEING_RESPONDED_TO)?,
+            affected_sop_class_uid: command.string(AFFECTED_SOP_CLASS_UID).unwrap_or_default(),
+            status: DimseStatus::from_u16(command.required_u16(STATUS)?),
+            sub_operations: SubOperations::read(command),
+            has_identifier: command.has_data_set(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CMoveRq {
+    pub message_id: u16,
+    pub affected_sop_class_uid: String,
+    pub priority: Priority,
+    pub move_destination: String,
+}
+
+impl DimseCommand for CMoveRq {
+    const COMMAND_FIELD: u16 = C_MOVE_RQ;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(MESSAGE_ID, self.message_id);
+        command.set_string(AFFECTED_SOP_CLASS_UID, "UI", &self.affected_sop_class_uid);
+        command.set_u16(PRIORITY, self.priority.as_u16());
+        command.set_string(MOVE_DESTINATION, "AE", &self.move_destination);
+        command.set_has_data_set(true);
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(CMoveRq {
+            message_id: command.required_u16(MESSAGE_ID)?,
+            affected_sop_class_uid: command.required_string(AFFECTED_SOP_CLASS_UID)?,
+            priority: Priority::from_u16(command.u16(PRIORITY).unwrap_or_default()),
+            move_destination: command.required_string(MOVE_DESTINATION)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CMoveRsp {
+    pub message_id_being_responded_to: u16,
+    pub affected_sop_class_uid: String,
+    pub status: DimseStatus,
+    pub sub_operations: SubOperations,
+    pub has_identifier: bool,
+}
+
+impl DimseCommand for CMoveRsp {
+    const COMMAND_FIELD: u16 = C_MOVE_RSP;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(
+            MESSAGE_ID_BEING_RESPONDED_TO,
+            self.message_id_being_responded_to,
+        );
+        command.set_string(AFFECTED_SOP_CLASS_UID, "UI", &self.affected_sop_class_uid);
+        command.set_u16(STATUS, self.status.code());
+        self.sub_operations.write(&mut command);
+        command.set_has_data_set(self.has_identifier);
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(CMoveRsp {
+            message_id_being_responded_to: command.required_u16(MESSAGE_ID_BEING_RESPONDED_TO)?,
+            affected_sop_class_uid: command.string(AFFECTED_SOP_CLASS_UID).unwrap_or_default(),
+            status: DimseStatus::from_u16(command.required_u16(STATUS)?),
+            sub_operations: SubOperations::read(command),
+            has_identifier: command.has_data_set(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CCancelRq {
+    pub message_id_being_responded_to: u16,
+}
+
+impl DimseCommand for CCancelRq {
+    const COMMAND_FIELD: u16 = C_CANCEL_RQ;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(
+            MESSAGE_ID_BEING_RESPONDED_TO,
+            self.message_id_being_responded_to,
+        );
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(CCancelRq {
+            message_id_being_responded_to: command.required_u16(MESSAGE_ID_BEING_RESPONDED_TO)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NEventReportRq {
+    pub message_id: u16,
+    pub affected_sop_class_uid: String,
+    pub affected_sop_instance_uid: String,
+    pub event_type_id: u16,
+    pub has_event_information: bool,
+}
+
+impl DimseCommand for NEventReportRq {
+    const COMMAND_FIELD: u16 = N_EVENT_REPORT_RQ;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(MESSAGE_ID, self.message_id);
+        command.set_string(AFFECTED_SOP_CLASS_UID, "UI", &self.affected_sop_class_uid);
+        command.set_string(
+            AFFECTED_SOP_INSTANCE_UID,
+            "UI",
+            &self.affected_sop_instance_uid,
+        );
+        command.set_u16(EVENT_TYPE_ID, self.event_type_id);
+        command.set_has_data_set(self.has_event_information);
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(NEventReportRq {
+            message_id: command.required_u16(MESSAGE_ID)?,
+            affected_sop_class_uid: command.required_string(AFFECTED_SOP_CLASS_UID)?,
+            affected_sop_instance_uid: command.required_string(AFFECTED_SOP_INSTANCE_UID)?,
+            event_type_id: command.required_u16(EVENT_TYPE_ID)?,
+            has_event_information: command.has_data_set(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NEventReportRsp {
+    pub message_id_being_responded_to: u16,
+    pub affected_sop_class_uid: String,
+    pub affected_sop_instance_uid: String,
+    pub event_type_id: Option<u16>,
+    pub status: DimseStatus,
+    pub has_event_reply: bool,
+}
+
+impl DimseCommand for NEventReportRsp {
+    const COMMAND_FIELD: u16 = N_EVENT_REPORT_RSP;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(
+            MESSAGE_ID_BEING_RESPONDED_TO,
+            self.message_id_being_responded_to,
+        );
+        command.set_string(AFFECTED_SOP_CLASS_UID, "UI", &self.affected_sop_class_uid);
+        command.set_string(
+            AFFECTED_SOP_INSTANCE_UID,
+            "UI",
+            &self.affected_sop_instance_uid,
+        );
+        command.optional_u16(EVENT_TYPE_ID, self.event_type_id);
+        command.set_u16(STATUS, self.status.code());
+        command.set_has_data_set(self.has_event_reply);
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(NEventReportRsp {
+            message_id_being_responded_to: command.required_u16(MESSAGE_ID_BEING_RESPONDED_TO)?,
+            affected_sop_class_uid: command.string(AFFECTED_SOP_CLASS_UID).unwrap_or_default(),
+            affected_sop_instance_uid: command
+                .string(AFFECTED_SOP_INSTANCE_UID)
+                .unwrap_or_default(),
+            event_type_id: command.u16(EVENT_TYPE_ID),
+            status: DimseStatus::from_u16(command.required_u16(STATUS)?),
+            has_event_reply: command.has_data_set(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NGetRq {
+    pub message_id: u16,
+    pub requested_sop_class_uid: String,
+    pub requested_sop_instance_uid: String,
+    // Empty asks for every attribute
+    pub attribute_identifier_list: Vec<(u16, u16)>,
+}
+
+impl DimseCommand for NGetRq {
+    const COMMAND_FIELD: u16 = N_GET_RQ;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(MESSAGE_ID, self.message_id);
+        command.set_string(REQUESTED_SOP_CLASS_UID, "UI", &self.requested_sop_class_uid);
+        command.set_string(
+            REQUESTED_SOP_INSTANCE_UID,
+            "UI",
+            &self.requested_sop_instance_uid,
+        );
+        if !self.attribute_identifier_list.is_empty() {
+            command.set_tags(ATTRIBUTE_IDENTIFIER_LIST, &self.attribute_identifier_list);
+        }
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(NGetRq {
+            message_id: command.required_u16(MESSAGE_ID)?,
+            requested_sop_class_uid: command.required_string(REQUESTED_SOP_CLASS_UID)?,
+            requested_sop_instance_uid: command.required_string(REQUESTED_SOP_INSTANCE_UID)?,
+            attribute_identifier_list: command.tags(ATTRIBUTE_IDENTIFIER_LIST),
+        })
+    }
+}
+
+// Shared by the N-GET, N-SET, N-ACTION, N-CREATE and N-DELETE responses
+#[derive(Debug, Clone, PartialEq)]
+pub struct NResponse {
+    pub message_id_being_responded_to: u16,
+    pub affected_sop_class_uid: String,
+    pub affected_sop_instance_uid: String,
+    pub status: DimseStatus,
+    pub error_comment: Option<String>,
+    pub has_data_set: bool,
+}
+
+impl NResponse {
+    fn to_command(&self, command_field: u16) -> CommandSet {
+        let mut command = CommandSet::new(command_field);
+        command.set_u16(
+            MESSAGE_ID_BEING_RESPONDED_TO,
+            self.message_id_being_responded_to,
+        );
+        command.set_string(AFFECTED_SOP_CLASS_UID, "UI", &self.affected_sop_class_uid);
+        command.set_string(
+            AFFECTED_SOP_INSTANCE_UID,
+            "UI",
+            &self.affected_sop_instance_uid,
+        );
+        command.set_u16(STATUS, self.status.code());
+        command.optional_string(ERROR_COMMENT, "LO", &self.error_comment);
+        command.set_has_data_set(self.has_data_set);
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Ok(NResponse {
+            message_id_being_responded_to: command.required_u16(MESSAGE_ID_BEING_RESPONDED_TO)?,
+            affected_sop_class_uid: command.string(AFFECTED_SOP_CLASS_UID).unwrap_or_default(),
+            affected_sop_instance_uid: command
+                .string(AFFECTED_SOP_INSTANCE_UID)
+                .unwrap_or_default(),
+            status: DimseStatus::from_u16(command.required_u16(STATUS)?),
+            error_comment: command.string(ERROR_COMMENT),
+            has_data_set: command.has_data_set(),
+        })
+    }
+}
+
+macro_rules! n_response {
+    ($name:ident, $field:expr) => {
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct $name(pub NResponse);
+
+        impl DimseCommand for $name {
+            const COMMAND_FIELD: u16 = $field;
+
+            fn to_command(&self) -> CommandSet {
+                self.0.to_command(Self::COMMAND_FIELD)
+            }
+
+            fn from_command(command: &CommandSet) -> DicomResult<Self> {
+                Self::check(command)?;
+                Ok($name(NResponse::from_command(command)?))
+            }
+        }
+    };
+}
+
+n_response!(NGetRsp, N_GET_RSP);
+n_response!(NSetRsp, N_SET_RSP);
+n_response!(NActionRsp, N_ACTION_RSP);
+n_response!(NCreateRsp, N_CREATE_RSP);
+n_response!(NDeleteRsp, N_DELETE_RSP);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NSetRq {
+    pub message_id: u16,
+    pub requested_sop_class_uid: String,
+    pub requested_sop_instance_uid: String,
+}
+
+impl DimseCommand for NSetRq {
+    const COMMAND_FIELD: u16 = N_SET_RQ;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(MESSAGE_ID, self.message_id);
+        command.set_string(REQUESTED_SOP_CLASS_UID, "UI", &self.requested_sop_class_uid);
+        command.set_string(
+            REQUESTED_SOP_INSTANCE_UID,
+            "UI",
+            &self.requested_sop_instance_uid,
+        );
+        command.set_has_data_set(true);
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(NSetRq {
+            message_id: command.required_u16(MESSAGE_ID)?,
+            requested_sop_class_uid: command.required_string(REQUESTED_SOP_CLASS_UID)?,
+            requested_sop_instance_uid: command.required_string(REQUESTED_SOP_INSTANCE_UID)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NActionRq {
+    pub message_id: u16,
+    pub requested_sop_class_uid: String,
+    pub requested_sop_instance_uid: String,
+    pub action_type_id: u16,
+    pub has_action_information: bool,
+}
+
+impl DimseCommand for NActionRq {
+    const COMMAND_FIELD: u16 = N_ACTION_RQ;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(MESSAGE_ID, self.message_id);
+        command.set_string(REQUESTED_SOP_CLASS_UID, "UI", &self.requested_sop_class_uid);
+        command.set_string(
+            REQUESTED_SOP_INSTANCE_UID,
+            "UI",
+            &self.requested_sop_instance_uid,
+        );
+        command.set_u16(ACTION_TYPE_ID, self.action_type_id);
+        command.set_has_data_set(self.has_action_information);
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(NActionRq {
+            message_id: command.required_u16(MESSAGE_ID)?,
+            requested_sop_class_uid: command.required_string(REQUESTED_SOP_CLASS_UID)?,
+            requested_sop_instance_uid: command.required_string(REQUESTED_SOP_INSTANCE_UID)?,
+            action_type_id: command.required_u16(ACTION_TYPE_ID)?,
+            has_action_information: command.has_data_set(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NCreateRq {
+    pub message_id: u16,
+    pub affected_sop_class_uid: String,
+    // Left out when the SCP should assign the instance UID
+    pub affected_sop_instance_uid: Option<String>,
+    pub has_attribute_list: bool,
+}
+
+impl DimseCommand for NCreateRq {
+    const COMMAND_FIELD: u16 = N_CREATE_RQ;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(MESSAGE_ID, self.message_id);
+        command.set_string(AFFECTED_SOP_CLASS_UID, "UI", &self.affected_sop_class_uid);
+        command.optional_string(
+            AFFECTED_SOP_INSTANCE_UID,
+            "UI",
+            &self.affected_sop_instance_uid,
+        );
+        command.set_has_data_set(self.has_attribute_list);
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(NCreateRq {
+            message_id: command.required_u16(MESSAGE_ID)?,
+            affected_sop_class_uid: command.required_string(AFFECTED_SOP_CLASS_UID)?,
+            affected_sop_instance_uid: command.string(AFFECTED_SOP_INSTANCE_UID),
+            has_attribute_list: command.has_data_set(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NDeleteRq {
+    pub message_id: u16,
+    pub requested_sop_class_uid: String,
+    pub requested_sop_instance_uid: String,
+}
+
+impl DimseCommand for NDeleteRq {
+    const COMMAND_FIELD: u16 = N_DELETE_RQ;
+
+    fn to_command(&self) -> CommandSet {
+        let mut command = CommandSet::new(Self::COMMAND_FIELD);
+        command.set_u16(MESSAGE_ID, self.message_id);
+        command.set_string(REQUESTED_SOP_CLASS_UID, "UI", &self.requested_sop_class_uid);
+        command.set_string(
+            REQUESTED_SOP_INSTANCE_UID,
+            "UI",
+            &self.requested_sop_instance_uid,
+        );
+        command
+    }
+
+    fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Self::check(command)?;
+        Ok(NDeleteRq {
+            message_id: command.required_u16(MESSAGE_ID)?,
+            requested_sop_class_uid: command.required_string(REQUESTED_SOP_CLASS_UID)?,
+            requested_sop_instance_uid: command.required_string(REQUESTED_SOP_INSTANCE_UID)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    CEchoRq(CEchoRq),
+    CEchoRsp(CEchoRsp),
+    CStoreRq(CStoreRq),
+    CStoreRsp(CStoreRsp),
+    CFindRq(CFindRq),
+    CFindRsp(CFindRsp),
+    CGetRq(CGetRq),
+    CGetRsp(CGetRsp),
+    CMoveRq(CMoveRq),
+    CMoveRsp(CMoveRsp),
+    CCancelRq(CCancelRq),
+    NEventReportRq(NEventReportRq),
+    NEventReportRsp(NEventReportRsp),
+    NGetRq(NGetRq),
+    NGetRsp(NGetRsp),
+    NSetRq(NSetRq),
+    NSetRsp(NSetRsp),
+    NActionRq(NActionRq),
+    NActionRsp(NActionRsp),
+    NCreateRq(NCreateRq),
+    NCreateRsp(NCreateRsp),
+    NDeleteRq(NDeleteRq),
+    NDeleteRsp(NDeleteRsp),
+}
+
+impl Command {
+    pub fn from_command(command: &CommandSet) -> DicomResult<Self> {
+        Ok(match command.command_field() {
+            C_ECHO_RQ => Command::CEchoRq(CEchoRq::from_command(command)?),
+            C_ECHO_RSP => Command::CEchoRsp(CEchoRsp::from_command(command)?),
+            C_STORE_RQ => Command::CStoreRq(CStoreRq::from_command(command)?),
+            C_STORE_RSP => Command::CStoreRsp(CStoreRsp::from_command(command)?),
+            C_FIND_RQ => Command::CFindRq(CFindRq::from_command(command)?),
+            C_FIND_RSP => Command::CFindRsp(CFindRsp::from_command(command)?),
+            C_GET_RQ => Command::CGetRq(CGetRq::from_command(command)?),
+            C_GET_RSP => Command::CGetRsp(CGetRsp::from_command(command)?),
+            C_MOVE_RQ => Command::CMoveRq(CMoveRq::from_command(command)?),
+            C_MOVE_RSP => Command::CMoveRsp(CMoveRsp::from_command(command)?),
+            C_CANCEL_RQ => Command::CCancelRq(CCancelRq::from_command(command)?),
+            N_EVENT_REPORT_RQ => Command::NEventReportRq(NEventReportRq::from_command(command)?),
+            N_EVENT_REPORT_RSP => Command::NEventReportRsp(NEventReportRsp::from_command(command)?),
+            N_GET_RQ => Command::NGetRq(NGetRq::from_command(command)?),
+            N_GET_RSP => Command::NGetRsp(NGetRsp::from_command(command)?),
+            N_SET_RQ => Command::NSetRq(NSetRq::from_command(command)?),
+            N_SET_RSP => Command::NSetRsp(NSetRsp::from_command(command)?),
+            N_ACTION_RQ => Command::NActionRq(NActionRq::from_command(command)?),
+            N_ACTION_RSP => Command::NActionRsp(NActionRsp::from_command(command)?),
+            N_CREATE_RQ => Command::NCreateRq(NCreateRq::from_command(command)?),
+            N_CREATE_RSP => Command::NCreateRsp(NCreateRsp::from_command(command)?),
+            N_DELETE_RQ => Command::NDeleteRq(NDeleteRq::from_command(command)?),
+            N_DELETE_RSP => Command::NDeleteRsp(NDeleteRsp::from_command(command)?),
+            other => {
+                return Err(DicomError::InvalidValue(format!(
+                    "Unknown command field {:#06X}",
+                    other
+                )))
+            }
+        })
+    }
+
+    pub fn to_command(&self) -> CommandSet {
+        match self {
+            Command::CEchoRq(command) => command.to_command(),
+            Command::CEchoRsp(command) => command.to_command(),
+            Command::CStoreRq(command) => command.to_command(),
+            Command::CStoreRsp(command) => command.to_command(),
+            Command::CFindRq(command) => command.to_command(),
+            Command::CFindRsp(command) => command.to_command(),
+            Command::CGetRq(command) => command.to_command(),
+            Command::CGetRsp(command) => command.to_command(),
+            Command::CMoveRq(command) => command.to_command(),
+            Command::CMoveRsp(command) => command.to_command(),
+            Command::CCancelRq(command) => command.to_command(),
+            Command::NEventReportRq(command) => command.to_command(),
+            Command::NEventReportRsp(command) => command.to_command(),
+            Command::NGetRq(command) => command.to_command(),
+            Command::NGetRsp(command) => command.to_command(),
+            Command::NSetRq(command) => command.to_command(),
+            Command::NSetRsp(command) => command.to_command(),
+            Command::NActionRq(command) => command.to_command(),
+            Command::NActionRsp(command) => command.to_command(),
+            Command::NCreateRq(command) => command.to_command(),
+            Command::NCreateRsp(command) => command.to_command(),
+            Command::NDeleteRq(command) => command.to_command(),
+            Command::NDeleteRsp(command) => command.to_command(),
+        }
+    }
+}
+
+// A command together with its data set, as exchanged over an association
+#[derive(Debug, Clone)]
+pub struct DimseMessage {
+    pub command: CommandSet,
+    pub data_set: Option<Dataset>,
+}
+
+impl DimseMessage {
+    pub fn new<C: DimseCommand>(command: &C, data_set: Option<Dataset>) -> Self {
+        let mut command = command.to_command();
+        command.set_has_data_set(data_set.is_some());
+        DimseMessage { command, data_set }
+    }
+
+    pub fn parse(&self) -> DicomResult<Command> {
+        Command::from_command(&self.command)
+    }
+
+    pub fn encode_command(&self) -> DicomResult<Vec<u8>> {
+        self.command.encode()
+    }
+
+    // Data sets use the transfer syntax of the presentation context
+    pub fn encode_data_set(&self, transfer_syntax: &str) -> DicomResult<Option<Vec<u8>>> {
+        self.data_set
+            .as_ref()
+            .map(|data_set| writer::write_dataset(data_set, transfer_syntax))
+            .transpose()
+    }
+
+    pub fn decode(
+        command: &[u8],
+        data_set: Option<&[u8]>,
+        transfer_syntax: &str,
+    ) -> DicomResult<Self> {
+        let command = CommandSet::decode(command)?;
+        let data_set = match data_set {
+            Some(data) => Some(reader::read_dataset(data, transfer_syntax)?),
+            None => None,
+        };
+
+        Ok(DimseMessage { command, data_set })
+    }
+}
@@ -0,0 +1,89 @@
+use super::{
+    association::Association,
+    cancel::CancellationToken,
+    dimse::{
+        CCancelRq, CFindRq, CFindRsp, Command, DimseCommand, DimseMessage, MessageIdGenerator,
+        Priority,
+    },
+    status::DimseStatus,
+};
+use crate::core::{
+    dataset::Dataset,
+    error::{AbortSource, DicomError, DicomResult},
+};
+
+// C-FIND as SCU, handing identifiers to `on_match` as they arrive. Once the
+// token is cancelled a C-CANCEL is sent and the responses still on their way
+// are dropped up to the final one, whose status is returned
+pub fn find<F: FnMut(Dataset)>(
+    association: &mut Association,
+    sop_class_uid: &str,
+    query: &Dataset,
+    token: &CancellationToken,
+    mut on_match: F,
+) -> DicomResult<DimseStatus> {
+    let context_id = association
+        .context_for(sop_class_uid)
+        .map(|context| context.id)
+        .ok_or_else(|| DicomError::InvalidValue(format!("{} was not accepted", sop_class_uid)))?;
+
+    let request = CFindRq {
+        message_id: MessageIdGenerator::new().next_id(),
+        affected_sop_class_uid: sop_class_uid.to_string(),
+        priority: Priority::default(),
+    };
+    let message = DimseMessage::new(&request, Some(query.clone()));
+    association.send_message(context_id, &message)?;
+
+    let mut cancel_sent = false;
+    loop {
+        let (_, response) = association.receive_response(&message.command)?;
+        let status = CFindRsp::from_command(&response.command)?.status;
+        if !status.is_pending() {
+            return Ok(status);
+        }
+
+        if token.is_cancelled() {
+            if !cancel_sent {
+                let cancel = CCancelRq {
+                    message_id_being_responded_to: request.message_id,
+                };
+                association.send_message(context_id, &DimseMessage::new(&cancel, None))?;
+                cancel_sent = true;
+            }
+        } else if let Some(identifier) = response.data_set {
+            on_match(identifier);
+        }
+    }
+}
+
+// For SCPs between pending responses: reads what the peer sent meanwhile
+// without blocking, cancelling the token on a C-CANCEL of the operation.
+// Returns whether the operation is cancelled
+pub fn poll_cancel(
+    association: &mut Association,
+    message_id: u16,
+    token: &CancellationToken,
+) -> DicomResult<bool> {
+    while !token.is_cancelled() && association.has_pending_input()? {
+        let Some((_, message)) = association.receive_message()? else {
+            return Err(DicomError::AssociationAborted {
+                by: AbortSource::ServiceUser,
+                reason: "peer released the association during an operation".to_string(),
+            });
+        };
+        match message.parse()? {
+            Command::CCancelRq(rq) if rq.message_id_being_responded_to == message_id => {
+                token.cancel()
+            }
+            // Late cancel of an operation already answered
+            Command::CCancelRq(_) => {}
+            _ => {
+                return Err(DicomError::InvalidValue(
+                    "Request received while an operation was pending".to_string(),
+                ))
+            }
+        }
+    }
+    Ok(token.is_cancelled())
+}
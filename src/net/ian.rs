@@ -0,0 +1,342 @@
+use std::{collections::BTreeMap, fmt::Display, rc::Rc};
+
+use super::{
+    association::Association,
+    dimse::{DimseCommand, DimseMessage, MessageIdGenerator, NCreateRq, NCreateRsp},
+    status::DimseStatus,
+};
+use crate::core::{
+    dataset::Dataset,
+    element::DicomElement,
+    error::{DicomError, DicomResult},
+    uid,
+};
+
+pub const INSTANCE_AVAILABILITY_NOTIFICATION_SOP_CLASS: &str = "1.2.840.10008.5.1.4.33";
+
+pub const SOP_CLASS_UID: (u16, u16) = (0x0008, 0x0016);
+pub const SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x0018);
+pub const INSTANCE_AVAILABILITY: (u16, u16) = (0x0008, 0x0056);
+pub const RETRIEVE_AE_TITLE: (u16, u16) = (0x0008, 0x0054);
+pub const REFERENCED_PERFORMED_PROCEDURE_STEP_SEQUENCE: (u16, u16) = (0x0008, 0x1111);
+pub const REFERENCED_SERIES_SEQUENCE: (u16, u16) = (0x0008, 0x1115);
+pub const REFERENCED_SOP_CLASS_UID: (u16, u16) = (0x0008, 0x1150);
+pub const REFERENCED_SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x1155);
+pub const REFERENCED_SOP_SEQUENCE: (u16, u16) = (0x0008, 0x1199);
+pub const STUDY_INSTANCE_UID: (u16, u16) = (0x0020, 0x000D);
+pub const SERIES_INSTANCE_UID: (u16, u16) = (0x0020, 0x000E);
+
+pub const MODALITY_PERFORMED_PROCEDURE_STEP_SOP_CLASS: &str = "1.2.840.10008.3.1.2.3.3";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InstanceAvailability {
+    #[default]
+    Online,
+    Nearline,
+    Offline,
+    Unavailable,
+}
+
+impl InstanceAvailability {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "ONLINE" => Some(InstanceAvailability::Online),
+            "NEARLINE" => Some(InstanceAvailability::Nearline),
+            "OFFLINE" => Some(InstanceAvailability::Offline),
+            "UNAVAILABLE" => Some(InstanceAvailability::Unavailable),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InstanceAvailability::Online => "ONLINE",
+            InstanceAvailability::Nearline => "NEARLINE",
+            InstanceAvailability::Offline => "OFFLINE",
+            InstanceAvailability::Unavailable => "UNAVAILABLE",
+        }
+    }
+}
+
+impl Display for InstanceAvailability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AvailableInstance {
+    pub sop_class_uid: String,
+    pub sop_instance_uid: String,
+    pub availability: InstanceAvailability,
+    pub retrieve_ae_title: String,
+}
+
+// PS3.3 B.25, the instances of one study that became available
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceAvailabilityNotification {
+    pub study_instance_uid: String,
+    // Modality performed procedure steps the instances were acquired under
+    pub performed_procedure_steps: Vec<String>,
+    // Instances by series instance UID
+    pub series: BTreeMap<String, Vec<AvailableInstance>>,
+}
+
+impl InstanceAvailabilityNotification {
+    pub fn new(study_instance_uid: &str) -> Self {
+        InstanceAvailabilityNotification {
+            study_instance_uid: study_instance_uid.to_string(),
+            performed_procedure_steps: Vec::new(),
+            series: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_performed_procedure_step(mut self, sop_instance_uid: &str) -> Self {
+        self.performed_procedure_steps
+            .push(sop_instance_uid.to_string());
+        self
+    }
+
+    pub fn with_instance(
+        mut self,
+        series_instance_uid: &str,
+        sop_class_uid: &str,
+        sop_instance_uid: &str,
+        retrieve_ae_title: &str,
+    ) -> Self {
+        self.add(
+            series_instance_uid,
+            AvailableInstance {
+                sop_class_uid: sop_class_uid.to_string(),
+                sop_instance_uid: sop_instance_uid.to_string(),
+                availability: InstanceAvailability::Online,
+                retrieve_ae_title: retrieve_ae_title.to_string(),
+            },
+        );
+        self
+    }
+
+    // Replaces an earlier entry for the same instance, so a notification can
+    // also report instances going offline
+    pub fn add(&mut self, series_instance_uid: &str, instance: AvailableInstance) {
+        let instances = self
+            .series
+            .entry(series_instance_uid.to_string())
+            .or_default();
+        match instances
+            .iter_mut()
+            .find(|existing| existing.sop_instance_uid == instance.sop_instance_uid)
+        {
+            Some(existing) => *existing = instance,
+            None => instances.push(instance),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.series.values().all(Vec::is_empty)
+    }
+
+    pub fn to_dataset(&self) -> Dataset {
+        let steps = self
+            .performed_procedure_steps
+            .iter()
+            .map(|step| {
+                let mut item = Dataset::new();
+                item.put_string(
+                    REFERENCED_SOP_CLASS_UID,
+                    "UI",
+                    MODALITY_PERFORMED_PROCEDURE_STEP_SOP_CLASS,
+                );
+                item.put_string(REFERENCED_SOP_INSTANCE_UID, "UI", step);
+                item
+            })
+            .collect();
+
+        let series = self
+            .series
+            .iter()
+            .map(|(series_instance_uid, instances)| {
+                let references = instances
+                    .iter()
+                    .map(|instance| {
+                        let mut item = Dataset::new();
+                        item.put_string(RETRIEVE_AE_TITLE, "AE", &instance.retrieve_ae_title);
+                        item.put_string(
+                            INSTANCE_AVAILABILITY,
+                            "CS",
+                            instance.availability.as_str(),
+                        );
+                        item.put_string(REFERENCED_SOP_CLASS_UID, "UI", &instance.sop_class_uid);
+                        item.put_string(
+                            REFERENCED_SOP_INSTANCE_UID,
+                            "UI",
+                            &instance.sop_instance_uid,
+                        );
+                        item
+                    })
+                    .collect();
+
+                let mut item = Dataset::new();
+                item.put(Rc::new(DicomElement::sequence(
+                    REFERENCED_SOP_SEQUENCE,
+                    references,
+                )));
+                item.put_string(SERIES_INSTANCE_UID, "UI", series_instance_uid);
+                item
+            })
+            .collect();
+
+        let mut dataset = Dataset::new();
+        // Type 2, present even when no MPPS was involved
+        dataset.put(Rc::new(DicomElement::sequence(
+            REFERENCED_PERFORMED_PROCEDURE_STEP_SEQUENCE,
+            steps,
+        )));
+        dataset.put(Rc::new(DicomElement::sequence(
+            REFERENCED_SERIES_SEQUENCE,
+            series,
+        )));
+        dataset.put_string(STUDY_INSTANCE_UID, "UI", &self.study_instance_uid);
+        dataset
+    }
+
+    pub fn from_dataset(dataset: &Dataset) -> DicomResult<Self> {
+        let study_instance_uid = dataset.string(STUDY_INSTANCE_UID).ok_or_else(|| {
+            DicomError::InvalidDataset("Notification lacks a study instance UID".to_string())
+        })?;
+
+        let mut notification = InstanceAvailabilityNotification::new(&study_instance_uid);
+        notification.performed_procedure_steps = dataset
+            .sequence(REFERENCED_PERFORMED_PROCEDURE_STEP_SEQUENCE)
+            .iter()
+            .filter_map(|item| item.string(REFERENCED_SOP_INSTANCE_UID))
+            .collect();
+
+        for series in dataset.sequence(REFERENCED_SERIES_SEQUENCE) {
+            let series_instance_uid = series.string(SERIES_INSTANCE_UID).ok_or_else(|| {
+                DicomError::InvalidDataset("Referenced series lacks its instance UID".to_string())
+            })?;
+
+            for reference in series.sequence(REFERENCED_SOP_SEQUENCE) {
+                let availability = reference.string(INSTANCE_AVAILABILITY).unwrap_or_default();
+                let availability = InstanceAvailability::parse(&availability).ok_or_else(|| {
+                    DicomError::InvalidValue(format!("Instance availability {}", availability))
+                })?;
+
+                notification.add(
+                    &series_instance_uid,
+                    AvailableInstance {
+                        sop_class_uid: reference
+                            .string(REFERENCED_SOP_CLASS_UID)
+                            .unwrap_or_default(),
+                        sop_instance_uid: reference
+                            .string(REFERENCED_SOP_INSTANCE_UID)
+                            .unwrap_or_default(),
+                        availability,
+                        retrieve_ae_title: reference.string(RETRIEVE_AE_TITLE).unwrap_or_default(),
+                    },
+                );
+            }
+        }
+
+        Ok(notification)
+    }
+
+    // N-CREATE of the notification over an association that accepted the
+    // IAN SOP class, returning the SOP instance UID it was created under
+    pub fn send(
+        &self,
+        association: &mut Association,
+        message_ids: &MessageIdGenerator,
+    ) -> DicomResult<String> {
+        let context_id = association
+            .context_for(INSTANCE_AVAILABILITY_NOTIFICATION_SOP_CLASS)
+            .map(|context| context.id)
+            .ok_or_else(|| {
+                DicomError::InvalidValue(
+                    "Instance Availability Notification was not accepted".to_string(),
+                )
+            })?;
+
+        let sop_instance_uid = uid::generate();
+        let request = NCreateRq {
+            message_id: message_ids.next_id(),
+            affected_sop_class_uid: INSTANCE_AVAILABILITY_NOTIFICATION_SOP_CLASS.to_string(),
+            affected_sop_instance_uid: Some(sop_instance_uid.clone()),
+            has_attribute_list: true,
+        };
+        let message = DimseMessage::new(&request, Some(self.to_dataset()));
+        association.send_message(context_id, &message)?;
+
+        let (_, response) = association.receive_response(&message.command)?;
+        let response = NCreateRsp::from_command(&response.command)?;
+        match response.0.status {
+            status if status.is_success() || status.is_warning() => Ok(sop_instance_uid),
+            status => Err(DicomError::InvalidValue(format!(
+                "N-CREATE of the notification failed: {}",
+                status
+            ))),
+        }
+    }
+}
+
+// Collects successfully stored instances, per study, until the caller
+// decides the studies are complete enough to announce
+#[derive(Debug, Clone, Default)]
+pub struct AvailabilityTracker {
+    retrieve_ae_title: String,
+    studies: BTreeMap<String, InstanceAvailabilityNotification>,
+}
+
+impl AvailabilityTracker {
+    pub fn new(retrieve_ae_title: &str) -> Self {
+        AvailabilityTracker {
+            retrieve_ae_title: retrieve_ae_title.to_string(),
+            studies: BTreeMap::new(),
+        }
+    }
+
+    // Records a stored instance, only successful and warning statuses count
+    pub fn stored(&mut self, dataset: &Dataset, status: DimseStatus) -> DicomResult<()> {
+        if !(status.is_success() || status.is_warning()) {
+            return Ok(());
+        }
+
+        let required = |tag: (u16, u16), name: &str| {
+            dataset.string(tag).ok_or_else(|| {
+                DicomError::InvalidDataset(format!("Stored instance lacks its {}", name))
+            })
+        };
+        let study_instance_uid = required(STUDY_INSTANCE_UID, "study instance UID")?;
+        let series_instance_uid = required(SERIES_INSTANCE_UID, "series instance UID")?;
+        let sop_class_uid = required(SOP_CLASS_UID, "SOP class UID")?;
+        let sop_instance_uid = required(SOP_INSTANCE_UID, "SOP instance UID")?;
+
+        self.studies
+            .entry(study_instance_uid.clone())
+            .or_insert_with(|| InstanceAvailabilityNotification::new(&study_instance_uid))
+            .add(
+                &series_instance_uid,
+                AvailableInstance {
+                    sop_class_uid,
+                    sop_instance_uid,
+                    availability: InstanceAvailability::Online,
+                    retrieve_ae_title: self.retrieve_ae_title.clone(),
+                },
+            );
+
+        Ok(())
+    }
+
+    pub fn pending_studies(&self) -> impl Iterator<Item = &str> {
+        self.studies.keys().map(String::as_str)
+    }
+
+    pub fn take(&mut self, study_instance_uid: &str) -> Option<InstanceAvailabilityNotification> {
+        self.studies.remove(study_instance_uid)
+    }
+
+    pub fn take_all(&mut self) -> Vec<InstanceAvailabilityNotification> {
+        std::mem::take(&mut self.studies).into_values().collect()
+    }
+}
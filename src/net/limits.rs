@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::pdu::AssociateRj;
+
+// Transient rejection by the presentation provider, local limit exceeded
+pub const LIMIT_EXCEEDED: AssociateRj = AssociateRj {
+    result: 2,
+    source: 3,
+    reason: 2,
+};
+
+// What one calling AE may use of a service, None for no limit
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PeerLimits {
+    pub max_associations: Option<usize>,
+    pub operations_per_second: Option<f64>,
+    pub bytes_per_second: Option<u64>,
+}
+
+impl PeerLimits {
+    pub fn new() -> Self {
+        PeerLimits::default()
+    }
+
+    pub fn with_max_associations(mut self, associations: usize) -> Self {
+        self.max_associations = Some(associations);
+        self
+    }
+
+    pub fn with_operations_per_second(mut self, operations: f64) -> Self {
+        self.operations_per_second = Some(operations);
+        self
+    }
+
+    pub fn with_bytes_per_second(mut self, bytes: u64) -> Self {
+        self.bytes_per_second = Some(bytes);
+        self
+    }
+}
+
+// Allows `rate` units a second with bursts of up to a second's worth
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        TokenBucket {
+            rate,
+            tokens: rate,
+            updated: Instant::now(),
+        }
+    }
+
+    // Takes the units, going into debt if need be, and returns how long the
+    // caller has to wait for the debt to be paid off
+    fn take(&mut self, units: f64) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.updated = now;
+        self.tokens -= units;
+        if self.tokens >= 0.0 || self.rate <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct PeerState {
+    associations: usize,
+    operations: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+}
+
+// Limits of the peers of an acceptor by calling AE title, the default limits
+// for peers not listed. Clones share their counts, so one limiter covers
+// every connection of a listener
+#[derive(Debug, Clone, Default)]
+pub struct PeerLimiter {
+    default: PeerLimits,
+    peers: HashMap<String, PeerLimits>,
+    state: Arc<Mutex<HashMap<String, PeerState>>>,
+}
+
+// Counts an association against its peer until dropped
+#[derive(Debug)]
+pub struct AssociationPermit {
+    limiter: PeerLimiter,
+    calling_ae: String,
+}
+
+impl Drop for AssociationPermit {
+    fn drop(&mut self) {
+        let mut state = self
+            .limiter
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(peer) = state.get_mut(&self.calling_ae) {
+            peer.associations = peer.associations.saturating_sub(1);
+        }
+    }
+}
+
+impl PeerLimiter {
+    pub fn new(default: PeerLimits) -> Self {
+        PeerLimiter {
+            default,
+            ..PeerLimiter::default()
+        }
+    }
+
+    pub fn with_peer(mut self, calling_ae: &str, limits: PeerLimits) -> Self {
+        self.peers.insert(calling_ae.trim().to_string(), limits);
+        self
+    }
+
+    pub fn limits_for(&self, calling_ae: &str) -> PeerLimits {
+        self.peers
+            .get(calling_ae.trim())
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    // Associations the peer holds right now
+    pub fn associations(&self, calling_ae: &str) -> usize {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(calling_ae.trim())
+            .map_or(0, |peer| peer.associations)
+    }
+
+    // A permit when the peer is below its association limit, None when the
+    // association is to be rejected with LIMIT_EXCEEDED
+    pub fn admit(&self, calling_ae: &str) -> Option<AssociationPermit> {
+        let calling_ae = calling_ae.trim();
+        let limits = self.limits_for(calling_ae);
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let peer = state.entry(calling_ae.to_string()).or_default();
+        if limits
+            .max_associations
+            .is_some_and(|max| peer.associations >= max)
+        {
+            return None;
+        }
+        peer.associations += 1;
+        Some(AssociationPermit {
+            limiter: self.clone(),
+            calling_ae: calling_ae.to_string(),
+        })
+    }
+
+    // Blocks the association until the peer is within its operation rate,
+    // called before each request is served
+    pub fn operation(&self, calling_ae: &str) {
+        let limits = self.limits_for(calling_ae);
+        if let Some(rate) = limits.operations_per_second {
+            let wait = self.take(calling_ae, 1.0, |peer| {
+                peer.operations
+                    .get_or_insert_with(|| TokenBucket::new(rate))
+            });
+            thread::sleep(wait);
+        }
+    }
+
+    // Blocks the association until the bytes it moved fit the peer's
+    // bandwidth, which TCP flow control then passes on to the peer
+    pub fn transfer(&self, calling_ae: &str, bytes: u64) {
+        let limits = self.limits_for(calling_ae);
+        if let Some(rate) = limits.bytes_per_second {
+            let wait = self.take(calling_ae, bytes as f64, |peer| {
+                peer.bytes
+                    .get_or_insert_with(|| TokenBucket::new(rate as f64))
+            });
+            thread::sleep(wait);
+        }
+    }
+
+    fn take<F>(&self, calling_ae: &str, units: f64, bucket: F) -> Duration
+    where
+        F: FnOnce(&mut PeerState) -> &mut TokenBucket,
+    {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let peer = state.entry(calling_ae.trim().to_string()).or_default();
+        bucket(peer).take(units)
+    }
+}
@@ -0,0 +1,119 @@
+use std::rc::Rc;
+
+use crate::core::{
+    dataset::Dataset, dictionary, element::DicomElement, person_name, tag::VisualRepresentation,
+};
+
+// Attribute matching of PS3.4 C.2.2.2, shared by the SCPs answering C-FIND.
+// Returns the response to `query` for a candidate, holding the query keys
+// with the candidate's values, or None when it does not match
+pub fn match_keys(query: &Dataset, candidate: &Dataset) -> Option<Dataset> {
+    let mut response = Dataset::new();
+
+    for key in query {
+        let tag = key.tag();
+        // Keys the model could not hold as their own VR come back as UN
+        let vr = match key.vr().code() {
+            "UN" => dictionary::vr_of(tag),
+            code => code,
+        };
+
+        if vr == "SQ" {
+            let items = match_sequence(&query.sequence(tag), candidate.sequence(tag))?;
+            response.put(Rc::new(DicomElement::sequence(tag, items)));
+            continue;
+        }
+
+        // Zero length keys read as empty values, so universal matching only
+        // applies to those and never to a real 19700101 or 000000
+        let pattern = query.string(tag).unwrap_or_default();
+        let value = candidate.string(tag).unwrap_or_default();
+        if !matches(&pattern, &value, vr) {
+            return None;
+        }
+        put_value(&mut response, tag, vr, &value);
+    }
+
+    Some(response)
+}
+
+// Sequence matching of C.2.2.2.6: an empty key or a key with an empty item
+// returns every item, an item with keys the items matching them
+pub fn match_sequence(query: &[Dataset], items: Vec<Dataset>) -> Option<Vec<Dataset>> {
+    let item_query = match query.first() {
+        Some(item_query) if !item_query.is_empty() => item_query,
+        _ => return Some(items),
+    };
+
+    let matched: Vec<Dataset> = items
+        .iter()
+        .filter_map(|item| match_keys(item_query, item))
+        .collect();
+    if matched.is_empty() {
+        None
+    } else {
+        Some(matched)
+    }
+}
+
+// Dates and times the model cannot hold are kept as raw bytes, as the reader
+// does, and zero length values stay empty values of the VR
+pub fn put_value(dataset: &mut Dataset, tag: (u16, u16), vr: &str, value: &str) {
+    if value.is_empty() {
+        dataset.set_empty_as(tag, vr);
+        return;
+    }
+    let value = VisualRepresentation::try_from_string(vr, value)
+        .unwrap_or_else(|_| VisualRepresentation::UN(value.as_bytes().to_vec()));
+    dataset.put(Rc::new(DicomElement::new(tag, value)));
+}
+
+// Matching of one key against a value. Values of several values match when
+// any of them does
+pub fn matches(pattern: &str, value: &str, vr: &str) -> bool {
+    let pattern = pattern.trim_end_matches(['\0', ' ']).trim();
+    if is_universal(pattern) {
+        return true;
+    }
+
+    // Person names match case and diacritic insensitively, component by component
+    if vr == "PN" {
+        return person_name::matches(pattern, value);
+    }
+
+    // List of UID matching, C.2.2.2.2
+    if vr == "UI" {
+        let value = value.trim_end_matches(['\0', ' ']);
+        return pattern
+            .split('\\')
+            .any(|uid| uid.trim_end_matches('\0') == value);
+    }
+
+    value
+        .split('\\')
+        .any(|value| matches_single(pattern, value.trim(), vr))
+}
+
+// Universal matching, C.2.2.2.3
+pub fn is_universal(pattern: &str) -> bool {
+    pattern.is_empty() || pattern.chars().all(|c| c == '*')
+}
+
+fn matches_single(pattern: &str, value: &str, vr: &str) -> bool {
+    // Range matching, C.2.2.2.5, either bound may be left open
+    if matches!(vr, "DA" | "TM" | "DT") {
+        if let Some((low, high)) = pattern.split_once('-') {
+            return !value.is_empty()
+                && (low.is_empty() || value >= low)
+                && (high.is_empty() || value <= high);
+        }
+    }
+
+    // Wildcard matching, C.2.2.2.4
+    if pattern.contains(['*', '?']) {
+        return person_name::wildcard(pattern, value);
+    }
+
+    // Single value matching, C.2.2.2.1
+    pattern == value
+}
@@ -0,0 +1,640 @@
+use std::{
+    fs,
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    thread::{self, JoinHandle},
+};
+
+use serde_json::Value;
+
+use super::{
+    association::{self, AssociateResponse, Association, AssociationOptions},
+    dimse::{self, CommandSet, DimseMessage, MessageIdGenerator},
+    pdu::AssociateRj,
+    shutdown::{self, Shutdown},
+    status::DimseStatus,
+};
+use crate::core::{
+    error::{DicomError, DicomResult},
+    json, transfer_syntax,
+};
+
+const C_STORE_RQ: u16 = 0x0001;
+const C_CANCEL_RQ: u16 = 0x0FFF;
+const C_ECHO_RQ: u16 = 0x0030;
+
+// A DIMSE message as DICOM JSON, which unlike datasets can be shared between
+// the threads serving connections
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedMessage {
+    pub command: Value,
+    pub data_set: Option<Value>,
+}
+
+impl RecordedMessage {
+    pub fn new(message: &DimseMessage) -> Self {
+        RecordedMessage {
+            command: json::to_json(message.command.dataset()),
+            data_set: message.data_set.as_ref().map(json::to_json),
+        }
+    }
+
+    pub fn to_message(&self) -> DicomResult<DimseMessage> {
+        Ok(DimseMessage {
+            command: CommandSet::from_dataset(json::from_json(&self.command)?),
+            data_set: self.data_set.as_ref().map(json::from_json).transpose()?,
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        serde_json::json!({ "command": self.command, "data_set": self.data_set })
+    }
+
+    fn from_json(value: &Value) -> DicomResult<Self> {
+        let command = value.get("command").cloned().ok_or_else(|| {
+            DicomError::InvalidValue("Recorded message lacks its command".to_string())
+        })?;
+        Ok(RecordedMessage {
+            command,
+            data_set: value
+                .get("data_set")
+                .filter(|value| !value.is_null())
+                .cloned(),
+        })
+    }
+}
+
+// One operation: the request of the SCU and everything the SCP sent up to
+// its final response, C-GET sub-operation requests included
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exchange {
+    pub abstract_syntax: String,
+    pub request: RecordedMessage,
+    pub replies: Vec<RecordedMessage>,
+}
+
+// Recorded exchanges in the order they happened, saved as JSON
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fixture {
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        Fixture::default()
+    }
+
+    pub fn load(path: &Path) -> DicomResult<Self> {
+        let value: Value = serde_json::from_slice(&fs::read(path)?)
+            .map_err(|error| DicomError::InvalidFile(format!("{}: {}", path.display(), error)))?;
+        Fixture::from_json(&value)
+    }
+
+    pub fn save(&self, path: &Path) -> DicomResult<()> {
+        let data = serde_json::to_vec_pretty(&self.to_json())
+            .map_err(|error| DicomError::Error(error.to_string()))?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Value {
+        let exchanges: Vec<Value> = self
+            .exchanges
+            .iter()
+            .map(|exchange| {
+                serde_json::json!({
+                    "abstract_syntax": exchange.abstract_syntax,
+                    "request": exchange.request.to_json(),
+                    "replies": exchange
+                        .replies
+                        .iter()
+                        .map(RecordedMessage::to_json)
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        serde_json::json!({ "exchanges": exchanges })
+    }
+
+    pub fn from_json(value: &Value) -> DicomResult<Self> {
+        let invalid = |what: &str| DicomError::InvalidValue(format!("Fixture {}", what));
+        let mut fixture = Fixture::new();
+        for exchange in value
+            .get("exchanges")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("lacks its exchanges"))?
+        {
+            let replies = exchange
+                .get("replies")
+                .and_then(Value::as_array)
+                .ok_or_else(|| invalid("exchange lacks its replies"))?
+                .iter()
+                .map(RecordedMessage::from_json)
+                .collect::<DicomResult<Vec<_>>>()?;
+            fixture.exchanges.push(Exchange {
+                abstract_syntax: exchange
+                    .get("abstract_syntax")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| invalid("exchange lacks its abstract syntax"))?
+                    .to_string(),
+                request: RecordedMessage::from_json(
+                    exchange
+                        .get("request")
+                        .ok_or_else(|| invalid("exchange lacks its request"))?,
+                )?,
+                replies,
+            });
+        }
+        Ok(fixture)
+    }
+
+    // Classes of the requests, and of the C-STORE sub-operations of C-GETs
+    pub fn abstract_syntaxes(&self) -> Vec<&str> {
+        let mut syntaxes: Vec<&str> = Vec::new();
+        for exchange in &self.exchanges {
+            syntaxes.push(&exchange.abstract_syntax);
+            syntaxes.extend(
+                exchange
+                    .replies
+                    .iter()
+                    .filter(|reply| {
+                        command_field(&reply.command).is_some_and(|field| field & 0x8000 == 0)
+                    })
+                    .filter_map(|reply| {
+                        reply
+                            .command
+                            .get("00000002")?
+                            .get("Value")?
+                            .get(0)?
+                            .as_str()
+                    }),
+            );
+        }
+        syntaxes.sort_unstable();
+        syntaxes.dedup();
+        syntaxes
+    }
+}
+
+// What of a request has to equal the recorded one for the recorded replies
+// to be sent. Message IDs and group lengths never count, and neither do
+// C-STORE data sets, whose UIDs are usually fresh on every run
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayMatching {
+    // Left out of commands and top level data set elements
+    pub ignored_tags: Vec<(u16, u16)>,
+    // Identifiers of queries and retrieves
+    pub data_sets: bool,
+}
+
+impl Default for ReplayMatching {
+    fn default() -> Self {
+        ReplayMatching {
+            ignored_tags: vec![dimse::AFFECTED_SOP_INSTANCE_UID],
+            data_sets: true,
+        }
+    }
+}
+
+impl ReplayMatching {
+    pub fn with_ignored_tag(mut self, tag: (u16, u16)) -> Self {
+        self.ignored_tags.push(tag);
+        self
+    }
+
+    pub fn with_data_sets(mut self, data_sets: bool) -> Self {
+        self.data_sets = data_sets;
+        self
+    }
+
+    fn strip(&self, value: &Value, ignored: &[(u16, u16)]) -> Value {
+        let mut value = value.clone();
+        if let Some(elements) = value.as_object_mut() {
+            for tag in self.ignored_tags.iter().chain(ignored) {
+                elements.remove(&format!("{:04X}{:04X}", tag.0, tag.1));
+            }
+        }
+        value
+    }
+
+    pub fn matches(&self, recorded: &RecordedMessage, request: &RecordedMessage) -> bool {
+        let ignored = [dimse::COMMAND_GROUP_LENGTH, dimse::MESSAGE_ID];
+        if self.strip(&recorded.command, &ignored) != self.strip(&request.command, &ignored) {
+            return false;
+        }
+        let store = command_field(&request.command) == Some(C_STORE_RQ);
+        if !self.data_sets || store {
+            return recorded.data_set.is_some() == request.data_set.is_some();
+        }
+        match (&recorded.data_set, &request.data_set) {
+            (Some(recorded), Some(request)) => {
+                self.strip(recorded, &[]) == self.strip(request, &[])
+            }
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+fn command_field(command: &Value) -> Option<u16> {
+    command
+        .get("00000100")?
+        .get("Value")?
+        .get(0)?
+        .as_u64()
+        .and_then(|field| u16::try_from(field).ok())
+}
+
+#[derive(Debug, Clone)]
+enum Mode {
+    // Proxies to the upstream SCP at this address and AE title
+    Record { address: String, ae_title: String },
+    Replay,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    fixture: Fixture,
+    // Recorded exchanges already replayed
+    used: Vec<bool>,
+    unmatched: Vec<String>,
+}
+
+// Test double of a PACS. Recording proxies every association to a real SCP
+// and saves the exchanges to a fixture file once each association ends;
+// replaying answers requests from the fixture alone, the earliest unused
+// matching exchange first and the last one again once all are used
+#[derive(Debug, Clone)]
+pub struct MockPacs {
+    ae_title: String,
+    mode: Mode,
+    path: Option<PathBuf>,
+    matching: ReplayMatching,
+    state: Arc<Mutex<State>>,
+    shutdown: Shutdown,
+}
+
+impl MockPacs {
+    pub fn record(path: &Path, upstream_address: &str, upstream_ae_title: &str) -> Self {
+        MockPacs {
+            ae_title: upstream_ae_title.to_string(),
+            mode: Mode::Record {
+                address: upstream_address.to_string(),
+                ae_title: upstream_ae_title.to_string(),
+            },
+            path: Some(path.to_path_buf()),
+            matching: ReplayMatching::default(),
+            state: Arc::new(Mutex::new(State::default())),
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    pub fn replay(path: &Path) -> DicomResult<Self> {
+        Ok(MockPacs::from_fixture(Fixture::load(path)?))
+    }
+
+    pub fn from_fixture(fixture: Fixture) -> Self {
+        let used = vec![false; fixture.exchanges.len()];
+        MockPacs {
+            ae_title: "MOCK-PACS".to_string(),
+            mode: Mode::Replay,
+            path: None,
+            matching: ReplayMatching::default(),
+            state: Arc::new(Mutex::new(State {
+                fixture,
+                used,
+                unmatched: Vec::new(),
+            })),
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    pub fn with_ae_title(mut self, ae_title: &str) -> Self {
+        self.ae_title = ae_title.to_string();
+        self
+    }
+
+    pub fn with_matching(mut self, matching: ReplayMatching) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    pub fn shutdown(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // What was recorded so far, or what is being replayed
+    pub fn fixture(&self) -> Fixture {
+        self.state().fixture.clone()
+    }
+
+    // Requests replay had no recorded exchange for, which were answered
+    // with a processing failure
+    pub fn unmatched(&self) -> Vec<String> {
+        self.state().unmatched.clone()
+    }
+
+    pub fn listen<A: ToSocketAddrs>(&self, address: A) -> DicomResult<()> {
+        self.listen_on(TcpListener::bind(address)?)
+    }
+
+    // Listens on a free local port in the background, for tests
+    pub fn spawn(&self) -> DicomResult<(SocketAddr, JoinHandle<DicomResult<()>>)> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        let mock = self.clone();
+        Ok((address, thread::spawn(move || mock.listen_on(listener))))
+    }
+
+    fn listen_on(&self, listener: TcpListener) -> DicomResult<()> {
+        listener.set_nonblocking(true)?;
+        while let Some(stream) = shutdown::accept(&listener, &self.shutdown)? {
+            let Some(guard) = self.shutdown.begin() else {
+                break;
+            };
+            let mock = self.clone();
+            thread::spawn(move || {
+                let _guard = guard;
+                mock.serve(stream)
+            });
+        }
+        Ok(())
+    }
+
+    pub fn serve(&self, stream: TcpStream) -> DicomResult<()> {
+        match &self.mode {
+            Mode::Record { address, ae_title } => {
+                self.record_association(stream, address, ae_title)
+            }
+            Mode::Replay => self.replay_association(stream),
+        }
+    }
+
+    fn record_association(
+        &self,
+        stream: TcpStream,
+        address: &str,
+        upstream_ae_title: &str,
+    ) -> DicomResult<()> {
+        let syntaxes = [
+            transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN,
+            transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN,
+        ];
+        let options = AssociationOptions::new(&self.ae_title, "");
+        let mut upstream = None;
+        let mut client = Association::accept(stream, options, |rq| {
+            let mut abstract_syntaxes: Vec<&str> = rq
+                .presentation_contexts
+                .iter()
+                .map(|context| context.abstract_syntax.as_str())
+                .collect();
+            abstract_syntaxes.sort_unstable();
+            abstract_syntaxes.dedup();
+
+            let mut options = AssociationOptions::new(&rq.calling_ae, upstream_ae_title);
+            for abstract_syntax in &abstract_syntaxes {
+                options = options.with_presentation_context(abstract_syntax, &syntaxes);
+            }
+            match Association::request(address, options) {
+                Ok(association) => {
+                    // Only what the upstream accepted, in its transfer syntax
+                    let accepted: Vec<(String, [&str; 1])> = association
+                        .presentation_contexts()
+                        .iter()
+                        .filter_map(|context| {
+                            let abstract_syntax = association.abstract_syntax(context.id)?;
+                            let transfer_syntax = syntaxes
+                                .iter()
+                                .find(|syntax| **syntax == context.transfer_syntax)?;
+                            Some((abstract_syntax.to_string(), [*transfer_syntax]))
+                        })
+                        .collect();
+                    let supported: Vec<(&str, &[&str])> = accepted
+                        .iter()
+                        .map(|(abstract_syntax, syntaxes)| {
+                            (abstract_syntax.as_str(), &syntaxes[..])
+                        })
+                        .collect();
+                    let contexts = association::negotiate(rq, &supported);
+                    upstream = Some(association);
+                    AssociateResponse::Accept(contexts)
+                }
+                // Transient rejection by the service user, no reason given
+                Err(_) => AssociateResponse::Reject(AssociateRj {
+                    result: 2,
+                    source: 1,
+                    reason: 1,
+                }),
+            }
+        })?;
+        let mut upstream = upstream.ok_or_else(|| {
+            DicomError::Network("Upstream association not established".to_string())
+        })?;
+
+        let result = self.proxy(&mut client, &mut upstream);
+        let released = upstream.release();
+        if let Some(path) = &self.path {
+            self.state().fixture.save(path)?;
+        }
+        result.and(released)
+    }
+
+    fn proxy(&self, client: &mut Association, upstream: &mut Association) -> DicomResult<()> {
+        let context = |association: &Association, abstract_syntax: &str| {
+            association
+                .context_for(abstract_syntax)
+                .map(|context| context.id)
+                .ok_or_else(|| {
+                    DicomError::InvalidValue(format!("{} was not accepted", abstract_syntax))
+                })
+        };
+
+        while let Some((context_id, request)) = client.receive_message()? {
+            let abstract_syntax = client
+                .abstract_syntax(context_id)
+                .unwrap_or_default()
+                .to_string();
+            upstream.send_message(context(upstream, &abstract_syntax)?, &request)?;
+            if request.command.command_field() == C_CANCEL_RQ {
+                continue;
+            }
+
+            let mut replies = Vec::new();
+            loop {
+                let (reply_context, reply) = upstream.receive_response(&request.command)?;
+                let reply_syntax = upstream
+                    .abstract_syntax(reply_context)
+                    .unwrap_or_default()
+                    .to_string();
+                client.send_message(context(client, &reply_syntax)?, &reply)?;
+                replies.push(RecordedMessage::new(&reply));
+
+                if !reply.command.is_response() {
+                    // Sub-operation of a C-GET, its response goes back up
+                    let (_, response) = client.receive_response(&reply.command)?;
+                    upstream.send_message(reply_context, &response)?;
+                    continue;
+                }
+                let status =
+                    DimseStatus::from_u16(reply.command.u16(dimse::STATUS).unwrap_or_default());
+                if !status.is_pending() {
+                    break;
+                }
+            }
+
+            let mut state = self.state();
+            state.fixture.exchanges.push(Exchange {
+                abstract_syntax,
+                request: RecordedMessage::new(&request),
+                replies,
+            });
+            state.used.push(true);
+        }
+        Ok(())
+    }
+
+    fn replay_association(&self, stream: TcpStream) -> DicomResult<()> {
+        let syntaxes = [
+            transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN,
+            transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN,
+        ];
+        let mut abstract_syntaxes: Vec<String> = self
+            .state()
+            .fixture
+            .abstract_syntaxes()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        abstract_syntaxes.push(dimse::VERIFICATION_SOP_CLASS.to_string());
+        let supported: Vec<(&str, &[&str])> = abstract_syntaxes
+            .iter()
+            .map(|abstract_syntax| (abstract_syntax.as_str(), &syntaxes[..]))
+            .collect();
+
+        let options = AssociationOptions::new(&self.ae_title, "");
+        let mut association = Association::accept(stream, options, |rq| {
+            AssociateResponse::Accept(association::negotiate(rq, &supported))
+        })?;
+        let message_ids = MessageIdGenerator::new();
+
+        while let Some((context_id, request)) = association.receive_message()? {
+            let field = request.command.command_field();
+            if field == C_CANCEL_RQ {
+                continue;
+            }
+            let abstract_syntax = association
+                .abstract_syntax(context_id)
+                .unwrap_or_default()
+                .to_string();
+            let Some(replies) = self.recorded_replies(&abstract_syntax, &request) else {
+                // Verification needs no recording
+                let status = if field == C_ECHO_RQ {
+                    DimseStatus::Success
+                } else {
+                    self.state().unmatched.push(format!(
+                        "{} on {}",
+                        dimse::command_name(field),
+                        abstract_syntax
+                    ));
+                    DimseStatus::ProcessingFailure
+                };
+                let message = DimseMessage {
+                    command: response(&request.command, status),
+                    data_set: None,
+                };
+                association.send_message(context_id, &message)?;
+                continue;
+            };
+
+            for reply in replies {
+                let mut reply = reply.to_message()?;
+                answer(&mut reply.command, &request.command, &message_ids);
+                // Sub-operations go over the context of their storage class
+                let reply_context = match reply.command.string(dimse::AFFECTED_SOP_CLASS_UID) {
+                    Some(sop_class_uid) if !reply.command.is_response() => association
+                        .context_for(&sop_class_uid)
+                        .map_or(context_id, |context| context.id),
+                    _ => context_id,
+                };
+                association.send_message(reply_context, &reply)?;
+                if !reply.command.is_response() {
+                    association.receive_response(&reply.command)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Replies of the exchange answering the request
+    fn recorded_replies(
+        &self,
+        abstract_syntax: &str,
+        request: &DimseMessage,
+    ) -> Option<Vec<RecordedMessage>> {
+        let recorded = RecordedMessage::new(request);
+        let mut state = self.state();
+        let candidates: Vec<usize> = state
+            .fixture
+            .exchanges
+            .iter()
+            .enumerate()
+            .filter(|(_, exchange)| {
+                exchange.abstract_syntax == abstract_syntax
+                    && self.matching.matches(&exchange.request, &recorded)
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        let chosen = candidates
+            .iter()
+            .copied()
+            .find(|index| !state.used[*index])
+            .or_else(|| candidates.last().copied());
+        let index = chosen?;
+        state.used[index] = true;
+        Some(state.fixture.exchanges[index].replies.clone())
+    }
+}
+
+// Rewrites a recorded reply for the request it now answers
+fn answer(reply: &mut CommandSet, request: &CommandSet, message_ids: &MessageIdGenerator) {
+    if reply.is_response() {
+        if let Some(message_id) = request.u16(dimse::MESSAGE_ID) {
+            reply.set_u16(dimse::MESSAGE_ID_BEING_RESPONDED_TO, message_id);
+        }
+        if let Some(instance) = request.string(dimse::AFFECTED_SOP_INSTANCE_UID) {
+            if reply.string(dimse::AFFECTED_SOP_INSTANCE_UID).is_some() {
+                reply.set_string(dimse::AFFECTED_SOP_INSTANCE_UID, "UI", &instance);
+            }
+        }
+    } else {
+        reply.set_u16(dimse::MESSAGE_ID, message_ids.next_id());
+    }
+}
+
+// Response of the request's command with only a status
+fn response(request: &CommandSet, status: DimseStatus) -> CommandSet {
+    let mut response = CommandSet::new(request.command_field() | 0x8000);
+    if let Some(message_id) = request.u16(dimse::MESSAGE_ID) {
+        response.set_u16(dimse::MESSAGE_ID_BEING_RESPONDED_TO, message_id);
+    }
+    let sop_class_uid = request
+        .string(dimse::AFFECTED_SOP_CLASS_UID)
+        .or_else(|| request.string(dimse::REQUESTED_SOP_CLASS_UID));
+    if let Some(sop_class_uid) = sop_class_uid {
+        response.set_string(dimse::AFFECTED_SOP_CLASS_UID, "UI", &sop_class_uid);
+    }
+    response.set_u16(dimse::STATUS, status.code());
+    response.set_has_data_set(false);
+    response
+}
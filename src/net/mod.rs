@@ -0,0 +1,26 @@
+pub mod association;
+pub mod cancel;
+
+#[cfg(any(all(feature = "serde", feature = "toml"), feature = "default"))]
+pub mod config;
+
+pub mod dimse;
+pub mod find;
+pub mod ian;
+pub mod limits;
+pub mod matching;
+
+#[cfg(any(feature = "serde", feature = "default"))]
+pub mod mock;
+
+pub mod move_queue;
+pub mod mpps;
+pub mod pdu;
+pub mod print;
+pub mod query;
+pub mod relational;
+pub mod results;
+pub mod shutdown;
+pub mod status;
+pub mod ups;
+pub mod worklist;
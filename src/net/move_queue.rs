@@ -0,0 +1,433 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use super::{
+    association::Association,
+    cancel::CancellationToken,
+    dimse::{CStoreRq, CStoreRsp, DimseCommand, DimseMessage, MessageIdGenerator, Priority},
+    status::DimseStatus,
+};
+use crate::core::{dataset::Dataset, error::DicomResult, meta::SOP_CLASS_UID};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_final(&self) -> bool {
+        matches!(self, JobState::Completed | JobState::Cancelled)
+    }
+}
+
+// Stat before routine before background
+fn rank(priority: Priority) -> u8 {
+    match priority {
+        Priority::High => 2,
+        Priority::Medium => 1,
+        Priority::Low => 0,
+    }
+}
+
+// The C-STORE sub-operations of one C-MOVE, with their progress. Jobs resume
+// at `next`, so an interrupted move does not send its instances twice
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveJob {
+    // Given by the queue
+    pub id: u64,
+    pub destination: String,
+    pub priority: Priority,
+    // Of the C-MOVE request, passed on in the C-STORE requests
+    pub originator_ae: Option<String>,
+    pub originator_message_id: Option<u16>,
+    // SOP Instance UIDs, in the order they are sent
+    pub instances: Vec<String>,
+    pub next: usize,
+    pub completed: usize,
+    pub warnings: usize,
+    pub failed: Vec<String>,
+    pub state: JobState,
+    // Times the job was interrupted and queued again
+    pub attempts: u32,
+}
+
+impl MoveJob {
+    pub fn new(destination: &str, instances: Vec<String>) -> Self {
+        MoveJob {
+            id: 0,
+            destination: destination.trim().to_string(),
+            priority: Priority::default(),
+            originator_ae: None,
+            originator_message_id: None,
+            instances,
+            next: 0,
+            completed: 0,
+            warnings: 0,
+            failed: Vec::new(),
+            state: JobState::Queued,
+            attempts: 0,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_originator(mut self, ae_title: &str, message_id: u16) -> Self {
+        self.originator_ae = Some(ae_title.to_string());
+        self.originator_message_id = Some(message_id);
+        self
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.instances.len().saturating_sub(self.next)
+    }
+
+    // Final status of the C-MOVE, PS3.4 C.4.2.1.5
+    pub fn status(&self) -> DimseStatus {
+        if self.state == JobState::Cancelled {
+            DimseStatus::Cancel
+        } else if self.failed.is_empty() && self.warnings == 0 {
+            DimseStatus::Success
+        } else if self.completed == 0 && self.warnings == 0 {
+            DimseStatus::from_u16(0xA702)
+        } else {
+            DimseStatus::from_u16(0xB000)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    jobs: BTreeMap<u64, MoveJob>,
+    tokens: HashMap<u64, CancellationToken>,
+    running: HashMap<String, usize>,
+    next_id: u64,
+}
+
+// Jobs of a C-MOVE SCP, handed to workers by priority and then in order of
+// arrival, with at most `limit` jobs running per destination. Clones share
+// the jobs
+#[derive(Debug, Clone)]
+pub struct MoveQueue {
+    shared: Arc<(Mutex<QueueState>, Condvar)>,
+    default_limit: usize,
+    limits: HashMap<String, usize>,
+}
+
+impl MoveQueue {
+    pub fn new(default_limit: usize) -> Self {
+        MoveQueue {
+            shared: Arc::default(),
+            default_limit: default_limit.max(1),
+            limits: HashMap::new(),
+        }
+    }
+
+    pub fn with_destination_limit(mut self, destination: &str, limit: usize) -> Self {
+        self.limits
+            .insert(destination.trim().to_string(), limit.max(1));
+        self
+    }
+
+    fn limit(&self, destination: &str) -> usize {
+        self.limits
+            .get(destination)
+            .copied()
+            .unwrap_or(self.default_limit)
+    }
+
+    fn state(&self) -> MutexGuard<'_, QueueState> {
+        self.shared.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn submit(&self, mut job: MoveJob) -> u64 {
+        let mut state = self.state();
+        state.next_id += 1;
+        job.id = state.next_id;
+        job.state = JobState::Queued;
+        state.jobs.insert(job.id, job);
+        self.shared.1.notify_all();
+        state.next_id
+    }
+
+    pub fn job(&self, id: u64) -> Option<MoveJob> {
+        self.state().jobs.get(&id).cloned()
+    }
+
+    // Every job the queue knows, finished ones included, by ID
+    pub fn jobs(&self) -> Vec<MoveJob> {
+        self.state().jobs.values().cloned().collect()
+    }
+
+    // Jobs not finished yet, what a service persists on shutdown
+    pub fn pending(&self) -> Vec<MoveJob> {
+        self.state()
+            .jobs
+            .values()
+            .filter(|job| !job.state.is_final())
+            .cloned()
+            .collect()
+    }
+
+    // Takes back persisted jobs, running ones resume where they stopped
+    pub fn restore(&self, jobs: Vec<MoveJob>) {
+        let mut state = self.state();
+        for mut job in jobs {
+            if job.state == JobState::Running {
+                job.state = JobState::Queued;
+            }
+            state.next_id = state.next_id.max(job.id);
+            state.jobs.insert(job.id, job);
+        }
+        self.shared.1.notify_all();
+    }
+
+    // Drops finished jobs, returning how many there were
+    pub fn purge(&self) -> usize {
+        let mut state = self.state();
+        let before = state.jobs.len();
+        state.jobs.retain(|_, job| !job.state.is_final());
+        before - state.jobs.len()
+    }
+
+    // Queued jobs are cancelled at once, running ones stop after their
+    // current sub-operation. False for unknown and finished jobs
+    pub fn cancel(&self, id: u64) -> bool {
+        let mut state = self.state();
+        let token = state.tokens.get(&id).cloned();
+        let Some(job) = state.jobs.get_mut(&id) else {
+            return false;
+        };
+        match job.state {
+            JobState::Queued => {
+                job.state = JobState::Cancelled;
+                true
+            }
+            JobState::Running => {
+                if let Some(token) = token {
+                    token.cancel();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // The most urgent job whose destination has a free slot, waiting up to
+    // `timeout` for one, forever when None
+    pub fn next(&self, timeout: Option<Duration>) -> Option<JobHandle> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.state();
+        loop {
+            let runnable = state
+                .jobs
+                .values()
+                .filter(|job| {
+                    job.state == JobState::Queued
+                        && state.running.get(&job.destination).copied().unwrap_or(0)
+                            < self.limit(&job.destination)
+                })
+                .max_by_key(|job| (rank(job.priority), std::cmp::Reverse(job.id)))
+                .map(|job| job.id);
+
+            if let Some(id) = runnable {
+                let token = CancellationToken::new();
+                let job = state.jobs.get_mut(&id).unwrap();
+                job.state = JobState::Running;
+                let destination = job.destination.clone();
+                *state.running.entry(destination).or_default() += 1;
+                state.tokens.insert(id, token.clone());
+                return Some(JobHandle {
+                    queue: self.clone(),
+                    id,
+                    token,
+                    done: false,
+                });
+            }
+
+            state = match deadline {
+                Some(deadline) => {
+                    let left = deadline.checked_duration_since(Instant::now())?;
+                    self.shared
+                        .1
+                        .wait_timeout(state, left)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+                None => self
+                    .shared
+                    .1
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+    }
+
+    fn release(&self, id: u64, update: impl FnOnce(&mut MoveJob)) -> Option<MoveJob> {
+        let mut state = self.state();
+        state.tokens.remove(&id);
+        let job = state.jobs.get_mut(&id)?;
+        update(job);
+        let job = job.clone();
+        if let Some(running) = state.running.get_mut(&job.destination) {
+            *running = running.saturating_sub(1);
+        }
+        self.shared.1.notify_all();
+        Some(job)
+    }
+}
+
+// A running job, owned by the worker sending its sub-operations. A handle
+// dropped before finish puts the job back in the queue, so a worker that
+// failed midway leaves it to be resumed
+#[derive(Debug)]
+pub struct JobHandle {
+    queue: MoveQueue,
+    id: u64,
+    token: CancellationToken,
+    done: bool,
+}
+
+impl JobHandle {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn job(&self) -> MoveJob {
+        self.queue
+            .job(self.id)
+            .expect("running jobs stay in the queue")
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    // Next instance to send, None once all were sent or the job was cancelled
+    pub fn next_instance(&self) -> Option<String> {
+        if self.is_cancelled() {
+            return None;
+        }
+        let job = self.job();
+        job.instances.get(job.next).cloned()
+    }
+
+    // Outcome of the sub-operation of the instance next_instance gave
+    pub fn record(&self, status: DimseStatus) {
+        let mut state = self.queue.state();
+        if let Some(job) = state.jobs.get_mut(&self.id) {
+            let Some(instance) = job.instances.get(job.next).cloned() else {
+                return;
+            };
+            job.next += 1;
+            if status.is_success() {
+                job.completed += 1;
+            } else if status.is_warning() {
+                job.warnings += 1;
+            } else {
+                job.failed.push(instance);
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> MoveJob {
+        self.done = true;
+        let cancelled = self.is_cancelled();
+        self.queue
+            .release(self.id, |job| {
+                job.state = if cancelled {
+                    JobState::Cancelled
+                } else {
+                    JobState::Completed
+                };
+            })
+            .expect("running jobs stay in the queue")
+    }
+
+    // Back in the queue with its progress, for when the destination went away
+    pub fn interrupt(mut self) -> MoveJob {
+        self.done = true;
+        self.queue
+            .release(self.id, |job| {
+                job.state = JobState::Queued;
+                job.attempts += 1;
+            })
+            .expect("running jobs stay in the queue")
+    }
+}
+
+impl Drop for JobHandle {
+    fn drop(&mut self) {
+        if !self.done {
+            self.queue.release(self.id, |job| {
+                job.state = JobState::Queued;
+                job.attempts += 1;
+            });
+        }
+    }
+}
+
+// Sends the remaining instances of a job over an association with its
+// destination, `load` giving the dataset of a SOP Instance UID. Instances
+// that cannot be loaded or have no accepted context count as failed; a lost
+// association interrupts the job, which then resumes with the instance that
+// was being sent
+pub fn execute<F>(handle: JobHandle, association: &mut Association, load: F) -> DicomResult<MoveJob>
+where
+    F: Fn(&str) -> DicomResult<Dataset>,
+{
+    let job = handle.job();
+    let message_ids = MessageIdGenerator::new();
+
+    while let Some(sop_instance_uid) = handle.next_instance() {
+        let dataset = match load(&sop_instance_uid) {
+            Ok(dataset) => dataset,
+            Err(_) => {
+                handle.record(DimseStatus::ProcessingFailure);
+                continue;
+            }
+        };
+        let sop_class_uid = dataset.string(SOP_CLASS_UID).unwrap_or_default();
+        let Some(context_id) = association
+            .context_for(&sop_class_uid)
+            .map(|context| context.id)
+        else {
+            handle.record(DimseStatus::SopClassNotSupported);
+            continue;
+        };
+
+        let request = CStoreRq {
+            message_id: message_ids.next_id(),
+            affected_sop_class_uid: sop_class_uid,
+            affected_sop_instance_uid: sop_instance_uid,
+            priority: job.priority,
+            move_originator_ae_title: job.originator_ae.clone(),
+            move_originator_message_id: job.originator_message_id,
+        };
+        let message = DimseMessage::new(&request, Some(dataset));
+        let sent = association
+            .send_message(context_id, &message)
+            .and_then(|_| association.receive_response(&message.command));
+        let response = match sent {
+            Ok((_, response)) => response,
+            Err(error) => {
+                handle.interrupt();
+                return Err(error);
+            }
+        };
+        match CStoreRsp::from_command(&response.command) {
+            Ok(response) => handle.record(response.status),
+            Err(_) => handle.record(DimseStatus::ProcessingFailure),
+        }
+    }
+
+    Ok(handle.finish())
+}
@@ -0,0 +1,401 @@
+use std::{fmt::Display, rc::Rc};
+
+use super::{
+    association::Association,
+    dimse::{
+        DimseCommand, DimseMessage, MessageIdGenerator, NCreateRq, NCreateRsp, NSetRq, NSetRsp,
+    },
+    ian::MODALITY_PERFORMED_PROCEDURE_STEP_SOP_CLASS,
+    matching,
+    status::DimseStatus,
+    worklist::WorklistItem,
+};
+use crate::core::{
+    dataset::Dataset,
+    element::DicomElement,
+    error::{DicomError, DicomResult},
+    uid,
+};
+
+pub const MODALITY: (u16, u16) = (0x0008, 0x0060);
+pub const PROCEDURE_CODE_SEQUENCE: (u16, u16) = (0x0008, 0x1032);
+pub const PERFORMING_PHYSICIAN_NAME: (u16, u16) = (0x0008, 0x1050);
+pub const OPERATORS_NAME: (u16, u16) = (0x0008, 0x1070);
+pub const SERIES_DESCRIPTION: (u16, u16) = (0x0008, 0x103E);
+pub const RETRIEVE_AE_TITLE: (u16, u16) = (0x0008, 0x0054);
+pub const REFERENCED_PATIENT_SEQUENCE: (u16, u16) = (0x0008, 0x1120);
+pub const REFERENCED_IMAGE_SEQUENCE: (u16, u16) = (0x0008, 0x1140);
+pub const REFERENCED_SOP_CLASS_UID: (u16, u16) = (0x0008, 0x1150);
+pub const REFERENCED_SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x1155);
+pub const PATIENT_NAME: (u16, u16) = (0x0010, 0x0010);
+pub const PATIENT_ID: (u16, u16) = (0x0010, 0x0020);
+pub const PATIENT_BIRTH_DATE: (u16, u16) = (0x0010, 0x0030);
+pub const PATIENT_SEX: (u16, u16) = (0x0010, 0x0040);
+pub const PROTOCOL_NAME: (u16, u16) = (0x0018, 0x1030);
+pub const STUDY_INSTANCE_UID: (u16, u16) = (0x0020, 0x000D);
+pub const SERIES_INSTANCE_UID: (u16, u16) = (0x0020, 0x000E);
+pub const STUDY_ID: (u16, u16) = (0x0020, 0x0010);
+pub const REQUESTED_PROCEDURE_DESCRIPTION: (u16, u16) = (0x0032, 0x1060);
+pub const SCHEDULED_PROCEDURE_STEP_DESCRIPTION: (u16, u16) = (0x0040, 0x0007);
+pub const SCHEDULED_PROTOCOL_CODE_SEQUENCE: (u16, u16) = (0x0040, 0x0008);
+pub const SCHEDULED_PROCEDURE_STEP_ID: (u16, u16) = (0x0040, 0x0009);
+pub const REFERENCED_NON_IMAGE_COMPOSITE_SOP_INSTANCE_SEQUENCE: (u16, u16) = (0x0040, 0x0220);
+pub const PERFORMED_STATION_AE_TITLE: (u16, u16) = (0x0040, 0x0241);
+pub const PERFORMED_STATION_NAME: (u16, u16) = (0x0040, 0x0242);
+pub const PERFORMED_LOCATION: (u16, u16) = (0x0040, 0x0243);
+pub const PERFORMED_PROCEDURE_STEP_START_DATE: (u16, u16) = (0x0040, 0x0244);
+pub const PERFORMED_PROCEDURE_STEP_START_TIME: (u16, u16) = (0x0040, 0x0245);
+pub const PERFORMED_PROCEDURE_STEP_END_DATE: (u16, u16) = (0x0040, 0x0250);
+pub const PERFORMED_PROCEDURE_STEP_END_TIME: (u16, u16) = (0x0040, 0x0251);
+pub const PERFORMED_PROCEDURE_STEP_STATUS: (u16, u16) = (0x0040, 0x0252);
+pub const PERFORMED_PROCEDURE_STEP_ID: (u16, u16) = (0x0040, 0x0253);
+pub const PERFORMED_PROCEDURE_STEP_DESCRIPTION: (u16, u16) = (0x0040, 0x0254);
+pub const PERFORMED_PROCEDURE_TYPE_DESCRIPTION: (u16, u16) = (0x0040, 0x0255);
+pub const PERFORMED_PROTOCOL_CODE_SEQUENCE: (u16, u16) = (0x0040, 0x0260);
+pub const SCHEDULED_STEP_ATTRIBUTES_SEQUENCE: (u16, u16) = (0x0040, 0x0270);
+pub const PERFORMED_SERIES_SEQUENCE: (u16, u16) = (0x0040, 0x0340);
+pub const ACCESSION_NUMBER: (u16, u16) = (0x0008, 0x0050);
+pub const REQUESTED_PROCEDURE_ID: (u16, u16) = (0x0040, 0x1001);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StepStatus {
+    #[default]
+    InProgress,
+    Discontinued,
+    Completed,
+}
+
+impl StepStatus {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "IN PROGRESS" => Some(StepStatus::InProgress),
+            "DISCONTINUED" => Some(StepStatus::Discontinued),
+            "COMPLETED" => Some(StepStatus::Completed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StepStatus::InProgress => "IN PROGRESS",
+            StepStatus::Discontinued => "DISCONTINUED",
+            StepStatus::Completed => "COMPLETED",
+        }
+    }
+}
+
+impl Display for StepStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PerformedSeries {
+    pub series_instance_uid: String,
+    pub series_description: String,
+    pub protocol_name: String,
+    // SOP class and instance UIDs of the images acquired
+    pub images: Vec<(String, String)>,
+}
+
+// Modality Performed Procedure Step of PS3.4 F.7, as a modality reports it:
+// N-CREATE once acquisition starts, N-SET with the series once it ends
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PerformedProcedureStep {
+    pub sop_instance_uid: String,
+    pub id: String,
+    pub status: StepStatus,
+    pub modality: String,
+    pub station_ae_title: String,
+    pub description: String,
+    pub study_id: String,
+    pub start_date: String,
+    pub start_time: String,
+    pub end_date: String,
+    pub end_time: String,
+    // The scheduled step performed, or an unscheduled one with the patient
+    // and study filled in
+    pub scheduled: WorklistItem,
+    pub series: Vec<PerformedSeries>,
+}
+
+impl PerformedProcedureStep {
+    pub fn new(modality: &str, station_ae_title: &str, scheduled: WorklistItem) -> Self {
+        PerformedProcedureStep {
+            sop_instance_uid: uid::generate(),
+            modality: modality.to_string(),
+            station_ae_title: station_ae_title.to_string(),
+            description: scheduled.scheduled_procedure_step_description.clone(),
+            scheduled,
+            ..PerformedProcedureStep::default()
+        }
+    }
+
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = id.to_string();
+        self
+    }
+
+    pub fn with_study_id(mut self, study_id: &str) -> Self {
+        self.study_id = study_id.to_string();
+        self
+    }
+
+    pub fn with_start(mut self, date: &str, time: &str) -> Self {
+        self.start_date = date.to_string();
+        self.start_time = time.to_string();
+        self
+    }
+
+    // Records an acquired image under its series, adding the series when new
+    pub fn add_image(
+        &mut self,
+        series_instance_uid: &str,
+        sop_class_uid: &str,
+        sop_instance_uid: &str,
+    ) {
+        let index = match self
+            .series
+            .iter()
+            .position(|series| series.series_instance_uid == series_instance_uid)
+        {
+            Some(index) => index,
+            None => {
+                self.series.push(PerformedSeries {
+                    series_instance_uid: series_instance_uid.to_string(),
+                    ..PerformedSeries::default()
+                });
+                self.series.len() - 1
+            }
+        };
+        self.series[index]
+            .images
+            .push((sop_class_uid.to_string(), sop_instance_uid.to_string()));
+    }
+
+    fn scheduled_step(&self) -> Dataset {
+        let scheduled = &self.scheduled;
+        let mut step = Dataset::new();
+        step.put_string(STUDY_INSTANCE_UID, "UI", &scheduled.study_instance_uid);
+        step.put_string(ACCESSION_NUMBER, "SH", &scheduled.accession_number);
+        step.put_string(
+            REQUESTED_PROCEDURE_ID,
+            "SH",
+            &scheduled.requested_procedure_id,
+        );
+        step.put_string(
+            REQUESTED_PROCEDURE_DESCRIPTION,
+            "LO",
+            &scheduled.requested_procedure_description,
+        );
+        step.put_string(
+            SCHEDULED_PROCEDURE_STEP_ID,
+            "SH",
+            &scheduled.scheduled_procedure_step_id,
+        );
+        step.put_string(
+            SCHEDULED_PROCEDURE_STEP_DESCRIPTION,
+            "LO",
+            &scheduled.scheduled_procedure_step_description,
+        );
+        step.set_empty_as(SCHEDULED_PROTOCOL_CODE_SEQUENCE, "SQ");
+        step
+    }
+
+    fn performed_series(&self) -> Vec<Dataset> {
+        self.series
+            .iter()
+            .map(|series| {
+                let images = series
+                    .images
+                    .iter()
+                    .map(|(sop_class_uid, sop_instance_uid)| {
+                        let mut item = Dataset::new();
+                        item.put_string(REFERENCED_SOP_CLASS_UID, "UI", sop_class_uid);
+                        item.put_string(REFERENCED_SOP_INSTANCE_UID, "UI", sop_instance_uid);
+                        item
+                    })
+                    .collect();
+
+                let mut item = Dataset::new();
+                item.set_empty_as(RETRIEVE_AE_TITLE, "AE");
+                item.put_string(SERIES_DESCRIPTION, "LO", &series.series_description);
+                item.set_empty_as(PERFORMING_PHYSICIAN_NAME, "PN");
+                item.set_empty_as(OPERATORS_NAME, "PN");
+                item.put(Rc::new(DicomElement::sequence(
+                    REFERENCED_IMAGE_SEQUENCE,
+                    images,
+                )));
+                item.put_string(PROTOCOL_NAME, "LO", &series.protocol_name);
+                item.put_string(SERIES_INSTANCE_UID, "UI", &series.series_instance_uid);
+                item.set_empty_as(REFERENCED_NON_IMAGE_COMPOSITE_SOP_INSTANCE_SEQUENCE, "SQ");
+                item
+            })
+            .collect()
+    }
+
+    // Attributes of the N-CREATE, F.7.2.1.1, with the Type 2 ones sent
+    // empty until the N-SET fills them
+    pub fn to_create_dataset(&self) -> Dataset {
+        let scheduled = &self.scheduled;
+        let mut dataset = Dataset::new();
+        dataset.put_string(MODALITY, "CS", &self.modality);
+        dataset.set_empty_as(PROCEDURE_CODE_SEQUENCE, "SQ");
+        dataset.set_empty_as(REFERENCED_PATIENT_SEQUENCE, "SQ");
+        dataset.put_string(PATIENT_NAME, "PN", &scheduled.patient_name);
+        dataset.put_string(PATIENT_ID, "LO", &scheduled.patient_id);
+        matching::put_value(
+            &mut dataset,
+            PATIENT_BIRTH_DATE,
+            "DA",
+            &scheduled.patient_birth_date,
+        );
+        dataset.put_string(PATIENT_SEX, "CS", &scheduled.patient_sex);
+        dataset.put_string(STUDY_ID, "SH", &self.study_id);
+        dataset.put_string(PERFORMED_STATION_AE_TITLE, "AE", &self.station_ae_title);
+        dataset.set_empty_as(PERFORMED_STATION_NAME, "SH");
+        dataset.set_empty_as(PERFORMED_LOCATION, "SH");
+        matching::put_value(
+            &mut dataset,
+            PERFORMED_PROCEDURE_STEP_START_DATE,
+            "DA",
+            &self.start_date,
+        );
+        matching::put_value(
+            &mut dataset,
+            PERFORMED_PROCEDURE_STEP_START_TIME,
+            "TM",
+            &self.start_time,
+        );
+        dataset.set_empty_as(PERFORMED_PROCEDURE_STEP_END_DATE, "DA");
+        dataset.set_empty_as(PERFORMED_PROCEDURE_STEP_END_TIME, "TM");
+        dataset.put_string(
+            PERFORMED_PROCEDURE_STEP_STATUS,
+            "CS",
+            StepStatus::InProgress.as_str(),
+        );
+        dataset.put_string(PERFORMED_PROCEDURE_STEP_ID, "SH", &self.id);
+        dataset.put_string(
+            PERFORMED_PROCEDURE_STEP_DESCRIPTION,
+            "LO",
+            &self.description,
+        );
+        dataset.set_empty_as(PERFORMED_PROCEDURE_TYPE_DESCRIPTION, "LO");
+        dataset.set_empty_as(PERFORMED_PROTOCOL_CODE_SEQUENCE, "SQ");
+        dataset.put(Rc::new(DicomElement::sequence(
+            SCHEDULED_STEP_ATTRIBUTES_SEQUENCE,
+            vec![self.scheduled_step()],
+        )));
+        dataset.set_empty_as(PERFORMED_SERIES_SEQUENCE, "SQ");
+        dataset
+    }
+
+    // Attributes of the final N-SET, F.7.2.2.1, ending the step with its
+    // status and series
+    pub fn to_set_dataset(&self) -> Dataset {
+        let mut dataset = Dataset::new();
+        matching::put_value(
+            &mut dataset,
+            PERFORMED_PROCEDURE_STEP_END_DATE,
+            "DA",
+            &self.end_date,
+        );
+        matching::put_value(
+            &mut dataset,
+            PERFORMED_PROCEDURE_STEP_END_TIME,
+            "TM",
+            &self.end_time,
+        );
+        dataset.put_string(PERFORMED_PROCEDURE_STEP_STATUS, "CS", self.status.as_str());
+        dataset.put_string(
+            PERFORMED_PROCEDURE_STEP_DESCRIPTION,
+            "LO",
+            &self.description,
+        );
+        dataset.put(Rc::new(DicomElement::sequence(
+            PERFORMED_SERIES_SEQUENCE,
+            self.performed_series(),
+        )));
+        dataset
+    }
+
+    fn context_id(association: &Association) -> DicomResult<u8> {
+        association
+            .context_for(MODALITY_PERFORMED_PROCEDURE_STEP_SOP_CLASS)
+            .map(|context| context.id)
+            .ok_or_else(|| {
+                DicomError::InvalidValue(
+                    "Modality Performed Procedure Step was not accepted".to_string(),
+                )
+            })
+    }
+
+    fn check(operation: &str, status: DimseStatus) -> DicomResult<()> {
+        if status.is_success() || status.is_warning() {
+            Ok(())
+        } else {
+            Err(DicomError::InvalidValue(format!(
+                "{} of the performed procedure step failed: {}",
+                operation, status
+            )))
+        }
+    }
+
+    // N-CREATE of the step as IN PROGRESS
+    pub fn create(
+        &mut self,
+        association: &mut Association,
+        message_ids: &MessageIdGenerator,
+    ) -> DicomResult<()> {
+        let context_id = Self::context_id(association)?;
+        let request = NCreateRq {
+            message_id: message_ids.next_id(),
+            affected_sop_class_uid: MODALITY_PERFORMED_PROCEDURE_STEP_SOP_CLASS.to_string(),
+            affected_sop_instance_uid: Some(self.sop_instance_uid.clone()),
+            has_attribute_list: true,
+        };
+        let message = DimseMessage::new(&request, Some(self.to_create_dataset()));
+        association.send_message(context_id, &message)?;
+
+        let (_, response) = association.receive_response(&message.command)?;
+        Self::check(
+            "N-CREATE",
+            NCreateRsp::from_command(&response.command)?.0.status,
+        )?;
+        self.status = StepStatus::InProgress;
+        Ok(())
+    }
+
+    // N-SET ending the step, COMPLETED or DISCONTINUED, at the given date
+    // and time
+    pub fn finish(
+        &mut self,
+        association: &mut Association,
+        message_ids: &MessageIdGenerator,
+        status: StepStatus,
+        end_date: &str,
+        end_time: &str,
+    ) -> DicomResult<()> {
+        if status == StepStatus::InProgress {
+            return Err(DicomError::InvalidValue(
+                "A performed procedure step ends COMPLETED or DISCONTINUED".to_string(),
+            ));
+        }
+        let context_id = Self::context_id(association)?;
+        self.status = status;
+        self.end_date = end_date.to_string();
+        self.end_time = end_time.to_string();
+
+        let request = NSetRq {
+            message_id: message_ids.next_id(),
+            requested_sop_class_uid: MODALITY_PERFORMED_PROCEDURE_STEP_SOP_CLASS.to_string(),
+            requested_sop_instance_uid: self.sop_instance_uid.clone(),
+        };
+        let message = DimseMessage::new(&request, Some(self.to_set_dataset()));
+        association.send_message(context_id, &message)?;
+
+        let (_, response) = association.receive_response(&message.command)?;
+        Self::check("N-SET", NSetRsp::from_command(&response.command)?.0.status)
+    }
+}
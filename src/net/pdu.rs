@@ -0,0 +1,704 @@
+use std::io::{ErrorKind, Read, Write};
+
+use crate::core::error::{AbortSource, DicomError, DicomResult};
+
+pub const A_ASSOCIATE_RQ: u8 = 0x01;
+pub const A_ASSOCIATE_AC: u8 = 0x02;
+pub const A_ASSOCIATE_RJ: u8 = 0x03;
+pub const P_DATA_TF: u8 = 0x04;
+pub const A_RELEASE_RQ: u8 = 0x05;
+pub const A_RELEASE_RP: u8 = 0x06;
+pub const A_ABORT: u8 = 0x07;
+
+const APPLICATION_CONTEXT_ITEM: u8 = 0x10;
+const PRESENTATION_CONTEXT_RQ_ITEM: u8 = 0x20;
+const PRESENTATION_CONTEXT_AC_ITEM: u8 = 0x21;
+const ABSTRACT_SYNTAX_ITEM: u8 = 0x30;
+const TRANSFER_SYNTAX_ITEM: u8 = 0x40;
+const USER_INFORMATION_ITEM: u8 = 0x50;
+const MAX_LENGTH_ITEM: u8 = 0x51;
+const IMPLEMENTATION_CLASS_UID_ITEM: u8 = 0x52;
+const ROLE_SELECTION_ITEM: u8 = 0x54;
+const IMPLEMENTATION_VERSION_NAME_ITEM: u8 = 0x55;
+const SOP_CLASS_EXTENDED_NEGOTIATION_ITEM: u8 = 0x56;
+
+pub const APPLICATION_CONTEXT_NAME: &str = "1.2.840.10008.3.1.1.1";
+pub const DEFAULT_MAX_PDU_LENGTH: u32 = 16384;
+
+// Upper bound for incoming PDUs regardless of what the peer claims, so a
+// corrupt length cannot make us allocate gigabytes
+pub const PDU_LENGTH_LIMIT: u32 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbortReason {
+    NotSpecified,
+    UnrecognizedPdu,
+    UnexpectedPdu,
+    UnrecognizedPduParameter,
+    UnexpectedPduParameter,
+    InvalidPduParameterValue,
+    Other(u8),
+}
+
+impl AbortReason {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => AbortReason::NotSpecified,
+            1 => AbortReason::UnrecognizedPdu,
+            2 => AbortReason::UnexpectedPdu,
+            4 => AbortReason::UnrecognizedPduParameter,
+            5 => AbortReason::UnexpectedPduParameter,
+            6 => AbortReason::InvalidPduParameterValue,
+            other => AbortReason::Other(other),
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            AbortReason::NotSpecified => 0,
+            AbortReason::UnrecognizedPdu => 1,
+            AbortReason::UnexpectedPdu => 2,
+            AbortReason::UnrecognizedPduParameter => 4,
+            AbortReason::UnexpectedPduParameter => 5,
+            AbortReason::InvalidPduParameterValue => 6,
+            AbortReason::Other(value) => *value,
+        }
+    }
+}
+
+impl std::fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AbortReason::NotSpecified => write!(f, "reason not specified"),
+            AbortReason::UnrecognizedPdu => write!(f, "unrecognized PDU"),
+            AbortReason::UnexpectedPdu => write!(f, "unexpected PDU"),
+            AbortReason::UnrecognizedPduParameter => write!(f, "unrecognized PDU parameter"),
+            AbortReason::UnexpectedPduParameter => write!(f, "unexpected PDU parameter"),
+            AbortReason::InvalidPduParameterValue => write!(f, "invalid PDU parameter value"),
+            AbortReason::Other(value) => write!(f, "reason {}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresentationContextRq {
+    pub id: u8,
+    pub abstract_syntax: String,
+    pub transfer_syntaxes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentationContextResult {
+    Acceptance,
+    UserRejection,
+    NoReason,
+    AbstractSyntaxNotSupported,
+    TransferSyntaxesNotSupported,
+}
+
+impl PresentationContextResult {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => PresentationContextResult::Acceptance,
+            1 => PresentationContextResult::UserRejection,
+            3 => PresentationContextResult::AbstractSyntaxNotSupported,
+            4 => PresentationContextResult::TransferSyntaxesNotSupported,
+            _ => PresentationContextResult::NoReason,
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            PresentationContextResult::Acceptance => 0,
+            PresentationContextResult::UserRejection => 1,
+            PresentationContextResult::NoReason => 2,
+            PresentationContextResult::AbstractSyntaxNotSupported => 3,
+            PresentationContextResult::TransferSyntaxesNotSupported => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresentationContextAc {
+    pub id: u8,
+    pub result: PresentationContextResult,
+    pub transfer_syntax: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct UserInformation {
+    // Zero means the sender accepts PDUs of any length
+    pub max_pdu_length: u32,
+    pub implementation_class_uid: String,
+    pub implementation_version_name: Option<String>,
+    pub role_selections: Vec<RoleSelection>,
+    pub extended_negotiations: Vec<ExtendedNegotiation>,
+}
+
+// SCP/SCU Role Selection of PS3.7 D.3.3.4, the roles are those of the
+// association requestor. Without one the requestor is SCU and the acceptor SCP
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleSelection {
+    pub sop_class_uid: String,
+    pub scu_role: bool,
+    pub scp_role: bool,
+}
+
+// SOP Class Extended Negotiation of PS3.7 D.3.3.5, the meaning of the
+// application information is up to the service class of the SOP class
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedNegotiation {
+    pub sop_class_uid: String,
+    pub service_class_application_information: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssociateRq {
+    pub protocol_version: u16,
+    pub called_ae: String,
+    pub calling_ae: String,
+    pub application_context: String,
+    pub presentation_contexts: Vec<PresentationContextRq>,
+    pub user_information: UserInformation,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssociateAc {
+    pub protocol_version: u16,
+    pub called_ae: String,
+    pub calling_ae: String,
+    pub application_context: String,
+    pub presentation_contexts: Vec<PresentationContextAc>,
+    pub user_information: UserInformation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssociateRj {
+    // 1 permanent, 2 transient
+    pub result: u8,
+    // 1 service user, 2 ACSE provider, 3 presentation provider
+    pub source: u8,
+    pub reason: u8,
+}
+
+impl std::fmt::Display for AssociateRj {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let result = if self.result == 1 {
+            "permanent"
+        } else {
+            "transient"
+        };
+        let reason = match (self.source, self.reason) {
+            (1, 1) => "no reason given",
+            (1, 2) => "application context name not supported",
+            (1, 3) => "calling AE title not recognized",
+            (1, 7) => "called AE title not recognized",
+            (2, 1) => "no reason given",
+            (2, 2) => "protocol version not supported",
+            (3, 1) => "temporary congestion",
+            (3, 2) => "local limit exceeded",
+            _ => "unknown reason",
+        };
+        write!(f, "{} rejection, {}", result, reason)
+    }
+}
+
+// A presentation data value, one fragment of a command or data set
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pdv {
+    pub context_id: u8,
+    pub is_command: bool,
+    pub is_last: bool,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pdu {
+    AssociateRq(AssociateRq),
+    AssociateAc(AssociateAc),
+    AssociateRj(AssociateRj),
+    PData(Vec<Pdv>),
+    ReleaseRq,
+    ReleaseRp,
+    Abort {
+        source: AbortSource,
+        reason: AbortReason,
+    },
+}
+
+impl Pdu {
+    pub fn pdu_type(&self) -> u8 {
+        match self {
+            Pdu::AssociateRq(_) => A_ASSOCIATE_RQ,
+            Pdu::AssociateAc(_) => A_ASSOCIATE_AC,
+            Pdu::AssociateRj(_) => A_ASSOCIATE_RJ,
+            Pdu::PData(_) => P_DATA_TF,
+            Pdu::ReleaseRq => A_RELEASE_RQ,
+            Pdu::ReleaseRp => A_RELEASE_RP,
+            Pdu::Abort { .. } => A_ABORT,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let body = match self {
+            Pdu::AssociateRq(rq) => {
+                let mut body = associate_header(
+                    rq.protocol_version,
+                    &rq.called_ae,
+                    &rq.calling_ae,
+                    &rq.application_context,
+                );
+                for context in &rq.presentation_contexts {
+                    let mut item = vec![context.id, 0, 0, 0];
+                    put_item(
+                        &mut item,
+                        ABSTRACT_SYNTAX_ITEM,
+                        context.abstract_syntax.as_bytes(),
+                    );
+                    for syntax in &context.transfer_syntaxes {
+                        put_item(&mut item, TRANSFER_SYNTAX_ITEM, syntax.as_bytes());
+                    }
+                    put_item(&mut body, PRESENTATION_CONTEXT_RQ_ITEM, &item);
+                }
+                put_user_information(&mut body, &rq.user_information);
+                body
+            }
+            Pdu::AssociateAc(ac) => {
+                let mut body = associate_header(
+                    ac.protocol_version,
+                    &ac.called_ae,
+                    &ac.calling_ae,
+                    &ac.application_context,
+                );
+                for context in &ac.presentation_contexts {
+                    let mut item = vec![context.id, 0, context.result.as_u8(), 0];
+                    put_item(
+                        &mut item,
+                        TRANSFER_SYNTAX_ITEM,
+                        context.transfer_syntax.as_bytes(),
+                    );
+                    put_item(&mut body, PRESENTATION_CONTEXT_AC_ITEM, &item);
+                }
+                put_user_information(&mut body, &ac.user_information);
+                body
+            }
+            Pdu::AssociateRj(rj) => vec![0, rj.result, rj.source, rj.reason],
+            Pdu::PData(pdvs) => {
+                let mut body = Vec::new();
+                for pdv in pdvs {
+                    body.extend_from_slice(&(pdv.data.len() as u32 + 2).to_be_bytes());
+                    body.push(pdv.context_id);
+                    body.push(pdv.is_command as u8 | (pdv.is_last as u8) << 1);
+                    body.extend_from_slice(&pdv.data);
+                }
+                body
+            }
+            Pdu::ReleaseRq | Pdu::ReleaseRp => vec![0; 4],
+            Pdu::Abort { source, reason } => vec![0, 0, source.as_u8(), reason.as_u8()],
+        };
+
+        let mut output = Vec::with_capacity(body.len() + 6);
+        output.push(self.pdu_type());
+        output.push(0);
+        output.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        output.extend(body);
+        output
+    }
+
+    pub fn decode(pdu_type: u8, body: &[u8]) -> DicomResult<Self> {
+        let mut body = Cursor::new(body);
+        match pdu_type {
+            A_ASSOCIATE_RQ | A_ASSOCIATE_AC => {
+                let protocol_version = body.u16()?;
+                body.take(2)?;
+                let called_ae = ae_title(body.take(16)?);
+                let calling_ae = ae_title(body.take(16)?);
+                body.take(32)?;
+
+                let mut application_context = String::new();
+                let mut requested = Vec::new();
+                let mut accepted = Vec::new();
+                let mut user_information = UserInformation::default();
+
+                while body.remaining() > 0 {
+                    let (item_type, item) = body.item()?;
+                    match item_type {
+                        APPLICATION_CONTEXT_ITEM => application_context = uid(item),
+                        PRESENTATION_CONTEXT_RQ_ITEM if pdu_type == A_ASSOCIATE_RQ => {
+                            requested.push(presentation_context_rq(item)?)
+                        }
+                        PRESENTATION_CONTEXT_AC_ITEM if pdu_type == A_ASSOCIATE_AC => {
+                            accepted.push(presentation_context_ac(item)?)
+                        }
+                        USER_INFORMATION_ITEM => user_information = parse_user_information(item)?,
+                        other => {
+                            return Err(DicomError::InvalidValue(format!(
+                                "Unexpected item {:#04X} in A-ASSOCIATE PDU",
+                                other
+                            )))
+                        }
+                    }
+                }
+
+                Ok(if pdu_type == A_ASSOCIATE_RQ {
+                    Pdu::AssociateRq(AssociateRq {
+                        protocol_version,
+                        called_ae,
+                        calling_ae,
+                        application_context,
+                        presentation_contexts: requested,
+                        user_information,
+                    })
+                } else {
+                    Pdu::AssociateAc(AssociateAc {
+                        protocol_version,
+                        called_ae,
+                        calling_ae,
+                        application_context,
+                        presentation_contexts: accepted,
+                        user_information,
+                    })
+                })
+            }
+            A_ASSOCIATE_RJ => {
+                let fields = body.take(4)?;
+                Ok(Pdu::AssociateRj(AssociateRj {
+                    result: fields[1],
+                    source: fields[2],
+                    reason: fields[3],
+                }))
+            }
+            P_DATA_TF => {
+                let mut pdvs = Vec::new();
+                while body.remaining() > 0 {
+                    let length = body.u32()? as usize;
+                    if length < 2 {
+                        return Err(DicomError::InvalidLength(format!(
+                            "PDV item of {} bytes",
+                            length
+                        )));
+                    }
+                    let item = body.take(length)?;
+                    pdvs.push(Pdv {
+                        context_id: item[0],
+                        is_command: item[1] & 0x01 != 0,
+                        is_last: item[1] & 0x02 != 0,
+                        data: item[2..].to_vec(),
+                    });
+                }
+                Ok(Pdu::PData(pdvs))
+            }
+            A_RELEASE_RQ => Ok(Pdu::ReleaseRq),
+            A_RELEASE_RP => Ok(Pdu::ReleaseRp),
+            A_ABORT => {
+                let fields = body.take(4)?;
+                Ok(Pdu::Abort {
+                    source: AbortSource::from_u8(fields[2]),
+                    reason: AbortReason::from_u8(fields[3]),
+                })
+            }
+            other => Err(DicomError::InvalidValue(format!(
+                "Unrecognized PDU type {:#04X}",
+                other
+            ))),
+        }
+    }
+}
+
+pub fn write_pdu<W: Write>(writer: &mut W, pdu: &Pdu) -> DicomResult<()> {
+    writer.write_all(&pdu.encode())?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Writes a P-DATA-TF PDU carrying a single PDV straight from `data`
+pub fn write_pdv<W: Write>(
+    writer: &mut W,
+    context_id: u8,
+    is_command: bool,
+    is_last: bool,
+    data: &[u8],
+) -> DicomResult<()> {
+    let mut header = [0u8; 12];
+    header[0] = P_DATA_TF;
+    header[2..6].copy_from_slice(&(data.len() as u32 + 6).to_be_bytes());
+    header[6..10].copy_from_slice(&(data.len() as u32 + 2).to_be_bytes());
+    header[10] = context_id;
+    header[11] = is_command as u8 | (is_last as u8) << 1;
+
+    writer.write_all(&header)?;
+    writer.write_all(data)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Reads the PDU header and body, `max_length` bounds P-DATA-TF bodies as
+// negotiated. Read timeouts come back as DicomError::Timeout
+pub fn read_pdu<R: Read>(reader: &mut R, max_length: u32) -> DicomResult<Pdu> {
+    let (pdu_type, length) = read_header(reader, max_length)?;
+    let mut body = vec![0u8; length as usize];
+    read_exact(reader, &mut body)?;
+    Pdu::decode(pdu_type, &body)
+}
+
+// PDU type and body length, checked against the limit for that type
+pub fn read_header<R: Read>(reader: &mut R, max_length: u32) -> DicomResult<(u8, u32)> {
+    let mut header = [0u8; 6];
+    read_exact(reader, &mut header)?;
+    let length = u32::from_be_bytes([header[2], header[3], header[4], header[5]]);
+
+    let limit = length_limit(header[0], max_length);
+    if length > limit {
+        return Err(DicomError::InvalidLength(format!(
+            "PDU of {} bytes exceeds the limit of {}",
+            length, limit
+        )));
+    }
+
+    Ok((header[0], length))
+}
+
+// Item length, presentation context ID and control header of the next PDV
+pub fn read_pdv_header<R: Read>(reader: &mut R) -> DicomResult<(u32, u8, u8)> {
+    let mut header = [0u8; 6];
+    read_exact(reader, &mut header)?;
+    let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    if length < 2 {
+        return Err(DicomError::InvalidLength(format!(
+            "PDV item of {} bytes",
+            length
+        )));
+    }
+
+    Ok((length, header[4], header[5]))
+}
+
+pub fn length_limit(pdu_type: u8, max_length: u32) -> u32 {
+    if max_length == 0 || pdu_type != P_DATA_TF {
+        PDU_LENGTH_LIMIT
+    } else {
+        max_length.min(PDU_LENGTH_LIMIT)
+    }
+}
+
+pub fn read_exact<R: Read>(reader: &mut R, buffer: &mut [u8]) -> DicomResult<()> {
+    reader
+        .read_exact(buffer)
+        .map_err(|error| match error.kind() {
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => {
+                DicomError::Timeout("reading PDU".to_string())
+            }
+            _ => error.into(),
+        })
+}
+
+fn associate_header(
+    protocol_version: u16,
+    called_ae: &str,
+    calling_ae: &str,
+    application_context: &str,
+) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&protocol_version.to_be_bytes());
+    body.extend_from_slice(&[0, 0]);
+    body.extend_from_slice(&pad_ae(called_ae));
+    body.extend_from_slice(&pad_ae(calling_ae));
+    body.extend_from_slice(&[0; 32]);
+    put_item(
+        &mut body,
+        APPLICATION_CONTEXT_ITEM,
+        application_context.as_bytes(),
+    );
+    body
+}
+
+fn put_user_information(body: &mut Vec<u8>, information: &UserInformation) {
+    let mut item = Vec::new();
+    put_item(
+        &mut item,
+        MAX_LENGTH_ITEM,
+        &information.max_pdu_length.to_be_bytes(),
+    );
+    put_item(
+        &mut item,
+        IMPLEMENTATION_CLASS_UID_ITEM,
+        information.implementation_class_uid.as_bytes(),
+    );
+    if let Some(version) = &information.implementation_version_name {
+        put_item(
+            &mut item,
+            IMPLEMENTATION_VERSION_NAME_ITEM,
+            version.as_bytes(),
+        );
+    }
+    for selection in &information.role_selections {
+        let mut value = Vec::new();
+        value.extend_from_slice(&(selection.sop_class_uid.len() as u16).to_be_bytes());
+        value.extend_from_slice(selection.sop_class_uid.as_bytes());
+        value.push(selection.scu_role as u8);
+        value.push(selection.scp_role as u8);
+        put_item(&mut item, ROLE_SELECTION_ITEM, &value);
+    }
+    for negotiation in &information.extended_negotiations {
+        let mut value = Vec::new();
+        value.extend_from_slice(&(negotiation.sop_class_uid.len() as u16).to_be_bytes());
+        value.extend_from_slice(negotiation.sop_class_uid.as_bytes());
+        value.extend_from_slice(&negotiation.service_class_application_information);
+        put_item(&mut item, SOP_CLASS_EXTENDED_NEGOTIATION_ITEM, &value);
+    }
+    put_item(body, USER_INFORMATION_ITEM, &item);
+}
+
+fn put_item(output: &mut Vec<u8>, item_type: u8, value: &[u8]) {
+    output.push(item_type);
+    output.push(0);
+    output.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    output.extend_from_slice(value);
+}
+
+fn pad_ae(title: &str) -> [u8; 16] {
+    let mut padded = [b' '; 16];
+    for (target, byte) in padded.iter_mut().zip(title.bytes()) {
+        *target = byte;
+    }
+    padded
+}
+
+fn ae_title(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).trim().to_string()
+}
+
+fn uid(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches(['\0', ' '])
+        .to_string()
+}
+
+fn presentation_context_rq(item: &[u8]) -> DicomResult<PresentationContextRq> {
+    let mut item = Cursor::new(item);
+    let header = item.take(4)?;
+    let mut context = PresentationContextRq {
+        id: header[0],
+        abstract_syntax: String::new(),
+        transfer_syntaxes: Vec::new(),
+    };
+
+    while item.remaining() > 0 {
+        let (sub_type, value) = item.item()?;
+        match sub_type {
+            ABSTRACT_SYNTAX_ITEM => context.abstract_syntax = uid(value),
+            TRANSFER_SYNTAX_ITEM => context.transfer_syntaxes.push(uid(value)),
+            _ => {}
+        }
+    }
+
+    Ok(context)
+}
+
+fn presentation_context_ac(item: &[u8]) -> DicomResult<PresentationContextAc> {
+    let mut item = Cursor::new(item);
+    let header = item.take(4)?;
+    let mut context = PresentationContextAc {
+        id: header[0],
+        result: PresentationContextResult::from_u8(header[2]),
+        transfer_syntax: String::new(),
+    };
+
+    while item.remaining() > 0 {
+        let (sub_type, value) = item.item()?;
+        if sub_type == TRANSFER_SYNTAX_ITEM {
+            context.transfer_syntax = uid(value);
+        }
+    }
+
+    Ok(context)
+}
+
+// Sub-items we do not know yet are skipped rather than aborting on them
+fn parse_user_information(item: &[u8]) -> DicomResult<UserInformation> {
+    let mut item = Cursor::new(item);
+    let mut information = UserInformation::default();
+
+    while item.remaining() > 0 {
+        let (sub_type, value) = item.item()?;
+        match sub_type {
+            MAX_LENGTH_ITEM if value.len() == 4 => {
+                information.max_pdu_length =
+                    u32::from_be_bytes([value[0], value[1], value[2], value[3]])
+            }
+            IMPLEMENTATION_CLASS_UID_ITEM => information.implementation_class_uid = uid(value),
+            IMPLEMENTATION_VERSION_NAME_ITEM => {
+                information.implementation_version_name = Some(ae_title(value))
+            }
+            ROLE_SELECTION_ITEM => {
+                let mut value = Cursor::new(value);
+                let length = value.u16()? as usize;
+                let sop_class_uid = uid(value.take(length)?);
+                let roles = value.take(2)?;
+                information.role_selections.push(RoleSelection {
+                    sop_class_uid,
+                    scu_role: roles[0] == 1,
+                    scp_role: roles[1] == 1,
+                });
+            }
+            SOP_CLASS_EXTENDED_NEGOTIATION_ITEM => {
+                let mut value = Cursor::new(value);
+                let length = value.u16()? as usize;
+                let sop_class_uid = uid(value.take(length)?);
+                let application_information = value.take(value.remaining())?;
+                information.extended_negotiations.push(ExtendedNegotiation {
+                    sop_class_uid,
+                    service_class_application_information: application_information.to_vec(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(information)
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Cursor { data, position: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    fn take(&mut self, length: usize) -> DicomResult<&'a [u8]> {
+        if length > self.remaining() {
+            return Err(DicomError::InvalidLength(format!(
+                "PDU field of {} bytes at offset {}, {} left",
+                length,
+                self.position,
+                self.remaining()
+            )));
+        }
+        let bytes = &self.data[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> DicomResult<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> DicomResult<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn item(&mut self) -> DicomResult<(u8, &'a [u8])> {
+        let header = self.take(2)?;
+        let length = self.u16()? as usize;
+        Ok((header[0], self.take(length)?))
+    }
+}
@@ -0,0 +1,680 @@
+use std::{
+    fmt::Display,
+    net::ToSocketAddrs,
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{
+    association::{Association, AssociationOptions},
+    dimse::{
+        self, DimseCommand, DimseMessage, MessageIdGenerator, NActionRq, NActionRsp, NCreateRq,
+        NCreateRsp, NDeleteRq, NDeleteRsp, NGetRq, NGetRsp, NResponse, NSetRq, NSetRsp,
+    },
+    status::DimseStatus,
+};
+use crate::core::{
+    dataset::Dataset,
+    element::DicomElement,
+    error::{DicomError, DicomResult},
+    tag::VisualRepresentation,
+    transfer_syntax,
+};
+
+pub const BASIC_GRAYSCALE_PRINT_MANAGEMENT_META_SOP_CLASS: &str = "1.2.840.10008.5.1.1.9";
+pub const BASIC_FILM_SESSION_SOP_CLASS: &str = "1.2.840.10008.5.1.1.1";
+pub const BASIC_FILM_BOX_SOP_CLASS: &str = "1.2.840.10008.5.1.1.2";
+pub const BASIC_GRAYSCALE_IMAGE_BOX_SOP_CLASS: &str = "1.2.840.10008.5.1.1.4";
+pub const PRINT_JOB_SOP_CLASS: &str = "1.2.840.10008.5.1.1.14";
+pub const PRINTER_SOP_CLASS: &str = "1.2.840.10008.5.1.1.16";
+pub const PRINTER_SOP_INSTANCE: &str = "1.2.840.10008.5.1.1.17";
+
+pub const REFERENCED_SOP_CLASS_UID: (u16, u16) = (0x0008, 0x1150);
+pub const REFERENCED_SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x1155);
+pub const SAMPLES_PER_PIXEL: (u16, u16) = (0x0028, 0x0002);
+pub const PHOTOMETRIC_INTERPRETATION: (u16, u16) = (0x0028, 0x0004);
+pub const ROWS: (u16, u16) = (0x0028, 0x0010);
+pub const COLUMNS: (u16, u16) = (0x0028, 0x0011);
+pub const PIXEL_ASPECT_RATIO: (u16, u16) = (0x0028, 0x0034);
+pub const BITS_ALLOCATED: (u16, u16) = (0x0028, 0x0100);
+pub const BITS_STORED: (u16, u16) = (0x0028, 0x0101);
+pub const HIGH_BIT: (u16, u16) = (0x0028, 0x0102);
+pub const PIXEL_REPRESENTATION: (u16, u16) = (0x0028, 0x0103);
+pub const NUMBER_OF_COPIES: (u16, u16) = (0x2000, 0x0010);
+pub const PRINT_PRIORITY: (u16, u16) = (0x2000, 0x0020);
+pub const MEDIUM_TYPE: (u16, u16) = (0x2000, 0x0030);
+pub const FILM_DESTINATION: (u16, u16) = (0x2000, 0x0040);
+pub const FILM_SESSION_LABEL: (u16, u16) = (0x2000, 0x0050);
+pub const IMAGE_DISPLAY_FORMAT: (u16, u16) = (0x2010, 0x0010);
+pub const FILM_ORIENTATION: (u16, u16) = (0x2010, 0x0040);
+pub const FILM_SIZE_ID: (u16, u16) = (0x2010, 0x0050);
+pub const MAGNIFICATION_TYPE: (u16, u16) = (0x2010, 0x0060);
+pub const BORDER_DENSITY: (u16, u16) = (0x2010, 0x0100);
+pub const EMPTY_IMAGE_DENSITY: (u16, u16) = (0x2010, 0x0110);
+pub const REFERENCED_FILM_SESSION_SEQUENCE: (u16, u16) = (0x2010, 0x0500);
+pub const REFERENCED_IMAGE_BOX_SEQUENCE: (u16, u16) = (0x2010, 0x0510);
+pub const IMAGE_BOX_POSITION: (u16, u16) = (0x2020, 0x0010);
+pub const POLARITY: (u16, u16) = (0x2020, 0x0020);
+pub const BASIC_GRAYSCALE_IMAGE_SEQUENCE: (u16, u16) = (0x2020, 0x0110);
+pub const EXECUTION_STATUS: (u16, u16) = (0x2100, 0x0020);
+pub const EXECUTION_STATUS_INFO: (u16, u16) = (0x2100, 0x0030);
+pub const REFERENCED_PRINT_JOB_SEQUENCE: (u16, u16) = (0x2100, 0x0500);
+pub const PRINTER_STATUS: (u16, u16) = (0x2110, 0x0010);
+pub const PRINTER_STATUS_INFO: (u16, u16) = (0x2110, 0x0020);
+pub const PRINTER_NAME: (u16, u16) = (0x2110, 0x0030);
+pub const PIXEL_DATA: (u16, u16) = (0x7FE0, 0x0010);
+
+// N-ACTION type of film session and film box
+const ACTION_PRINT: u16 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilmOrientation {
+    Portrait,
+    Landscape,
+}
+
+impl FilmOrientation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilmOrientation::Portrait => "PORTRAIT",
+            FilmOrientation::Landscape => "LANDSCAPE",
+        }
+    }
+}
+
+// Image Display Format of PS3.3 C.13.8, the layouts printers commonly support
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisplayFormat {
+    // Columns and rows of equally sized image boxes
+    Standard { columns: u16, rows: u16 },
+    // Image boxes per row, top to bottom
+    Row(Vec<u16>),
+    // Image boxes per column, left to right
+    Column(Vec<u16>),
+}
+
+impl DisplayFormat {
+    pub fn image_boxes(&self) -> usize {
+        match self {
+            DisplayFormat::Standard { columns, rows } => *columns as usize * *rows as usize,
+            DisplayFormat::Row(counts) | DisplayFormat::Column(counts) => {
+                counts.iter().map(|count| *count as usize).sum()
+            }
+        }
+    }
+}
+
+impl Display for DisplayFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |counts: &[u16]| {
+            counts
+                .iter()
+                .map(u16::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        match self {
+            DisplayFormat::Standard { columns, rows } => {
+                write!(f, "STANDARD\\{},{}", columns, rows)
+            }
+            DisplayFormat::Row(counts) => write!(f, "ROW\\{}", join(counts)),
+            DisplayFormat::Column(counts) => write!(f, "COL\\{}", join(counts)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FilmSession {
+    pub number_of_copies: u16,
+    // HIGH, MED or LOW
+    pub print_priority: Option<String>,
+    // PAPER, CLEAR FILM, BLUE FILM...
+    pub medium_type: Option<String>,
+    // MAGAZINE or PROCESSOR
+    pub film_destination: Option<String>,
+    pub label: Option<String>,
+}
+
+impl Default for FilmSession {
+    fn default() -> Self {
+        FilmSession {
+            number_of_copies: 1,
+            print_priority: None,
+            medium_type: None,
+            film_destination: None,
+            label: None,
+        }
+    }
+}
+
+impl FilmSession {
+    pub fn to_dataset(&self) -> Dataset {
+        let mut dataset = Dataset::new();
+        dataset.put_string(NUMBER_OF_COPIES, "IS", &self.number_of_copies.to_string());
+        let optional = [
+            (PRINT_PRIORITY, "CS", &self.print_priority),
+            (MEDIUM_TYPE, "CS", &self.medium_type),
+            (FILM_DESTINATION, "CS", &self.film_destination),
+            (FILM_SESSION_LABEL, "LO", &self.label),
+        ];
+        for (tag, vr, value) in optional {
+            if let Some(value) = value {
+                dataset.put_string(tag, vr, value);
+            }
+        }
+        dataset
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FilmBox {
+    pub display_format: DisplayFormat,
+    pub orientation: FilmOrientation,
+    // e.g. 8INX10IN, 14INX17IN, A4
+    pub film_size: Option<String>,
+    // REPLICATE, BILINEAR, CUBIC or NONE
+    pub magnification_type: Option<String>,
+    pub border_density: Option<String>,
+    pub empty_image_density: Option<String>,
+}
+
+impl FilmBox {
+    pub fn new(display_format: DisplayFormat) -> Self {
+        FilmBox {
+            display_format,
+            orientation: FilmOrientation::Portrait,
+            film_size: None,
+            magnification_type: None,
+            border_density: None,
+            empty_image_density: None,
+        }
+    }
+
+    pub fn with_orientation(mut self, orientation: FilmOrientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn with_film_size(mut self, film_size: &str) -> Self {
+        self.film_size = Some(film_size.to_string());
+        self
+    }
+
+    pub fn with_magnification_type(mut self, magnification_type: &str) -> Self {
+        self.magnification_type = Some(magnification_type.to_string());
+        self
+    }
+
+    fn to_dataset(&self, film_session_uid: &str) -> Dataset {
+        let mut session = Dataset::new();
+        session.put_string(REFERENCED_SOP_CLASS_UID, "UI", BASIC_FILM_SESSION_SOP_CLASS);
+        session.put_string(REFERENCED_SOP_INSTANCE_UID, "UI", film_session_uid);
+
+        let mut dataset = Dataset::new();
+        dataset.put_string(IMAGE_DISPLAY_FORMAT, "ST", &self.display_format.to_string());
+        dataset.put_string(FILM_ORIENTATION, "CS", self.orientation.as_str());
+        let optional = [
+            (FILM_SIZE_ID, "CS", &self.film_size),
+            (MAGNIFICATION_TYPE, "CS", &self.magnification_type),
+            (BORDER_DENSITY, "CS", &self.border_density),
+            (EMPTY_IMAGE_DENSITY, "CS", &self.empty_image_density),
+        ];
+        for (tag, vr, value) in optional {
+            if let Some(value) = value {
+                dataset.put_string(tag, vr, value);
+            }
+        }
+        dataset.put(Rc::new(DicomElement::sequence(
+            REFERENCED_FILM_SESSION_SEQUENCE,
+            vec![session],
+        )));
+        dataset
+    }
+}
+
+// Preformatted grayscale image of an image box, printers take 8 or 12 bits
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrayscaleImage {
+    pub rows: u16,
+    pub columns: u16,
+    pub bits_stored: u16,
+    pub monochrome1: bool,
+    pub pixels: Vec<u16>,
+}
+
+impl GrayscaleImage {
+    pub fn new(rows: u16, columns: u16, bits_stored: u16, pixels: Vec<u16>) -> DicomResult<Self> {
+        if bits_stored != 8 && bits_stored != 12 {
+            return Err(DicomError::InvalidValue(format!(
+                "Printers take 8 or 12 bits stored, not {}",
+                bits_stored
+            )));
+        }
+        if pixels.len() != rows as usize * columns as usize {
+            return Err(DicomError::InvalidLength(format!(
+                "{} pixels for a {}x{} image",
+                pixels.len(),
+                columns,
+                rows
+            )));
+        }
+
+        Ok(GrayscaleImage {
+            rows,
+            columns,
+            bits_stored,
+            monochrome1: false,
+            pixels,
+        })
+    }
+
+    // Uncompressed single frame grayscale, deeper images are scaled down to 12 bits
+    pub fn from_dataset(dataset: &Dataset) -> DicomResult<Self> {
+        let number = |tag: (u16, u16)| match dataset.value(tag) {
+            Some(VisualRepresentation::US(value)) => Some(value),
+            Some(other) => other.to_string().trim().parse().ok(),
+            None => None,
+        };
+        let missing = |name: &str| DicomError::InvalidDataset(format!("Image lacks {}", name));
+
+        let rows = number(ROWS).ok_or_else(|| missing("rows"))?;
+        let columns = number(COLUMNS).ok_or_else(|| missing("columns"))?;
+        let bits_allocated = number(BITS_ALLOCATED).unwrap_or(16);
+        let bits_stored = number(BITS_STORED).unwrap_or(bits_allocated);
+        if number(SAMPLES_PER_PIXEL).unwrap_or(1) != 1 {
+            return Err(DicomError::InvalidValue(
+                "Only grayscale images can be printed on a grayscale film box".to_string(),
+            ));
+        }
+
+        let count = rows as usize * columns as usize;
+        let mut pixels: Vec<u16> = match dataset.value(PIXEL_DATA) {
+            Some(VisualRepresentation::OW(words)) if bits_allocated == 16 => words,
+            Some(VisualRepresentation::OB(bytes)) if bits_allocated == 8 => {
+                bytes.into_iter().map(u16::from).collect()
+            }
+            Some(VisualRepresentation::OB(bytes)) if bits_allocated == 16 => bytes
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect(),
+            Some(_) => {
+                return Err(DicomError::InvalidValue(format!(
+                    "Pixel data with {} bits allocated",
+                    bits_allocated
+                )))
+            }
+            None => return Err(missing("pixel data")),
+        };
+        pixels.truncate(count);
+
+        let target = if bits_stored <= 8 { 8 } else { 12 };
+        let mask = if bits_stored >= 16 {
+            u16::MAX
+        } else {
+            (1 << bits_stored) - 1
+        };
+        for pixel in pixels.iter_mut() {
+            *pixel = (*pixel & mask) >> bits_stored.saturating_sub(target);
+        }
+
+        let mut image = GrayscaleImage::new(rows, columns, target, pixels)?;
+        image.monochrome1 = dataset
+            .string(PHOTOMETRIC_INTERPRETATION)
+            .is_some_and(|value| value == "MONOCHROME1");
+        Ok(image)
+    }
+
+    fn to_dataset(&self) -> Dataset {
+        let mut dataset = Dataset::new();
+        let photometric = if self.monochrome1 {
+            "MONOCHROME1"
+        } else {
+            "MONOCHROME2"
+        };
+        let bits_allocated = if self.bits_stored == 8 { 8 } else { 16 };
+
+        dataset.put(Rc::new(DicomElement::new(
+            SAMPLES_PER_PIXEL,
+            VisualRepresentation::US(1),
+        )));
+        dataset.put_string(PHOTOMETRIC_INTERPRETATION, "CS", photometric);
+        dataset.put(Rc::new(DicomElement::new(
+            ROWS,
+            VisualRepresentation::US(self.rows),
+        )));
+        dataset.put(Rc::new(DicomElement::new(
+            COLUMNS,
+            VisualRepresentation::US(self.columns),
+        )));
+        dataset.put_string(PIXEL_ASPECT_RATIO, "IS", "1\\1");
+        dataset.put(Rc::new(DicomElement::new(
+            BITS_ALLOCATED,
+            VisualRepresentation::US(bits_allocated),
+        )));
+        dataset.put(Rc::new(DicomElement::new(
+            BITS_STORED,
+            VisualRepresentation::US(self.bits_stored),
+        )));
+        dataset.put(Rc::new(DicomElement::new(
+            HIGH_BIT,
+            VisualRepresentation::US(self.bits_stored - 1),
+        )));
+        dataset.put(Rc::new(DicomElement::new(
+            PIXEL_REPRESENTATION,
+            VisualRepresentation::US(0),
+        )));
+
+        let pixel_data = if bits_allocated == 8 {
+            VisualRepresentation::OB(self.pixels.iter().map(|pixel| *pixel as u8).collect())
+        } else {
+            VisualRepresentation::OW(self.pixels.clone())
+        };
+        dataset.put(Rc::new(DicomElement::new(PIXEL_DATA, pixel_data)));
+        dataset
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionStatus {
+    Pending,
+    Printing,
+    Done,
+    Failure,
+}
+
+impl ExecutionStatus {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "PENDING" => Some(ExecutionStatus::Pending),
+            "PRINTING" => Some(ExecutionStatus::Printing),
+            "DONE" => Some(ExecutionStatus::Done),
+            "FAILURE" => Some(ExecutionStatus::Failure),
+            _ => None,
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        matches!(self, ExecutionStatus::Done | ExecutionStatus::Failure)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintJobStatus {
+    pub status: ExecutionStatus,
+    // Defined terms of PS3.3 C.13.9.1, e.g. NO SUPPLY or FILM JAM
+    pub info: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrinterStatus {
+    // NORMAL, WARNING or FAILURE
+    pub status: String,
+    pub info: Option<String>,
+    pub name: Option<String>,
+}
+
+// Basic Grayscale Print Management SCU, one film session per association
+pub struct PrintScu {
+    association: Association,
+    context_id: u8,
+    message_ids: MessageIdGenerator,
+    film_session: Option<String>,
+}
+
+impl PrintScu {
+    // Proposes the meta SOP class on top of whatever `options` holds
+    pub fn connect<A: ToSocketAddrs>(address: A, options: AssociationOptions) -> DicomResult<Self> {
+        let options = options.with_presentation_context(
+            BASIC_GRAYSCALE_PRINT_MANAGEMENT_META_SOP_CLASS,
+            &[
+                transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN,
+                transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN,
+            ],
+        );
+        PrintScu::new(Association::request(address, options)?)
+    }
+
+    pub fn new(association: Association) -> DicomResult<Self> {
+        let context_id = association
+            .context_for(BASIC_GRAYSCALE_PRINT_MANAGEMENT_META_SOP_CLASS)
+            .map(|context| context.id)
+            .ok_or_else(|| {
+                DicomError::InvalidValue(
+                    "Basic Grayscale Print Management was not accepted".to_string(),
+                )
+            })?;
+
+        Ok(PrintScu {
+            association,
+            context_id,
+            message_ids: MessageIdGenerator::new(),
+            film_session: None,
+        })
+    }
+
+    pub fn printer_status(&mut self) -> DicomResult<PrinterStatus> {
+        let dataset = self.get(
+            PRINTER_SOP_CLASS,
+            PRINTER_SOP_INSTANCE,
+            vec![PRINTER_STATUS, PRINTER_STATUS_INFO, PRINTER_NAME],
+        )?;
+
+        Ok(PrinterStatus {
+            status: dataset.string(PRINTER_STATUS).unwrap_or_default(),
+            info: dataset.string(PRINTER_STATUS_INFO),
+            name: dataset.string(PRINTER_NAME),
+        })
+    }
+
+    // Returns the SOP instance UID the printer assigned to the session
+    pub fn create_film_session(&mut self, session: &FilmSession) -> DicomResult<String> {
+        let (response, _) = self.create(BASIC_FILM_SESSION_SOP_CLASS, session.to_dataset())?;
+        self.film_session = Some(response.affected_sop_instance_uid.clone());
+        Ok(response.affected_sop_instance_uid)
+    }
+
+    // Prints `images` on one film, in image box order, and returns the print
+    // job UID when the printer supports print jobs
+    pub fn print_film(
+        &mut self,
+        film: &FilmBox,
+        images: &[GrayscaleImage],
+    ) -> DicomResult<Option<String>> {
+        let film_session = self.film_session.clone().ok_or_else(|| {
+            DicomError::InvalidValue("Create a film session before printing".to_string())
+        })?;
+        if images.len() > film.display_format.image_boxes() {
+            return Err(DicomError::InvalidValue(format!(
+                "{} images for {} image boxes",
+                images.len(),
+                film.display_format.image_boxes()
+            )));
+        }
+
+        let (response, dataset) =
+            self.create(BASIC_FILM_BOX_SOP_CLASS, film.to_dataset(&film_session))?;
+        let film_box = response.affected_sop_instance_uid;
+        let image_boxes: Vec<String> = dataset
+            .map(|dataset| {
+                dataset
+                    .sequence(REFERENCED_IMAGE_BOX_SEQUENCE)
+                    .iter()
+                    .filter_map(|item| item.string(REFERENCED_SOP_INSTANCE_UID))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if image_boxes.len() < images.len() {
+            return Err(DicomError::InvalidDataset(format!(
+                "Printer created {} image boxes for {} images",
+                image_boxes.len(),
+                images.len()
+            )));
+        }
+
+        for (position, (image_box, image)) in image_boxes.iter().zip(images).enumerate() {
+            let mut dataset = Dataset::new();
+            dataset.put(Rc::new(DicomElement::new(
+                IMAGE_BOX_POSITION,
+                VisualRepresentation::US(position as u16 + 1),
+            )));
+            dataset.put(Rc::new(DicomElement::sequence(
+                BASIC_GRAYSCALE_IMAGE_SEQUENCE,
+                vec![image.to_dataset()],
+            )));
+            self.set(BASIC_GRAYSCALE_IMAGE_BOX_SOP_CLASS, image_box, dataset)?;
+        }
+
+        let dataset = self.action(BASIC_FILM_BOX_SOP_CLASS, &film_box, ACTION_PRINT)?;
+        let print_job = dataset.and_then(|dataset| {
+            dataset
+                .sequence(REFERENCED_PRINT_JOB_SEQUENCE)
+                .first()
+                .and_then(|item| item.string(REFERENCED_SOP_INSTANCE_UID))
+        });
+
+        self.delete(BASIC_FILM_BOX_SOP_CLASS, &film_box)?;
+        Ok(print_job)
+    }
+
+    pub fn print_job_status(&mut self, print_job: &str) -> DicomResult<PrintJobStatus> {
+        let dataset = self.get(
+            PRINT_JOB_SOP_CLASS,
+            print_job,
+            vec![EXECUTION_STATUS, EXECUTION_STATUS_INFO],
+        )?;
+        let status = dataset.string(EXECUTION_STATUS).unwrap_or_default();
+
+        Ok(PrintJobStatus {
+            status: ExecutionStatus::parse(&status)
+                .ok_or_else(|| DicomError::InvalidValue(format!("Execution status {}", status)))?,
+            info: dataset.string(EXECUTION_STATUS_INFO),
+        })
+    }
+
+    // Polls the print job until it is done or failed
+    pub fn wait_for_job(
+        &mut self,
+        print_job: &str,
+        interval: Duration,
+        timeout: Duration,
+    ) -> DicomResult<PrintJobStatus> {
+        let started = Instant::now();
+        loop {
+            let status = self.print_job_status(print_job)?;
+            if status.status.is_final() {
+                return Ok(status);
+            }
+            if started.elapsed() >= timeout {
+                return Err(DicomError::Timeout(format!(
+                    "print job {} still {:?}",
+                    print_job, status.status
+                )));
+            }
+            thread::sleep(interval);
+        }
+    }
+
+    // Deletes the film session and releases the association
+    pub fn finish(mut self) -> DicomResult<()> {
+        if let Some(film_session) = self.film_session.take() {
+            self.delete(BASIC_FILM_SESSION_SOP_CLASS, &film_session)?;
+        }
+        self.association.release()
+    }
+
+    fn create(
+        &mut self,
+        sop_class_uid: &str,
+        dataset: Dataset,
+    ) -> DicomResult<(NResponse, Option<Dataset>)> {
+        let request = NCreateRq {
+            message_id: self.message_ids.next_id(),
+            affected_sop_class_uid: sop_class_uid.to_string(),
+            affected_sop_instance_uid: None,
+            has_attribute_list: true,
+        };
+        let (response, dataset) = self.request::<_, NCreateRsp>(&request, Some(dataset))?;
+        Ok((response.0, dataset))
+    }
+
+    fn set(
+        &mut self,
+        sop_class_uid: &str,
+        sop_instance_uid: &str,
+        dataset: Dataset,
+    ) -> DicomResult<()> {
+        let request = NSetRq {
+            message_id: self.message_ids.next_id(),
+            requested_sop_class_uid: sop_class_uid.to_string(),
+            requested_sop_instance_uid: sop_instance_uid.to_string(),
+        };
+        self.request::<_, NSetRsp>(&request, Some(dataset))?;
+        Ok(())
+    }
+
+    fn get(
+        &mut self,
+        sop_class_uid: &str,
+        sop_instance_uid: &str,
+        attributes: Vec<(u16, u16)>,
+    ) -> DicomResult<Dataset> {
+        let request = NGetRq {
+            message_id: self.message_ids.next_id(),
+            requested_sop_class_uid: sop_class_uid.to_string(),
+            requested_sop_instance_uid: sop_instance_uid.to_string(),
+            attribute_identifier_list: attributes,
+        };
+        let (_, dataset) = self.request::<_, NGetRsp>(&request, None)?;
+        Ok(dataset.unwrap_or_else(Dataset::new))
+    }
+
+    fn action(
+        &mut self,
+        sop_class_uid: &str,
+        sop_instance_uid: &str,
+        action_type_id: u16,
+    ) -> DicomResult<Option<Dataset>> {
+        let request = NActionRq {
+            message_id: self.message_ids.next_id(),
+            requested_sop_class_uid: sop_class_uid.to_string(),
+            requested_sop_instance_uid: sop_instance_uid.to_string(),
+            action_type_id,
+            has_action_information: false,
+        };
+        let (_, dataset) = self.request::<_, NActionRsp>(&request, None)?;
+        Ok(dataset)
+    }
+
+    fn delete(&mut self, sop_class_uid: &str, sop_instance_uid: &str) -> DicomResult<()> {
+        let request = NDeleteRq {
+            message_id: self.message_ids.next_id(),
+            requested_sop_class_uid: sop_class_uid.to_string(),
+            requested_sop_instance_uid: sop_instance_uid.to_string(),
+        };
+        self.request::<_, NDeleteRsp>(&request, None)?;
+        Ok(())
+    }
+
+    // Warnings pass, printers use them for e.g. a downgraded film size
+    fn request<Rq: DimseCommand, Rsp: DimseCommand>(
+        &mut self,
+        request: &Rq,
+        dataset: Option<Dataset>,
+    ) -> DicomResult<(Rsp, Option<Dataset>)> {
+        let message = DimseMessage::new(request, dataset);
+        self.association.send_message(self.context_id, &message)?;
+
+        let (_, response) = self.association.receive_response(&message.command)?;
+        let status = DimseStatus::from_u16(response.command.u16(dimse::STATUS).unwrap_or_default());
+        if !(status.is_success() || status.is_warning()) {
+            return Err(DicomError::InvalidValue(format!(
+                "{} failed: {}",
+                dimse::command_name(message.command.command_field()),
+                status
+            )));
+        }
+
+        Ok((Rsp::from_command(&response.command)?, response.data_set))
+    }
+}
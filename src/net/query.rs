@@ -0,0 +1,184 @@
+use super::relational::{check_query, QueryLevel, QUERY_RETRIEVE_LEVEL};
+use crate::core::{
+    dataset::Dataset,
+    dictionary,
+    error::{DicomError, DicomResult},
+};
+
+const PATIENT_NAME: (u16, u16) = (0x0010, 0x0010);
+const PATIENT_ID: (u16, u16) = (0x0010, 0x0020);
+const STUDY_INSTANCE_UID: (u16, u16) = (0x0020, 0x000D);
+const SERIES_INSTANCE_UID: (u16, u16) = (0x0020, 0x000E);
+
+// Required and unique keys of each level, PS3.4 C.6.1.1 and C.6.2.1. SCPs
+// must return these, so asking for them costs nothing and spares a second
+// query for the UIDs to retrieve with
+pub fn required_keys(level: QueryLevel, root: QueryLevel) -> Vec<(u16, u16)> {
+    match level {
+        QueryLevel::Patient => vec![PATIENT_NAME, PATIENT_ID],
+        QueryLevel::Study => {
+            let mut keys = vec![
+                (0x0008, 0x0020),
+                (0x0008, 0x0030),
+                (0x0008, 0x0050),
+                (0x0020, 0x0010),
+                STUDY_INSTANCE_UID,
+            ];
+            // The Study Root model has no patient level, its study level
+            // carries the patient's keys
+            if root == QueryLevel::Study {
+                keys.extend([PATIENT_NAME, PATIENT_ID]);
+            }
+            keys
+        }
+        QueryLevel::Series => vec![(0x0008, 0x0060), (0x0020, 0x0011), SERIES_INSTANCE_UID],
+        // SOP Class UID is optional, but a retrieve needs it to negotiate
+        QueryLevel::Image => vec![(0x0008, 0x0016), (0x0008, 0x0018), (0x0020, 0x0013)],
+    }
+}
+
+// C-FIND identifier or QIDO-RS search built from the level and the fields
+// asked for. The level's required keys are added as empty return keys, and
+// a hierarchical query that lacks the unique key of a level above fails
+// here instead of coming back empty from the SCP
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryBuilder {
+    pub level: QueryLevel,
+    pub root: QueryLevel,
+    // Keys with a value to match
+    pub matching: Vec<((u16, u16), String)>,
+    // Keys sent empty, to be returned
+    pub return_keys: Vec<(u16, u16)>,
+}
+
+impl QueryBuilder {
+    // Study Root unless the level only exists in Patient Root
+    pub fn new(level: QueryLevel) -> Self {
+        QueryBuilder {
+            level,
+            root: level.min(QueryLevel::Study),
+            matching: Vec::new(),
+            return_keys: Vec::new(),
+        }
+    }
+
+    pub fn with_root(mut self, root: QueryLevel) -> Self {
+        self.root = root;
+        self
+    }
+
+    pub fn with_key(mut self, tag: (u16, u16), value: &str) -> Self {
+        self.matching.retain(|(key, _)| *key != tag);
+        self.matching.push((tag, value.to_string()));
+        self
+    }
+
+    pub fn with_patient_id(self, patient_id: &str) -> Self {
+        self.with_key(PATIENT_ID, patient_id)
+    }
+
+    pub fn with_study(self, study_uid: &str) -> Self {
+        self.with_key(STUDY_INSTANCE_UID, study_uid)
+    }
+
+    pub fn with_series(self, series_uid: &str) -> Self {
+        self.with_key(SERIES_INSTANCE_UID, series_uid)
+    }
+
+    pub fn with_return_key(mut self, tag: (u16, u16)) -> Self {
+        if !self.return_keys.contains(&tag) {
+            self.return_keys.push(tag);
+        }
+        self
+    }
+
+    // Keyword such as StudyDescription, or a tag such as 0008103E
+    pub fn with_return_field(self, field: &str) -> DicomResult<Self> {
+        Ok(self.with_return_key(dictionary::parse_tag(field)?))
+    }
+
+    pub fn value(&self, tag: (u16, u16)) -> Option<&str> {
+        self.matching
+            .iter()
+            .find(|(key, _)| *key == tag)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Identifier of a hierarchical C-FIND
+    pub fn build(&self) -> DicomResult<Dataset> {
+        let mut level = self.root;
+        while level < self.level {
+            let key = level.unique_key();
+            if self.value(key).is_none_or(str::is_empty) {
+                return Err(DicomError::InvalidValue(format!(
+                    "{} level queries need the unique key ({:04X},{:04X}) of the {} level",
+                    self.level.code(),
+                    key.0,
+                    key.1,
+                    level.code()
+                )));
+            }
+            level = level.below().unwrap_or(self.level);
+        }
+
+        let mut query = Dataset::new();
+        query.put_string(QUERY_RETRIEVE_LEVEL, "CS", self.level.code());
+        for tag in required_keys(self.level, self.root)
+            .into_iter()
+            .chain(self.return_keys.iter().copied())
+        {
+            query.set_empty(tag);
+        }
+        for (tag, value) in &self.matching {
+            query.put_string(*tag, dictionary::vr_of(*tag), value);
+        }
+        check_query(&query, self.root, false)?;
+        Ok(query)
+    }
+
+    // Resource path and parameters of a QIDO-RS search. QIDO has no patient
+    // level and needs no UIDs above the level, the ones given narrow the
+    // path
+    pub fn to_qido(&self) -> DicomResult<(String, Vec<(String, String)>)> {
+        let study = self.value(STUDY_INSTANCE_UID).filter(|uid| !uid.is_empty());
+        let series = self
+            .value(SERIES_INSTANCE_UID)
+            .filter(|uid| !uid.is_empty());
+        let (path, in_path): (String, &[(u16, u16)]) = match (self.level, study, series) {
+            (QueryLevel::Patient, _, _) => {
+                return Err(DicomError::InvalidValue(
+                    "QIDO-RS has no patient level".to_string(),
+                ))
+            }
+            (QueryLevel::Study, _, _) => ("studies".to_string(), &[]),
+            (QueryLevel::Series, Some(study), _) => {
+                (format!("studies/{}/series", study), &[STUDY_INSTANCE_UID])
+            }
+            (QueryLevel::Series, None, _) => ("series".to_string(), &[]),
+            (QueryLevel::Image, Some(study), Some(series)) => (
+                format!("studies/{}/series/{}/instances", study, series),
+                &[STUDY_INSTANCE_UID, SERIES_INSTANCE_UID],
+            ),
+            (QueryLevel::Image, Some(study), None) => (
+                format!("studies/{}/instances", study),
+                &[STUDY_INSTANCE_UID],
+            ),
+            (QueryLevel::Image, None, _) => ("instances".to_string(), &[]),
+        };
+
+        let hex = |tag: (u16, u16)| format!("{:04X}{:04X}", tag.0, tag.1);
+        let mut parameters: Vec<(String, String)> = self
+            .matching
+            .iter()
+            .filter(|(tag, _)| !in_path.contains(tag))
+            .map(|(tag, value)| (hex(*tag), value.clone()))
+            .collect();
+        for tag in required_keys(self.level, self.root)
+            .into_iter()
+            .chain(self.return_keys.iter().copied())
+        {
+            parameters.push(("includefield".to_string(), hex(tag)));
+        }
+        Ok((path, parameters))
+    }
+}
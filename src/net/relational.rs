@@ -0,0 +1,349 @@
+use std::collections::BTreeMap;
+
+use super::{association::Association, matching::match_keys};
+use crate::core::{
+    dataset::Dataset,
+    error::{DicomError, DicomResult},
+};
+
+pub const PATIENT_ROOT_FIND_SOP_CLASS: &str = "1.2.840.10008.5.1.4.1.2.1.1";
+pub const STUDY_ROOT_FIND_SOP_CLASS: &str = "1.2.840.10008.5.1.4.1.2.2.1";
+
+pub const QUERY_RETRIEVE_LEVEL: (u16, u16) = (0x0008, 0x0052);
+
+// Keys of each level of the Query/Retrieve information model, PS3.4 C.6.
+// Patient keys belong to the study in the Study Root model
+const PATIENT_KEYS: &[(u16, u16)] = &[
+    (0x0010, 0x0010),
+    (0x0010, 0x0020),
+    (0x0010, 0x0021),
+    (0x0010, 0x0030),
+    (0x0010, 0x0040),
+    (0x0020, 0x1200),
+    (0x0020, 0x1202),
+    (0x0020, 0x1204),
+];
+const STUDY_KEYS: &[(u16, u16)] = &[
+    (0x0008, 0x0020),
+    (0x0008, 0x0030),
+    (0x0008, 0x0050),
+    (0x0008, 0x0061),
+    (0x0008, 0x0062),
+    (0x0008, 0x0090),
+    (0x0008, 0x1030),
+    (0x0020, 0x000D),
+    (0x0020, 0x0010),
+    (0x0020, 0x1206),
+    (0x0020, 0x1208),
+];
+const SERIES_KEYS: &[(u16, u16)] = &[
+    (0x0008, 0x0021),
+    (0x0008, 0x0031),
+    (0x0008, 0x0060),
+    (0x0008, 0x103E),
+    (0x0018, 0x0015),
+    (0x0020, 0x000E),
+    (0x0020, 0x0011),
+    (0x0020, 0x1209),
+];
+const IMAGE_KEYS: &[(u16, u16)] = &[
+    (0x0008, 0x0016),
+    (0x0008, 0x0018),
+    (0x0008, 0x0023),
+    (0x0008, 0x0033),
+    (0x0020, 0x0013),
+];
+
+// The one key of each level a hierarchical query may carry above its level
+const UNIQUE_KEYS: &[(u16, u16)] = &[
+    (0x0010, 0x0020),
+    (0x0020, 0x000D),
+    (0x0020, 0x000E),
+    (0x0008, 0x0018),
+];
+
+// Query/Retrieve extended negotiation of PS3.4 C.5.1.1.1, what a C-FIND SCP
+// supports beyond hierarchical queries with exact keys
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryExtensions {
+    pub relational_queries: bool,
+    pub combined_datetime_matching: bool,
+    pub fuzzy_semantic_matching: bool,
+    pub timezone_adjustment: bool,
+}
+
+impl QueryExtensions {
+    pub fn new() -> Self {
+        QueryExtensions::default()
+    }
+
+    pub fn with_relational_queries(mut self, relational_queries: bool) -> Self {
+        self.relational_queries = relational_queries;
+        self
+    }
+
+    pub fn with_combined_datetime_matching(mut self, combined: bool) -> Self {
+        self.combined_datetime_matching = combined;
+        self
+    }
+
+    pub fn with_fuzzy_semantic_matching(mut self, fuzzy: bool) -> Self {
+        self.fuzzy_semantic_matching = fuzzy;
+        self
+    }
+
+    pub fn with_timezone_adjustment(mut self, adjustment: bool) -> Self {
+        self.timezone_adjustment = adjustment;
+        self
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        vec![
+            self.relational_queries as u8,
+            self.combined_datetime_matching as u8,
+            self.fuzzy_semantic_matching as u8,
+            self.timezone_adjustment as u8,
+        ]
+    }
+
+    // Bytes a peer left out are not supported
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let flag = |index: usize| bytes.get(index) == Some(&1);
+        QueryExtensions {
+            relational_queries: flag(0),
+            combined_datetime_matching: flag(1),
+            fuzzy_semantic_matching: flag(2),
+            timezone_adjustment: flag(3),
+        }
+    }
+
+    // What the association agreed on for the SOP class, nothing without
+    // extended negotiation
+    pub fn negotiated(association: &Association, sop_class_uid: &str) -> Self {
+        association
+            .extended_negotiation(sop_class_uid)
+            .map(QueryExtensions::from_bytes)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum QueryLevel {
+    Patient,
+    Study,
+    Series,
+    Image,
+}
+
+impl QueryLevel {
+    pub fn from_code(code: &str) -> DicomResult<Self> {
+        match code.trim_end_matches(['\0', ' ']).trim() {
+            "PATIENT" => Ok(QueryLevel::Patient),
+            "STUDY" => Ok(QueryLevel::Study),
+            "SERIES" => Ok(QueryLevel::Series),
+            "IMAGE" => Ok(QueryLevel::Image),
+            other => Err(DicomError::InvalidValue(format!(
+                "Unknown query level {}",
+                other
+            ))),
+        }
+    }
+
+    pub fn from_query(query: &Dataset) -> DicomResult<Self> {
+        let code = query.string(QUERY_RETRIEVE_LEVEL).ok_or_else(|| {
+            DicomError::InvalidDataset("Query lacks its Query/Retrieve Level".to_string())
+        })?;
+        QueryLevel::from_code(&code)
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            QueryLevel::Patient => "PATIENT",
+            QueryLevel::Study => "STUDY",
+            QueryLevel::Series => "SERIES",
+            QueryLevel::Image => "IMAGE",
+        }
+    }
+
+    // Key identifying the entities of the level, in UNIQUE_KEYS order
+    pub fn unique_key(&self) -> (u16, u16) {
+        UNIQUE_KEYS[*self as usize]
+    }
+
+    pub fn below(&self) -> Option<QueryLevel> {
+        match self {
+            QueryLevel::Patient => Some(QueryLevel::Study),
+            QueryLevel::Study => Some(QueryLevel::Series),
+            QueryLevel::Series => Some(QueryLevel::Image),
+            QueryLevel::Image => None,
+        }
+    }
+
+    // Level a key belongs to, None for keys the model does not place
+    pub fn of(tag: (u16, u16), root: QueryLevel) -> Option<QueryLevel> {
+        let level = [
+            (QueryLevel::Patient, PATIENT_KEYS),
+            (QueryLevel::Study, STUDY_KEYS),
+            (QueryLevel::Series, SERIES_KEYS),
+            (QueryLevel::Image, IMAGE_KEYS),
+        ]
+        .into_iter()
+        .find(|(_, keys)| keys.contains(&tag))
+        .map(|(level, _)| level)?;
+        Some(level.max(root))
+    }
+}
+
+// A patient, study, series or instance of the model with the entities below
+// it, what an SCP queries against
+#[derive(Debug, Clone)]
+pub struct Entity {
+    pub attributes: Dataset,
+    pub children: Vec<Entity>,
+}
+
+impl Entity {
+    pub fn new(attributes: Dataset) -> Self {
+        Entity {
+            attributes,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_child(mut self, child: Entity) -> Self {
+        self.children.push(child);
+        self
+    }
+}
+
+// The keys of a query by the level they apply to, keys the model does not
+// place applying at the query level
+fn keys_by_level(
+    query: &Dataset,
+    level: QueryLevel,
+    root: QueryLevel,
+) -> BTreeMap<QueryLevel, Dataset> {
+    let mut keys: BTreeMap<QueryLevel, Dataset> = BTreeMap::new();
+    for key in query {
+        if key.tag() == QUERY_RETRIEVE_LEVEL {
+            continue;
+        }
+        let key_level = QueryLevel::of(key.tag(), root).unwrap_or(level);
+        keys.entry(key_level)
+            .or_insert_with(Dataset::new)
+            .put(key.clone());
+    }
+    keys
+}
+
+// Hierarchical queries carry keys of their own level and unique keys of the
+// levels above only. Relational queries, PS3.4 C.4.1.3.2, may carry keys of
+// any level
+pub fn check_query(query: &Dataset, root: QueryLevel, relational: bool) -> DicomResult<QueryLevel> {
+    let level = QueryLevel::from_query(query)?;
+    if level < root {
+        return Err(DicomError::InvalidValue(format!(
+            "{} level queries are not part of the model",
+            level.code()
+        )));
+    }
+    if relational {
+        return Ok(level);
+    }
+
+    for (key_level, keys) in keys_by_level(query, level, root) {
+        for key in &keys {
+            let tag = key.tag();
+            if key_level > level || (key_level < level && !UNIQUE_KEYS.contains(&tag)) {
+                return Err(DicomError::InvalidValue(format!(
+                    "({:04X},{:04X}) cannot be a key of a hierarchical {} level query",
+                    tag.0,
+                    tag.1,
+                    level.code()
+                )));
+            }
+        }
+    }
+    Ok(level)
+}
+
+// Responses to a query over the entities of the model, one per entity of the
+// query level. Keys above that level match its ancestors, keys below it any
+// of its descendants, whose values the response carries from the first one
+// matching
+pub fn find(
+    query: &Dataset,
+    roots: &[Entity],
+    root: QueryLevel,
+    relational: bool,
+) -> DicomResult<Vec<Dataset>> {
+    let level = check_query(query, root, relational)?;
+    let keys = keys_by_level(query, level, root);
+    let mut responses = Vec::new();
+    collect(roots, root, level, &keys, &Dataset::new(), &mut responses);
+    for response in &mut responses {
+        response.put_string(QUERY_RETRIEVE_LEVEL, "CS", level.code());
+    }
+    Ok(responses)
+}
+
+fn collect(
+    entities: &[Entity],
+    current: QueryLevel,
+    level: QueryLevel,
+    keys: &BTreeMap<QueryLevel, Dataset>,
+    ancestors: &Dataset,
+    responses: &mut Vec<Dataset>,
+) {
+    let empty = Dataset::new();
+    let own = keys.get(&current).unwrap_or(&empty);
+    for entity in entities {
+        let Some(matched) = match_keys(own, &entity.attributes) else {
+            continue;
+        };
+        let mut response = ancestors.clone();
+        merge(&mut response, &matched);
+
+        if current == level {
+            let Some(below) = current.below().map_or(Some(Dataset::new()), |below| {
+                match_descendants(&entity.children, below, keys)
+            }) else {
+                continue;
+            };
+            merge(&mut response, &below);
+            responses.push(response);
+        } else if let Some(below) = current.below() {
+            collect(&entity.children, below, level, keys, &response, responses);
+        }
+    }
+}
+
+// Keys of `current` and the levels below matched against the first
+// descendants that match them all
+fn match_descendants(
+    entities: &[Entity],
+    current: QueryLevel,
+    keys: &BTreeMap<QueryLevel, Dataset>,
+) -> Option<Dataset> {
+    if keys.range(current..).next().is_none() {
+        return Some(Dataset::new());
+    }
+
+    let empty = Dataset::new();
+    let own = keys.get(&current).unwrap_or(&empty);
+    entities.iter().find_map(|entity| {
+        let mut matched = match_keys(own, &entity.attributes)?;
+        let below = match current.below() {
+            Some(below) => match_descendants(&entity.children, below, keys)?,
+            None => Dataset::new(),
+        };
+        merge(&mut matched, &below);
+        Some(matched)
+    })
+}
+
+fn merge(dataset: &mut Dataset, other: &Dataset) {
+    for element in other {
+        dataset.put(element.clone());
+    }
+}
@@ -0,0 +1,181 @@
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use super::matching;
+use crate::core::{dataset::Dataset, dictionary};
+
+pub const STUDY_INSTANCE_UID: (u16, u16) = (0x0020, 0x000D);
+pub const SERIES_INSTANCE_UID: (u16, u16) = (0x0020, 0x000E);
+pub const SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x0018);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Order {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+// Identifiers of a C-FIND or QIDO-RS search, for the sorting, filtering and
+// counting servers leave to their clients. QIDO results come in through
+// json::from_json
+#[derive(Debug, Clone)]
+pub struct QueryResults {
+    datasets: Vec<Dataset>,
+}
+
+impl From<Vec<Dataset>> for QueryResults {
+    fn from(datasets: Vec<Dataset>) -> Self {
+        QueryResults::new(datasets)
+    }
+}
+
+impl QueryResults {
+    pub fn new(datasets: Vec<Dataset>) -> Self {
+        QueryResults { datasets }
+    }
+
+    pub fn datasets(&self) -> &[Dataset] {
+        &self.datasets
+    }
+
+    pub fn into_datasets(self) -> Vec<Dataset> {
+        self.datasets
+    }
+
+    pub fn len(&self) -> usize {
+        self.datasets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.datasets.is_empty()
+    }
+
+    // Keeps the results matching the pattern as a C-FIND key would, with
+    // wildcards, ranges and UID lists
+    pub fn filter(self, tag: (u16, u16), pattern: &str) -> Self {
+        let vr = dictionary::vr_of(tag);
+        self.filter_by(|dataset| {
+            matching::matches(pattern, &dataset.string(tag).unwrap_or_default(), vr)
+        })
+    }
+
+    pub fn filter_by<F: Fn(&Dataset) -> bool>(mut self, predicate: F) -> Self {
+        self.datasets.retain(|dataset| predicate(dataset));
+        self
+    }
+
+    pub fn sort_by(self, tag: (u16, u16), order: Order) -> Self {
+        self.sort_by_keys(&[(tag, order)])
+    }
+
+    // Stable, by the first key and then the next for ties. Numbers compare
+    // by value, and results lacking a key come last in either order
+    pub fn sort_by_keys(mut self, keys: &[((u16, u16), Order)]) -> Self {
+        self.datasets.sort_by(|a, b| {
+            keys.iter()
+                .map(|(tag, order)| compare(a, b, *tag, *order))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        self
+    }
+
+    // Keeps the first result of each value of the tag, results lacking it
+    // are all kept
+    pub fn dedup_by(mut self, tag: (u16, u16)) -> Self {
+        let mut seen = Vec::new();
+        self.datasets.retain(|dataset| {
+            match dataset.string(tag).filter(|value| !value.is_empty()) {
+                Some(value) if seen.contains(&value) => false,
+                Some(value) => {
+                    seen.push(value);
+                    true
+                }
+                None => true,
+            }
+        });
+        self
+    }
+
+    // Studies some servers return once per matching series or from several
+    // of their nodes
+    pub fn dedup_studies(self) -> Self {
+        self.dedup_by(STUDY_INSTANCE_UID)
+    }
+
+    // Results by the value of the tag, in order of first appearance within
+    // each group. Results lacking the tag go under the empty string
+    pub fn group_by(&self, tag: (u16, u16)) -> BTreeMap<String, Vec<Dataset>> {
+        let mut groups: BTreeMap<String, Vec<Dataset>> = BTreeMap::new();
+        for dataset in &self.datasets {
+            groups
+                .entry(dataset.string(tag).unwrap_or_default())
+                .or_default()
+                .push(dataset.clone());
+        }
+        groups
+    }
+
+    // Results for each value of the tag, such as instances per series out of
+    // an instance level search
+    pub fn count_by(&self, tag: (u16, u16)) -> BTreeMap<String, usize> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for dataset in &self.datasets {
+            *counts
+                .entry(dataset.string(tag).unwrap_or_default())
+                .or_default() += 1;
+        }
+        counts
+    }
+
+    // Sum of a numeric attribute for each value of the tag, such as Number of
+    // Series Related Instances per study out of a series level search
+    pub fn sum_by(&self, tag: (u16, u16), value: (u16, u16)) -> BTreeMap<String, f64> {
+        let mut sums: BTreeMap<String, f64> = BTreeMap::new();
+        for dataset in &self.datasets {
+            let number = number(dataset, value).unwrap_or(0.0);
+            *sums
+                .entry(dataset.string(tag).unwrap_or_default())
+                .or_default() += number;
+        }
+        sums
+    }
+
+    // Instances per series, counted from an instance level search
+    pub fn instances_per_series(&self) -> BTreeMap<String, usize> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for (series, instances) in self.group_by(SERIES_INSTANCE_UID) {
+            let instances = QueryResults::new(instances).dedup_by(SOP_INSTANCE_UID);
+            counts.insert(series, instances.len());
+        }
+        counts
+    }
+}
+
+fn number(dataset: &Dataset, tag: (u16, u16)) -> Option<f64> {
+    dataset.string(tag)?.split('\\').next()?.trim().parse().ok()
+}
+
+fn compare(a: &Dataset, b: &Dataset, tag: (u16, u16), order: Order) -> Ordering {
+    let numeric = matches!(
+        dictionary::vr_of(tag),
+        "IS" | "DS" | "US" | "UL" | "SS" | "SL" | "FL" | "FD" | "UV" | "SV"
+    );
+    let (left, right) = (
+        a.string(tag).filter(|value| !value.is_empty()),
+        b.string(tag).filter(|value| !value.is_empty()),
+    );
+    let ordering = match (&left, &right) {
+        (None, None) => return Ordering::Equal,
+        (None, Some(_)) => return Ordering::Greater,
+        (Some(_), None) => return Ordering::Less,
+        (Some(left), Some(right)) if numeric => match (number(a, tag), number(b, tag)) {
+            (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+            _ => left.cmp(right),
+        },
+        (Some(left), Some(right)) => left.cmp(right),
+    };
+    match order {
+        Order::Ascending => ordering,
+        Order::Descending => ordering.reverse(),
+    }
+}
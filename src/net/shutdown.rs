@@ -0,0 +1,233 @@
+use std::{
+    fmt,
+    io::ErrorKind,
+    net::{TcpListener, TcpStream},
+    pin::pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures_util::future;
+use tokio::{runtime::Builder, signal};
+
+use super::association::Association;
+use crate::core::error::{DicomError, DicomResult};
+
+// How often listeners and idle associations look at the shutdown flag
+pub const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+type Hook = Box<dyn FnOnce() -> DicomResult<()> + Send>;
+
+#[derive(Default)]
+struct Inner {
+    triggered: AtomicBool,
+    active: Mutex<usize>,
+    idle: Condvar,
+    hooks: Mutex<Vec<Hook>>,
+}
+
+// Shared by a server and whoever stops it. Once triggered listeners accept
+// no more connections and associations end after their current operation;
+// drain waits for them up to a deadline and then runs the shutdown hooks,
+// which is where services persist their queues
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    inner: Arc<Inner>,
+}
+
+impl fmt::Debug for Shutdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shutdown")
+            .field("triggered", &self.is_triggered())
+            .field("active", &self.active())
+            .finish()
+    }
+}
+
+// Keeps an association counted as in flight until dropped
+#[derive(Debug)]
+pub struct ActiveGuard {
+    shutdown: Shutdown,
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        let mut active = self
+            .shutdown
+            .inner
+            .active
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *active -= 1;
+        if *active == 0 {
+            self.shutdown.inner.idle.notify_all();
+        }
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Shutdown::default()
+    }
+
+    pub fn trigger(&self) {
+        self.inner.triggered.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.inner.triggered.load(Ordering::SeqCst)
+    }
+
+    // Associations in flight
+    pub fn active(&self) -> usize {
+        *self
+            .inner
+            .active
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // None once triggered, the connection is to be turned away then
+    pub fn begin(&self) -> Option<ActiveGuard> {
+        let mut active = self
+            .inner
+            .active
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if self.is_triggered() {
+            return None;
+        }
+        *active += 1;
+        Some(ActiveGuard {
+            shutdown: self.clone(),
+        })
+    }
+
+    // Runs once the associations drained or the deadline passed, in the
+    // order added
+    pub fn on_shutdown<F>(&self, hook: F)
+    where
+        F: FnOnce() -> DicomResult<()> + Send + 'static,
+    {
+        self.inner
+            .hooks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Box::new(hook));
+    }
+
+    // Triggers the shutdown and waits for the associations in flight, true
+    // when all of them ended in time. The hooks run either way, the first
+    // failing one is reported after all ran
+    pub fn drain(&self, timeout: Duration) -> DicomResult<bool> {
+        self.trigger();
+        let deadline = Instant::now() + timeout;
+        let mut active = self
+            .inner
+            .active
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        while *active > 0 {
+            let Some(left) = deadline.checked_duration_since(Instant::now()) else {
+                break;
+            };
+            active = self
+                .inner
+                .idle
+                .wait_timeout(active, left)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+        let drained = *active == 0;
+        drop(active);
+
+        let hooks: Vec<Hook> = self
+            .inner
+            .hooks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .drain(..)
+            .collect();
+        let mut result = Ok(drained);
+        for hook in hooks {
+            if let Err(error) = hook() {
+                if result.is_ok() {
+                    result = Err(error);
+                }
+            }
+        }
+        result
+    }
+
+    // Triggers on SIGTERM or SIGINT, how systemd and Kubernetes stop
+    // services, from a thread of its own. Draining is left to the caller
+    pub fn trigger_on_signals(&self) -> DicomResult<()> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        #[cfg(unix)]
+        let mut terminate = runtime
+            .block_on(async { signal::unix::signal(signal::unix::SignalKind::terminate()) })?;
+
+        let shutdown = self.clone();
+        thread::spawn(move || {
+            runtime.block_on(async {
+                #[cfg(unix)]
+                {
+                    let interrupt = pin!(signal::ctrl_c());
+                    let terminate = pin!(terminate.recv());
+                    let _ = future::select(interrupt, terminate).await;
+                }
+                #[cfg(not(unix))]
+                let _ = signal::ctrl_c().await;
+            });
+            shutdown.trigger();
+        });
+        Ok(())
+    }
+}
+
+// Next connection of a listener set to nonblocking, None once the shutdown
+// is triggered
+pub fn accept(listener: &TcpListener, shutdown: &Shutdown) -> DicomResult<Option<TcpStream>> {
+    loop {
+        if shutdown.is_triggered() {
+            return Ok(None);
+        }
+        match listener.accept() {
+            Ok((stream, _)) => {
+                // Some platforms pass the listener's mode on
+                stream.set_nonblocking(false)?;
+                return Ok(Some(stream));
+            }
+            Err(error) if error.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(error) => return Err(error.into()),
+        }
+    }
+}
+
+// Waits for the next message of the peer, false when the shutdown was
+// triggered first. A peer idle for longer than `timeout` has its association
+// aborted
+pub fn await_message(
+    association: &mut Association,
+    shutdown: &Shutdown,
+    timeout: Option<Duration>,
+) -> DicomResult<bool> {
+    let started = Instant::now();
+    while !association.has_pending_input()? {
+        if shutdown.is_triggered() {
+            return Ok(false);
+        }
+        if timeout.is_some_and(|timeout| started.elapsed() > timeout) {
+            return Err(DicomError::Timeout(format!(
+                "{} sent nothing",
+                association.peer_ae()
+            )));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    Ok(true)
+}
@@ -0,0 +1,243 @@
+use std::fmt;
+
+// Status codes of PS3.7 Annex C. Service specific codes that have no variant
+// of their own keep their raw value in the catch-all variants
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DimseStatus {
+    #[default]
+    Success,
+    Pending,
+    // Pending, but optional keys were not supported for matching
+    PendingWarning,
+    Cancel,
+    AttributeListError,
+    AttributeValueOutOfRange,
+    CoercionOfDataElements,
+    ElementsDiscarded,
+    DataSetDoesNotMatchSopClassWarning,
+    Warning(u16),
+    NoSuchAttribute,
+    InvalidAttributeValue,
+    ProcessingFailure,
+    DuplicateSopInstance,
+    NoSuchSopInstance,
+    NoSuchEventType,
+    NoSuchArgument,
+    InvalidArgumentValue,
+    InvalidObjectInstance,
+    NoSuchSopClass,
+    ClassInstanceConflict,
+    MissingAttribute,
+    MissingAttributeValue,
+    SopClassNotSupported,
+    NoSuchActionType,
+    NotAuthorized,
+    DuplicateInvocation,
+    UnrecognizedOperation,
+    MistypedArgument,
+    ResourceLimitation,
+    MoveDestinationUnknown,
+    // A7xx
+    OutOfResources(u16),
+    // A9xx
+    DataSetDoesNotMatchSopClass(u16),
+    // C3xx, the failures of the Unified Procedure Step service, PS3.4 CC.2
+    UpsFailure(u16),
+    // Cxxx
+    CannotUnderstand(u16),
+    Failure(u16),
+}
+
+impl DimseStatus {
+    pub fn from_u16(code: u16) -> Self {
+        match code {
+            0x0000 => DimseStatus::Success,
+            0xFF00 => DimseStatus::Pending,
+            0xFF01 => DimseStatus::PendingWarning,
+            0xFE00 => DimseStatus::Cancel,
+            0x0107 => DimseStatus::AttributeListError,
+            0x0116 => DimseStatus::AttributeValueOutOfRange,
+            0xB000 => DimseStatus::CoercionOfDataElements,
+            0xB006 => DimseStatus::ElementsDiscarded,
+            0xB007 => DimseStatus::DataSetDoesNotMatchSopClassWarning,
+            0x0001 | 0xB001..=0xBFFF => DimseStatus::Warning(code),
+            0x0105 => DimseStatus::NoSuchAttribute,
+            0x0106 => DimseStatus::InvalidAttributeValue,
+            0x0110 => DimseStatus::ProcessingFailure,
+            0x0111 => DimseStatus::DuplicateSopInstance,
+            0x0112 => DimseStatus::NoSuchSopInstance,
+            0x0113 => DimseStatus::NoSuchEventType,
+            0x0114 => DimseStatus::NoSuchArgument,
+            0x0115 => DimseStatus::InvalidArgumentValue,
+            0x0117 => DimseStatus::InvalidObjectInstance,
+            0x0118 => DimseStatus::NoSuchSopClass,
+            0x0119 => DimseStatus::ClassInstanceConflict,
+            0x0120 => DimseStatus::MissingAttribute,
+            0x0121 => DimseStatus::MissingAttributeValue,
+            0x0122 => DimseStatus::SopClassNotSupported,
+            0x0123 => DimseStatus::NoSuchActionType,
+            0x0124 => DimseStatus::NotAuthorized,
+            0x0210 => DimseStatus::DuplicateInvocation,
+            0x0211 => DimseStatus::UnrecognizedOperation,
+            0x0212 => DimseStatus::MistypedArgument,
+            0x0213 => DimseStatus::ResourceLimitation,
+            0xA801 => DimseStatus::MoveDestinationUnknown,
+            0xA700..=0xA7FF => DimseStatus::OutOfResources(code),
+            0xA900..=0xA9FF => DimseStatus::DataSetDoesNotMatchSopClass(code),
+            0xC300..=0xC3FF => DimseStatus::UpsFailure(code),
+            0xC000..=0xCFFF => DimseStatus::CannotUnderstand(code),
+            _ => DimseStatus::Failure(code),
+        }
+    }
+
+    pub fn code(&self) -> u16 {
+        match self {
+            DimseStatus::Success => 0x0000,
+            DimseStatus::Pending => 0xFF00,
+            DimseStatus::PendingWarning => 0xFF01,
+            DimseStatus::Cancel => 0xFE00,
+            DimseStatus::AttributeListError => 0x0107,
+            DimseStatus::AttributeValueOutOfRange => 0x0116,
+            DimseStatus::CoercionOfDataElements => 0xB000,
+            DimseStatus::ElementsDiscarded => 0xB006,
+            DimseStatus::DataSetDoesNotMatchSopClassWarning => 0xB007,
+            DimseStatus::NoSuchAttribute => 0x0105,
+            DimseStatus::InvalidAttributeValue => 0x0106,
+            DimseStatus::ProcessingFailure => 0x0110,
+            DimseStatus::DuplicateSopInstance => 0x0111,
+            DimseStatus::NoSuchSopInstance => 0x0112,
+            DimseStatus::NoSuchEventType => 0x0113,
+            DimseStatus::NoSuchArgument => 0x0114,
+            DimseStatus::InvalidArgumentValue => 0x0115,
+            DimseStatus::InvalidObjectInstance => 0x0117,
+            DimseStatus::NoSuchSopClass => 0x0118,
+            DimseStatus::ClassInstanceConflict => 0x0119,
+            DimseStatus::MissingAttribute => 0x0120,
+            DimseStatus::MissingAttributeValue => 0x0121,
+            DimseStatus::SopClassNotSupported => 0x0122,
+            DimseStatus::NoSuchActionType => 0x0123,
+            DimseStatus::NotAuthorized => 0x0124,
+            DimseStatus::DuplicateInvocation => 0x0210,
+            DimseStatus::UnrecognizedOperation => 0x0211,
+            DimseStatus::MistypedArgument => 0x0212,
+            DimseStatus::ResourceLimitation => 0x0213,
+            DimseStatus::MoveDestinationUnknown => 0xA801,
+            DimseStatus::Warning(code)
+            | DimseStatus::OutOfResources(code)
+            | DimseStatus::DataSetDoesNotMatchSopClass(code)
+            | DimseStatus::UpsFailure(code)
+            | DimseStatus::CannotUnderstand(code)
+            | DimseStatus::Failure(code) => *code,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(self, DimseStatus::Success)
+    }
+
+    pub fn is_pending(&self) -> bool {
+        matches!(self, DimseStatus::Pending | DimseStatus::PendingWarning)
+    }
+
+    pub fn is_cancel(&self) -> bool {
+        matches!(self, DimseStatus::Cancel)
+    }
+
+    pub fn is_warning(&self) -> bool {
+        matches!(
+            self,
+            DimseStatus::AttributeListError
+                | DimseStatus::AttributeValueOutOfRange
+                | DimseStatus::CoercionOfDataElements
+                | DimseStatus::ElementsDiscarded
+                | DimseStatus::DataSetDoesNotMatchSopClassWarning
+                | DimseStatus::Warning(_)
+        )
+    }
+
+    pub fn is_failure(&self) -> bool {
+        !(self.is_success() || self.is_pending() || self.is_cancel() || self.is_warning())
+    }
+
+    // Anything but pending ends the operation
+    pub fn is_final(&self) -> bool {
+        !self.is_pending()
+    }
+
+    pub fn meaning(&self) -> &'static str {
+        match self {
+            DimseStatus::Success => "Success",
+            DimseStatus::Pending => "Pending",
+            DimseStatus::PendingWarning => "Pending, optional keys not supported",
+            DimseStatus::Cancel => "Cancelled",
+            DimseStatus::AttributeListError => "Attribute list error",
+            DimseStatus::AttributeValueOutOfRange => "Attribute value out of range",
+            DimseStatus::CoercionOfDataElements => "Coercion of data elements",
+            DimseStatus::ElementsDiscarded => "Elements discarded",
+            DimseStatus::DataSetDoesNotMatchSopClassWarning => "Data set does not match SOP class",
+            DimseStatus::Warning(_) => "Warning",
+            DimseStatus::NoSuchAttribute => "No such attribute",
+            DimseStatus::InvalidAttributeValue => "Invalid attribute value",
+            DimseStatus::ProcessingFailure => "Processing failure",
+            DimseStatus::DuplicateSopInstance => "Duplicate SOP instance",
+            DimseStatus::NoSuchSopInstance => "No such SOP instance",
+            DimseStatus::NoSuchEventType => "No such event type",
+            DimseStatus::NoSuchArgument => "No such argument",
+            DimseStatus::InvalidArgumentValue => "Invalid argument value",
+            DimseStatus::InvalidObjectInstance => "Invalid object instance",
+            DimseStatus::NoSuchSopClass => "No such SOP class",
+            DimseStatus::ClassInstanceConflict => "Class-instance conflict",
+            DimseStatus::MissingAttribute => "Missing attribute",
+            DimseStatus::MissingAttributeValue => "Missing attribute value",
+            DimseStatus::SopClassNotSupported => "SOP class not supported",
+            DimseStatus::NoSuchActionType => "No such action type",
+            DimseStatus::NotAuthorized => "Not authorized",
+            DimseStatus::DuplicateInvocation => "Duplicate invocation",
+            DimseStatus::UnrecognizedOperation => "Unrecognized operation",
+            DimseStatus::MistypedArgument => "Mistyped argument",
+            DimseStatus::ResourceLimitation => "Resource limitation",
+            DimseStatus::MoveDestinationUnknown => "Move destination unknown",
+            DimseStatus::OutOfResources(_) => "Refused, out of resources",
+            DimseStatus::DataSetDoesNotMatchSopClass(_) => {
+                "Error, data set does not match SOP class"
+            }
+            DimseStatus::UpsFailure(code) => match code {
+                0xC300 => "The UPS may no longer be updated",
+                0xC301 => "The correct transaction UID was not provided",
+                0xC302 => "The UPS is already IN PROGRESS",
+                0xC303 => "The UPS may only become SCHEDULED via N-CREATE",
+                0xC304 => "The UPS has not met final state requirements",
+                0xC307 => "No such UPS instance",
+                0xC308 => "Receiving AE title is unknown",
+                0xC309 => "The UPS was not created SCHEDULED",
+                0xC310 => "The UPS is not yet IN PROGRESS",
+                0xC311 => "The UPS is already COMPLETED",
+                0xC312 => "The performer cannot be contacted",
+                0xC313 => "The performer chooses not to cancel",
+                0xC314 => "Action not appropriate for the UPS instance",
+                0xC315 => "Event reports are not supported",
+                _ => "UPS failure",
+            },
+            DimseStatus::CannotUnderstand(_) => "Error, cannot understand",
+            DimseStatus::Failure(_) => "Failure",
+        }
+    }
+}
+
+impl From<u16> for DimseStatus {
+    fn from(code: u16) -> Self {
+        DimseStatus::from_u16(code)
+    }
+}
+
+impl From<DimseStatus> for u16 {
+    fn from(status: DimseStatus) -> Self {
+        status.code()
+    }
+}
+
+impl fmt::Display for DimseStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:#06X})", self.meaning(), self.code())
+    }
+}
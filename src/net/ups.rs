@@ -0,0 +1,729 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Display,
+    rc::Rc,
+};
+
+use super::{
+    association::Association,
+    dimse::{
+        self, CEchoRsp, Command, DimseCommand, DimseMessage, MessageIdGenerator, NActionRsp,
+        NCreateRsp, NEventReportRq, NEventReportRsp, NGetRsp, NResponse, NSetRsp,
+    },
+    status::DimseStatus,
+};
+use crate::core::{
+    dataset::Dataset,
+    error::{DicomError, DicomResult},
+    uid,
+};
+
+pub const UPS_PUSH_SOP_CLASS: &str = "1.2.840.10008.5.1.4.34.6.1";
+pub const UPS_WATCH_SOP_CLASS: &str = "1.2.840.10008.5.1.4.34.6.2";
+pub const UPS_PULL_SOP_CLASS: &str = "1.2.840.10008.5.1.4.34.6.3";
+pub const UPS_EVENT_SOP_CLASS: &str = "1.2.840.10008.5.1.4.34.6.4";
+pub const UPS_GLOBAL_SUBSCRIPTION_INSTANCE: &str = "1.2.840.10008.5.1.4.34.5";
+
+pub const SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x0018);
+pub const TRANSACTION_UID: (u16, u16) = (0x0008, 0x1195);
+pub const PROCEDURE_STEP_STATE: (u16, u16) = (0x0074, 0x1000);
+pub const INPUT_READINESS_STATE: (u16, u16) = (0x0040, 0x4041);
+pub const REASON_FOR_CANCELLATION: (u16, u16) = (0x0074, 0x1238);
+pub const PERFORMED_PROCEDURE_SEQUENCE: (u16, u16) = (0x0074, 0x1216);
+pub const RECEIVING_AE: (u16, u16) = (0x0074, 0x1234);
+
+pub const STATUS_SUCCESS: DimseStatus = DimseStatus::Success;
+pub const STATUS_NO_SUCH_ATTRIBUTE: DimseStatus = DimseStatus::NoSuchAttribute;
+pub const STATUS_INVALID_ATTRIBUTE_VALUE: DimseStatus = DimseStatus::InvalidAttributeValue;
+pub const STATUS_DUPLICATE_INSTANCE: DimseStatus = DimseStatus::DuplicateSopInstance;
+pub const STATUS_ALREADY_CANCELED: DimseStatus = DimseStatus::Warning(0xB304);
+pub const STATUS_ALREADY_COMPLETED: DimseStatus = DimseStatus::Warning(0xB306);
+pub const STATUS_NO_LONGER_UPDATABLE: DimseStatus = DimseStatus::UpsFailure(0xC300);
+pub const STATUS_WRONG_TRANSACTION_UID: DimseStatus = DimseStatus::UpsFailure(0xC301);
+pub const STATUS_ALREADY_IN_PROGRESS: DimseStatus = DimseStatus::UpsFailure(0xC302);
+pub const STATUS_SCHEDULED_ONLY_VIA_CREATE: DimseStatus = DimseStatus::UpsFailure(0xC303);
+pub const STATUS_FINAL_STATE_NOT_MET: DimseStatus = DimseStatus::UpsFailure(0xC304);
+pub const STATUS_NO_SUCH_UPS: DimseStatus = DimseStatus::UpsFailure(0xC307);
+pub const STATUS_NOT_SCHEDULED: DimseStatus = DimseStatus::UpsFailure(0xC309);
+pub const STATUS_NOT_IN_PROGRESS: DimseStatus = DimseStatus::UpsFailure(0xC310);
+pub const STATUS_ALREADY_COMPLETED_FAILURE: DimseStatus = DimseStatus::UpsFailure(0xC311);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsState {
+    Scheduled,
+    InProgress,
+    Canceled,
+    Completed,
+}
+
+impl UpsState {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "SCHEDULED" => Some(UpsState::Scheduled),
+            "IN PROGRESS" => Some(UpsState::InProgress),
+            "CANCELED" => Some(UpsState::Canceled),
+            "COMPLETED" => Some(UpsState::Completed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UpsState::Scheduled => "SCHEDULED",
+            UpsState::InProgress => "IN PROGRESS",
+            UpsState::Canceled => "CANCELED",
+            UpsState::Completed => "COMPLETED",
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        matches!(self, UpsState::Canceled | UpsState::Completed)
+    }
+}
+
+impl Display for UpsState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsEventType {
+    StateReport = 1,
+    CancelRequested = 2,
+    ProgressReport = 3,
+    ScpStatusChange = 4,
+    Assigned = 5,
+}
+
+#[derive(Debug, Clone)]
+pub enum UpsAction {
+    ChangeState {
+        state: UpsState,
+        transaction_uid: String,
+    },
+    RequestCancel {
+        reason: Option<String>,
+    },
+    Subscribe {
+        ae_title: String,
+    },
+    Unsubscribe {
+        ae_title: String,
+    },
+}
+
+impl UpsAction {
+    // Action of an N-ACTION from its type and action information, CC.2.4.
+    // Subscriptions without a Receiving AE are for the requesting AE
+    pub fn from_action(
+        action_type_id: u16,
+        information: &Dataset,
+        peer_ae: &str,
+    ) -> Result<Self, DimseStatus> {
+        let receiving_ae = || {
+            information
+                .string(RECEIVING_AE)
+                .map(|ae_title| ae_title.trim().to_string())
+                .filter(|ae_title| !ae_title.is_empty())
+                .unwrap_or_else(|| peer_ae.to_string())
+        };
+
+        match action_type_id {
+            1 => {
+                let state = information
+                    .string(PROCEDURE_STEP_STATE)
+                    .ok_or(DimseStatus::MissingAttribute)?;
+                Ok(UpsAction::ChangeState {
+                    state: UpsState::parse(&state).ok_or(DimseStatus::InvalidArgumentValue)?,
+                    transaction_uid: information.string(TRANSACTION_UID).unwrap_or_default(),
+                })
+            }
+            2 => Ok(UpsAction::RequestCancel {
+                reason: information.string(REASON_FOR_CANCELLATION),
+            }),
+            3 => Ok(UpsAction::Subscribe {
+                ae_title: receiving_ae(),
+            }),
+            4 => Ok(UpsAction::Unsubscribe {
+                ae_title: receiving_ae(),
+            }),
+            _ => Err(DimseStatus::NoSuchActionType),
+        }
+    }
+
+    pub fn action_type_id(&self) -> u16 {
+        match self {
+            UpsAction::ChangeState { .. } => 1,
+            UpsAction::RequestCancel { .. } => 2,
+            UpsAction::Subscribe { .. } => 3,
+            UpsAction::Unsubscribe { .. } => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum UpsRequest {
+    NCreate {
+        sop_instance_uid: String,
+        dataset: Dataset,
+    },
+    NSet {
+        sop_instance_uid: String,
+        dataset: Dataset,
+    },
+    NGet {
+        sop_instance_uid: String,
+        attributes: Vec<(u16, u16)>,
+    },
+    NAction {
+        sop_instance_uid: String,
+        action: UpsAction,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct UpsResponse {
+    pub status: DimseStatus,
+    pub dataset: Option<Dataset>,
+}
+
+impl UpsResponse {
+    fn status(status: DimseStatus) -> Self {
+        UpsResponse {
+            status,
+            dataset: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NEventReport {
+    pub ae_title: String,
+    pub sop_instance_uid: String,
+    pub event_type: UpsEventType,
+    pub dataset: Dataset,
+}
+
+#[derive(Debug, Clone)]
+pub struct UpsInstance {
+    state: UpsState,
+    transaction_uid: Option<String>,
+    dataset: Dataset,
+}
+
+impl UpsInstance {
+    pub fn state(&self) -> UpsState {
+        self.state
+    }
+
+    pub fn dataset(&self) -> &Dataset {
+        &self.dataset
+    }
+
+    fn set_state(&mut self, state: UpsState) {
+        self.state = state;
+        self.dataset
+            .put_string(PROCEDURE_STEP_STATE, "CS", state.as_str());
+    }
+}
+
+// Keeps the UPS instances of an SCP and enforces the PS3.4 CC state machine
+#[derive(Debug, Default)]
+pub struct UpsScp {
+    instances: HashMap<String, UpsInstance>,
+    subscribers: HashMap<String, HashSet<String>>,
+    global_subscribers: HashSet<String>,
+    events: VecDeque<NEventReport>,
+}
+
+impl UpsScp {
+    pub fn new() -> Self {
+        UpsScp::default()
+    }
+
+    pub fn get(&self, sop_instance_uid: &str) -> Option<&UpsInstance> {
+        self.instances.get(sop_instance_uid)
+    }
+
+    pub fn take_events(&mut self) -> Vec<NEventReport> {
+        self.events.drain(..).collect()
+    }
+
+    pub fn handle(&mut self, request: UpsRequest) -> UpsResponse {
+        match request {
+            UpsRequest::NCreate {
+                sop_instance_uid,
+                dataset,
+            } => self.n_create(sop_instance_uid, dataset),
+            UpsRequest::NSet {
+                sop_instance_uid,
+                dataset,
+            } => self.n_set(&sop_instance_uid, dataset),
+            UpsRequest::NGet {
+                sop_instance_uid,
+                attributes,
+            } => self.n_get(&sop_instance_uid, &attributes),
+            UpsRequest::NAction {
+                sop_instance_uid,
+                action,
+            } => self.n_action(&sop_instance_uid, action),
+        }
+    }
+
+    // Answers the N-CREATE, N-SET, N-GET and N-ACTION requests of the UPS
+    // SOP classes, None for any other message
+    pub fn handle_message(
+        &mut self,
+        message: &DimseMessage,
+        peer_ae: &str,
+    ) -> DicomResult<Option<DimseMessage>> {
+        let data_set = || message.data_set.clone().unwrap_or_else(Dataset::new);
+
+        let response = match message.parse()? {
+            Command::NCreateRq(rq) if is_ups_sop_class(&rq.affected_sop_class_uid) => {
+                let sop_instance_uid = rq.affected_sop_instance_uid.unwrap_or_else(uid::generate);
+                let response = self.handle(UpsRequest::NCreate {
+                    sop_instance_uid: sop_instance_uid.clone(),
+                    dataset: data_set(),
+                });
+                let (rsp, data_set) = n_response(
+                    rq.message_id,
+                    rq.affected_sop_class_uid,
+                    sop_instance_uid,
+                    response,
+                );
+                DimseMessage::new(&NCreateRsp(rsp), data_set)
+            }
+            Command::NSetRq(rq) if is_ups_sop_class(&rq.requested_sop_class_uid) => {
+                let response = self.handle(UpsRequest::NSet {
+                    sop_instance_uid: rq.requested_sop_instance_uid.clone(),
+                    dataset: data_set(),
+                });
+                let (rsp, data_set) = n_response(
+                    rq.message_id,
+                    rq.requested_sop_class_uid,
+                    rq.requested_sop_instance_uid,
+                    response,
+                );
+                DimseMessage::new(&NSetRsp(rsp), data_set)
+            }
+            Command::NGetRq(rq) if is_ups_sop_class(&rq.requested_sop_class_uid) => {
+                let response = self.handle(UpsRequest::NGet {
+                    sop_instance_uid: rq.requested_sop_instance_uid.clone(),
+                    attributes: rq.attribute_identifier_list,
+                });
+                let (rsp, data_set) = n_response(
+                    rq.message_id,
+                    rq.requested_sop_class_uid,
+                    rq.requested_sop_instance_uid,
+                    response,
+                );
+                DimseMessage::new(&NGetRsp(rsp), data_set)
+            }
+            Command::NActionRq(rq) if is_ups_sop_class(&rq.requested_sop_class_uid) => {
+                let response = match UpsAction::from_action(rq.action_type_id, &data_set(), peer_ae)
+                {
+                    Ok(action) => self.handle(UpsRequest::NAction {
+                        sop_instance_uid: rq.requested_sop_instance_uid.clone(),
+                        action,
+                    }),
+                    Err(status) => UpsResponse::status(status),
+                };
+                let (rsp, data_set) = n_response(
+                    rq.message_id,
+                    rq.requested_sop_class_uid,
+                    rq.requested_sop_instance_uid,
+                    response,
+                );
+                DimseMessage::new(&NActionRsp(rsp), data_set)
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(response))
+    }
+
+    // Answers C-ECHO and the UPS requests of an accepted association until
+    // the peer releases it. Events stay queued for deliver_events
+    pub fn serve(&mut self, mut association: Association) -> DicomResult<()> {
+        let peer_ae = association.peer_ae().to_string();
+
+        while let Some((context_id, message)) = association.receive_message()? {
+            if let Command::CEchoRq(rq) = message.parse()? {
+                let rsp = CEchoRsp {
+                    message_id_being_responded_to: rq.message_id,
+                    affected_sop_class_uid: rq.affected_sop_class_uid,
+                    status: DimseStatus::Success,
+                };
+                association.send_message(context_id, &DimseMessage::new(&rsp, None))?;
+                continue;
+            }
+
+            match self.handle_message(&message, &peer_ae)? {
+                Some(response) => association.send_message(context_id, &response)?,
+                None => {
+                    return Err(DicomError::InvalidValue(format!(
+                        "Unsupported {} on the UPS SCP",
+                        dimse::command_name(message.command.command_field())
+                    )))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Sends the queued events as N-EVENT-REPORT, over one association per
+    // subscriber opened by connect from its AE title. Events of subscribers
+    // that could not be reached go back in the queue for a later call
+    pub fn deliver_events<F>(
+        &mut self,
+        mut connect: F,
+        message_ids: &MessageIdGenerator,
+    ) -> Vec<DicomError>
+    where
+        F: FnMut(&str) -> DicomResult<Association>,
+    {
+        let mut subscribers: Vec<(String, Vec<NEventReport>)> = Vec::new();
+        for report in self.events.drain(..) {
+            match subscribers
+                .iter_mut()
+                .find(|(ae_title, _)| *ae_title == report.ae_title)
+            {
+                Some((_, reports)) => reports.push(report),
+                None => subscribers.push((report.ae_title.clone(), vec![report])),
+            }
+        }
+
+        let mut errors = Vec::new();
+        for (ae_title, reports) in subscribers {
+            let mut association = match connect(&ae_title) {
+                Ok(association) => association,
+                Err(error) => {
+                    self.events.extend(reports);
+                    errors.push(error);
+                    continue;
+                }
+            };
+
+            for (index, report) in reports.iter().enumerate() {
+                if let Err(error) = send_event_report(&mut association, message_ids, report) {
+                    self.events.extend(reports[index..].iter().cloned());
+                    errors.push(error);
+                    break;
+                }
+            }
+            let _ = association.release();
+        }
+
+        errors
+    }
+
+    fn n_create(&mut self, sop_instance_uid: String, mut dataset: Dataset) -> UpsResponse {
+        if self.instances.contains_key(&sop_instance_uid) {
+            return UpsResponse::status(STATUS_DUPLICATE_INSTANCE);
+        }
+
+        let state = dataset
+            .string(PROCEDURE_STEP_STATE)
+            .map(|state| UpsState::parse(&state));
+        if let Some(state) = state {
+            if state != Some(UpsState::Scheduled) {
+                return UpsResponse::status(STATUS_NOT_SCHEDULED);
+            }
+        }
+
+        // The transaction UID is only ever handed out when claiming the step
+        dataset.remove(TRANSACTION_UID);
+        dataset.put_string(SOP_INSTANCE_UID, "UI", &sop_instance_uid);
+
+        let mut instance = UpsInstance {
+            state: UpsState::Scheduled,
+            transaction_uid: None,
+            dataset,
+        };
+        instance.set_state(UpsState::Scheduled);
+
+        self.instances.insert(sop_instance_uid.clone(), instance);
+        self.notify_state(&sop_instance_uid);
+
+        UpsResponse::status(STATUS_SUCCESS)
+    }
+
+    fn n_set(&mut self, sop_instance_uid: &str, modifications: Dataset) -> UpsResponse {
+        let Some(instance) = self.instances.get_mut(sop_instance_uid) else {
+            return UpsResponse::status(STATUS_NO_SUCH_UPS);
+        };
+
+        if instance.state.is_final() {
+            return UpsResponse::status(STATUS_NO_LONGER_UPDATABLE);
+        }
+
+        if instance.state == UpsState::InProgress
+            && modifications.string(TRANSACTION_UID) != instance.transaction_uid
+        {
+            return UpsResponse::status(STATUS_WRONG_TRANSACTION_UID);
+        }
+
+        // State changes go through N-ACTION only
+        if modifications.contains(PROCEDURE_STEP_STATE) {
+            return UpsResponse::status(STATUS_INVALID_ATTRIBUTE_VALUE);
+        }
+
+        for object in &modifications {
+            if object.tag() != TRANSACTION_UID {
+                instance.dataset.put(Rc::clone(object));
+            }
+        }
+
+        UpsResponse::status(STATUS_SUCCESS)
+    }
+
+    fn n_get(&self, sop_instance_uid: &str, attributes: &[(u16, u16)]) -> UpsResponse {
+        let Some(instance) = self.instances.get(sop_instance_uid) else {
+            return UpsResponse::status(STATUS_NO_SUCH_UPS);
+        };
+
+        let mut dataset = Dataset::new();
+        let mut missing = false;
+        for object in &instance.dataset {
+            if attributes.is_empty() || attributes.contains(&object.tag()) {
+                dataset.push_back(Rc::clone(object));
+            }
+        }
+        for tag in attributes {
+            if !instance.dataset.contains(*tag) {
+                missing = true;
+            }
+        }
+
+        UpsResponse {
+            status: if missing {
+                STATUS_NO_SUCH_ATTRIBUTE
+            } else {
+                STATUS_SUCCESS
+            },
+            dataset: Some(dataset),
+        }
+    }
+
+    fn n_action(&mut self, sop_instance_uid: &str, action: UpsAction) -> UpsResponse {
+        match action {
+            UpsAction::ChangeState {
+                state,
+                transaction_uid,
+            } => self.change_state(sop_instance_uid, state, transaction_uid),
+            UpsAction::RequestCancel { reason } => self.request_cancel(sop_instance_uid, reason),
+            UpsAction::Subscribe { ae_title } => self.subscribe(sop_instance_uid, ae_title),
+            UpsAction::Unsubscribe { ae_title } => {
+                if sop_instance_uid == UPS_GLOBAL_SUBSCRIPTION_INSTANCE {
+                    self.global_subscribers.remove(&ae_title);
+                } else if let Some(subscribers) = self.subscribers.get_mut(sop_instance_uid) {
+                    subscribers.remove(&ae_title);
+                }
+                UpsResponse::status(STATUS_SUCCESS)
+            }
+        }
+    }
+
+    fn change_state(
+        &mut self,
+        sop_instance_uid: &str,
+        requested: UpsState,
+        transaction_uid: String,
+    ) -> UpsResponse {
+        let Some(instance) = self.instances.get_mut(sop_instance_uid) else {
+            return UpsResponse::status(STATUS_NO_SUCH_UPS);
+        };
+
+        let status = match (instance.state, requested) {
+            (_, UpsState::Scheduled) => STATUS_SCHEDULED_ONLY_VIA_CREATE,
+            (UpsState::Scheduled, UpsState::InProgress) => {
+                instance.transaction_uid = Some(transaction_uid);
+                instance.set_state(UpsState::InProgress);
+                STATUS_SUCCESS
+            }
+            (UpsState::InProgress, UpsState::InProgress) => STATUS_ALREADY_IN_PROGRESS,
+            (UpsState::Scheduled, _) => STATUS_NOT_IN_PROGRESS,
+            (UpsState::InProgress, _)
+                if instance.transaction_uid.as_deref() != Some(transaction_uid.as_str()) =>
+            {
+                STATUS_WRONG_TRANSACTION_UID
+            }
+            (UpsState::InProgress, UpsState::Completed)
+                if !instance.dataset.contains(PERFORMED_PROCEDURE_SEQUENCE) =>
+            {
+                STATUS_FINAL_STATE_NOT_MET
+            }
+            (UpsState::InProgress, state) => {
+                instance.set_state(state);
+                STATUS_SUCCESS
+            }
+            (UpsState::Canceled, UpsState::Canceled) => STATUS_ALREADY_CANCELED,
+            (UpsState::Completed, UpsState::Completed) => STATUS_ALREADY_COMPLETED,
+            (_, _) => STATUS_NO_LONGER_UPDATABLE,
+        };
+
+        if status == STATUS_SUCCESS {
+            self.notify_state(sop_instance_uid);
+        }
+
+        UpsResponse::status(status)
+    }
+
+    fn request_cancel(&mut self, sop_instance_uid: &str, reason: Option<String>) -> UpsResponse {
+        let Some(instance) = self.instances.get_mut(sop_instance_uid) else {
+            return UpsResponse::status(STATUS_NO_SUCH_UPS);
+        };
+
+        match instance.state {
+            // Nobody owns the step yet, so the SCP may cancel it on its own
+            UpsState::Scheduled => {
+                if let Some(reason) = &reason {
+                    instance
+                        .dataset
+                        .put_string(REASON_FOR_CANCELLATION, "LT", reason);
+                }
+                instance.set_state(UpsState::Canceled);
+                self.notify_state(sop_instance_uid);
+                UpsResponse::status(STATUS_SUCCESS)
+            }
+            // The performer decides, we only forward the request
+            UpsState::InProgress => {
+                let mut dataset = Dataset::new();
+                if let Some(reason) = &reason {
+                    dataset.put_string(REASON_FOR_CANCELLATION, "LT", reason);
+                }
+                self.notify(sop_instance_uid, UpsEventType::CancelRequested, dataset);
+                UpsResponse::status(STATUS_SUCCESS)
+            }
+            UpsState::Canceled => UpsResponse::status(STATUS_ALREADY_CANCELED),
+            UpsState::Completed => UpsResponse::status(STATUS_ALREADY_COMPLETED_FAILURE),
+        }
+    }
+
+    fn subscribe(&mut self, sop_instance_uid: &str, ae_title: String) -> UpsResponse {
+        if sop_instance_uid == UPS_GLOBAL_SUBSCRIPTION_INSTANCE {
+            self.global_subscribers.insert(ae_title.clone());
+
+            let uids: Vec<String> = self.instances.keys().cloned().collect();
+            for uid in uids {
+                self.report_state_to(&uid, &ae_title);
+            }
+            return UpsResponse::status(STATUS_SUCCESS);
+        }
+
+        if !self.instances.contains_key(sop_instance_uid) {
+            return UpsResponse::status(STATUS_NO_SUCH_UPS);
+        }
+
+        self.subscribers
+            .entry(sop_instance_uid.to_string())
+            .or_default()
+            .insert(ae_title.clone());
+        self.report_state_to(sop_instance_uid, &ae_title);
+
+        UpsResponse::status(STATUS_SUCCESS)
+    }
+
+    fn state_dataset(&self, sop_instance_uid: &str) -> Option<Dataset> {
+        let instance = self.instances.get(sop_instance_uid)?;
+
+        let mut dataset = Dataset::new();
+        dataset.put_string(PROCEDURE_STEP_STATE, "CS", instance.state.as_str());
+        if let Some(readiness) = instance.dataset.find(INPUT_READINESS_STATE) {
+            dataset.put(Rc::clone(readiness));
+        }
+
+        Some(dataset)
+    }
+
+    fn report_state_to(&mut self, sop_instance_uid: &str, ae_title: &str) {
+        if let Some(dataset) = self.state_dataset(sop_instance_uid) {
+            self.events.push_back(NEventReport {
+                ae_title: ae_title.to_string(),
+                sop_instance_uid: sop_instance_uid.to_string(),
+                event_type: UpsEventType::StateReport,
+                dataset,
+            });
+        }
+    }
+
+    fn notify_state(&mut self, sop_instance_uid: &str) {
+        if let Some(dataset) = self.state_dataset(sop_instance_uid) {
+            self.notify(sop_instance_uid, UpsEventType::StateReport, dataset);
+        }
+    }
+
+    fn notify(&mut self, sop_instance_uid: &str, event_type: UpsEventType, dataset: Dataset) {
+        let mut recipients: HashSet<&String> = self.global_subscribers.iter().collect();
+        if let Some(subscribers) = self.subscribers.get(sop_instance_uid) {
+            recipients.extend(subscribers.iter());
+        }
+
+        for ae_title in recipients {
+            self.events.push_back(NEventReport {
+                ae_title: ae_title.clone(),
+                sop_instance_uid: sop_instance_uid.to_string(),
+                event_type,
+                dataset: dataset.clone(),
+            });
+        }
+    }
+}
+
+pub fn is_ups_sop_class(sop_class_uid: &str) -> bool {
+    [
+        UPS_PUSH_SOP_CLASS,
+        UPS_WATCH_SOP_CLASS,
+        UPS_PULL_SOP_CLASS,
+        UPS_EVENT_SOP_CLASS,
+    ]
+    .contains(&sop_class_uid)
+}
+
+fn n_response(
+    message_id: u16,
+    sop_class_uid: String,
+    sop_instance_uid: String,
+    response: UpsResponse,
+) -> (NResponse, Option<Dataset>) {
+    let rsp = NResponse {
+        message_id_being_responded_to: message_id,
+        affected_sop_class_uid: sop_class_uid,
+        affected_sop_instance_uid: sop_instance_uid,
+        status: response.status,
+        error_comment: None,
+        has_data_set: response.dataset.is_some(),
+    };
+    (rsp, response.dataset)
+}
+
+// N-EVENT-REPORT of one event, over an association that accepted the UPS
+// Event SOP class. Gives the status the subscriber answered with
+pub fn send_event_report(
+    association: &mut Association,
+    message_ids: &MessageIdGenerator,
+    report: &NEventReport,
+) -> DicomResult<DimseStatus> {
+    let context_id = association
+        .context_for(UPS_EVENT_SOP_CLASS)
+        .map(|context| context.id)
+        .ok_or_else(|| {
+            DicomError::InvalidValue("Unified Procedure Step - Event was not accepted".to_string())
+        })?;
+
+    let request = NEventReportRq {
+        message_id: message_ids.next_id(),
+        affected_sop_class_uid: UPS_EVENT_SOP_CLASS.to_string(),
+        affected_sop_instance_uid: report.sop_instance_uid.clone(),
+        event_type_id: report.event_type as u16,
+        has_event_information: true,
+    };
+    let message = DimseMessage::new(&request, Some(report.dataset.clone()));
+    association.send_message(context_id, &message)?;
+
+    let (_, response) = association.receive_response(&message.command)?;
+    Ok(NEventReportRsp::from_command(&response.command)?.status)
+}
@@ -0,0 +1,404 @@
+use std::{
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    rc::Rc,
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+};
+
+use super::{
+    association::{self, AssociateResponse, Association, AssociationOptions},
+    cancel::CancellationToken,
+    dimse::{self, CEchoRsp, CFindRsp, Command, DimseMessage},
+    find::poll_cancel,
+    limits::{self, PeerLimiter},
+    matching::{match_keys, put_value},
+    shutdown::{self, Shutdown},
+    status::DimseStatus,
+};
+
+use crate::core::{
+    dataset::Dataset,
+    element::DicomElement,
+    error::{DicomError, DicomResult},
+    syntax_policy::TransferSyntaxPolicy,
+    transfer_syntax,
+};
+
+pub const MODALITY_WORKLIST_FIND_SOP_CLASS: &str = "1.2.840.10008.5.1.4.31";
+
+pub const ACCESSION_NUMBER: (u16, u16) = (0x0008, 0x0050);
+pub const MODALITY: (u16, u16) = (0x0008, 0x0060);
+pub const REFERRING_PHYSICIAN_NAME: (u16, u16) = (0x0008, 0x0090);
+pub const PATIENT_NAME: (u16, u16) = (0x0010, 0x0010);
+pub const PATIENT_ID: (u16, u16) = (0x0010, 0x0020);
+pub const PATIENT_BIRTH_DATE: (u16, u16) = (0x0010, 0x0030);
+pub const PATIENT_SEX: (u16, u16) = (0x0010, 0x0040);
+pub const STUDY_INSTANCE_UID: (u16, u16) = (0x0020, 0x000D);
+pub const REQUESTED_PROCEDURE_DESCRIPTION: (u16, u16) = (0x0032, 0x1060);
+pub const SCHEDULED_STATION_AE_TITLE: (u16, u16) = (0x0040, 0x0001);
+pub const SCHEDULED_PROCEDURE_STEP_START_DATE: (u16, u16) = (0x0040, 0x0002);
+pub const SCHEDULED_PROCEDURE_STEP_START_TIME: (u16, u16) = (0x0040, 0x0003);
+pub const SCHEDULED_PERFORMING_PHYSICIAN_NAME: (u16, u16) = (0x0040, 0x0006);
+pub const SCHEDULED_PROCEDURE_STEP_DESCRIPTION: (u16, u16) = (0x0040, 0x0007);
+pub const SCHEDULED_PROCEDURE_STEP_ID: (u16, u16) = (0x0040, 0x0009);
+pub const SCHEDULED_PROCEDURE_STEP_SEQUENCE: (u16, u16) = (0x0040, 0x0100);
+pub const REQUESTED_PROCEDURE_ID: (u16, u16) = (0x0040, 0x1001);
+pub const PLACER_ORDER_NUMBER: (u16, u16) = (0x0040, 0x2016);
+pub const FILLER_ORDER_NUMBER: (u16, u16) = (0x0040, 0x2017);
+
+// A scheduled procedure step with its requested procedure and patient, kept
+// as plain strings so the worklist can be shared between threads
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WorklistItem {
+    pub patient_id: String,
+    pub patient_name: String,
+    pub patient_birth_date: String,
+    pub patient_sex: String,
+    pub accession_number: String,
+    pub referring_physician_name: String,
+    pub placer_order_number: String,
+    pub filler_order_number: String,
+    pub study_instance_uid: String,
+    pub requested_procedure_id: String,
+    pub requested_procedure_description: String,
+    pub modality: String,
+    pub scheduled_station_ae_title: String,
+    pub scheduled_start_date: String,
+    pub scheduled_start_time: String,
+    pub scheduled_performing_physician_name: String,
+    pub scheduled_procedure_step_id: String,
+    pub scheduled_procedure_step_description: String,
+}
+
+impl WorklistItem {
+    pub fn to_dataset(&self) -> Dataset {
+        let mut step = Dataset::new();
+        step.put_string(MODALITY, "CS", &self.modality);
+        step.put_string(
+            SCHEDULED_STATION_AE_TITLE,
+            "AE",
+            &self.scheduled_station_ae_title,
+        );
+        put_value(
+            &mut step,
+            SCHEDULED_PROCEDURE_STEP_START_DATE,
+            "DA",
+            &self.scheduled_start_date,
+        );
+        put_value(
+            &mut step,
+            SCHEDULED_PROCEDURE_STEP_START_TIME,
+            "TM",
+            &self.scheduled_start_time,
+        );
+        step.put_string(
+            SCHEDULED_PERFORMING_PHYSICIAN_NAME,
+            "PN",
+            &self.scheduled_performing_physician_name,
+        );
+        step.put_string(
+            SCHEDULED_PROCEDURE_STEP_DESCRIPTION,
+            "LO",
+            &self.scheduled_procedure_step_description,
+        );
+        step.put_string(
+            SCHEDULED_PROCEDURE_STEP_ID,
+            "SH",
+            &self.scheduled_procedure_step_id,
+        );
+
+        let mut dataset = Dataset::new();
+        dataset.put_string(ACCESSION_NUMBER, "SH", &self.accession_number);
+        dataset.put_string(
+            REFERRING_PHYSICIAN_NAME,
+            "PN",
+            &self.referring_physician_name,
+        );
+        dataset.put_string(PATIENT_NAME, "PN", &self.patient_name);
+        dataset.put_string(PATIENT_ID, "LO", &self.patient_id);
+        put_value(
+            &mut dataset,
+            PATIENT_BIRTH_DATE,
+            "DA",
+            &self.patient_birth_date,
+        );
+        dataset.put_string(PATIENT_SEX, "CS", &self.patient_sex);
+        dataset.put_string(STUDY_INSTANCE_UID, "UI", &self.study_instance_uid);
+        dataset.put_string(
+            REQUESTED_PROCEDURE_DESCRIPTION,
+            "LO",
+            &self.requested_procedure_description,
+        );
+        dataset.put(Rc::new(DicomElement::sequence(
+            SCHEDULED_PROCEDURE_STEP_SEQUENCE,
+            vec![step],
+        )));
+        dataset.put_string(REQUESTED_PROCEDURE_ID, "SH", &self.requested_procedure_id);
+        dataset.put_string(PLACER_ORDER_NUMBER, "LO", &self.placer_order_number);
+        dataset.put_string(FILLER_ORDER_NUMBER, "LO", &self.filler_order_number);
+        dataset
+    }
+
+    // Item of a C-FIND response, the first scheduled step only. Keys the
+    // SCP left out come back empty
+    pub fn from_dataset(dataset: &Dataset) -> Self {
+        let step = dataset
+            .sequence(SCHEDULED_PROCEDURE_STEP_SEQUENCE)
+            .into_iter()
+            .next()
+            .unwrap_or_else(Dataset::new);
+        let value = |dataset: &Dataset, tag| dataset.string(tag).unwrap_or_default();
+        WorklistItem {
+            patient_id: value(dataset, PATIENT_ID),
+            patient_name: value(dataset, PATIENT_NAME),
+            patient_birth_date: value(dataset, PATIENT_BIRTH_DATE),
+            patient_sex: value(dataset, PATIENT_SEX),
+            accession_number: value(dataset, ACCESSION_NUMBER),
+            referring_physician_name: value(dataset, REFERRING_PHYSICIAN_NAME),
+            placer_order_number: value(dataset, PLACER_ORDER_NUMBER),
+            filler_order_number: value(dataset, FILLER_ORDER_NUMBER),
+            study_instance_uid: value(dataset, STUDY_INSTANCE_UID),
+            requested_procedure_id: value(dataset, REQUESTED_PROCEDURE_ID),
+            requested_procedure_description: value(dataset, REQUESTED_PROCEDURE_DESCRIPTION),
+            modality: value(&step, MODALITY),
+            scheduled_station_ae_title: value(&step, SCHEDULED_STATION_AE_TITLE),
+            scheduled_start_date: value(&step, SCHEDULED_PROCEDURE_STEP_START_DATE),
+            scheduled_start_time: value(&step, SCHEDULED_PROCEDURE_STEP_START_TIME),
+            scheduled_performing_physician_name: value(&step, SCHEDULED_PERFORMING_PHYSICIAN_NAME),
+            scheduled_procedure_step_id: value(&step, SCHEDULED_PROCEDURE_STEP_ID),
+            scheduled_procedure_step_description: value(
+                &step,
+                SCHEDULED_PROCEDURE_STEP_DESCRIPTION,
+            ),
+        }
+    }
+
+    fn same_step(&self, other: &WorklistItem) -> bool {
+        self.accession_number == other.accession_number
+            && self.scheduled_procedure_step_id == other.scheduled_procedure_step_id
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Worklist {
+    items: Vec<WorklistItem>,
+}
+
+pub type SharedWorklist = Arc<Mutex<Worklist>>;
+
+impl Worklist {
+    pub fn new() -> Self {
+        Worklist::default()
+    }
+
+    pub fn shared(self) -> SharedWorklist {
+        Arc::new(Mutex::new(self))
+    }
+
+    pub fn items(&self) -> &[WorklistItem] {
+        &self.items
+    }
+
+    // Replaces the step with the same accession number and step ID
+    pub fn upsert(&mut self, item: WorklistItem) {
+        match self
+            .items
+            .iter_mut()
+            .find(|existing| existing.same_step(&item))
+        {
+            Some(existing) => *existing = item,
+            None => self.items.push(item),
+        }
+    }
+
+    // Drops every step of an order, returning how many there were
+    pub fn remove(&mut self, accession_number: &str) -> usize {
+        let before = self.items.len();
+        self.items
+            .retain(|item| item.accession_number != accession_number);
+        before - self.items.len()
+    }
+
+    // C-FIND matching of PS3.4 C.2.2.2, each response holds the query keys
+    pub fn find(&self, query: &Dataset) -> Vec<Dataset> {
+        self.items
+            .iter()
+            .filter_map(|item| match_keys(query, &item.to_dataset()))
+            .collect()
+    }
+}
+
+// Modality Worklist SCP, answering C-ECHO and C-FIND from a shared worklist
+#[derive(Debug, Clone)]
+pub struct WorklistScp {
+    worklist: SharedWorklist,
+    options: AssociationOptions,
+    shutdown: Shutdown,
+    limiter: PeerLimiter,
+    syntax_policy: TransferSyntaxPolicy,
+}
+
+impl WorklistScp {
+    pub fn new(worklist: SharedWorklist, ae_title: &str) -> Self {
+        WorklistScp {
+            worklist,
+            options: AssociationOptions::new(ae_title, ""),
+            shutdown: Shutdown::new(),
+            limiter: PeerLimiter::default(),
+            syntax_policy: TransferSyntaxPolicy::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: AssociationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    pub fn with_limiter(mut self, limiter: PeerLimiter) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    pub fn with_syntax_policy(mut self, policy: TransferSyntaxPolicy) -> Self {
+        self.syntax_policy = policy;
+        self
+    }
+
+    pub fn worklist(&self) -> SharedWorklist {
+        self.worklist.clone()
+    }
+
+    pub fn shutdown(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    // Serves each connection on its own thread until the shutdown is
+    // triggered. Failed associations only affect their own connection, so
+    // their errors are dropped
+    pub fn listen<A: ToSocketAddrs>(&self, address: A) -> DicomResult<()> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        while let Some(stream) = shutdown::accept(&listener, &self.shutdown)? {
+            let Some(guard) = self.shutdown.begin() else {
+                break;
+            };
+            let scp = self.clone();
+            thread::spawn(move || {
+                let _guard = guard;
+                scp.serve(stream)
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn serve(&self, stream: TcpStream) -> DicomResult<()> {
+        let syntaxes = [
+            transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN,
+            transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN,
+        ];
+        let supported = [
+            (dimse::VERIFICATION_SOP_CLASS, &syntaxes[..]),
+            (MODALITY_WORKLIST_FIND_SOP_CLASS, &syntaxes[..]),
+        ];
+
+        let mut permit = None;
+        let mut association = Association::accept(stream, self.options.clone(), |rq| {
+            permit = self.limiter.admit(&rq.calling_ae);
+            match permit {
+                Some(_) => AssociateResponse::Accept(association::negotiate_with_policy(
+                    rq,
+                    &supported,
+                    &self.syntax_policy,
+                )),
+                None => AssociateResponse::Reject(limits::LIMIT_EXCEEDED),
+            }
+        })?;
+        let peer_ae = association.peer_ae().to_string();
+        let mut transferred = 0;
+
+        loop {
+            // Between operations is where a shutdown ends the association
+            let idle = self.options.dimse_timeouts.default;
+            if !shutdown::await_message(&mut association, &self.shutdown, idle)? {
+                return association.release();
+            }
+            let Some((context_id, message)) = association.receive_message()? else {
+                break;
+            };
+            self.limiter.operation(&peer_ae);
+            match message.parse()? {
+                Command::CEchoRq(rq) => {
+                    let rsp = CEchoRsp {
+                        message_id_being_responded_to: rq.message_id,
+                        affected_sop_class_uid: rq.affected_sop_class_uid,
+                        status: DimseStatus::Success,
+                    };
+                    association.send_message(context_id, &DimseMessage::new(&rsp, None))?;
+                }
+                Command::CFindRq(rq) => {
+                    let status = if rq.affected_sop_class_uid == MODALITY_WORKLIST_FIND_SOP_CLASS {
+                        let query = message.data_set.unwrap_or_else(Dataset::new);
+                        let matches = self
+                            .worklist
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .find(&query);
+
+                        let token = CancellationToken::new();
+                        for identifier in matches {
+                            if poll_cancel(&mut association, rq.message_id, &token)? {
+                                break;
+                            }
+                            let rsp = CFindRsp {
+                                message_id_being_responded_to: rq.message_id,
+                                affected_sop_class_uid: rq.affected_sop_class_uid.clone(),
+                                status: DimseStatus::Pending,
+                                error_comment: None,
+                            };
+                            association.send_message(
+                                context_id,
+                                &DimseMessage::new(&rsp, Some(identifier)),
+                            )?;
+                        }
+                        if token.is_cancelled() {
+                            DimseStatus::Cancel
+                        } else {
+                            DimseStatus::Success
+                        }
+                    } else {
+                        DimseStatus::SopClassNotSupported
+                    };
+
+                    let rsp = CFindRsp {
+                        message_id_being_responded_to: rq.message_id,
+                        affected_sop_class_uid: rq.affected_sop_class_uid,
+                        status,
+                        error_comment: None,
+                    };
+                    association.send_message(context_id, &DimseMessage::new(&rsp, None))?;
+                }
+                // Cancels during a query are read by poll_cancel, later ones
+                // are for queries already answered
+                Command::CCancelRq(_) => {}
+                _ => {
+                    return Err(DicomError::InvalidValue(format!(
+                        "Unsupported {} on the worklist SCP",
+                        dimse::command_name(message.command.command_field())
+                    )))
+                }
+            }
+
+            let moved = association.bytes_sent() + association.bytes_received();
+            self.limiter.transfer(&peer_ae, moved - transferred);
+            transferred = moved;
+        }
+
+        Ok(())
+    }
+}
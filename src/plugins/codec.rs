@@ -0,0 +1,465 @@
+use std::{
+    sync::{Arc, OnceLock, RwLock},
+    time::Instant,
+};
+
+use crate::core::{
+    dataset::Dataset,
+    error::{DicomError, DicomResult},
+    metrics, transfer_syntax,
+};
+
+pub const ROWS: (u16, u16) = (0x0028, 0x0010);
+pub const COLUMNS: (u16, u16) = (0x0028, 0x0011);
+pub const SAMPLES_PER_PIXEL: (u16, u16) = (0x0028, 0x0002);
+pub const PHOTOMETRIC_INTERPRETATION: (u16, u16) = (0x0028, 0x0004);
+pub const BITS_ALLOCATED: (u16, u16) = (0x0028, 0x0100);
+pub const BITS_STORED: (u16, u16) = (0x0028, 0x0101);
+pub const PIXEL_REPRESENTATION: (u16, u16) = (0x0028, 0x0103);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameInfo {
+    pub rows: u16,
+    pub columns: u16,
+    pub samples_per_pixel: u16,
+    pub bits_allocated: u16,
+    pub bits_stored: u16,
+    pub pixel_representation: u16,
+    pub photometric_interpretation: String,
+}
+
+impl FrameInfo {
+    pub fn from_dataset(dataset: &Dataset) -> DicomResult<Self> {
+        let number = |tag: (u16, u16), default: Option<u16>| -> DicomResult<u16> {
+            match dataset.string(tag) {
+                Some(value) => value.trim().parse().map_err(|_| {
+                    DicomError::InvalidValue(format!(
+                        "({:04X},{:04X}) is not a number: {}",
+                        tag.0, tag.1, value
+                    ))
+                }),
+                None => default.ok_or_else(|| {
+                    DicomError::InvalidDataset(format!("Missing ({:04X},{:04X})", tag.0, tag.1))
+                }),
+            }
+        };
+
+        let bits_allocated = number(BITS_ALLOCATED, None)?;
+        Ok(FrameInfo {
+            rows: number(ROWS, None)?,
+            columns: number(COLUMNS, None)?,
+            samples_per_pixel: number(SAMPLES_PER_PIXEL, Some(1))?,
+            bits_allocated,
+            bits_stored: number(BITS_STORED, Some(bits_allocated))?,
+            pixel_representation: number(PIXEL_REPRESENTATION, Some(0))?,
+            photometric_interpretation: dataset
+                .string(PHOTOMETRIC_INTERPRETATION)
+                .unwrap_or_else(|| "MONOCHROME2".to_string()),
+        })
+    }
+
+    pub fn pixel_count(&self) -> usize {
+        self.rows as usize * self.columns as usize
+    }
+
+    pub fn bytes_per_sample(&self) -> usize {
+        (self.bits_allocated as usize).div_ceil(8)
+    }
+
+    pub fn frame_bits(&self) -> usize {
+        self.pixel_count() * self.samples_per_pixel as usize * self.bits_allocated as usize
+    }
+
+    // Bytes of a frame, 1 and 12 bit allocated samples packed
+    pub fn frame_size(&self) -> usize {
+        self.frame_bits().div_ceil(8)
+    }
+}
+
+pub trait PixelCodec: Send + Sync {
+    fn name(&self) -> &str;
+    fn transfer_syntaxes(&self) -> Vec<String>;
+
+    fn can_decode(&self) -> bool {
+        true
+    }
+
+    fn can_encode(&self) -> bool {
+        false
+    }
+
+    // Turns one frame worth of fragment data into native little endian pixels
+    fn decode(
+        &self,
+        transfer_syntax: &str,
+        fragment: &[u8],
+        info: &FrameInfo,
+    ) -> DicomResult<Vec<u8>>;
+
+    fn encode(
+        &self,
+        transfer_syntax: &str,
+        _frame: &[u8],
+        _info: &FrameInfo,
+    ) -> DicomResult<Vec<u8>> {
+        Err(DicomError::UnsupportedTransferSyntax(format!(
+            "{} cannot encode {}",
+            self.name(),
+            transfer_syntax
+        )))
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct CodecRegistry {
+    codecs: Vec<Arc<dyn PixelCodec>>,
+}
+
+impl CodecRegistry {
+    pub fn new() -> Self {
+        CodecRegistry::default()
+    }
+
+    pub fn with_builtins() -> Self {
+        let mut registry = CodecRegistry::new();
+        registry.register(NativeCodec);
+        registry.register(RleCodec);
+        registry
+    }
+
+    // Codecs registered later win, so plugins can override the built-in ones
+    pub fn register<C: PixelCodec + 'static>(&mut self, codec: C) {
+        self.register_arc(Arc::new(codec));
+    }
+
+    pub fn register_arc(&mut self, codec: Arc<dyn PixelCodec>) {
+        self.codecs.insert(0, codec);
+    }
+
+    pub fn unregister(&mut self, name: &str) {
+        self.codecs.retain(|codec| codec.name() != name);
+    }
+
+    pub fn codecs(&self) -> &[Arc<dyn PixelCodec>] {
+        &self.codecs
+    }
+
+    pub fn decoder(&self, transfer_syntax: &str) -> Option<Arc<dyn PixelCodec>> {
+        self.find(transfer_syntax, |codec| codec.can_decode())
+    }
+
+    pub fn encoder(&self, transfer_syntax: &str) -> Option<Arc<dyn PixelCodec>> {
+        self.find(transfer_syntax, |codec| codec.can_encode())
+    }
+
+    pub fn transfer_syntaxes(&self) -> Vec<String> {
+        let mut syntaxes: Vec<String> = self
+            .codecs
+            .iter()
+            .flat_map(|codec| codec.transfer_syntaxes())
+            .collect();
+        syntaxes.sort();
+        syntaxes.dedup();
+        syntaxes
+    }
+
+    pub fn decode(
+        &self,
+        transfer_syntax: &str,
+        fragment: &[u8],
+        info: &FrameInfo,
+    ) -> DicomResult<Vec<u8>> {
+        let decoder = self
+            .decoder(transfer_syntax)
+            .ok_or_else(|| DicomError::UnsupportedTransferSyntax(transfer_syntax.to_string()))?;
+        let started = Instant::now();
+        let frame = decoder.decode(transfer_syntax, fragment, info)?;
+        metrics::global()
+            .decode_time(transfer_syntax)
+            .observe(started.elapsed());
+        Ok(frame)
+    }
+
+    pub fn encode(
+        &self,
+        transfer_syntax: &str,
+        frame: &[u8],
+        info: &FrameInfo,
+    ) -> DicomResult<Vec<u8>> {
+        self.encoder(transfer_syntax)
+            .ok_or_else(|| DicomError::UnsupportedTransferSyntax(transfer_syntax.to_string()))?
+            .encode(transfer_syntax, frame, info)
+    }
+
+    fn find<F>(&self, transfer_syntax: &str, capable: F) -> Option<Arc<dyn PixelCodec>>
+    where
+        F: Fn(&Arc<dyn PixelCodec>) -> bool,
+    {
+        let transfer_syntax = transfer_syntax.trim_end_matches(['\0', ' ']);
+        self.codecs
+            .iter()
+            .find(|codec| {
+                capable(codec)
+                    && codec
+                        .transfer_syntaxes()
+                        .iter()
+                        .any(|syntax| syntax == transfer_syntax)
+            })
+            .cloned()
+    }
+}
+
+fn global() -> &'static RwLock<CodecRegistry> {
+    static GLOBAL: OnceLock<RwLock<CodecRegistry>> = OnceLock::new();
+    GLOBAL.get_or_init(|| RwLock::new(CodecRegistry::with_builtins()))
+}
+
+pub fn register_codec<C: PixelCodec + 'static>(codec: C) {
+    global()
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .register(codec);
+}
+
+// Snapshot of the process wide registry, cheap since codecs are shared
+pub fn global_registry() -> CodecRegistry {
+    global()
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+pub struct NativeCodec;
+
+impl NativeCodec {
+    fn swap(transfer_syntax: &str, data: &[u8], info: &FrameInfo) -> Vec<u8> {
+        let mut data = data.to_vec();
+        if transfer_syntax == transfer_syntax::EXPLICIT_VR_BIG_ENDIAN && info.bytes_per_sample() > 1
+        {
+            for sample in data.chunks_exact_mut(info.bytes_per_sample()) {
+                sample.reverse();
+            }
+        }
+        data
+    }
+}
+
+impl PixelCodec for NativeCodec {
+    fn name(&self) -> &str {
+        "native"
+    }
+
+    fn transfer_syntaxes(&self) -> Vec<String> {
+        vec![
+            transfer_syntax::IMPLICIT_VR_LITTLE_ENDIAN.to_string(),
+            transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN.to_string(),
+            transfer_syntax::DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN.to_string(),
+            transfer_syntax::EXPLICIT_VR_BIG_ENDIAN.to_string(),
+        ]
+    }
+
+    fn can_encode(&self) -> bool {
+        true
+    }
+
+    fn decode(
+        &self,
+        transfer_syntax: &str,
+        fragment: &[u8],
+        info: &FrameInfo,
+    ) -> DicomResult<Vec<u8>> {
+        Ok(NativeCodec::swap(transfer_syntax, fragment, info))
+    }
+
+    fn encode(
+        &self,
+        transfer_syntax: &str,
+        frame: &[u8],
+        info: &FrameInfo,
+    ) -> DicomResult<Vec<u8>> {
+        Ok(NativeCodec::swap(transfer_syntax, frame, info))
+    }
+}
+
+// PS3.5 Annex G
+pub struct RleCodec;
+
+impl RleCodec {
+    fn unpack_bits(segment: &[u8], expected: usize) -> Vec<u8> {
+        let mut output = Vec::with_capacity(expected);
+        let mut i = 0;
+
+        while i < segment.len() && output.len() < expected {
+            let header = segment[i] as i8;
+            i += 1;
+
+            if header >= 0 {
+                let count = header as usize + 1;
+                let end = (i + count).min(segment.len());
+                output.extend_from_slice(&segment[i..end]);
+                i = end;
+            } else if header != -128 {
+                if let Some(&byte) = segment.get(i) {
+                    let count = (1 - header as isize) as usize;
+                    output.extend(std::iter::repeat_n(byte, count));
+                }
+                i += 1;
+            }
+        }
+
+        output.truncate(expected);
+        output
+    }
+
+    fn pack_bits(data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut i = 0;
+
+        while i < data.len() {
+            let mut run = 1;
+            while i + run < data.len() && data[i + run] == data[i] && run < 128 {
+                run += 1;
+            }
+
+            if run >= 2 {
+                output.push((1 - run as i16) as i8 as u8);
+                output.push(data[i]);
+                i += run;
+            } else {
+                let start = i;
+                while i < data.len() && i - start < 128 {
+                    if i + 1 < data.len() && data[i] == data[i + 1] {
+                        break;
+                    }
+                    i += 1;
+                }
+                output.push((i - start - 1) as u8);
+                output.extend_from_slice(&data[start..i]);
+            }
+        }
+
+        // Segments have to be of even length
+        if output.len() % 2 == 1 {
+            output.push(0);
+        }
+        output
+    }
+}
+
+impl PixelCodec for RleCodec {
+    fn name(&self) -> &str {
+        "rle"
+    }
+
+    fn transfer_syntaxes(&self) -> Vec<String> {
+        vec![transfer_syntax::RLE_LOSSLESS.to_string()]
+    }
+
+    fn can_encode(&self) -> bool {
+        true
+    }
+
+    fn decode(
+        &self,
+        _transfer_syntax: &str,
+        fragment: &[u8],
+        info: &FrameInfo,
+    ) -> DicomResult<Vec<u8>> {
+        if fragment.len() < 64 {
+            return Err(DicomError::InvalidLength(
+                "RLE header is 64 bytes".to_string(),
+            ));
+        }
+
+        let header: Vec<usize> = fragment[..64]
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]) as usize)
+            .collect();
+        let segments = header[0];
+        let bytes = info.bytes_per_sample();
+        let samples = info.samples_per_pixel as usize;
+
+        if segments != bytes * samples || segments > 15 {
+            return Err(DicomError::InvalidValue(format!(
+                "Expected {} RLE segments, found {}",
+                bytes * samples,
+                segments
+            )));
+        }
+
+        let pixels = info.pixel_count();
+        let mut frame = vec![0u8; info.frame_size()];
+
+        for segment in 0..segments {
+            let start = header[segment + 1];
+            let end = if segment + 1 < segments {
+                header[segment + 2]
+            } else {
+                fragment.len()
+            };
+            if start > end || end > fragment.len() {
+                return Err(DicomError::InvalidLength(format!(
+                    "RLE segment {} out of bounds",
+                    segment
+                )));
+            }
+
+            // Segments hold the most significant byte first, output is little endian
+            let decoded = RleCodec::unpack_bits(&fragment[start..end], pixels);
+            let sample = segment / bytes;
+            let byte = bytes - 1 - segment % bytes;
+            for (pixel, value) in decoded.into_iter().enumerate() {
+                frame[(pixel * samples + sample) * bytes + byte] = value;
+            }
+        }
+
+        Ok(frame)
+    }
+
+    fn encode(
+        &self,
+        _transfer_syntax: &str,
+        frame: &[u8],
+        info: &FrameInfo,
+    ) -> DicomResult<Vec<u8>> {
+        if frame.len() < info.frame_size() {
+            return Err(DicomError::InvalidLength(format!(
+                "Frame holds {} bytes, expected {}",
+                frame.len(),
+                info.frame_size()
+            )));
+        }
+
+        let bytes = info.bytes_per_sample();
+        let samples = info.samples_per_pixel as usize;
+        let pixels = info.pixel_count();
+        let segments = bytes * samples;
+
+        // The header has room for 15 segment offsets
+        if segments > 15 {
+            return Err(DicomError::InvalidValue(format!(
+                "At most 15 RLE segments, {} needed",
+                segments
+            )));
+        }
+
+        let mut header = [0u32; 16];
+        let mut body = Vec::new();
+        header[0] = segments as u32;
+
+        for segment in 0..segments {
+            let sample = segment / bytes;
+            let byte = bytes - 1 - segment % bytes;
+            let plane: Vec<u8> = (0..pixels)
+                .map(|pixel| frame[(pixel * samples + sample) * bytes + byte])
+                .collect();
+
+            header[segment + 1] = (64 + body.len()) as u32;
+            body.extend(RleCodec::pack_bits(&plane));
+        }
+
+        let mut output: Vec<u8> = header.iter().flat_map(|word| word.to_le_bytes()).collect();
+        output.extend(body);
+        Ok(output)
+    }
+}
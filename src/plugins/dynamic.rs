@@ -0,0 +1,324 @@
+use std::{
+    ffi::{c_char, CStr, OsStr},
+    fs,
+    path::Path,
+};
+
+use libloading::{Library, Symbol};
+
+use crate::core::{
+    dataset::Dataset,
+    error::{DicomError, DicomResult},
+    json,
+};
+
+// Bumped whenever PluginDescriptor or the calling convention changes
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+pub const PLUGIN_ENTRY_SYMBOL: &[u8] = b"dicom_plugin_descriptor\0";
+
+// Return codes of PluginDescriptor::process
+pub const PLUGIN_MODIFIED: i32 = 0;
+pub const PLUGIN_UNCHANGED: i32 = 1;
+pub const PLUGIN_REJECTED: i32 = 2;
+pub const PLUGIN_FAILED: i32 = -1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginKind {
+    Coercion,
+    RoutingFilter,
+    AnonymizerExtension,
+}
+
+impl PluginKind {
+    pub fn from_raw(kind: u32) -> Option<Self> {
+        match kind {
+            1 => Some(PluginKind::Coercion),
+            2 => Some(PluginKind::RoutingFilter),
+            3 => Some(PluginKind::AnonymizerExtension),
+            _ => None,
+        }
+    }
+
+    pub fn as_raw(&self) -> u32 {
+        match self {
+            PluginKind::Coercion => 1,
+            PluginKind::RoutingFilter => 2,
+            PluginKind::AnonymizerExtension => 3,
+        }
+    }
+}
+
+// Memory handed out by a plugin, always given back through its own free_buffer
+#[repr(C)]
+#[derive(Debug)]
+pub struct PluginBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+    pub capacity: usize,
+}
+
+impl PluginBuffer {
+    pub fn empty() -> Self {
+        PluginBuffer {
+            data: std::ptr::null_mut(),
+            len: 0,
+            capacity: 0,
+        }
+    }
+
+    // For plugins written in Rust, pair it with free_plugin_buffer
+    pub fn from_vec(data: Vec<u8>) -> Self {
+        let mut data = std::mem::ManuallyDrop::new(data);
+        PluginBuffer {
+            data: data.as_mut_ptr(),
+            len: data.len(),
+            capacity: data.capacity(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_null() || self.len == 0
+    }
+
+    /// # Safety
+    /// `data` must point to `len` readable bytes or be null.
+    pub unsafe fn to_vec(&self) -> Vec<u8> {
+        if self.is_empty() {
+            return vec![];
+        }
+        std::slice::from_raw_parts(self.data, self.len).to_vec()
+    }
+}
+
+/// # Safety
+/// Only for buffers created with `PluginBuffer::from_vec` in the same library.
+pub unsafe extern "C" fn free_plugin_buffer(buffer: PluginBuffer) {
+    if !buffer.data.is_null() {
+        drop(Vec::from_raw_parts(
+            buffer.data,
+            buffer.len,
+            buffer.capacity,
+        ));
+    }
+}
+
+// Datasets cross the boundary as DICOM JSON (PS3.18 Annex F), so plugins do
+// not depend on the layout of any type in this crate
+#[repr(C)]
+pub struct PluginDescriptor {
+    pub abi_version: u32,
+    pub kind: u32,
+    pub name: *const c_char,
+    pub version: *const c_char,
+    // Receives the plugin configuration as UTF-8, may be absent
+    pub configure: Option<unsafe extern "C" fn(config: *const u8, len: usize) -> i32>,
+    pub process:
+        unsafe extern "C" fn(input: *const u8, len: usize, output: *mut PluginBuffer) -> i32,
+    pub free_buffer: unsafe extern "C" fn(buffer: PluginBuffer),
+}
+
+#[derive(Debug)]
+pub enum ProcessOutcome {
+    Unchanged,
+    Modified(Dataset),
+    Rejected(String),
+}
+
+pub struct DynamicPlugin {
+    name: String,
+    version: String,
+    kind: PluginKind,
+    descriptor: *const PluginDescriptor,
+    // Has to outlive the descriptor, which points into the library
+    _library: Library,
+}
+
+// The ABI contract requires plugins to be callable from any thread
+unsafe impl Send for DynamicPlugin {}
+unsafe impl Sync for DynamicPlugin {}
+
+impl DynamicPlugin {
+    /// # Safety
+    /// Loading a library runs its initialisers, and the library has to honour
+    /// the descriptor contract for every later call.
+    pub unsafe fn load<P: AsRef<OsStr>>(path: P) -> DicomResult<Self> {
+        let display = Path::new(path.as_ref()).display().to_string();
+        let library = Library::new(path.as_ref()).map_err(|error| DicomError::External {
+            context: display.clone(),
+            source: Box::new(error),
+        })?;
+
+        let entry: Symbol<unsafe extern "C" fn() -> *const PluginDescriptor> = library
+            .get(PLUGIN_ENTRY_SYMBOL)
+            .map_err(|error| DicomError::InvalidFile(format!("{}: {}", display, error)))?;
+        let descriptor = entry();
+
+        if descriptor.is_null() {
+            return Err(DicomError::InvalidFile(format!(
+                "{}: plugin returned no descriptor",
+                display
+            )));
+        }
+        if (*descriptor).abi_version != PLUGIN_ABI_VERSION {
+            return Err(DicomError::InvalidFile(format!(
+                "{}: plugin ABI {} does not match {}",
+                display,
+                (*descriptor).abi_version,
+                PLUGIN_ABI_VERSION
+            )));
+        }
+
+        let kind = PluginKind::from_raw((*descriptor).kind).ok_or_else(|| {
+            DicomError::InvalidFile(format!(
+                "{}: unknown plugin kind {}",
+                display,
+                (*descriptor).kind
+            ))
+        })?;
+
+        Ok(DynamicPlugin {
+            name: c_string((*descriptor).name).unwrap_or(display),
+            version: c_string((*descriptor).version).unwrap_or_default(),
+            kind,
+            descriptor,
+            _library: library,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn kind(&self) -> PluginKind {
+        self.kind
+    }
+
+    pub fn configure(&self, config: &str) -> DicomResult<()> {
+        // SAFETY: the descriptor lives as long as the library we hold
+        let Some(configure) = (unsafe { (*self.descriptor).configure }) else {
+            return Ok(());
+        };
+
+        match unsafe { configure(config.as_ptr(), config.len()) } {
+            PLUGIN_FAILED => Err(DicomError::Error(format!(
+                "Plugin {} rejected its configuration",
+                self.name
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn process(&self, dataset: &Dataset) -> DicomResult<ProcessOutcome> {
+        let input = json::to_vec(dataset)?;
+        let mut output = PluginBuffer::empty();
+
+        // SAFETY: the descriptor lives as long as the library we hold, and
+        // whatever the plugin hands back is released by the plugin itself
+        let (code, data) = unsafe {
+            let descriptor = &*self.descriptor;
+            let code = (descriptor.process)(input.as_ptr(), input.len(), &mut output);
+            let data = output.to_vec();
+            if !output.data.is_null() {
+                (descriptor.free_buffer)(output);
+            }
+            (code, data)
+        };
+
+        match code {
+            PLUGIN_MODIFIED => Ok(ProcessOutcome::Modified(json::from_slice(&data)?)),
+            PLUGIN_UNCHANGED => Ok(ProcessOutcome::Unchanged),
+            PLUGIN_REJECTED => Ok(ProcessOutcome::Rejected(
+                String::from_utf8_lossy(&data).into_owned(),
+            )),
+            _ => Err(DicomError::Error(format!(
+                "Plugin {} failed with {}: {}",
+                self.name,
+                code,
+                String::from_utf8_lossy(&data)
+            ))),
+        }
+    }
+}
+
+unsafe fn c_string(pointer: *const c_char) -> Option<String> {
+    if pointer.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(pointer).to_string_lossy().into_owned())
+    }
+}
+
+#[derive(Default)]
+pub struct PluginHost {
+    plugins: Vec<DynamicPlugin>,
+}
+
+impl PluginHost {
+    pub fn new() -> Self {
+        PluginHost::default()
+    }
+
+    /// # Safety
+    /// See `DynamicPlugin::load`.
+    pub unsafe fn load<P: AsRef<OsStr>>(&mut self, path: P) -> DicomResult<&DynamicPlugin> {
+        self.plugins.push(DynamicPlugin::load(path)?);
+        Ok(self.plugins.last().unwrap())
+    }
+
+    // Loads every shared library in the directory, in file name order so the
+    // processing order is predictable across hosts
+    /// # Safety
+    /// See `DynamicPlugin::load`.
+    pub unsafe fn load_directory<P: AsRef<Path>>(&mut self, directory: P) -> DicomResult<usize> {
+        let mut paths: Vec<_> = fs::read_dir(directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == std::env::consts::DLL_EXTENSION)
+            })
+            .collect();
+        paths.sort();
+
+        for path in &paths {
+            self.load(path)?;
+        }
+
+        Ok(paths.len())
+    }
+
+    pub fn plugins(&self) -> &[DynamicPlugin] {
+        &self.plugins
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DynamicPlugin> {
+        self.plugins.iter().find(|plugin| plugin.name() == name)
+    }
+
+    pub fn of_kind(&self, kind: PluginKind) -> impl Iterator<Item = &DynamicPlugin> {
+        self.plugins
+            .iter()
+            .filter(move |plugin| plugin.kind() == kind)
+    }
+
+    // Chains every plugin of the kind, stopping at the first rejection
+    pub fn process(&self, kind: PluginKind, dataset: &Dataset) -> DicomResult<ProcessOutcome> {
+        let mut current: Option<Dataset> = None;
+
+        for plugin in self.of_kind(kind) {
+            match plugin.process(current.as_ref().unwrap_or(dataset))? {
+                ProcessOutcome::Unchanged => {}
+                ProcessOutcome::Modified(modified) => current = Some(modified),
+                rejected @ ProcessOutcome::Rejected(_) => return Ok(rejected),
+            }
+        }
+
+        Ok(match current {
+            Some(modified) => ProcessOutcome::Modified(modified),
+            None => ProcessOutcome::Unchanged,
+        })
+    }
+}
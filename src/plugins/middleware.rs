@@ -0,0 +1,280 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::core::{
+    dataset::Dataset,
+    error::{DicomError, DicomResult},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DatasetSource {
+    StoreScp {
+        calling_ae: String,
+        called_ae: String,
+    },
+    StowServer {
+        remote_addr: Option<String>,
+        study_uid: Option<String>,
+    },
+    FileImport(PathBuf),
+    Other(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessingContext {
+    pub source: DatasetSource,
+    // Free form values handlers can use to talk to the ones after them
+    pub attributes: HashMap<String, String>,
+}
+
+impl ProcessingContext {
+    pub fn new(source: DatasetSource) -> Self {
+        ProcessingContext {
+            source,
+            attributes: HashMap::new(),
+        }
+    }
+
+    pub fn with_attribute(mut self, key: &str, value: &str) -> Self {
+        self.attributes.insert(key.to_string(), value.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Continue,
+    // Accept the dataset as it is, skipping the remaining handlers
+    Stop,
+    Reject(String),
+}
+
+pub trait Middleware: Send + Sync {
+    fn name(&self) -> &str;
+    fn handle(
+        &self,
+        context: &mut ProcessingContext,
+        dataset: &mut Dataset,
+    ) -> DicomResult<Verdict>;
+}
+
+pub struct FnMiddleware<F> {
+    name: String,
+    handler: F,
+}
+
+impl<F> FnMiddleware<F>
+where
+    F: Fn(&mut ProcessingContext, &mut Dataset) -> DicomResult<Verdict> + Send + Sync,
+{
+    pub fn new(name: &str, handler: F) -> Self {
+        FnMiddleware {
+            name: name.to_string(),
+            handler,
+        }
+    }
+}
+
+impl<F> Middleware for FnMiddleware<F>
+where
+    F: Fn(&mut ProcessingContext, &mut Dataset) -> DicomResult<Verdict> + Send + Sync,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn handle(
+        &self,
+        context: &mut ProcessingContext,
+        dataset: &mut Dataset,
+    ) -> DicomResult<Verdict> {
+        (self.handler)(context, dataset)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct HandlerMetrics {
+    calls: AtomicU64,
+    stopped: AtomicU64,
+    rejected: AtomicU64,
+    failed: AtomicU64,
+    nanos: AtomicU64,
+}
+
+impl HandlerMetrics {
+    fn record(&self, result: &DicomResult<Verdict>, elapsed: Duration) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+
+        match result {
+            Ok(Verdict::Continue) => {}
+            Ok(Verdict::Stop) => {
+                self.stopped.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(Verdict::Reject(_)) => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsSnapshot {
+    pub name: String,
+    pub calls: u64,
+    pub stopped: u64,
+    pub rejected: u64,
+    pub failed: u64,
+    pub total_time: Duration,
+}
+
+impl MetricsSnapshot {
+    pub fn average_time(&self) -> Duration {
+        if self.calls == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_nanos((self.total_time.as_nanos() / self.calls as u128) as u64)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PipelineOutcome {
+    Accepted,
+    Rejected { handler: String, reason: String },
+}
+
+impl PipelineOutcome {
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, PipelineOutcome::Accepted)
+    }
+}
+
+#[derive(Clone)]
+struct Stage {
+    handler: Arc<dyn Middleware>,
+    metrics: Arc<HandlerMetrics>,
+}
+
+// Clones share the handlers and their metrics
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Pipeline::default()
+    }
+
+    pub fn with<M: Middleware + 'static>(self, handler: M) -> Self {
+        self.with_arc(Arc::new(handler))
+    }
+
+    pub fn with_arc(mut self, handler: Arc<dyn Middleware>) -> Self {
+        self.push(handler);
+        self
+    }
+
+    pub fn push(&mut self, handler: Arc<dyn Middleware>) {
+        self.stages.push(Stage {
+            handler,
+            metrics: Arc::new(HandlerMetrics::default()),
+        });
+    }
+
+    pub fn with_fn<F>(self, name: &str, handler: F) -> Self
+    where
+        F: Fn(&mut ProcessingContext, &mut Dataset) -> DicomResult<Verdict> + Send + Sync + 'static,
+    {
+        self.with(FnMiddleware::new(name, handler))
+    }
+
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    pub fn run(
+        &self,
+        context: &mut ProcessingContext,
+        dataset: &mut Dataset,
+    ) -> DicomResult<PipelineOutcome> {
+        for stage in self.stages.iter() {
+            let started = Instant::now();
+            let result = stage.handler.handle(context, dataset);
+            stage.metrics.record(&result, started.elapsed());
+
+            match result {
+                Ok(Verdict::Continue) => {}
+                Ok(Verdict::Stop) => break,
+                Ok(Verdict::Reject(reason)) => {
+                    return Ok(PipelineOutcome::Rejected {
+                        handler: stage.handler.name().to_string(),
+                        reason,
+                    })
+                }
+                Err(error) => {
+                    return Err(DicomError::Error(format!(
+                        "Middleware {} failed: {}",
+                        stage.handler.name(),
+                        error
+                    )))
+                }
+            }
+        }
+
+        Ok(PipelineOutcome::Accepted)
+    }
+
+    pub fn metrics(&self) -> Vec<MetricsSnapshot> {
+        self.stages
+            .iter()
+            .map(|stage| MetricsSnapshot {
+                name: stage.handler.name().to_string(),
+                calls: stage.metrics.calls.load(Ordering::Relaxed),
+                stopped: stage.metrics.stopped.load(Ordering::Relaxed),
+                rejected: stage.metrics.rejected.load(Ordering::Relaxed),
+                failed: stage.metrics.failed.load(Ordering::Relaxed),
+                total_time: Duration::from_nanos(stage.metrics.nanos.load(Ordering::Relaxed)),
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "dynamic-plugins")]
+impl Middleware for super::dynamic::DynamicPlugin {
+    fn name(&self) -> &str {
+        super::dynamic::DynamicPlugin::name(self)
+    }
+
+    fn handle(
+        &self,
+        _context: &mut ProcessingContext,
+        dataset: &mut Dataset,
+    ) -> DicomResult<Verdict> {
+        use super::dynamic::ProcessOutcome;
+
+        match self.process(dataset)? {
+            ProcessOutcome::Unchanged => {}
+            ProcessOutcome::Modified(modified) => *dataset = modified,
+            ProcessOutcome::Rejected(reason) => return Ok(Verdict::Reject(reason)),
+        }
+
+        Ok(Verdict::Continue)
+    }
+}
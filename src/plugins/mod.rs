@@ -0,0 +1,7 @@
+pub mod codec;
+#[cfg(feature = "dynamic-plugins")]
+pub mod dynamic;
+pub mod middleware;
+
+#[cfg(any(all(feature = "serde", feature = "toml"), feature = "default"))]
+pub mod rules;
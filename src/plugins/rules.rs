@@ -0,0 +1,577 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{Arc, Mutex, PoisonError, RwLock},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use serde::Deserialize;
+
+use super::middleware::{Middleware, ProcessingContext, Verdict};
+use crate::core::{
+    dataset::Dataset,
+    dictionary::{self, parse_tag},
+    element::DicomElement,
+    error::{DicomError, DicomResult},
+    meta::SOP_INSTANCE_UID,
+    person_name, reader,
+    tag::VisualRepresentation,
+};
+
+// Context attribute the rules middleware leaves the routing destinations in,
+// comma separated
+pub const DESTINATIONS_ATTRIBUTE: &str = "destinations";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct RulesFile {
+    coerce: Vec<CoercionEntry>,
+    route: Vec<RouteEntry>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct CoercionEntry {
+    name: String,
+    when: BTreeMap<String, String>,
+    set: BTreeMap<String, String>,
+    remove: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct RouteEntry {
+    name: String,
+    when: BTreeMap<String, String>,
+    destinations: Vec<String>,
+}
+
+// An element whose value matches a pattern, '*' and '?' as wildcards. An
+// empty pattern matches elements that are absent or empty
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub tag: (u16, u16),
+    pub pattern: String,
+}
+
+impl Condition {
+    pub fn matches(&self, dataset: &Dataset) -> bool {
+        let value = dataset.string(self.tag).unwrap_or_default();
+        if self.pattern.is_empty() {
+            return value.trim().is_empty();
+        }
+        person_name::wildcard(&self.pattern, value.trim())
+    }
+}
+
+fn conditions(rule: &str, when: &BTreeMap<String, String>) -> DicomResult<Vec<Condition>> {
+    when.iter()
+        .map(|(name, pattern)| {
+            Ok(Condition {
+                tag: parse_tag(name).map_err(|error| invalid_rule(rule, error))?,
+                pattern: pattern.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+fn invalid_rule(rule: &str, error: impl fmt::Display) -> DicomError {
+    DicomError::InvalidFile(format!("Rule {}: {}", rule, error))
+}
+
+// Elements written into or removed from the datasets the conditions match
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoercionRule {
+    pub name: String,
+    pub conditions: Vec<Condition>,
+    // Empty values leave the element present but empty
+    pub set: Vec<((u16, u16), String)>,
+    pub remove: Vec<(u16, u16)>,
+}
+
+impl CoercionRule {
+    pub fn applies(&self, dataset: &Dataset) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(dataset))
+    }
+
+    // Tags whose value the rule changed
+    pub fn apply(&self, dataset: &mut Dataset) -> DicomResult<Vec<(u16, u16)>> {
+        let mut changed = Vec::new();
+        for (tag, value) in &self.set {
+            let vr = match dataset.find(*tag) {
+                Some(element) => element.vr().code(),
+                None => dictionary::vr_of(*tag),
+            };
+            if dataset.string(*tag).as_deref() == Some(value.as_str()) {
+                continue;
+            }
+            if value.is_empty() {
+                dataset.set_empty_as(*tag, vr);
+            } else {
+                let value = VisualRepresentation::try_from_string(vr, value)?;
+                dataset.put(Rc::new(DicomElement::new(*tag, value)));
+            }
+            changed.push(*tag);
+        }
+        for tag in &self.remove {
+            if dataset.remove(*tag).is_some() {
+                changed.push(*tag);
+            }
+        }
+        Ok(changed)
+    }
+}
+
+// AE titles or peer names the datasets the conditions match are sent to
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutingRule {
+    pub name: String,
+    pub conditions: Vec<Condition>,
+    pub destinations: Vec<String>,
+}
+
+impl RoutingRule {
+    pub fn applies(&self, dataset: &Dataset) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(dataset))
+    }
+}
+
+// Names of the coercions that applied, with the tags each changed
+pub type AppliedCoercions = Vec<(String, Vec<(u16, u16)>)>;
+
+// Coercion and routing rules, loaded from TOML such as
+//
+// [[coerce]]
+// name = "fix-institution"
+// when = { StationName = "CT0?" }
+// set = { InstitutionName = "General Hospital" }
+// remove = ["OtherPatientIDs"]
+//
+// [[route]]
+// name = "ct-to-archive"
+// when = { Modality = "CT" }
+// destinations = ["archive"]
+//
+// Coercions apply in file order, routes are decided on the coerced dataset
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RuleSet {
+    pub coercions: Vec<CoercionRule>,
+    pub routes: Vec<RoutingRule>,
+}
+
+impl RuleSet {
+    pub fn new() -> Self {
+        RuleSet::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> DicomResult<Self> {
+        RuleSet::from_toml_str(&fs::read_to_string(path)?)
+    }
+
+    // Rejects rules that could only fail once applied, such as unknown
+    // keywords, values their VR cannot hold and routes going nowhere
+    pub fn from_toml_str(text: &str) -> DicomResult<Self> {
+        let file: RulesFile =
+            toml::from_str(text).map_err(|error| DicomError::InvalidFile(error.to_string()))?;
+
+        let mut names = BTreeSet::new();
+        let mut check_name = |name: &str| {
+            if name.trim().is_empty() {
+                return Err(DicomError::InvalidFile("Rule without a name".to_string()));
+            }
+            if !names.insert(name.to_string()) {
+                return Err(invalid_rule(name, "defined twice"));
+            }
+            Ok(())
+        };
+
+        let mut rules = RuleSet::new();
+        for entry in file.coerce {
+            check_name(&entry.name)?;
+            let mut set = Vec::new();
+            for (name, value) in &entry.set {
+                let tag = parse_tag(name).map_err(|error| invalid_rule(&entry.name, error))?;
+                let vr = dictionary::vr_of(tag);
+                if vr == "SQ" {
+                    return Err(invalid_rule(
+                        &entry.name,
+                        format!("{} is a sequence and cannot be set", name),
+                    ));
+                }
+                let value = value.trim().to_string();
+                if !value.is_empty() {
+                    VisualRepresentation::try_from_string(vr, &value)
+                        .map_err(|error| invalid_rule(&entry.name, error))?;
+                }
+                set.push((tag, value));
+            }
+            let remove = entry
+                .remove
+                .iter()
+                .map(|name| parse_tag(name).map_err(|error| invalid_rule(&entry.name, error)))
+                .collect::<DicomResult<Vec<_>>>()?;
+
+            rules.coercions.push(CoercionRule {
+                conditions: conditions(&entry.name, &entry.when)?,
+                name: entry.name,
+                set,
+                remove,
+            });
+        }
+
+        for entry in file.route {
+            check_name(&entry.name)?;
+            let destinations: Vec<String> = entry
+                .destinations
+                .iter()
+                .map(|destination| destination.trim().to_string())
+                .filter(|destination| !destination.is_empty())
+                .collect();
+            if destinations.is_empty() {
+                return Err(invalid_rule(&entry.name, "no destinations"));
+            }
+            rules.routes.push(RoutingRule {
+                conditions: conditions(&entry.name, &entry.when)?,
+                name: entry.name,
+                destinations,
+            });
+        }
+
+        Ok(rules)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coercions.is_empty() && self.routes.is_empty()
+    }
+
+    // Applies the coercions whose conditions match, in file order
+    pub fn coerce(&self, dataset: &mut Dataset) -> DicomResult<AppliedCoercions> {
+        let mut applied = Vec::new();
+        for rule in &self.coercions {
+            if rule.applies(dataset) {
+                applied.push((rule.name.clone(), rule.apply(dataset)?));
+            }
+        }
+        Ok(applied)
+    }
+
+    // Destinations of every matching route, each once in rule order
+    pub fn destinations(&self, dataset: &Dataset) -> Vec<String> {
+        let mut destinations: Vec<String> = Vec::new();
+        for rule in self.routes.iter().filter(|rule| rule.applies(dataset)) {
+            for destination in &rule.destinations {
+                if !destinations.contains(destination) {
+                    destinations.push(destination.clone());
+                }
+            }
+        }
+        destinations
+    }
+
+    // What the rules would do to the datasets, which are left untouched
+    pub fn dry_run(&self, datasets: &[Dataset]) -> DryRunReport {
+        let mut report = DryRunReport {
+            coercions: self.coercions.len(),
+            routes: self.routes.len(),
+            ..DryRunReport::default()
+        };
+        let mut used = BTreeSet::new();
+
+        for dataset in datasets {
+            let mut coerced = dataset.clone();
+            let mut outcome = DryRunOutcome {
+                sop_instance_uid: dataset.string(SOP_INSTANCE_UID).unwrap_or_default(),
+                ..DryRunOutcome::default()
+            };
+            match self.coerce(&mut coerced) {
+                Ok(applied) => {
+                    for (name, changed) in applied {
+                        used.insert(name.clone());
+                        outcome.changed.extend(changed);
+                        outcome.coercions.push(name);
+                    }
+                    for rule in self.routes.iter().filter(|rule| rule.applies(&coerced)) {
+                        used.insert(rule.name.clone());
+                        outcome.routes.push(rule.name.clone());
+                    }
+                    outcome.destinations = self.destinations(&coerced);
+                }
+                Err(error) => outcome.error = Some(error.to_string()),
+            }
+            report.outcomes.push(outcome);
+        }
+
+        report.unused = self
+            .coercions
+            .iter()
+            .map(|rule| &rule.name)
+            .chain(self.routes.iter().map(|rule| &rule.name))
+            .filter(|name| !used.contains(*name))
+            .cloned()
+            .collect();
+        report
+    }
+}
+
+// What a rule set did to one sample dataset
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DryRunOutcome {
+    pub sop_instance_uid: String,
+    pub coercions: Vec<String>,
+    pub changed: Vec<(u16, u16)>,
+    pub routes: Vec<String>,
+    pub destinations: Vec<String>,
+    // Set when a coercion failed on the dataset
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DryRunReport {
+    pub coercions: usize,
+    pub routes: usize,
+    pub outcomes: Vec<DryRunOutcome>,
+    // Rules no sample matched, often a typo in a condition
+    pub unused: Vec<String>,
+}
+
+impl DryRunReport {
+    pub fn failures(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.error.is_some())
+            .count()
+    }
+}
+
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} coercion rules, {} routing rules, {} samples",
+            self.coercions,
+            self.routes,
+            self.outcomes.len()
+        )?;
+        for outcome in &self.outcomes {
+            let uid = if outcome.sop_instance_uid.is_empty() {
+                "(no SOP Instance UID)"
+            } else {
+                &outcome.sop_instance_uid
+            };
+            if let Some(error) = &outcome.error {
+                writeln!(f, "{}: failed, {}", uid, error)?;
+                continue;
+            }
+            let changed: Vec<String> = outcome
+                .changed
+                .iter()
+                .map(|tag| match dictionary::keyword(*tag) {
+                    Some(keyword) => keyword.to_string(),
+                    None => format!("({:04X},{:04X})", tag.0, tag.1),
+                })
+                .collect();
+            writeln!(
+                f,
+                "{}: coerced by [{}] changing [{}], routed to [{}]",
+                uid,
+                outcome.coercions.join(", "),
+                changed.join(", "),
+                outcome.destinations.join(", ")
+            )?;
+        }
+        if !self.unused.is_empty() {
+            writeln!(f, "Matched no sample: {}", self.unused.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Loaded {
+    rules: RwLock<Arc<RuleSet>>,
+    modified: Mutex<Option<SystemTime>>,
+}
+
+// A rule file that can be reloaded while associations use it. A new file is
+// validated and dry run against the sample datasets before it replaces the
+// rules; a file that does not load leaves the current rules in place.
+// Datasets being processed keep the rules they started with
+#[derive(Debug, Clone)]
+pub struct ReloadableRules {
+    path: PathBuf,
+    samples: Vec<PathBuf>,
+    loaded: Arc<Loaded>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl ReloadableRules {
+    pub fn load<P: AsRef<Path>>(path: P) -> DicomResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let stamp = modified(&path);
+        let rules = RuleSet::load(&path)?;
+        Ok(ReloadableRules {
+            path,
+            samples: Vec::new(),
+            loaded: Arc::new(Loaded {
+                rules: RwLock::new(Arc::new(rules)),
+                modified: Mutex::new(stamp),
+            }),
+        })
+    }
+
+    // DICOM files each reload is dry run against
+    pub fn with_samples(mut self, samples: Vec<PathBuf>) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn current(&self) -> Arc<RuleSet> {
+        self.loaded
+            .rules
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    // Loads the file again and swaps it in, once it validated and every
+    // sample coerced without error
+    pub fn reload(&self) -> DicomResult<DryRunReport> {
+        let stamp = modified(&self.path);
+        let rules = RuleSet::load(&self.path)?;
+
+        let mut samples = Vec::new();
+        for path in &self.samples {
+            samples.push(reader::read_file(&fs::read(path)?)?.dataset);
+        }
+        let report = rules.dry_run(&samples);
+        if report.failures() > 0 {
+            return Err(DicomError::InvalidFile(format!(
+                "Rules in {} failed on {} samples\n{}",
+                self.path.display(),
+                report.failures(),
+                report
+            )));
+        }
+
+        *self
+            .loaded
+            .rules
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Arc::new(rules);
+        *self
+            .loaded
+            .modified
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = stamp;
+        Ok(report)
+    }
+
+    // Reloads when the file changed since it was last loaded, None otherwise
+    pub fn reload_if_changed(&self) -> Option<DicomResult<DryRunReport>> {
+        let stamp = modified(&self.path);
+        let last = *self
+            .loaded
+            .modified
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if stamp.is_none() || stamp == last {
+            return None;
+        }
+        let result = self.reload();
+        if result.is_err() {
+            // Reported once, not on every poll until the file is fixed
+            *self
+                .loaded
+                .modified
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = stamp;
+        }
+        Some(result)
+    }
+
+    // Polls the file from a thread of its own, which ends once every clone
+    // of the rules was dropped
+    pub fn watch<F>(&self, interval: Duration, on_reload: F)
+    where
+        F: Fn(DicomResult<DryRunReport>) + Send + 'static,
+    {
+        let path = self.path.clone();
+        let samples = self.samples.clone();
+        let loaded = Arc::downgrade(&self.loaded);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let Some(loaded) = loaded.upgrade() else {
+                break;
+            };
+            let rules = ReloadableRules {
+                path: path.clone(),
+                samples: samples.clone(),
+                loaded,
+            };
+            if let Some(result) = rules.reload_if_changed() {
+                on_reload(result);
+            }
+        });
+    }
+
+    // Reloads on SIGHUP, how daemons are asked to read their configuration
+    // again
+    #[cfg(all(unix, any(feature = "net", feature = "default")))]
+    pub fn reload_on_hangup<F>(&self, on_reload: F) -> DicomResult<()>
+    where
+        F: Fn(DicomResult<DryRunReport>) + Send + 'static,
+    {
+        use tokio::{runtime::Builder, signal::unix};
+
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        let mut hangup = runtime.block_on(async { unix::signal(unix::SignalKind::hangup()) })?;
+
+        let rules = self.clone();
+        thread::spawn(move || {
+            while runtime.block_on(hangup.recv()).is_some() {
+                on_reload(rules.reload());
+            }
+        });
+        Ok(())
+    }
+}
+
+// Coerces each dataset and leaves its destinations in the context, under
+// DESTINATIONS_ATTRIBUTE
+impl Middleware for ReloadableRules {
+    fn name(&self) -> &str {
+        "rules"
+    }
+
+    fn handle(
+        &self,
+        context: &mut ProcessingContext,
+        dataset: &mut Dataset,
+    ) -> DicomResult<Verdict> {
+        let rules = self.current();
+        rules.coerce(dataset)?;
+        let destinations = rules.destinations(dataset);
+        if !destinations.is_empty() {
+            context
+                .attributes
+                .insert(DESTINATIONS_ATTRIBUTE.to_string(), destinations.join(","));
+        }
+        Ok(Verdict::Continue)
+    }
+}
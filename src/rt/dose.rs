@@ -0,0 +1,437 @@
+use std::fmt::Display;
+
+use super::structure::{Roi, RoiMask};
+use crate::{
+    core::{
+        dataset::Dataset,
+        error::{DicomError, DicomResult},
+        reader::DicomFile,
+    },
+    image::render::{frame_values, number_of_frames},
+    plugins::codec::FrameInfo,
+};
+
+pub const SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x0018);
+pub const REFERENCED_SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x1155);
+pub const IMAGE_POSITION_PATIENT: (u16, u16) = (0x0020, 0x0032);
+pub const IMAGE_ORIENTATION_PATIENT: (u16, u16) = (0x0020, 0x0037);
+pub const FRAME_OF_REFERENCE_UID: (u16, u16) = (0x0020, 0x0052);
+pub const PIXEL_SPACING: (u16, u16) = (0x0028, 0x0030);
+pub const DOSE_UNITS: (u16, u16) = (0x3004, 0x0002);
+pub const DOSE_TYPE: (u16, u16) = (0x3004, 0x0004);
+pub const DOSE_SUMMATION_TYPE: (u16, u16) = (0x3004, 0x000A);
+pub const GRID_FRAME_OFFSET_VECTOR: (u16, u16) = (0x3004, 0x000C);
+pub const DOSE_GRID_SCALING: (u16, u16) = (0x3004, 0x000E);
+pub const REFERENCED_RT_PLAN_SEQUENCE: (u16, u16) = (0x300C, 0x0002);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoseSummationType {
+    Plan,
+    MultiPlan,
+    PlanOverview,
+    Fraction,
+    Beam,
+    Brachy,
+    FractionSession,
+    BeamSession,
+    BrachySession,
+    ControlPoint,
+    Record,
+}
+
+impl DoseSummationType {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "PLAN" => Some(DoseSummationType::Plan),
+            "MULTI_PLAN" => Some(DoseSummationType::MultiPlan),
+            "PLAN_OVERVIEW" => Some(DoseSummationType::PlanOverview),
+            "FRACTION" => Some(DoseSummationType::Fraction),
+            "BEAM" => Some(DoseSummationType::Beam),
+            "BRACHY" => Some(DoseSummationType::Brachy),
+            "FRACTION_SESSION" => Some(DoseSummationType::FractionSession),
+            "BEAM_SESSION" => Some(DoseSummationType::BeamSession),
+            "BRACHY_SESSION" => Some(DoseSummationType::BrachySession),
+            "CONTROL_POINT" => Some(DoseSummationType::ControlPoint),
+            "RECORD" => Some(DoseSummationType::Record),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DoseSummationType::Plan => "PLAN",
+            DoseSummationType::MultiPlan => "MULTI_PLAN",
+            DoseSummationType::PlanOverview => "PLAN_OVERVIEW",
+            DoseSummationType::Fraction => "FRACTION",
+            DoseSummationType::Beam => "BEAM",
+            DoseSummationType::Brachy => "BRACHY",
+            DoseSummationType::FractionSession => "FRACTION_SESSION",
+            DoseSummationType::BeamSession => "BEAM_SESSION",
+            DoseSummationType::BrachySession => "BRACHY_SESSION",
+            DoseSummationType::ControlPoint => "CONTROL_POINT",
+            DoseSummationType::Record => "RECORD",
+        }
+    }
+}
+
+impl Display for DoseSummationType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// The dose of an RT Dose object in its units, scaled by the Dose Grid
+// Scaling, on frames stacked along the normal of their plane in patient
+// space
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoseGrid {
+    pub rows: usize,
+    pub columns: usize,
+    // Image Position (Patient) of the first voxel of the first frame, mm
+    pub origin: [f64; 3],
+    // Along a row, then down a column
+    pub row_direction: [f64; 3],
+    pub column_direction: [f64; 3],
+    pub normal: [f64; 3],
+    // Between rows, then between columns, mm
+    pub pixel_spacing: (f64, f64),
+    // Distance of each frame from the first along the normal, mm
+    pub frame_offsets: Vec<f64>,
+    // Frame by frame, row by row
+    pub values: Vec<f64>,
+    // GY or RELATIVE
+    pub units: String,
+    // PHYSICAL, EFFECTIVE or ERROR
+    pub dose_type: String,
+    pub summation_type: Option<DoseSummationType>,
+    pub sop_instance_uid: String,
+    pub frame_of_reference_uid: String,
+    pub referenced_plan_uid: Option<String>,
+}
+
+impl DoseGrid {
+    pub fn from_file(file: &DicomFile) -> DicomResult<Self> {
+        let dataset = &file.dataset;
+        let info = FrameInfo::from_dataset(dataset)?;
+        let frames = number_of_frames(dataset);
+
+        let origin = triple(dataset, IMAGE_POSITION_PATIENT)?;
+        let orientation = numbers(dataset, IMAGE_ORIENTATION_PATIENT);
+        let (row_direction, column_direction) = match orientation[..] {
+            [a, b, c, d, e, f] => ([a, b, c], [d, e, f]),
+            _ => return Err(missing(IMAGE_ORIENTATION_PATIENT)),
+        };
+        let normal = cross(row_direction, column_direction);
+        let pixel_spacing = match numbers(dataset, PIXEL_SPACING)[..] {
+            [rows, columns] if rows > 0.0 && columns > 0.0 => (rows, columns),
+            _ => return Err(missing(PIXEL_SPACING)),
+        };
+
+        // Offsets not starting at 0 are positions along the normal, only
+        // found on transverse grids
+        let mut frame_offsets = numbers(dataset, GRID_FRAME_OFFSET_VECTOR);
+        if frame_offsets.is_empty() && frames == 1 {
+            frame_offsets.push(0.0);
+        }
+        if frames == 0 || frame_offsets.len() != frames {
+            return Err(DicomError::InvalidDataset(format!(
+                "Grid Frame Offset Vector of {} values for {} frames",
+                frame_offsets.len(),
+                frames
+            )));
+        }
+        if frame_offsets[0] != 0.0 {
+            let start = dot(origin, normal);
+            for offset in &mut frame_offsets {
+                *offset -= start;
+            }
+        }
+
+        let scaling = numbers(dataset, DOSE_GRID_SCALING)
+            .first()
+            .copied()
+            .unwrap_or(1.0);
+        let mut values = Vec::with_capacity(info.pixel_count() * frames);
+        for frame in 0..frames {
+            values.extend(
+                frame_values(file, frame)?
+                    .into_iter()
+                    .map(|value| value * scaling),
+            );
+        }
+
+        Ok(DoseGrid {
+            rows: info.rows as usize,
+            columns: info.columns as usize,
+            origin,
+            row_direction,
+            column_direction,
+            normal,
+            pixel_spacing,
+            frame_offsets,
+            values,
+            units: text(dataset, DOSE_UNITS),
+            dose_type: text(dataset, DOSE_TYPE),
+            summation_type: dataset
+                .string(DOSE_SUMMATION_TYPE)
+                .and_then(|value| DoseSummationType::parse(&value)),
+            sop_instance_uid: text(dataset, SOP_INSTANCE_UID),
+            frame_of_reference_uid: text(dataset, FRAME_OF_REFERENCE_UID),
+            referenced_plan_uid: dataset
+                .sequence(REFERENCED_RT_PLAN_SEQUENCE)
+                .first()
+                .and_then(|item| item.string(REFERENCED_SOP_INSTANCE_UID)),
+        })
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frame_offsets.len()
+    }
+
+    pub fn value(&self, column: usize, row: usize, frame: usize) -> Option<f64> {
+        if column >= self.columns || row >= self.rows {
+            return None;
+        }
+        self.values
+            .get((frame * self.rows + row) * self.columns + column)
+            .copied()
+    }
+
+    pub fn max(&self) -> f64 {
+        self.values.iter().copied().fold(0.0, f64::max)
+    }
+
+    // Patient coordinates of the centre of a voxel
+    pub fn position(&self, column: usize, row: usize, frame: usize) -> [f64; 3] {
+        let (row_spacing, column_spacing) = self.pixel_spacing;
+        let along = column as f64 * column_spacing;
+        let down = row as f64 * row_spacing;
+        let across = self.frame_offsets.get(frame).copied().unwrap_or(0.0);
+        [0, 1, 2].map(|axis| {
+            self.origin[axis]
+                + self.row_direction[axis] * along
+                + self.column_direction[axis] * down
+                + self.normal[axis] * across
+        })
+    }
+
+    // Fractional column, row and frame of a patient point, None outside the
+    // grid
+    pub fn index_of(&self, point: [f64; 3]) -> Option<[f64; 3]> {
+        let relative = [0, 1, 2].map(|axis| point[axis] - self.origin[axis]);
+        let (row_spacing, column_spacing) = self.pixel_spacing;
+        let column = dot(relative, self.row_direction) / column_spacing;
+        let row = dot(relative, self.column_direction) / row_spacing;
+        let across = dot(relative, self.normal);
+
+        const EPSILON: f64 = 1e-6;
+        if column < -EPSILON
+            || row < -EPSILON
+            || column > (self.columns - 1) as f64 + EPSILON
+            || row > (self.rows - 1) as f64 + EPSILON
+        {
+            return None;
+        }
+
+        // Offsets may run either way along the normal
+        let frame = if self.frames() == 1 {
+            ((across - self.frame_offsets[0]).abs() < EPSILON).then_some(0.0)?
+        } else {
+            self.frame_offsets
+                .windows(2)
+                .enumerate()
+                .find_map(|(index, pair)| {
+                    let (low, high) = (pair[0].min(pair[1]), pair[0].max(pair[1]));
+                    if across < low - EPSILON || across > high + EPSILON {
+                        return None;
+                    }
+                    Some(index as f64 + ((across - pair[0]) / (pair[1] - pair[0])).clamp(0.0, 1.0))
+                })?
+        };
+        Some([
+            column.clamp(0.0, (self.columns - 1) as f64),
+            row.clamp(0.0, (self.rows - 1) as f64),
+            frame,
+        ])
+    }
+
+    // Dose at a patient point, trilinear between the eight voxels around it
+    pub fn sample(&self, point: [f64; 3]) -> Option<f64> {
+        let [column, row, frame] = self.index_of(point)?;
+        let corner = |value: f64, size: usize| {
+            let low = (value.floor() as usize).min(size.saturating_sub(1));
+            let high = (low + 1).min(size.saturating_sub(1));
+            (low, high, value - low as f64)
+        };
+        let (c0, c1, tc) = corner(column, self.columns);
+        let (r0, r1, tr) = corner(row, self.rows);
+        let (f0, f1, tf) = corner(frame, self.frames());
+
+        let plane = |frame: usize| -> Option<f64> {
+            let top = lerp(self.value(c0, r0, frame)?, self.value(c1, r0, frame)?, tc);
+            let bottom = lerp(self.value(c0, r1, frame)?, self.value(c1, r1, frame)?, tc);
+            Some(lerp(top, bottom, tr))
+        };
+        Some(lerp(plane(f0)?, plane(f1)?, tf))
+    }
+
+    // Voxels whose centre lies inside the ROI, in the order of `values`
+    pub fn mask(&self, roi: &Roi) -> Vec<bool> {
+        self.mask_with(&roi.mask())
+    }
+
+    pub fn mask_with(&self, mask: &RoiMask) -> Vec<bool> {
+        let mut voxels = Vec::with_capacity(self.values.len());
+        for frame in 0..self.frames() {
+            for row in 0..self.rows {
+                for column in 0..self.columns {
+                    voxels.push(mask.contains(self.position(column, row, frame)));
+                }
+            }
+        }
+        voxels
+    }
+
+    // Cubic centimetres
+    pub fn voxel_volume(&self) -> f64 {
+        let thickness = match self.frame_offsets.as_slice() {
+            [first, .., last] => (last - first).abs() / (self.frames() - 1) as f64,
+            _ => 1.0,
+        };
+        self.pixel_spacing.0 * self.pixel_spacing.1 * thickness / 1000.0
+    }
+
+    pub fn dvh(&self, roi: &Roi, bin_width: f64) -> DicomResult<Dvh> {
+        Dvh::from_mask(self, &roi.name, &self.mask(roi), bin_width)
+    }
+}
+
+// Dose volume histogram of an ROI, by the voxels of the dose grid inside it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dvh {
+    pub roi_name: String,
+    pub bin_width: f64,
+    // Differential, cm3 with a dose in each bin starting at 0
+    pub volumes: Vec<f64>,
+    pub total_volume: f64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+impl Dvh {
+    pub fn from_mask(
+        grid: &DoseGrid,
+        roi_name: &str,
+        mask: &[bool],
+        bin_width: f64,
+    ) -> DicomResult<Self> {
+        if bin_width <= 0.0 {
+            return Err(DicomError::InvalidValue(format!(
+                "DVH bin width of {}",
+                bin_width
+            )));
+        }
+        let doses: Vec<f64> = grid
+            .values
+            .iter()
+            .zip(mask)
+            .filter(|(_, inside)| **inside)
+            .map(|(dose, _)| *dose)
+            .collect();
+        if doses.is_empty() {
+            return Err(DicomError::InvalidValue(format!(
+                "ROI {} covers no voxel of the dose grid",
+                roi_name
+            )));
+        }
+
+        let voxel = grid.voxel_volume();
+        let max = doses.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let mut volumes = vec![0.0; (max / bin_width).floor() as usize + 1];
+        for dose in &doses {
+            volumes[(dose.max(0.0) / bin_width).floor() as usize] += voxel;
+        }
+        Ok(Dvh {
+            roi_name: roi_name.to_string(),
+            bin_width,
+            volumes,
+            total_volume: doses.len() as f64 * voxel,
+            min: doses.iter().copied().fold(f64::INFINITY, f64::min),
+            max,
+            mean: doses.iter().sum::<f64>() / doses.len() as f64,
+        })
+    }
+
+    // Volume receiving at least the dose of each bin
+    pub fn cumulative(&self) -> Vec<f64> {
+        let mut remaining = self.total_volume;
+        self.volumes
+            .iter()
+            .map(|volume| {
+                let at_least = remaining;
+                remaining -= volume;
+                at_least
+            })
+            .collect()
+    }
+
+    // Percentage of the volume receiving at least the dose, V20 and the like
+    pub fn volume_at_dose(&self, dose: f64) -> f64 {
+        let bin = (dose / self.bin_width).ceil().max(0.0) as usize;
+        let volume = self.cumulative().get(bin).copied().unwrap_or(0.0);
+        100.0 * volume / self.total_volume
+    }
+
+    // Least dose the hottest percentage of the volume receives, D95 and the
+    // like
+    pub fn dose_at_volume(&self, percent: f64) -> f64 {
+        let volume = self.total_volume * percent / 100.0;
+        let bins = self
+            .cumulative()
+            .iter()
+            .take_while(|at_least| **at_least >= volume - 1e-9)
+            .count();
+        bins.saturating_sub(1) as f64 * self.bin_width
+    }
+}
+
+fn numbers(dataset: &Dataset, tag: (u16, u16)) -> Vec<f64> {
+    dataset
+        .string(tag)
+        .unwrap_or_default()
+        .split('\\')
+        .filter_map(|value| value.trim().parse().ok())
+        .collect()
+}
+
+fn triple(dataset: &Dataset, tag: (u16, u16)) -> DicomResult<[f64; 3]> {
+    match numbers(dataset, tag)[..] {
+        [x, y, z] => Ok([x, y, z]),
+        _ => Err(missing(tag)),
+    }
+}
+
+fn text(dataset: &Dataset, tag: (u16, u16)) -> String {
+    dataset.string(tag).unwrap_or_default().trim().to_string()
+}
+
+fn missing(tag: (u16, u16)) -> DicomError {
+    DicomError::InvalidDataset(format!("RT Dose needs ({:04X},{:04X})", tag.0, tag.1))
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
@@ -0,0 +1,3 @@
+pub mod dose;
+pub mod plan;
+pub mod structure;
@@ -0,0 +1,284 @@
+use super::{dose::DoseGrid, structure::StructureSet};
+use crate::core::{
+    dataset::Dataset,
+    error::{DicomError, DicomResult},
+};
+
+pub const SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x0018);
+pub const REFERENCED_SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x1155);
+pub const FRAME_OF_REFERENCE_UID: (u16, u16) = (0x0020, 0x0052);
+pub const RT_PLAN_LABEL: (u16, u16) = (0x300A, 0x0002);
+pub const RT_PLAN_NAME: (u16, u16) = (0x300A, 0x0003);
+pub const RT_PLAN_GEOMETRY: (u16, u16) = (0x300A, 0x000C);
+pub const FRACTION_GROUP_SEQUENCE: (u16, u16) = (0x300A, 0x0070);
+pub const FRACTION_GROUP_NUMBER: (u16, u16) = (0x300A, 0x0071);
+pub const NUMBER_OF_FRACTIONS_PLANNED: (u16, u16) = (0x300A, 0x0078);
+pub const BEAM_METERSET: (u16, u16) = (0x300A, 0x0086);
+pub const BEAM_SEQUENCE: (u16, u16) = (0x300A, 0x00B0);
+pub const TREATMENT_MACHINE_NAME: (u16, u16) = (0x300A, 0x00B2);
+pub const PRIMARY_DOSIMETER_UNIT: (u16, u16) = (0x300A, 0x00B3);
+pub const BEAM_NUMBER: (u16, u16) = (0x300A, 0x00C0);
+pub const BEAM_NAME: (u16, u16) = (0x300A, 0x00C2);
+pub const BEAM_TYPE: (u16, u16) = (0x300A, 0x00C4);
+pub const RADIATION_TYPE: (u16, u16) = (0x300A, 0x00C6);
+pub const TREATMENT_DELIVERY_TYPE: (u16, u16) = (0x300A, 0x00CE);
+pub const FINAL_CUMULATIVE_METERSET_WEIGHT: (u16, u16) = (0x300A, 0x010E);
+pub const CONTROL_POINT_SEQUENCE: (u16, u16) = (0x300A, 0x0111);
+pub const CONTROL_POINT_INDEX: (u16, u16) = (0x300A, 0x0112);
+pub const NOMINAL_BEAM_ENERGY: (u16, u16) = (0x300A, 0x0114);
+pub const DOSE_RATE_SET: (u16, u16) = (0x300A, 0x0115);
+pub const GANTRY_ANGLE: (u16, u16) = (0x300A, 0x011E);
+pub const GANTRY_ROTATION_DIRECTION: (u16, u16) = (0x300A, 0x011F);
+pub const BEAM_LIMITING_DEVICE_ANGLE: (u16, u16) = (0x300A, 0x0120);
+pub const PATIENT_SUPPORT_ANGLE: (u16, u16) = (0x300A, 0x0122);
+pub const ISOCENTER_POSITION: (u16, u16) = (0x300A, 0x012C);
+pub const CUMULATIVE_METERSET_WEIGHT: (u16, u16) = (0x300A, 0x0134);
+pub const REFERENCED_BEAM_SEQUENCE: (u16, u16) = (0x300C, 0x0004);
+pub const REFERENCED_BEAM_NUMBER: (u16, u16) = (0x300C, 0x0006);
+pub const REFERENCED_STRUCTURE_SET_SEQUENCE: (u16, u16) = (0x300C, 0x0060);
+
+// One control point of a beam. Only the first control point has to carry
+// every attribute, later ones only what changed, so values left out are
+// taken over from the control point before
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlPoint {
+    pub index: usize,
+    pub cumulative_meterset_weight: Option<f64>,
+    // MV for photons, MeV for electrons
+    pub energy: Option<f64>,
+    pub dose_rate: Option<f64>,
+    // IEC 61217 angles in degrees
+    pub gantry_angle: Option<f64>,
+    // CW, CC or NONE
+    pub gantry_rotation_direction: Option<String>,
+    pub collimator_angle: Option<f64>,
+    pub couch_angle: Option<f64>,
+    // Patient coordinates in mm
+    pub isocenter: Option<[f64; 3]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Beam {
+    pub number: i32,
+    pub name: String,
+    // STATIC or DYNAMIC
+    pub beam_type: String,
+    // PHOTON, ELECTRON, PROTON...
+    pub radiation_type: String,
+    pub machine: String,
+    // TREATMENT, SETUP...
+    pub delivery_type: String,
+    // MU or MINUTE
+    pub dosimeter_unit: String,
+    pub final_cumulative_meterset_weight: f64,
+    // Meterset of one fraction from the fraction group, MU for most beams
+    pub meterset: Option<f64>,
+    pub control_points: Vec<ControlPoint>,
+}
+
+impl Beam {
+    pub fn isocenter(&self) -> Option<[f64; 3]> {
+        self.control_points
+            .first()
+            .and_then(|point| point.isocenter)
+    }
+
+    pub fn is_treatment(&self) -> bool {
+        self.delivery_type.is_empty() || self.delivery_type == "TREATMENT"
+    }
+
+    // Meterset delivered up to a control point, from its cumulative weight
+    pub fn meterset_at(&self, control_point: usize) -> Option<f64> {
+        let weight = self
+            .control_points
+            .get(control_point)?
+            .cumulative_meterset_weight?;
+        if self.final_cumulative_meterset_weight <= 0.0 {
+            return None;
+        }
+        Some(self.meterset? * weight / self.final_cumulative_meterset_weight)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FractionGroup {
+    pub number: i32,
+    pub fractions_planned: Option<u32>,
+    // Beam numbers with their meterset per fraction
+    pub beam_metersets: Vec<(i32, Option<f64>)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtPlan {
+    pub sop_instance_uid: String,
+    pub label: String,
+    pub name: String,
+    // PATIENT or TREATMENT_DEVICE
+    pub geometry: String,
+    pub frame_of_reference_uid: String,
+    pub referenced_structure_set_uid: Option<String>,
+    pub fraction_groups: Vec<FractionGroup>,
+    pub beams: Vec<Beam>,
+}
+
+impl RtPlan {
+    pub fn from_dataset(dataset: &Dataset) -> DicomResult<Self> {
+        let items = dataset.sequence(BEAM_SEQUENCE);
+        if items.is_empty() {
+            return Err(DicomError::InvalidDataset(
+                "RT Plan without Beam Sequence".to_string(),
+            ));
+        }
+
+        let fraction_groups: Vec<FractionGroup> = dataset
+            .sequence(FRACTION_GROUP_SEQUENCE)
+            .iter()
+            .map(|group| FractionGroup {
+                number: integer(group, FRACTION_GROUP_NUMBER).unwrap_or_default(),
+                fractions_planned: integer(group, NUMBER_OF_FRACTIONS_PLANNED)
+                    .and_then(|fractions| u32::try_from(fractions).ok()),
+                beam_metersets: group
+                    .sequence(REFERENCED_BEAM_SEQUENCE)
+                    .iter()
+                    .filter_map(|item| {
+                        Some((
+                            integer(item, REFERENCED_BEAM_NUMBER)?,
+                            number(item, BEAM_METERSET),
+                        ))
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let beams = items
+            .iter()
+            .map(|item| {
+                let beam_number = integer(item, BEAM_NUMBER).unwrap_or_default();
+                Beam {
+                    number: beam_number,
+                    name: text(item, BEAM_NAME),
+                    beam_type: text(item, BEAM_TYPE),
+                    radiation_type: text(item, RADIATION_TYPE),
+                    machine: text(item, TREATMENT_MACHINE_NAME),
+                    delivery_type: text(item, TREATMENT_DELIVERY_TYPE),
+                    dosimeter_unit: text(item, PRIMARY_DOSIMETER_UNIT),
+                    final_cumulative_meterset_weight: number(
+                        item,
+                        FINAL_CUMULATIVE_METERSET_WEIGHT,
+                    )
+                    .unwrap_or(1.0),
+                    meterset: fraction_groups
+                        .iter()
+                        .flat_map(|group| &group.beam_metersets)
+                        .find(|(beam, _)| *beam == beam_number)
+                        .and_then(|(_, meterset)| *meterset),
+                    control_points: control_points(item),
+                }
+            })
+            .collect();
+
+        Ok(RtPlan {
+            sop_instance_uid: text(dataset, SOP_INSTANCE_UID),
+            label: text(dataset, RT_PLAN_LABEL),
+            name: text(dataset, RT_PLAN_NAME),
+            geometry: text(dataset, RT_PLAN_GEOMETRY),
+            frame_of_reference_uid: text(dataset, FRAME_OF_REFERENCE_UID),
+            referenced_structure_set_uid: dataset
+                .sequence(REFERENCED_STRUCTURE_SET_SEQUENCE)
+                .first()
+                .and_then(|item| item.string(REFERENCED_SOP_INSTANCE_UID))
+                .map(|uid| uid.trim().to_string()),
+            fraction_groups,
+            beams,
+        })
+    }
+
+    pub fn beam(&self, number: i32) -> Option<&Beam> {
+        self.beams.iter().find(|beam| beam.number == number)
+    }
+
+    pub fn treatment_beams(&self) -> impl Iterator<Item = &Beam> {
+        self.beams.iter().filter(|beam| beam.is_treatment())
+    }
+
+    // Meterset of one fraction over all treatment beams
+    pub fn total_meterset(&self) -> f64 {
+        self.treatment_beams()
+            .filter_map(|beam| beam.meterset)
+            .sum()
+    }
+
+    // Dose grids computed for this plan
+    pub fn doses<'a>(&self, grids: &'a [DoseGrid]) -> Vec<&'a DoseGrid> {
+        grids
+            .iter()
+            .filter(|grid| {
+                grid.referenced_plan_uid.as_deref().map(str::trim)
+                    == Some(self.sop_instance_uid.as_str())
+            })
+            .collect()
+    }
+
+    // The structure set the plan was made on
+    pub fn structure_set<'a>(&self, sets: &'a [StructureSet]) -> Option<&'a StructureSet> {
+        let uid = self.referenced_structure_set_uid.as_deref()?;
+        sets.iter().find(|set| set.sop_instance_uid.trim() == uid)
+    }
+}
+
+fn control_points(beam: &Dataset) -> Vec<ControlPoint> {
+    let mut points: Vec<ControlPoint> = Vec::new();
+    for (position, item) in beam.sequence(CONTROL_POINT_SEQUENCE).iter().enumerate() {
+        let previous = points.last();
+        let inherit = |value: Option<f64>, field: fn(&ControlPoint) -> Option<f64>| {
+            value.or_else(|| previous.and_then(field))
+        };
+        let isocenter = match numbers(item, ISOCENTER_POSITION)[..] {
+            [x, y, z] => Some([x, y, z]),
+            _ => previous.and_then(|point| point.isocenter),
+        };
+        let point = ControlPoint {
+            index: integer(item, CONTROL_POINT_INDEX)
+                .and_then(|index| usize::try_from(index).ok())
+                .unwrap_or(position),
+            cumulative_meterset_weight: number(item, CUMULATIVE_METERSET_WEIGHT),
+            energy: inherit(number(item, NOMINAL_BEAM_ENERGY), |point| point.energy),
+            dose_rate: inherit(number(item, DOSE_RATE_SET), |point| point.dose_rate),
+            gantry_angle: inherit(number(item, GANTRY_ANGLE), |point| point.gantry_angle),
+            gantry_rotation_direction: item
+                .string(GANTRY_ROTATION_DIRECTION)
+                .map(|value| value.trim().to_string())
+                .or_else(|| previous.and_then(|point| point.gantry_rotation_direction.clone())),
+            collimator_angle: inherit(number(item, BEAM_LIMITING_DEVICE_ANGLE), |point| {
+                point.collimator_angle
+            }),
+            couch_angle: inherit(number(item, PATIENT_SUPPORT_ANGLE), |point| {
+                point.couch_angle
+            }),
+            isocenter,
+        };
+        points.push(point);
+    }
+    points
+}
+
+fn numbers(dataset: &Dataset, tag: (u16, u16)) -> Vec<f64> {
+    dataset
+        .string(tag)
+        .unwrap_or_default()
+        .split('\\')
+        .filter_map(|value| value.trim().parse().ok())
+        .collect()
+}
+
+fn number(dataset: &Dataset, tag: (u16, u16)) -> Option<f64> {
+    numbers(dataset, tag).first().copied()
+}
+
+fn integer(dataset: &Dataset, tag: (u16, u16)) -> Option<i32> {
+    dataset.string(tag)?.trim().parse().ok()
+}
+
+fn text(dataset: &Dataset, tag: (u16, u16)) -> String {
+    dataset.string(tag).unwrap_or_default().trim().to_string()
+}
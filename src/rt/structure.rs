@@ -0,0 +1,223 @@
+use crate::core::{
+    dataset::Dataset,
+    error::{DicomError, DicomResult},
+};
+
+pub const SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x0018);
+pub const FRAME_OF_REFERENCE_UID: (u16, u16) = (0x0020, 0x0052);
+pub const STRUCTURE_SET_LABEL: (u16, u16) = (0x3006, 0x0002);
+pub const STRUCTURE_SET_ROI_SEQUENCE: (u16, u16) = (0x3006, 0x0020);
+pub const ROI_NUMBER: (u16, u16) = (0x3006, 0x0022);
+pub const REFERENCED_FRAME_OF_REFERENCE_UID: (u16, u16) = (0x3006, 0x0024);
+pub const ROI_NAME: (u16, u16) = (0x3006, 0x0026);
+pub const ROI_CONTOUR_SEQUENCE: (u16, u16) = (0x3006, 0x0039);
+pub const CONTOUR_SEQUENCE: (u16, u16) = (0x3006, 0x0040);
+pub const CONTOUR_GEOMETRIC_TYPE: (u16, u16) = (0x3006, 0x0042);
+pub const CONTOUR_DATA: (u16, u16) = (0x3006, 0x0050);
+pub const REFERENCED_ROI_NUMBER: (u16, u16) = (0x3006, 0x0084);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contour {
+    // CLOSED_PLANAR, OPEN_PLANAR, OPEN_NONPLANAR or POINT
+    pub geometric_type: String,
+    // Patient coordinates in mm
+    pub points: Vec<[f64; 3]>,
+}
+
+impl Contour {
+    pub fn is_closed_planar(&self) -> bool {
+        self.geometric_type == "CLOSED_PLANAR"
+    }
+}
+
+// A region of interest of an RT Structure Set with its contours
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roi {
+    pub number: i32,
+    pub name: String,
+    pub frame_of_reference_uid: String,
+    pub contours: Vec<Contour>,
+}
+
+impl Roi {
+    pub fn mask(&self) -> RoiMask {
+        RoiMask::new(self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureSet {
+    pub sop_instance_uid: String,
+    pub label: String,
+    pub frame_of_reference_uid: String,
+    pub rois: Vec<Roi>,
+}
+
+impl StructureSet {
+    pub fn from_dataset(dataset: &Dataset) -> DicomResult<Self> {
+        let items = dataset.sequence(STRUCTURE_SET_ROI_SEQUENCE);
+        if items.is_empty() {
+            return Err(DicomError::InvalidDataset(
+                "RT Structure Set without Structure Set ROI Sequence".to_string(),
+            ));
+        }
+
+        let mut rois: Vec<Roi> = items
+            .iter()
+            .map(|item| Roi {
+                number: integer(item, ROI_NUMBER).unwrap_or_default(),
+                name: item.string(ROI_NAME).unwrap_or_default(),
+                frame_of_reference_uid: item
+                    .string(REFERENCED_FRAME_OF_REFERENCE_UID)
+                    .unwrap_or_default(),
+                contours: Vec::new(),
+            })
+            .collect();
+        for item in dataset.sequence(ROI_CONTOUR_SEQUENCE) {
+            let number = integer(&item, REFERENCED_ROI_NUMBER);
+            let Some(roi) = rois.iter_mut().find(|roi| Some(roi.number) == number) else {
+                continue;
+            };
+            for contour in item.sequence(CONTOUR_SEQUENCE) {
+                let values: Vec<f64> = contour
+                    .string(CONTOUR_DATA)
+                    .unwrap_or_default()
+                    .split('\\')
+                    .filter_map(|value| value.trim().parse().ok())
+                    .collect();
+                roi.contours.push(Contour {
+                    geometric_type: contour
+                        .string(CONTOUR_GEOMETRIC_TYPE)
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                    points: values
+                        .chunks_exact(3)
+                        .map(|point| [point[0], point[1], point[2]])
+                        .collect(),
+                });
+            }
+        }
+
+        Ok(StructureSet {
+            sop_instance_uid: dataset.string(SOP_INSTANCE_UID).unwrap_or_default(),
+            label: dataset.string(STRUCTURE_SET_LABEL).unwrap_or_default(),
+            frame_of_reference_uid: dataset.string(FRAME_OF_REFERENCE_UID).unwrap_or_default(),
+            rois,
+        })
+    }
+
+    pub fn roi(&self, name: &str) -> Option<&Roi> {
+        self.rois
+            .iter()
+            .find(|roi| roi.name.trim().eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn roi_by_number(&self, number: i32) -> Option<&Roi> {
+        self.rois.iter().find(|roi| roi.number == number)
+    }
+}
+
+fn integer(dataset: &Dataset, tag: (u16, u16)) -> Option<i32> {
+    dataset.string(tag)?.trim().parse().ok()
+}
+
+// The closed planar contours of one slice
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContourPlane {
+    pub z: f64,
+    pub polygons: Vec<Vec<(f64, f64)>>,
+}
+
+// Inside test of an ROI, for masks on dose grids. Contours are taken as
+// transverse, as planning systems draw them, and a point belongs to the
+// plane nearest to it within half the contour spacing. Contours inside
+// others are holes
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoiMask {
+    pub planes: Vec<ContourPlane>,
+    // Largest distance from a plane a point still belongs to it
+    pub tolerance: f64,
+}
+
+impl RoiMask {
+    pub fn new(roi: &Roi) -> Self {
+        let mut planes: Vec<ContourPlane> = Vec::new();
+        for contour in roi
+            .contours
+            .iter()
+            .filter(|contour| contour.is_closed_planar())
+        {
+            let Some(first) = contour.points.first() else {
+                continue;
+            };
+            let polygon = contour.points.iter().map(|p| (p[0], p[1])).collect();
+            match planes
+                .iter_mut()
+                .find(|plane| (plane.z - first[2]).abs() < 1e-3)
+            {
+                Some(plane) => plane.polygons.push(polygon),
+                None => planes.push(ContourPlane {
+                    z: first[2],
+                    polygons: vec![polygon],
+                }),
+            }
+        }
+        planes.sort_by(|a, b| a.z.total_cmp(&b.z));
+
+        let spacing = planes
+            .windows(2)
+            .map(|pair| pair[1].z - pair[0].z)
+            .fold(f64::INFINITY, f64::min);
+        RoiMask {
+            planes,
+            tolerance: if spacing.is_finite() {
+                spacing / 2.0
+            } else {
+                0.5
+            },
+        }
+    }
+
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn contains(&self, point: [f64; 3]) -> bool {
+        let Some(plane) = self
+            .planes
+            .iter()
+            .min_by(|a, b| (a.z - point[2]).abs().total_cmp(&(b.z - point[2]).abs()))
+        else {
+            return false;
+        };
+        if (plane.z - point[2]).abs() > self.tolerance {
+            return false;
+        }
+        plane
+            .polygons
+            .iter()
+            .filter(|polygon| inside(polygon, point[0], point[1]))
+            .count()
+            % 2
+            == 1
+    }
+}
+
+// Even-odd ray casting
+fn inside(polygon: &[(f64, f64)], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    let mut previous = match polygon.last() {
+        Some(point) => *point,
+        None => return false,
+    };
+    for &(px, py) in polygon {
+        let (qx, qy) = previous;
+        if (py > y) != (qy > y) && x < (qx - px) * (y - py) / (qy - py) + px {
+            inside = !inside;
+        }
+        previous = (px, py);
+    }
+    inside
+}
@@ -0,0 +1,174 @@
+use super::Code;
+use crate::core::{
+    dataset::Dataset,
+    error::{DicomError, DicomResult},
+};
+
+// Coded entry as held by a code sequence item, the same triplet SR content
+// items use
+pub type CodedConcept = Code;
+
+// Context group of PS3.16, its concepts as (scheme, value, meaning)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextGroup {
+    pub id: u32,
+    pub name: &'static str,
+    // Extensible groups accept concepts beyond the listed ones
+    pub extensible: bool,
+    pub concepts: &'static [(&'static str, &'static str, &'static str)],
+}
+
+impl ContextGroup {
+    pub fn codes(&self) -> Vec<CodedConcept> {
+        self.concepts
+            .iter()
+            .map(|(scheme, value, meaning)| Code::new(value, scheme, meaning))
+            .collect()
+    }
+
+    pub fn lookup(&self, value: &str, scheme: &str) -> Option<CodedConcept> {
+        self.concepts
+            .iter()
+            .find(|(s, v, _)| *s == scheme && *v == value)
+            .map(|(scheme, value, meaning)| Code::new(value, scheme, meaning))
+    }
+
+    pub fn contains(&self, code: &CodedConcept) -> bool {
+        self.lookup(&code.value, &code.scheme).is_some()
+    }
+
+    pub fn validate(&self, code: &CodedConcept) -> DicomResult<()> {
+        if code.value.is_empty() || code.scheme.is_empty() {
+            return Err(DicomError::InvalidValue(format!(
+                "{} lacks a code value or coding scheme",
+                code
+            )));
+        }
+        if !self.extensible && !self.contains(code) {
+            return Err(DicomError::InvalidValue(format!(
+                "{} is not in CID {} {}",
+                code, self.id, self.name
+            )));
+        }
+        Ok(())
+    }
+
+    // Every item of the code sequence has to be a concept of the group
+    pub fn validate_sequence(&self, dataset: &Dataset, tag: (u16, u16)) -> DicomResult<()> {
+        for item in dataset.sequence(tag) {
+            let code = Code::from_dataset(&item).ok_or_else(|| {
+                DicomError::InvalidValue(format!(
+                    "Item of ({:04X},{:04X}) is not a code",
+                    tag.0, tag.1
+                ))
+            })?;
+            self.validate(&code)?;
+        }
+        Ok(())
+    }
+}
+
+pub const ACQUISITION_MODALITY: ContextGroup = ContextGroup {
+    id: 29,
+    name: "Acquisition Modality",
+    extensible: true,
+    concepts: &[
+        ("DCM", "CR", "Computed Radiography"),
+        ("DCM", "CT", "Computed Tomography"),
+        ("DCM", "DX", "Digital Radiography"),
+        ("DCM", "IVUS", "Intravascular Ultrasound"),
+        ("DCM", "MG", "Mammography"),
+        ("DCM", "MR", "Magnetic Resonance"),
+        ("DCM", "NM", "Nuclear Medicine"),
+        ("DCM", "OCT", "Optical Coherence Tomography"),
+        ("DCM", "OP", "Ophthalmic Photography"),
+        ("DCM", "PT", "Positron emission tomography"),
+        ("DCM", "RF", "Radio Fluoroscopy"),
+        ("DCM", "SM", "Slide Microscopy"),
+        ("DCM", "US", "Ultrasound"),
+        ("DCM", "XA", "X-Ray Angiography"),
+        ("DCM", "XC", "External-camera Photography"),
+    ],
+};
+
+pub const LATERALITY: ContextGroup = ContextGroup {
+    id: 244,
+    name: "Laterality",
+    extensible: false,
+    concepts: &[
+        ("SCT", "24028007", "Right"),
+        ("SCT", "7771000", "Left"),
+        ("SCT", "51440002", "Right and left"),
+        ("SCT", "66459002", "Unilateral"),
+    ],
+};
+
+pub const KEY_OBJECT_SELECTION_DOCUMENT_TITLE: ContextGroup = ContextGroup {
+    id: 7010,
+    name: "Key Object Selection Document Title",
+    extensible: true,
+    concepts: &[
+        ("DCM", "113000", "Of Interest"),
+        ("DCM", "113001", "Rejected for Quality Reasons"),
+        ("DCM", "113002", "For Referring Provider"),
+        ("DCM", "113003", "For Surgery"),
+        ("DCM", "113004", "For Teaching"),
+        ("DCM", "113005", "For Conference"),
+        ("DCM", "113006", "For Therapy"),
+        ("DCM", "113007", "For Patient"),
+        ("DCM", "113008", "For Peer Review"),
+        ("DCM", "113009", "For Research"),
+        ("DCM", "113010", "Quality Issue"),
+        ("DCM", "113013", "Best In Set"),
+        ("DCM", "113018", "For Printing"),
+        ("DCM", "113020", "For Report Attachment"),
+        ("DCM", "113021", "For Litigation"),
+        ("DCM", "113030", "Manifest"),
+        ("DCM", "113031", "Signed Manifest"),
+        ("DCM", "113032", "Complete Study Content"),
+        ("DCM", "113033", "Signed Complete Study Content"),
+        ("DCM", "113034", "Complete Acquisition Content"),
+        ("DCM", "113035", "Signed Complete Acquisition Content"),
+        ("DCM", "113036", "Group of Frames for Display"),
+        ("DCM", "113037", "Rejected for Patient Safety Reasons"),
+        ("DCM", "113038", "Incorrect Modality Worklist Entry"),
+        ("DCM", "113039", "Data Retention Policy Expired"),
+    ],
+};
+
+pub const MEASUREMENT_REPORT_DOCUMENT_TITLE: ContextGroup = ContextGroup {
+    id: 7021,
+    name: "Measurement Report Document Title",
+    extensible: true,
+    concepts: &[
+        ("DCM", "126000", "Imaging Measurement Report"),
+        ("LN", "18748-4", "Diagnostic Imaging Report"),
+    ],
+};
+
+pub const SEGMENTATION_PROPERTY_CATEGORY: ContextGroup = ContextGroup {
+    id: 7150,
+    name: "Segmentation Property Category",
+    extensible: true,
+    concepts: &[
+        ("SCT", "85756007", "Tissue"),
+        ("SCT", "91723000", "Anatomical Structure"),
+        ("SCT", "260787004", "Physical object"),
+        ("SCT", "49755003", "Morphologically Abnormal Structure"),
+        ("SCT", "246464006", "Function"),
+        ("SCT", "91720002", "Body Substance"),
+        ("SCT", "105590001", "Substance"),
+    ],
+};
+
+pub const CONTEXT_GROUPS: &[ContextGroup] = &[
+    ACQUISITION_MODALITY,
+    LATERALITY,
+    KEY_OBJECT_SELECTION_DOCUMENT_TITLE,
+    MEASUREMENT_REPORT_DOCUMENT_TITLE,
+    SEGMENTATION_PROPERTY_CATEGORY,
+];
+
+pub fn context_group(id: u32) -> Option<&'static ContextGroup> {
+    CONTEXT_GROUPS.iter().find(|group| group.id == id)
+}
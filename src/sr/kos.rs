@@ -0,0 +1,554 @@
+use std::{collections::BTreeMap, rc::Rc};
+
+use chrono::Local;
+
+use super::{content_template, put_value, Code, ContentItem, ContentValue, SopReference};
+use crate::core::{
+    dataset::Dataset,
+    element::DicomElement,
+    error::{DicomError, DicomResult},
+    uid,
+};
+
+pub const KEY_OBJECT_SELECTION_DOCUMENT_SOP_CLASS: &str = "1.2.840.10008.5.1.4.1.1.88.59";
+
+pub const SPECIFIC_CHARACTER_SET: (u16, u16) = (0x0008, 0x0005);
+pub const STUDY_DATE: (u16, u16) = (0x0008, 0x0020);
+pub const CONTENT_DATE: (u16, u16) = (0x0008, 0x0023);
+pub const STUDY_TIME: (u16, u16) = (0x0008, 0x0030);
+pub const CONTENT_TIME: (u16, u16) = (0x0008, 0x0033);
+pub const ACCESSION_NUMBER: (u16, u16) = (0x0008, 0x0050);
+pub const MODALITY: (u16, u16) = (0x0008, 0x0060);
+pub const MANUFACTURER: (u16, u16) = (0x0008, 0x0070);
+pub const REFERRING_PHYSICIAN_NAME: (u16, u16) = (0x0008, 0x0090);
+pub const REFERENCED_SERIES_SEQUENCE: (u16, u16) = (0x0008, 0x1115);
+pub const RETRIEVE_AE_TITLE: (u16, u16) = (0x0008, 0x0054);
+pub const RETRIEVE_URL: (u16, u16) = (0x0008, 0x1190);
+pub const REFERENCED_PERFORMED_PROCEDURE_STEP_SEQUENCE: (u16, u16) = (0x0008, 0x1111);
+pub const PATIENT_NAME: (u16, u16) = (0x0010, 0x0010);
+pub const PATIENT_ID: (u16, u16) = (0x0010, 0x0020);
+pub const PATIENT_BIRTH_DATE: (u16, u16) = (0x0010, 0x0030);
+pub const PATIENT_SEX: (u16, u16) = (0x0010, 0x0040);
+pub const STUDY_INSTANCE_UID: (u16, u16) = (0x0020, 0x000D);
+pub const SERIES_INSTANCE_UID: (u16, u16) = (0x0020, 0x000E);
+pub const STUDY_ID: (u16, u16) = (0x0020, 0x0010);
+pub const SERIES_NUMBER: (u16, u16) = (0x0020, 0x0011);
+pub const INSTANCE_NUMBER: (u16, u16) = (0x0020, 0x0013);
+pub const CURRENT_REQUESTED_PROCEDURE_EVIDENCE_SEQUENCE: (u16, u16) = (0x0040, 0xA375);
+pub const RETRIEVE_LOCATION_UID: (u16, u16) = (0x0040, 0xE011);
+
+// Document titles of CID 7010
+pub fn of_interest() -> Code {
+    Code::dcm("113000", "Of Interest")
+}
+
+pub fn rejected_for_quality_reasons() -> Code {
+    Code::dcm("113001", "Rejected for Quality Reasons")
+}
+
+pub fn for_referring_provider() -> Code {
+    Code::dcm("113002", "For Referring Provider")
+}
+
+pub fn for_surgery() -> Code {
+    Code::dcm("113003", "For Surgery")
+}
+
+pub fn for_teaching() -> Code {
+    Code::dcm("113004", "For Teaching")
+}
+
+pub fn for_conference() -> Code {
+    Code::dcm("113005", "For Conference")
+}
+
+pub fn for_therapy() -> Code {
+    Code::dcm("113006", "For Therapy")
+}
+
+pub fn for_patient() -> Code {
+    Code::dcm("113007", "For Patient")
+}
+
+pub fn for_peer_review() -> Code {
+    Code::dcm("113008", "For Peer Review")
+}
+
+pub fn for_research() -> Code {
+    Code::dcm("113009", "For Research")
+}
+
+pub fn quality_issue() -> Code {
+    Code::dcm("113010", "Quality Issue")
+}
+
+pub fn best_in_set() -> Code {
+    Code::dcm("113013", "Best In Set")
+}
+
+pub fn for_printing() -> Code {
+    Code::dcm("113018", "For Printing")
+}
+
+pub fn for_report_attachment() -> Code {
+    Code::dcm("113020", "For Report Attachment")
+}
+
+pub fn manifest() -> Code {
+    Code::dcm("113030", "Manifest")
+}
+
+pub fn signed_manifest() -> Code {
+    Code::dcm("113031", "Signed Manifest")
+}
+
+pub fn complete_study_content() -> Code {
+    Code::dcm("113032", "Complete Study Content")
+}
+
+pub fn rejected_for_patient_safety_reasons() -> Code {
+    Code::dcm("113037", "Rejected for Patient Safety Reasons")
+}
+
+pub fn incorrect_modality_worklist_entry() -> Code {
+    Code::dcm("113038", "Incorrect Modality Worklist Entry")
+}
+
+pub fn data_retention_policy_expired() -> Code {
+    Code::dcm("113039", "Data Retention Policy Expired")
+}
+
+pub fn key_object_description() -> Code {
+    Code::dcm("113012", "Key Object Description")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectedInstance {
+    pub study_instance_uid: String,
+    pub series_instance_uid: String,
+    pub reference: SopReference,
+    pub retrieve_ae_title: Option<String>,
+    pub retrieve_url: Option<String>,
+    pub retrieve_location_uid: Option<String>,
+}
+
+impl SelectedInstance {
+    pub fn new(
+        study_instance_uid: &str,
+        series_instance_uid: &str,
+        sop_class_uid: &str,
+        sop_instance_uid: &str,
+    ) -> Self {
+        SelectedInstance {
+            study_instance_uid: study_instance_uid.to_string(),
+            series_instance_uid: series_instance_uid.to_string(),
+            reference: SopReference::new(sop_class_uid, sop_instance_uid),
+            retrieve_ae_title: None,
+            retrieve_url: None,
+            retrieve_location_uid: None,
+        }
+    }
+
+    // Takes the hierarchy from a stored instance
+    pub fn from_dataset(dataset: &Dataset) -> DicomResult<Self> {
+        let required = |tag: (u16, u16), name: &str| {
+            dataset.string(tag).ok_or_else(|| {
+                DicomError::InvalidDataset(format!("Selected instance lacks its {}", name))
+            })
+        };
+
+        Ok(SelectedInstance::new(
+            &required(STUDY_INSTANCE_UID, "Study Instance UID")?,
+            &required(SERIES_INSTANCE_UID, "Series Instance UID")?,
+            &required(super::SOP_CLASS_UID, "SOP Class UID")?,
+            &required(super::SOP_INSTANCE_UID, "SOP Instance UID")?,
+        ))
+    }
+
+    pub fn with_frames(mut self, frames: &[u32]) -> Self {
+        self.reference.frames = frames.to_vec();
+        self
+    }
+
+    pub fn with_retrieve_ae_title(mut self, ae_title: &str) -> Self {
+        self.retrieve_ae_title = Some(ae_title.to_string());
+        self
+    }
+
+    pub fn with_retrieve_url(mut self, url: &str) -> Self {
+        self.retrieve_url = Some(url.to_string());
+        self
+    }
+
+    // Repository holding the instance, as XDS-I.b imaging document sources require
+    pub fn with_retrieve_location_uid(mut self, uid: &str) -> Self {
+        self.retrieve_location_uid = Some(uid.to_string());
+        self
+    }
+
+    // Value type of the content item, by the storage SOP class
+    pub fn content_value(&self) -> ContentValue {
+        let sop_class = self.reference.sop_class_uid.as_str();
+        let reference = self.reference.clone();
+        if sop_class.starts_with("1.2.840.10008.5.1.4.1.1.9.") {
+            ContentValue::Waveform(reference)
+        } else if [
+            "1.2.840.10008.5.1.4.1.1.11.",
+            "1.2.840.10008.5.1.4.1.1.88.",
+            "1.2.840.10008.5.1.4.1.1.104.",
+            "1.2.840.10008.5.1.4.1.1.66",
+            "1.2.840.10008.5.1.4.1.1.481.",
+        ]
+        .iter()
+        .any(|prefix| sop_class.starts_with(prefix))
+        {
+            ContentValue::Composite(reference)
+        } else {
+            ContentValue::Image(reference)
+        }
+    }
+}
+
+// Key Object Selection document of PS3.3 A.35.4, content following TID 2010
+#[derive(Debug, Clone)]
+pub struct KeyObjectSelection {
+    pub title: Code,
+    pub description: Option<String>,
+    pub patient_id: String,
+    pub patient_name: String,
+    pub patient_birth_date: String,
+    pub patient_sex: String,
+    pub study_instance_uid: String,
+    pub study_id: String,
+    pub study_date: String,
+    pub study_time: String,
+    pub accession_number: String,
+    pub referring_physician_name: String,
+    pub series_instance_uid: String,
+    pub series_number: u32,
+    pub sop_instance_uid: String,
+    pub instance_number: u32,
+    pub content_date: String,
+    pub content_time: String,
+    pub manufacturer: String,
+    pub instances: Vec<SelectedInstance>,
+}
+
+impl KeyObjectSelection {
+    // New document in the study, with fresh series and instance UIDs
+    pub fn new(title: Code, study_instance_uid: &str) -> Self {
+        let now = Local::now();
+        KeyObjectSelection {
+            title,
+            description: None,
+            patient_id: String::new(),
+            patient_name: String::new(),
+            patient_birth_date: String::new(),
+            patient_sex: String::new(),
+            study_instance_uid: study_instance_uid.to_string(),
+            study_id: String::new(),
+            study_date: String::new(),
+            study_time: String::new(),
+            accession_number: String::new(),
+            referring_physician_name: String::new(),
+            series_instance_uid: uid::generate(),
+            series_number: 1,
+            sop_instance_uid: uid::generate(),
+            instance_number: 1,
+            content_date: now.format("%Y%m%d").to_string(),
+            content_time: now.format("%H%M%S").to_string(),
+            manufacturer: String::new(),
+            instances: Vec::new(),
+        }
+    }
+
+    // Patient and study attributes copied from an instance of the study
+    pub fn for_study(title: Code, dataset: &Dataset) -> DicomResult<Self> {
+        let study_instance_uid = dataset.string(STUDY_INSTANCE_UID).ok_or_else(|| {
+            DicomError::InvalidDataset("Instance lacks its Study Instance UID".to_string())
+        })?;
+        let text = |tag: (u16, u16)| dataset.string(tag).unwrap_or_default();
+
+        let mut document = KeyObjectSelection::new(title, &study_instance_uid);
+        document.patient_id = text(PATIENT_ID);
+        document.patient_name = text(PATIENT_NAME);
+        document.patient_birth_date = text(PATIENT_BIRTH_DATE);
+        document.patient_sex = text(PATIENT_SEX);
+        document.study_id = text(STUDY_ID);
+        document.study_date = text(STUDY_DATE);
+        document.study_time = text(STUDY_TIME);
+        document.accession_number = text(ACCESSION_NUMBER);
+        document.referring_physician_name = text(REFERRING_PHYSICIAN_NAME);
+        Ok(document)
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn with_patient(mut self, patient_id: &str, patient_name: &str) -> Self {
+        self.patient_id = patient_id.to_string();
+        self.patient_name = patient_name.to_string();
+        self
+    }
+
+    pub fn with_instance(mut self, instance: SelectedInstance) -> Self {
+        self.select(instance);
+        self
+    }
+
+    // Selecting an instance twice keeps the latest selection
+    pub fn select(&mut self, instance: SelectedInstance) {
+        let uid = &instance.reference.sop_instance_uid;
+        match self
+            .instances
+            .iter()
+            .position(|selected| &selected.reference.sop_instance_uid == uid)
+        {
+            Some(position) => self.instances[position] = instance,
+            None => self.instances.push(instance),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    pub fn content(&self) -> ContentItem {
+        let mut root = ContentItem::root(self.title.clone());
+        if let Some(description) = &self.description {
+            root = root.with_child(ContentItem::contains(
+                Some(key_object_description()),
+                ContentValue::Text(description.clone()),
+            ));
+        }
+        for instance in &self.instances {
+            root = root.with_child(ContentItem::contains(None, instance.content_value()));
+        }
+        root
+    }
+
+    pub fn to_dataset(&self) -> DicomResult<Dataset> {
+        if self.instances.is_empty() {
+            return Err(DicomError::InvalidDataset(
+                "Key object selection references no instances".to_string(),
+            ));
+        }
+
+        let mut dataset = Dataset::new();
+        dataset.put_string(SPECIFIC_CHARACTER_SET, "CS", "ISO_IR 192");
+        dataset.put_string(
+            super::SOP_CLASS_UID,
+            "UI",
+            KEY_OBJECT_SELECTION_DOCUMENT_SOP_CLASS,
+        );
+        dataset.put_string(super::SOP_INSTANCE_UID, "UI", &self.sop_instance_uid);
+        put_value(&mut dataset, STUDY_DATE, "DA", &self.study_date);
+        put_value(&mut dataset, CONTENT_DATE, "DA", &self.content_date);
+        put_value(&mut dataset, STUDY_TIME, "TM", &self.study_time);
+        put_value(&mut dataset, CONTENT_TIME, "TM", &self.content_time);
+        dataset.put_string(ACCESSION_NUMBER, "SH", &self.accession_number);
+        dataset.put_string(MODALITY, "CS", "KO");
+        dataset.put_string(MANUFACTURER, "LO", &self.manufacturer);
+        dataset.put_string(
+            REFERRING_PHYSICIAN_NAME,
+            "PN",
+            &self.referring_physician_name,
+        );
+        dataset.put(Rc::new(DicomElement::sequence(
+            REFERENCED_PERFORMED_PROCEDURE_STEP_SEQUENCE,
+            Vec::new(),
+        )));
+        dataset.put_string(PATIENT_NAME, "PN", &self.patient_name);
+        dataset.put_string(PATIENT_ID, "LO", &self.patient_id);
+        put_value(
+            &mut dataset,
+            PATIENT_BIRTH_DATE,
+            "DA",
+            &self.patient_birth_date,
+        );
+        dataset.put_string(PATIENT_SEX, "CS", &self.patient_sex);
+        dataset.put_string(STUDY_INSTANCE_UID, "UI", &self.study_instance_uid);
+        dataset.put_string(SERIES_INSTANCE_UID, "UI", &self.series_instance_uid);
+        dataset.put_string(STUDY_ID, "SH", &self.study_id);
+        dataset.put_string(SERIES_NUMBER, "IS", &self.series_number.to_string());
+        dataset.put_string(INSTANCE_NUMBER, "IS", &self.instance_number.to_string());
+        dataset.put(Rc::new(DicomElement::sequence(
+            CURRENT_REQUESTED_PROCEDURE_EVIDENCE_SEQUENCE,
+            evidence(&self.instances),
+        )));
+
+        self.content().write_to(&mut dataset);
+        dataset.put(content_template("DCMR", "2010"));
+        Ok(dataset)
+    }
+
+    pub fn from_dataset(dataset: &Dataset) -> DicomResult<Self> {
+        let sop_class = dataset.string(super::SOP_CLASS_UID).unwrap_or_default();
+        if sop_class != KEY_OBJECT_SELECTION_DOCUMENT_SOP_CLASS {
+            return Err(DicomError::InvalidDataset(format!(
+                "{} is not a key object selection document",
+                sop_class
+            )));
+        }
+
+        let content = ContentItem::from_dataset(dataset)?;
+        let title = content.concept_name.clone().ok_or_else(|| {
+            DicomError::InvalidDataset("Key object selection lacks its title".to_string())
+        })?;
+        let text = |tag: (u16, u16)| dataset.string(tag).unwrap_or_default();
+        let number = |tag: (u16, u16)| text(tag).parse().unwrap_or(1);
+
+        let locations = evidence_instances(dataset);
+        let mut instances = Vec::new();
+        for item in &content.children {
+            let Some(reference) = item.value.reference() else {
+                continue;
+            };
+            let located = locations.get(&reference.sop_instance_uid).ok_or_else(|| {
+                DicomError::InvalidDataset(format!(
+                    "Selected instance {} is missing from the evidence",
+                    reference.sop_instance_uid
+                ))
+            })?;
+            instances.push(SelectedInstance {
+                reference: reference.clone(),
+                ..located.clone()
+            });
+        }
+
+        let description_concept = key_object_description();
+        let description = content
+            .children_named(&description_concept)
+            .find_map(|item| match &item.value {
+                ContentValue::Text(text) => Some(text.clone()),
+                _ => None,
+            });
+
+        Ok(KeyObjectSelection {
+            description,
+            title,
+            patient_id: text(PATIENT_ID),
+            patient_name: text(PATIENT_NAME),
+            patient_birth_date: text(PATIENT_BIRTH_DATE),
+            patient_sex: text(PATIENT_SEX),
+            study_instance_uid: text(STUDY_INSTANCE_UID),
+            study_id: text(STUDY_ID),
+            study_date: text(STUDY_DATE),
+            study_time: text(STUDY_TIME),
+            accession_number: text(ACCESSION_NUMBER),
+            referring_physician_name: text(REFERRING_PHYSICIAN_NAME),
+            series_instance_uid: text(SERIES_INSTANCE_UID),
+            series_number: number(SERIES_NUMBER),
+            sop_instance_uid: text(super::SOP_INSTANCE_UID),
+            instance_number: number(INSTANCE_NUMBER),
+            content_date: text(CONTENT_DATE),
+            content_time: text(CONTENT_TIME),
+            manufacturer: text(MANUFACTURER),
+            instances,
+        })
+    }
+}
+
+// Hierarchical SOP instance references of PS3.3 C.17.2.1, one item per study
+pub fn evidence(instances: &[SelectedInstance]) -> Vec<Dataset> {
+    let mut studies: BTreeMap<&str, BTreeMap<&str, Vec<&SelectedInstance>>> = BTreeMap::new();
+    for instance in instances {
+        studies
+            .entry(&instance.study_instance_uid)
+            .or_default()
+            .entry(&instance.series_instance_uid)
+            .or_default()
+            .push(instance);
+    }
+
+    studies
+        .into_iter()
+        .map(|(study, series)| {
+            let series = series
+                .into_iter()
+                .map(|(series, instances)| {
+                    let mut item = Dataset::new();
+                    item.put_string(SERIES_INSTANCE_UID, "UI", series);
+                    // Series level retrieve locations, taken from the first instance
+                    if let Some(ae_title) = instances[0].retrieve_ae_title.as_ref() {
+                        item.put_string(RETRIEVE_AE_TITLE, "AE", ae_title);
+                    }
+                    if let Some(url) = instances[0].retrieve_url.as_ref() {
+                        item.put_string(RETRIEVE_URL, "UR", url);
+                    }
+                    if let Some(uid) = instances[0].retrieve_location_uid.as_ref() {
+                        item.put_string(RETRIEVE_LOCATION_UID, "UI", uid);
+                    }
+                    let references = instances
+                        .iter()
+                        .map(|instance| {
+                            let mut reference = Dataset::new();
+                            reference.put_string(
+                                super::REFERENCED_SOP_CLASS_UID,
+                                "UI",
+                                &instance.reference.sop_class_uid,
+                            );
+                            reference.put_string(
+                                super::REFERENCED_SOP_INSTANCE_UID,
+                                "UI",
+                                &instance.reference.sop_instance_uid,
+                            );
+                            reference
+                        })
+                        .collect();
+                    item.put(Rc::new(DicomElement::sequence(
+                        super::REFERENCED_SOP_SEQUENCE,
+                        references,
+                    )));
+                    item
+                })
+                .collect();
+
+            let mut item = Dataset::new();
+            item.put_string(STUDY_INSTANCE_UID, "UI", study);
+            item.put(Rc::new(DicomElement::sequence(
+                REFERENCED_SERIES_SEQUENCE,
+                series,
+            )));
+            item
+        })
+        .collect()
+}
+
+// Instances listed by the evidence of a document, by SOP Instance UID, with
+// the retrieve locations of their series
+pub fn evidence_instances(dataset: &Dataset) -> BTreeMap<String, SelectedInstance> {
+    let mut instances = BTreeMap::new();
+    for study in dataset.sequence(CURRENT_REQUESTED_PROCEDURE_EVIDENCE_SEQUENCE) {
+        let study_uid = study.string(STUDY_INSTANCE_UID).unwrap_or_default();
+        for series in study.sequence(REFERENCED_SERIES_SEQUENCE) {
+            let series_uid = series.string(SERIES_INSTANCE_UID).unwrap_or_default();
+            for reference in series.sequence(super::REFERENCED_SOP_SEQUENCE) {
+                let Some(instance_uid) = reference.string(super::REFERENCED_SOP_INSTANCE_UID)
+                else {
+                    continue;
+                };
+                let sop_class_uid = reference
+                    .string(super::REFERENCED_SOP_CLASS_UID)
+                    .unwrap_or_default();
+                instances.insert(
+                    instance_uid.clone(),
+                    SelectedInstance {
+                        retrieve_ae_title: series.string(RETRIEVE_AE_TITLE),
+                        retrieve_url: series.string(RETRIEVE_URL),
+                        retrieve_location_uid: series.string(RETRIEVE_LOCATION_UID),
+                        ..SelectedInstance::new(
+                            &study_uid,
+                            &series_uid,
+                            &sop_class_uid,
+                            &instance_uid,
+                        )
+                    },
+                );
+            }
+        }
+    }
+    instances
+}
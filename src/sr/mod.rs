@@ -0,0 +1,446 @@
+use std::{fmt::Display, rc::Rc};
+
+use crate::core::{
+    dataset::Dataset,
+    element::DicomElement,
+    error::{DicomError, DicomResult},
+    tag::VisualRepresentation,
+};
+
+pub mod cid;
+pub mod kos;
+pub mod rdsr;
+pub mod tid1500;
+pub mod ucum;
+
+pub const SOP_CLASS_UID: (u16, u16) = (0x0008, 0x0016);
+pub const SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x0018);
+pub const CODE_VALUE: (u16, u16) = (0x0008, 0x0100);
+pub const CODING_SCHEME_DESIGNATOR: (u16, u16) = (0x0008, 0x0102);
+pub const CODE_MEANING: (u16, u16) = (0x0008, 0x0104);
+pub const MAPPING_RESOURCE: (u16, u16) = (0x0008, 0x0105);
+pub const REFERENCED_SOP_CLASS_UID: (u16, u16) = (0x0008, 0x1150);
+pub const REFERENCED_SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x1155);
+pub const REFERENCED_FRAME_NUMBER: (u16, u16) = (0x0008, 0x1160);
+pub const REFERENCED_SOP_SEQUENCE: (u16, u16) = (0x0008, 0x1199);
+pub const MEASUREMENT_UNITS_CODE_SEQUENCE: (u16, u16) = (0x0040, 0x08EA);
+pub const RELATIONSHIP_TYPE: (u16, u16) = (0x0040, 0xA010);
+pub const VALUE_TYPE: (u16, u16) = (0x0040, 0xA040);
+pub const CONCEPT_NAME_CODE_SEQUENCE: (u16, u16) = (0x0040, 0xA043);
+pub const CONTINUITY_OF_CONTENT: (u16, u16) = (0x0040, 0xA050);
+pub const DATETIME: (u16, u16) = (0x0040, 0xA120);
+pub const DATE: (u16, u16) = (0x0040, 0xA121);
+pub const TIME: (u16, u16) = (0x0040, 0xA122);
+pub const PERSON_NAME: (u16, u16) = (0x0040, 0xA123);
+pub const UID: (u16, u16) = (0x0040, 0xA124);
+pub const TEXT_VALUE: (u16, u16) = (0x0040, 0xA160);
+pub const CONCEPT_CODE_SEQUENCE: (u16, u16) = (0x0040, 0xA168);
+pub const MEASURED_VALUE_SEQUENCE: (u16, u16) = (0x0040, 0xA300);
+pub const NUMERIC_VALUE: (u16, u16) = (0x0040, 0xA30A);
+pub const CONTENT_TEMPLATE_SEQUENCE: (u16, u16) = (0x0040, 0xA504);
+pub const CONTENT_SEQUENCE: (u16, u16) = (0x0040, 0xA730);
+pub const TEMPLATE_IDENTIFIER: (u16, u16) = (0x0040, 0xDB00);
+
+// Coded concept of PS3.3 8.8, compared on value and scheme only
+#[derive(Debug, Clone)]
+pub struct Code {
+    pub value: String,
+    pub scheme: String,
+    pub meaning: String,
+}
+
+impl PartialEq for Code {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value && self.scheme == other.scheme
+    }
+}
+
+impl Code {
+    pub fn new(value: &str, scheme: &str, meaning: &str) -> Self {
+        Code {
+            value: value.to_string(),
+            scheme: scheme.to_string(),
+            meaning: meaning.to_string(),
+        }
+    }
+
+    // DICOM Controlled Terminology of PS3.16
+    pub fn dcm(value: &str, meaning: &str) -> Self {
+        Code::new(value, "DCM", meaning)
+    }
+
+    pub fn to_dataset(&self) -> Dataset {
+        let mut dataset = Dataset::new();
+        dataset.put_string(CODE_VALUE, "SH", &self.value);
+        dataset.put_string(CODING_SCHEME_DESIGNATOR, "SH", &self.scheme);
+        dataset.put_string(CODE_MEANING, "LO", &self.meaning);
+        dataset
+    }
+
+    pub fn from_dataset(dataset: &Dataset) -> Option<Self> {
+        Some(Code {
+            value: dataset.string(CODE_VALUE)?,
+            scheme: dataset.string(CODING_SCHEME_DESIGNATOR)?,
+            meaning: dataset.string(CODE_MEANING).unwrap_or_default(),
+        })
+    }
+
+    // The single item of a code sequence
+    pub fn from_sequence(dataset: &Dataset, tag: (u16, u16)) -> Option<Self> {
+        dataset.sequence(tag).first().and_then(Code::from_dataset)
+    }
+
+    pub fn to_sequence(&self, tag: (u16, u16)) -> Rc<DicomElement> {
+        Rc::new(DicomElement::sequence(tag, vec![self.to_dataset()]))
+    }
+}
+
+impl Display for Code {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {}, \"{}\")", self.value, self.scheme, self.meaning)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelationshipType {
+    Contains,
+    HasProperties,
+    HasObsContext,
+    HasAcqContext,
+    InferredFrom,
+    SelectedFrom,
+    HasConceptMod,
+}
+
+impl RelationshipType {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "CONTAINS" => Some(RelationshipType::Contains),
+            "HAS PROPERTIES" => Some(RelationshipType::HasProperties),
+            "HAS OBS CONTEXT" => Some(RelationshipType::HasObsContext),
+            "HAS ACQ CONTEXT" => Some(RelationshipType::HasAcqContext),
+            "INFERRED FROM" => Some(RelationshipType::InferredFrom),
+            "SELECTED FROM" => Some(RelationshipType::SelectedFrom),
+            "HAS CONCEPT MOD" => Some(RelationshipType::HasConceptMod),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RelationshipType::Contains => "CONTAINS",
+            RelationshipType::HasProperties => "HAS PROPERTIES",
+            RelationshipType::HasObsContext => "HAS OBS CONTEXT",
+            RelationshipType::HasAcqContext => "HAS ACQ CONTEXT",
+            RelationshipType::InferredFrom => "INFERRED FROM",
+            RelationshipType::SelectedFrom => "SELECTED FROM",
+            RelationshipType::HasConceptMod => "HAS CONCEPT MOD",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SopReference {
+    pub sop_class_uid: String,
+    pub sop_instance_uid: String,
+    // Empty references the whole instance
+    pub frames: Vec<u32>,
+}
+
+impl SopReference {
+    pub fn new(sop_class_uid: &str, sop_instance_uid: &str) -> Self {
+        SopReference {
+            sop_class_uid: sop_class_uid.to_string(),
+            sop_instance_uid: sop_instance_uid.to_string(),
+            frames: Vec::new(),
+        }
+    }
+
+    fn to_dataset(&self) -> Dataset {
+        let mut dataset = Dataset::new();
+        dataset.put_string(REFERENCED_SOP_CLASS_UID, "UI", &self.sop_class_uid);
+        dataset.put_string(REFERENCED_SOP_INSTANCE_UID, "UI", &self.sop_instance_uid);
+        if !self.frames.is_empty() {
+            let frames: Vec<String> = self.frames.iter().map(u32::to_string).collect();
+            dataset.put_string(REFERENCED_FRAME_NUMBER, "IS", &frames.join("\\"));
+        }
+        dataset
+    }
+
+    fn from_dataset(dataset: &Dataset) -> DicomResult<Self> {
+        let reference = dataset
+            .sequence(REFERENCED_SOP_SEQUENCE)
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                DicomError::InvalidDataset("Reference lacks its referenced SOP".to_string())
+            })?;
+
+        Ok(SopReference {
+            sop_class_uid: reference
+                .string(REFERENCED_SOP_CLASS_UID)
+                .unwrap_or_default(),
+            sop_instance_uid: reference
+                .string(REFERENCED_SOP_INSTANCE_UID)
+                .unwrap_or_default(),
+            frames: reference
+                .string(REFERENCED_FRAME_NUMBER)
+                .map(|frames| {
+                    frames
+                        .split('\\')
+                        .filter_map(|frame| frame.trim().parse().ok())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContentValue {
+    // SEPARATE continuity when true, CONTINUOUS otherwise
+    Container { separate: bool },
+    Text(String),
+    Code(Code),
+    Num { value: String, units: Code },
+    UidRef(String),
+    PName(String),
+    Date(String),
+    Time(String),
+    DateTime(String),
+    Image(SopReference),
+    Waveform(SopReference),
+    Composite(SopReference),
+}
+
+impl ContentValue {
+    pub fn value_type(&self) -> &'static str {
+        match self {
+            ContentValue::Container { .. } => "CONTAINER",
+            ContentValue::Text(_) => "TEXT",
+            ContentValue::Code(_) => "CODE",
+            ContentValue::Num { .. } => "NUM",
+            ContentValue::UidRef(_) => "UIDREF",
+            ContentValue::PName(_) => "PNAME",
+            ContentValue::Date(_) => "DATE",
+            ContentValue::Time(_) => "TIME",
+            ContentValue::DateTime(_) => "DATETIME",
+            ContentValue::Image(_) => "IMAGE",
+            ContentValue::Waveform(_) => "WAVEFORM",
+            ContentValue::Composite(_) => "COMPOSITE",
+        }
+    }
+
+    pub fn reference(&self) -> Option<&SopReference> {
+        match self {
+            ContentValue::Image(reference)
+            | ContentValue::Waveform(reference)
+            | ContentValue::Composite(reference) => Some(reference),
+            _ => None,
+        }
+    }
+}
+
+// Node of the SR content tree of PS3.3 C.17.3, the root having no relationship
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentItem {
+    pub relationship: Option<RelationshipType>,
+    pub concept_name: Option<Code>,
+    pub value: ContentValue,
+    pub children: Vec<ContentItem>,
+}
+
+impl ContentItem {
+    pub fn new(
+        relationship: Option<RelationshipType>,
+        concept_name: Option<Code>,
+        value: ContentValue,
+    ) -> Self {
+        ContentItem {
+            relationship,
+            concept_name,
+            value,
+            children: Vec::new(),
+        }
+    }
+
+    // Document root, its concept name being the document title
+    pub fn root(title: Code) -> Self {
+        ContentItem::new(
+            None,
+            Some(title),
+            ContentValue::Container { separate: true },
+        )
+    }
+
+    pub fn contains(concept_name: Option<Code>, value: ContentValue) -> Self {
+        ContentItem::new(Some(RelationshipType::Contains), concept_name, value)
+    }
+
+    pub fn with_child(mut self, child: ContentItem) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub fn has_concept(&self, concept: &Code) -> bool {
+        self.concept_name.as_ref() == Some(concept)
+    }
+
+    // Direct children with the concept name
+    pub fn children_named<'a>(
+        &'a self,
+        concept: &'a Code,
+    ) -> impl Iterator<Item = &'a ContentItem> {
+        self.children
+            .iter()
+            .filter(move |child| child.has_concept(concept))
+    }
+
+    // Depth first search of the whole tree below this item
+    pub fn find(&self, concept: &Code) -> Option<&ContentItem> {
+        self.children.iter().find_map(|child| {
+            if child.has_concept(concept) {
+                Some(child)
+            } else {
+                child.find(concept)
+            }
+        })
+    }
+
+    // Content item attributes, into a sequence item or the document root
+    pub fn write_to(&self, dataset: &mut Dataset) {
+        if let Some(relationship) = self.relationship {
+            dataset.put_string(RELATIONSHIP_TYPE, "CS", relationship.as_str());
+        }
+        dataset.put_string(VALUE_TYPE, "CS", self.value.value_type());
+        if let Some(concept_name) = &self.concept_name {
+            dataset.put(concept_name.to_sequence(CONCEPT_NAME_CODE_SEQUENCE));
+        }
+
+        match &self.value {
+            ContentValue::Container { separate } => {
+                let continuity = if *separate { "SEPARATE" } else { "CONTINUOUS" };
+                dataset.put_string(CONTINUITY_OF_CONTENT, "CS", continuity);
+            }
+            ContentValue::Text(text) => dataset.put_string(TEXT_VALUE, "UT", text),
+            ContentValue::Code(code) => dataset.put(code.to_sequence(CONCEPT_CODE_SEQUENCE)),
+            ContentValue::Num { value, units } => {
+                let mut measured = Dataset::new();
+                measured.put(units.to_sequence(MEASUREMENT_UNITS_CODE_SEQUENCE));
+                put_value(&mut measured, NUMERIC_VALUE, "DS", value);
+                dataset.put(Rc::new(DicomElement::sequence(
+                    MEASURED_VALUE_SEQUENCE,
+                    vec![measured],
+                )));
+            }
+            ContentValue::UidRef(uid) => dataset.put_string(UID, "UI", uid),
+            ContentValue::PName(name) => dataset.put_string(PERSON_NAME, "PN", name),
+            ContentValue::Date(date) => put_value(dataset, DATE, "DA", date),
+            ContentValue::Time(time) => put_value(dataset, TIME, "TM", time),
+            ContentValue::DateTime(datetime) => put_value(dataset, DATETIME, "DT", datetime),
+            ContentValue::Image(reference)
+            | ContentValue::Waveform(reference)
+            | ContentValue::Composite(reference) => {
+                dataset.put(Rc::new(DicomElement::sequence(
+                    REFERENCED_SOP_SEQUENCE,
+                    vec![reference.to_dataset()],
+                )));
+            }
+        }
+
+        if !self.children.is_empty() {
+            let items = self.children.iter().map(ContentItem::to_dataset).collect();
+            dataset.put(Rc::new(DicomElement::sequence(CONTENT_SEQUENCE, items)));
+        }
+    }
+
+    pub fn to_dataset(&self) -> Dataset {
+        let mut dataset = Dataset::new();
+        self.write_to(&mut dataset);
+        dataset
+    }
+
+    // Reads a content item, or the root from a whole SR document
+    pub fn from_dataset(dataset: &Dataset) -> DicomResult<Self> {
+        let value_type = dataset.string(VALUE_TYPE).ok_or_else(|| {
+            DicomError::InvalidDataset("Content item lacks its value type".to_string())
+        })?;
+        let relationship = dataset
+            .string(RELATIONSHIP_TYPE)
+            .and_then(|relationship| RelationshipType::parse(&relationship));
+        let concept_name = Code::from_sequence(dataset, CONCEPT_NAME_CODE_SEQUENCE);
+        let text = |tag: (u16, u16)| dataset.string(tag).unwrap_or_default();
+
+        let value = match value_type.as_str() {
+            "CONTAINER" => ContentValue::Container {
+                separate: text(CONTINUITY_OF_CONTENT) != "CONTINUOUS",
+            },
+            "TEXT" => ContentValue::Text(text(TEXT_VALUE)),
+            "CODE" => ContentValue::Code(
+                Code::from_sequence(dataset, CONCEPT_CODE_SEQUENCE).ok_or_else(|| {
+                    DicomError::InvalidDataset("CODE content item lacks its code".to_string())
+                })?,
+            ),
+            "NUM" => {
+                let measured = dataset.sequence(MEASURED_VALUE_SEQUENCE);
+                let measured = measured.first().ok_or_else(|| {
+                    DicomError::InvalidDataset("NUM content item lacks its value".to_string())
+                })?;
+                ContentValue::Num {
+                    value: measured.string(NUMERIC_VALUE).unwrap_or_default(),
+                    units: Code::from_sequence(measured, MEASUREMENT_UNITS_CODE_SEQUENCE)
+                        .ok_or_else(|| {
+                            DicomError::InvalidDataset(
+                                "NUM content item lacks its units".to_string(),
+                            )
+                        })?,
+                }
+            }
+            "UIDREF" => ContentValue::UidRef(text(UID)),
+            "PNAME" => ContentValue::PName(text(PERSON_NAME)),
+            "DATE" => ContentValue::Date(text(DATE)),
+            "TIME" => ContentValue::Time(text(TIME)),
+            "DATETIME" => ContentValue::DateTime(text(DATETIME)),
+            "IMAGE" => ContentValue::Image(SopReference::from_dataset(dataset)?),
+            "WAVEFORM" => ContentValue::Waveform(SopReference::from_dataset(dataset)?),
+            "COMPOSITE" => ContentValue::Composite(SopReference::from_dataset(dataset)?),
+            other => {
+                return Err(DicomError::InvalidValue(format!(
+                    "Unsupported content item value type {}",
+                    other
+                )))
+            }
+        };
+
+        let children = dataset
+            .sequence(CONTENT_SEQUENCE)
+            .iter()
+            .map(ContentItem::from_dataset)
+            .collect::<DicomResult<Vec<_>>>()?;
+
+        Ok(ContentItem {
+            relationship,
+            concept_name,
+            value,
+            children,
+        })
+    }
+}
+
+// Template the content follows, e.g. ("DCMR", "2010")
+pub fn content_template(mapping_resource: &str, template_identifier: &str) -> Rc<DicomElement> {
+    let mut item = Dataset::new();
+    item.put_string(MAPPING_RESOURCE, "CS", mapping_resource);
+    item.put_string(TEMPLATE_IDENTIFIER, "CS", template_identifier);
+    Rc::new(DicomElement::sequence(
+        CONTENT_TEMPLATE_SEQUENCE,
+        vec![item],
+    ))
+}
+
+// Dates and times the model cannot hold are kept as raw bytes, as the reader does
+fn put_value(dataset: &mut Dataset, tag: (u16, u16), vr: &str, value: &str) {
+    let value = VisualRepresentation::try_from_string(vr, value)
+        .unwrap_or_else(|_| VisualRepresentation::UN(value.as_bytes().to_vec()));
+    dataset.put(Rc::new(DicomElement::new(tag, value)));
+}
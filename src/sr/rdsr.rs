@@ -0,0 +1,347 @@
+use std::fmt::Write;
+
+use super::{
+    ucum::{Measurement, Unit},
+    Code, ContentItem, ContentValue,
+};
+use crate::core::{
+    dataset::Dataset,
+    error::{DicomError, DicomResult},
+};
+
+pub const X_RAY_RADIATION_DOSE_SR_SOP_CLASS: &str = "1.2.840.10008.5.1.4.1.1.88.67";
+pub const ENHANCED_X_RAY_RADIATION_DOSE_SR_SOP_CLASS: &str = "1.2.840.10008.5.1.4.1.1.88.76";
+
+pub const PATIENT_ID: (u16, u16) = (0x0010, 0x0020);
+pub const STUDY_INSTANCE_UID: (u16, u16) = (0x0020, 0x000D);
+
+// Concepts of TID 10001 projection X-ray and TID 10011 CT dose reports
+pub fn x_ray_radiation_dose_report() -> Code {
+    Code::dcm("113701", "X-Ray Radiation Dose Report")
+}
+
+pub fn start_of_x_ray_irradiation() -> Code {
+    Code::dcm("113809", "Start of X-Ray Irradiation")
+}
+
+pub fn irradiation_event_x_ray_data() -> Code {
+    Code::dcm("113706", "Irradiation Event X-Ray Data")
+}
+
+pub fn ct_acquisition() -> Code {
+    Code::dcm("113819", "CT Acquisition")
+}
+
+pub fn irradiation_event_uid() -> Code {
+    Code::dcm("113769", "Irradiation Event UID")
+}
+
+pub fn datetime_started() -> Code {
+    Code::dcm("111526", "DateTime Started")
+}
+
+pub fn irradiation_event_type() -> Code {
+    Code::dcm("113721", "Irradiation Event Type")
+}
+
+pub fn ct_acquisition_type() -> Code {
+    Code::dcm("113820", "CT Acquisition Type")
+}
+
+pub fn acquisition_protocol() -> Code {
+    Code::dcm("125203", "Acquisition Protocol")
+}
+
+pub fn target_region() -> Code {
+    Code::dcm("123014", "Target Region")
+}
+
+pub fn mean_ctdi_vol() -> Code {
+    Code::dcm("113830", "Mean CTDIvol")
+}
+
+pub fn dlp() -> Code {
+    Code::dcm("113838", "DLP")
+}
+
+pub fn ct_dose_length_product_total() -> Code {
+    Code::dcm("113813", "CT Dose Length Product Total")
+}
+
+pub fn dose_area_product() -> Code {
+    Code::dcm("122130", "Dose Area Product")
+}
+
+pub fn dose_area_product_total() -> Code {
+    Code::dcm("113722", "Dose Area Product Total")
+}
+
+pub fn dose_rp() -> Code {
+    Code::dcm("113738", "Dose (RP)")
+}
+
+pub fn dose_rp_total() -> Code {
+    Code::dcm("113725", "Dose (RP) Total")
+}
+
+pub fn entrance_exposure_at_rp() -> Code {
+    Code::dcm("111636", "Entrance Exposure at RP")
+}
+
+pub fn average_glandular_dose() -> Code {
+    Code::dcm("111631", "Average Glandular Dose")
+}
+
+// One irradiation event, a CT acquisition or a projection X-ray exposure
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct IrradiationEvent {
+    pub uid: String,
+    pub datetime: Option<String>,
+    // Irradiation event type or CT acquisition type
+    pub kind: Option<Code>,
+    pub protocol: Option<String>,
+    pub target_region: Option<Code>,
+    pub ctdi_vol: Option<Measurement>,
+    pub dlp: Option<Measurement>,
+    pub dose_area_product: Option<Measurement>,
+    // Dose at the reference point, or the entrance exposure when only that
+    // was recorded
+    pub entrance_dose: Option<Measurement>,
+    pub average_glandular_dose: Option<Measurement>,
+}
+
+impl IrradiationEvent {
+    pub fn from_content(item: &ContentItem) -> DicomResult<Self> {
+        let measure = |concept: Code| -> DicomResult<Option<Measurement>> {
+            match item.find(&concept) {
+                Some(found) => Measurement::from_content_item(found),
+                None => Ok(None),
+            }
+        };
+        let code = |concept: Code| {
+            item.find(&concept).and_then(|found| match &found.value {
+                ContentValue::Code(code) => Some(code.clone()),
+                _ => None,
+            })
+        };
+        let text = |concept: Code| {
+            item.find(&concept).and_then(|found| match &found.value {
+                ContentValue::Text(text)
+                | ContentValue::UidRef(text)
+                | ContentValue::DateTime(text) => Some(text.clone()),
+                _ => None,
+            })
+        };
+
+        Ok(IrradiationEvent {
+            uid: text(irradiation_event_uid()).unwrap_or_default(),
+            datetime: text(datetime_started()),
+            kind: code(irradiation_event_type()).or_else(|| code(ct_acquisition_type())),
+            protocol: text(acquisition_protocol()),
+            target_region: code(target_region()),
+            ctdi_vol: measure(mean_ctdi_vol())?,
+            dlp: measure(dlp())?,
+            dose_area_product: measure(dose_area_product())?,
+            entrance_dose: match measure(dose_rp())? {
+                Some(dose) => Some(dose),
+                None => measure(entrance_exposure_at_rp())?,
+            },
+            average_glandular_dose: measure(average_glandular_dose())?,
+        })
+    }
+}
+
+// Typed view of an X-Ray Radiation Dose SR, its accumulated totals and events
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DoseReport {
+    pub sop_instance_uid: String,
+    pub patient_id: String,
+    pub study_instance_uid: String,
+    pub start: Option<String>,
+    pub total_dlp: Option<Measurement>,
+    pub total_dose_area_product: Option<Measurement>,
+    pub total_dose_rp: Option<Measurement>,
+    pub events: Vec<IrradiationEvent>,
+}
+
+impl DoseReport {
+    pub fn from_dataset(dataset: &Dataset) -> DicomResult<Self> {
+        let sop_class = dataset.string(super::SOP_CLASS_UID).unwrap_or_default();
+        if sop_class != X_RAY_RADIATION_DOSE_SR_SOP_CLASS
+            && sop_class != ENHANCED_X_RAY_RADIATION_DOSE_SR_SOP_CLASS
+        {
+            return Err(DicomError::InvalidDataset(format!(
+                "{} is not a radiation dose report",
+                sop_class
+            )));
+        }
+        DoseReport::from_content(&ContentItem::from_dataset(dataset)?).map(|report| DoseReport {
+            sop_instance_uid: dataset.string(super::SOP_INSTANCE_UID).unwrap_or_default(),
+            patient_id: dataset.string(PATIENT_ID).unwrap_or_default(),
+            study_instance_uid: dataset.string(STUDY_INSTANCE_UID).unwrap_or_default(),
+            ..report
+        })
+    }
+
+    pub fn from_content(root: &ContentItem) -> DicomResult<Self> {
+        if !root.has_concept(&x_ray_radiation_dose_report()) {
+            return Err(DicomError::InvalidDataset(format!(
+                "Document title {} is not {}",
+                root.concept_name
+                    .as_ref()
+                    .map(|title| title.to_string())
+                    .unwrap_or_default(),
+                x_ray_radiation_dose_report()
+            )));
+        }
+
+        let measure = |concept: Code| -> DicomResult<Option<Measurement>> {
+            match root.find(&concept) {
+                Some(found) => Measurement::from_content_item(found),
+                None => Ok(None),
+            }
+        };
+        let events = root
+            .children_named(&ct_acquisition())
+            .chain(root.children_named(&irradiation_event_x_ray_data()))
+            .map(IrradiationEvent::from_content)
+            .collect::<DicomResult<Vec<_>>>()?;
+
+        Ok(DoseReport {
+            start: root
+                .find(&start_of_x_ray_irradiation())
+                .and_then(|item| match &item.value {
+                    ContentValue::DateTime(datetime) => Some(datetime.clone()),
+                    _ => None,
+                }),
+            total_dlp: measure(ct_dose_length_product_total())?,
+            total_dose_area_product: measure(dose_area_product_total())?,
+            total_dose_rp: measure(dose_rp_total())?,
+            events,
+            ..DoseReport::default()
+        })
+    }
+
+    // One row per event, doses in mGy, mGy.cm and Gy.m2 whatever the report
+    // used
+    pub fn to_csv(&self) -> DicomResult<String> {
+        let mut csv = String::from(
+            "sop_instance_uid,patient_id,study_instance_uid,event_uid,datetime,type,protocol,\
+             target_region,ctdi_vol_mGy,dlp_mGy.cm,dap_Gy.m2,entrance_dose_mGy,agd_mGy\n",
+        );
+        for event in &self.events {
+            let values = [
+                self.sop_instance_uid.clone(),
+                self.patient_id.clone(),
+                self.study_instance_uid.clone(),
+                event.uid.clone(),
+                event.datetime.clone().unwrap_or_default(),
+                meaning(&event.kind),
+                event.protocol.clone().unwrap_or_default(),
+                meaning(&event.target_region),
+                value_in(&event.ctdi_vol, "mGy")?,
+                value_in(&event.dlp, "mGy.cm")?,
+                value_in(&event.dose_area_product, "Gy.m2")?,
+                value_in(&event.entrance_dose, "mGy")?,
+                value_in(&event.average_glandular_dose, "mGy")?,
+            ];
+            let row: Vec<String> = values.iter().map(|value| csv_field(value)).collect();
+            let _ = writeln!(csv, "{}", row.join(","));
+        }
+        Ok(csv)
+    }
+
+    pub fn to_json(&self) -> DicomResult<String> {
+        let mut events = Vec::new();
+        for event in &self.events {
+            events.push(format!(
+                "{{\"uid\":{},\"datetime\":{},\"type\":{},\"protocol\":{},\"targetRegion\":{},\
+                 \"ctdiVol\":{},\"dlp\":{},\"doseAreaProduct\":{},\"entranceDose\":{},\
+                 \"averageGlandularDose\":{}}}",
+                json_string(Some(&event.uid)),
+                json_string(event.datetime.as_deref()),
+                json_string(event.kind.as_ref().map(|code| code.meaning.as_str())),
+                json_string(event.protocol.as_deref()),
+                json_string(
+                    event
+                        .target_region
+                        .as_ref()
+                        .map(|code| code.meaning.as_str())
+                ),
+                json_number(&event.ctdi_vol, "mGy")?,
+                json_number(&event.dlp, "mGy.cm")?,
+                json_number(&event.dose_area_product, "Gy.m2")?,
+                json_number(&event.entrance_dose, "mGy")?,
+                json_number(&event.average_glandular_dose, "mGy")?,
+            ));
+        }
+        Ok(format!(
+            "{{\"sopInstanceUid\":{},\"patientId\":{},\"studyInstanceUid\":{},\"start\":{},\
+             \"totalDlp\":{},\"totalDoseAreaProduct\":{},\"totalDoseRp\":{},\"events\":[{}]}}",
+            json_string(Some(&self.sop_instance_uid)),
+            json_string(Some(&self.patient_id)),
+            json_string(Some(&self.study_instance_uid)),
+            json_string(self.start.as_deref()),
+            json_number(&self.total_dlp, "mGy.cm")?,
+            json_number(&self.total_dose_area_product, "Gy.m2")?,
+            json_number(&self.total_dose_rp, "mGy")?,
+            events.join(",")
+        ))
+    }
+}
+
+fn converted(measurement: &Option<Measurement>, unit: &str) -> DicomResult<Option<f64>> {
+    match measurement {
+        Some(measurement) => Ok(Some(measurement.convert_to(&Unit::parse(unit)?)?.value)),
+        None => Ok(None),
+    }
+}
+
+fn value_in(measurement: &Option<Measurement>, unit: &str) -> DicomResult<String> {
+    Ok(converted(measurement, unit)?
+        .map(|value| value.to_string())
+        .unwrap_or_default())
+}
+
+fn meaning(code: &Option<Code>) -> String {
+    code.as_ref()
+        .map(|code| code.meaning.clone())
+        .unwrap_or_default()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn json_number(measurement: &Option<Measurement>, unit: &str) -> DicomResult<String> {
+    Ok(converted(measurement, unit)?
+        .filter(|value| value.is_finite())
+        .map(|value| value.to_string())
+        .unwrap_or_else(|| "null".to_string()))
+}
+
+fn json_string(value: Option<&str>) -> String {
+    let Some(value) = value else {
+        return "null".to_string();
+    };
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
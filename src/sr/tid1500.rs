@@ -0,0 +1,810 @@
+use std::rc::Rc;
+
+use chrono::Local;
+
+use super::{
+    content_template,
+    kos::{
+        self, SelectedInstance, ACCESSION_NUMBER, CONTENT_DATE, CONTENT_TIME,
+        CURRENT_REQUESTED_PROCEDURE_EVIDENCE_SEQUENCE, INSTANCE_NUMBER, MANUFACTURER, MODALITY,
+        PATIENT_BIRTH_DATE, PATIENT_ID, PATIENT_NAME, PATIENT_SEX,
+        REFERENCED_PERFORMED_PROCEDURE_STEP_SEQUENCE, REFERRING_PHYSICIAN_NAME,
+        SERIES_INSTANCE_UID, SERIES_NUMBER, SPECIFIC_CHARACTER_SET, STUDY_DATE, STUDY_ID,
+        STUDY_INSTANCE_UID, STUDY_TIME,
+    },
+    put_value,
+    ucum::Measurement,
+    Code, ContentItem, ContentValue, RelationshipType, SopReference,
+};
+use crate::core::{
+    dataset::Dataset,
+    element::DicomElement,
+    error::{DicomError, DicomResult},
+    uid,
+};
+
+pub const COMPREHENSIVE_SR_SOP_CLASS: &str = "1.2.840.10008.5.1.4.1.1.88.33";
+pub const ENHANCED_SR_SOP_CLASS: &str = "1.2.840.10008.5.1.4.1.1.88.22";
+pub const COMPREHENSIVE_3D_SR_SOP_CLASS: &str = "1.2.840.10008.5.1.4.1.1.88.34";
+pub const MAMMOGRAPHY_CAD_SR_SOP_CLASS: &str = "1.2.840.10008.5.1.4.1.1.88.50";
+
+pub const COMPLETION_FLAG: (u16, u16) = (0x0040, 0xA491);
+pub const VERIFICATION_FLAG: (u16, u16) = (0x0040, 0xA493);
+
+// Patient and study attributes a report takes from the instances it measures
+const STUDY_ATTRIBUTES: &[(u16, u16)] = &[
+    STUDY_DATE,
+    STUDY_TIME,
+    ACCESSION_NUMBER,
+    REFERRING_PHYSICIAN_NAME,
+    PATIENT_NAME,
+    PATIENT_ID,
+    PATIENT_BIRTH_DATE,
+    PATIENT_SEX,
+    STUDY_INSTANCE_UID,
+    STUDY_ID,
+];
+
+// Concepts of TID 1500 and the templates it includes
+pub fn imaging_measurement_report() -> Code {
+    Code::dcm("126000", "Imaging Measurement Report")
+}
+
+pub fn language_of_content() -> Code {
+    Code::dcm("121049", "Language of Content Item and Descendants")
+}
+
+pub fn english() -> Code {
+    Code::new("en-US", "RFC5646", "English (United States)")
+}
+
+pub fn observer_type() -> Code {
+    Code::dcm("121005", "Observer Type")
+}
+
+pub fn person() -> Code {
+    Code::dcm("121006", "Person")
+}
+
+pub fn device() -> Code {
+    Code::dcm("121007", "Device")
+}
+
+pub fn person_observer_name() -> Code {
+    Code::dcm("121008", "Person Observer Name")
+}
+
+pub fn device_observer_uid() -> Code {
+    Code::dcm("121012", "Device Observer UID")
+}
+
+pub fn device_observer_name() -> Code {
+    Code::dcm("121013", "Device Observer Name")
+}
+
+pub fn device_observer_manufacturer() -> Code {
+    Code::dcm("121014", "Device Observer Manufacturer")
+}
+
+pub fn device_observer_model_name() -> Code {
+    Code::dcm("121015", "Device Observer Model Name")
+}
+
+pub fn device_observer_serial_number() -> Code {
+    Code::dcm("121016", "Device Observer Serial Number")
+}
+
+pub fn procedure_reported() -> Code {
+    Code::dcm("121058", "Procedure reported")
+}
+
+pub fn image_library() -> Code {
+    Code::dcm("111028", "Image Library")
+}
+
+pub fn image_library_group() -> Code {
+    Code::dcm("126200", "Image Library Group")
+}
+
+pub fn imaging_measurements() -> Code {
+    Code::dcm("126010", "Imaging Measurements")
+}
+
+pub fn measurement_group() -> Code {
+    Code::dcm("125007", "Measurement Group")
+}
+
+pub fn tracking_identifier() -> Code {
+    Code::dcm("112039", "Tracking Identifier")
+}
+
+pub fn tracking_unique_identifier() -> Code {
+    Code::dcm("112040", "Tracking Unique Identifier")
+}
+
+pub fn finding_category() -> Code {
+    Code::new("276214006", "SCT", "Finding category")
+}
+
+pub fn finding() -> Code {
+    Code::dcm("121071", "Finding")
+}
+
+pub fn source_of_measurement() -> Code {
+    Code::dcm("121112", "Source of Measurement")
+}
+
+pub fn qualitative_evaluations() -> Code {
+    Code::new("C0034375", "UMLS", "Qualitative Evaluations")
+}
+
+// Observer context of TID 1002, a person reading the images or the device,
+// such as an AI model, that produced the results
+#[derive(Debug, Clone, PartialEq)]
+pub enum Observer {
+    Person {
+        name: String,
+    },
+    Device {
+        uid: String,
+        name: Option<String>,
+        manufacturer: Option<String>,
+        model: Option<String>,
+        serial: Option<String>,
+    },
+}
+
+impl Observer {
+    pub fn person(name: &str) -> Self {
+        Observer::Person {
+            name: name.to_string(),
+        }
+    }
+
+    pub fn device(uid: &str) -> Self {
+        Observer::Device {
+            uid: uid.to_string(),
+            name: None,
+            manufacturer: None,
+            model: None,
+            serial: None,
+        }
+    }
+
+    // Device details, which person observers have no place for
+    pub fn with_name(mut self, value: &str) -> Self {
+        if let Observer::Device { name, .. } = &mut self {
+            *name = Some(value.to_string());
+        }
+        self
+    }
+
+    pub fn with_manufacturer(mut self, value: &str) -> Self {
+        if let Observer::Device { manufacturer, .. } = &mut self {
+            *manufacturer = Some(value.to_string());
+        }
+        self
+    }
+
+    pub fn with_model(mut self, value: &str) -> Self {
+        if let Observer::Device { model, .. } = &mut self {
+            *model = Some(value.to_string());
+        }
+        self
+    }
+
+    pub fn with_serial(mut self, value: &str) -> Self {
+        if let Observer::Device { serial, .. } = &mut self {
+            *serial = Some(value.to_string());
+        }
+        self
+    }
+
+    pub fn content(&self) -> Vec<ContentItem> {
+        let context = |concept: Code, value: ContentValue| {
+            ContentItem::new(Some(RelationshipType::HasObsContext), Some(concept), value)
+        };
+        match self {
+            Observer::Person { name } => vec![
+                context(observer_type(), ContentValue::Code(person())),
+                context(person_observer_name(), ContentValue::PName(name.clone())),
+            ],
+            Observer::Device {
+                uid,
+                name,
+                manufacturer,
+                model,
+                serial,
+            } => {
+                let mut items = vec![
+                    context(observer_type(), ContentValue::Code(device())),
+                    context(device_observer_uid(), ContentValue::UidRef(uid.clone())),
+                ];
+                for (concept, value) in [
+                    (device_observer_name(), name),
+                    (device_observer_manufacturer(), manufacturer),
+                    (device_observer_model_name(), model),
+                    (device_observer_serial_number(), serial),
+                ] {
+                    if let Some(value) = value {
+                        items.push(context(concept, ContentValue::Text(value.clone())));
+                    }
+                }
+                items
+            }
+        }
+    }
+
+    // Observers among the children of a container, each starting with its
+    // Observer Type
+    pub fn from_content(parent: &ContentItem) -> Vec<Observer> {
+        let mut observers: Vec<Observer> = Vec::new();
+        for child in &parent.children {
+            if child.relationship != Some(RelationshipType::HasObsContext) {
+                continue;
+            }
+            let Some(concept) = child.concept_name.as_ref() else {
+                continue;
+            };
+            let text = match &child.value {
+                ContentValue::Text(text)
+                | ContentValue::PName(text)
+                | ContentValue::UidRef(text) => text.clone(),
+                ContentValue::Code(code) if *concept == observer_type() => {
+                    observers.push(if *code == device() {
+                        Observer::device("")
+                    } else {
+                        Observer::person("")
+                    });
+                    continue;
+                }
+                _ => continue,
+            };
+
+            match observers.last_mut() {
+                Some(Observer::Person { name }) if *concept == person_observer_name() => {
+                    *name = text
+                }
+                Some(Observer::Device {
+                    uid,
+                    name,
+                    manufacturer,
+                    model,
+                    serial,
+                }) => {
+                    if *concept == device_observer_uid() {
+                        *uid = text;
+                    } else if *concept == device_observer_name() {
+                        *name = Some(text);
+                    } else if *concept == device_observer_manufacturer() {
+                        *manufacturer = Some(text);
+                    } else if *concept == device_observer_model_name() {
+                        *model = Some(text);
+                    } else if *concept == device_observer_serial_number() {
+                        *serial = Some(text);
+                    }
+                }
+                // A name without a preceding type is taken as a person
+                _ if *concept == person_observer_name() => observers.push(Observer::person(&text)),
+                _ => {}
+            }
+        }
+        observers
+    }
+}
+
+// Coded answer to a question about a finding, e.g. malignancy likelihood
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualitativeEvaluation {
+    pub concept: Code,
+    pub value: Code,
+}
+
+impl QualitativeEvaluation {
+    pub fn new(concept: Code, value: Code) -> Self {
+        QualitativeEvaluation { concept, value }
+    }
+
+    pub fn content(&self) -> ContentItem {
+        ContentItem::contains(
+            Some(self.concept.clone()),
+            ContentValue::Code(self.value.clone()),
+        )
+    }
+
+    pub fn from_content(item: &ContentItem) -> Option<Self> {
+        match (&item.concept_name, &item.value) {
+            (Some(concept), ContentValue::Code(value)) => {
+                Some(QualitativeEvaluation::new(concept.clone(), value.clone()))
+            }
+            _ => None,
+        }
+    }
+}
+
+// Measurement group of TID 1501, the measurements and evaluations of one
+// tracked finding
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeasurementGroup {
+    pub tracking_identifier: String,
+    pub tracking_uid: String,
+    pub finding_category: Option<Code>,
+    pub finding: Option<Code>,
+    // Image the measurements were made on, which the image library lists
+    pub source: Option<SopReference>,
+    pub measurements: Vec<Measurement>,
+    pub evaluations: Vec<QualitativeEvaluation>,
+}
+
+impl MeasurementGroup {
+    // New group with a fresh tracking UID
+    pub fn new(tracking_identifier: &str) -> Self {
+        MeasurementGroup {
+            tracking_identifier: tracking_identifier.to_string(),
+            tracking_uid: uid::generate(),
+            finding_category: None,
+            finding: None,
+            source: None,
+            measurements: Vec::new(),
+            evaluations: Vec::new(),
+        }
+    }
+
+    pub fn with_tracking_uid(mut self, tracking_uid: &str) -> Self {
+        self.tracking_uid = tracking_uid.to_string();
+        self
+    }
+
+    pub fn with_finding(mut self, category: Code, finding: Code) -> Self {
+        self.finding_category = Some(category);
+        self.finding = Some(finding);
+        self
+    }
+
+    pub fn with_source(mut self, source: SopReference) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    // Measurements need a concept name to say what they measure
+    pub fn with_measurement(mut self, measurement: Measurement) -> Self {
+        self.measurements.push(measurement);
+        self
+    }
+
+    pub fn with_evaluation(mut self, evaluation: QualitativeEvaluation) -> Self {
+        self.evaluations.push(evaluation);
+        self
+    }
+
+    pub fn content(&self) -> DicomResult<ContentItem> {
+        let context = |concept: Code, value: ContentValue| {
+            ContentItem::new(Some(RelationshipType::HasObsContext), Some(concept), value)
+        };
+        let mut group = ContentItem::contains(
+            Some(measurement_group()),
+            ContentValue::Container { separate: true },
+        )
+        .with_child(context(
+            tracking_identifier(),
+            ContentValue::Text(self.tracking_identifier.clone()),
+        ))
+        .with_child(context(
+            tracking_unique_identifier(),
+            ContentValue::UidRef(self.tracking_uid.clone()),
+        ));
+
+        if let Some(category) = &self.finding_category {
+            group = group.with_child(ContentItem::contains(
+                Some(finding_category()),
+                ContentValue::Code(category.clone()),
+            ));
+        }
+        if let Some(found) = &self.finding {
+            group = group.with_child(ContentItem::contains(
+                Some(finding()),
+                ContentValue::Code(found.clone()),
+            ));
+        }
+        if let Some(source) = &self.source {
+            group = group.with_child(ContentItem::contains(
+                Some(source_of_measurement()),
+                ContentValue::Image(source.clone()),
+            ));
+        }
+        for measurement in &self.measurements {
+            let concept = measurement.concept.clone().ok_or_else(|| {
+                DicomError::InvalidValue(format!(
+                    "Measurement {} of {} lacks its concept name",
+                    measurement, self.tracking_identifier
+                ))
+            })?;
+            group = group.with_child(ContentItem::contains(
+                Some(concept),
+                measurement.to_content_value(),
+            ));
+        }
+        for evaluation in &self.evaluations {
+            group = group.with_child(evaluation.content());
+        }
+        Ok(group)
+    }
+
+    pub fn from_content(item: &ContentItem) -> DicomResult<Self> {
+        let text = |concept: Code| {
+            item.children_named(&concept)
+                .find_map(|child| match &child.value {
+                    ContentValue::Text(text) | ContentValue::UidRef(text) => Some(text.clone()),
+                    _ => None,
+                })
+                .unwrap_or_default()
+        };
+        let code = |concept: Code| {
+            item.children_named(&concept)
+                .find_map(|child| match &child.value {
+                    ContentValue::Code(code) => Some(code.clone()),
+                    _ => None,
+                })
+        };
+
+        let context = [
+            tracking_identifier(),
+            tracking_unique_identifier(),
+            finding_category(),
+            finding(),
+            source_of_measurement(),
+        ];
+        let mut measurements = Vec::new();
+        let mut evaluations = Vec::new();
+        for child in &item.children {
+            if context.iter().any(|concept| child.has_concept(concept)) {
+                continue;
+            }
+            if let Some(measurement) = Measurement::from_content_item(child)? {
+                measurements.push(measurement);
+            } else if child.relationship == Some(RelationshipType::Contains) {
+                evaluations.extend(QualitativeEvaluation::from_content(child));
+            }
+        }
+
+        Ok(MeasurementGroup {
+            tracking_identifier: text(tracking_identifier()),
+            tracking_uid: text(tracking_unique_identifier()),
+            finding_category: code(finding_category()),
+            finding: code(finding()),
+            source: item
+                .children_named(&source_of_measurement())
+                .find_map(|child| child.value.reference().cloned()),
+            measurements,
+            evaluations,
+        })
+    }
+}
+
+// Imaging Measurement Report of TID 1500, stored as a Comprehensive SR
+#[derive(Debug, Clone)]
+pub struct MeasurementReport {
+    pub title: Code,
+    pub language: Code,
+    pub observers: Vec<Observer>,
+    pub procedures_reported: Vec<Code>,
+    pub image_library: Vec<SelectedInstance>,
+    pub groups: Vec<MeasurementGroup>,
+    // Evaluations of the whole study rather than of one finding
+    pub evaluations: Vec<QualitativeEvaluation>,
+    pub series_instance_uid: String,
+    pub series_number: u32,
+    pub sop_instance_uid: String,
+    pub instance_number: u32,
+    pub content_date: String,
+    pub content_time: String,
+    pub manufacturer: String,
+}
+
+impl Default for MeasurementReport {
+    fn default() -> Self {
+        MeasurementReport::new()
+    }
+}
+
+impl MeasurementReport {
+    // New report with fresh series and instance UIDs
+    pub fn new() -> Self {
+        let now = Local::now();
+        MeasurementReport {
+            title: imaging_measurement_report(),
+            language: english(),
+            observers: Vec::new(),
+            procedures_reported: Vec::new(),
+            image_library: Vec::new(),
+            groups: Vec::new(),
+            evaluations: Vec::new(),
+            series_instance_uid: uid::generate(),
+            series_number: 1,
+            sop_instance_uid: uid::generate(),
+            instance_number: 1,
+            content_date: now.format("%Y%m%d").to_string(),
+            content_time: now.format("%H%M%S").to_string(),
+            manufacturer: String::new(),
+        }
+    }
+
+    pub fn with_title(mut self, title: Code) -> Self {
+        self.title = title;
+        self
+    }
+
+    pub fn with_language(mut self, language: Code) -> Self {
+        self.language = language;
+        self
+    }
+
+    pub fn with_observer(mut self, observer: Observer) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    pub fn with_procedure_reported(mut self, procedure: Code) -> Self {
+        self.procedures_reported.push(procedure);
+        self
+    }
+
+    pub fn with_manufacturer(mut self, manufacturer: &str) -> Self {
+        self.manufacturer = manufacturer.to_string();
+        self
+    }
+
+    // Listing an image twice keeps the latest entry
+    pub fn with_image(mut self, instance: SelectedInstance) -> Self {
+        let uid = &instance.reference.sop_instance_uid;
+        match self
+            .image_library
+            .iter()
+            .position(|listed| &listed.reference.sop_instance_uid == uid)
+        {
+            Some(position) => self.image_library[position] = instance,
+            None => self.image_library.push(instance),
+        }
+        self
+    }
+
+    pub fn with_group(mut self, group: MeasurementGroup) -> Self {
+        self.groups.push(group);
+        self
+    }
+
+    pub fn with_evaluation(mut self, evaluation: QualitativeEvaluation) -> Self {
+        self.evaluations.push(evaluation);
+        self
+    }
+
+    pub fn content(&self) -> DicomResult<ContentItem> {
+        let mut root = ContentItem::root(self.title.clone()).with_child(ContentItem::new(
+            Some(RelationshipType::HasConceptMod),
+            Some(language_of_content()),
+            ContentValue::Code(self.language.clone()),
+        ));
+        for observer in &self.observers {
+            root.children.extend(observer.content());
+        }
+        for procedure in &self.procedures_reported {
+            root = root.with_child(ContentItem::new(
+                Some(RelationshipType::HasConceptMod),
+                Some(procedure_reported()),
+                ContentValue::Code(procedure.clone()),
+            ));
+        }
+
+        let mut library_group = ContentItem::contains(
+            Some(image_library_group()),
+            ContentValue::Container { separate: true },
+        );
+        for instance in &self.image_library {
+            library_group =
+                library_group.with_child(ContentItem::contains(None, instance.content_value()));
+        }
+        let mut library = ContentItem::contains(
+            Some(image_library()),
+            ContentValue::Container { separate: true },
+        );
+        if !self.image_library.is_empty() {
+            library = library.with_child(library_group);
+        }
+        root = root.with_child(library);
+
+        let mut measurements = ContentItem::contains(
+            Some(imaging_measurements()),
+            ContentValue::Container { separate: true },
+        );
+        for group in &self.groups {
+            measurements = measurements.with_child(group.content()?);
+        }
+        root = root.with_child(measurements);
+
+        if !self.evaluations.is_empty() {
+            let mut evaluations = ContentItem::contains(
+                Some(qualitative_evaluations()),
+                ContentValue::Container { separate: true },
+            );
+            for evaluation in &self.evaluations {
+                evaluations = evaluations.with_child(evaluation.content());
+            }
+            root = root.with_child(evaluations);
+        }
+        Ok(root)
+    }
+
+    // Report content only, the image library holding bare references
+    pub fn from_content(root: &ContentItem) -> DicomResult<Self> {
+        let title = root.concept_name.clone().ok_or_else(|| {
+            DicomError::InvalidDataset("Measurement report lacks its title".to_string())
+        })?;
+        if root.find(&imaging_measurements()).is_none() {
+            return Err(DicomError::InvalidDataset(format!(
+                "{} has no {}",
+                title,
+                imaging_measurements()
+            )));
+        }
+
+        let language = root
+            .children_named(&language_of_content())
+            .find_map(|item| match &item.value {
+                ContentValue::Code(code) => Some(code.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(english);
+        let procedure_concept = procedure_reported();
+        let procedures_reported = root
+            .children_named(&procedure_concept)
+            .filter_map(|item| match &item.value {
+                ContentValue::Code(code) => Some(code.clone()),
+                _ => None,
+            })
+            .collect();
+
+        // Older reports list the images straight under the library
+        let library_concept = image_library();
+        let mut image_library = Vec::new();
+        for library in root.children_named(&library_concept) {
+            for item in &library.children {
+                let entries = if item.has_concept(&image_library_group()) {
+                    item.children.iter().collect()
+                } else {
+                    vec![item]
+                };
+                for entry in entries {
+                    if let Some(reference) = entry.value.reference() {
+                        image_library.push(SelectedInstance {
+                            reference: reference.clone(),
+                            ..SelectedInstance::new("", "", "", "")
+                        });
+                    }
+                }
+            }
+        }
+
+        let measurements_concept = imaging_measurements();
+        let group_concept = measurement_group();
+        let groups = root
+            .children_named(&measurements_concept)
+            .flat_map(|measurements| measurements.children_named(&group_concept))
+            .map(MeasurementGroup::from_content)
+            .collect::<DicomResult<Vec<_>>>()?;
+
+        let evaluations_concept = qualitative_evaluations();
+        let evaluations = root
+            .children_named(&evaluations_concept)
+            .flat_map(|evaluations| evaluations.children.iter())
+            .filter_map(QualitativeEvaluation::from_content)
+            .collect();
+
+        Ok(MeasurementReport {
+            title,
+            language,
+            observers: Observer::from_content(root),
+            procedures_reported,
+            image_library,
+            groups,
+            evaluations,
+            ..MeasurementReport::new()
+        })
+    }
+
+    pub fn from_dataset(dataset: &Dataset) -> DicomResult<Self> {
+        let sop_class = dataset.string(super::SOP_CLASS_UID).unwrap_or_default();
+        if ![
+            COMPREHENSIVE_SR_SOP_CLASS,
+            ENHANCED_SR_SOP_CLASS,
+            COMPREHENSIVE_3D_SR_SOP_CLASS,
+        ]
+        .contains(&sop_class.as_str())
+        {
+            return Err(DicomError::InvalidDataset(format!(
+                "{} cannot hold a measurement report",
+                sop_class
+            )));
+        }
+
+        let mut report = MeasurementReport::from_content(&ContentItem::from_dataset(dataset)?)?;
+        // The library takes its study and series from the evidence
+        let locations = kos::evidence_instances(dataset);
+        for instance in &mut report.image_library {
+            if let Some(located) = locations.get(&instance.reference.sop_instance_uid) {
+                *instance = SelectedInstance {
+                    reference: instance.reference.clone(),
+                    ..located.clone()
+                };
+            }
+        }
+
+        let text = |tag: (u16, u16)| dataset.string(tag).unwrap_or_default();
+        let number = |tag: (u16, u16)| text(tag).parse().unwrap_or(1);
+        Ok(MeasurementReport {
+            series_instance_uid: text(SERIES_INSTANCE_UID),
+            series_number: number(SERIES_NUMBER),
+            sop_instance_uid: text(super::SOP_INSTANCE_UID),
+            instance_number: number(INSTANCE_NUMBER),
+            content_date: text(CONTENT_DATE),
+            content_time: text(CONTENT_TIME),
+            manufacturer: text(MANUFACTURER),
+            ..report
+        })
+    }
+
+    // Comprehensive SR in the study of the given instance, whose patient and
+    // study attributes it copies
+    pub fn to_dataset(&self, study_instance: &Dataset) -> DicomResult<Dataset> {
+        for group in &self.groups {
+            let Some(source) = &group.source else {
+                continue;
+            };
+            if !self
+                .image_library
+                .iter()
+                .any(|instance| instance.reference.sop_instance_uid == source.sop_instance_uid)
+            {
+                return Err(DicomError::InvalidDataset(format!(
+                    "Source {} of {} is not in the image library",
+                    source.sop_instance_uid, group.tracking_identifier
+                )));
+            }
+        }
+        if !study_instance.contains(STUDY_INSTANCE_UID) {
+            return Err(DicomError::InvalidDataset(
+                "Instance lacks its Study Instance UID".to_string(),
+            ));
+        }
+
+        let mut dataset = Dataset::new();
+        dataset.put_string(SPECIFIC_CHARACTER_SET, "CS", "ISO_IR 192");
+        dataset.put_string(super::SOP_CLASS_UID, "UI", COMPREHENSIVE_SR_SOP_CLASS);
+        dataset.put_string(super::SOP_INSTANCE_UID, "UI", &self.sop_instance_uid);
+        for tag in STUDY_ATTRIBUTES {
+            if let Some(element) = study_instance.find(*tag) {
+                dataset.put(element.clone());
+            }
+        }
+        put_value(&mut dataset, CONTENT_DATE, "DA", &self.content_date);
+        put_value(&mut dataset, CONTENT_TIME, "TM", &self.content_time);
+        dataset.put_string(MODALITY, "CS", "SR");
+        dataset.put_string(MANUFACTURER, "LO", &self.manufacturer);
+        dataset.put(Rc::new(DicomElement::sequence(
+            REFERENCED_PERFORMED_PROCEDURE_STEP_SEQUENCE,
+            Vec::new(),
+        )));
+        dataset.put_string(SERIES_INSTANCE_UID, "UI", &self.series_instance_uid);
+        dataset.put_string(SERIES_NUMBER, "IS", &self.series_number.to_string());
+        dataset.put_string(INSTANCE_NUMBER, "IS", &self.instance_number.to_string());
+        dataset.put_string(COMPLETION_FLAG, "CS", "COMPLETE");
+        dataset.put_string(VERIFICATION_FLAG, "CS", "UNVERIFIED");
+        dataset.put(Rc::new(DicomElement::sequence(
+            CURRENT_REQUESTED_PROCEDURE_EVIDENCE_SEQUENCE,
+            kos::evidence(&self.image_library),
+        )));
+
+        self.content()?.write_to(&mut dataset);
+        dataset.put(content_template("DCMR", "1500"));
+        Ok(dataset)
+    }
+}
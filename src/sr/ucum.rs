@@ -0,0 +1,492 @@
+use std::fmt::Display;
+
+use super::{Code, ContentItem, ContentValue, MEASUREMENT_UNITS_CODE_SEQUENCE};
+use crate::core::{
+    dataset::Dataset,
+    error::{DicomError, DicomResult},
+};
+
+pub const UCUM: &str = "UCUM";
+
+pub const LUT_EXPLANATION: (u16, u16) = (0x0028, 0x3003);
+pub const REAL_WORLD_VALUE_MAPPING_SEQUENCE: (u16, u16) = (0x0040, 0x9096);
+pub const LUT_LABEL: (u16, u16) = (0x0040, 0x9210);
+pub const REAL_WORLD_VALUE_LAST_VALUE_MAPPED: (u16, u16) = (0x0040, 0x9211);
+pub const REAL_WORLD_VALUE_FIRST_VALUE_MAPPED: (u16, u16) = (0x0040, 0x9216);
+pub const REAL_WORLD_VALUE_INTERCEPT: (u16, u16) = (0x0040, 0x9224);
+pub const REAL_WORLD_VALUE_SLOPE: (u16, u16) = (0x0040, 0x9225);
+
+// Exponents of the UCUM base units m, s, g, rad, K, C and cd
+type Dimension = [i32; 7];
+
+const NONE: Dimension = [0, 0, 0, 0, 0, 0, 0];
+
+// Atom, its magnitude in base units, dimension and whether it takes metric
+// prefixes. Arbitrary units only compare with themselves
+struct Atom {
+    code: &'static str,
+    factor: f64,
+    dimension: Dimension,
+    metric: bool,
+    arbitrary: bool,
+}
+
+const fn atom(code: &'static str, factor: f64, dimension: Dimension, metric: bool) -> Atom {
+    Atom {
+        code,
+        factor,
+        dimension,
+        metric,
+        arbitrary: false,
+    }
+}
+
+const fn arbitrary(code: &'static str) -> Atom {
+    Atom {
+        code,
+        factor: 1.0,
+        dimension: NONE,
+        metric: true,
+        arbitrary: true,
+    }
+}
+
+// The atoms met in imaging and measurement reports, not the whole of UCUM
+const ATOMS: &[Atom] = &[
+    atom("m", 1.0, [1, 0, 0, 0, 0, 0, 0], true),
+    atom("s", 1.0, [0, 1, 0, 0, 0, 0, 0], true),
+    atom("g", 1.0, [0, 0, 1, 0, 0, 0, 0], true),
+    atom("rad", 1.0, [0, 0, 0, 1, 0, 0, 0], true),
+    atom("K", 1.0, [0, 0, 0, 0, 1, 0, 0], true),
+    atom("C", 1.0, [0, 0, 0, 0, 0, 1, 0], true),
+    atom("cd", 1.0, [0, 0, 0, 0, 0, 0, 1], true),
+    atom("sr", 1.0, [0, 0, 0, 2, 0, 0, 0], true),
+    atom("mol", 6.02214076e23, NONE, true),
+    atom("eq", 6.02214076e23, NONE, true),
+    atom(
+        "U",
+        6.02214076e23 * 1e-6 / 60.0,
+        [0, -1, 0, 0, 0, 0, 0],
+        true,
+    ),
+    atom("Hz", 1.0, [0, -1, 0, 0, 0, 0, 0], true),
+    atom("Bq", 1.0, [0, -1, 0, 0, 0, 0, 0], true),
+    atom("Ci", 3.7e10, [0, -1, 0, 0, 0, 0, 0], true),
+    atom("N", 1e3, [1, -2, 1, 0, 0, 0, 0], true),
+    atom("Pa", 1e3, [-1, -2, 1, 0, 0, 0, 0], true),
+    atom("J", 1e3, [2, -2, 1, 0, 0, 0, 0], true),
+    atom("W", 1e3, [2, -3, 1, 0, 0, 0, 0], true),
+    atom("A", 1.0, [0, -1, 0, 0, 0, 1, 0], true),
+    atom("V", 1e3, [2, -2, 1, 0, 0, -1, 0], true),
+    atom("Ohm", 1e3, [2, -1, 1, 0, 0, -2, 0], true),
+    atom("T", 1e3, [0, -1, 1, 0, 0, -1, 0], true),
+    atom("G", 1e-1, [0, -1, 1, 0, 0, -1, 0], true),
+    atom("Gy", 1.0, [2, -2, 0, 0, 0, 0, 0], true),
+    atom("Sv", 1.0, [2, -2, 0, 0, 0, 0, 0], true),
+    atom("L", 1e-3, [3, 0, 0, 0, 0, 0, 0], true),
+    atom("l", 1e-3, [3, 0, 0, 0, 0, 0, 0], true),
+    atom("m[Hg]", 1.33322e8, [-1, -2, 1, 0, 0, 0, 0], true),
+    atom("m[H2O]", 9.80665e6, [-1, -2, 1, 0, 0, 0, 0], true),
+    atom("min", 60.0, [0, 1, 0, 0, 0, 0, 0], false),
+    atom("h", 3600.0, [0, 1, 0, 0, 0, 0, 0], false),
+    atom("d", 86400.0, [0, 1, 0, 0, 0, 0, 0], false),
+    atom("wk", 604800.0, [0, 1, 0, 0, 0, 0, 0], false),
+    atom("mo", 2629800.0, [0, 1, 0, 0, 0, 0, 0], false),
+    atom("a", 31557600.0, [0, 1, 0, 0, 0, 0, 0], false),
+    atom(
+        "deg",
+        std::f64::consts::PI / 180.0,
+        [0, 0, 0, 1, 0, 0, 0],
+        false,
+    ),
+    atom("%", 1e-2, NONE, false),
+    atom("[ppm]", 1e-6, NONE, false),
+    atom("[ppb]", 1e-9, NONE, false),
+    arbitrary("[iU]"),
+    arbitrary("[IU]"),
+    arbitrary("[arb'U]"),
+    arbitrary("[hnsf'U]"),
+];
+
+const PREFIXES: &[(&str, f64)] = &[
+    ("da", 1e1),
+    ("Y", 1e24),
+    ("Z", 1e21),
+    ("E", 1e18),
+    ("P", 1e15),
+    ("T", 1e12),
+    ("G", 1e9),
+    ("M", 1e6),
+    ("k", 1e3),
+    ("h", 1e2),
+    ("d", 1e-1),
+    ("c", 1e-2),
+    ("m", 1e-3),
+    ("u", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+    ("f", 1e-15),
+    ("a", 1e-18),
+    ("z", 1e-21),
+    ("y", 1e-24),
+];
+
+// Unit of the case sensitive UCUM syntax, such as "mm", "mg/mL", "mm2/s" or
+// "{ratio}". Celsius is the one special unit supported, and only on its own
+#[derive(Debug, Clone)]
+pub struct Unit {
+    pub code: String,
+    factor: f64,
+    offset: f64,
+    dimension: Dimension,
+    // Arbitrary atoms involved, which convert to nothing but themselves
+    arbitrary: Vec<String>,
+}
+
+impl PartialEq for Unit {
+    fn eq(&self, other: &Self) -> bool {
+        self.code == other.code
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code)
+    }
+}
+
+impl Unit {
+    pub fn parse(code: &str) -> DicomResult<Self> {
+        let code = code.trim();
+        if code == "Cel" {
+            return Ok(Unit {
+                code: code.to_string(),
+                factor: 1.0,
+                offset: 273.15,
+                dimension: [0, 0, 0, 0, 1, 0, 0],
+                arbitrary: Vec::new(),
+            });
+        }
+        if code.is_empty() {
+            return Err(DicomError::InvalidValue("Empty UCUM unit".to_string()));
+        }
+
+        let mut parser = Parser {
+            code,
+            position: 0,
+            arbitrary: Vec::new(),
+        };
+        let (factor, dimension) = parser.term()?;
+        if parser.position < code.len() {
+            return Err(parser.error("unexpected character"));
+        }
+        let mut arbitrary = parser.arbitrary;
+        arbitrary.sort();
+        Ok(Unit {
+            code: code.to_string(),
+            factor,
+            offset: 0.0,
+            dimension,
+            arbitrary,
+        })
+    }
+
+    pub fn from_code(code: &Code) -> DicomResult<Self> {
+        if code.scheme != UCUM {
+            return Err(DicomError::InvalidValue(format!(
+                "{} is not a UCUM unit",
+                code
+            )));
+        }
+        Unit::parse(&code.value)
+    }
+
+    // Units code of a NUM content item, the UCUM code doubling as meaning
+    pub fn to_code(&self) -> Code {
+        Code::new(&self.code, UCUM, &self.code)
+    }
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.dimension == NONE && self.arbitrary.is_empty()
+    }
+
+    pub fn is_commensurable(&self, other: &Unit) -> bool {
+        self.dimension == other.dimension && self.arbitrary == other.arbitrary
+    }
+
+    pub fn convert(&self, value: f64, to: &Unit) -> DicomResult<f64> {
+        if !self.is_commensurable(to) {
+            return Err(DicomError::InvalidValue(format!(
+                "Cannot convert {} to {}",
+                self.code, to.code
+            )));
+        }
+        Ok((value * self.factor + self.offset - to.offset) / to.factor)
+    }
+}
+
+// Numeric value with its unit, as held by NUM content items and real world
+// value maps
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub concept: Option<Code>,
+    pub value: f64,
+    pub unit: Unit,
+}
+
+impl Display for Measurement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(concept) = &self.concept {
+            write!(f, "{}: ", concept.meaning)?;
+        }
+        write!(f, "{} {}", self.value, self.unit)
+    }
+}
+
+impl Measurement {
+    pub fn new(value: f64, unit: Unit) -> Self {
+        Measurement {
+            concept: None,
+            value,
+            unit,
+        }
+    }
+
+    pub fn convert_to(&self, unit: &Unit) -> DicomResult<Measurement> {
+        Ok(Measurement {
+            concept: self.concept.clone(),
+            value: self.unit.convert(self.value, unit)?,
+            unit: unit.clone(),
+        })
+    }
+
+    // NUM content item holding the measurement, with the given concept name
+    pub fn to_content_value(&self) -> ContentValue {
+        ContentValue::Num {
+            value: format_decimal(self.value),
+            units: self.unit.to_code(),
+        }
+    }
+
+    pub fn from_content_item(item: &ContentItem) -> DicomResult<Option<Measurement>> {
+        let ContentValue::Num { value, units } = &item.value else {
+            return Ok(None);
+        };
+        let number = value
+            .trim()
+            .parse()
+            .map_err(|_| DicomError::InvalidValue(format!("Numeric value {}", value)))?;
+        Ok(Some(Measurement {
+            concept: item.concept_name.clone(),
+            value: number,
+            unit: Unit::from_code(units)?,
+        }))
+    }
+}
+
+// Measurements of every NUM item of the content tree, depth first
+pub fn measurements(root: &ContentItem) -> DicomResult<Vec<Measurement>> {
+    let mut found = Vec::new();
+    if let Some(measurement) = Measurement::from_content_item(root)? {
+        found.push(measurement);
+    }
+    for child in &root.children {
+        found.extend(measurements(child)?);
+    }
+    Ok(found)
+}
+
+// Item of the Real World Value Mapping Sequence, a linear map from stored
+// values to a quantity in UCUM units
+#[derive(Debug, Clone, PartialEq)]
+pub struct RealWorldValueMap {
+    pub label: String,
+    pub explanation: Option<String>,
+    pub first_value: f64,
+    pub last_value: f64,
+    pub slope: f64,
+    pub intercept: f64,
+    pub unit: Unit,
+}
+
+impl RealWorldValueMap {
+    pub fn from_dataset(dataset: &Dataset) -> DicomResult<Vec<RealWorldValueMap>> {
+        dataset
+            .sequence(REAL_WORLD_VALUE_MAPPING_SEQUENCE)
+            .iter()
+            .map(|item| {
+                let number = |tag: (u16, u16)| -> DicomResult<f64> {
+                    item.string(tag)
+                        .and_then(|value| value.trim().parse().ok())
+                        .ok_or_else(|| {
+                            DicomError::InvalidValue(format!(
+                                "Real world value map without ({:04X},{:04X})",
+                                tag.0, tag.1
+                            ))
+                        })
+                };
+                let units = Code::from_sequence(item, MEASUREMENT_UNITS_CODE_SEQUENCE).ok_or_else(
+                    || DicomError::InvalidValue("Real world value map without units".to_string()),
+                )?;
+                Ok(RealWorldValueMap {
+                    label: item.string(LUT_LABEL).unwrap_or_default(),
+                    explanation: item.string(LUT_EXPLANATION),
+                    first_value: number(REAL_WORLD_VALUE_FIRST_VALUE_MAPPED)?,
+                    last_value: number(REAL_WORLD_VALUE_LAST_VALUE_MAPPED)?,
+                    slope: number(REAL_WORLD_VALUE_SLOPE)?,
+                    intercept: number(REAL_WORLD_VALUE_INTERCEPT)?,
+                    unit: Unit::from_code(&units)?,
+                })
+            })
+            .collect()
+    }
+
+    pub fn covers(&self, stored: f64) -> bool {
+        stored >= self.first_value && stored <= self.last_value
+    }
+
+    pub fn apply(&self, stored: f64) -> Measurement {
+        Measurement::new(stored * self.slope + self.intercept, self.unit.clone())
+    }
+}
+
+struct Parser<'a> {
+    code: &'a str,
+    position: usize,
+    arbitrary: Vec<String>,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: &str) -> DicomError {
+        DicomError::InvalidValue(format!(
+            "UCUM unit \"{}\": {} at {}",
+            self.code, reason, self.position
+        ))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.code[self.position..].chars().next()
+    }
+
+    // Components joined by "." and "/", a leading "/" meaning the reciprocal
+    fn term(&mut self) -> DicomResult<(f64, Dimension)> {
+        let (mut factor, mut dimension) = if self.peek() == Some('/') {
+            (1.0, NONE)
+        } else {
+            self.component()?
+        };
+
+        while let Some(operator) = self.peek().filter(|c| *c == '.' || *c == '/') {
+            self.position += 1;
+            let (next_factor, next_dimension) = self.component()?;
+            let sign = if operator == '/' { -1 } else { 1 };
+            factor *= next_factor.powi(sign);
+            for (exponent, next) in dimension.iter_mut().zip(next_dimension) {
+                *exponent += sign * next;
+            }
+        }
+        Ok((factor, dimension))
+    }
+
+    fn component(&mut self) -> DicomResult<(f64, Dimension)> {
+        if self.peek() == Some('(') {
+            self.position += 1;
+            let term = self.term()?;
+            if self.peek() != Some(')') {
+                return Err(self.error("unbalanced parenthesis"));
+            }
+            self.position += 1;
+            self.annotation()?;
+            return Ok(term);
+        }
+
+        // Annotations stand for the unity when alone
+        if self.peek() == Some('{') {
+            self.annotation()?;
+            return Ok((1.0, NONE));
+        }
+
+        let start = self.position;
+        let mut depth = 0;
+        while let Some(c) = self.peek() {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                '.' | '/' | '(' | ')' | '{' if depth == 0 => break,
+                _ => {}
+            }
+            self.position += c.len_utf8();
+        }
+        let symbol = &self.code[start..self.position];
+        self.annotation()?;
+        if symbol.is_empty() {
+            return Err(self.error("missing unit"));
+        }
+
+        // A plain integer is a factor, such as the 1 of "1/min"
+        if symbol.chars().all(|c| c.is_ascii_digit()) {
+            let factor = symbol.parse().map_err(|_| self.error("bad factor"))?;
+            return Ok((factor, NONE));
+        }
+
+        // Trailing signed digits are the exponent, as in "cm2" or "s-1"
+        let digits = symbol.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+        let (base, exponent) = if digits < symbol.len() {
+            let base = symbol[..digits].trim_end_matches(['+', '-']);
+            let exponent: i32 = symbol[base.len()..]
+                .parse()
+                .map_err(|_| self.error("bad exponent"))?;
+            (base, exponent)
+        } else {
+            (symbol, 1)
+        };
+
+        let (factor, atom) = self
+            .atom(base)
+            .ok_or_else(|| self.error(&format!("unknown unit {}", base)))?;
+        if atom.arbitrary {
+            self.arbitrary.push(atom.code.to_string());
+        }
+        let mut dimension = atom.dimension;
+        for value in dimension.iter_mut() {
+            *value *= exponent;
+        }
+        Ok((factor.powi(exponent), dimension))
+    }
+
+    fn annotation(&mut self) -> DicomResult<()> {
+        if self.peek() != Some('{') {
+            return Ok(());
+        }
+        match self.code[self.position..].find('}') {
+            Some(end) => {
+                self.position += end + 1;
+                Ok(())
+            }
+            None => Err(self.error("unterminated annotation")),
+        }
+    }
+
+    // Atom with its prefix applied, exact matches taking precedence
+    fn atom(&self, symbol: &str) -> Option<(f64, &'static Atom)> {
+        if let Some(atom) = ATOMS.iter().find(|atom| atom.code == symbol) {
+            return Some((atom.factor, atom));
+        }
+        PREFIXES.iter().find_map(|(prefix, scale)| {
+            let rest = symbol.strip_prefix(prefix)?;
+            ATOMS
+                .iter()
+                .find(|atom| atom.metric && atom.code == rest)
+                .map(|atom| (scale * atom.factor, atom))
+        })
+    }
+}
+
+// Decimal string short enough for a DS value
+fn format_decimal(value: f64) -> String {
+    let text = value.to_string();
+    if text.len() <= 16 {
+        return text;
+    }
+    format!("{:.8e}", value)
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use super::InstanceStore;
+use crate::core::{error::DicomResult, reader, reader::DicomFile};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStatistics {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+// One cached instance, linked from the least to the most recently used
+#[derive(Debug)]
+struct Node {
+    sop_instance_uid: String,
+    data: Arc<[u8]>,
+    older: Option<usize>,
+    newer: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    used: usize,
+    index: HashMap<String, usize>,
+    // Slots of the nodes, freed ones are reused
+    nodes: Vec<Option<Node>>,
+    free: Vec<usize>,
+    oldest: Option<usize>,
+    newest: Option<usize>,
+    statistics: CacheStatistics,
+}
+
+impl CacheState {
+    fn node(&mut self, slot: usize) -> &mut Node {
+        self.nodes[slot].as_mut().expect("linked slot holds a node")
+    }
+
+    fn unlink(&mut self, slot: usize) {
+        let (older, newer) = {
+            let node = self.node(slot);
+            (node.older.take(), node.newer.take())
+        };
+        match older {
+            Some(older) => self.node(older).newer = newer,
+            None => self.oldest = newer,
+        }
+        match newer {
+            Some(newer) => self.node(newer).older = older,
+            None => self.newest = older,
+        }
+    }
+
+    fn link_newest(&mut self, slot: usize) {
+        let newest = self.newest;
+        self.node(slot).older = newest;
+        match newest {
+            Some(newest) => self.node(newest).newer = Some(slot),
+            None => self.oldest = Some(slot),
+        }
+        self.newest = Some(slot);
+    }
+
+    fn remove(&mut self, sop_instance_uid: &str) -> Option<Arc<[u8]>> {
+        let slot = self.index.remove(sop_instance_uid)?;
+        self.unlink(slot);
+        let node = self.nodes[slot].take()?;
+        self.free.push(slot);
+        self.used -= node.data.len();
+        Some(node.data)
+    }
+}
+
+// Part 10 files keyed by SOP Instance UID, least recently used dropped first
+// once they pass the capacity in bytes, so hot studies are not read from the
+// store again on every retrieve. Lookups and updates take constant time.
+// Datasets share their elements through Rc and cannot cross threads, so the
+// cache keeps the encoded files and clones share them between the web
+// server, the QR SCP and other workers
+#[derive(Debug, Clone)]
+pub struct DatasetCache {
+    capacity: usize,
+    state: Arc<Mutex<CacheState>>,
+}
+
+impl DatasetCache {
+    pub fn new(capacity: usize) -> Self {
+        DatasetCache {
+            capacity,
+            state: Arc::default(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Bytes the cached files take
+    pub fn used(&self) -> usize {
+        self.state().used
+    }
+
+    pub fn len(&self) -> usize {
+        self.state().index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state().index.is_empty()
+    }
+
+    pub fn contains(&self, sop_instance_uid: &str) -> bool {
+        self.state().index.contains_key(sop_instance_uid)
+    }
+
+    pub fn statistics(&self) -> CacheStatistics {
+        let state = self.state();
+        CacheStatistics {
+            entries: state.index.len(),
+            bytes: state.used,
+            ..state.statistics
+        }
+    }
+
+    pub fn get(&self, sop_instance_uid: &str) -> Option<Arc<[u8]>> {
+        let mut state = self.state();
+        match state.index.get(sop_instance_uid).copied() {
+            Some(slot) => {
+                state.statistics.hits += 1;
+                state.unlink(slot);
+                state.link_newest(slot);
+                Some(state.node(slot).data.clone())
+            }
+            None => {
+                state.statistics.misses += 1;
+                None
+            }
+        }
+    }
+
+    // Files larger than the whole capacity are not kept
+    pub fn insert(&self, sop_instance_uid: &str, data: Arc<[u8]>) -> bool {
+        let mut state = self.state();
+        state.remove(sop_instance_uid);
+        if data.len() > self.capacity {
+            return false;
+        }
+        while state.used + data.len() > self.capacity {
+            let Some(oldest) = state.oldest else {
+                break;
+            };
+            let oldest = state.node(oldest).sop_instance_uid.clone();
+            state.remove(&oldest);
+            state.statistics.evictions += 1;
+        }
+
+        state.used += data.len();
+        let node = Node {
+            sop_instance_uid: sop_instance_uid.to_string(),
+            data,
+            older: None,
+            newer: None,
+        };
+        let slot = match state.free.pop() {
+            Some(slot) => {
+                state.nodes[slot] = Some(node);
+                slot
+            }
+            None => {
+                state.nodes.push(Some(node));
+                state.nodes.len() - 1
+            }
+        };
+        state.index.insert(sop_instance_uid.to_string(), slot);
+        state.link_newest(slot);
+        true
+    }
+
+    // Called when an instance is stored again or deleted
+    pub fn remove(&self, sop_instance_uid: &str) -> Option<Arc<[u8]>> {
+        self.state().remove(sop_instance_uid)
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state();
+        let statistics = state.statistics;
+        *state = CacheState {
+            statistics,
+            ..CacheState::default()
+        };
+    }
+
+    // Cached instance, read from the store on a miss
+    pub fn load(
+        &self,
+        store: &dyn InstanceStore,
+        sop_instance_uid: &str,
+    ) -> DicomResult<Option<DicomFile>> {
+        if let Some(data) = self.get(sop_instance_uid) {
+            return reader::read_file(&data).map(Some);
+        }
+        let Some(data) = store.get(sop_instance_uid)? else {
+            return Ok(None);
+        };
+        // Only files that parse are kept
+        let file = reader::read_file(&data)?;
+        self.insert(sop_instance_uid, data.into());
+        Ok(Some(file))
+    }
+}
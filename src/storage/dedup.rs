@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use sha1::{Digest, Sha1};
+
+use crate::core::{
+    error::{DicomError, DicomResult},
+    reader::{DicomFile, PIXEL_DATA},
+    transfer_syntax, writer,
+};
+use crate::image::render::{self, number_of_frames};
+
+// What to do with an instance whose SOP Instance UID is already stored with
+// other content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    // Keep the instance stored first
+    #[default]
+    Ignore,
+    Replace,
+    // Keep both, the new one as the next version
+    Version,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ingest {
+    New,
+    // Same content, possibly in another transfer syntax, nothing to store
+    Identical,
+    // Other content, dropped as the policy says
+    Ignored,
+    Replace,
+    Version(u32),
+}
+
+impl Ingest {
+    // Whether the instance has to be written
+    pub fn stores(&self) -> bool {
+        matches!(self, Ingest::New | Ingest::Replace | Ingest::Version(_))
+    }
+}
+
+// Hash of the decoded dataset: the elements in Explicit VR Little Endian and
+// the native pixel data frame by frame, so that the same instance hashes the
+// same in any lossless transfer syntax. Pixel data no codec decodes is
+// hashed as stored
+pub fn content_hash(file: &DicomFile) -> DicomResult<String> {
+    let mut dataset = file.dataset.clone();
+    let pixel_data = dataset.remove(PIXEL_DATA);
+
+    let mut hasher = Sha1::new();
+    hasher.update(writer::write_dataset(
+        &dataset,
+        transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN,
+    )?);
+
+    if let Some(pixel_data) = pixel_data {
+        let frames: DicomResult<Vec<Vec<u8>>> = (0..number_of_frames(&file.dataset))
+            .map(|frame| render::frame_data(file, frame))
+            .collect();
+        match frames {
+            Ok(frames) => frames.iter().for_each(|frame| hasher.update(frame)),
+            Err(_) => hasher.update(writer::encode_value(&pixel_data.vr(), false)),
+        }
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub hash: String,
+    // 1 for the first instance stored under the UID
+    pub version: u32,
+}
+
+// Versions of one instance as the stores keep them, "<version> <hash>" lines,
+// oldest first
+pub fn parse_versions(text: &str) -> DicomResult<Vec<IndexEntry>> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let corrupt = || DicomError::InvalidFile(format!("Corrupt version line {}", line));
+            let (version, hash) = line.trim().split_once(' ').ok_or_else(corrupt)?;
+            Ok(IndexEntry {
+                hash: hash.to_string(),
+                version: version.parse().map_err(|_| corrupt())?,
+            })
+        })
+        .collect()
+}
+
+pub fn versions_text(versions: &[IndexEntry]) -> String {
+    versions
+        .iter()
+        .map(|entry| format!("{} {}\n", entry.version, entry.hash))
+        .collect()
+}
+
+// Content hashes of stored instances by SOP Instance UID, deciding what an
+// incoming instance amounts to
+#[derive(Debug, Clone, Default)]
+pub struct DuplicateIndex {
+    pub policy: DuplicatePolicy,
+    entries: HashMap<String, IndexEntry>,
+}
+
+impl DuplicateIndex {
+    pub fn new(policy: DuplicatePolicy) -> Self {
+        DuplicateIndex {
+            policy,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, sop_instance_uid: &str) -> Option<&IndexEntry> {
+        self.entries.get(sop_instance_uid)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // What storing an instance would do, without recording it
+    pub fn check(&self, sop_instance_uid: &str, hash: &str) -> Ingest {
+        match self.entries.get(sop_instance_uid) {
+            None => Ingest::New,
+            Some(entry) if entry.hash == hash => Ingest::Identical,
+            Some(entry) => match self.policy {
+                DuplicatePolicy::Ignore => Ingest::Ignored,
+                DuplicatePolicy::Replace => Ingest::Replace,
+                DuplicatePolicy::Version => Ingest::Version(entry.version + 1),
+            },
+        }
+    }
+
+    // As check, recording the instance when it is to be stored
+    pub fn ingest(&mut self, sop_instance_uid: &str, hash: &str) -> Ingest {
+        let ingest = self.check(sop_instance_uid, hash);
+        let version = match ingest {
+            Ingest::New => 1,
+            Ingest::Replace => self.entries[sop_instance_uid].version,
+            Ingest::Version(version) => version,
+            Ingest::Identical | Ingest::Ignored => return ingest,
+        };
+        self.entries.insert(
+            sop_instance_uid.to_string(),
+            IndexEntry {
+                hash: hash.to_string(),
+                version,
+            },
+        );
+        ingest
+    }
+
+    // Entry of an instance stored before, e.g. when a store reopens
+    pub fn insert(&mut self, sop_instance_uid: &str, entry: IndexEntry) {
+        self.entries.insert(sop_instance_uid.to_string(), entry);
+    }
+
+    pub fn remove(&mut self, sop_instance_uid: &str) -> Option<IndexEntry> {
+        self.entries.remove(sop_instance_uid)
+    }
+}
@@ -0,0 +1,262 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+use super::{
+    dedup::{self, DuplicateIndex, DuplicatePolicy, IndexEntry, Ingest},
+    InstanceStore, StoredInstance,
+};
+use crate::core::{
+    error::{DicomError, DicomResult},
+    meta::SOP_INSTANCE_UID,
+    metrics, reader, uid,
+};
+
+static TEMPORARY: AtomicU64 = AtomicU64::new(0);
+
+// Instances kept under their content hash, objects/ab/cdef….dcm, with
+// uids/<SOP Instance UID> listing the versions stored for the UID as
+// "<version> <hash>" lines, latest last. Files are written under unique
+// temporary names and renamed into place, so readers never see partial
+// files and concurrent writers of the same content both succeed
+#[derive(Debug)]
+pub struct FileStore {
+    root: PathBuf,
+    index: Mutex<DuplicateIndex>,
+}
+
+impl FileStore {
+    // Opens or creates a store, reading the versions already stored
+    pub fn open(root: &Path, policy: DuplicatePolicy) -> DicomResult<Self> {
+        fs::create_dir_all(root.join("objects"))?;
+        fs::create_dir_all(root.join("uids"))?;
+
+        let mut index = DuplicateIndex::new(policy);
+        for entry in fs::read_dir(root.join("uids"))? {
+            let path = entry?.path();
+            let Some(sop_instance_uid) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if sop_instance_uid.starts_with('.') {
+                continue;
+            }
+            if let Some(latest) = read_versions(&path)?.pop() {
+                index.insert(sop_instance_uid, latest);
+            }
+        }
+
+        Ok(FileStore {
+            root: root.to_path_buf(),
+            index: Mutex::new(index),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn object_path(&self, hash: &str) -> PathBuf {
+        let (prefix, rest) = hash.split_at(2.min(hash.len()));
+        self.root
+            .join("objects")
+            .join(prefix)
+            .join(format!("{}.dcm", rest))
+    }
+
+    fn uid_path(&self, sop_instance_uid: &str) -> DicomResult<PathBuf> {
+        // UIDs name files, so only valid ones are taken
+        if !uid::is_valid(sop_instance_uid) {
+            return Err(DicomError::InvalidValue(format!(
+                "Invalid SOP Instance UID {}",
+                sop_instance_uid
+            )));
+        }
+        Ok(self.root.join("uids").join(sop_instance_uid))
+    }
+
+    // Every version stored for an instance, oldest first
+    pub fn versions(&self, sop_instance_uid: &str) -> DicomResult<Vec<IndexEntry>> {
+        let path = self.uid_path(sop_instance_uid)?;
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        read_versions(&path)
+    }
+
+    // Path of the latest version, for serving WADO or C-MOVE from the files
+    pub fn path(&self, sop_instance_uid: &str) -> DicomResult<Option<PathBuf>> {
+        Ok(self
+            .versions(sop_instance_uid)?
+            .pop()
+            .map(|entry| self.object_path(&entry.hash)))
+    }
+
+    pub fn get_version(
+        &self,
+        sop_instance_uid: &str,
+        version: u32,
+    ) -> DicomResult<Option<Vec<u8>>> {
+        match self
+            .versions(sop_instance_uid)?
+            .into_iter()
+            .find(|entry| entry.version == version)
+        {
+            Some(entry) => Ok(Some(fs::read(self.object_path(&entry.hash))?)),
+            None => Ok(None),
+        }
+    }
+
+    // Problems of the version lists, unreadable ones and versions whose
+    // object is gone. Empty while the index is intact
+    pub fn check_index(&self) -> DicomResult<Vec<String>> {
+        let mut problems = Vec::new();
+        for entry in fs::read_dir(self.root.join("uids"))? {
+            let path = entry?.path();
+            let Some(sop_instance_uid) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if sop_instance_uid.starts_with('.') {
+                continue;
+            }
+            match read_versions(&path) {
+                Ok(versions) => {
+                    for entry in versions {
+                        if !self.object_path(&entry.hash).is_file() {
+                            problems.push(format!(
+                                "{} version {}: object {} missing",
+                                sop_instance_uid, entry.version, entry.hash
+                            ));
+                        }
+                    }
+                }
+                Err(error) => problems.push(format!("{}: {}", sop_instance_uid, error)),
+            }
+        }
+        Ok(problems)
+    }
+}
+
+impl InstanceStore for FileStore {
+    fn store(&self, data: &[u8]) -> DicomResult<StoredInstance> {
+        let file = reader::read_file(data)?;
+        let sop_instance_uid = file
+            .dataset
+            .string(SOP_INSTANCE_UID)
+            .map(|uid| uid.trim_end_matches('\0').to_string())
+            .ok_or_else(|| {
+                DicomError::InvalidDataset("Dataset lacks its SOP Instance UID".to_string())
+            })?;
+        let uid_path = self.uid_path(&sop_instance_uid)?;
+        let hash = dedup::content_hash(&file)?;
+        let object = self.object_path(&hash);
+
+        // Objects are written before the index knows them, a crash leaves an
+        // unreferenced object at worst
+        let written = !object.exists();
+        if written {
+            write_unique(&object, data)?;
+        }
+
+        let mut index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+        let ingest = index.ingest(&sop_instance_uid, &hash);
+        match ingest {
+            Ingest::New | Ingest::Version(_) => {
+                let mut versions = if uid_path.exists() {
+                    read_versions(&uid_path)?
+                } else {
+                    vec![]
+                };
+                versions.push(index.get(&sop_instance_uid).unwrap().clone());
+                write_unique(&uid_path, dedup::versions_text(&versions).as_bytes())?;
+            }
+            Ingest::Replace => {
+                let replaced = read_versions(&uid_path)?;
+                let entry = index.get(&sop_instance_uid).unwrap().clone();
+                write_unique(&uid_path, dedup::versions_text(&[entry]).as_bytes())?;
+                for old in replaced.iter().filter(|old| old.hash != hash) {
+                    let _ = fs::remove_file(self.object_path(&old.hash));
+                }
+            }
+            Ingest::Ignored if written => {
+                let _ = fs::remove_file(&object);
+            }
+            Ingest::Identical | Ingest::Ignored => {}
+        }
+        if ingest.stores() {
+            metrics::global().instances_stored.increment();
+        }
+
+        Ok(StoredInstance {
+            sop_instance_uid,
+            hash,
+            ingest,
+            location: object.display().to_string(),
+        })
+    }
+
+    fn get(&self, sop_instance_uid: &str) -> DicomResult<Option<Vec<u8>>> {
+        match self.path(sop_instance_uid)? {
+            Some(path) => Ok(Some(fs::read(path)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn remove(&self, sop_instance_uid: &str) -> DicomResult<bool> {
+        let uid_path = self.uid_path(sop_instance_uid)?;
+        let mut index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+        index.remove(sop_instance_uid);
+        if !uid_path.exists() {
+            return Ok(false);
+        }
+
+        let versions = read_versions(&uid_path)?;
+        fs::remove_file(&uid_path)?;
+        for entry in versions {
+            let _ = fs::remove_file(self.object_path(&entry.hash));
+        }
+        Ok(true)
+    }
+
+    fn contains(&self, sop_instance_uid: &str) -> DicomResult<bool> {
+        Ok(self.uid_path(sop_instance_uid)?.exists())
+    }
+}
+
+fn read_versions(path: &Path) -> DicomResult<Vec<IndexEntry>> {
+    dedup::parse_versions(&fs::read_to_string(path)?)
+}
+
+// As document::write_atomic, with a temporary name no other writer uses
+fn write_unique(path: &Path, data: &[u8]) -> DicomResult<()> {
+    let directory = path.parent().ok_or_else(|| {
+        DicomError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} is not a file", path.display()),
+        ))
+    })?;
+    fs::create_dir_all(directory)?;
+    let temporary = directory.join(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        TEMPORARY.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = (|| -> DicomResult<()> {
+        let mut file = File::create(&temporary)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
@@ -0,0 +1,64 @@
+use crate::core::{dataset::Dataset, error::DicomResult, meta::FileMeta, writer};
+use crate::plugins::middleware::DatasetSource;
+
+pub mod cache;
+pub mod dedup;
+pub mod file_store;
+#[cfg(any(feature = "s3", feature = "default"))]
+pub mod s3;
+
+#[cfg(any(
+    all(feature = "fs", feature = "serde", feature = "compress", feature = "images"),
+    feature = "default"
+))]
+pub mod transcode;
+
+use dedup::Ingest;
+
+// What storing one instance amounted to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredInstance {
+    pub sop_instance_uid: String,
+    // Content hash of the decoded dataset, see dedup::content_hash
+    pub hash: String,
+    pub ingest: Ingest,
+    // Where the backend keeps it, a path or an object key
+    pub location: String,
+}
+
+// Backends keeping Part 10 files by SOP Instance UID. They are shared by the
+// workers of receiving services, so every method takes &self
+pub trait InstanceStore: Send + Sync {
+    fn store(&self, data: &[u8]) -> DicomResult<StoredInstance>;
+
+    // Latest version of an instance
+    fn get(&self, sop_instance_uid: &str) -> DicomResult<Option<Vec<u8>>>;
+
+    fn remove(&self, sop_instance_uid: &str) -> DicomResult<bool>;
+
+    fn contains(&self, sop_instance_uid: &str) -> DicomResult<bool> {
+        Ok(self.get(sop_instance_uid)?.is_some())
+    }
+}
+
+// What receiving services, C-STORE and STOW-RS, hand their instances to.
+// Every InstanceStore is one, writing the instance as a Part 10 file
+pub trait StorageHandler: Send + Sync {
+    fn handle(
+        &self,
+        source: &DatasetSource,
+        meta: &FileMeta,
+        dataset: &Dataset,
+    ) -> DicomResult<StoredInstance>;
+}
+
+impl<S: InstanceStore> StorageHandler for S {
+    fn handle(
+        &self,
+        _source: &DatasetSource,
+        meta: &FileMeta,
+        dataset: &Dataset,
+    ) -> DicomResult<StoredInstance> {
+        self.store(&writer::write_file_with_meta(meta, dataset)?)
+    }
+}
@@ -0,0 +1,656 @@
+use std::{
+    fmt::Write as _,
+    sync::{Mutex, PoisonError},
+};
+
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Method, Response, StatusCode};
+use sha2::{Digest, Sha256};
+use tokio::runtime::{Builder, Runtime};
+
+use super::{
+    dedup::{self, DuplicateIndex, DuplicatePolicy, IndexEntry, Ingest},
+    InstanceStore, StoredInstance,
+};
+#[cfg(feature = "serde")]
+use crate::core::bulkdata::BulkDataResolver;
+use crate::core::{
+    error::{DicomError, DicomResult},
+    meta::SOP_INSTANCE_UID,
+    metrics, reader, uid,
+};
+
+// Smallest part S3 takes in a multipart upload, save the last one
+pub const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerSideEncryption {
+    // SSE-S3, keys managed by the store
+    Aes256,
+    // SSE-KMS, with the default key of the bucket when None
+    Kms(Option<String>),
+}
+
+impl ServerSideEncryption {
+    fn headers(&self) -> Vec<(String, String)> {
+        match self {
+            ServerSideEncryption::Aes256 => vec![(
+                "x-amz-server-side-encryption".to_string(),
+                "AES256".to_string(),
+            )],
+            ServerSideEncryption::Kms(key_id) => {
+                let mut headers = vec![(
+                    "x-amz-server-side-encryption".to_string(),
+                    "aws:kms".to_string(),
+                )];
+                if let Some(key_id) = key_id {
+                    headers.push((
+                        "x-amz-server-side-encryption-aws-kms-key-id".to_string(),
+                        key_id.clone(),
+                    ));
+                }
+                headers
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    // Scheme and host, such as https://s3.eu-west-1.amazonaws.com
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub access_key: String,
+    pub secret_key: String,
+    // Of temporary credentials
+    pub session_token: Option<String>,
+    // Prepended to every key, so that stores can share a bucket
+    pub prefix: String,
+    // Bucket in the path rather than the host name, as MinIO and most other
+    // S3 compatible stores expect
+    pub path_style: bool,
+    pub encryption: Option<ServerSideEncryption>,
+    // Instances larger than this go up in parts of part_size bytes
+    pub multipart_threshold: usize,
+    pub part_size: usize,
+    pub policy: DuplicatePolicy,
+}
+
+impl S3Config {
+    pub fn new(
+        endpoint: &str,
+        region: &str,
+        bucket: &str,
+        access_key: &str,
+        secret_key: &str,
+    ) -> Self {
+        S3Config {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            region: region.to_string(),
+            bucket: bucket.to_string(),
+            access_key: access_key.to_string(),
+            secret_key: secret_key.to_string(),
+            session_token: None,
+            prefix: String::new(),
+            path_style: false,
+            encryption: None,
+            multipart_threshold: 64 * 1024 * 1024,
+            part_size: 16 * 1024 * 1024,
+            policy: DuplicatePolicy::default(),
+        }
+    }
+
+    pub fn with_session_token(mut self, token: &str) -> Self {
+        self.session_token = Some(token.to_string());
+        self
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.trim_matches('/').to_string();
+        self
+    }
+
+    pub fn with_path_style(mut self, path_style: bool) -> Self {
+        self.path_style = path_style;
+        self
+    }
+
+    pub fn with_encryption(mut self, encryption: ServerSideEncryption) -> Self {
+        self.encryption = Some(encryption);
+        self
+    }
+
+    pub fn with_multipart(mut self, threshold: usize, part_size: usize) -> Self {
+        self.multipart_threshold = threshold;
+        self.part_size = part_size.max(MIN_PART_SIZE);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+// The layout of FileStore in a bucket: objects/ab/cdef….dcm by content hash
+// and uids/<SOP Instance UID> with the "<version> <hash>" lines. Requests are
+// signed with AWS Signature Version 4 and block on a runtime of their own, so
+// the store is not to be used from async code. The versions of an instance
+// are read back from the bucket on every store, writers in other processes
+// racing on the same UID can still lose a version
+pub struct S3Store {
+    config: S3Config,
+    client: Client,
+    runtime: Runtime,
+    index: Mutex<DuplicateIndex>,
+}
+
+impl S3Store {
+    pub fn new(config: S3Config) -> DicomResult<Self> {
+        if !config.endpoint.contains("://") {
+            return Err(DicomError::InvalidValue(format!(
+                "S3 endpoint {} lacks its scheme",
+                config.endpoint
+            )));
+        }
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+
+        Ok(S3Store {
+            index: Mutex::new(DuplicateIndex::new(config.policy)),
+            config,
+            client: Client::new(),
+            runtime,
+        })
+    }
+
+    pub fn config(&self) -> &S3Config {
+        &self.config
+    }
+
+    fn key(&self, name: &str) -> String {
+        if self.config.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", self.config.prefix, name)
+        }
+    }
+
+    pub fn object_key(&self, hash: &str) -> String {
+        let (prefix, rest) = hash.split_at(2.min(hash.len()));
+        self.key(&format!("objects/{}/{}.dcm", prefix, rest))
+    }
+
+    fn uid_key(&self, sop_instance_uid: &str) -> DicomResult<String> {
+        // UIDs name objects, so only valid ones are taken
+        if !uid::is_valid(sop_instance_uid) {
+            return Err(DicomError::InvalidValue(format!(
+                "Invalid SOP Instance UID {}",
+                sop_instance_uid
+            )));
+        }
+        Ok(self.key(&format!("uids/{}", sop_instance_uid)))
+    }
+
+    // Every version stored for an instance, oldest first
+    pub fn versions(&self, sop_instance_uid: &str) -> DicomResult<Vec<IndexEntry>> {
+        let key = self.uid_key(sop_instance_uid)?;
+        match self.runtime.block_on(self.get_object(&key))? {
+            Some(data) => dedup::parse_versions(&String::from_utf8_lossy(&data)),
+            None => Ok(vec![]),
+        }
+    }
+
+    pub fn get_version(
+        &self,
+        sop_instance_uid: &str,
+        version: u32,
+    ) -> DicomResult<Option<Vec<u8>>> {
+        match self
+            .versions(sop_instance_uid)?
+            .into_iter()
+            .find(|entry| entry.version == version)
+        {
+            Some(entry) => self
+                .runtime
+                .block_on(self.get_object(&self.object_key(&entry.hash))),
+            None => Ok(None),
+        }
+    }
+
+    fn write_versions(&self, sop_instance_uid: &str, versions: &[IndexEntry]) -> DicomResult<()> {
+        let key = self.uid_key(sop_instance_uid)?;
+        let text = dedup::versions_text(versions);
+        self.runtime
+            .block_on(self.put_object(&key, text.into_bytes(), "text/plain"))
+    }
+
+    // URL and signed headers of a request, see the Signature Version 4
+    // documentation of S3 for the canonical request
+    fn sign(
+        &self,
+        method: &Method,
+        key: &str,
+        query: &[(&str, String)],
+        mut headers: Vec<(String, String)>,
+        payload: &[u8],
+    ) -> (String, Vec<(String, String)>) {
+        let (scheme, host) = self
+            .config
+            .endpoint
+            .split_once("://")
+            .unwrap_or(("https", &self.config.endpoint));
+        let (host, path) = if self.config.path_style {
+            (host.to_string(), format!("/{}/{}", self.config.bucket, key))
+        } else {
+            (
+                format!("{}.{}", self.config.bucket, host),
+                format!("/{}", key),
+            )
+        };
+        let path = encode(&path, true);
+
+        let mut query: Vec<(String, String)> = query
+            .iter()
+            .map(|(name, value)| (encode(name, false), encode(value, false)))
+            .collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let now = chrono::Utc::now();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex(&Sha256::digest(payload));
+
+        headers.push(("host".to_string(), host.clone()));
+        headers.push(("x-amz-content-sha256".to_string(), payload_hash.clone()));
+        headers.push(("x-amz-date".to_string(), timestamp.clone()));
+        if let Some(token) = &self.config.session_token {
+            headers.push(("x-amz-security-token".to_string(), token.clone()));
+        }
+        let mut headers: Vec<(String, String)> = headers
+            .into_iter()
+            .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+            .collect();
+        headers.sort();
+
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+        let mut canonical = format!("{}\n{}\n{}\n", method.as_str(), path, query);
+        for (name, value) in &headers {
+            let _ = writeln!(canonical, "{}:{}", name, value);
+        }
+        let _ = write!(canonical, "\n{}\n{}", signed_headers, payload_hash);
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            timestamp,
+            scope,
+            hex(&Sha256::digest(canonical.as_bytes()))
+        );
+        let signing_key = [self.config.region.as_str(), "s3", "aws4_request"]
+            .iter()
+            .fold(
+                hmac(
+                    format!("AWS4{}", self.config.secret_key).as_bytes(),
+                    date.as_bytes(),
+                ),
+                |key, part| hmac(&key, part.as_bytes()),
+            );
+        let signature = hex(&hmac(&signing_key, string_to_sign.as_bytes()));
+
+        headers.retain(|(name, _)| name != "host");
+        headers.push((
+            "authorization".to_string(),
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                self.config.access_key, scope, signed_headers, signature
+            ),
+        ));
+
+        let mut url = format!("{}://{}{}", scheme, host, path);
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(&query);
+        }
+        (url, headers)
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, String)],
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    ) -> DicomResult<Response> {
+        let (url, headers) = self.sign(&method, key, query, headers, &body);
+        let mut request = self.client.request(method.clone(), &url);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        Ok(request.body(body).send().await?)
+    }
+
+    // The response of a request S3 answered with success, an error carrying
+    // the S3 error code otherwise
+    async fn expect_success(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, String)],
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    ) -> DicomResult<Response> {
+        let response = self.send(method.clone(), key, query, headers, body).await?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status().as_u16();
+        let text = response.text().await.unwrap_or_default();
+        Err(failure(&method, key, status, &text))
+    }
+
+    async fn get_object(&self, key: &str) -> DicomResult<Option<Vec<u8>>> {
+        let response = self.send(Method::GET, key, &[], vec![], vec![]).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let text = response.text().await.unwrap_or_default();
+            return Err(failure(&Method::GET, key, status, &text));
+        }
+        Ok(Some(response.bytes().await?.to_vec()))
+    }
+
+    async fn head_object(&self, key: &str) -> DicomResult<bool> {
+        let response = self.send(Method::HEAD, key, &[], vec![], vec![]).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(false),
+            status if status.is_success() => Ok(true),
+            status => Err(failure(&Method::HEAD, key, status.as_u16(), "")),
+        }
+    }
+
+    async fn delete_object(&self, key: &str) -> DicomResult<()> {
+        self.expect_success(Method::DELETE, key, &[], vec![], vec![])
+            .await?;
+        Ok(())
+    }
+
+    fn encryption_headers(&self) -> Vec<(String, String)> {
+        self.config
+            .encryption
+            .as_ref()
+            .map(ServerSideEncryption::headers)
+            .unwrap_or_default()
+    }
+
+    async fn put_object(&self, key: &str, data: Vec<u8>, content_type: &str) -> DicomResult<()> {
+        if data.len() > self.config.multipart_threshold {
+            return self.put_multipart(key, data, content_type).await;
+        }
+        let mut headers = self.encryption_headers();
+        headers.push(("content-type".to_string(), content_type.to_string()));
+        self.expect_success(Method::PUT, key, &[], headers, data)
+            .await?;
+        Ok(())
+    }
+
+    // Parts go up one after the other, an upload that fails is aborted so
+    // that its parts are not billed
+    async fn put_multipart(&self, key: &str, data: Vec<u8>, content_type: &str) -> DicomResult<()> {
+        let mut headers = self.encryption_headers();
+        headers.push(("content-type".to_string(), content_type.to_string()));
+        let response = self
+            .expect_success(
+                Method::POST,
+                key,
+                &[("uploads", String::new())],
+                headers,
+                vec![],
+            )
+            .await?;
+        let text = response.text().await?;
+        let upload_id = xml_value(&text, "UploadId").ok_or_else(|| {
+            DicomError::Io(std::io::Error::other(format!(
+                "S3 gave no upload ID for {}",
+                key
+            )))
+        })?;
+
+        let result = self.upload_parts(key, &upload_id, &data).await;
+        if result.is_err() {
+            let _ = self
+                .send(
+                    Method::DELETE,
+                    key,
+                    &[("uploadId", upload_id.clone())],
+                    vec![],
+                    vec![],
+                )
+                .await;
+        }
+        result
+    }
+
+    async fn upload_parts(&self, key: &str, upload_id: &str, data: &[u8]) -> DicomResult<()> {
+        let mut complete = String::from("<CompleteMultipartUpload>");
+        for (index, part) in data.chunks(self.config.part_size).enumerate() {
+            let number = (index + 1).to_string();
+            let response = self
+                .expect_success(
+                    Method::PUT,
+                    key,
+                    &[
+                        ("partNumber", number.clone()),
+                        ("uploadId", upload_id.to_string()),
+                    ],
+                    vec![],
+                    part.to_vec(),
+                )
+                .await?;
+            let etag = response
+                .headers()
+                .get("etag")
+                .and_then(|etag| etag.to_str().ok())
+                .map(str::to_string)
+                .ok_or_else(|| {
+                    DicomError::Io(std::io::Error::other(format!(
+                        "S3 gave no ETag for part {} of {}",
+                        number, key
+                    )))
+                })?;
+            let _ = write!(
+                complete,
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                number, etag
+            );
+        }
+        complete.push_str("</CompleteMultipartUpload>");
+
+        // Completion can fail after S3 answered 200, with the error in the body
+        let response = self
+            .expect_success(
+                Method::POST,
+                key,
+                &[("uploadId", upload_id.to_string())],
+                vec![],
+                complete.into_bytes(),
+            )
+            .await?;
+        let text = response.text().await?;
+        if text.contains("<Error>") {
+            return Err(failure(&Method::POST, key, 200, &text));
+        }
+        Ok(())
+    }
+}
+
+// s3://bucket/key URIs of bulk data the store's bucket holds, the key taken
+// as it is without the prefix of the store
+#[cfg(feature = "serde")]
+impl BulkDataResolver for S3Store {
+    fn fetch(&self, uri: &str) -> DicomResult<Vec<u8>> {
+        let (bucket, key) = uri
+            .strip_prefix("s3://")
+            .and_then(|location| location.split_once('/'))
+            .ok_or_else(|| DicomError::InvalidValue(format!("Not an S3 URI: {}", uri)))?;
+        if bucket != self.config.bucket {
+            return Err(DicomError::InvalidValue(format!(
+                "{} is outside bucket {}",
+                uri, self.config.bucket
+            )));
+        }
+        self.runtime
+            .block_on(self.get_object(key))?
+            .ok_or_else(|| DicomError::InvalidValue(format!("No bulk data at {}", uri)))
+    }
+}
+
+impl InstanceStore for S3Store {
+    fn store(&self, data: &[u8]) -> DicomResult<StoredInstance> {
+        let file = reader::read_file(data)?;
+        let sop_instance_uid = file
+            .dataset
+            .string(SOP_INSTANCE_UID)
+            .map(|uid| uid.trim_end_matches('\0').to_string())
+            .ok_or_else(|| {
+                DicomError::InvalidDataset("Dataset lacks its SOP Instance UID".to_string())
+            })?;
+        self.uid_key(&sop_instance_uid)?;
+        let hash = dedup::content_hash(&file)?;
+        let object = self.object_key(&hash);
+
+        // Objects are written before the versions name them, a failure leaves
+        // an unreferenced object at worst
+        let written = !self.runtime.block_on(self.head_object(&object))?;
+        if written {
+            self.runtime
+                .block_on(self.put_object(&object, data.to_vec(), "application/dicom"))?;
+        }
+
+        let mut index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut versions = self.versions(&sop_instance_uid)?;
+        index.remove(&sop_instance_uid);
+        if let Some(latest) = versions.last() {
+            index.insert(&sop_instance_uid, latest.clone());
+        }
+        let ingest = index.ingest(&sop_instance_uid, &hash);
+        match ingest {
+            Ingest::New | Ingest::Version(_) => {
+                versions.push(index.get(&sop_instance_uid).unwrap().clone());
+                self.write_versions(&sop_instance_uid, &versions)?;
+            }
+            Ingest::Replace => {
+                let entry = index.get(&sop_instance_uid).unwrap().clone();
+                self.write_versions(&sop_instance_uid, &[entry])?;
+                for old in versions.iter().filter(|old| old.hash != hash) {
+                    let _ = self
+                        .runtime
+                        .block_on(self.delete_object(&self.object_key(&old.hash)));
+                }
+            }
+            Ingest::Ignored if written => {
+                let _ = self.runtime.block_on(self.delete_object(&object));
+            }
+            Ingest::Identical | Ingest::Ignored => {}
+        }
+        if ingest.stores() {
+            metrics::global().instances_stored.increment();
+        }
+
+        Ok(StoredInstance {
+            sop_instance_uid,
+            hash,
+            ingest,
+            location: format!("s3://{}/{}", self.config.bucket, object),
+        })
+    }
+
+    fn get(&self, sop_instance_uid: &str) -> DicomResult<Option<Vec<u8>>> {
+        match self.versions(sop_instance_uid)?.pop() {
+            Some(entry) => self
+                .runtime
+                .block_on(self.get_object(&self.object_key(&entry.hash))),
+            None => Ok(None),
+        }
+    }
+
+    fn remove(&self, sop_instance_uid: &str) -> DicomResult<bool> {
+        let key = self.uid_key(sop_instance_uid)?;
+        let mut index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+        index.remove(sop_instance_uid);
+        let versions = self.versions(sop_instance_uid)?;
+        if versions.is_empty() {
+            return Ok(false);
+        }
+
+        self.runtime.block_on(self.delete_object(&key))?;
+        for entry in versions {
+            let _ = self
+                .runtime
+                .block_on(self.delete_object(&self.object_key(&entry.hash)));
+        }
+        Ok(true)
+    }
+
+    fn contains(&self, sop_instance_uid: &str) -> DicomResult<bool> {
+        let key = self.uid_key(sop_instance_uid)?;
+        self.runtime.block_on(self.head_object(&key))
+    }
+}
+
+fn failure(method: &Method, key: &str, status: u16, body: &str) -> DicomError {
+    let code = xml_value(body, "Code").unwrap_or_else(|| format!("status {}", status));
+    DicomError::Io(std::io::Error::other(format!(
+        "S3 {} {} failed: {}",
+        method.as_str(),
+        key,
+        code
+    )))
+}
+
+// Text of the first <name> element, enough for the few S3 responses read
+fn xml_value(text: &str, name: &str) -> Option<String> {
+    let start = text.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = start + text[start..].find(&format!("</{}>", name))?;
+    Some(text[start..end].to_string())
+}
+
+// Percent encoding of SigV4, everything but the unreserved characters
+fn encode(text: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if keep_slash => encoded.push('/'),
+            _ => {
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+    encoded
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    // HMAC takes keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC key of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use super::{StorageHandler, StoredInstance};
+use crate::core::{
+    dataset::Dataset, error::DicomResult, meta::FileMeta, reader::DicomFile, transfer_syntax,
+};
+use crate::mods::transcode;
+use crate::plugins::{
+    codec::{self, CodecRegistry},
+    middleware::DatasetSource,
+};
+
+// Stores instances in one canonical transfer syntax, whatever they were
+// received in, by transcoding them before the wrapped handler sees them.
+// SOP classes can be kept as received or get a syntax of their own, and
+// lossy compressed instances are always kept, decompressing them would only
+// make them larger
+pub struct TranscodingHandler<H> {
+    inner: H,
+    target: String,
+    // None keeps the class as received
+    exceptions: HashMap<String, Option<String>>,
+    registry: CodecRegistry,
+    // Fail the store instead of keeping instances no codec can transcode
+    strict: bool,
+}
+
+impl<H: StorageHandler> TranscodingHandler<H> {
+    pub fn new(inner: H, target: &str) -> Self {
+        TranscodingHandler {
+            inner,
+            target: target.to_string(),
+            exceptions: HashMap::new(),
+            registry: codec::global_registry(),
+            strict: false,
+        }
+    }
+
+    pub fn with_registry(mut self, registry: CodecRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    pub fn with_exception(mut self, sop_class_uid: &str, target: Option<&str>) -> Self {
+        self.exceptions
+            .insert(sop_class_uid.trim().to_string(), target.map(str::to_string));
+        self
+    }
+
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    // Syntax an instance is stored in, None when it stays as received
+    pub fn target_for(&self, meta: &FileMeta) -> Option<&str> {
+        let received = meta.transfer_syntax.trim_end_matches(['\0', ' ']);
+        if transfer_syntax::lookup(received).is_none_or(|ts| ts.lossy) {
+            return None;
+        }
+        let sop_class_uid = meta
+            .media_storage_sop_class_uid
+            .trim_end_matches(['\0', ' ']);
+        let target = match self.exceptions.get(sop_class_uid) {
+            Some(exception) => exception.as_deref()?,
+            None => &self.target,
+        };
+        (target != received).then_some(target)
+    }
+}
+
+impl<H: StorageHandler> StorageHandler for TranscodingHandler<H> {
+    fn handle(
+        &self,
+        source: &DatasetSource,
+        meta: &FileMeta,
+        dataset: &Dataset,
+    ) -> DicomResult<StoredInstance> {
+        let Some(target) = self.target_for(meta) else {
+            return self.inner.handle(source, meta, dataset);
+        };
+
+        let file = DicomFile {
+            meta: meta.to_dataset()?,
+            dataset: dataset.clone(),
+            transfer_syntax: meta.transfer_syntax.clone(),
+        };
+        match transcode::transcode(&file, target, &self.registry) {
+            Ok(transcoded) => self.inner.handle(
+                source,
+                &meta.clone().with_transfer_syntax(target),
+                &transcoded.dataset,
+            ),
+            Err(error) if self.strict => Err(error),
+            Err(_) => self.inner.handle(source, meta, dataset),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, PoisonError,
+    },
+    thread,
+};
+
+use indicatif::{ProgressBar, ProgressStyle};
+use walkdir::WalkDir;
+
+use crate::core::error::{DicomError, DicomResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchOptions {
+    // Worker threads, at least 1
+    pub jobs: usize,
+    // Progress bar on stderr, drawn only when it is a terminal
+    pub progress: bool,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions {
+            jobs: 1,
+            progress: false,
+        }
+    }
+}
+
+impl BatchOptions {
+    // None takes every core
+    pub fn new(jobs: Option<usize>) -> Self {
+        let jobs = jobs.unwrap_or_else(|| {
+            thread::available_parallelism()
+                .map(|jobs| jobs.get())
+                .unwrap_or(1)
+        });
+        BatchOptions {
+            jobs: jobs.max(1),
+            progress: false,
+        }
+    }
+
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+}
+
+// Files of a file or folder, searched recursively in name order
+pub fn files(path: &Path) -> DicomResult<Vec<PathBuf>> {
+    if !path.exists() {
+        return Err(DicomError::InvalidFile(format!(
+            "{} does not exist",
+            path.display()
+        )));
+    }
+    let mut files = Vec::new();
+    for entry in WalkDir::new(path).sort_by_file_name() {
+        let entry = entry.map_err(|error| DicomError::Error(error.to_string()))?;
+        if entry.file_type().is_file() {
+            files.push(entry.into_path());
+        }
+    }
+    Ok(files)
+}
+
+fn progress_bar(options: &BatchOptions, files: usize) -> ProgressBar {
+    if !options.progress {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(files as u64);
+    if let Ok(style) =
+        ProgressStyle::with_template("{bar:40} {pos}/{len} files, {per_sec}, ETA {eta} {msg}")
+    {
+        bar.set_style(style.progress_chars("=> "));
+    }
+    bar
+}
+
+// Runs work on every file over the worker threads, results in the order of
+// the files. Datasets hold their elements in Rc, so workers parse their own
+// files and hand back only what is Send
+pub fn run<T, F>(files: &[PathBuf], options: &BatchOptions, work: F) -> Vec<DicomResult<T>>
+where
+    T: Send,
+    F: Fn(&Path) -> DicomResult<T> + Sync,
+{
+    let bar = progress_bar(options, files.len());
+    let next = AtomicUsize::new(0);
+    let errors = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<DicomResult<T>>>> =
+        Mutex::new((0..files.len()).map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..options.jobs.min(files.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = files.get(index) else {
+                    break;
+                };
+                let result = work(path);
+                if result.is_err() {
+                    let errors = errors.fetch_add(1, Ordering::Relaxed) + 1;
+                    bar.set_message(format!("{} errors", errors));
+                }
+                bar.inc(1);
+                results.lock().unwrap_or_else(PoisonError::into_inner)[index] = Some(result);
+            });
+        }
+    });
+    bar.finish_and_clear();
+
+    results
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+        .into_iter()
+        .map(|result| {
+            result.unwrap_or_else(|| Err(DicomError::Error("File not processed".to_string())))
+        })
+        .collect()
+}
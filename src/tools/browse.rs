@@ -0,0 +1,573 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph},
+    DefaultTerminal, Frame,
+};
+use walkdir::WalkDir;
+
+use crate::core::{
+    dataset::Dataset,
+    dictionary, document,
+    error::{DicomError, DicomResult},
+    meta::SOP_INSTANCE_UID,
+    reader::ParserLimits,
+    writer,
+};
+use crate::image::{
+    render::{self, RenderedFrame},
+    thumbnail,
+};
+use crate::mods::anonymize::Anonymizer;
+use crate::net::config::AeRegistry;
+use crate::utils::config::Config;
+
+pub const PATIENT_NAME: (u16, u16) = (0x0010, 0x0010);
+pub const PATIENT_ID: (u16, u16) = (0x0010, 0x0020);
+pub const STUDY_DATE: (u16, u16) = (0x0008, 0x0020);
+pub const STUDY_DESCRIPTION: (u16, u16) = (0x0008, 0x1030);
+pub const STUDY_INSTANCE_UID: (u16, u16) = (0x0020, 0x000D);
+pub const MODALITY: (u16, u16) = (0x0008, 0x0060);
+pub const SERIES_DESCRIPTION: (u16, u16) = (0x0008, 0x103E);
+pub const SERIES_INSTANCE_UID: (u16, u16) = (0x0020, 0x000E);
+pub const INSTANCE_NUMBER: (u16, u16) = (0x0020, 0x0013);
+
+#[derive(Debug, Clone, Args)]
+pub struct BrowseArgs {
+    #[arg(help = "Folder to index, searched recursively")]
+    pub dir: PathBuf,
+    #[arg(
+        short,
+        long,
+        help = "AE registry file for send, the global settings otherwise"
+    )]
+    pub config: Option<PathBuf>,
+    #[arg(short, long, help = "Peer instances are sent to")]
+    pub peer: Option<String>,
+    #[arg(
+        short,
+        long,
+        default_value = "anonymized",
+        help = "Folder anonymized instances are written to"
+    )]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub path: PathBuf,
+    pub sop_instance_uid: String,
+    pub number: Option<i32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Series {
+    pub uid: String,
+    pub modality: String,
+    pub description: String,
+    pub instances: Vec<Instance>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Study {
+    pub uid: String,
+    pub date: String,
+    pub description: String,
+    pub series: Vec<Series>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Patient {
+    pub id: String,
+    pub name: String,
+    pub studies: Vec<Study>,
+}
+
+// Files of a folder grouped by patient, study and series, in the order they
+// were found
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryIndex {
+    pub patients: Vec<Patient>,
+    // Files that are not DICOM or failed to parse
+    pub skipped: usize,
+}
+
+impl DirectoryIndex {
+    pub fn scan(dir: &Path, limits: &ParserLimits) -> DicomResult<Self> {
+        if !dir.is_dir() {
+            return Err(DicomError::InvalidFile(format!(
+                "{} is not a folder",
+                dir.display()
+            )));
+        }
+
+        let mut index = DirectoryIndex::default();
+        for entry in WalkDir::new(dir).sort_by_file_name() {
+            let entry = entry.map_err(|error| DicomError::Error(error.to_string()))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            match super::read_file(entry.path(), limits) {
+                Ok(file) => index.add(entry.path(), &file.dataset),
+                Err(_) => index.skipped += 1,
+            }
+        }
+        Ok(index)
+    }
+
+    pub fn add(&mut self, path: &Path, dataset: &Dataset) {
+        let text = |tag| dataset.string(tag).unwrap_or_default();
+
+        let patient_id = text(PATIENT_ID);
+        let patient = match self.patients.iter().position(|p| p.id == patient_id) {
+            Some(position) => &mut self.patients[position],
+            None => {
+                self.patients.push(Patient {
+                    id: patient_id,
+                    name: text(PATIENT_NAME),
+                    studies: vec![],
+                });
+                self.patients.last_mut().unwrap()
+            }
+        };
+
+        let study_uid = text(STUDY_INSTANCE_UID);
+        let study = match patient.studies.iter().position(|s| s.uid == study_uid) {
+            Some(position) => &mut patient.studies[position],
+            None => {
+                patient.studies.push(Study {
+                    uid: study_uid,
+                    date: text(STUDY_DATE),
+                    description: text(STUDY_DESCRIPTION),
+                    series: vec![],
+                });
+                patient.studies.last_mut().unwrap()
+            }
+        };
+
+        let series_uid = text(SERIES_INSTANCE_UID);
+        let series = match study.series.iter().position(|s| s.uid == series_uid) {
+            Some(position) => &mut study.series[position],
+            None => {
+                study.series.push(Series {
+                    uid: series_uid,
+                    modality: text(MODALITY),
+                    description: text(SERIES_DESCRIPTION),
+                    instances: vec![],
+                });
+                study.series.last_mut().unwrap()
+            }
+        };
+
+        series.instances.push(Instance {
+            path: path.to_path_buf(),
+            sop_instance_uid: text(SOP_INSTANCE_UID),
+            number: dataset
+                .string(INSTANCE_NUMBER)
+                .and_then(|number| number.trim().parse().ok()),
+        });
+        series.instances.sort_by_key(|instance| instance.number);
+    }
+
+    pub fn instances(&self) -> usize {
+        self.patients
+            .iter()
+            .flat_map(|patient| &patient.studies)
+            .flat_map(|study| &study.series)
+            .map(|series| series.instances.len())
+            .sum()
+    }
+
+    // The tree as indented rows, each with the files below it
+    pub fn rows(&self) -> Vec<Row> {
+        let mut rows = vec![];
+        for patient in &self.patients {
+            let files = |studies: &[Study]| -> Vec<PathBuf> {
+                studies
+                    .iter()
+                    .flat_map(|study| &study.series)
+                    .flat_map(|series| &series.instances)
+                    .map(|instance| instance.path.clone())
+                    .collect()
+            };
+            rows.push(Row {
+                depth: 0,
+                label: format!("{} [{}]", patient.name, patient.id),
+                files: files(&patient.studies),
+            });
+
+            for study in &patient.studies {
+                rows.push(Row {
+                    depth: 1,
+                    label: format!("{} {}", study.date, study.description),
+                    files: files(std::slice::from_ref(study)),
+                });
+
+                for series in &study.series {
+                    rows.push(Row {
+                        depth: 2,
+                        label: format!(
+                            "{} {} ({})",
+                            series.modality,
+                            series.description,
+                            series.instances.len()
+                        ),
+                        files: series
+                            .instances
+                            .iter()
+                            .map(|instance| instance.path.clone())
+                            .collect(),
+                    });
+
+                    for instance in &series.instances {
+                        rows.push(Row {
+                            depth: 3,
+                            label: match instance.number {
+                                Some(number) => {
+                                    format!("#{} {}", number, instance.sop_instance_uid)
+                                }
+                                None => instance.sop_instance_uid.clone(),
+                            },
+                            files: vec![instance.path.clone()],
+                        });
+                    }
+                }
+            }
+        }
+        rows
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub depth: usize,
+    pub label: String,
+    pub files: Vec<PathBuf>,
+}
+
+// Largest terminal images need no more pixels than this
+const PREVIEW_SIZE: u32 = 512;
+
+// Metadata and image of the first file below the selected row
+struct Preview {
+    elements: Vec<String>,
+    image: Option<RenderedFrame>,
+}
+
+impl Preview {
+    fn load(path: &Path, limits: &ParserLimits) -> Self {
+        let file = match super::read_file(path, limits) {
+            Ok(file) => file,
+            Err(error) => {
+                return Preview {
+                    elements: vec![error.to_string()],
+                    image: None,
+                }
+            }
+        };
+
+        let mut elements: Vec<_> = file.dataset.into_iter().collect();
+        elements.sort_by_key(|element| element.tag());
+        let elements = elements
+            .into_iter()
+            .filter(|element| element.tag().0 != 0x7FE0)
+            .map(|element| {
+                let tag = element.tag();
+                let value = element.vr();
+                format!(
+                    "({:04X},{:04X}) {} {} = {}",
+                    tag.0,
+                    tag.1,
+                    value.code(),
+                    dictionary::keyword(tag).unwrap_or("Unknown"),
+                    value
+                )
+            })
+            .collect();
+
+        Preview {
+            elements,
+            image: render::render_frame(&file, 0)
+                .ok()
+                .map(|frame| thumbnail::downsample(&frame, PREVIEW_SIZE)),
+        }
+    }
+}
+
+// Frame scaled to fit the area, two pixels per cell: the upper half block
+// takes the top pixel as foreground and the bottom one as background
+pub fn half_blocks(frame: &RenderedFrame, columns: u16, rows: u16) -> Vec<Line<'static>> {
+    if frame.width == 0 || frame.height == 0 || columns == 0 || rows == 0 {
+        return vec![];
+    }
+    let scale = (columns as f64 / frame.width as f64).min(rows as f64 * 2.0 / frame.height as f64);
+    let width = ((frame.width as f64 * scale) as u32).max(1);
+    let height = ((frame.height as f64 * scale) as u32).max(1);
+
+    let pixel = |x: u32, y: u32| {
+        let x = (x * frame.width / width).min(frame.width - 1);
+        let y = (y * frame.height / height).min(frame.height - 1);
+        let offset = ((y * frame.width + x) * 4) as usize;
+        match frame.pixels.get(offset..offset + 3) {
+            Some(rgb) => Color::Rgb(rgb[0], rgb[1], rgb[2]),
+            None => Color::Black,
+        }
+    };
+
+    (0..height.div_ceil(2))
+        .map(|row| {
+            let spans: Vec<Span> = (0..width)
+                .map(|x| {
+                    let bottom = if row * 2 + 1 < height {
+                        pixel(x, row * 2 + 1)
+                    } else {
+                        Color::Reset
+                    };
+                    Span::styled("▀", Style::new().fg(pixel(x, row * 2)).bg(bottom))
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect()
+}
+
+pub struct Browser {
+    index: DirectoryIndex,
+    rows: Vec<Row>,
+    state: ListState,
+    preview: Option<Preview>,
+    registry: AeRegistry,
+    limits: ParserLimits,
+    peer: Option<String>,
+    output: PathBuf,
+    anonymizer: Anonymizer,
+    status: String,
+}
+
+impl Browser {
+    pub fn new(index: DirectoryIndex, registry: AeRegistry) -> Self {
+        let rows = index.rows();
+        let mut state = ListState::default();
+        if !rows.is_empty() {
+            state.select(Some(0));
+        }
+        let status = format!(
+            "{} instances, {} files skipped. Arrows move, a anonymizes, s sends, q quits",
+            index.instances(),
+            index.skipped
+        );
+
+        let mut browser = Browser {
+            index,
+            rows,
+            state,
+            preview: None,
+            registry,
+            limits: ParserLimits::default(),
+            peer: None,
+            output: PathBuf::from("anonymized"),
+            anonymizer: Anonymizer::new(),
+            status,
+        };
+        browser.load_preview();
+        browser
+    }
+
+    pub fn with_peer(mut self, peer: &str) -> Self {
+        self.peer = Some(peer.to_string());
+        self
+    }
+
+    // The preview already loaded is read again within the limits
+    pub fn with_limits(mut self, limits: ParserLimits) -> Self {
+        self.limits = limits;
+        self.load_preview();
+        self
+    }
+
+    pub fn with_output(mut self, output: &Path) -> Self {
+        self.output = output.to_path_buf();
+        self
+    }
+
+    pub fn index(&self) -> &DirectoryIndex {
+        &self.index
+    }
+
+    fn selected(&self) -> Option<&Row> {
+        self.state.selected().and_then(|row| self.rows.get(row))
+    }
+
+    fn load_preview(&mut self) {
+        self.preview = self
+            .selected()
+            .and_then(|row| row.files.first())
+            .map(|path| Preview::load(path, &self.limits));
+    }
+
+    fn move_selection(&mut self, step: isize) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let current = self.state.selected().unwrap_or(0) as isize;
+        let next = (current + step).clamp(0, self.rows.len() as isize - 1) as usize;
+        if Some(next) != self.state.selected() {
+            self.state.select(Some(next));
+            self.load_preview();
+        }
+    }
+
+    // Anonymizes every file below the selected row into the output folder
+    pub fn anonymize(&mut self) -> DicomResult<String> {
+        let files = self
+            .selected()
+            .map(|row| row.files.clone())
+            .unwrap_or_default();
+        fs::create_dir_all(&self.output)?;
+
+        for path in &files {
+            let file = super::read_file(path, &self.limits)?;
+            let anonymized = self.anonymizer.anonymize(&file.dataset)?;
+            let name = anonymized.string(SOP_INSTANCE_UID).unwrap_or_else(|| {
+                path.file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            });
+            let data = writer::write_file(&anonymized, &file.transfer_syntax)?;
+            document::write_atomic(&self.output.join(format!("{}.dcm", name)), &data, false)?;
+        }
+        Ok(format!(
+            "Anonymized {} files into {}",
+            files.len(),
+            self.output.display()
+        ))
+    }
+
+    // C-STOREs every file below the selected row to the peer
+    pub fn send(&mut self) -> DicomResult<String> {
+        let peer = self
+            .peer
+            .clone()
+            .ok_or_else(|| DicomError::InvalidValue("No peer given, see --peer".to_string()))?;
+        let files = self
+            .selected()
+            .map(|row| row.files.clone())
+            .unwrap_or_default();
+
+        let mut failed = 0;
+        for path in &files {
+            let file = super::read_file(path, &self.limits)?;
+            if !self.registry.store(&peer, &file.dataset)?.is_success() {
+                failed += 1;
+            }
+        }
+        Ok(format!(
+            "Sent {} files to {}, {} failed",
+            files.len() - failed,
+            peer,
+            failed
+        ))
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [tree, right] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+        let [metadata, image] =
+            Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(right);
+
+        let items: Vec<ListItem> = self
+            .rows
+            .iter()
+            .map(|row| ListItem::new(format!("{}{}", "  ".repeat(row.depth), row.label)))
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title("Patients"))
+            .highlight_style(Style::new().fg(Color::Black).bg(Color::White));
+        frame.render_stateful_widget(list, tree, &mut self.state);
+
+        let elements: Vec<Line> = self
+            .preview
+            .iter()
+            .flat_map(|preview| &preview.elements)
+            .map(|element| Line::from(element.clone()))
+            .collect();
+        frame.render_widget(
+            Paragraph::new(elements).block(Block::bordered().title("Metadata")),
+            metadata,
+        );
+
+        let inner = Rect {
+            x: image.x + 1,
+            y: image.y + 1,
+            width: image.width.saturating_sub(2),
+            height: image.height.saturating_sub(2),
+        };
+        let lines = self
+            .preview
+            .as_ref()
+            .and_then(|preview| preview.image.as_ref())
+            .map(|rendered| half_blocks(rendered, inner.width, inner.height))
+            .unwrap_or_default();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Image")),
+            image,
+        );
+
+        frame.render_widget(Paragraph::new(self.status.clone()), status);
+    }
+
+    pub fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> DicomResult<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let key = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                _ => continue,
+            };
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+                KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+                KeyCode::PageDown => self.move_selection(10),
+                KeyCode::PageUp => self.move_selection(-10),
+                KeyCode::Char('a') => {
+                    self.status = self.anonymize().unwrap_or_else(|error| error.to_string())
+                }
+                KeyCode::Char('s') => {
+                    self.status = "Sending...".to_string();
+                    terminal.draw(|frame| self.draw(frame))?;
+                    self.status = self.send().unwrap_or_else(|error| error.to_string())
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+pub fn run(args: BrowseArgs, config: &Config) -> DicomResult<()> {
+    let registry = config.registry_for(args.config.as_deref())?;
+    let index = DirectoryIndex::scan(&args.dir, &config.limits)?;
+    let mut browser = Browser::new(index, registry)
+        .with_limits(config.limits)
+        .with_output(&args.output);
+    if let Some(peer) = &args.peer {
+        browser = browser.with_peer(peer);
+    }
+
+    let mut terminal = ratatui::init();
+    let result = browser.event_loop(&mut terminal);
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,48 @@
+use std::{fs, path::PathBuf};
+
+use clap::{Args, CommandFactory};
+use clap_complete::Shell;
+
+use super::Cli;
+use crate::core::error::{DicomError, DicomResult};
+
+#[derive(Debug, Clone, Args)]
+pub struct CompletionsArgs {
+    #[arg(value_enum, help = "Shell to write the completion script for")]
+    pub shell: Option<Shell>,
+    #[arg(
+        short,
+        long,
+        help = "Folder the completion script is written to, stdout otherwise"
+    )]
+    pub output: Option<PathBuf>,
+    #[arg(long, help = "Folder to write man pages to, one per subcommand")]
+    pub man: Option<PathBuf>,
+}
+
+pub fn run(args: CompletionsArgs) -> DicomResult<()> {
+    if args.shell.is_none() && args.man.is_none() {
+        return Err(DicomError::InvalidValue(
+            "Give a shell, --man or both".to_string(),
+        ));
+    }
+
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    if let Some(shell) = args.shell {
+        match &args.output {
+            Some(folder) => {
+                fs::create_dir_all(folder)?;
+                let path = clap_complete::generate_to(shell, &mut command, &name, folder)?;
+                eprintln!("Wrote {}", path.display());
+            }
+            None => clap_complete::generate(shell, &mut command, &name, &mut std::io::stdout()),
+        }
+    }
+    if let Some(folder) = &args.man {
+        fs::create_dir_all(folder)?;
+        clap_mangen::generate_to(command, folder)?;
+        eprintln!("Wrote man pages to {}", folder.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,626 @@
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::Args;
+use tokio::runtime::{Builder, Runtime};
+
+use super::{
+    output::{self, OutputFormat, Report},
+    simulate,
+};
+use crate::core::{
+    error::{DicomError, DicomResult},
+    uid,
+};
+use crate::net::{
+    association::Association,
+    cancel::CancellationToken,
+    config::PeerConfig,
+    dimse::MessageIdGenerator,
+    find,
+    query::QueryBuilder,
+    relational::{QueryLevel, STUDY_ROOT_FIND_SOP_CLASS},
+};
+use crate::utils::config::Config;
+use crate::web::client::{DicomWebClient, WadoClient};
+
+// Instances remembered for WADO-RS, enough to spread reads over the archive
+const MAX_TARGETS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Operation {
+    Store,
+    Find,
+    Wado,
+}
+
+impl Operation {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "store" | "c-store" => Some(Operation::Store),
+            "find" | "c-find" => Some(Operation::Find),
+            "wado" | "wado-rs" => Some(Operation::Wado),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Store => "C-STORE",
+            Operation::Find => "C-FIND",
+            Operation::Wado => "WADO-RS",
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// Weighted mix such as store=2,find=1,wado=1, spread into the order the
+// requests are issued in so each stretch of the run has the same mix
+pub fn parse_mix(mix: &str) -> DicomResult<Vec<Operation>> {
+    let mut weights = Vec::new();
+    for entry in mix.split(',').filter(|entry| !entry.trim().is_empty()) {
+        let (name, weight) = entry.split_once('=').unwrap_or((entry, "1"));
+        let operation = Operation::parse(name).ok_or_else(|| {
+            DicomError::InvalidValue(format!(
+                "Operation {} in the mix, expected store, find or wado",
+                name
+            ))
+        })?;
+        let weight: usize = weight.trim().parse().map_err(|_| {
+            DicomError::InvalidValue(format!("Weight {} of {} in the mix", weight, name))
+        })?;
+        weights.push((operation, weight));
+    }
+
+    let total: usize = weights.iter().map(|(_, weight)| weight).sum();
+    if total == 0 {
+        return Err(DicomError::InvalidValue(
+            "The mix has no operation with a weight".to_string(),
+        ));
+    }
+    // Smooth weighted round robin, store=2,find=1 gives store find store
+    let mut current = vec![0isize; weights.len()];
+    let mut plan = Vec::with_capacity(total);
+    for _ in 0..total {
+        for (credit, (_, weight)) in current.iter_mut().zip(&weights) {
+            *credit += *weight as isize;
+        }
+        let Some(index) =
+            (0..weights.len()).max_by_key(|index| (current[*index], -(*index as isize)))
+        else {
+            break;
+        };
+        current[index] -= total as isize;
+        plan.push(weights[index].0);
+    }
+    Ok(plan)
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct LoadtestArgs {
+    #[arg(
+        short,
+        long,
+        help = "Registered peer name or AE@host[:port] for C-STORE and C-FIND"
+    )]
+    pub peer: Option<String>,
+    #[arg(short, long, help = "DICOMweb base URL for WADO-RS")]
+    pub url: Option<String>,
+    #[arg(
+        long,
+        default_value = "store=1,find=1,wado=1",
+        help = "Weights of the operations, e.g. store=4,find=2,wado=1"
+    )]
+    pub mix: String,
+    #[arg(
+        short,
+        long,
+        help = "Target requests per second over all workers, as fast as possible otherwise"
+    )]
+    pub rate: Option<f64>,
+    #[arg(
+        short,
+        long,
+        default_value_t = 30,
+        help = "Length of the run in seconds"
+    )]
+    pub duration: u64,
+    #[arg(short = 'n', long, help = "Stop after this many requests")]
+    pub requests: Option<u64>,
+    #[arg(
+        short,
+        long,
+        default_value_t = 4,
+        help = "Concurrent workers, each with its own association"
+    )]
+    pub concurrency: usize,
+    #[arg(
+        short,
+        long,
+        default_value = "CT",
+        help = "Modality of the stored instances"
+    )]
+    pub modality: String,
+    #[arg(
+        long,
+        default_value = "LOADTEST",
+        help = "Patient ID of the stored instances, which C-FIND looks for"
+    )]
+    pub patient_id: String,
+    #[arg(
+        long = "instance",
+        help = "STUDY/SERIES/INSTANCE UIDs for WADO-RS to read, repeatable, the stored ones otherwise"
+    )]
+    pub instances: Vec<String>,
+    #[arg(short, long, help = "AE registry file, the global settings otherwise")]
+    pub config: Option<PathBuf>,
+    #[arg(long, help = "Calling AE title, overrides the registry")]
+    pub calling_ae: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OperationStats {
+    pub operation: Operation,
+    // Sorted once the run ends
+    pub latencies: Vec<Duration>,
+    pub errors: usize,
+    // WADO-RS requests with no instance stored yet to read
+    pub skipped: usize,
+    pub first_error: Option<String>,
+}
+
+impl OperationStats {
+    pub fn new(operation: Operation) -> Self {
+        OperationStats {
+            operation,
+            latencies: Vec::new(),
+            errors: 0,
+            skipped: 0,
+            first_error: None,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.latencies.len() + self.errors
+    }
+
+    // Nearest rank percentile of the successful requests
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let rank = (percent / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        self.latencies
+            .get(rank.clamp(1, self.latencies.len()) - 1)
+            .copied()
+    }
+
+    pub fn merge(&mut self, other: OperationStats) {
+        self.latencies.extend(other.latencies);
+        self.errors += other.errors;
+        self.skipped += other.skipped;
+        if self.first_error.is_none() {
+            self.first_error = other.first_error;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadtestReport {
+    pub target_rate: Option<f64>,
+    pub concurrency: usize,
+    pub elapsed: Duration,
+    pub operations: Vec<OperationStats>,
+}
+
+impl LoadtestReport {
+    pub fn requests(&self) -> usize {
+        self.operations.iter().map(OperationStats::count).sum()
+    }
+
+    pub fn errors(&self) -> usize {
+        self.operations.iter().map(|stats| stats.errors).sum()
+    }
+
+    pub fn rate(&self, count: usize) -> f64 {
+        count as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.errors() == 0
+    }
+}
+
+fn millis(duration: Option<Duration>) -> String {
+    duration.map_or_else(
+        || "-".to_string(),
+        |duration| format!("{:.1}", duration.as_secs_f64() * 1000.0),
+    )
+}
+
+impl fmt::Display for LoadtestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} requests in {:.1} s by {} workers, {:.1}/s{}",
+            self.requests(),
+            self.elapsed.as_secs_f64(),
+            self.concurrency,
+            self.rate(self.requests()),
+            self.target_rate
+                .map(|rate| format!(" of {:.1}/s targeted", rate))
+                .unwrap_or_default()
+        )?;
+        writeln!(
+            f,
+            "{:<8} {:>8} {:>7} {:>8} {:>9} {:>9} {:>9} {:>9}",
+            "", "requests", "errors", "per s", "p50 ms", "p90 ms", "p99 ms", "max ms"
+        )?;
+        for stats in &self.operations {
+            writeln!(
+                f,
+                "{:<8} {:>8} {:>7} {:>8.1} {:>9} {:>9} {:>9} {:>9}",
+                stats.operation.as_str(),
+                stats.count(),
+                stats.errors,
+                self.rate(stats.count()),
+                millis(stats.percentile(50.0)),
+                millis(stats.percentile(90.0)),
+                millis(stats.percentile(99.0)),
+                millis(stats.latencies.last().copied())
+            )?;
+        }
+        for stats in &self.operations {
+            if stats.skipped > 0 {
+                writeln!(
+                    f,
+                    "{} skipped {} times, nothing stored yet to read",
+                    stats.operation, stats.skipped
+                )?;
+            }
+            if let Some(error) = &stats.first_error {
+                writeln!(f, "{} first error: {}", stats.operation, error)?;
+            }
+        }
+        write!(f, "Result: {}", if self.is_ok() { "OK" } else { "FAILED" })
+    }
+}
+
+impl Report for LoadtestReport {
+    fn to_json(&self) -> serde_json::Value {
+        let ms =
+            |duration: Option<Duration>| duration.map(|duration| duration.as_secs_f64() * 1000.0);
+        let operations: Vec<serde_json::Value> = self
+            .operations
+            .iter()
+            .map(|stats| {
+                serde_json::json!({
+                    "operation": stats.operation.as_str(),
+                    "requests": stats.count(),
+                    "errors": stats.errors,
+                    "skipped": stats.skipped,
+                    "per_second": self.rate(stats.count()),
+                    "p50_ms": ms(stats.percentile(50.0)),
+                    "p90_ms": ms(stats.percentile(90.0)),
+                    "p99_ms": ms(stats.percentile(99.0)),
+                    "max_ms": ms(stats.latencies.last().copied()),
+                    "first_error": stats.first_error,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "target_rate": self.target_rate,
+            "concurrency": self.concurrency,
+            "elapsed_ms": self.elapsed.as_secs_f64() * 1000.0,
+            "requests": self.requests(),
+            "errors": self.errors(),
+            "per_second": self.rate(self.requests()),
+            "operations": operations,
+            "ok": self.is_ok(),
+        })
+    }
+}
+
+// What the workers share: the request plan, the pace and the instances
+// stored so far for WADO-RS to read
+struct Run {
+    plan: Vec<Operation>,
+    peer: Option<PeerConfig>,
+    calling_ae: String,
+    wado: Option<WadoClient>,
+    modality: String,
+    patient_id: String,
+    rate: Option<f64>,
+    requests: Option<u64>,
+    started: Instant,
+    deadline: Instant,
+    next: AtomicU64,
+    targets: Mutex<Vec<(String, String, String)>>,
+}
+
+impl Run {
+    // Slot of the next request and when it is due, None once the run is over
+    fn next_slot(&self) -> Option<(u64, Instant)> {
+        let slot = self.next.fetch_add(1, Ordering::Relaxed);
+        if self.requests.is_some_and(|requests| slot >= requests) {
+            return None;
+        }
+        let due = match self.rate {
+            Some(rate) => self.started + Duration::from_secs_f64(slot as f64 / rate),
+            None => Instant::now(),
+        };
+        (due < self.deadline).then_some((slot, due))
+    }
+}
+
+// One worker, with an association and a runtime of its own, reconnecting
+// after a failed DIMSE request
+struct Worker<'a> {
+    run: &'a Run,
+    association: Option<Association>,
+    runtime: Option<Runtime>,
+    message_ids: MessageIdGenerator,
+    study_instance_uid: String,
+    series_instance_uid: String,
+}
+
+impl<'a> Worker<'a> {
+    fn new(run: &'a Run) -> Self {
+        Worker {
+            run,
+            association: None,
+            runtime: None,
+            message_ids: MessageIdGenerator::new(),
+            study_instance_uid: uid::generate(),
+            series_instance_uid: uid::generate(),
+        }
+    }
+
+    // Opened on first use, fields borrowed apart so the message IDs stay
+    // usable alongside
+    fn connect<'b>(
+        run: &Run,
+        association: &'b mut Option<Association>,
+    ) -> DicomResult<&'b mut Association> {
+        if association.is_none() {
+            let peer = run.peer.as_ref().ok_or_else(|| {
+                DicomError::InvalidValue("C-STORE and C-FIND need a peer".to_string())
+            })?;
+            let sop_class_uid = simulate::template(&run.modality)?.sop_class_uid;
+            let options = peer
+                .association_options(&run.calling_ae, &[sop_class_uid, STUDY_ROOT_FIND_SOP_CLASS]);
+            *association = Some(Association::request(
+                (peer.host.as_str(), peer.port),
+                options,
+            )?);
+        }
+        association
+            .as_mut()
+            .ok_or_else(|| DicomError::Network("No association".to_string()))
+    }
+
+    fn store(&mut self) -> DicomResult<()> {
+        let dataset = simulate::template(&self.run.modality)?
+            .with_patient(&self.run.patient_id, "LOADTEST^PATIENT")
+            .with_study(&self.study_instance_uid)
+            .with_series(&self.series_instance_uid)
+            .build();
+        let association = Self::connect(self.run, &mut self.association)?;
+        simulate::store(association, &self.message_ids, &dataset)?;
+
+        let mut targets = self
+            .run
+            .targets
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if targets.len() < MAX_TARGETS {
+            targets.push((
+                self.study_instance_uid.clone(),
+                self.series_instance_uid.clone(),
+                dataset.string((0x0008, 0x0018)).unwrap_or_default(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn find(&mut self) -> DicomResult<()> {
+        let query = QueryBuilder::new(QueryLevel::Study)
+            .with_patient_id(&self.run.patient_id)
+            .build()?;
+        let status = find::find(
+            Self::connect(self.run, &mut self.association)?,
+            STUDY_ROOT_FIND_SOP_CLASS,
+            &query,
+            &CancellationToken::new(),
+            |_| {},
+        )?;
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(DicomError::InvalidValue(status.to_string()))
+        }
+    }
+
+    // Whether there was an instance to read
+    fn wado(&mut self, slot: u64) -> DicomResult<bool> {
+        let wado = self
+            .run
+            .wado
+            .as_ref()
+            .ok_or_else(|| DicomError::InvalidValue("WADO-RS needs a URL".to_string()))?;
+        let target = {
+            let targets = self
+                .run
+                .targets
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            match targets.len() {
+                0 => return Ok(false),
+                count => targets[slot as usize % count].clone(),
+            }
+        };
+        if self.runtime.is_none() {
+            self.runtime = Some(Builder::new_current_thread().enable_all().build()?);
+        }
+        if let Some(runtime) = &self.runtime {
+            runtime.block_on(wado.retrieve_instance(&target.0, &target.1, &target.2))?;
+        }
+        Ok(true)
+    }
+
+    fn work(mut self) -> Vec<OperationStats> {
+        let mut stats: Vec<OperationStats> = [Operation::Store, Operation::Find, Operation::Wado]
+            .into_iter()
+            .map(OperationStats::new)
+            .collect();
+
+        while let Some((slot, due)) = self.run.next_slot() {
+            thread::sleep(due.saturating_duration_since(Instant::now()));
+            let operation = self.run.plan[slot as usize % self.run.plan.len()];
+            let sent = Instant::now();
+            let result = match operation {
+                Operation::Store => self.store().map(|_| true),
+                Operation::Find => self.find().map(|_| true),
+                Operation::Wado => self.wado(slot),
+            };
+
+            let stats = &mut stats[operation as usize];
+            match result {
+                Ok(true) => stats.latencies.push(sent.elapsed()),
+                Ok(false) => stats.skipped += 1,
+                Err(error) => {
+                    stats.errors += 1;
+                    stats.first_error.get_or_insert_with(|| error.to_string());
+                    // The association may be unusable after a failure
+                    if operation != Operation::Wado {
+                        self.association = None;
+                    }
+                }
+            }
+        }
+
+        if let Some(association) = self.association.take() {
+            let _ = association.release();
+        }
+        stats
+    }
+}
+
+pub fn run(args: LoadtestArgs, format: OutputFormat, config: &Config) -> DicomResult<()> {
+    let plan = parse_mix(&args.mix)?;
+    let needs_peer = plan.iter().any(|operation| *operation != Operation::Wado);
+    if needs_peer && args.peer.is_none() {
+        return Err(DicomError::InvalidValue(
+            "C-STORE and C-FIND in the mix need --peer".to_string(),
+        ));
+    }
+    if plan.contains(&Operation::Wado) && args.url.is_none() {
+        return Err(DicomError::InvalidValue(
+            "WADO-RS in the mix needs --url".to_string(),
+        ));
+    }
+    if args.rate.is_some_and(|rate| rate <= 0.0) {
+        return Err(DicomError::InvalidValue(
+            "The rate must be above 0".to_string(),
+        ));
+    }
+    simulate::template(&args.modality)?;
+
+    let mut targets = Vec::new();
+    for instance in &args.instances {
+        match instance.split('/').collect::<Vec<_>>()[..] {
+            [study, series, instance] => {
+                targets.push((study.to_string(), series.to_string(), instance.to_string()))
+            }
+            _ => {
+                return Err(DicomError::InvalidValue(format!(
+                    "Instance {}, expected STUDY/SERIES/INSTANCE",
+                    instance
+                )))
+            }
+        }
+    }
+
+    let (peer, calling_ae) = match &args.peer {
+        Some(peer) => {
+            let registry = config.registry_for(args.config.as_deref())?;
+            let calling_ae = args
+                .calling_ae
+                .clone()
+                .unwrap_or_else(|| registry.calling_ae().to_string());
+            (Some(registry.resolve(peer)?), calling_ae)
+        }
+        None => (None, args.calling_ae.clone().unwrap_or_default()),
+    };
+
+    let started = Instant::now();
+    let run = Run {
+        plan,
+        peer,
+        calling_ae,
+        wado: args
+            .url
+            .as_deref()
+            .map(|url| WadoClient::new(DicomWebClient::new(url))),
+        modality: args.modality.to_ascii_uppercase(),
+        patient_id: args.patient_id.clone(),
+        rate: args.rate,
+        requests: args.requests,
+        started,
+        deadline: started + Duration::from_secs(args.duration),
+        next: AtomicU64::new(0),
+        targets: Mutex::new(targets),
+    };
+
+    let mut operations: Vec<OperationStats> = [Operation::Store, Operation::Find, Operation::Wado]
+        .into_iter()
+        .map(OperationStats::new)
+        .collect();
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..args.concurrency.max(1))
+            .map(|_| scope.spawn(|| Worker::new(&run).work()))
+            .collect();
+        for worker in workers {
+            if let Ok(stats) = worker.join() {
+                for (total, stats) in operations.iter_mut().zip(stats) {
+                    total.merge(stats);
+                }
+            }
+        }
+    });
+
+    operations.retain(|stats| run.plan.contains(&stats.operation));
+    for stats in &mut operations {
+        stats.latencies.sort();
+    }
+    let report = LoadtestReport {
+        target_rate: args.rate,
+        concurrency: args.concurrency.max(1),
+        elapsed: started.elapsed(),
+        operations,
+    };
+    output::print(&report, format)?;
+
+    if report.is_ok() {
+        Ok(())
+    } else {
+        Err(DicomError::Network(format!(
+            "{} of {} requests failed",
+            report.errors(),
+            report.requests()
+        )))
+    }
+}
@@ -0,0 +1,283 @@
+use std::{fs, path::Path};
+
+use clap::{Parser, Subcommand};
+
+use crate::{
+    core::{
+        error::{DicomError, DicomResult},
+        padding::ValuePolicy,
+        reader::{self, DicomFile, ParserLimits},
+    },
+    utils::config::Config,
+};
+use output::OutputFormat;
+
+#[cfg(any(
+    all(
+        feature = "tui",
+        feature = "net",
+        feature = "serde",
+        feature = "toml",
+        feature = "images",
+        feature = "compress",
+        feature = "sha2",
+        feature = "hmac"
+    ),
+    feature = "default"
+))]
+pub mod browse;
+
+#[cfg(any(feature = "completions", feature = "default"))]
+pub mod completions;
+
+pub mod output;
+
+#[cfg(any(
+    all(feature = "net", feature = "serde", feature = "toml"),
+    feature = "default"
+))]
+pub mod ping;
+
+#[cfg(any(
+    all(
+        feature = "shell",
+        feature = "net",
+        feature = "serde",
+        feature = "toml"
+    ),
+    feature = "default"
+))]
+pub mod shell;
+
+#[cfg(any(
+    all(
+        feature = "storage",
+        feature = "net",
+        feature = "secure",
+        feature = "serde",
+        feature = "toml"
+    ),
+    feature = "default"
+))]
+pub mod selftest;
+
+#[cfg(any(feature = "fs", feature = "default"))]
+pub mod batch;
+
+#[cfg(any(feature = "fs", feature = "default"))]
+pub mod stats;
+
+#[cfg(any(
+    all(feature = "net", feature = "serde", feature = "toml"),
+    feature = "default"
+))]
+pub mod simulate;
+
+#[cfg(any(
+    all(
+        feature = "net",
+        feature = "secure",
+        feature = "serde",
+        feature = "toml"
+    ),
+    feature = "default"
+))]
+pub mod loadtest;
+
+// Command line of the dicom binary
+#[derive(Debug, Parser)]
+#[command(name = "dicom", version, about = "DICOM toolkit")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+    #[arg(long, global = true, help = "Print the result as JSON")]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    // Patient, study and series tree of a folder with previews, anonymize and send
+    #[cfg(any(
+        all(
+            feature = "tui",
+            feature = "net",
+            feature = "serde",
+            feature = "toml",
+            feature = "images",
+            feature = "compress",
+            feature = "sha2",
+            feature = "hmac"
+        ),
+        feature = "default"
+    ))]
+    Browse(browse::BrowseArgs),
+    // Shell completion scripts and man pages
+    #[cfg(any(feature = "completions", feature = "default"))]
+    Completions(completions::CompletionsArgs),
+    // Checks connectivity to a peer with C-ECHO and reports what it accepts
+    #[cfg(any(
+        all(feature = "net", feature = "serde", feature = "toml"),
+        feature = "default"
+    ))]
+    Ping(ping::PingArgs),
+    // Interactive prompt to inspect, edit, save and send a file
+    #[cfg(any(
+        all(
+            feature = "shell",
+            feature = "net",
+            feature = "serde",
+            feature = "toml"
+        ),
+        feature = "default"
+    ))]
+    Shell(shell::ShellArgs),
+    // Checks the instance store, its index and peers, for health probes
+    #[cfg(any(
+        all(
+            feature = "storage",
+            feature = "net",
+            feature = "secure",
+            feature = "serde",
+            feature = "toml"
+        ),
+        feature = "default"
+    ))]
+    Selftest(selftest::SelftestArgs),
+    // Element counts, memory usage and the largest elements of files in a folder
+    #[cfg(any(feature = "fs", feature = "default"))]
+    Stats(stats::StatsArgs),
+    // Acquires a synthetic study as a modality would, worklist, C-STORE and MPPS
+    #[cfg(any(
+        all(feature = "net", feature = "serde", feature = "toml"),
+        feature = "default"
+    ))]
+    Simulate(simulate::SimulateArgs),
+    // C-STORE, C-FIND and WADO-RS traffic at a target rate, with latency percentiles
+    #[cfg(any(
+        all(
+            feature = "net",
+            feature = "secure",
+            feature = "serde",
+            feature = "toml"
+        ),
+        feature = "default"
+    ))]
+    Loadtest(loadtest::LoadtestArgs),
+}
+
+impl Command {
+    // Interactive commands print no report
+    pub fn has_json_output(&self) -> bool {
+        match self {
+            #[cfg(any(
+                all(
+                    feature = "tui",
+                    feature = "net",
+                    feature = "serde",
+                    feature = "toml",
+                    feature = "images",
+                    feature = "compress",
+                    feature = "sha2",
+                    feature = "hmac"
+                ),
+                feature = "default"
+            ))]
+            Command::Browse(_) => false,
+            #[cfg(any(feature = "completions", feature = "default"))]
+            Command::Completions(_) => false,
+            #[cfg(any(
+                all(
+                    feature = "shell",
+                    feature = "net",
+                    feature = "serde",
+                    feature = "toml"
+                ),
+                feature = "default"
+            ))]
+            Command::Shell(_) => false,
+            _ => true,
+        }
+    }
+}
+
+// Runs a command with the configuration main loaded
+pub fn run(command: Command, format: OutputFormat, config: &Config) -> DicomResult<()> {
+    match command {
+        #[cfg(any(
+            all(
+                feature = "tui",
+                feature = "net",
+                feature = "serde",
+                feature = "toml",
+                feature = "images",
+                feature = "compress",
+                feature = "sha2",
+                feature = "hmac"
+            ),
+            feature = "default"
+        ))]
+        Command::Browse(args) => browse::run(args, config),
+        #[cfg(any(feature = "completions", feature = "default"))]
+        Command::Completions(args) => completions::run(args),
+        #[cfg(any(
+            all(feature = "net", feature = "serde", feature = "toml"),
+            feature = "default"
+        ))]
+        Command::Ping(args) => ping::run(args, format, config),
+        #[cfg(any(
+            all(
+                feature = "shell",
+                feature = "net",
+                feature = "serde",
+                feature = "toml"
+            ),
+            feature = "default"
+        ))]
+        Command::Shell(args) => shell::run(args, config),
+        #[cfg(any(
+            all(
+                feature = "storage",
+                feature = "net",
+                feature = "secure",
+                feature = "serde",
+                feature = "toml"
+            ),
+            feature = "default"
+        ))]
+        Command::Selftest(args) => selftest::run(args, format, config),
+        #[cfg(any(feature = "fs", feature = "default"))]
+        Command::Stats(args) => stats::run(args, format, &config.limits),
+        #[cfg(any(
+            all(feature = "net", feature = "serde", feature = "toml"),
+            feature = "default"
+        ))]
+        Command::Simulate(args) => simulate::run(args, format, config),
+        #[cfg(any(
+            all(
+                feature = "net",
+                feature = "secure",
+                feature = "serde",
+                feature = "toml"
+            ),
+            feature = "default"
+        ))]
+        Command::Loadtest(args) => loadtest::run(args, format, config),
+    }
+}
+
+// Reads a file within the parser limits of the configuration, the size
+// checked before anything is read
+pub fn read_file(path: &Path, limits: &ParserLimits) -> DicomResult<DicomFile> {
+    if let Some(max) = limits.max_file_size {
+        let size = fs::metadata(path)?.len();
+        if size > max as u64 {
+            return Err(DicomError::InvalidLength(format!(
+                "{} bytes exceed the limit of {}",
+                size, max
+            )));
+        }
+    }
+
+    let data = fs::read(path)?;
+    reader::read_file_with_limits(&data, &ValuePolicy::default(), limits).map(|(file, _)| file)
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+
+use crate::core::error::{DicomError, DicomResult};
+
+// Exit code of an invalid command line, as clap exits with it
+pub const USAGE_EXIT_CODE: i32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    // One JSON document on stdout, errors included
+    Json,
+}
+
+// What went wrong, by what a script would do about it. Exit codes:
+//   0  success
+//   1  other failures
+//   2  invalid command line
+//   3  input that does not parse, files, values or settings
+//   4  checks that ran and did not pass
+//   5  connections, associations and HTTP requests
+//   6  reading or writing files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureClass {
+    Parse,
+    Validation,
+    Network,
+    Io,
+    Other,
+}
+
+impl FailureClass {
+    pub fn of(error: &DicomError) -> Self {
+        use std::io::ErrorKind;
+        match error.root() {
+            DicomError::InvalidTag(_)
+            | DicomError::InvalidVR(_)
+            | DicomError::InvalidValue(_)
+            | DicomError::InvalidLength(_)
+            | DicomError::InvalidFile(_)
+            | DicomError::InvalidDataset(_)
+            | DicomError::ObsoleteElement(_)
+            | DicomError::SyntaxError(_)
+            | DicomError::UnsupportedTransferSyntax(_) => FailureClass::Parse,
+            DicomError::ValidationFailed(_) => FailureClass::Validation,
+            DicomError::Network(_)
+            | DicomError::HttpStatus(_)
+            | DicomError::AssociationAborted { .. }
+            | DicomError::AssociationRejected(_)
+            | DicomError::Timeout(_)
+            | DicomError::External { .. } => FailureClass::Network,
+            DicomError::Io(error) => match error.kind() {
+                ErrorKind::ConnectionRefused
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::NotConnected
+                | ErrorKind::AddrNotAvailable
+                | ErrorKind::BrokenPipe
+                | ErrorKind::TimedOut => FailureClass::Network,
+                _ => FailureClass::Io,
+            },
+            _ => FailureClass::Other,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureClass::Parse => "parse",
+            FailureClass::Validation => "validation",
+            FailureClass::Network => "network",
+            FailureClass::Io => "io",
+            FailureClass::Other => "other",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            FailureClass::Other => 1,
+            FailureClass::Parse => 3,
+            FailureClass::Validation => 4,
+            FailureClass::Network => 5,
+            FailureClass::Io => 6,
+        }
+    }
+}
+
+impl fmt::Display for FailureClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// Result of a command, printed for people or as JSON for scripts. Field
+// names of the JSON are kept once released, new ones only get added
+pub trait Report: fmt::Display {
+    #[cfg(any(feature = "serde", feature = "default"))]
+    fn to_json(&self) -> serde_json::Value;
+}
+
+pub fn print<R: Report>(report: &R, format: OutputFormat) -> DicomResult<()> {
+    match format {
+        OutputFormat::Text => println!("{}", report.to_string().trim_end()),
+        #[cfg(any(feature = "serde", feature = "default"))]
+        OutputFormat::Json => println!("{}", report.to_json()),
+        #[cfg(not(any(feature = "serde", feature = "default")))]
+        OutputFormat::Json => {
+            return Err(DicomError::InvalidValue(
+                "JSON output needs the serde feature".to_string(),
+            ))
+        }
+    }
+    Ok(())
+}
+
+// Errors go to stderr as text, and to stdout as
+// {"error": {"class", "message", "exit_code"}} in JSON mode, so scripts
+// read one document either way
+pub fn print_error(error: &DicomError, format: OutputFormat) {
+    let class = FailureClass::of(error);
+    match format {
+        #[cfg(any(feature = "serde", feature = "default"))]
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::json!({
+                "error": {
+                    "class": class.as_str(),
+                    "message": error.to_string(),
+                    "exit_code": class.exit_code(),
+                }
+            })
+        ),
+        _ => eprintln!("{}", error),
+    }
+}
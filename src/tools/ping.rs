@@ -0,0 +1,379 @@
+use std::{
+    fmt,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use clap::Args;
+
+use super::output::{self, OutputFormat, Report};
+use crate::core::error::{DicomError, DicomResult};
+use crate::net::{
+    association::{Association, AssociationOptions},
+    config::PeerConfig,
+    dimse::{self, CEchoRq, CEchoRsp, DimseCommand, DimseMessage, MessageIdGenerator},
+    pdu::PresentationContextResult,
+    status::DimseStatus,
+};
+use crate::utils::config::Config;
+
+// An association proposes at most 128 presentation contexts
+const MAX_CONTEXTS: usize = 128;
+
+#[derive(Debug, Clone, Args)]
+pub struct PingArgs {
+    #[arg(short, long, help = "Registered peer name or AE@host[:port]")]
+    pub peer: String,
+    #[arg(short, long, help = "AE registry file, the global settings otherwise")]
+    pub config: Option<PathBuf>,
+    #[arg(long, help = "Calling AE title, overrides the registry")]
+    pub calling_ae: Option<String>,
+    #[arg(
+        short = 'n',
+        long,
+        default_value_t = 3,
+        help = "Number of C-ECHO requests"
+    )]
+    pub count: u32,
+    #[arg(long, default_value_t = 10, help = "Timeout in seconds")]
+    pub timeout: u64,
+    #[arg(
+        short = 't',
+        long = "transfer-syntax",
+        help = "Transfer syntax to test, repeatable, the peer's preferred ones by default"
+    )]
+    pub transfer_syntaxes: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PingOptions {
+    pub count: u32,
+    pub timeout: Duration,
+    pub transfer_syntaxes: Vec<String>,
+}
+
+impl Default for PingOptions {
+    fn default() -> Self {
+        PingOptions {
+            count: 3,
+            timeout: Duration::from_secs(10),
+            transfer_syntaxes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ContextReport {
+    pub abstract_syntax: String,
+    pub transfer_syntax: String,
+    // None when the peer did not answer the context
+    pub result: Option<PresentationContextResult>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PingReport {
+    pub peer: String,
+    pub calling_ae: String,
+    pub called_ae: String,
+    pub address: String,
+    // Connection and association negotiation
+    pub setup: Option<Duration>,
+    pub contexts: Vec<ContextReport>,
+    pub echoes: Vec<Result<(Duration, DimseStatus), String>>,
+    pub release: Option<Duration>,
+    pub error: Option<String>,
+}
+
+impl PingReport {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+            && !self.echoes.is_empty()
+            && self
+                .echoes
+                .iter()
+                .all(|echo| matches!(echo, Ok((_, status)) if status.is_success()))
+    }
+
+    // Minimum, average and maximum round trip of the answered echoes
+    pub fn latency(&self) -> Option<(Duration, Duration, Duration)> {
+        let times: Vec<Duration> = self
+            .echoes
+            .iter()
+            .filter_map(|echo| echo.as_ref().ok().map(|(time, _)| *time))
+            .collect();
+        let min = times.iter().min()?;
+        let max = times.iter().max()?;
+        let average = times.iter().sum::<Duration>() / times.len() as u32;
+        Some((*min, average, *max))
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
+
+fn describe(result: Option<PresentationContextResult>) -> &'static str {
+    match result {
+        Some(PresentationContextResult::Acceptance) => "accepted",
+        Some(PresentationContextResult::UserRejection) => "rejected by user",
+        Some(PresentationContextResult::NoReason) => "rejected, no reason",
+        Some(PresentationContextResult::AbstractSyntaxNotSupported) => {
+            "rejected, abstract syntax not supported"
+        }
+        Some(PresentationContextResult::TransferSyntaxesNotSupported) => {
+            "rejected, transfer syntax not supported"
+        }
+        None => "not answered",
+    }
+}
+
+impl fmt::Display for PingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Peer {} ({} -> {} at {})",
+            self.peer, self.calling_ae, self.called_ae, self.address
+        )?;
+
+        match self.setup {
+            Some(setup) => writeln!(f, "Association established in {}", millis(setup))?,
+            None => writeln!(f, "Association not established")?,
+        }
+
+        if self.setup.is_some() {
+            writeln!(f, "Presentation contexts:")?;
+            for context in &self.contexts {
+                writeln!(
+                    f,
+                    "  {} / {}: {}",
+                    context.abstract_syntax,
+                    context.transfer_syntax,
+                    describe(context.result)
+                )?;
+            }
+        }
+
+        for (index, echo) in self.echoes.iter().enumerate() {
+            match echo {
+                Ok((time, status)) => {
+                    writeln!(f, "C-ECHO {}: {} in {}", index + 1, status, millis(*time))?
+                }
+                Err(error) => writeln!(f, "C-ECHO {}: {}", index + 1, error)?,
+            }
+        }
+
+        if let Some((min, average, max)) = self.latency() {
+            writeln!(
+                f,
+                "Round trip min/avg/max: {} / {} / {}",
+                millis(min),
+                millis(average),
+                millis(max)
+            )?;
+        }
+
+        if let Some(release) = self.release {
+            writeln!(f, "Association released in {}", millis(release))?;
+        }
+
+        if let Some(error) = &self.error {
+            writeln!(f, "Error: {}", error)?;
+        }
+
+        write!(f, "Result: {}", if self.is_ok() { "OK" } else { "FAILED" })
+    }
+}
+
+impl Report for PingReport {
+    fn to_json(&self) -> serde_json::Value {
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let contexts: Vec<serde_json::Value> = self
+            .contexts
+            .iter()
+            .map(|context| {
+                serde_json::json!({
+                    "abstract_syntax": context.abstract_syntax,
+                    "transfer_syntax": context.transfer_syntax,
+                    "accepted": context.result == Some(PresentationContextResult::Acceptance),
+                    "result": describe(context.result),
+                })
+            })
+            .collect();
+        let echoes: Vec<serde_json::Value> = self
+            .echoes
+            .iter()
+            .map(|echo| match echo {
+                Ok((time, status)) => serde_json::json!({
+                    "ok": status.is_success(),
+                    "ms": millis(*time),
+                    "status": status.to_string(),
+                }),
+                Err(error) => serde_json::json!({ "ok": false, "error": error }),
+            })
+            .collect();
+        serde_json::json!({
+            "peer": self.peer,
+            "calling_ae": self.calling_ae,
+            "called_ae": self.called_ae,
+            "address": self.address,
+            "setup_ms": self.setup.map(millis),
+            "contexts": contexts,
+            "echoes": echoes,
+            "release_ms": self.release.map(millis),
+            "error": self.error,
+            "ok": self.is_ok(),
+        })
+    }
+}
+
+// Verification plus the peer's SOP classes, each proposed once per transfer
+// syntax so the report shows which combinations the peer accepts
+pub fn ping(peer: &PeerConfig, calling_ae: &str, options: &PingOptions) -> PingReport {
+    let transfer_syntaxes: Vec<&str> = if options.transfer_syntaxes.is_empty() {
+        peer.preferred_transfer_syntaxes()
+    } else {
+        options
+            .transfer_syntaxes
+            .iter()
+            .map(String::as_str)
+            .collect()
+    };
+
+    let mut abstract_syntaxes = vec![dimse::VERIFICATION_SOP_CLASS];
+    abstract_syntaxes.extend(
+        peer.sop_classes
+            .iter()
+            .map(String::as_str)
+            .filter(|uid| *uid != dimse::VERIFICATION_SOP_CLASS),
+    );
+
+    let mut association_options = AssociationOptions::new(calling_ae, &peer.ae_title)
+        .with_connect_timeout(Some(options.timeout))
+        .with_artim_timeout(options.timeout)
+        .with_dimse_timeout(Some(options.timeout));
+    let mut contexts = Vec::new();
+    for abstract_syntax in &abstract_syntaxes {
+        for transfer_syntax in &transfer_syntaxes {
+            if contexts.len() == MAX_CONTEXTS {
+                break;
+            }
+            association_options =
+                association_options.with_presentation_context(abstract_syntax, &[transfer_syntax]);
+            contexts.push(ContextReport {
+                abstract_syntax: abstract_syntax.to_string(),
+                transfer_syntax: transfer_syntax.to_string(),
+                result: None,
+            });
+        }
+    }
+
+    let mut report = PingReport {
+        peer: peer.name.clone(),
+        calling_ae: calling_ae.to_string(),
+        called_ae: peer.ae_title.clone(),
+        address: peer.address(),
+        setup: None,
+        contexts,
+        echoes: Vec::new(),
+        release: None,
+        error: None,
+    };
+
+    if peer.tls.is_some() {
+        report.error =
+            Some("TLS is configured but DIMSE associations do not support it yet".into());
+        return report;
+    }
+
+    let started = Instant::now();
+    let association = Association::request((peer.host.as_str(), peer.port), association_options);
+    let mut association = match association {
+        Ok(association) => association,
+        Err(error) => {
+            report.error = Some(error.to_string());
+            return report;
+        }
+    };
+    report.setup = Some(started.elapsed());
+
+    for (index, context) in report.contexts.iter_mut().enumerate() {
+        let id = (index * 2 + 1) as u8;
+        context.result = association
+            .presentation_contexts()
+            .iter()
+            .find(|accepted| accepted.id == id)
+            .map(|accepted| accepted.result);
+    }
+
+    let Some(context_id) = association
+        .context_for(dimse::VERIFICATION_SOP_CLASS)
+        .map(|context| context.id)
+    else {
+        report.error = Some("Verification SOP class was not accepted".into());
+        if let Err(error) = association.release() {
+            report.error = Some(format!(
+                "Verification SOP class was not accepted, {}",
+                error
+            ));
+        }
+        return report;
+    };
+
+    let ids = MessageIdGenerator::new();
+    for _ in 0..options.count {
+        let request = CEchoRq {
+            message_id: ids.next_id(),
+            affected_sop_class_uid: dimse::VERIFICATION_SOP_CLASS.to_string(),
+        };
+        let message = DimseMessage::new(&request, None);
+
+        let started = Instant::now();
+        let response = association
+            .send_message(context_id, &message)
+            .and_then(|_| association.receive_response(&message.command))
+            .and_then(|(_, response)| CEchoRsp::from_command(&response.command));
+        match response {
+            Ok(response) => report.echoes.push(Ok((started.elapsed(), response.status))),
+            Err(error) => {
+                // The association is unusable once a request failed
+                report.echoes.push(Err(error.to_string()));
+                report.error = Some(error.to_string());
+                return report;
+            }
+        }
+    }
+
+    let started = Instant::now();
+    match association.release() {
+        Ok(()) => report.release = Some(started.elapsed()),
+        Err(error) => report.error = Some(error.to_string()),
+    }
+
+    report
+}
+
+pub fn run(args: PingArgs, format: OutputFormat, config: &Config) -> DicomResult<()> {
+    let registry = config.registry_for(args.config.as_deref())?;
+    let peer = registry.resolve(&args.peer)?;
+    let calling_ae = args
+        .calling_ae
+        .as_deref()
+        .unwrap_or_else(|| registry.calling_ae());
+
+    let options = PingOptions {
+        count: args.count,
+        timeout: Duration::from_secs(args.timeout),
+        transfer_syntaxes: args.transfer_syntaxes,
+    };
+    let report = ping(&peer, calling_ae, &options);
+    output::print(&report, format)?;
+
+    if report.is_ok() {
+        Ok(())
+    } else {
+        Err(DicomError::Network(format!(
+            "Verification of peer {} failed",
+            peer.name
+        )))
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    fmt,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use clap::Args;
+
+use super::{
+    output::{self, OutputFormat, Report},
+    ping::{self, PingOptions},
+};
+use crate::core::error::{DicomError, DicomResult};
+use crate::net::config::PeerConfig;
+use crate::storage::{dedup::DuplicatePolicy, file_store::FileStore, InstanceStore};
+use crate::utils::config::Config;
+use crate::web::metrics::MetricsEndpoint;
+
+// Valid but never issued, so looking it up only proves the backend answers
+const PROBE_UID: &str = "2.25.0";
+
+#[derive(Debug, Clone, Args)]
+pub struct SelftestArgs {
+    #[arg(short, long, help = "Root folder of the instance store to check")]
+    pub store: Option<PathBuf>,
+    #[arg(
+        short,
+        long = "peer",
+        help = "Registered peer name or AE@host[:port] to C-ECHO, repeatable"
+    )]
+    pub peers: Vec<String>,
+    #[arg(long, help = "C-ECHO every peer of the registry")]
+    pub all_peers: bool,
+    #[arg(short, long, help = "AE registry file, the global settings otherwise")]
+    pub config: Option<PathBuf>,
+    #[arg(long, help = "Calling AE title, overrides the registry")]
+    pub calling_ae: Option<String>,
+    #[arg(long, default_value_t = 5, help = "Timeout of each peer in seconds")]
+    pub timeout: u64,
+    #[arg(
+        long,
+        help = "Serve /healthz, /readyz and /metrics on this address instead of checking once"
+    )]
+    pub listen: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+    pub duration: Duration,
+}
+
+impl Check {
+    fn timed<F: FnOnce() -> Result<String, String>>(name: &str, check: F) -> Self {
+        let started = Instant::now();
+        let result = check();
+        Check {
+            name: name.to_string(),
+            ok: result.is_ok(),
+            detail: result.unwrap_or_else(|error| error),
+            duration: started.elapsed(),
+        }
+    }
+}
+
+// Outcome of the checks behind readiness. Liveness needs none, a process
+// able to answer is alive
+#[derive(Debug, Clone, Default)]
+pub struct SelftestReport {
+    pub checks: Vec<Check>,
+}
+
+impl SelftestReport {
+    pub fn is_ok(&self) -> bool {
+        self.checks.iter().all(|check| check.ok)
+    }
+
+    // Status of /readyz, 503 keeps orchestrators from routing traffic here
+    pub fn status_code(&self) -> u16 {
+        if self.is_ok() {
+            200
+        } else {
+            503
+        }
+    }
+
+    // Any backend answering a lookup, e.g. an S3 bucket
+    pub fn check_store(&mut self, name: &str, store: &dyn InstanceStore) {
+        self.checks.push(Check::timed(name, || {
+            store
+                .contains(PROBE_UID)
+                .map(|_| "reachable".to_string())
+                .map_err(|error| error.to_string())
+        }));
+    }
+
+    // Reachability of a file store plus the integrity of its version index
+    pub fn check_file_store(&mut self, store: &FileStore) {
+        let name = format!("store {}", store.root().display());
+        self.check_store(&name, store);
+        self.checks.push(Check::timed(
+            &format!("index {}", store.root().display()),
+            || {
+                let problems = store.check_index().map_err(|error| error.to_string())?;
+                match problems.len() {
+                    0 => Ok("intact".to_string()),
+                    count => Err(format!("{} problems, first {}", count, problems[0])),
+                }
+            },
+        ));
+    }
+
+    // A single C-ECHO over a verification only association
+    pub fn check_peer(&mut self, peer: &PeerConfig, calling_ae: &str, timeout: Duration) {
+        let peer = PeerConfig {
+            sop_classes: Vec::new(),
+            ..peer.clone()
+        };
+        let options = PingOptions {
+            count: 1,
+            timeout,
+            transfer_syntaxes: Vec::new(),
+        };
+        self.checks
+            .push(Check::timed(&format!("peer {}", peer.name), || {
+                let report = ping::ping(&peer, calling_ae, &options);
+                match (report.is_ok(), report.latency()) {
+                    (true, Some((_, average, _))) => Ok(format!(
+                        "C-ECHO in {:.1} ms",
+                        average.as_secs_f64() * 1000.0
+                    )),
+                    _ => Err(report.error.unwrap_or_else(|| "C-ECHO failed".to_string())),
+                }
+            }));
+    }
+}
+
+impl fmt::Display for SelftestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(
+                f,
+                "{} {}: {} ({:.1} ms)",
+                if check.ok { "OK    " } else { "FAILED" },
+                check.name,
+                check.detail,
+                check.duration.as_secs_f64() * 1000.0
+            )?;
+        }
+        write!(f, "Result: {}", if self.is_ok() { "OK" } else { "FAILED" })
+    }
+}
+
+impl Report for SelftestReport {
+    fn to_json(&self) -> serde_json::Value {
+        let checks: Vec<serde_json::Value> = self
+            .checks
+            .iter()
+            .map(|check| {
+                serde_json::json!({
+                    "name": check.name,
+                    "ok": check.ok,
+                    "detail": check.detail,
+                    "ms": check.duration.as_secs_f64() * 1000.0,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "checks": checks,
+            "status_code": self.status_code(),
+            "ok": self.is_ok(),
+        })
+    }
+}
+
+// What readiness depends on, checked afresh on each run so a lost mount or
+// a peer going down shows on the next probe
+#[derive(Debug, Clone, Default)]
+pub struct ReadinessChecks {
+    pub store: Option<PathBuf>,
+    pub peers: Vec<PeerConfig>,
+    pub calling_ae: String,
+    pub timeout: Duration,
+}
+
+impl ReadinessChecks {
+    pub fn is_empty(&self) -> bool {
+        self.store.is_none() && self.peers.is_empty()
+    }
+
+    pub fn run(&self) -> SelftestReport {
+        let mut report = SelftestReport::default();
+        if let Some(root) = &self.store {
+            // Opening creates missing folders, which would hide a lost mount
+            let store = if root.is_dir() {
+                FileStore::open(root, DuplicatePolicy::default()).map_err(|error| error.to_string())
+            } else {
+                Err("not a folder".to_string())
+            };
+            match store {
+                Ok(store) => report.check_file_store(&store),
+                Err(error) => report.checks.push(Check {
+                    name: format!("store {}", root.display()),
+                    ok: false,
+                    detail: error,
+                    duration: Duration::ZERO,
+                }),
+            }
+        }
+
+        for peer in &self.peers {
+            report.check_peer(peer, &self.calling_ae, self.timeout);
+        }
+        report
+    }
+
+    // Readiness check of MetricsEndpoint, the status code and report text
+    pub fn probe(self) -> impl Fn() -> (u16, String) + Send + Sync + 'static {
+        move || {
+            let report = self.run();
+            (report.status_code(), format!("{}\n", report))
+        }
+    }
+}
+
+pub fn run(args: SelftestArgs, format: OutputFormat, config: &Config) -> DicomResult<()> {
+    let mut checks = ReadinessChecks {
+        store: args.store.clone(),
+        timeout: Duration::from_secs(args.timeout),
+        ..ReadinessChecks::default()
+    };
+
+    if args.all_peers || !args.peers.is_empty() {
+        let registry = config.registry_for(args.config.as_deref())?;
+        checks.calling_ae = args
+            .calling_ae
+            .clone()
+            .unwrap_or_else(|| registry.calling_ae().to_string());
+        if args.all_peers {
+            checks.peers.extend(registry.peers().cloned());
+        }
+        for spec in &args.peers {
+            checks.peers.push(registry.resolve(spec)?);
+        }
+    }
+
+    if checks.is_empty() {
+        return Err(DicomError::InvalidValue(
+            "Nothing to check, give a store or peers".to_string(),
+        ));
+    }
+
+    if let Some(address) = &args.listen {
+        return MetricsEndpoint::new()
+            .with_readiness(checks.probe())
+            .listen(address.as_str());
+    }
+
+    let report = checks.run();
+    output::print(&report, format)?;
+    if report.is_ok() {
+        Ok(())
+    } else {
+        let failed = report.checks.iter().filter(|check| !check.ok).count();
+        Err(DicomError::ValidationFailed(format!(
+            "{} of {} self-test checks",
+            failed,
+            report.checks.len()
+        )))
+    }
+}
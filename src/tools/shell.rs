@@ -0,0 +1,373 @@
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use clap::Args;
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Context, Editor, Helper,
+};
+
+use crate::core::{
+    dataset::Dataset,
+    dictionary::{self, parse_tag},
+    document,
+    element::DicomElement,
+    error::{DicomError, DicomResult},
+    reader::ParserLimits,
+    tag::{DicomTag, VisualRepresentation},
+    transfer_syntax, writer,
+};
+use crate::net::config::AeRegistry;
+use crate::utils::config::Config;
+
+// Name, arguments and description
+const COMMANDS: &[(&str, &str, &str)] = &[
+    ("get", "<tag|keyword>", "show an element"),
+    (
+        "set",
+        "<tag|keyword> [value]",
+        "set an element, empty without a value",
+    ),
+    ("remove", "<tag|keyword>", "remove an element"),
+    ("tree", "", "show the dataset with its sequences"),
+    ("hexdump", "<tag|keyword>", "show the encoded value"),
+    ("lookup", "<text>", "find dictionary keywords"),
+    ("save", "[path]", "write the file, in place by default"),
+    ("send", "<peer>", "C-STORE the dataset to a peer"),
+    ("help", "", "list the commands"),
+    ("quit", "", "leave, unsaved changes are lost"),
+];
+
+#[derive(Debug, Clone, Args)]
+pub struct ShellArgs {
+    #[arg(help = "DICOM file to explore")]
+    pub path: PathBuf,
+    #[arg(
+        short,
+        long,
+        help = "AE registry file for send, the global settings otherwise"
+    )]
+    pub config: Option<PathBuf>,
+}
+
+// Result of one shell command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Output(String),
+    Quit,
+}
+
+pub struct Shell {
+    path: PathBuf,
+    dataset: Dataset,
+    transfer_syntax: String,
+    registry: AeRegistry,
+    modified: bool,
+}
+
+fn describe(element: &Rc<dyn DicomTag>) -> String {
+    let tag = element.tag();
+    let value = element.vr();
+    let keyword = dictionary::keyword(tag).unwrap_or("Unknown");
+    format!(
+        "({:04X},{:04X}) {} {} = {}",
+        tag.0,
+        tag.1,
+        value.code(),
+        keyword,
+        value
+    )
+}
+
+fn tree(dataset: &Dataset, depth: usize, output: &mut String) {
+    let mut elements: Vec<_> = dataset.into_iter().collect();
+    elements.sort_by_key(|element| element.tag());
+
+    for element in elements {
+        let _ = writeln!(output, "{}{}", "  ".repeat(depth), describe(element));
+        for (index, item) in element.vr().items().iter().enumerate() {
+            if let Some(item) = item.dataset() {
+                let _ = writeln!(output, "{}Item {}", "  ".repeat(depth + 1), index + 1);
+                tree(item, depth + 2, output);
+            }
+        }
+    }
+}
+
+// Offsets, 16 bytes in hex and their printable characters per line
+pub fn hexdump(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let text: String = chunk
+            .iter()
+            .map(|byte| {
+                if byte.is_ascii_graphic() || *byte == b' ' {
+                    *byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let _ = writeln!(output, "{:08X}  {:<47}  {}", line * 16, hex.join(" "), text);
+    }
+    output
+}
+
+impl Shell {
+    pub fn open(path: &Path, registry: AeRegistry, limits: &ParserLimits) -> DicomResult<Self> {
+        let file = super::read_file(path, limits)?;
+        Ok(Shell {
+            path: path.to_path_buf(),
+            dataset: file.dataset,
+            transfer_syntax: file.transfer_syntax,
+            registry,
+            modified: false,
+        })
+    }
+
+    pub fn dataset(&self) -> &Dataset {
+        &self.dataset
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    pub fn execute(&mut self, line: &str) -> DicomResult<Step> {
+        let line = line.trim();
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        let output = match command {
+            "" => String::new(),
+            "get" => self.get(rest)?,
+            "set" => self.set(rest)?,
+            "remove" => self.remove(rest)?,
+            "tree" => {
+                let mut output = String::new();
+                tree(&self.dataset, 0, &mut output);
+                output
+            }
+            "hexdump" => self.hexdump(rest)?,
+            "lookup" => lookup(rest),
+            "save" => self.save(rest)?,
+            "send" => self.send(rest)?,
+            "help" => COMMANDS
+                .iter()
+                .map(|(name, arguments, description)| {
+                    format!(
+                        "{:<30} {}\n",
+                        format!("{} {}", name, arguments),
+                        description
+                    )
+                })
+                .collect(),
+            "quit" | "exit" => return Ok(Step::Quit),
+            other => {
+                return Err(DicomError::InvalidValue(format!(
+                    "Unknown command {}, see help",
+                    other
+                )))
+            }
+        };
+        Ok(Step::Output(output))
+    }
+
+    fn element(&self, argument: &str) -> DicomResult<&Rc<dyn DicomTag>> {
+        let tag = parse_tag(argument)?;
+        self.dataset.find(tag).ok_or_else(|| {
+            DicomError::InvalidTag(format!("({:04X},{:04X}) is not set", tag.0, tag.1))
+        })
+    }
+
+    fn get(&self, argument: &str) -> DicomResult<String> {
+        Ok(format!("{}\n", describe(self.element(argument)?)))
+    }
+
+    fn set(&mut self, argument: &str) -> DicomResult<String> {
+        let (name, value) = argument.split_once(' ').unwrap_or((argument, ""));
+        let tag = parse_tag(name)?;
+        let vr = match self.dataset.find(tag) {
+            Some(element) => element.vr().code(),
+            None => dictionary::vr_of(tag),
+        };
+        if vr == "SQ" {
+            return Err(DicomError::InvalidVR(
+                "Sequences cannot be set from the shell".to_string(),
+            ));
+        }
+
+        let value = value.trim();
+        if value.is_empty() {
+            self.dataset.set_empty_as(tag, vr);
+        } else {
+            let value = VisualRepresentation::try_from_string(vr, value)?;
+            self.dataset.put(Rc::new(DicomElement::new(tag, value)));
+        }
+        self.modified = true;
+        self.get(name)
+    }
+
+    fn remove(&mut self, argument: &str) -> DicomResult<String> {
+        let tag = parse_tag(argument)?;
+        match self.dataset.remove(tag) {
+            Some(element) => {
+                self.modified = true;
+                Ok(format!("Removed {}\n", describe(&element)))
+            }
+            None => Err(DicomError::InvalidTag(format!(
+                "({:04X},{:04X}) is not set",
+                tag.0, tag.1
+            ))),
+        }
+    }
+
+    fn hexdump(&self, argument: &str) -> DicomResult<String> {
+        let big_endian =
+            transfer_syntax::lookup(&self.transfer_syntax).is_some_and(|syntax| syntax.big_endian);
+        let value = self.element(argument)?.vr();
+        if let VisualRepresentation::SQ(_) = value {
+            return Err(DicomError::InvalidVR(
+                "Sequences have no value of their own, see tree".to_string(),
+            ));
+        }
+        Ok(hexdump(&writer::encode_value(&value, big_endian)))
+    }
+
+    fn save(&mut self, argument: &str) -> DicomResult<String> {
+        let path = if argument.is_empty() {
+            self.path.clone()
+        } else {
+            PathBuf::from(argument)
+        };
+
+        let data = writer::write_file(&self.dataset, &self.transfer_syntax)?;
+        document::write_atomic(&path, &data, false)?;
+        if path == self.path {
+            self.modified = false;
+        }
+        Ok(format!(
+            "Wrote {} bytes to {}\n",
+            data.len(),
+            path.display()
+        ))
+    }
+
+    fn send(&mut self, peer: &str) -> DicomResult<String> {
+        if peer.is_empty() {
+            return Err(DicomError::InvalidValue("send needs a peer".to_string()));
+        }
+        let status = self.registry.store(peer, &self.dataset)?;
+        Ok(format!("C-STORE to {}: {}\n", peer, status))
+    }
+}
+
+fn lookup(text: &str) -> String {
+    let text = text.to_lowercase();
+    dictionary::ENTRIES
+        .iter()
+        .filter(|entry| entry.keyword.to_lowercase().contains(&text))
+        .map(|entry| {
+            format!(
+                "({:04X},{:04X}) {} {}\n",
+                entry.tag.0, entry.tag.1, entry.vr, entry.keyword
+            )
+        })
+        .collect()
+}
+
+// Completes commands, then keywords or peer names depending on the command
+struct ShellHelper {
+    peers: Vec<String>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _context: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let start = line.rfind(' ').map_or(0, |space| space + 1);
+        let word = &line[start..];
+
+        let before: Vec<&str> = line[..start].split_whitespace().collect();
+        let candidates = match before.as_slice() {
+            [] => COMMANDS
+                .iter()
+                .map(|(command, _, _)| command.to_string())
+                .filter(|command| command.starts_with(word))
+                .collect(),
+            ["send"] => self
+                .peers
+                .iter()
+                .filter(|peer| peer.starts_with(word))
+                .cloned()
+                .collect(),
+            ["get" | "set" | "remove" | "hexdump"] => dictionary::ENTRIES
+                .iter()
+                .map(|entry| entry.keyword)
+                .filter(|keyword| keyword.starts_with(word))
+                .map(str::to_string)
+                .collect(),
+            _ => vec![],
+        };
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+pub fn run(args: ShellArgs, config: &Config) -> DicomResult<()> {
+    let registry = config.registry_for(args.config.as_deref())?;
+    let peers = registry.peers().map(|peer| peer.name.clone()).collect();
+    let mut shell = Shell::open(&args.path, registry, &config.limits)?;
+
+    let mut editor: Editor<ShellHelper, DefaultHistory> =
+        Editor::new().map_err(|error| DicomError::Error(error.to_string()))?;
+    editor.set_helper(Some(ShellHelper { peers }));
+
+    let name = args
+        .path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    println!(
+        "{}, {} elements. Type help for the commands.",
+        name,
+        shell.dataset().len()
+    );
+
+    loop {
+        let prompt = format!("{}{}> ", name, if shell.is_modified() { "*" } else { "" });
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(error) => return Err(DicomError::Error(error.to_string())),
+        };
+        let _ = editor.add_history_entry(line.as_str());
+
+        match shell.execute(&line) {
+            Ok(Step::Output(output)) => print!("{}", output),
+            Ok(Step::Quit) => break,
+            Err(error) => eprintln!("{}", error),
+        }
+    }
+    Ok(())
+}
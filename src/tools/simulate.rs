@@ -0,0 +1,603 @@
+use std::{
+    fmt, fs,
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use clap::Args;
+
+use super::output::{self, OutputFormat, Report};
+use crate::core::{
+    dataset::Dataset,
+    element::DicomElement,
+    error::{DicomError, DicomResult},
+    template::{self, Template},
+    transfer_syntax, uid, writer,
+};
+use crate::net::{
+    association::Association,
+    cancel::CancellationToken,
+    config::AeRegistry,
+    dimse::{CStoreRq, CStoreRsp, DimseCommand, DimseMessage, MessageIdGenerator, Priority},
+    find,
+    ian::MODALITY_PERFORMED_PROCEDURE_STEP_SOP_CLASS,
+    matching,
+    mpps::{self, PerformedProcedureStep, StepStatus},
+    worklist::{WorklistItem, MODALITY_WORKLIST_FIND_SOP_CLASS},
+};
+use crate::utils::config::Config;
+
+const FRAME_OF_REFERENCE_UID: (u16, u16) = (0x0020, 0x0052);
+const IMAGE_POSITION_PATIENT: (u16, u16) = (0x0020, 0x0032);
+const CONTENT_DATE: (u16, u16) = (0x0008, 0x0023);
+const CONTENT_TIME: (u16, u16) = (0x0008, 0x0033);
+const STUDY_DATE: (u16, u16) = (0x0008, 0x0020);
+const STUDY_TIME: (u16, u16) = (0x0008, 0x0030);
+const SERIES_NUMBER: (u16, u16) = (0x0020, 0x0011);
+const INSTANCE_NUMBER: (u16, u16) = (0x0020, 0x0013);
+const REFERENCED_PERFORMED_PROCEDURE_STEP_SEQUENCE: (u16, u16) = (0x0008, 0x1111);
+const REQUEST_ATTRIBUTES_SEQUENCE: (u16, u16) = (0x0040, 0x0275);
+
+#[derive(Debug, Clone, Args)]
+pub struct SimulateArgs {
+    #[arg(
+        short,
+        long,
+        default_value = "CT",
+        help = "Modality to simulate: CT, MR, US or OT for secondary capture"
+    )]
+    pub modality: String,
+    #[arg(long, default_value_t = 1, help = "Number of series in the study")]
+    pub series: u32,
+    #[arg(long, default_value_t = 10, help = "Number of images per series")]
+    pub images: u32,
+    #[arg(long, help = "Registered peer name or AE@host[:port] to C-STORE to")]
+    pub send: Option<String>,
+    #[arg(
+        long,
+        help = "Peer to query for a scheduled step of the modality, which gives the patient and study"
+    )]
+    pub worklist: Option<String>,
+    #[arg(long, help = "Peer to report the procedure step to with MPPS")]
+    pub mpps: Option<String>,
+    #[arg(short, long, help = "Folder to write the generated files to")]
+    pub output: Option<PathBuf>,
+    #[arg(
+        long,
+        default_value = "SIM0001",
+        help = "Patient ID without a worklist"
+    )]
+    pub patient_id: String,
+    #[arg(
+        long,
+        default_value = "SIMULATED^PATIENT",
+        help = "Patient name without a worklist"
+    )]
+    pub patient_name: String,
+    #[arg(short, long, help = "AE registry file, the global settings otherwise")]
+    pub config: Option<PathBuf>,
+    #[arg(long, help = "Calling AE title, overrides the registry")]
+    pub calling_ae: Option<String>,
+}
+
+// What a simulated acquisition did, step by step
+#[derive(Debug, Clone, Default)]
+pub struct SimulateReport {
+    pub modality: String,
+    pub patient_id: String,
+    pub patient_name: String,
+    pub accession_number: String,
+    pub study_instance_uid: String,
+    // Scheduled procedure step ID taken from the worklist
+    pub scheduled_step: Option<String>,
+    // Series instance UID and number of images
+    pub series: Vec<(String, u32)>,
+    pub written: usize,
+    pub stored: usize,
+    pub store_failures: Vec<String>,
+    // Performed procedure step instance UID and its final status
+    pub mpps: Option<(String, StepStatus)>,
+    pub elapsed: Duration,
+    pub error: Option<String>,
+}
+
+impl SimulateReport {
+    pub fn images(&self) -> u32 {
+        self.series.iter().map(|(_, images)| images).sum()
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+            && self.store_failures.is_empty()
+            && self
+                .mpps
+                .as_ref()
+                .is_none_or(|(_, status)| *status == StepStatus::Completed)
+    }
+}
+
+impl fmt::Display for SimulateReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Patient {} ({}), accession {}",
+            self.patient_name, self.patient_id, self.accession_number
+        )?;
+        writeln!(f, "Study {}", self.study_instance_uid)?;
+        if let Some(step) = &self.scheduled_step {
+            writeln!(f, "Scheduled step {} from the worklist", step)?;
+        }
+        for (series, images) in &self.series {
+            writeln!(
+                f,
+                "  {} series {}: {} images",
+                self.modality, series, images
+            )?;
+        }
+        if self.written > 0 {
+            writeln!(f, "Written: {} files", self.written)?;
+        }
+        if self.stored > 0 || !self.store_failures.is_empty() {
+            writeln!(
+                f,
+                "Stored: {} of {}",
+                self.stored,
+                self.stored + self.store_failures.len()
+            )?;
+        }
+        for failure in &self.store_failures {
+            writeln!(f, "  {}", failure)?;
+        }
+        if let Some((sop_instance_uid, status)) = &self.mpps {
+            writeln!(f, "MPPS {}: {}", sop_instance_uid, status)?;
+        }
+        writeln!(f, "Elapsed: {:.1} s", self.elapsed.as_secs_f64())?;
+        if let Some(error) = &self.error {
+            writeln!(f, "Error: {}", error)?;
+        }
+        write!(f, "Result: {}", if self.is_ok() { "OK" } else { "FAILED" })
+    }
+}
+
+impl Report for SimulateReport {
+    fn to_json(&self) -> serde_json::Value {
+        let series: Vec<serde_json::Value> = self
+            .series
+            .iter()
+            .map(|(series_instance_uid, images)| {
+                serde_json::json!({
+                    "series_instance_uid": series_instance_uid,
+                    "images": images,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "modality": self.modality,
+            "patient_id": self.patient_id,
+            "patient_name": self.patient_name,
+            "accession_number": self.accession_number,
+            "study_instance_uid": self.study_instance_uid,
+            "scheduled_step": self.scheduled_step,
+            "series": series,
+            "images": self.images(),
+            "written": self.written,
+            "stored": self.stored,
+            "store_failures": self.store_failures,
+            "mpps": self.mpps.as_ref().map(|(sop_instance_uid, status)| serde_json::json!({
+                "sop_instance_uid": sop_instance_uid,
+                "status": status.as_str(),
+            })),
+            "elapsed_ms": self.elapsed.as_secs_f64() * 1000.0,
+            "error": self.error,
+            "ok": self.is_ok(),
+        })
+    }
+}
+
+pub fn template(modality: &str) -> DicomResult<Template> {
+    match modality.to_ascii_uppercase().as_str() {
+        "CT" => Ok(template::ct_image()),
+        "MR" => Ok(template::mr_image()),
+        "US" => Ok(template::us_image()),
+        "OT" | "SC" => Ok(template::secondary_capture()),
+        other => Err(DicomError::InvalidValue(format!(
+            "No template for modality {}, expected CT, MR, US or OT",
+            other
+        ))),
+    }
+}
+
+fn associate(
+    registry: &AeRegistry,
+    peer: &str,
+    calling_ae: &str,
+    sop_classes: &[&str],
+) -> DicomResult<Association> {
+    let peer = registry.resolve(peer)?;
+    let options = peer.association_options(calling_ae, sop_classes);
+    if options.presentation_contexts.is_empty() {
+        return Err(DicomError::InvalidValue(format!(
+            "Peer {} allows none of {}",
+            peer.name,
+            sop_classes.join(", ")
+        )));
+    }
+    Association::request((peer.host.as_str(), peer.port), options)
+}
+
+// First scheduled step of the modality, preferring those scheduled for the
+// calling AE title
+pub fn query_worklist(
+    registry: &AeRegistry,
+    peer: &str,
+    calling_ae: &str,
+    modality: &str,
+) -> DicomResult<Option<WorklistItem>> {
+    let query = WorklistItem {
+        modality: modality.to_string(),
+        ..WorklistItem::default()
+    }
+    .to_dataset();
+
+    let mut association = associate(
+        registry,
+        peer,
+        calling_ae,
+        &[MODALITY_WORKLIST_FIND_SOP_CLASS],
+    )?;
+    let mut items = Vec::new();
+    let status = find::find(
+        &mut association,
+        MODALITY_WORKLIST_FIND_SOP_CLASS,
+        &query,
+        &CancellationToken::new(),
+        |identifier| items.push(WorklistItem::from_dataset(&identifier)),
+    )?;
+    association.release()?;
+    if !status.is_success() {
+        return Err(DicomError::InvalidValue(format!(
+            "Worklist query failed: {}",
+            status
+        )));
+    }
+
+    let position = items
+        .iter()
+        .position(|item| item.scheduled_station_ae_title == calling_ae)
+        .unwrap_or(0);
+    Ok((position < items.len()).then(|| items.swap_remove(position)))
+}
+
+// Instances of one series from the modality's template, sharing the study,
+// series and frame of reference, with the slices stacked 1 mm apart
+pub struct SeriesGenerator<'a> {
+    pub modality: &'a str,
+    pub scheduled: &'a WorklistItem,
+    pub study_id: &'a str,
+    pub study_date: &'a str,
+    pub study_time: &'a str,
+    pub series_number: u32,
+    pub series_instance_uid: String,
+    pub frame_of_reference_uid: String,
+    pub performed_procedure_step: Option<&'a str>,
+}
+
+impl SeriesGenerator<'_> {
+    pub fn instance(&self, instance_number: u32) -> DicomResult<Dataset> {
+        let scheduled = self.scheduled;
+        let mut template = template(self.modality)?
+            .with_patient(&scheduled.patient_id, &scheduled.patient_name)
+            .with_string((0x0010, 0x0040), "CS", &scheduled.patient_sex)
+            .with_study(&scheduled.study_instance_uid)
+            .with_string(STUDY_DATE, "DA", self.study_date)
+            .with_string(STUDY_TIME, "TM", self.study_time)
+            .with_string((0x0008, 0x0050), "SH", &scheduled.accession_number)
+            .with_string((0x0008, 0x0090), "PN", &scheduled.referring_physician_name)
+            .with_string((0x0020, 0x0010), "SH", self.study_id)
+            .with_series(&self.series_instance_uid)
+            .with_string(SERIES_NUMBER, "IS", &self.series_number.to_string())
+            .with_string(INSTANCE_NUMBER, "IS", &instance_number.to_string())
+            .with_string(
+                (0x0008, 0x103E),
+                "LO",
+                &format!("Simulated series {}", self.series_number),
+            );
+
+        // The birth date comes from the worklist, often empty or partial
+        matching::put_value(
+            &mut template.dataset,
+            (0x0010, 0x0030),
+            "DA",
+            &scheduled.patient_birth_date,
+        );
+
+        if template.dataset.contains(FRAME_OF_REFERENCE_UID) {
+            template = template
+                .with_string(FRAME_OF_REFERENCE_UID, "UI", &self.frame_of_reference_uid)
+                .with_string(
+                    IMAGE_POSITION_PATIENT,
+                    "DS",
+                    &format!("0\\0\\{}", instance_number),
+                );
+        }
+        if template.dataset.contains(CONTENT_DATE) {
+            template = template
+                .with_string(CONTENT_DATE, "DA", self.study_date)
+                .with_string(CONTENT_TIME, "TM", self.study_time);
+        }
+
+        if !scheduled.scheduled_procedure_step_id.is_empty() {
+            let mut request = Dataset::new();
+            request.put_string(
+                mpps::REQUESTED_PROCEDURE_ID,
+                "SH",
+                &scheduled.requested_procedure_id,
+            );
+            request.put_string(
+                mpps::SCHEDULED_PROCEDURE_STEP_ID,
+                "SH",
+                &scheduled.scheduled_procedure_step_id,
+            );
+            request.put_string(
+                mpps::SCHEDULED_PROCEDURE_STEP_DESCRIPTION,
+                "LO",
+                &scheduled.scheduled_procedure_step_description,
+            );
+            template = template.with_element(Rc::new(DicomElement::sequence(
+                REQUEST_ATTRIBUTES_SEQUENCE,
+                vec![request],
+            )));
+        }
+        if let Some(step) = self.performed_procedure_step {
+            let mut reference = Dataset::new();
+            reference.put_string(
+                mpps::REFERENCED_SOP_CLASS_UID,
+                "UI",
+                MODALITY_PERFORMED_PROCEDURE_STEP_SOP_CLASS,
+            );
+            reference.put_string(mpps::REFERENCED_SOP_INSTANCE_UID, "UI", step);
+            template = template.with_element(Rc::new(DicomElement::sequence(
+                REFERENCED_PERFORMED_PROCEDURE_STEP_SEQUENCE,
+                vec![reference],
+            )));
+        }
+
+        Ok(template.build())
+    }
+}
+
+// C-STORE over an open association, the status of a refusal as the error
+pub fn store(
+    association: &mut Association,
+    message_ids: &MessageIdGenerator,
+    dataset: &Dataset,
+) -> DicomResult<()> {
+    let sop_class_uid = dataset.string((0x0008, 0x0016)).unwrap_or_default();
+    let sop_instance_uid = dataset.string((0x0008, 0x0018)).unwrap_or_default();
+    let context_id = association
+        .context_for(&sop_class_uid)
+        .map(|context| context.id)
+        .ok_or_else(|| DicomError::InvalidValue(format!("{} was not accepted", sop_class_uid)))?;
+
+    let request = CStoreRq {
+        message_id: message_ids.next_id(),
+        affected_sop_class_uid: sop_class_uid,
+        affected_sop_instance_uid: sop_instance_uid,
+        priority: Priority::default(),
+        move_originator_ae_title: None,
+        move_originator_message_id: None,
+    };
+    let message = DimseMessage::new(&request, Some(dataset.clone()));
+    association.send_message(context_id, &message)?;
+    let (_, response) = association.receive_response(&message.command)?;
+    let status = CStoreRsp::from_command(&response.command)?.status;
+    if status.is_success() || status.is_warning() {
+        Ok(())
+    } else {
+        Err(DicomError::InvalidValue(status.to_string()))
+    }
+}
+
+// Acquisition of one study as a modality does it: the scheduled step from
+// the worklist, MPPS IN PROGRESS, images stored as they are generated and
+// MPPS COMPLETED, or DISCONTINUED when storing failed
+pub fn simulate(
+    args: &SimulateArgs,
+    registry: Option<&AeRegistry>,
+    calling_ae: &str,
+    report: &mut SimulateReport,
+) -> DicomResult<()> {
+    let started = Local::now();
+    let study_date = started.format("%Y%m%d").to_string();
+    let study_time = started.format("%H%M%S").to_string();
+    let modality = args.modality.to_ascii_uppercase();
+    let sop_class_uid = template(&modality)?.sop_class_uid;
+    let registry =
+        || registry.ok_or_else(|| DicomError::InvalidValue("No AE registry loaded".to_string()));
+
+    let mut scheduled = match &args.worklist {
+        Some(peer) => {
+            let item =
+                query_worklist(registry()?, peer, calling_ae, &modality)?.ok_or_else(|| {
+                    DicomError::InvalidValue(format!(
+                        "Worklist of {} has no {} step scheduled",
+                        peer, modality
+                    ))
+                })?;
+            report.scheduled_step = Some(item.scheduled_procedure_step_id.clone());
+            item
+        }
+        None => WorklistItem {
+            patient_id: args.patient_id.clone(),
+            patient_name: args.patient_name.clone(),
+            accession_number: format!("SIM{}", started.format("%y%m%d%H%M%S")),
+            modality: modality.clone(),
+            ..WorklistItem::default()
+        },
+    };
+    if scheduled.study_instance_uid.is_empty() {
+        scheduled.study_instance_uid = uid::generate();
+    }
+    report.patient_id = scheduled.patient_id.clone();
+    report.patient_name = scheduled.patient_name.clone();
+    report.accession_number = scheduled.accession_number.clone();
+    report.study_instance_uid = scheduled.study_instance_uid.clone();
+
+    let message_ids = MessageIdGenerator::new();
+    let mut step = match &args.mpps {
+        Some(peer) => {
+            let mut association = associate(
+                registry()?,
+                peer,
+                calling_ae,
+                &[MODALITY_PERFORMED_PROCEDURE_STEP_SOP_CLASS],
+            )?;
+            let mut step = PerformedProcedureStep::new(&modality, calling_ae, scheduled.clone())
+                .with_id(&format!("PPS{}", started.format("%H%M%S")))
+                .with_study_id("1")
+                .with_start(&study_date, &study_time);
+            step.create(&mut association, &message_ids)?;
+            association.release()?;
+            report.mpps = Some((step.sop_instance_uid.clone(), StepStatus::InProgress));
+            Some(step)
+        }
+        None => None,
+    };
+
+    if let Some(folder) = &args.output {
+        fs::create_dir_all(folder)?;
+    }
+    let mut association = match &args.send {
+        Some(peer) => Some(associate(registry()?, peer, calling_ae, &[sop_class_uid])?),
+        None => None,
+    };
+
+    let step_uid = step.as_ref().map(|step| step.sop_instance_uid.clone());
+    let mut result = Ok(());
+    'series: for series_number in 1..=args.series {
+        let generator = SeriesGenerator {
+            modality: &modality,
+            scheduled: &scheduled,
+            study_id: "1",
+            study_date: &study_date,
+            study_time: &study_time,
+            series_number,
+            series_instance_uid: uid::generate(),
+            frame_of_reference_uid: uid::generate(),
+            performed_procedure_step: step_uid.as_deref(),
+        };
+        report
+            .series
+            .push((generator.series_instance_uid.clone(), 0));
+
+        for instance_number in 1..=args.images {
+            let dataset = match generator.instance(instance_number) {
+                Ok(dataset) => dataset,
+                Err(error) => {
+                    result = Err(error);
+                    break 'series;
+                }
+            };
+            let sop_instance_uid = dataset.string((0x0008, 0x0018)).unwrap_or_default();
+
+            if let Some(folder) = &args.output {
+                let bytes =
+                    writer::write_file(&dataset, transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN)?;
+                fs::write(folder.join(format!("{}.dcm", sop_instance_uid)), bytes)?;
+                report.written += 1;
+            }
+            if let Some(association) = association.as_mut() {
+                match store(association, &message_ids, &dataset) {
+                    Ok(()) => report.stored += 1,
+                    Err(error) => report
+                        .store_failures
+                        .push(format!("{}: {}", sop_instance_uid, error)),
+                }
+            }
+
+            if let Some(step) = step.as_mut() {
+                step.add_image(
+                    &generator.series_instance_uid,
+                    sop_class_uid,
+                    &sop_instance_uid,
+                );
+            }
+            if let Some((_, images)) = report.series.last_mut() {
+                *images += 1;
+            }
+        }
+    }
+    if let Some(association) = association {
+        association.release()?;
+    }
+
+    if let (Some(step), Some(peer)) = (step.as_mut(), &args.mpps) {
+        let ended = Local::now();
+        let status = if result.is_ok() && report.store_failures.is_empty() {
+            StepStatus::Completed
+        } else {
+            StepStatus::Discontinued
+        };
+        let mut association = associate(
+            registry()?,
+            peer,
+            calling_ae,
+            &[MODALITY_PERFORMED_PROCEDURE_STEP_SOP_CLASS],
+        )?;
+        step.finish(
+            &mut association,
+            &message_ids,
+            status,
+            &ended.format("%Y%m%d").to_string(),
+            &ended.format("%H%M%S").to_string(),
+        )?;
+        association.release()?;
+        report.mpps = Some((step.sop_instance_uid.clone(), status));
+    }
+    result
+}
+
+pub fn run(args: SimulateArgs, format: OutputFormat, config: &Config) -> DicomResult<()> {
+    if args.send.is_none() && args.output.is_none() {
+        return Err(DicomError::InvalidValue(
+            "Nothing to do with the images, give --send or --output".to_string(),
+        ));
+    }
+
+    let needs_registry = args.send.is_some() || args.worklist.is_some() || args.mpps.is_some();
+    let registry = if needs_registry {
+        Some(config.registry_for(args.config.as_deref())?)
+    } else {
+        None
+    };
+    let calling_ae = args
+        .calling_ae
+        .clone()
+        .or_else(|| {
+            registry
+                .as_ref()
+                .map(|registry| registry.calling_ae().to_string())
+        })
+        .unwrap_or_else(|| "ANY-SCU".to_string());
+
+    let started = Instant::now();
+    let mut report = SimulateReport {
+        modality: args.modality.to_ascii_uppercase(),
+        ..SimulateReport::default()
+    };
+    if let Err(error) = simulate(&args, registry.as_ref(), &calling_ae, &mut report) {
+        report.error = Some(error.to_string());
+    }
+    report.elapsed = started.elapsed();
+    output::print(&report, format)?;
+
+    if report.is_ok() {
+        Ok(())
+    } else {
+        Err(DicomError::Network(
+            "Simulated acquisition did not complete".to_string(),
+        ))
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+
+use super::{
+    batch::{self, BatchOptions},
+    output::{self, OutputFormat, Report},
+};
+use crate::core::{
+    error::DicomResult,
+    padding::ValuePolicy,
+    reader::{self, ParserLimits},
+};
+
+#[derive(Debug, Clone, Args)]
+pub struct StatsArgs {
+    #[arg(help = "File or folder, searched recursively")]
+    pub path: PathBuf,
+    #[arg(
+        long,
+        default_value_t = 10,
+        help = "Number of largest elements to list"
+    )]
+    pub top: usize,
+    #[arg(short, long, help = "Files read in parallel, every core by default")]
+    pub jobs: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StatsReport {
+    pub files: usize,
+    // Files that are not DICOM or failed to parse
+    pub skipped: usize,
+    pub file_size: u64,
+    pub memory_usage: usize,
+    pub elements: usize,
+    pub by_group: BTreeMap<u16, usize>,
+    pub by_vr: BTreeMap<&'static str, usize>,
+    // Largest top level elements over all files, largest first
+    pub largest: Vec<(PathBuf, (u16, u16), usize)>,
+    pub sequence_depth: usize,
+}
+
+impl StatsReport {
+    pub fn add(
+        &mut self,
+        path: &Path,
+        data: &[u8],
+        top: usize,
+        limits: &ParserLimits,
+    ) -> DicomResult<()> {
+        let (file, _) = reader::read_file_with_limits(data, &ValuePolicy::default(), limits)?;
+        let statistics = file.dataset.statistics();
+
+        self.files += 1;
+        self.file_size += data.len() as u64;
+        self.memory_usage += file.meta.memory_usage() + statistics.memory_usage;
+        self.elements += statistics.elements;
+        for (group, count) in statistics.by_group {
+            *self.by_group.entry(group).or_default() += count;
+        }
+        for (vr, count) in statistics.by_vr {
+            *self.by_vr.entry(vr).or_default() += count;
+        }
+        self.sequence_depth = self.sequence_depth.max(statistics.sequence_depth);
+
+        self.largest.extend(
+            statistics
+                .largest
+                .into_iter()
+                .map(|(tag, size)| (path.to_path_buf(), tag, size)),
+        );
+        self.largest.sort_by_key(|(_, _, size)| Reverse(*size));
+        self.largest.truncate(top);
+        Ok(())
+    }
+
+    // Adds the report of further files
+    pub fn merge(&mut self, other: StatsReport, top: usize) {
+        self.files += other.files;
+        self.skipped += other.skipped;
+        self.file_size += other.file_size;
+        self.memory_usage += other.memory_usage;
+        self.elements += other.elements;
+        for (group, count) in other.by_group {
+            *self.by_group.entry(group).or_default() += count;
+        }
+        for (vr, count) in other.by_vr {
+            *self.by_vr.entry(vr).or_default() += count;
+        }
+        self.sequence_depth = self.sequence_depth.max(other.sequence_depth);
+
+        self.largest.extend(other.largest);
+        self.largest.sort_by_key(|(_, _, size)| Reverse(*size));
+        self.largest.truncate(top);
+    }
+}
+
+impl fmt::Display for StatsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Files:          {} ({} skipped)",
+            self.files, self.skipped
+        )?;
+        writeln!(f, "Size on disk:   {} bytes", self.file_size)?;
+        writeln!(f, "Memory usage:   {} bytes", self.memory_usage)?;
+        writeln!(f, "Elements:       {}", self.elements)?;
+        writeln!(f, "Sequence depth: {}", self.sequence_depth)?;
+
+        writeln!(f, "\nElements by group:")?;
+        for (group, count) in &self.by_group {
+            writeln!(f, "  {:04X}  {}", group, count)?;
+        }
+        writeln!(f, "\nElements by VR:")?;
+        for (vr, count) in &self.by_vr {
+            writeln!(f, "  {}    {}", vr, count)?;
+        }
+        writeln!(f, "\nLargest elements:")?;
+        for (path, tag, size) in &self.largest {
+            writeln!(
+                f,
+                "  ({:04X},{:04X})  {:>12} bytes  {}",
+                tag.0,
+                tag.1,
+                size,
+                path.display()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Report for StatsReport {
+    #[cfg(any(feature = "serde", feature = "default"))]
+    fn to_json(&self) -> serde_json::Value {
+        let by_group: BTreeMap<String, usize> = self
+            .by_group
+            .iter()
+            .map(|(group, count)| (format!("{:04X}", group), *count))
+            .collect();
+        let largest: Vec<serde_json::Value> = self
+            .largest
+            .iter()
+            .map(|(path, tag, size)| {
+                serde_json::json!({
+                    "path": path.display().to_string(),
+                    "tag": format!("{:04X}{:04X}", tag.0, tag.1),
+                    "size": size,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "files": self.files,
+            "skipped": self.skipped,
+            "file_size": self.file_size,
+            "memory_usage": self.memory_usage,
+            "elements": self.elements,
+            "sequence_depth": self.sequence_depth,
+            "by_group": by_group,
+            "by_vr": self.by_vr,
+            "largest": largest,
+        })
+    }
+}
+
+pub fn stats(path: &Path, top: usize) -> DicomResult<StatsReport> {
+    stats_with(
+        path,
+        top,
+        &BatchOptions::default(),
+        &ParserLimits::default(),
+    )
+}
+
+// Files are read over the worker threads and merged in name order, so the
+// report does not depend on the number of jobs
+pub fn stats_with(
+    path: &Path,
+    top: usize,
+    options: &BatchOptions,
+    limits: &ParserLimits,
+) -> DicomResult<StatsReport> {
+    let files = batch::files(path)?;
+    let results = batch::run(&files, options, |path| {
+        let data = fs::read(path)?;
+        let mut report = StatsReport::default();
+        Ok(report
+            .add(path, &data, top, limits)
+            .is_ok()
+            .then_some(report))
+    });
+
+    let mut report = StatsReport::default();
+    for result in results {
+        match result? {
+            Some(file) => report.merge(file, top),
+            None => report.skipped += 1,
+        }
+    }
+    Ok(report)
+}
+
+pub fn run(args: StatsArgs, format: OutputFormat, limits: &ParserLimits) -> DicomResult<()> {
+    let options = BatchOptions::new(args.jobs).with_progress(format == OutputFormat::Text);
+    let report = stats_with(&args.path, args.top, &options, limits)?;
+    output::print(&report, format)
+}
@@ -0,0 +1,204 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::core::{
+    error::{DicomError, DicomResult},
+    reader::ParserLimits,
+};
+use crate::net::config::{AeRegistry, PeerConfig, TlsSettings};
+
+// File read when DICOM_CONFIG does not name one, from the working directory
+// and then the user configuration folder
+pub const CONFIG_FILE: &str = "dicom.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct LimitsFile {
+    max_file_size: Option<usize>,
+    max_value_length: Option<usize>,
+    max_sequence_depth: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct ConfigFile {
+    ae_title: Option<String>,
+    log_level: Option<String>,
+    transfer_syntaxes: Vec<String>,
+    tls: TlsSettings,
+    limits: LimitsFile,
+    peers: BTreeMap<String, PeerConfig>,
+}
+
+// Settings shared by the tools and library users, loaded from TOML such as
+//
+// ae_title = "MY-SCU"
+// log_level = "info"
+// transfer_syntaxes = ["1.2.840.10008.1.2.1"]
+//
+// [tls]
+// cert_file = "scu.pem"
+// key_file = "scu.key"
+//
+// [limits]
+// max_value_length = 268435456
+//
+// [peers.pacs]
+// ae_title = "PACS"
+// host = "pacs.example.org"
+//
+// and overridden by DICOM_AE_TITLE, DICOM_LOG_LEVEL, DICOM_TRANSFER_SYNTAXES
+// (comma separated), DICOM_TLS_CA_FILE, DICOM_TLS_CERT_FILE,
+// DICOM_TLS_KEY_FILE, DICOM_MAX_FILE_SIZE, DICOM_MAX_VALUE_LENGTH and
+// DICOM_MAX_SEQUENCE_DEPTH
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    // File the settings came from, None for defaults and environment only
+    pub path: Option<PathBuf>,
+    pub ae_title: Option<String>,
+    pub log_level: Option<String>,
+    // Proposed to peers that do not list their own, in order of preference
+    pub transfer_syntaxes: Vec<String>,
+    // Files of the local AE, used by peers whose TLS settings lack them
+    pub tls: TlsSettings,
+    pub limits: ParserLimits,
+    pub peers: BTreeMap<String, PeerConfig>,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Config::default()
+    }
+
+    // The file DICOM_CONFIG names or the first of the default ones, with the
+    // environment applied. No file at all gives the defaults
+    pub fn load() -> DicomResult<Self> {
+        let path = match env::var_os("DICOM_CONFIG") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => default_paths().into_iter().find(|path| path.is_file()),
+        };
+        let config = match path {
+            Some(path) => Config::load_file(&path)?,
+            None => Config::default(),
+        };
+        config.with_env(env::vars())
+    }
+
+    pub fn load_file(path: &Path) -> DicomResult<Self> {
+        let mut config = Config::from_toml_str(&fs::read_to_string(path)?)?;
+        config.path = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    pub fn from_toml_str(text: &str) -> DicomResult<Self> {
+        let file: ConfigFile =
+            toml::from_str(text).map_err(|error| DicomError::InvalidFile(error.to_string()))?;
+
+        let peers = file
+            .peers
+            .into_iter()
+            .map(|(name, mut peer)| {
+                peer.name = name.clone();
+                (name, peer)
+            })
+            .collect();
+        Ok(Config {
+            path: None,
+            ae_title: file.ae_title,
+            log_level: file.log_level,
+            transfer_syntaxes: file.transfer_syntaxes,
+            tls: file.tls,
+            limits: ParserLimits {
+                max_file_size: file.limits.max_file_size,
+                max_value_length: file.limits.max_value_length,
+                max_sequence_depth: file
+                    .limits
+                    .max_sequence_depth
+                    .or(ParserLimits::default().max_sequence_depth),
+            },
+            peers,
+        })
+    }
+
+    // Applies DICOM_* variables, given as from std::env::vars
+    pub fn with_env<I: IntoIterator<Item = (String, String)>>(
+        mut self,
+        variables: I,
+    ) -> DicomResult<Self> {
+        let number = |name: &str, value: &str| {
+            value.trim().parse::<usize>().map(Some).map_err(|_| {
+                DicomError::InvalidValue(format!("{} is not a number: {}", name, value))
+            })
+        };
+
+        for (name, value) in variables {
+            match name.as_str() {
+                "DICOM_AE_TITLE" => self.ae_title = Some(value),
+                "DICOM_LOG_LEVEL" => self.log_level = Some(value),
+                "DICOM_TRANSFER_SYNTAXES" => {
+                    self.transfer_syntaxes = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|uid| !uid.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                }
+                "DICOM_TLS_CA_FILE" => self.tls.ca_file = Some(value),
+                "DICOM_TLS_CERT_FILE" => self.tls.cert_file = Some(value),
+                "DICOM_TLS_KEY_FILE" => self.tls.key_file = Some(value),
+                "DICOM_MAX_FILE_SIZE" => self.limits.max_file_size = number(&name, &value)?,
+                "DICOM_MAX_VALUE_LENGTH" => self.limits.max_value_length = number(&name, &value)?,
+                "DICOM_MAX_SEQUENCE_DEPTH" => {
+                    self.limits.max_sequence_depth = number(&name, &value)?
+                }
+                _ => {}
+            }
+        }
+        Ok(self)
+    }
+
+    // Peers with the transfer syntaxes and TLS files they do not set
+    // themselves taken from the global settings
+    pub fn registry(&self) -> DicomResult<AeRegistry> {
+        let mut registry = AeRegistry::new();
+        registry.local_ae = self.ae_title.clone();
+
+        for peer in self.peers.values() {
+            let mut peer = peer.clone();
+            if peer.transfer_syntaxes.is_empty() {
+                peer.transfer_syntaxes = self.transfer_syntaxes.clone();
+            }
+            if let Some(tls) = &mut peer.tls {
+                tls.ca_file = tls.ca_file.take().or_else(|| self.tls.ca_file.clone());
+                tls.cert_file = tls.cert_file.take().or_else(|| self.tls.cert_file.clone());
+                tls.key_file = tls.key_file.take().or_else(|| self.tls.key_file.clone());
+            }
+            registry.add(peer)?;
+        }
+        Ok(registry)
+    }
+
+    // Registry file of a --config argument, the global settings otherwise
+    pub fn registry_for(&self, path: Option<&Path>) -> DicomResult<AeRegistry> {
+        match path {
+            Some(path) => AeRegistry::load(path),
+            None => self.registry(),
+        }
+    }
+}
+
+fn default_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(CONFIG_FILE)];
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+    if let Some(config_home) = config_home {
+        paths.push(config_home.join("dicom").join("config.toml"));
+    }
+    paths
+}
@@ -0,0 +1,5 @@
+#[cfg(any(
+    all(feature = "net", feature = "serde", feature = "toml"),
+    feature = "default"
+))]
+pub mod config;
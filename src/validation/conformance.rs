@@ -0,0 +1,476 @@
+use std::fmt::Write;
+
+use crate::{
+    core::{iod, transfer_syntax},
+    net::{
+        association::AssociationOptions,
+        dimse::VERIFICATION_SOP_CLASS,
+        ian::{
+            INSTANCE_AVAILABILITY_NOTIFICATION_SOP_CLASS,
+            MODALITY_PERFORMED_PROCEDURE_STEP_SOP_CLASS,
+        },
+        pdu,
+        print::BASIC_GRAYSCALE_PRINT_MANAGEMENT_META_SOP_CLASS,
+        ups::{UPS_EVENT_SOP_CLASS, UPS_PULL_SOP_CLASS, UPS_PUSH_SOP_CLASS, UPS_WATCH_SOP_CLASS},
+        worklist::MODALITY_WORKLIST_FIND_SOP_CLASS,
+    },
+    plugins::codec::CodecRegistry,
+};
+
+pub const STORAGE_COMMITMENT_PUSH_MODEL_SOP_CLASS: &str = "1.2.840.10008.1.20.1";
+
+// Names of the SOP classes commonly negotiated, others are listed by UID
+pub const SOP_CLASS_NAMES: &[(&str, &str)] = &[
+    (VERIFICATION_SOP_CLASS, "Verification"),
+    (
+        STORAGE_COMMITMENT_PUSH_MODEL_SOP_CLASS,
+        "Storage Commitment Push Model",
+    ),
+    (
+        MODALITY_PERFORMED_PROCEDURE_STEP_SOP_CLASS,
+        "Modality Performed Procedure Step",
+    ),
+    (
+        BASIC_GRAYSCALE_PRINT_MANAGEMENT_META_SOP_CLASS,
+        "Basic Grayscale Print Management Meta",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.1.1",
+        "Computed Radiography Image Storage",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.1.1.1",
+        "Digital X-Ray Image Storage - For Presentation",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.1.1.2",
+        "Digital Mammography X-Ray Image Storage - For Presentation",
+    ),
+    ("1.2.840.10008.5.1.4.1.1.2", "CT Image Storage"),
+    ("1.2.840.10008.5.1.4.1.1.2.1", "Enhanced CT Image Storage"),
+    (
+        "1.2.840.10008.5.1.4.1.1.2.2",
+        "Legacy Converted Enhanced CT Image Storage",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.1.3.1",
+        "Ultrasound Multi-frame Image Storage",
+    ),
+    ("1.2.840.10008.5.1.4.1.1.4", "MR Image Storage"),
+    ("1.2.840.10008.5.1.4.1.1.4.1", "Enhanced MR Image Storage"),
+    (
+        "1.2.840.10008.5.1.4.1.1.4.4",
+        "Legacy Converted Enhanced MR Image Storage",
+    ),
+    ("1.2.840.10008.5.1.4.1.1.6.1", "Ultrasound Image Storage"),
+    (
+        "1.2.840.10008.5.1.4.1.1.7",
+        "Secondary Capture Image Storage",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.1.11.1",
+        "Grayscale Softcopy Presentation State Storage",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.1.12.1",
+        "X-Ray Angiographic Image Storage",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.1.20",
+        "Nuclear Medicine Image Storage",
+    ),
+    ("1.2.840.10008.5.1.4.1.1.88.11", "Basic Text SR Storage"),
+    ("1.2.840.10008.5.1.4.1.1.88.22", "Enhanced SR Storage"),
+    ("1.2.840.10008.5.1.4.1.1.88.33", "Comprehensive SR Storage"),
+    (
+        "1.2.840.10008.5.1.4.1.1.88.59",
+        "Key Object Selection Document Storage",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.1.88.67",
+        "X-Ray Radiation Dose SR Storage",
+    ),
+    ("1.2.840.10008.5.1.4.1.1.104.1", "Encapsulated PDF Storage"),
+    (
+        "1.2.840.10008.5.1.4.1.1.128",
+        "Positron Emission Tomography Image Storage",
+    ),
+    ("1.2.840.10008.5.1.4.1.1.481.1", "RT Image Storage"),
+    ("1.2.840.10008.5.1.4.1.1.481.2", "RT Dose Storage"),
+    ("1.2.840.10008.5.1.4.1.1.481.3", "RT Structure Set Storage"),
+    ("1.2.840.10008.5.1.4.1.1.481.5", "RT Plan Storage"),
+    (
+        "1.2.840.10008.5.1.4.1.2.1.1",
+        "Patient Root Query/Retrieve Information Model - FIND",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.2.1.2",
+        "Patient Root Query/Retrieve Information Model - MOVE",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.2.1.3",
+        "Patient Root Query/Retrieve Information Model - GET",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.2.2.1",
+        "Study Root Query/Retrieve Information Model - FIND",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.2.2.2",
+        "Study Root Query/Retrieve Information Model - MOVE",
+    ),
+    (
+        "1.2.840.10008.5.1.4.1.2.2.3",
+        "Study Root Query/Retrieve Information Model - GET",
+    ),
+    (
+        MODALITY_WORKLIST_FIND_SOP_CLASS,
+        "Modality Worklist Information Model - FIND",
+    ),
+    (
+        INSTANCE_AVAILABILITY_NOTIFICATION_SOP_CLASS,
+        "Instance Availability Notification",
+    ),
+    (UPS_PUSH_SOP_CLASS, "Unified Procedure Step - Push"),
+    (UPS_WATCH_SOP_CLASS, "Unified Procedure Step - Watch"),
+    (UPS_PULL_SOP_CLASS, "Unified Procedure Step - Pull"),
+    (UPS_EVENT_SOP_CLASS, "Unified Procedure Step - Event"),
+];
+
+// Taken from the generated IOD tables first, which cover every SOP class of
+// the standard when they could be fetched at build time
+pub fn sop_class_name(uid: &str) -> Option<&'static str> {
+    iod::sop_class(uid)
+        .map(|sop_class| sop_class.name)
+        .or_else(|| {
+            SOP_CLASS_NAMES
+                .iter()
+                .find(|(known, _)| *known == uid)
+                .map(|(_, name)| *name)
+        })
+}
+
+// DIMSE services a SOP class is used with, guessed from its UID branch
+pub fn services(uid: &str) -> &'static str {
+    match uid {
+        VERIFICATION_SOP_CLASS => "C-ECHO",
+        STORAGE_COMMITMENT_PUSH_MODEL_SOP_CLASS => "N-ACTION, N-EVENT-REPORT",
+        MODALITY_PERFORMED_PROCEDURE_STEP_SOP_CLASS => "N-CREATE, N-SET",
+        INSTANCE_AVAILABILITY_NOTIFICATION_SOP_CLASS => "N-CREATE",
+        UPS_EVENT_SOP_CLASS => "N-EVENT-REPORT",
+        MODALITY_WORKLIST_FIND_SOP_CLASS => "C-FIND",
+        uid if uid.starts_with("1.2.840.10008.5.1.4.34.6.") => "N-CREATE, N-SET, N-GET, N-ACTION",
+        uid if uid.starts_with("1.2.840.10008.5.1.1.") => {
+            "N-CREATE, N-SET, N-GET, N-ACTION, N-DELETE"
+        }
+        uid if uid.starts_with("1.2.840.10008.5.1.4.1.2.") => match uid.rsplit('.').next() {
+            Some("1") => "C-FIND",
+            Some("2") => "C-MOVE",
+            Some("3") => "C-GET",
+            _ => "C-FIND, C-MOVE, C-GET",
+        },
+        uid if uid.starts_with("1.2.840.10008.5.1.4.1.1.") => "C-STORE",
+        _ => "",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SopClassSupport {
+    pub uid: String,
+    pub transfer_syntaxes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodecSupport {
+    pub name: String,
+    pub transfer_syntaxes: Vec<String>,
+    pub decode: bool,
+    pub encode: bool,
+}
+
+// Skeleton of a PS3.2 conformance statement, filled from what the
+// application configures. The parts only a human can state, such as the
+// real world activities, are left for the vendor to write
+#[derive(Debug, Clone)]
+pub struct ConformanceStatement {
+    pub product: String,
+    pub version: String,
+    pub manufacturer: String,
+    pub ae_title: String,
+    pub max_pdu_length: u32,
+    pub scu: Vec<SopClassSupport>,
+    pub scp: Vec<SopClassSupport>,
+    pub codecs: Vec<CodecSupport>,
+}
+
+impl ConformanceStatement {
+    pub fn new(product: &str, version: &str) -> Self {
+        ConformanceStatement {
+            product: product.to_string(),
+            version: version.to_string(),
+            manufacturer: String::new(),
+            ae_title: String::new(),
+            max_pdu_length: pdu::DEFAULT_MAX_PDU_LENGTH,
+            scu: Vec::new(),
+            scp: Vec::new(),
+            codecs: Vec::new(),
+        }
+    }
+
+    pub fn with_manufacturer(mut self, manufacturer: &str) -> Self {
+        self.manufacturer = manufacturer.to_string();
+        self
+    }
+
+    // Presentation contexts an SCU proposes, the calling AE title taken as
+    // the application's own when none was set
+    pub fn with_scu(mut self, options: &AssociationOptions) -> Self {
+        if self.ae_title.is_empty() {
+            self.ae_title = options.calling_ae.clone();
+        }
+        self.max_pdu_length = options.max_pdu_length;
+        for context in &options.presentation_contexts {
+            add(
+                &mut self.scu,
+                &context.abstract_syntax,
+                &context.transfer_syntaxes,
+            );
+        }
+        // SCP roles taken by the requestor, as for C-GET storage
+        for selection in options.role_selections.iter().filter(|s| s.scp_role) {
+            let syntaxes = options
+                .presentation_contexts
+                .iter()
+                .filter(|context| context.abstract_syntax == selection.sop_class_uid)
+                .flat_map(|context| context.transfer_syntaxes.clone())
+                .collect::<Vec<_>>();
+            add(&mut self.scp, &selection.sop_class_uid, &syntaxes);
+        }
+        self
+    }
+
+    // What an SCP accepts, in the form given to association::negotiate
+    pub fn with_scp(mut self, ae_title: &str, supported: &[(&str, &[&str])]) -> Self {
+        self.ae_title = ae_title.to_string();
+        for (abstract_syntax, syntaxes) in supported {
+            let syntaxes: Vec<String> = syntaxes.iter().map(|ts| ts.to_string()).collect();
+            add(&mut self.scp, abstract_syntax, &syntaxes);
+        }
+        self
+    }
+
+    pub fn with_codecs(mut self, registry: &CodecRegistry) -> Self {
+        self.codecs = registry
+            .codecs()
+            .iter()
+            .map(|codec| CodecSupport {
+                name: codec.name().to_string(),
+                transfer_syntaxes: codec.transfer_syntaxes(),
+                decode: codec.can_decode(),
+                encode: codec.can_encode(),
+            })
+            .collect();
+        self
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "# DICOM Conformance Statement\n");
+        let _ = writeln!(text, "| Product | Version | Manufacturer |");
+        let _ = writeln!(text, "|---|---|---|");
+        let _ = writeln!(
+            text,
+            "| {} | {} | {} |\n",
+            self.product, self.version, self.manufacturer
+        );
+
+        for section in self.sections() {
+            let _ = writeln!(text, "## {}\n", section.title);
+            if let Some(note) = section.note {
+                let _ = writeln!(text, "{}\n", note);
+            }
+            let _ = writeln!(text, "| {} |", section.header.join(" | "));
+            let _ = writeln!(text, "|{}", "---|".repeat(section.header.len()));
+            for row in &section.rows {
+                let _ = writeln!(text, "| {} |", row.join(" | "));
+            }
+            let _ = writeln!(text);
+        }
+        text
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let _ = writeln!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{} DICOM Conformance Statement</title></head>\n<body>",
+            escape(&self.product)
+        );
+        let _ = writeln!(html, "<h1>DICOM Conformance Statement</h1>");
+        let _ = writeln!(
+            html,
+            "<table>\n<tr><th>Product</th><th>Version</th><th>Manufacturer</th></tr>\n<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n</table>",
+            escape(&self.product),
+            escape(&self.version),
+            escape(&self.manufacturer)
+        );
+
+        for section in self.sections() {
+            let _ = writeln!(html, "<h2>{}</h2>", escape(&section.title));
+            if let Some(note) = section.note {
+                let _ = writeln!(html, "<p>{}</p>", escape(&note));
+            }
+            let _ = write!(html, "<table>\n<tr>");
+            for column in &section.header {
+                let _ = write!(html, "<th>{}</th>", escape(column));
+            }
+            let _ = writeln!(html, "</tr>");
+            for row in &section.rows {
+                let _ = write!(html, "<tr>");
+                for cell in row {
+                    let _ = write!(html, "<td>{}</td>", escape(cell).replace('\n', "<br>"));
+                }
+                let _ = writeln!(html, "</tr>");
+            }
+            let _ = writeln!(html, "</table>");
+        }
+        let _ = writeln!(html, "</body>\n</html>");
+        html
+    }
+
+    // Tables shared by both renderings, multiple values of a cell split by
+    // line breaks
+    fn sections(&self) -> Vec<Section> {
+        let mut sections = vec![Section {
+            title: "Application Entity".to_string(),
+            note: None,
+            header: vec!["AE Title", "Maximum PDU Length"],
+            rows: vec![vec![self.ae_title.clone(), self.max_pdu_length.to_string()]],
+        }];
+
+        for (role, classes) in [("SCU", &self.scu), ("SCP", &self.scp)] {
+            if classes.is_empty() {
+                continue;
+            }
+            sections.push(Section {
+                title: format!("SOP Classes as {}", role),
+                note: Some(format!(
+                    "Presentation contexts the application negotiates as an {}.",
+                    role
+                )),
+                header: vec!["SOP Class", "UID", "Services", "Transfer Syntaxes"],
+                rows: classes
+                    .iter()
+                    .map(|class| {
+                        vec![
+                            sop_class_name(&class.uid).unwrap_or("Unknown").to_string(),
+                            class.uid.clone(),
+                            services(&class.uid).to_string(),
+                            class
+                                .transfer_syntaxes
+                                .iter()
+                                .map(|ts| syntax_name(ts))
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                        ]
+                    })
+                    .collect(),
+            });
+        }
+
+        if !self.codecs.is_empty() {
+            sections.push(Section {
+                title: "Pixel Data Codecs".to_string(),
+                note: Some(
+                    "Compressed transfer syntaxes the application can convert pixel data from or to."
+                        .to_string(),
+                ),
+                header: vec!["Codec", "Transfer Syntaxes", "Decode", "Encode"],
+                rows: self
+                    .codecs
+                    .iter()
+                    .map(|codec| {
+                        vec![
+                            codec.name.clone(),
+                            codec
+                                .transfer_syntaxes
+                                .iter()
+                                .map(|ts| syntax_name(ts))
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                            yes_no(codec.decode),
+                            yes_no(codec.encode),
+                        ]
+                    })
+                    .collect(),
+            });
+        }
+        sections
+    }
+}
+
+// AE title of an SCP with what it accepts, as given to with_scp
+pub type ScpSupport<'a> = (&'a str, &'a [(&'a str, &'a [&'a str])]);
+
+// Conformance statement of an application with the given SCU associations,
+// SCP abstract syntaxes and codecs
+pub fn conformance_statement(
+    product: &str,
+    version: &str,
+    scu: &[AssociationOptions],
+    scp: Option<ScpSupport>,
+    codecs: &CodecRegistry,
+) -> ConformanceStatement {
+    let mut statement = ConformanceStatement::new(product, version);
+    if let Some((ae_title, supported)) = scp {
+        statement = statement.with_scp(ae_title, supported);
+    }
+    for options in scu {
+        statement = statement.with_scu(options);
+    }
+    statement.with_codecs(codecs)
+}
+
+struct Section {
+    title: String,
+    note: Option<String>,
+    header: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+// Merges the transfer syntaxes of SOP classes listed more than once
+fn add(classes: &mut Vec<SopClassSupport>, uid: &str, syntaxes: &[String]) {
+    let index = match classes.iter().position(|class| class.uid == uid) {
+        Some(index) => index,
+        None => {
+            classes.push(SopClassSupport {
+                uid: uid.to_string(),
+                transfer_syntaxes: Vec::new(),
+            });
+            classes.len() - 1
+        }
+    };
+    for syntax in syntaxes {
+        if !classes[index].transfer_syntaxes.contains(syntax) {
+            classes[index].transfer_syntaxes.push(syntax.clone());
+        }
+    }
+}
+
+fn syntax_name(uid: &str) -> String {
+    match transfer_syntax::lookup(uid) {
+        Some(syntax) => format!("{} ({})", syntax.name, uid),
+        None => uid.to_string(),
+    }
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "Yes" } else { "No" }.to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
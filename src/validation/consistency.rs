@@ -0,0 +1,209 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use crate::core::{dataset::Dataset, dictionary};
+
+pub const SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x0018);
+pub const PATIENT_ID: (u16, u16) = (0x0010, 0x0020);
+pub const STUDY_INSTANCE_UID: (u16, u16) = (0x0020, 0x000D);
+pub const SERIES_INSTANCE_UID: (u16, u16) = (0x0020, 0x000E);
+pub const FRAME_OF_REFERENCE_UID: (u16, u16) = (0x0020, 0x0052);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Study,
+    Series,
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Level::Study => write!(f, "study"),
+            Level::Series => write!(f, "series"),
+        }
+    }
+}
+
+// An attribute with several values within one study or series. Absent
+// attributes count as the empty value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inconsistency {
+    pub level: Level,
+    // Study or Series Instance UID of the group
+    pub uid: String,
+    pub tag: (u16, u16),
+    // SOP Instance UIDs holding each value
+    pub values: BTreeMap<String, Vec<String>>,
+    // Value held by most instances, the first seen on a tie
+    pub expected: String,
+}
+
+impl Inconsistency {
+    // Instances whose value differs from the expected one
+    pub fn divergent(&self) -> Vec<&str> {
+        self.values
+            .iter()
+            .filter(|(value, _)| **value != self.expected)
+            .flat_map(|(_, instances)| instances.iter().map(String::as_str))
+            .collect()
+    }
+}
+
+impl Display for Inconsistency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({:04X},{:04X}) of {} {} has {} values, expected \"{}\" but {} instance(s) differ",
+            dictionary::keyword(self.tag).unwrap_or("Unknown"),
+            self.tag.0,
+            self.tag.1,
+            self.level,
+            self.uid,
+            self.values.len(),
+            self.expected,
+            self.divergent().len()
+        )
+    }
+}
+
+// Attributes that have to agree across the instances of a study or series
+#[derive(Debug, Clone)]
+pub struct ConsistencyCheck {
+    pub study_attributes: Vec<(u16, u16)>,
+    pub series_attributes: Vec<(u16, u16)>,
+}
+
+impl Default for ConsistencyCheck {
+    fn default() -> Self {
+        ConsistencyCheck {
+            study_attributes: vec![PATIENT_ID],
+            series_attributes: vec![STUDY_INSTANCE_UID, FRAME_OF_REFERENCE_UID],
+        }
+    }
+}
+
+impl ConsistencyCheck {
+    pub fn new() -> Self {
+        ConsistencyCheck::default()
+    }
+
+    pub fn with_study_attribute(mut self, tag: (u16, u16)) -> Self {
+        if !self.study_attributes.contains(&tag) {
+            self.study_attributes.push(tag);
+        }
+        self
+    }
+
+    pub fn with_series_attribute(mut self, tag: (u16, u16)) -> Self {
+        if !self.series_attributes.contains(&tag) {
+            self.series_attributes.push(tag);
+        }
+        self
+    }
+
+    pub fn check(&self, datasets: &[Dataset]) -> Vec<Inconsistency> {
+        let mut found = inconsistencies(datasets, Level::Series, &self.series_attributes);
+        found.extend(inconsistencies(
+            datasets,
+            Level::Study,
+            &self.study_attributes,
+        ));
+        found
+    }
+
+    // Sets divergent values to the expected one, series first so that
+    // repaired Study Instance UIDs group the study level check. Returns what
+    // was fixed
+    pub fn fix(&self, datasets: &mut [Dataset]) -> Vec<Inconsistency> {
+        let mut fixed = inconsistencies(datasets, Level::Series, &self.series_attributes);
+        repair(datasets, &fixed);
+
+        let study = inconsistencies(datasets, Level::Study, &self.study_attributes);
+        repair(datasets, &study);
+        fixed.extend(study);
+        fixed
+    }
+}
+
+fn group_key(level: Level) -> (u16, u16) {
+    match level {
+        Level::Study => STUDY_INSTANCE_UID,
+        Level::Series => SERIES_INSTANCE_UID,
+    }
+}
+
+fn inconsistencies(datasets: &[Dataset], level: Level, tags: &[(u16, u16)]) -> Vec<Inconsistency> {
+    let mut groups: BTreeMap<String, Vec<&Dataset>> = BTreeMap::new();
+    for dataset in datasets {
+        if let Some(uid) = dataset.string(group_key(level)) {
+            groups.entry(uid).or_default().push(dataset);
+        }
+    }
+
+    let mut found = Vec::new();
+    for (uid, members) in groups {
+        for tag in tags {
+            // Values in order of first appearance, to break ties
+            let mut order: Vec<String> = Vec::new();
+            let mut values: BTreeMap<String, Vec<String>> = BTreeMap::new();
+            for dataset in &members {
+                let value = dataset.string(*tag).unwrap_or_default();
+                if !values.contains_key(&value) {
+                    order.push(value.clone());
+                }
+                values
+                    .entry(value)
+                    .or_default()
+                    .push(dataset.string(SOP_INSTANCE_UID).unwrap_or_default());
+            }
+
+            if values.len() > 1 {
+                let mut expected = order[0].clone();
+                for value in &order {
+                    if values[value].len() > values[&expected].len() {
+                        expected = value.clone();
+                    }
+                }
+                found.push(Inconsistency {
+                    level,
+                    uid: uid.clone(),
+                    tag: *tag,
+                    values,
+                    expected,
+                });
+            }
+        }
+    }
+    found
+}
+
+// Copies the expected element from an instance holding it, removing the
+// attribute when most instances lack it
+fn repair(datasets: &mut [Dataset], inconsistencies: &[Inconsistency]) {
+    for inconsistency in inconsistencies {
+        let key = group_key(inconsistency.level);
+        let in_group =
+            |dataset: &Dataset| dataset.string(key).as_deref() == Some(&inconsistency.uid);
+
+        let expected = datasets
+            .iter()
+            .filter(|dataset| in_group(dataset))
+            .find(|dataset| {
+                dataset.string(inconsistency.tag).unwrap_or_default() == inconsistency.expected
+            })
+            .and_then(|dataset| dataset.find(inconsistency.tag).cloned());
+
+        for dataset in datasets.iter_mut() {
+            if !in_group(dataset)
+                || dataset.string(inconsistency.tag).unwrap_or_default() == inconsistency.expected
+            {
+                continue;
+            }
+            match &expected {
+                Some(element) => dataset.put(element.clone()),
+                None => {
+                    dataset.remove(inconsistency.tag);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,7 @@
+#[cfg(any(feature = "net", feature = "default"))]
+pub mod conformance;
+pub mod consistency;
+pub mod presence;
+
+#[cfg(any(feature = "net", feature = "default"))]
+pub use conformance::conformance_statement;
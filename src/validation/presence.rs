@@ -0,0 +1,379 @@
+use std::{
+    fmt::{Display, Write as _},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+#[cfg(any(feature = "config", feature = "default"))]
+use std::{fs, path::Path};
+
+#[cfg(any(feature = "config", feature = "default"))]
+use serde::Deserialize;
+
+#[cfg(any(feature = "config", feature = "default"))]
+use crate::core::error::DicomError;
+use crate::core::{dataset::Dataset, dictionary, error::DicomResult};
+#[cfg(any(
+    all(feature = "net", feature = "serde", feature = "compress"),
+    feature = "image",
+    feature = "default"
+))]
+use crate::plugins::middleware::{Middleware, ProcessingContext, Verdict};
+
+pub const MODALITY: (u16, u16) = (0x0008, 0x0060);
+pub const SOP_INSTANCE_UID: (u16, u16) = (0x0008, 0x0018);
+pub const STUDY_INSTANCE_UID: (u16, u16) = (0x0020, 0x000D);
+
+// Attributes a site wants on every instance of a modality. Required ones
+// need a value, present ones may be empty as Type 2 attributes are. They
+// are looked for in sequence items too, where enhanced objects keep most of
+// them
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresenceProfile {
+    pub name: String,
+    // Modality the profile applies to, "*" for every one
+    pub modality: String,
+    pub required: Vec<(u16, u16)>,
+    pub present: Vec<(u16, u16)>,
+}
+
+impl PresenceProfile {
+    pub fn new(name: &str, modality: &str) -> Self {
+        PresenceProfile {
+            name: name.to_string(),
+            modality: modality.trim().to_string(),
+            required: Vec::new(),
+            present: Vec::new(),
+        }
+    }
+
+    pub fn with_required(mut self, tag: (u16, u16)) -> Self {
+        self.required.push(tag);
+        self
+    }
+
+    pub fn with_present(mut self, tag: (u16, u16)) -> Self {
+        self.present.push(tag);
+        self
+    }
+
+    pub fn applies(&self, dataset: &Dataset) -> bool {
+        self.modality == "*"
+            || dataset
+                .string(MODALITY)
+                .is_some_and(|modality| modality.trim() == self.modality)
+    }
+
+    // None when the dataset satisfies the profile or it does not apply
+    pub fn check(&self, dataset: &Dataset) -> Option<QcFailure> {
+        if !self.applies(dataset) {
+            return None;
+        }
+
+        let mut failure = QcFailure {
+            profile: self.name.clone(),
+            sop_instance_uid: dataset.string(SOP_INSTANCE_UID).unwrap_or_default(),
+            study_instance_uid: dataset.string(STUDY_INSTANCE_UID).unwrap_or_default(),
+            modality: dataset.string(MODALITY).unwrap_or_default(),
+            missing: Vec::new(),
+            empty: Vec::new(),
+        };
+        for tag in &self.required {
+            match presence(dataset, *tag) {
+                Some(true) => {}
+                Some(false) => failure.empty.push(*tag),
+                None => failure.missing.push(*tag),
+            }
+        }
+        for tag in &self.present {
+            if presence(dataset, *tag).is_none() {
+                failure.missing.push(*tag);
+            }
+        }
+
+        (!failure.missing.is_empty() || !failure.empty.is_empty()).then_some(failure)
+    }
+}
+
+// Some(true) for an element with a value anywhere in the dataset, Some(false)
+// when it is only ever empty
+fn presence(dataset: &Dataset, tag: (u16, u16)) -> Option<bool> {
+    let mut found = dataset.find(tag).map(|element| !element.vr().is_empty());
+    if found == Some(true) {
+        return found;
+    }
+    for element in dataset {
+        if element.vr().code() != "SQ" {
+            continue;
+        }
+        for item in dataset.sequence(element.tag()) {
+            match presence(&item, tag) {
+                Some(true) => return Some(true),
+                Some(false) => found = Some(false),
+                None => {}
+            }
+        }
+    }
+    found
+}
+
+// An instance failing a profile, one entry of the QC worklist
+#[derive(Debug, Clone, PartialEq)]
+pub struct QcFailure {
+    pub profile: String,
+    pub sop_instance_uid: String,
+    pub study_instance_uid: String,
+    pub modality: String,
+    pub missing: Vec<(u16, u16)>,
+    // Required but present without a value
+    pub empty: Vec<(u16, u16)>,
+}
+
+fn names(tags: &[(u16, u16)]) -> String {
+    tags.iter()
+        .map(|tag| match dictionary::keyword(*tag) {
+            Some(keyword) => keyword.to_string(),
+            None => format!("({:04X},{:04X})", tag.0, tag.1),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl Display for QcFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} fails {}",
+            self.modality, self.sop_instance_uid, self.profile
+        )?;
+        if !self.missing.is_empty() {
+            write!(f, ", missing {}", names(&self.missing))?;
+        }
+        if !self.empty.is_empty() {
+            write!(f, ", empty {}", names(&self.empty))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "config", feature = "default"))]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct ProfilesFile {
+    profile: Vec<ProfileEntry>,
+}
+
+#[cfg(any(feature = "config", feature = "default"))]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct ProfileEntry {
+    name: String,
+    modality: String,
+    required: Vec<String>,
+    present: Vec<String>,
+}
+
+// The profiles of a site, each instance checked against every one that
+// applies. Loaded from TOML such as
+//
+// [[profile]]
+// name = "CT dose"
+// modality = "CT"
+// required = ["KVP", "CTDIvol", "IrradiationEventUID"]
+// present = ["ContrastBolusAgent"]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PresenceProfiles {
+    pub profiles: Vec<PresenceProfile>,
+}
+
+impl PresenceProfiles {
+    pub fn new() -> Self {
+        PresenceProfiles::default()
+    }
+
+    pub fn with_profile(mut self, profile: PresenceProfile) -> Self {
+        self.profiles.push(profile);
+        self
+    }
+
+    #[cfg(any(feature = "config", feature = "default"))]
+    pub fn load<P: AsRef<Path>>(path: P) -> DicomResult<Self> {
+        PresenceProfiles::from_toml_str(&fs::read_to_string(path)?)
+    }
+
+    #[cfg(any(feature = "config", feature = "default"))]
+    pub fn from_toml_str(text: &str) -> DicomResult<Self> {
+        let file: ProfilesFile =
+            toml::from_str(text).map_err(|error| DicomError::InvalidFile(error.to_string()))?;
+
+        let tags = |profile: &str, names: &[String]| {
+            names
+                .iter()
+                .map(|name| {
+                    dictionary::parse_tag(name).map_err(|error| {
+                        DicomError::InvalidFile(format!("Profile {}: {}", profile, error))
+                    })
+                })
+                .collect::<DicomResult<Vec<_>>>()
+        };
+        let mut profiles = PresenceProfiles::new();
+        for entry in file.profile {
+            if entry.modality.trim().is_empty() {
+                return Err(DicomError::InvalidFile(format!(
+                    "Profile {} names no modality",
+                    entry.name
+                )));
+            }
+            profiles.profiles.push(PresenceProfile {
+                required: tags(&entry.name, &entry.required)?,
+                present: tags(&entry.name, &entry.present)?,
+                modality: entry.modality.trim().to_string(),
+                name: entry.name,
+            });
+        }
+        Ok(profiles)
+    }
+
+    pub fn check(&self, dataset: &Dataset) -> Vec<QcFailure> {
+        self.profiles
+            .iter()
+            .filter_map(|profile| profile.check(dataset))
+            .collect()
+    }
+}
+
+// Failures collected during ingest for someone to work through. Clones share
+// the entries, so receiving workers and the QC tooling see the same list
+#[derive(Debug, Clone, Default)]
+pub struct QcWorklist {
+    failures: Arc<Mutex<Vec<QcFailure>>>,
+}
+
+impl QcWorklist {
+    pub fn new() -> Self {
+        QcWorklist::default()
+    }
+
+    // A failure of an instance replaces the earlier one of the same profile,
+    // for instances sent again
+    pub fn add(&self, failure: QcFailure) {
+        let mut failures = self.failures.lock().unwrap_or_else(PoisonError::into_inner);
+        failures.retain(|entry| {
+            entry.sop_instance_uid != failure.sop_instance_uid || entry.profile != failure.profile
+        });
+        failures.push(failure);
+    }
+
+    pub fn failures(&self) -> Vec<QcFailure> {
+        self.failures
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.failures
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn for_study(&self, study_instance_uid: &str) -> Vec<QcFailure> {
+        self.failures()
+            .into_iter()
+            .filter(|failure| failure.study_instance_uid == study_instance_uid)
+            .collect()
+    }
+
+    // Takes an instance off the list once it was corrected, returning
+    // whether it was on it
+    pub fn resolve(&self, sop_instance_uid: &str) -> bool {
+        let mut failures = self.failures.lock().unwrap_or_else(PoisonError::into_inner);
+        let before = failures.len();
+        failures.retain(|failure| failure.sop_instance_uid != sop_instance_uid);
+        failures.len() != before
+    }
+
+    // One line per failure for spreadsheets, keywords separated by spaces
+    pub fn to_csv(&self) -> String {
+        let mut csv =
+            String::from("profile,modality,study_instance_uid,sop_instance_uid,missing,empty\n");
+        for failure in self.failures() {
+            let _ = writeln!(
+                csv,
+                "\"{}\",{},{},{},{},{}",
+                failure.profile.replace('"', "\"\""),
+                failure.modality,
+                failure.study_instance_uid,
+                failure.sop_instance_uid,
+                names(&failure.missing),
+                names(&failure.empty)
+            );
+        }
+        csv
+    }
+}
+
+// Checks each ingested dataset against the profiles and puts the failures on
+// the worklist. Instances are stored all the same unless rejecting is asked
+// for, QC is done after the fact
+#[cfg(any(
+    all(feature = "net", feature = "serde", feature = "compress"),
+    feature = "image",
+    feature = "default"
+))]
+pub struct PresenceCheck {
+    profiles: PresenceProfiles,
+    worklist: QcWorklist,
+    reject: bool,
+}
+
+#[cfg(any(
+    all(feature = "net", feature = "serde", feature = "compress"),
+    feature = "image",
+    feature = "default"
+))]
+impl PresenceCheck {
+    pub fn new(profiles: PresenceProfiles, worklist: QcWorklist) -> Self {
+        PresenceCheck {
+            profiles,
+            worklist,
+            reject: false,
+        }
+    }
+
+    pub fn with_reject(mut self, reject: bool) -> Self {
+        self.reject = reject;
+        self
+    }
+}
+
+#[cfg(any(
+    all(feature = "net", feature = "serde", feature = "compress"),
+    feature = "image",
+    feature = "default"
+))]
+impl Middleware for PresenceCheck {
+    fn name(&self) -> &str {
+        "presence-check"
+    }
+
+    fn handle(
+        &self,
+        _context: &mut ProcessingContext,
+        dataset: &mut Dataset,
+    ) -> DicomResult<Verdict> {
+        let failures = self.profiles.check(dataset);
+        let verdict = match failures.first() {
+            Some(failure) if self.reject => Verdict::Reject(failure.to_string()),
+            _ => Verdict::Continue,
+        };
+        for failure in failures {
+            self.worklist.add(failure);
+        }
+        Ok(verdict)
+    }
+}
@@ -0,0 +1,66 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    core::{json, reader},
+    image::render,
+};
+
+fn error(error: crate::core::error::DicomError) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+// DICOM JSON of the dataset, with the file meta information merged in
+#[wasm_bindgen]
+pub fn parse(data: &[u8]) -> Result<String, JsError> {
+    let file = reader::read_file(data).map_err(error)?;
+    let mut dataset = file.meta;
+    for element in &file.dataset {
+        dataset.push_back(element.clone());
+    }
+
+    Ok(json::to_json(&dataset).to_string())
+}
+
+#[wasm_bindgen]
+pub fn number_of_frames(data: &[u8]) -> Result<u32, JsError> {
+    let file = reader::read_file(data).map_err(error)?;
+    Ok(render::number_of_frames(&file.dataset) as u32)
+}
+
+#[wasm_bindgen]
+pub struct Frame {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl Frame {
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // RGBA bytes, ready for ImageData
+    #[wasm_bindgen(getter)]
+    pub fn pixels(&self) -> Vec<u8> {
+        self.pixels.clone()
+    }
+}
+
+#[wasm_bindgen]
+pub fn render_frame(data: &[u8], frame: u32) -> Result<Frame, JsError> {
+    let file = reader::read_file(data).map_err(error)?;
+    let rendered = render::render_frame(&file, frame as usize).map_err(error)?;
+
+    Ok(Frame {
+        width: rendered.width,
+        height: rendered.height,
+        pixels: rendered.pixels,
+    })
+}
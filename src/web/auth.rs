@@ -0,0 +1,173 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    time::{Duration, Instant},
+};
+
+use reqwest::{Client, RequestBuilder};
+use tokio::sync::Mutex;
+
+use crate::core::error::{DicomError, DicomResult};
+
+pub type AuthFuture<'a> = Pin<Box<dyn Future<Output = DicomResult<RequestBuilder>> + Send + 'a>>;
+pub type InvalidateFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+// Tokens are refreshed this long before the server considers them expired
+const REFRESH_MARGIN: Duration = Duration::from_secs(30);
+
+pub trait Authenticator: Send + Sync {
+    fn authenticate(&self, request: RequestBuilder) -> AuthFuture<'_>;
+
+    // Called after the server answered 401, so cached credentials get refetched
+    fn invalidate(&self) -> InvalidateFuture<'_> {
+        Box::pin(async {})
+    }
+}
+
+pub trait RequestHook: Send + Sync {
+    fn apply(&self, request: RequestBuilder) -> RequestBuilder;
+}
+
+impl<F> RequestHook for F
+where
+    F: Fn(RequestBuilder) -> RequestBuilder + Send + Sync,
+{
+    fn apply(&self, request: RequestBuilder) -> RequestBuilder {
+        self(request)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BearerToken {
+    token: String,
+}
+
+impl BearerToken {
+    pub fn new(token: &str) -> Self {
+        BearerToken {
+            token: token.to_string(),
+        }
+    }
+}
+
+impl Authenticator for BearerToken {
+    fn authenticate(&self, request: RequestBuilder) -> AuthFuture<'_> {
+        Box::pin(async move { Ok(request.bearer_auth(&self.token)) })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BasicAuth {
+    username: String,
+    password: String,
+}
+
+impl BasicAuth {
+    pub fn new(username: &str, password: &str) -> Self {
+        BasicAuth {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+}
+
+impl Authenticator for BasicAuth {
+    fn authenticate(&self, request: RequestBuilder) -> AuthFuture<'_> {
+        Box::pin(async move { Ok(request.basic_auth(&self.username, Some(&self.password))) })
+    }
+}
+
+#[derive(Debug)]
+struct CachedToken {
+    access_token: String,
+    expires_at: Instant,
+}
+
+#[derive(Debug)]
+pub struct OAuth2ClientCredentials {
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+    http: Client,
+    token: Mutex<Option<CachedToken>>,
+}
+
+impl OAuth2ClientCredentials {
+    pub fn new(token_url: &str, client_id: &str, client_secret: &str) -> Self {
+        OAuth2ClientCredentials {
+            token_url: token_url.to_string(),
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            scope: None,
+            http: Client::new(),
+            token: Mutex::new(None),
+        }
+    }
+
+    pub fn with_scope(mut self, scope: &str) -> Self {
+        self.scope = Some(scope.to_string());
+        self
+    }
+
+    pub fn with_http_client(mut self, http: Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    pub async fn access_token(&self) -> DicomResult<String> {
+        // Holding the lock across the refresh keeps concurrent requests from all refreshing
+        let mut cached = self.token.lock().await;
+        if let Some(token) = cached.as_ref() {
+            if token.expires_at > Instant::now() + REFRESH_MARGIN {
+                return Ok(token.access_token.clone());
+            }
+        }
+
+        let mut form = vec![
+            ("grant_type", "client_credentials"),
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+        ];
+        if let Some(scope) = &self.scope {
+            form.push(("scope", scope.as_str()));
+        }
+
+        let response = self.http.post(&self.token_url).form(&form).send().await?;
+        if !response.status().is_success() {
+            return Err(DicomError::HttpStatus(response.status().as_u16()));
+        }
+
+        let body: serde_json::Value = response.json().await?;
+        let access_token = body["access_token"]
+            .as_str()
+            .ok_or_else(|| {
+                DicomError::InvalidValue("Token response without access_token".to_string())
+            })?
+            .to_string();
+        let expires_in = body["expires_in"].as_u64().unwrap_or(3600);
+
+        *cached = Some(CachedToken {
+            access_token: access_token.clone(),
+            expires_at: Instant::now() + Duration::from_secs(expires_in),
+        });
+
+        Ok(access_token)
+    }
+}
+
+impl Authenticator for OAuth2ClientCredentials {
+    fn authenticate(&self, request: RequestBuilder) -> AuthFuture<'_> {
+        Box::pin(async move {
+            let token = self.access_token().await?;
+            Ok(request.bearer_auth(token))
+        })
+    }
+
+    // Waits for a refresh in flight, so the retry never gets the stale token
+    fn invalidate(&self) -> InvalidateFuture<'_> {
+        Box::pin(async move {
+            *self.token.lock().await = None;
+        })
+    }
+}
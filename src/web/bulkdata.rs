@@ -0,0 +1,49 @@
+use reqwest::header::ACCEPT;
+use tokio::runtime::{Builder, Runtime};
+
+use super::client::{read_parts, DicomWebClient};
+use crate::core::{
+    bulkdata::BulkDataResolver,
+    error::{DicomError, DicomResult},
+};
+
+// http and https bulk data URIs, fetched with the authentication and retries
+// of a DICOMweb client. Requests block on a runtime of its own, so it is not
+// to be used from async code
+pub struct HttpResolver {
+    client: DicomWebClient,
+    runtime: Runtime,
+}
+
+impl HttpResolver {
+    pub fn new(client: DicomWebClient) -> DicomResult<Self> {
+        Ok(HttpResolver {
+            client,
+            runtime: Builder::new_current_thread().enable_all().build()?,
+        })
+    }
+}
+
+impl BulkDataResolver for HttpResolver {
+    fn fetch(&self, uri: &str) -> DicomResult<Vec<u8>> {
+        // Relative URIs are taken from the base URL of the client
+        let url = if uri.contains("://") {
+            uri.to_string()
+        } else {
+            self.client.url(uri)
+        };
+        let request = self.client.http().get(url).header(
+            ACCEPT,
+            "multipart/related; type=\"application/octet-stream\"",
+        );
+        self.runtime.block_on(async {
+            let response = self.client.execute(request).await?;
+            read_parts(response)
+                .await?
+                .into_iter()
+                .next()
+                .map(|part| part.body)
+                .ok_or_else(|| DicomError::InvalidValue(format!("No bulk data at {}", uri)))
+        })
+    }
+}
@@ -0,0 +1,816 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use reqwest::{
+    header::{ACCEPT, CONTENT_TYPE, ETAG, IF_NONE_MATCH, IF_RANGE, RANGE},
+    Body, Client, RequestBuilder, Response, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::Semaphore};
+
+use super::{
+    auth::{Authenticator, RequestHook},
+    config::HttpConfig,
+    etag::{CachedResponse, ResponseCache},
+    frames::{FrameList, OCTET_STREAM_MEDIA_TYPE},
+    multipart::{self, MultipartDecoder, Part, PartSource, ProgressCallback},
+    retry::RetryPolicy,
+};
+use crate::core::error::{DicomError, DicomResult};
+use crate::net::{cancel::CancellationToken, query::QueryBuilder};
+
+pub const DICOM_MEDIA_TYPE: &str = "application/dicom";
+pub const DICOM_JSON_MEDIA_TYPE: &str = "application/dicom+json";
+
+#[derive(Clone)]
+pub struct DicomWebClient {
+    http: Client,
+    base_url: String,
+    auth: Option<Arc<dyn Authenticator>>,
+    hooks: Vec<Arc<dyn RequestHook>>,
+    retry: RetryPolicy,
+    limiter: Option<Arc<Semaphore>>,
+    cache: Option<Arc<ResponseCache>>,
+}
+
+impl DicomWebClient {
+    pub fn new(base_url: &str) -> Self {
+        DicomWebClient {
+            http: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            auth: None,
+            hooks: vec![],
+            retry: RetryPolicy::default(),
+            limiter: None,
+            cache: None,
+        }
+    }
+
+    pub fn with_config(mut self, config: &HttpConfig) -> DicomResult<Self> {
+        self.http = config.build_client()?;
+        self.retry = config.retry.clone();
+        self.limiter = config
+            .max_concurrent_requests
+            .map(|limit| Arc::new(Semaphore::new(limit)));
+        Ok(self)
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_http_client(mut self, http: Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    // Metadata and rendered responses are kept with their ETag and asked for
+    // again with If-None-Match
+    pub fn with_cache(mut self, cache: Arc<ResponseCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn with_auth<A: Authenticator + 'static>(mut self, auth: A) -> Self {
+        self.auth = Some(Arc::new(auth));
+        self
+    }
+
+    pub fn with_hook<H: RequestHook + 'static>(mut self, hook: H) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    pub fn http(&self) -> &Client {
+        &self.http
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path.trim_start_matches('/'))
+    }
+
+    async fn prepare(&self, mut request: RequestBuilder) -> DicomResult<RequestBuilder> {
+        if let Some(auth) = &self.auth {
+            request = auth.authenticate(request).await?;
+        }
+        for hook in &self.hooks {
+            request = hook.apply(request);
+        }
+
+        Ok(request)
+    }
+
+    pub async fn execute(&self, request: RequestBuilder) -> DicomResult<Response> {
+        // The permit only covers the exchange itself, bodies are streamed by the caller
+        let _permit = match &self.limiter {
+            Some(limiter) => {
+                Some(
+                    limiter
+                        .acquire()
+                        .await
+                        .map_err(|error| DicomError::External {
+                            context: "Download limiter closed".to_string(),
+                            source: Box::new(error),
+                        })?,
+                )
+            }
+            None => None,
+        };
+
+        let mut pending = request;
+        let mut attempt = 0;
+        let mut refreshed = false;
+
+        loop {
+            let replay = pending.try_clone();
+            let outcome = self.prepare(pending).await?.send().await;
+
+            // One more attempt with fresh credentials after a 401
+            let refresh = !refreshed
+                && self.auth.is_some()
+                && matches!(&outcome, Ok(response) if response.status() == StatusCode::UNAUTHORIZED);
+            let wait = if refresh {
+                Some(Duration::ZERO)
+            } else if attempt < self.retry.max_retries {
+                match &outcome {
+                    Ok(response) if self.retry.should_retry(response.status().as_u16()) => {
+                        Some(self.retry.delay_for(response, attempt))
+                    }
+                    Err(error) if error.is_timeout() || error.is_connect() => {
+                        Some(self.retry.backoff(attempt))
+                    }
+                    _ => None,
+                }
+            } else {
+                None
+            };
+
+            // Bodies that cannot be replayed get a single shot
+            if let (Some(wait), Some(replay)) = (wait, replay) {
+                if refresh {
+                    if let Some(auth) = &self.auth {
+                        auth.invalidate().await;
+                    }
+                    refreshed = true;
+                } else {
+                    attempt += 1;
+                }
+
+                tokio::time::sleep(wait).await;
+                pending = replay;
+                continue;
+            }
+
+            let response = outcome?;
+            // Only conditional requests are answered with 304
+            if !response.status().is_success() && response.status() != StatusCode::NOT_MODIFIED {
+                return Err(DicomError::HttpStatus(response.status().as_u16()));
+            }
+
+            return Ok(response);
+        }
+    }
+
+    pub async fn get(&self, path: &str, accept: &str) -> DicomResult<Response> {
+        let request = self.http.get(self.url(path)).header(ACCEPT, accept);
+        self.execute(request).await
+    }
+
+    // GET through the response cache, the cached body reused when the server
+    // answers 304 Not Modified. Without a cache it is a plain GET
+    pub async fn get_cached(&self, path: &str, accept: &str) -> DicomResult<CachedResponse> {
+        let url = self.url(path);
+        let key = format!("{} {}", accept, url);
+        let cached = self.cache.as_ref().and_then(|cache| cache.get(&key));
+
+        let mut request = self.http.get(&url).header(ACCEPT, accept);
+        if let Some(cached) = &cached {
+            request = request.header(IF_NONE_MATCH, &cached.etag);
+        }
+        let response = self.execute(request).await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return cached.ok_or(DicomError::HttpStatus(StatusCode::NOT_MODIFIED.as_u16()));
+        }
+
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+        let fresh = CachedResponse {
+            etag: etag.clone().unwrap_or_default(),
+            content_type: response_content_type(&response),
+            body: response.bytes().await?.to_vec(),
+        };
+        if let (Some(cache), Some(_)) = (&self.cache, etag) {
+            cache.insert(&key, fresh.clone());
+        }
+        Ok(fresh)
+    }
+
+    pub async fn post(
+        &self,
+        path: &str,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> DicomResult<Response> {
+        let request = self
+            .http
+            .post(self.url(path))
+            .header(CONTENT_TYPE, content_type)
+            .header(ACCEPT, DICOM_JSON_MEDIA_TYPE)
+            .body(body);
+        self.execute(request).await
+    }
+}
+
+pub(crate) fn response_content_type(response: &Response) -> String {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+pub(crate) async fn read_parts(response: Response) -> DicomResult<Vec<Part>> {
+    let content_type = response_content_type(&response);
+    let body = response.bytes().await?;
+
+    match multipart::boundary_from_content_type(&content_type) {
+        Some(boundary) => multipart::decode(&body, &boundary),
+        None => Ok(vec![Part::new(&content_type, body.to_vec())]),
+    }
+}
+
+pub(crate) async fn read_json_array(response: Response) -> DicomResult<Vec<Value>> {
+    parse_json_array(&response.bytes().await?)
+}
+
+pub(crate) fn parse_json_array(body: &[u8]) -> DicomResult<Vec<Value>> {
+    if body.is_empty() {
+        return Ok(vec![]);
+    }
+
+    match serde_json::from_slice(body) {
+        Ok(Value::Array(values)) => Ok(values),
+        Ok(_) => Err(DicomError::InvalidValue(
+            "Expected a JSON array".to_string(),
+        )),
+        Err(error) => Err(DicomError::InvalidValue(error.to_string())),
+    }
+}
+
+pub(crate) fn json_string(dataset: &Value, tag: &str) -> Option<String> {
+    dataset[tag]["Value"][0]
+        .as_str()
+        .map(|value| value.to_string())
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetrievalCheckpoint {
+    pub completed: HashSet<String>,
+}
+
+impl RetrievalCheckpoint {
+    pub fn load(path: &Path) -> DicomResult<Self> {
+        if !path.exists() {
+            return Ok(RetrievalCheckpoint::default());
+        }
+
+        let data = fs::read(path)?;
+        serde_json::from_slice(&data).map_err(|error| DicomError::InvalidFile(error.to_string()))
+    }
+
+    pub fn save(&self, path: &Path) -> DicomResult<()> {
+        let data = serde_json::to_vec(self).map_err(std::io::Error::from)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+}
+
+// What is known of an interrupted download, kept next to the bytes received
+// so far as <file>.part.json
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PartialDownload {
+    // Resuming is only safe while the server still has the same entity
+    pub etag: Option<String>,
+    pub content_type: String,
+}
+
+impl PartialDownload {
+    pub fn data_path(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_os_string();
+        name.push(".part");
+        PathBuf::from(name)
+    }
+
+    pub fn state_path(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_os_string();
+        name.push(".part.json");
+        PathBuf::from(name)
+    }
+
+    pub fn load(path: &Path) -> DicomResult<Option<Self>> {
+        let state = PartialDownload::state_path(path);
+        if !state.exists() || !PartialDownload::data_path(path).exists() {
+            return Ok(None);
+        }
+        let data = fs::read(state)?;
+        serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|error| DicomError::InvalidFile(error.to_string()))
+    }
+
+    pub fn save(&self, path: &Path) -> DicomResult<()> {
+        let data = serde_json::to_vec(self).map_err(std::io::Error::from)?;
+        fs::write(PartialDownload::state_path(path), data)?;
+        Ok(())
+    }
+
+    pub fn discard(path: &Path) -> DicomResult<()> {
+        for partial in [
+            PartialDownload::data_path(path),
+            PartialDownload::state_path(path),
+        ] {
+            if partial.exists() {
+                fs::remove_file(partial)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct WadoClient {
+    client: DicomWebClient,
+}
+
+impl WadoClient {
+    pub fn new(client: DicomWebClient) -> Self {
+        WadoClient { client }
+    }
+
+    pub fn client(&self) -> &DicomWebClient {
+        &self.client
+    }
+
+    pub async fn retrieve_study(&self, study_uid: &str) -> DicomResult<Vec<Vec<u8>>> {
+        self.retrieve(&format!("studies/{}", study_uid)).await
+    }
+
+    pub async fn retrieve_series(
+        &self,
+        study_uid: &str,
+        series_uid: &str,
+    ) -> DicomResult<Vec<Vec<u8>>> {
+        self.retrieve(&format!("studies/{}/series/{}", study_uid, series_uid))
+            .await
+    }
+
+    pub async fn retrieve_instance(
+        &self,
+        study_uid: &str,
+        series_uid: &str,
+        instance_uid: &str,
+    ) -> DicomResult<Vec<u8>> {
+        let path = format!(
+            "studies/{}/series/{}/instances/{}",
+            study_uid, series_uid, instance_uid
+        );
+
+        self.retrieve(&path)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| DicomError::InvalidValue("Empty WADO-RS response".to_string()))
+    }
+
+    // Only the listed frames, each part in the transfer syntax its content
+    // type names. Any syntax is accepted so compressed frames arrive as the
+    // server stores them
+    pub async fn retrieve_frames(
+        &self,
+        study_uid: &str,
+        series_uid: &str,
+        instance_uid: &str,
+        frames: &FrameList,
+    ) -> DicomResult<Vec<Part>> {
+        let path = format!(
+            "studies/{}/series/{}/instances/{}/frames/{}",
+            study_uid, series_uid, instance_uid, frames
+        );
+        let accept = format!(
+            "multipart/related; type=\"{}\"; transfer-syntax=*",
+            OCTET_STREAM_MEDIA_TYPE
+        );
+        let response = self.client.get(&path, &accept).await?;
+
+        let parts = read_parts(response).await?;
+        if parts.len() != frames.frames.len() {
+            return Err(DicomError::InvalidValue(format!(
+                "{} frames asked for, {} received",
+                frames.frames.len(),
+                parts.len()
+            )));
+        }
+        Ok(parts)
+    }
+
+    pub async fn retrieve_metadata(&self, study_uid: &str) -> DicomResult<Vec<Value>> {
+        let response = self
+            .client
+            .get_cached(
+                &format!("studies/{}/metadata", study_uid),
+                DICOM_JSON_MEDIA_TYPE,
+            )
+            .await?;
+
+        parse_json_array(&response.body)
+    }
+
+    // The instance rendered by the server, a JPEG unless it prefers another
+    // image type
+    pub async fn retrieve_rendered(
+        &self,
+        study_uid: &str,
+        series_uid: &str,
+        instance_uid: &str,
+    ) -> DicomResult<CachedResponse> {
+        let path = format!(
+            "studies/{}/series/{}/instances/{}/rendered",
+            study_uid, series_uid, instance_uid
+        );
+        self.client
+            .get_cached(&path, "image/jpeg, image/*;q=0.5")
+            .await
+    }
+
+    // Fetches a study instance by instance, skipping whatever the checkpoint already holds,
+    // so an interrupted download picks up where it stopped
+    pub async fn retrieve_study_resumable<F>(
+        &self,
+        study_uid: &str,
+        checkpoint: &mut RetrievalCheckpoint,
+        mut on_instance: F,
+    ) -> DicomResult<usize>
+    where
+        F: FnMut(&str, Vec<u8>) -> DicomResult<()>,
+    {
+        let mut retrieved = 0;
+
+        for metadata in self.retrieve_metadata(study_uid).await? {
+            let (Some(series_uid), Some(instance_uid)) = (
+                json_string(&metadata, "0020000E"),
+                json_string(&metadata, "00080018"),
+            ) else {
+                continue;
+            };
+
+            if checkpoint.completed.contains(&instance_uid) {
+                continue;
+            }
+
+            let instance = self
+                .retrieve_instance(study_uid, &series_uid, &instance_uid)
+                .await?;
+            on_instance(&instance_uid, instance)?;
+
+            checkpoint.completed.insert(instance_uid);
+            retrieved += 1;
+        }
+
+        Ok(retrieved)
+    }
+
+    // Hands every instance to `on_part` as soon as it has been received instead of
+    // collecting the whole study first
+    pub async fn retrieve_streaming<F>(
+        &self,
+        path: &str,
+        progress: Option<ProgressCallback>,
+        mut on_part: F,
+    ) -> DicomResult<usize>
+    where
+        F: FnMut(Part) -> DicomResult<()>,
+    {
+        let accept = format!("multipart/related; type=\"{}\"", DICOM_MEDIA_TYPE);
+        let mut response = self.client.get(path, &accept).await?;
+
+        let content_type = response_content_type(&response);
+        let boundary = multipart::boundary_from_content_type(&content_type).ok_or_else(|| {
+            DicomError::InvalidValue(format!("Not a multipart response: {}", content_type))
+        })?;
+
+        let mut decoder = MultipartDecoder::new(&boundary);
+        if let Some(progress) = progress {
+            decoder = decoder.with_progress(progress);
+        }
+
+        let mut received = 0;
+        while let Some(chunk) = response.chunk().await? {
+            for part in decoder.feed(&chunk)? {
+                on_part(part)?;
+                received += 1;
+            }
+        }
+        decoder.finish()?;
+
+        Ok(received)
+    }
+
+    // Downloads an instance into `path`. Bytes are written to <path>.part as
+    // they arrive, so a download cut off, in this call or an earlier run,
+    // continues with a Range request from where it stopped instead of
+    // starting over. Returns the size of the instance
+    pub async fn download_instance(
+        &self,
+        study_uid: &str,
+        series_uid: &str,
+        instance_uid: &str,
+        path: &Path,
+    ) -> DicomResult<u64> {
+        let url_path = format!(
+            "studies/{}/series/{}/instances/{}",
+            study_uid, series_uid, instance_uid
+        );
+
+        let mut attempt = 0;
+        let state = loop {
+            match self.download_part(&url_path, path).await {
+                Ok(state) => break state,
+                // The part on disk no longer fits the instance
+                Err(DicomError::HttpStatus(416)) if attempt == 0 => {
+                    PartialDownload::discard(path)?;
+                }
+                Err(DicomError::External { .. }) if attempt < self.client.retry.max_retries => {
+                    tokio::time::sleep(self.client.retry.backoff(attempt)).await;
+                }
+                Err(error) => return Err(error),
+            }
+            attempt += 1;
+        };
+
+        // Servers answering with multipart/related wrap the instance in a part
+        let data = PartialDownload::data_path(path);
+        match multipart::boundary_from_content_type(&state.content_type) {
+            Some(boundary) => {
+                let parts = multipart::decode(&fs::read(&data)?, &boundary)?;
+                let part = parts.into_iter().next().ok_or_else(|| {
+                    DicomError::InvalidValue("Empty WADO-RS response".to_string())
+                })?;
+                fs::write(path, part.body)?;
+                fs::remove_file(&data)?;
+            }
+            None => fs::rename(&data, path)?,
+        }
+        fs::remove_file(PartialDownload::state_path(path))?;
+        Ok(fs::metadata(path)?.len())
+    }
+
+    async fn download_part(&self, url_path: &str, path: &Path) -> DicomResult<PartialDownload> {
+        let data = PartialDownload::data_path(path);
+        let partial = PartialDownload::load(path)?;
+        let offset = match &partial {
+            Some(_) => fs::metadata(&data)?.len(),
+            None => 0,
+        };
+
+        let accept = format!("{}; transfer-syntax=*", DICOM_MEDIA_TYPE);
+        let mut request = self
+            .client
+            .http
+            .get(self.client.url(url_path))
+            .header(ACCEPT, accept);
+        if let Some(partial) = partial.as_ref().filter(|_| offset > 0) {
+            request = request.header(RANGE, format!("bytes={}-", offset));
+            if let Some(etag) = &partial.etag {
+                request = request.header(IF_RANGE, etag);
+            }
+        }
+        let mut response = self.client.execute(request).await?;
+
+        // 200 instead of 206 means the range was ignored or the instance
+        // changed, so the body starts from the beginning
+        let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+        let state = match partial {
+            Some(partial) if resumed => partial,
+            _ => PartialDownload {
+                etag: response
+                    .headers()
+                    .get(ETAG)
+                    .and_then(|value| value.to_str().ok())
+                    .map(|value| value.to_string()),
+                content_type: response_content_type(&response),
+            },
+        };
+        state.save(path)?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resumed)
+            .truncate(!resumed)
+            .open(&data)
+            .await?;
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        Ok(state)
+    }
+
+    async fn retrieve(&self, path: &str) -> DicomResult<Vec<Vec<u8>>> {
+        let accept = format!("multipart/related; type=\"{}\"", DICOM_MEDIA_TYPE);
+        let response = self.client.get(path, &accept).await?;
+
+        Ok(read_parts(response)
+            .await?
+            .into_iter()
+            .map(|part| part.body)
+            .collect())
+    }
+}
+
+#[derive(Clone)]
+pub struct QidoClient {
+    client: DicomWebClient,
+}
+
+impl QidoClient {
+    pub fn new(client: DicomWebClient) -> Self {
+        QidoClient { client }
+    }
+
+    pub fn client(&self) -> &DicomWebClient {
+        &self.client
+    }
+
+    pub async fn search_studies(&self, query: &[(&str, &str)]) -> DicomResult<Vec<Value>> {
+        self.search("studies", query).await
+    }
+
+    pub async fn search_series(
+        &self,
+        study_uid: &str,
+        query: &[(&str, &str)],
+    ) -> DicomResult<Vec<Value>> {
+        self.search(&format!("studies/{}/series", study_uid), query)
+            .await
+    }
+
+    pub async fn search_instances(
+        &self,
+        study_uid: &str,
+        series_uid: &str,
+        query: &[(&str, &str)],
+    ) -> DicomResult<Vec<Value>> {
+        let path = format!("studies/{}/series/{}/instances", study_uid, series_uid);
+        self.search(&path, query).await
+    }
+
+    // Search at the builder's level with its required keys included
+    pub async fn search_query(&self, query: &QueryBuilder) -> DicomResult<Vec<Value>> {
+        let (path, parameters) = query.to_qido()?;
+        let parameters: Vec<(&str, &str)> = parameters
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        self.search(&path, &parameters).await
+    }
+
+    // Searches `path`, e.g. "studies", page by page with limit and offset,
+    // handing each page to `on_page`. Stops after a short page or once the
+    // token is cancelled, returning the number of results received
+    pub async fn search_pages<F: FnMut(Vec<Value>)>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+        page_size: usize,
+        token: &CancellationToken,
+        mut on_page: F,
+    ) -> DicomResult<usize> {
+        let page_size = page_size.max(1);
+        let limit = page_size.to_string();
+        let mut received = 0;
+
+        while !token.is_cancelled() {
+            let offset = received.to_string();
+            let mut paged = query.to_vec();
+            paged.push(("limit", &limit));
+            paged.push(("offset", &offset));
+
+            let page = self.search(path, &paged).await?;
+            let count = page.len();
+            received += count;
+            if !token.is_cancelled() {
+                on_page(page);
+            }
+            if count < page_size {
+                break;
+            }
+        }
+        Ok(received)
+    }
+
+    async fn search(&self, path: &str, query: &[(&str, &str)]) -> DicomResult<Vec<Value>> {
+        let request = self
+            .client
+            .http()
+            .get(self.client.url(path))
+            .header(ACCEPT, DICOM_JSON_MEDIA_TYPE)
+            .query(query);
+        let response = self.client.execute(request).await?;
+
+        read_json_array(response).await
+    }
+}
+
+#[derive(Clone)]
+pub struct StowClient {
+    client: DicomWebClient,
+}
+
+impl StowClient {
+    pub fn new(client: DicomWebClient) -> Self {
+        StowClient { client }
+    }
+
+    pub fn client(&self) -> &DicomWebClient {
+        &self.client
+    }
+
+    pub async fn store(
+        &self,
+        study_uid: Option<&str>,
+        instances: Vec<Vec<u8>>,
+    ) -> DicomResult<Value> {
+        let path = match study_uid {
+            Some(study_uid) => format!("studies/{}", study_uid),
+            None => "studies".to_string(),
+        };
+
+        let parts: Vec<Part> = instances
+            .into_iter()
+            .map(|instance| Part::new(DICOM_MEDIA_TYPE, instance))
+            .collect();
+        let boundary = multipart::boundary();
+        let body = multipart::encode(&parts, &boundary);
+
+        let response = self
+            .client
+            .post(
+                &path,
+                &multipart::content_type(DICOM_MEDIA_TYPE, &boundary),
+                body,
+            )
+            .await?;
+        let body = response.bytes().await?;
+        if body.is_empty() {
+            return Ok(Value::Null);
+        }
+
+        serde_json::from_slice(&body).map_err(|error| DicomError::InvalidValue(error.to_string()))
+    }
+
+    // Streamed uploads cannot be replayed, so they are not retried on failure
+    pub async fn store_streaming(
+        &self,
+        study_uid: Option<&str>,
+        sources: Vec<PartSource>,
+        progress: Option<ProgressCallback>,
+    ) -> DicomResult<Value> {
+        let path = match study_uid {
+            Some(study_uid) => format!("studies/{}", study_uid),
+            None => "studies".to_string(),
+        };
+
+        let boundary = multipart::boundary();
+        let body = Body::wrap_stream(multipart::encode_stream(sources, &boundary, progress));
+        let request = self
+            .client
+            .http()
+            .post(self.client.url(&path))
+            .header(
+                CONTENT_TYPE,
+                multipart::content_type(DICOM_MEDIA_TYPE, &boundary),
+            )
+            .header(ACCEPT, DICOM_JSON_MEDIA_TYPE)
+            .body(body);
+
+        let response = self.client.execute(request).await?;
+        let body = response.bytes().await?;
+        if body.is_empty() {
+            return Ok(Value::Null);
+        }
+
+        serde_json::from_slice(&body).map_err(|error| DicomError::InvalidValue(error.to_string()))
+    }
+}
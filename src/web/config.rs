@@ -0,0 +1,208 @@
+use std::{fs, path::PathBuf, time::Duration};
+
+use reqwest::{Certificate, Client, ClientBuilder, Identity, NoProxy, Proxy};
+
+use super::retry::RetryPolicy;
+use crate::core::error::DicomResult;
+
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub no_proxy: Option<String>,
+}
+
+impl ProxyConfig {
+    pub fn new(url: &str) -> Self {
+        ProxyConfig {
+            url: url.to_string(),
+            username: None,
+            password: None,
+            no_proxy: None,
+        }
+    }
+
+    pub fn with_credentials(mut self, username: &str, password: &str) -> Self {
+        self.username = Some(username.to_string());
+        self.password = Some(password.to_string());
+        self
+    }
+
+    // Comma separated hosts or CIDRs that bypass the proxy, as in NO_PROXY
+    pub fn with_no_proxy(mut self, hosts: &str) -> Self {
+        self.no_proxy = Some(hosts.to_string());
+        self
+    }
+
+    fn build(&self) -> DicomResult<Proxy> {
+        let mut proxy = Proxy::all(self.url.as_str())?;
+        if let Some(username) = &self.username {
+            proxy = proxy.basic_auth(username, self.password.as_deref().unwrap_or_default());
+        }
+        if let Some(hosts) = &self.no_proxy {
+            proxy = proxy.no_proxy(NoProxy::from_string(hosts));
+        }
+
+        Ok(proxy)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    // PEM files, each may hold a whole bundle
+    pub root_certificates: Vec<PathBuf>,
+    // PEM file holding the client certificate chain and its private key
+    pub client_identity: Option<PathBuf>,
+    pub built_in_roots: bool,
+    pub accept_invalid_certs: bool,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        TlsConfig {
+            root_certificates: vec![],
+            client_identity: None,
+            built_in_roots: true,
+            accept_invalid_certs: false,
+        }
+    }
+}
+
+impl TlsConfig {
+    fn apply(&self, mut builder: ClientBuilder) -> DicomResult<ClientBuilder> {
+        builder = builder
+            .use_rustls_tls()
+            .tls_built_in_root_certs(self.built_in_roots);
+
+        for path in &self.root_certificates {
+            for certificate in pem_certificates(&fs::read(path)?)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        if let Some(path) = &self.client_identity {
+            builder = builder.identity(Identity::from_pem(&fs::read(path)?)?);
+        }
+
+        if self.accept_invalid_certs {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        Ok(builder)
+    }
+}
+
+// Splits a PEM bundle, since intercepting proxies usually ship their whole chain in one file
+fn pem_certificates(data: &[u8]) -> DicomResult<Vec<Certificate>> {
+    const END: &str = "-----END CERTIFICATE-----";
+
+    let text = String::from_utf8_lossy(data);
+    let mut certificates = Vec::new();
+    let mut rest = text.as_ref();
+
+    while let Some(start) = rest.find("-----BEGIN CERTIFICATE-----") {
+        let Some(end) = rest[start..].find(END) else {
+            break;
+        };
+        let block = &rest[start..start + end + END.len()];
+        certificates.push(Certificate::from_pem(block.as_bytes())?);
+        rest = &rest[start + end + END.len()..];
+    }
+
+    if certificates.is_empty() {
+        certificates.push(Certificate::from_der(data)?);
+    }
+
+    Ok(certificates)
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    pub connect_timeout: Option<Duration>,
+    pub read_timeout: Option<Duration>,
+    pub request_timeout: Option<Duration>,
+    pub retry: RetryPolicy,
+    pub max_concurrent_requests: Option<usize>,
+    pub proxy: Option<ProxyConfig>,
+    pub use_system_proxy: bool,
+    pub tls: TlsConfig,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            connect_timeout: Some(Duration::from_secs(10)),
+            read_timeout: Some(Duration::from_secs(60)),
+            request_timeout: None,
+            retry: RetryPolicy::default(),
+            max_concurrent_requests: None,
+            proxy: None,
+            use_system_proxy: true,
+            tls: TlsConfig::default(),
+        }
+    }
+}
+
+impl HttpConfig {
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_max_concurrent_requests(mut self, limit: usize) -> Self {
+        self.max_concurrent_requests = Some(limit);
+        self
+    }
+
+    pub fn with_proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    pub fn build_client(&self) -> DicomResult<Client> {
+        let mut builder = Client::builder();
+
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            builder = builder.read_timeout(timeout);
+        }
+        if let Some(timeout) = self.request_timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        // reqwest picks up HTTP(S)_PROXY by itself unless told otherwise
+        if !self.use_system_proxy {
+            builder = builder.no_proxy();
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.build()?);
+        }
+
+        builder = self.tls.apply(builder)?;
+
+        Ok(builder.build()?)
+    }
+}
@@ -0,0 +1,201 @@
+use std::time::Duration;
+
+// Cross-origin access for browser based viewers such as OHIF. Browsers send
+// an OPTIONS preflight before requests with an Authorization header or a
+// DICOM Accept, and drop responses that do not name their origin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsConfig {
+    // Exact origins such as https://viewer.example.org, or * for any when
+    // credentials are not allowed
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    // Request headers a preflight may ask for, matched case insensitively
+    pub allowed_headers: Vec<String>,
+    // Response headers scripts get to read beyond the safelisted ones
+    pub exposed_headers: Vec<String>,
+    pub max_age: Duration,
+    pub allow_credentials: bool,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        let strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+        CorsConfig {
+            allowed_origins: Vec::new(),
+            allowed_methods: strings(&["GET", "HEAD", "POST", "OPTIONS"]),
+            allowed_headers: strings(&[
+                "Accept",
+                "Authorization",
+                "Content-Type",
+                "If-None-Match",
+                "If-Range",
+                "Range",
+            ]),
+            exposed_headers: strings(&[
+                "Accept-Ranges",
+                "Content-Location",
+                "Content-Range",
+                "ETag",
+                "Location",
+                "Warning",
+            ]),
+            max_age: Duration::from_secs(600),
+            allow_credentials: false,
+        }
+    }
+}
+
+impl CorsConfig {
+    // No origin allowed until one is added
+    pub fn new() -> Self {
+        CorsConfig::default()
+    }
+
+    pub fn with_origin(mut self, origin: &str) -> Self {
+        self.allowed_origins
+            .push(origin.trim_end_matches('/').to_string());
+        self
+    }
+
+    pub fn with_any_origin(self) -> Self {
+        self.with_origin("*")
+    }
+
+    pub fn with_method(mut self, method: &str) -> Self {
+        self.allowed_methods.push(method.to_ascii_uppercase());
+        self
+    }
+
+    pub fn with_header(mut self, header: &str) -> Self {
+        self.allowed_headers.push(header.to_string());
+        self
+    }
+
+    pub fn with_exposed_header(mut self, header: &str) -> Self {
+        self.exposed_headers.push(header.to_string());
+        self
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    // Credentialed answers go to origins listed by name only, * then allows
+    // none, so no site can read another user's studies
+    pub fn with_credentials(mut self) -> Self {
+        self.allow_credentials = true;
+        self
+    }
+
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        let origin = origin.trim_end_matches('/');
+        self.allowed_origins
+            .iter()
+            .any(|allowed| match allowed.as_str() {
+                "*" => !self.allow_credentials,
+                allowed => allowed.eq_ignore_ascii_case(origin),
+            })
+    }
+
+    pub fn allows_method(&self, method: &str) -> bool {
+        self.allowed_methods
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(method))
+    }
+
+    pub fn allows_header(&self, header: &str) -> bool {
+        self.allowed_headers
+            .iter()
+            .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(header.trim()))
+    }
+
+    // Answer to an OPTIONS preflight, sent with 204 No Content. None when
+    // the origin, method or any requested header is not allowed, which the
+    // browser reports as a CORS failure
+    pub fn preflight(
+        &self,
+        origin: &str,
+        method: &str,
+        request_headers: Option<&str>,
+    ) -> Option<Vec<(String, String)>> {
+        if !self.allows_origin(origin) || !self.allows_method(method) {
+            return None;
+        }
+        let requested: Vec<&str> = request_headers
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|header| !header.is_empty())
+            .collect();
+        if !requested.iter().all(|header| self.allows_header(header)) {
+            return None;
+        }
+
+        let mut headers = self.origin_headers(origin);
+        headers.push((
+            "Access-Control-Allow-Methods".to_string(),
+            self.allowed_methods.join(", "),
+        ));
+        if !requested.is_empty() {
+            headers.push((
+                "Access-Control-Allow-Headers".to_string(),
+                requested.join(", "),
+            ));
+        }
+        headers.push((
+            "Access-Control-Max-Age".to_string(),
+            self.max_age.as_secs().to_string(),
+        ));
+        Some(headers)
+    }
+
+    // Headers added to the actual response of a cross-origin request, none
+    // for origins not allowed
+    pub fn response_headers(&self, origin: &str) -> Vec<(String, String)> {
+        if !self.allows_origin(origin) {
+            return Vec::new();
+        }
+        let mut headers = self.origin_headers(origin);
+        if !self.exposed_headers.is_empty() {
+            headers.push((
+                "Access-Control-Expose-Headers".to_string(),
+                self.exposed_headers.join(", "),
+            ));
+        }
+        headers
+    }
+
+    // Credentialed requests may not be answered with *, so the origin, one
+    // listed by name, is echoed whenever it is not a plain wildcard answer.
+    // Vary keeps caches from serving one origin's answer to another
+    fn origin_headers(&self, origin: &str) -> Vec<(String, String)> {
+        let wildcard =
+            !self.allow_credentials && self.allowed_origins.iter().any(|allowed| allowed == "*");
+        let mut headers = vec![(
+            "Access-Control-Allow-Origin".to_string(),
+            if wildcard { "*" } else { origin }.to_string(),
+        )];
+        if !wildcard {
+            headers.push(("Vary".to_string(), "Origin".to_string()));
+        }
+        if self.allow_credentials {
+            headers.push((
+                "Access-Control-Allow-Credentials".to_string(),
+                "true".to_string(),
+            ));
+        }
+        headers
+    }
+}
+
+// Whether a request is a CORS preflight rather than an OPTIONS request of
+// its own
+pub fn is_preflight(method: &str, headers: &[(String, String)]) -> bool {
+    let has = |name: &str| {
+        headers
+            .iter()
+            .any(|(key, _)| key.eq_ignore_ascii_case(name))
+    };
+    method.eq_ignore_ascii_case("OPTIONS") && has("Origin") && has("Access-Control-Request-Method")
+}
@@ -0,0 +1,106 @@
+use tokio::runtime::{Builder, Runtime};
+
+use super::client::{DicomWebClient, StowClient, WadoClient};
+use crate::core::{
+    dataset::Dataset,
+    document::{Document, DocumentState},
+    error::{DicomError, DicomResult},
+    reader, transfer_syntax, writer,
+};
+
+// Instance on a DICOMweb server, retrieved with WADO-RS and written back with
+// STOW-RS. Requests block on a runtime of its own, so it is not to be used
+// from async code
+pub struct WadoDocument {
+    wado: WadoClient,
+    stow: StowClient,
+    runtime: Runtime,
+    study_uid: String,
+    series_uid: String,
+    instance_uid: String,
+    dataset: Option<Dataset>,
+    transfer_syntax: String,
+    state: DocumentState,
+    size: Option<usize>,
+}
+
+impl WadoDocument {
+    pub fn new(
+        client: DicomWebClient,
+        study_uid: &str,
+        series_uid: &str,
+        instance_uid: &str,
+    ) -> DicomResult<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+
+        Ok(WadoDocument {
+            wado: WadoClient::new(client.clone()),
+            stow: StowClient::new(client),
+            runtime,
+            study_uid: study_uid.to_string(),
+            series_uid: series_uid.to_string(),
+            instance_uid: instance_uid.to_string(),
+            dataset: None,
+            transfer_syntax: transfer_syntax::EXPLICIT_VR_LITTLE_ENDIAN.to_string(),
+            state: DocumentState::Open,
+            size: None,
+        })
+    }
+}
+
+impl Document for WadoDocument {
+    fn refresh(&mut self) {
+        self.dataset = None;
+    }
+
+    fn read(&mut self) -> DicomResult<&Dataset> {
+        if self.dataset.is_none() {
+            let data = self.runtime.block_on(self.wado.retrieve_instance(
+                &self.study_uid,
+                &self.series_uid,
+                &self.instance_uid,
+            ))?;
+            let file = reader::read_file(&data)?;
+            self.size = Some(data.len());
+            self.transfer_syntax = file.transfer_syntax;
+            self.dataset = Some(file.dataset);
+        }
+        Ok(self.dataset.as_ref().unwrap())
+    }
+
+    fn write(&mut self, dataset: &Dataset) -> DicomResult<()> {
+        if self.state == DocumentState::Closed {
+            return Err(DicomError::Io(std::io::Error::other("Document is closed")));
+        }
+        let data = writer::write_file(dataset, &self.transfer_syntax)?;
+        let size = data.len();
+        self.runtime
+            .block_on(self.stow.store(Some(&self.study_uid), vec![data]))?;
+
+        self.size = Some(size);
+        self.dataset = Some(dataset.clone());
+        self.state = DocumentState::Modified;
+        Ok(())
+    }
+
+    fn close(&mut self) -> DicomResult<()> {
+        self.state = DocumentState::Closed;
+        Ok(())
+    }
+
+    fn is_open(&self) -> bool {
+        self.state != DocumentState::Closed
+    }
+
+    fn is_modified(&self) -> bool {
+        self.state == DocumentState::Modified
+    }
+
+    fn get_name(&self) -> Option<&str> {
+        Some(&self.instance_uid)
+    }
+
+    fn get_size(&self) -> Option<usize> {
+        self.size
+    }
+}
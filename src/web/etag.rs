@@ -0,0 +1,96 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Mutex, PoisonError},
+};
+
+// Strong entity tag of a response body, quoted as the header carries it.
+// FNV-1a keeps it stable across builds and processes, so tags stay valid
+// while the same body is served again
+pub fn etag(body: &[u8]) -> String {
+    let hash = body.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("\"{:x}-{:016x}\"", body.len(), hash)
+}
+
+// Whether an If-None-Match header names the tag, in which case the server
+// answers 304 Not Modified without a body. Tags compare weakly, RFC 9110
+// 13.1.2
+pub fn if_none_match(header: &str, etag: &str) -> bool {
+    let header = header.trim();
+    if header == "*" {
+        return true;
+    }
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    header.split(',').any(|tag| opaque(tag) == etag)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedResponse {
+    pub etag: String,
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+// Bodies of earlier responses by URL with their entity tags, so clients
+// send If-None-Match and reuse the body on 304. Shared by the clones of a
+// client, least recently stored dropped first
+#[derive(Debug)]
+pub struct ResponseCache {
+    capacity: usize,
+    entries: Mutex<(HashMap<String, CachedResponse>, VecDeque<String>)>,
+}
+
+impl ResponseCache {
+    pub fn new(capacity: usize) -> Self {
+        ResponseCache {
+            capacity,
+            entries: Mutex::new((HashMap::new(), VecDeque::new())),
+        }
+    }
+
+    pub fn get(&self, url: &str) -> Option<CachedResponse> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.0.get(url).cloned()
+    }
+
+    pub fn insert(&self, url: &str, response: CachedResponse) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let (responses, order) = &mut *entries;
+        if responses.insert(url.to_string(), response).is_some() {
+            order.retain(|stored| stored != url);
+        }
+        order.push_back(url.to_string());
+        while responses.len() > self.capacity {
+            match order.pop_front() {
+                Some(oldest) => responses.remove(&oldest),
+                None => break,
+            };
+        }
+    }
+
+    pub fn remove(&self, url: &str) -> Option<CachedResponse> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.1.retain(|stored| stored != url);
+        entries.0.remove(url)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .0
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.0.clear();
+        entries.1.clear();
+    }
+}
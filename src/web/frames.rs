@@ -0,0 +1,208 @@
+use std::{collections::HashSet, fmt::Display};
+
+use super::multipart::Part;
+use crate::core::{
+    error::{DicomError, DicomResult},
+    transfer_syntax::{self, EXPLICIT_VR_LITTLE_ENDIAN},
+};
+#[cfg(any(feature = "image", feature = "default"))]
+use crate::{
+    core::{reader, reader::DicomFile, reader::PIXEL_DATA, tag::VisualRepresentation},
+    image::render::{frame_data, number_of_frames},
+};
+
+pub const OCTET_STREAM_MEDIA_TYPE: &str = "application/octet-stream";
+
+// Frames one request may name, ranges counted frame by frame, so a URL
+// cannot make the list take gigabytes
+pub const MAX_FRAMES: usize = 1 << 20;
+
+// Frame numbers of a WADO-RS frame retrieval, counted from 1 as in the URL,
+// e.g. /frames/1,3,5-10. Order is kept and repeats dropped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameList {
+    pub frames: Vec<u32>,
+}
+
+impl FrameList {
+    pub fn new(frames: &[u32]) -> DicomResult<Self> {
+        let mut list = FrameList { frames: Vec::new() };
+        for frame in frames {
+            list.push(*frame)?;
+        }
+        list.dedup();
+        Ok(list)
+    }
+
+    pub fn parse(text: &str) -> DicomResult<Self> {
+        let invalid = |reason: &str| {
+            DicomError::InvalidValue(format!("Frame list {}: {}", text.trim(), reason))
+        };
+        let number = |value: &str| {
+            value
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| invalid(&format!("{} is not a frame number", value.trim())))
+        };
+
+        let mut list = FrameList { frames: Vec::new() };
+        for part in text.split(',') {
+            match part.split_once('-') {
+                Some((first, last)) => {
+                    let (first, last) = (number(first)?, number(last)?);
+                    if first > last {
+                        return Err(invalid(&format!("{} runs backwards", part.trim())));
+                    }
+                    if (last - first) as usize >= MAX_FRAMES - list.frames.len() {
+                        return Err(invalid(&format!("more than {} frames", MAX_FRAMES)));
+                    }
+                    for frame in first..=last {
+                        list.push(frame)?;
+                    }
+                }
+                None => list.push(number(part)?)?,
+            }
+        }
+        if list.frames.is_empty() {
+            return Err(invalid("no frames"));
+        }
+        list.dedup();
+        Ok(list)
+    }
+
+    fn push(&mut self, frame: u32) -> DicomResult<()> {
+        if frame == 0 {
+            return Err(DicomError::InvalidValue(
+                "Frame numbers start at 1".to_string(),
+            ));
+        }
+        if self.frames.len() >= MAX_FRAMES {
+            return Err(DicomError::InvalidValue(format!(
+                "More than {} frames",
+                MAX_FRAMES
+            )));
+        }
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    fn dedup(&mut self) {
+        let mut seen = HashSet::new();
+        self.frames.retain(|frame| seen.insert(*frame));
+    }
+
+    // Frames counted from 0, as render::frame_data takes them
+    pub fn indices(&self) -> Vec<usize> {
+        self.frames
+            .iter()
+            .map(|frame| *frame as usize - 1)
+            .collect()
+    }
+}
+
+// Consecutive frames are written back as ranges
+impl Display for FrameList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        let mut index = 0;
+        while index < self.frames.len() {
+            let first = self.frames[index];
+            let mut last = first;
+            while last
+                .checked_add(1)
+                .is_some_and(|next| self.frames.get(index + 1) == Some(&next))
+            {
+                last += 1;
+                index += 1;
+            }
+            parts.push(match last - first {
+                0 => first.to_string(),
+                1 => format!("{},{}", first, last),
+                _ => format!("{}-{}", first, last),
+            });
+            index += 1;
+        }
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+// Media type of frames of a transfer syntax, PS3.18 8.7.3.3.2. Native
+// frames go out as octet streams
+pub fn frame_media_type(transfer_syntax: &str) -> &'static str {
+    match transfer_syntax {
+        transfer_syntax::JPEG_BASELINE
+        | transfer_syntax::JPEG_EXTENDED
+        | transfer_syntax::JPEG_LOSSLESS
+        | transfer_syntax::JPEG_LOSSLESS_SV1 => "image/jpeg",
+        transfer_syntax::JPEG_LS_LOSSLESS | transfer_syntax::JPEG_LS_NEAR_LOSSLESS => "image/jls",
+        transfer_syntax::JPEG_2000_LOSSLESS | transfer_syntax::JPEG_2000 => "image/jp2",
+        transfer_syntax::RLE_LOSSLESS => "image/dicom-rle",
+        _ => OCTET_STREAM_MEDIA_TYPE,
+    }
+}
+
+// Content type of one frame part, naming the transfer syntax it is in
+pub fn frame_content_type(transfer_syntax: &str) -> String {
+    let transfer_syntax = if transfer_syntax::is_encapsulated(transfer_syntax) {
+        transfer_syntax
+    } else {
+        EXPLICIT_VR_LITTLE_ENDIAN
+    };
+    format!(
+        "{}; transfer-syntax={}",
+        frame_media_type(transfer_syntax),
+        transfer_syntax
+    )
+}
+
+// The requested frames of an instance as the parts of a frames response.
+// Compressed frames are cut out of the encapsulated pixel data as they are
+// and native ones sliced, so frames not asked for are never decoded or
+// copied
+#[cfg(any(feature = "image", feature = "default"))]
+pub fn frame_parts(file: &DicomFile, frames: &FrameList) -> DicomResult<Vec<Part>> {
+    let count = number_of_frames(&file.dataset);
+    if let Some(frame) = frames.frames.iter().find(|frame| **frame as usize > count) {
+        return Err(DicomError::InvalidValue(format!(
+            "Frame {} out of {}",
+            frame, count
+        )));
+    }
+
+    let content_type = frame_content_type(&file.transfer_syntax);
+    if !transfer_syntax::is_encapsulated(&file.transfer_syntax) {
+        return frames
+            .indices()
+            .into_iter()
+            .map(|frame| Ok(Part::new(&content_type, frame_data(file, frame)?)))
+            .collect();
+    }
+
+    let Some(VisualRepresentation::OB(data)) = file.dataset.value(PIXEL_DATA) else {
+        return Err(DicomError::InvalidVR(
+            "Encapsulated pixel data has to be OB".to_string(),
+        ));
+    };
+    // Past the basic offset table, one fragment per frame or a single frame
+    // over several fragments
+    let fragments = reader::fragments(&data)?;
+    let fragments = fragments.get(1..).unwrap_or_default();
+    frames
+        .indices()
+        .into_iter()
+        .map(|frame| {
+            let body = if fragments.len() == count {
+                fragments[frame].to_vec()
+            } else if count == 1 {
+                fragments.concat()
+            } else {
+                return Err(DicomError::InvalidValue(format!(
+                    "{} fragments for {} frames",
+                    fragments.len(),
+                    count
+                )));
+            };
+            Ok(Part::new(&content_type, body))
+        })
+        .collect()
+}
@@ -0,0 +1,406 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use rsa::{BigUint, Pkcs1v15Sign, RsaPublicKey};
+use serde_json::Value;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use tokio::sync::Mutex;
+
+use crate::core::error::{DicomError, DicomResult};
+
+// Unknown key IDs refetch the key set at most this often, so tokens with
+// made up IDs cannot flood the identity provider
+const REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+
+// DICOMweb services a token can be allowed to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    // QIDO-RS
+    Query,
+    // WADO-RS
+    Retrieve,
+    // STOW-RS
+    Store,
+}
+
+impl Operation {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.to_ascii_lowercase().as_str() {
+            "query" | "qido" => Some(Operation::Query),
+            "retrieve" | "wado" => Some(Operation::Retrieve),
+            "store" | "stow" => Some(Operation::Store),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Query => "query",
+            Operation::Retrieve => "retrieve",
+            Operation::Store => "store",
+        }
+    }
+
+    // Operation of a request: POST stores, GET on a collection of studies,
+    // series or instances searches and any other GET retrieves
+    pub fn of(method: &str, path: &str) -> Option<Self> {
+        let path = path.split('?').next().unwrap_or_default();
+        let last = path.trim_end_matches('/').rsplit('/').next()?;
+        match method.to_ascii_uppercase().as_str() {
+            "POST" => Some(Operation::Store),
+            "GET" | "HEAD" if matches!(last, "studies" | "series" | "instances") => {
+                Some(Operation::Query)
+            }
+            "GET" | "HEAD" => Some(Operation::Retrieve),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Claims {
+    pub subject: Option<String>,
+    pub issuer: Option<String>,
+    pub audience: Vec<String>,
+    // From a space separated scope claim or an scp array
+    pub scopes: Vec<String>,
+    // Seconds since the Unix epoch
+    pub expires_at: Option<u64>,
+    pub not_before: Option<u64>,
+    // Every claim of the token
+    pub raw: Value,
+}
+
+impl Claims {
+    pub fn from_json(raw: Value) -> Self {
+        let strings = |value: &Value| match value {
+            Value::String(value) => vec![value.clone()],
+            Value::Array(values) => values
+                .iter()
+                .filter_map(|value| value.as_str().map(String::from))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let scopes = match &raw["scope"] {
+            Value::String(scope) => scope.split_whitespace().map(String::from).collect(),
+            _ => strings(&raw["scp"]),
+        };
+        Claims {
+            subject: raw["sub"].as_str().map(String::from),
+            issuer: raw["iss"].as_str().map(String::from),
+            audience: strings(&raw["aud"]),
+            scopes,
+            expires_at: raw["exp"].as_u64(),
+            not_before: raw["nbf"].as_u64(),
+            raw,
+        }
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|granted| granted == scope)
+    }
+}
+
+#[derive(Debug)]
+struct CachedKeys {
+    keys: HashMap<String, RsaPublicKey>,
+    fetched_at: Instant,
+}
+
+// Checks bearer tokens of an identity provider in front of QIDO, WADO and
+// STOW endpoints. RS256, RS384 and RS512 tokens are checked against the
+// provider's JWKS, fetched and kept for the TTL, HS256 ones against a
+// shared secret
+#[derive(Debug)]
+pub struct JwtValidator {
+    issuer: Option<String>,
+    audience: Option<String>,
+    jwks_url: Option<String>,
+    secret: Option<Vec<u8>>,
+    leeway: Duration,
+    // Tokens without exp are refused unless turned off
+    require_expiry: bool,
+    jwks_ttl: Duration,
+    // Scopes and the operations each allows, none lets any valid token do
+    // everything
+    scopes: Vec<(String, Vec<Operation>)>,
+    http: Client,
+    keys: Mutex<Option<CachedKeys>>,
+}
+
+impl Default for JwtValidator {
+    fn default() -> Self {
+        JwtValidator {
+            issuer: None,
+            audience: None,
+            jwks_url: None,
+            secret: None,
+            leeway: Duration::from_secs(60),
+            require_expiry: true,
+            jwks_ttl: Duration::from_secs(3600),
+            scopes: Vec::new(),
+            http: Client::new(),
+            keys: Mutex::new(None),
+        }
+    }
+}
+
+impl JwtValidator {
+    pub fn new() -> Self {
+        JwtValidator::default()
+    }
+
+    pub fn with_issuer(mut self, issuer: &str) -> Self {
+        self.issuer = Some(issuer.to_string());
+        self
+    }
+
+    pub fn with_audience(mut self, audience: &str) -> Self {
+        self.audience = Some(audience.to_string());
+        self
+    }
+
+    pub fn with_jwks_url(mut self, url: &str) -> Self {
+        self.jwks_url = Some(url.to_string());
+        self
+    }
+
+    pub fn with_secret(mut self, secret: &[u8]) -> Self {
+        self.secret = Some(secret.to_vec());
+        self
+    }
+
+    // Clock skew allowed on exp and nbf
+    pub fn with_leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    // Whether tokens need an exp claim, off for providers issuing tokens
+    // that never expire
+    pub fn with_expiry_required(mut self, required: bool) -> Self {
+        self.require_expiry = required;
+        self
+    }
+
+    pub fn with_jwks_ttl(mut self, ttl: Duration) -> Self {
+        self.jwks_ttl = ttl;
+        self
+    }
+
+    pub fn with_scope(mut self, scope: &str, operations: &[Operation]) -> Self {
+        self.scopes.push((scope.to_string(), operations.to_vec()));
+        self
+    }
+
+    pub fn with_http_client(mut self, http: Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    // Claims of a token whose signature, issuer, audience and validity
+    // period check out
+    pub async fn validate(&self, token: &str) -> DicomResult<Claims> {
+        let invalid = |reason: &str| DicomError::InvalidValue(format!("JWT {}", reason));
+        let token = token.trim();
+        let Some((signed, signature)) = token.rsplit_once('.') else {
+            return Err(invalid("is not three dot separated parts"));
+        };
+        let Some((header, payload)) = signed
+            .split_once('.')
+            .filter(|(_, payload)| !payload.contains('.'))
+        else {
+            return Err(invalid("is not three dot separated parts"));
+        };
+        let decode = |part: &str| {
+            URL_SAFE_NO_PAD
+                .decode(part)
+                .map_err(|_| invalid("part is not base64url"))
+        };
+        let header: Value =
+            serde_json::from_slice(&decode(header)?).map_err(|_| invalid("header is not JSON"))?;
+        let raw: Value = serde_json::from_slice(&decode(payload)?)
+            .map_err(|_| invalid("payload is not JSON"))?;
+        let signature = decode(signature)?;
+
+        let signed = signed.as_bytes();
+        let algorithm = header["alg"].as_str().unwrap_or_default();
+        match algorithm {
+            "HS256" => {
+                let secret = self
+                    .secret
+                    .as_ref()
+                    .ok_or_else(|| invalid("signed with HS256 but no secret is set"))?;
+                let mut mac = Hmac::<Sha256>::new_from_slice(secret)
+                    .map_err(|error| DicomError::Error(error.to_string()))?;
+                mac.update(signed);
+                mac.verify_slice(&signature)
+                    .map_err(|_| invalid("signature does not match"))?;
+            }
+            "RS256" | "RS384" | "RS512" => {
+                let key = self.key(header["kid"].as_str()).await?;
+                let verified = match algorithm {
+                    "RS256" => key.verify(
+                        Pkcs1v15Sign::new::<Sha256>(),
+                        &Sha256::digest(signed),
+                        &signature,
+                    ),
+                    "RS384" => key.verify(
+                        Pkcs1v15Sign::new::<Sha384>(),
+                        &Sha384::digest(signed),
+                        &signature,
+                    ),
+                    _ => key.verify(
+                        Pkcs1v15Sign::new::<Sha512>(),
+                        &Sha512::digest(signed),
+                        &signature,
+                    ),
+                };
+                verified.map_err(|_| invalid("signature does not match"))?;
+            }
+            // Including none, which would accept anything
+            algorithm => return Err(invalid(&format!("algorithm {} is not accepted", algorithm))),
+        }
+
+        let claims = Claims::from_json(raw);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs())
+            .unwrap_or_default();
+        let leeway = self.leeway.as_secs();
+        match claims.expires_at {
+            None if self.require_expiry => return Err(invalid("has no expiry")),
+            Some(exp) if exp.saturating_add(leeway) <= now => return Err(invalid("has expired")),
+            _ => {}
+        }
+        if claims
+            .not_before
+            .is_some_and(|nbf| nbf > now.saturating_add(leeway))
+        {
+            return Err(invalid("is not valid yet"));
+        }
+        if let Some(issuer) = &self.issuer {
+            if claims.issuer.as_ref() != Some(issuer) {
+                return Err(invalid("is from another issuer"));
+            }
+        }
+        if let Some(audience) = &self.audience {
+            if !claims.audience.contains(audience) {
+                return Err(invalid("is for another audience"));
+            }
+        }
+        Ok(claims)
+    }
+
+    pub fn allows(&self, claims: &Claims, operation: Operation) -> bool {
+        self.scopes.is_empty()
+            || self.scopes.iter().any(|(scope, operations)| {
+                operations.contains(&operation) && claims.has_scope(scope)
+            })
+    }
+
+    // What a server runs before a handler: 401 for a missing or invalid
+    // token, 403 for a valid one without a scope for the operation
+    pub async fn authorize(
+        &self,
+        authorization: Option<&str>,
+        operation: Operation,
+    ) -> DicomResult<Claims> {
+        let token = authorization
+            .map(str::trim)
+            .and_then(|authorization| authorization.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Bearer"))
+            .map(|(_, token)| token)
+            .ok_or(DicomError::HttpStatus(401))?;
+        let claims = self
+            .validate(token)
+            .await
+            .map_err(|_| DicomError::HttpStatus(401))?;
+        if !self.allows(&claims, operation) {
+            return Err(DicomError::HttpStatus(403));
+        }
+        Ok(claims)
+    }
+
+    // Key of a token, refetching the set once its TTL passed or when it
+    // does not know the key ID. Tokens without an ID take the only key
+    async fn key(&self, kid: Option<&str>) -> DicomResult<RsaPublicKey> {
+        let mut cached = self.keys.lock().await;
+        let find = |cached: &Option<CachedKeys>| {
+            let keys = &cached.as_ref()?.keys;
+            match kid {
+                Some(kid) => keys.get(kid).cloned(),
+                None if keys.len() == 1 => keys.values().next().cloned(),
+                None => keys.get("").cloned(),
+            }
+        };
+
+        let age = cached.as_ref().map(|keys| keys.fetched_at.elapsed());
+        let expired = age.is_none_or(|age| age > self.jwks_ttl);
+        if !expired {
+            if let Some(key) = find(&cached) {
+                return Ok(key);
+            }
+        }
+        if expired || age.is_some_and(|age| age > REFETCH_INTERVAL) {
+            *cached = Some(CachedKeys {
+                keys: self.fetch_keys().await?,
+                fetched_at: Instant::now(),
+            });
+        }
+        find(&cached).ok_or_else(|| {
+            DicomError::InvalidValue(format!("JWT key {} unknown", kid.unwrap_or_default()))
+        })
+    }
+
+    async fn fetch_keys(&self) -> DicomResult<HashMap<String, RsaPublicKey>> {
+        let url = self
+            .jwks_url
+            .as_ref()
+            .ok_or_else(|| DicomError::InvalidValue("No JWKS URL set".to_string()))?;
+        let response = self.http.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(DicomError::HttpStatus(response.status().as_u16()));
+        }
+        let body: Value = response.json().await?;
+        Ok(parse_jwks(&body))
+    }
+}
+
+// RSA signing keys of a JWK set by key ID, others skipped
+pub fn parse_jwks(jwks: &Value) -> HashMap<String, RsaPublicKey> {
+    let mut keys = HashMap::new();
+    for key in jwks["keys"].as_array().into_iter().flatten() {
+        if key["kty"].as_str() != Some("RSA")
+            || key["use"].as_str().is_some_and(|usage| usage != "sig")
+        {
+            continue;
+        }
+        let component = |name: &str| {
+            key[name]
+                .as_str()
+                .and_then(|value| URL_SAFE_NO_PAD.decode(value).ok())
+                .map(|bytes| BigUint::from_bytes_be(&bytes))
+        };
+        let (Some(n), Some(e)) = (component("n"), component("e")) else {
+            continue;
+        };
+        if let Ok(public) = RsaPublicKey::new(n, e) {
+            let kid = key["kid"].as_str().unwrap_or_default();
+            keys.insert(kid.to_string(), public);
+        }
+    }
+    keys
+}
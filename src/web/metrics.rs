@@ -0,0 +1,171 @@
+use std::{
+    fmt,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use crate::core::{
+    error::{DicomError, DicomResult},
+    metrics::{self, Metrics},
+};
+use crate::net::shutdown::{self, Shutdown};
+
+// Longest request head we read before giving up on a scrape
+const MAX_REQUEST_HEAD: usize = 8192;
+
+pub const LIVENESS_PATH: &str = "/healthz";
+pub const READINESS_PATH: &str = "/readyz";
+
+// Check behind /readyz, giving the HTTP status code and a text body
+#[derive(Clone)]
+pub struct Readiness(Arc<dyn Fn() -> (u16, String) + Send + Sync>);
+
+impl fmt::Debug for Readiness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Readiness")
+    }
+}
+
+// Serves the crate metrics to Prometheus, GET on the metrics path answers
+// with the text exposition format. /healthz answers 200 while the process
+// runs and /readyz with the readiness check, 200 without one. Anything else
+// gets 404
+#[derive(Debug, Clone)]
+pub struct MetricsEndpoint {
+    metrics: &'static Metrics,
+    path: String,
+    timeout: Duration,
+    shutdown: Shutdown,
+    readiness: Option<Readiness>,
+}
+
+impl Default for MetricsEndpoint {
+    fn default() -> Self {
+        MetricsEndpoint {
+            metrics: metrics::global(),
+            path: "/metrics".to_string(),
+            timeout: Duration::from_secs(10),
+            shutdown: Shutdown::new(),
+            readiness: None,
+        }
+    }
+}
+
+impl MetricsEndpoint {
+    pub fn new() -> Self {
+        MetricsEndpoint::default()
+    }
+
+    pub fn with_metrics(mut self, metrics: &'static Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn with_path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Shared with the services it reports on, so one signal stops them all
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    // Run on each /readyz request, e.g. a SelftestReport of the stores and
+    // peers the service depends on
+    pub fn with_readiness<F>(mut self, check: F) -> Self
+    where
+        F: Fn() -> (u16, String) + Send + Sync + 'static,
+    {
+        self.readiness = Some(Readiness(Arc::new(check)));
+        self
+    }
+
+    // Serves each scrape on its own thread until the shutdown is triggered,
+    // failed scrapes are dropped
+    pub fn listen<A: ToSocketAddrs>(&self, address: A) -> DicomResult<()> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        while let Some(stream) = shutdown::accept(&listener, &self.shutdown)? {
+            let endpoint = self.clone();
+            thread::spawn(move || endpoint.serve(stream));
+        }
+
+        Ok(())
+    }
+
+    pub fn serve(&self, mut stream: TcpStream) -> DicomResult<()> {
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        let mut head = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = stream.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            head.extend_from_slice(&buffer[..read]);
+            if head.len() > MAX_REQUEST_HEAD {
+                return Err(DicomError::InvalidValue(
+                    "Request head too long".to_string(),
+                ));
+            }
+        }
+
+        let head = String::from_utf8_lossy(&head);
+        let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+        let method = request_line.next().unwrap_or_default();
+        let target = request_line.next().unwrap_or_default();
+        let path = target.split('?').next().unwrap_or_default();
+
+        let text = "text/plain; charset=utf-8";
+        let known = path == self.path || path == LIVENESS_PATH || path == READINESS_PATH;
+        let (status, content_type, body) = match method {
+            "GET" | "HEAD" if path == self.path => (
+                200,
+                "text/plain; version=0.0.4; charset=utf-8",
+                self.metrics.render(),
+            ),
+            "GET" | "HEAD" if path == LIVENESS_PATH => (200, text, "ok\n".to_string()),
+            "GET" | "HEAD" if path == READINESS_PATH => match &self.readiness {
+                Some(readiness) => {
+                    let (status, body) = (readiness.0)();
+                    (status, text, body)
+                }
+                None => (200, text, "ok\n".to_string()),
+            },
+            _ if known => (405, text, String::new()),
+            _ => (404, text, String::new()),
+        };
+        let status = match status {
+            200 => "200 OK".to_string(),
+            404 => "404 Not Found".to_string(),
+            405 => "405 Method Not Allowed".to_string(),
+            503 => "503 Service Unavailable".to_string(),
+            code => code.to_string(),
+        };
+
+        let mut response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            content_type,
+            body.len()
+        );
+        if method != "HEAD" {
+            response.push_str(&body);
+        }
+        stream.write_all(response.as_bytes())?;
+        stream.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+pub mod auth;
+pub mod bulkdata;
+pub mod client;
+pub mod config;
+pub mod cors;
+pub mod document;
+pub mod etag;
+pub mod frames;
+#[cfg(any(feature = "jwt", feature = "default"))]
+pub mod jwt;
+pub mod metrics;
+pub mod multipart;
+#[cfg(any(
+    all(feature = "fs", feature = "compress", feature = "images"),
+    feature = "default"
+))]
+pub mod negotiation;
+pub mod retry;
+#[cfg(any(feature = "storage", feature = "default"))]
+pub mod tenant;
@@ -0,0 +1,464 @@
+use std::{
+    collections::VecDeque,
+    io,
+    path::PathBuf,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures_util::stream::{self, Stream};
+use tokio::{fs::File, io::AsyncReadExt};
+
+use crate::core::error::{DicomError, DicomResult};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PartProgress {
+    pub part: usize,
+    pub bytes: u64,
+    pub total: Option<u64>,
+}
+
+pub type ProgressCallback = Arc<dyn Fn(PartProgress) + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub struct Part {
+    pub content_type: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Part {
+    pub fn new(content_type: &str, body: Vec<u8>) -> Self {
+        Part {
+            content_type: content_type.to_string(),
+            headers: vec![],
+            body,
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub fn boundary() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+
+    format!("DICOM-{:032x}", nanos)
+}
+
+// Pulls the boundary parameter out of a multipart Content-Type header
+pub fn boundary_from_content_type(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|parameter| {
+        let (key, value) = parameter.split_once('=')?;
+        if key.trim().eq_ignore_ascii_case("boundary") {
+            Some(value.trim().trim_matches('"').to_string())
+        } else {
+            None
+        }
+    })
+}
+
+pub fn content_type(media_type: &str, boundary: &str) -> String {
+    format!(
+        "multipart/related; type=\"{}\"; boundary={}",
+        media_type, boundary
+    )
+}
+
+pub fn encode(parts: &[Part], boundary: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+
+    for part in parts {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        body.extend_from_slice(format!("Content-Type: {}\r\n", part.content_type).as_bytes());
+        for (name, value) in &part.headers {
+            body.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        body.extend_from_slice(b"\r\n");
+        body.extend_from_slice(&part.body);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    body
+}
+
+pub fn decode(body: &[u8], boundary: &str) -> DicomResult<Vec<Part>> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = Vec::new();
+
+    let mut position = find(body, &delimiter, 0)
+        .ok_or_else(|| DicomError::InvalidValue("Multipart body without boundary".to_string()))?;
+
+    loop {
+        position += delimiter.len();
+        if body[position..].starts_with(b"--") {
+            break;
+        }
+        position += skip_line_break(&body[position..]);
+
+        let header_end = find(body, b"\r\n\r\n", position)
+            .ok_or_else(|| DicomError::InvalidValue("Unterminated part headers".to_string()))?;
+        let headers = parse_headers(&body[position..header_end]);
+        position = header_end + 4;
+
+        let next = find(body, &delimiter, position)
+            .ok_or_else(|| DicomError::InvalidValue("Unterminated multipart body".to_string()))?;
+        let end = if next >= 2 && &body[next - 2..next] == b"\r\n" {
+            next - 2
+        } else {
+            next
+        };
+
+        let content_type = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Content-Type"))
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default();
+        parts.push(Part {
+            content_type,
+            headers,
+            body: body[position..end].to_vec(),
+        });
+
+        position = next;
+    }
+
+    Ok(parts)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DecoderState {
+    Preamble,
+    Delimiter,
+    Headers,
+    Body,
+    Done,
+}
+
+// Incremental counterpart of `decode`: only the part currently being received is kept in memory
+pub struct MultipartDecoder {
+    delimiter: Vec<u8>,
+    buffer: Vec<u8>,
+    state: DecoderState,
+    headers: Vec<(String, String)>,
+    scanned: usize,
+    parts: usize,
+    progress: Option<ProgressCallback>,
+}
+
+impl MultipartDecoder {
+    pub fn new(boundary: &str) -> Self {
+        MultipartDecoder {
+            delimiter: format!("--{}", boundary).into_bytes(),
+            buffer: Vec::new(),
+            state: DecoderState::Preamble,
+            headers: vec![],
+            scanned: 0,
+            parts: 0,
+            progress: None,
+        }
+    }
+
+    pub fn with_progress(mut self, progress: ProgressCallback) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state == DecoderState::Done
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> DicomResult<Vec<Part>> {
+        self.buffer.extend_from_slice(chunk);
+        let mut parts = Vec::new();
+
+        loop {
+            match self.state {
+                DecoderState::Preamble => match find(&self.buffer, &self.delimiter, 0) {
+                    Some(position) => {
+                        self.buffer.drain(..position + self.delimiter.len());
+                        self.state = DecoderState::Delimiter;
+                    }
+                    None => {
+                        let keep = self.buffer.len().min(self.delimiter.len());
+                        self.buffer.drain(..self.buffer.len() - keep);
+                        break;
+                    }
+                },
+                DecoderState::Delimiter => {
+                    if self.buffer.len() < 2 {
+                        break;
+                    }
+                    if self.buffer.starts_with(b"--") {
+                        self.buffer.clear();
+                        self.state = DecoderState::Done;
+                        break;
+                    }
+                    self.buffer.drain(..skip_line_break(&self.buffer));
+                    self.state = DecoderState::Headers;
+                }
+                DecoderState::Headers => match find(&self.buffer, b"\r\n\r\n", 0) {
+                    Some(position) => {
+                        self.headers = parse_headers(&self.buffer[..position]);
+                        self.buffer.drain(..position + 4);
+                        self.scanned = 0;
+                        self.state = DecoderState::Body;
+                    }
+                    None => break,
+                },
+                DecoderState::Body => {
+                    let mut needle = b"\r\n".to_vec();
+                    needle.extend_from_slice(&self.delimiter);
+
+                    // Only rescan the tail that could still hold a split delimiter
+                    let from = self.scanned.saturating_sub(needle.len());
+                    match find(&self.buffer, &needle, from) {
+                        Some(position) => {
+                            let body: Vec<u8> = self.buffer.drain(..position).collect();
+                            self.buffer.drain(..needle.len());
+
+                            let headers = std::mem::take(&mut self.headers);
+                            let content_type = headers
+                                .iter()
+                                .find(|(name, _)| name.eq_ignore_ascii_case("Content-Type"))
+                                .map(|(_, value)| value.clone())
+                                .unwrap_or_default();
+
+                            self.report(body.len() as u64);
+                            parts.push(Part {
+                                content_type,
+                                headers,
+                                body,
+                            });
+                            self.parts += 1;
+                            self.state = DecoderState::Delimiter;
+                        }
+                        None => {
+                            self.scanned = self.buffer.len();
+                            self.report(self.buffer.len() as u64);
+                            break;
+                        }
+                    }
+                }
+                DecoderState::Done => break,
+            }
+        }
+
+        Ok(parts)
+    }
+
+    pub fn finish(self) -> DicomResult<()> {
+        match self.state {
+            DecoderState::Done => Ok(()),
+            _ => Err(DicomError::InvalidValue(
+                "Multipart body ended before the closing boundary".to_string(),
+            )),
+        }
+    }
+
+    fn report(&self, bytes: u64) {
+        if let Some(progress) = &self.progress {
+            progress(PartProgress {
+                part: self.parts,
+                bytes,
+                total: None,
+            });
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PartSource {
+    Memory { content_type: String, data: Vec<u8> },
+    File { content_type: String, path: PathBuf },
+}
+
+impl PartSource {
+    pub fn memory(content_type: &str, data: Vec<u8>) -> Self {
+        PartSource::Memory {
+            content_type: content_type.to_string(),
+            data,
+        }
+    }
+
+    pub fn file(content_type: &str, path: PathBuf) -> Self {
+        PartSource::File {
+            content_type: content_type.to_string(),
+            path,
+        }
+    }
+
+    pub fn content_type(&self) -> &str {
+        match self {
+            PartSource::Memory { content_type, .. } | PartSource::File { content_type, .. } => {
+                content_type
+            }
+        }
+    }
+}
+
+enum BodyReader {
+    Memory { data: Vec<u8>, offset: usize },
+    File(File),
+}
+
+impl BodyReader {
+    async fn open(source: PartSource) -> io::Result<(Self, Option<u64>)> {
+        match source {
+            PartSource::Memory { data, .. } => {
+                let total = data.len() as u64;
+                Ok((BodyReader::Memory { data, offset: 0 }, Some(total)))
+            }
+            PartSource::File { path, .. } => {
+                let file = File::open(&path).await?;
+                let total = file.metadata().await.ok().map(|metadata| metadata.len());
+                Ok((BodyReader::File(file), total))
+            }
+        }
+    }
+
+    async fn read_chunk(&mut self) -> io::Result<Vec<u8>> {
+        match self {
+            BodyReader::Memory { data, offset } => {
+                let end = (*offset + CHUNK_SIZE).min(data.len());
+                let chunk = data[*offset..end].to_vec();
+                *offset = end;
+                Ok(chunk)
+            }
+            BodyReader::File(file) => {
+                let mut chunk = vec![0; CHUNK_SIZE];
+                let read = file.read(&mut chunk).await?;
+                chunk.truncate(read);
+                Ok(chunk)
+            }
+        }
+    }
+}
+
+struct EncoderState {
+    sources: VecDeque<PartSource>,
+    boundary: String,
+    body: Option<BodyReader>,
+    next_part: usize,
+    written: u64,
+    total: Option<u64>,
+    progress: Option<ProgressCallback>,
+    closed: bool,
+}
+
+impl EncoderState {
+    async fn next_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        if let Some(body) = self.body.as_mut() {
+            let chunk = body.read_chunk().await?;
+            if !chunk.is_empty() {
+                self.written += chunk.len() as u64;
+                if let Some(progress) = &self.progress {
+                    progress(PartProgress {
+                        part: self.next_part - 1,
+                        bytes: self.written,
+                        total: self.total,
+                    });
+                }
+                return Ok(Some(chunk));
+            }
+
+            self.body = None;
+            return Ok(Some(b"\r\n".to_vec()));
+        }
+
+        match self.sources.pop_front() {
+            Some(source) => {
+                let header = format!(
+                    "--{}\r\nContent-Type: {}\r\n\r\n",
+                    self.boundary,
+                    source.content_type()
+                );
+                let (body, total) = BodyReader::open(source).await?;
+
+                self.body = Some(body);
+                self.total = total;
+                self.written = 0;
+                self.next_part += 1;
+
+                Ok(Some(header.into_bytes()))
+            }
+            None if !self.closed => {
+                self.closed = true;
+                Ok(Some(format!("--{}--\r\n", self.boundary).into_bytes()))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+// Streams the parts one chunk at a time, reading files lazily as the upload progresses
+pub fn encode_stream(
+    sources: Vec<PartSource>,
+    boundary: &str,
+    progress: Option<ProgressCallback>,
+) -> impl Stream<Item = io::Result<Vec<u8>>> + Send + 'static {
+    let state = EncoderState {
+        sources: sources.into(),
+        boundary: boundary.to_string(),
+        body: None,
+        next_part: 0,
+        written: 0,
+        total: None,
+        progress,
+        closed: false,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        match state.next_chunk().await {
+            Ok(Some(chunk)) => Some((Ok(chunk), state)),
+            Ok(None) => None,
+            Err(error) => {
+                state.sources.clear();
+                state.body = None;
+                state.closed = true;
+                Some((Err(error), state))
+            }
+        }
+    })
+}
+
+pub(crate) fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    if from > haystack.len() {
+        return None;
+    }
+
+    haystack[from..]
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|position| position + from)
+}
+
+pub(crate) fn parse_headers(block: &[u8]) -> Vec<(String, String)> {
+    String::from_utf8_lossy(block)
+        .split("\r\n")
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+fn skip_line_break(data: &[u8]) -> usize {
+    if data.starts_with(b"\r\n") {
+        2
+    } else if data.starts_with(b"\n") {
+        1
+    } else {
+        0
+    }
+}
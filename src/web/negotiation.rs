@@ -0,0 +1,239 @@
+use std::fmt::Display;
+
+use serde_json::Value;
+
+use super::client::{DICOM_JSON_MEDIA_TYPE, DICOM_MEDIA_TYPE};
+use crate::{
+    core::{
+        error::{DicomError, DicomResult},
+        json,
+        reader::{DicomFile, PIXEL_DATA},
+        transfer_syntax::{self, EXPLICIT_VR_LITTLE_ENDIAN},
+        writer,
+    },
+    image::{render, thumbnail},
+    mods::transcode,
+    plugins::codec::CodecRegistry,
+};
+
+pub const JPEG_MEDIA_TYPE: &str = "image/jpeg";
+
+// One entry of an Accept header, e.g.
+// multipart/related; type="application/dicom"; transfer-syntax=1.2.840.10008.1.2.4.90
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaRange {
+    // Lowercase, without parameters
+    pub media_type: String,
+    // Names lowercase, values unquoted
+    pub parameters: Vec<(String, String)>,
+    pub quality: f32,
+}
+
+impl MediaRange {
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.split(';');
+        let media_type = parts.next()?.trim().to_ascii_lowercase();
+        if !media_type.contains('/') {
+            return None;
+        }
+        let mut range = MediaRange {
+            media_type,
+            parameters: Vec::new(),
+            quality: 1.0,
+        };
+        for parameter in parts {
+            let Some((name, value)) = parameter.split_once('=') else {
+                continue;
+            };
+            let name = name.trim().to_ascii_lowercase();
+            let value = value.trim().trim_matches('"').to_string();
+            if name == "q" {
+                range.quality = value.parse().unwrap_or(0.0);
+            } else {
+                range.parameters.push((name, value));
+            }
+        }
+        Some(range)
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // The media type of each part for multipart/related, as its type
+    // parameter names it
+    pub fn part_type(&self) -> String {
+        match self.parameter("type") {
+            Some(part_type) if self.media_type == "multipart/related" => {
+                part_type.to_ascii_lowercase()
+            }
+            _ => self.media_type.clone(),
+        }
+    }
+
+    // Wildcards of the range match any type or subtype
+    pub fn matches(&self, media_type: &str) -> bool {
+        let part_type = self.part_type();
+        if part_type == "*/*" || part_type == media_type {
+            return true;
+        }
+        match part_type.strip_suffix("/*") {
+            Some(main) => media_type.split('/').next() == Some(main),
+            None => false,
+        }
+    }
+}
+
+// Ranges of an Accept header, most preferred first. Ranges of equal quality
+// keep the order the client gave them in
+pub fn parse_accept(header: &str) -> Vec<MediaRange> {
+    let mut ranges: Vec<MediaRange> = split_ranges(header)
+        .iter()
+        .filter_map(|range| MediaRange::parse(range))
+        .filter(|range| range.quality > 0.0)
+        .collect();
+    ranges.sort_by(|a, b| b.quality.total_cmp(&a.quality));
+    ranges
+}
+
+// Commas inside quoted parameter values do not separate ranges
+fn split_ranges(header: &str) -> Vec<String> {
+    let mut ranges = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for character in header.chars() {
+        match character {
+            '"' => {
+                quoted = !quoted;
+                current.push(character);
+            }
+            ',' if !quoted => ranges.push(std::mem::take(&mut current)),
+            _ => current.push(character),
+        }
+    }
+    ranges.push(current);
+    ranges
+}
+
+// What a WADO-RS instance request is answered with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Representation {
+    Dicom { transfer_syntax: String },
+    DicomJson,
+    // First frame rendered through the window of the dataset
+    Rendered { media_type: String },
+}
+
+impl Representation {
+    pub fn media_type(&self) -> &str {
+        match self {
+            Representation::Dicom { .. } => DICOM_MEDIA_TYPE,
+            Representation::DicomJson => DICOM_JSON_MEDIA_TYPE,
+            Representation::Rendered { media_type } => media_type,
+        }
+    }
+
+    pub fn content_type(&self) -> String {
+        match self {
+            Representation::Dicom { transfer_syntax } => {
+                format!("{}; transfer-syntax={}", DICOM_MEDIA_TYPE, transfer_syntax)
+            }
+            representation => representation.media_type().to_string(),
+        }
+    }
+}
+
+impl Display for Representation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.content_type())
+    }
+}
+
+// Picks the representation of an instance for an Accept header, PS3.18
+// 8.7.3. A missing transfer-syntax parameter asks for Explicit VR Little
+// Endian, * for the syntax the instance is stored in, and other syntaxes are
+// offered when the registry can transcode to them. No header or an empty one
+// accepts application/dicom
+pub fn negotiate(
+    accept: Option<&str>,
+    file: &DicomFile,
+    registry: &CodecRegistry,
+) -> DicomResult<Representation> {
+    let accept = accept.map(str::trim).filter(|accept| !accept.is_empty());
+    let ranges = parse_accept(accept.unwrap_or(DICOM_MEDIA_TYPE));
+    for range in &ranges {
+        if range.matches(DICOM_MEDIA_TYPE) {
+            let stored = file.transfer_syntax.trim_end_matches(['\0', ' ']);
+            let requested = match range.parameter("transfer-syntax") {
+                Some("*") => stored,
+                Some(requested) => requested,
+                None => EXPLICIT_VR_LITTLE_ENDIAN,
+            };
+            if can_transcode(file, requested, registry) {
+                return Ok(Representation::Dicom {
+                    transfer_syntax: requested.to_string(),
+                });
+            }
+        }
+        if range.matches(DICOM_JSON_MEDIA_TYPE) || range.part_type() == "application/json" {
+            return Ok(Representation::DicomJson);
+        }
+        if range.matches(JPEG_MEDIA_TYPE) && file.dataset.contains(PIXEL_DATA) {
+            return Ok(Representation::Rendered {
+                media_type: JPEG_MEDIA_TYPE.to_string(),
+            });
+        }
+    }
+    Err(DicomError::InvalidValue(format!(
+        "Not acceptable: {}",
+        accept.unwrap_or(DICOM_MEDIA_TYPE)
+    )))
+}
+
+// Whether the registry decodes the stored pixel data and encodes the target
+// syntax, files without pixel data going to any known syntax
+pub fn can_transcode(file: &DicomFile, target: &str, registry: &CodecRegistry) -> bool {
+    let source = file.transfer_syntax.trim_end_matches(['\0', ' ']);
+    if source == target {
+        return true;
+    }
+    if transfer_syntax::lookup(target).is_none() {
+        return false;
+    }
+    if !file.dataset.contains(PIXEL_DATA) {
+        return true;
+    }
+    (!transfer_syntax::is_encapsulated(source) || registry.decoder(source).is_some())
+        && (!transfer_syntax::is_encapsulated(target) || registry.encoder(target).is_some())
+}
+
+// Body of the response in the negotiated representation, transcoding or
+// rendering as it asks
+pub fn respond(
+    file: &DicomFile,
+    representation: &Representation,
+    registry: &CodecRegistry,
+) -> DicomResult<Vec<u8>> {
+    match representation {
+        Representation::Dicom { transfer_syntax } => {
+            let file = transcode::transcode(file, transfer_syntax, registry)?;
+            writer::write_file(&file.dataset, &file.transfer_syntax)
+        }
+        // Instances go out as a one element array, as metadata responses do
+        Representation::DicomJson => {
+            serde_json::to_vec(&Value::Array(vec![json::to_json(&file.dataset)]))
+                .map_err(|error| DicomError::Error(error.to_string()))
+        }
+        Representation::Rendered { media_type } if media_type == JPEG_MEDIA_TYPE => {
+            let frame = render::render_frame(file, 0)?;
+            thumbnail::encode_jpeg(&frame, 90)
+        }
+        Representation::Rendered { media_type } => Err(DicomError::InvalidValue(format!(
+            "Cannot render to {}",
+            media_type
+        ))),
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use reqwest::{header::RETRY_AFTER, Response};
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            ..RetryPolicy::default()
+        }
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.powi(attempt as i32);
+        let backoff = self.initial_backoff.as_secs_f64() * factor;
+        Duration::from_secs_f64(backoff.min(self.max_backoff.as_secs_f64()))
+    }
+
+    // 501 means the server will never support the request, so it is not worth repeating
+    pub fn should_retry(&self, status: u16) -> bool {
+        status == 429 || status == 408 || (status >= 500 && status != 501)
+    }
+
+    // Servers throttling us usually say for how long, which beats guessing
+    pub fn delay_for(&self, response: &Response, attempt: u32) -> Duration {
+        response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .map(|seconds| Duration::from_secs(seconds).min(self.max_backoff))
+            .unwrap_or_else(|| self.backoff(attempt))
+    }
+}
@@ -0,0 +1,179 @@
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{
+    core::error::{DicomError, DicomResult},
+    storage::{dedup::DuplicatePolicy, file_store::FileStore, InstanceStore},
+};
+
+pub const TENANT_HEADER: &str = "X-Tenant";
+
+// Where a request names its tenant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TenantSelector {
+    // First path segment, e.g. /project-a/studies/1.2.3
+    PathPrefix,
+    Header(String),
+}
+
+// One isolated set of studies with its own storage root and credentials
+#[derive(Clone)]
+pub struct Tenant {
+    pub id: String,
+    pub store: Arc<dyn InstanceStore>,
+    // Authorization header values accepted, none leaves the tenant open
+    credentials: Vec<String>,
+}
+
+impl std::fmt::Debug for Tenant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tenant")
+            .field("id", &self.id)
+            .field("credentials", &self.credentials.len())
+            .finish()
+    }
+}
+
+impl Tenant {
+    pub fn new<S: InstanceStore + 'static>(id: &str, store: S) -> Self {
+        Tenant {
+            id: id.to_string(),
+            store: Arc::new(store),
+            credentials: Vec::new(),
+        }
+    }
+
+    // Tenant keeping its instances in a FileStore in a folder of its own
+    // below root
+    pub fn open(id: &str, root: &Path, policy: DuplicatePolicy) -> DicomResult<Self> {
+        if !valid_id(id) {
+            return Err(DicomError::InvalidValue(format!("Tenant ID {}", id)));
+        }
+        Ok(Tenant::new(id, FileStore::open(&root.join(id), policy)?))
+    }
+
+    pub fn with_bearer_token(mut self, token: &str) -> Self {
+        self.credentials.push(format!("Bearer {}", token));
+        self
+    }
+
+    pub fn with_basic_auth(mut self, username: &str, password: &str) -> Self {
+        let encoded = STANDARD.encode(format!("{}:{}", username, password));
+        self.credentials.push(format!("Basic {}", encoded));
+        self
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.credentials.is_empty()
+    }
+
+    pub fn authorize(&self, authorization: Option<&str>) -> bool {
+        if self.is_open() {
+            return true;
+        }
+        let Some(authorization) = authorization.map(str::trim) else {
+            return false;
+        };
+        // Schemes are case insensitive, credentials are not
+        let normalized = |value: &str| match value.split_once(' ') {
+            Some((scheme, credentials)) => {
+                format!("{} {}", scheme.to_ascii_lowercase(), credentials.trim())
+            }
+            None => value.to_string(),
+        };
+        let authorization = normalized(authorization);
+        // Every credential is compared in full, so the time taken does not
+        // tell how much of a guess was right
+        self.credentials.iter().fold(false, |authorized, accepted| {
+            authorized | constant_time_eq(normalized(accepted).as_bytes(), authorization.as_bytes())
+        })
+    }
+}
+
+// Byte comparison whose time only depends on the lengths
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+// Tenant IDs end up in paths and URLs, so they are kept to letters, digits,
+// dashes and underscores
+pub fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+}
+
+// The tenants one process serves. A request is routed to its tenant and
+// only sees that tenant's store, with the tenant part of the path removed
+#[derive(Debug, Clone)]
+pub struct TenantRegistry {
+    pub selector: TenantSelector,
+    tenants: BTreeMap<String, Tenant>,
+}
+
+impl TenantRegistry {
+    pub fn new(selector: TenantSelector) -> Self {
+        TenantRegistry {
+            selector,
+            tenants: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_tenant(mut self, tenant: Tenant) -> Self {
+        self.insert(tenant);
+        self
+    }
+
+    pub fn insert(&mut self, tenant: Tenant) -> Option<Tenant> {
+        self.tenants.insert(tenant.id.clone(), tenant)
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<Tenant> {
+        self.tenants.remove(id)
+    }
+
+    pub fn tenant(&self, id: &str) -> Option<&Tenant> {
+        self.tenants.get(id)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.tenants.keys().map(String::as_str)
+    }
+
+    // Tenant of a request and the path within it. Unknown tenants answer
+    // 404 so their names are not given away, wrong credentials 401
+    pub fn resolve<'a>(
+        &self,
+        path: &'a str,
+        headers: &[(String, String)],
+    ) -> DicomResult<(&Tenant, &'a str)> {
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        };
+
+        let (id, path) = match &self.selector {
+            TenantSelector::PathPrefix => {
+                let trimmed = path.trim_start_matches('/');
+                match trimmed.split_once('/') {
+                    Some((id, _)) => (id, &trimmed[id.len()..]),
+                    None => (trimmed, "/"),
+                }
+            }
+            TenantSelector::Header(name) => (header(name).unwrap_or_default().trim(), path),
+        };
+        let tenant = self.tenant(id).ok_or(DicomError::HttpStatus(404))?;
+        if !tenant.authorize(header("Authorization")) {
+            return Err(DicomError::HttpStatus(401));
+        }
+        Ok((tenant, path))
+    }
+}